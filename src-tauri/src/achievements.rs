@@ -0,0 +1,328 @@
+use crate::event_log::GameEvent;
+use crate::game_state::GameState;
+use crate::npc::NPC;
+use serde::{Deserialize, Serialize};
+
+/// 成功突破时写入事件日志的事件类型
+pub const BREAKTHROUGH_EVENT: &str = "breakthrough";
+/// 章节完结时写入事件日志的事件类型
+pub const CHAPTER_COMPLETE_EVENT: &str = "chapter_complete";
+
+/// 成就的达成条件
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AchievementCondition {
+    Breakthroughs { count: u32 },
+    ChaptersCompleted { count: u32 },
+    /// 任一 NPC 对玩家的好感达到该值
+    NpcAffinityAtLeast { affinity: i32 },
+    /// 任一 NPC 对玩家的好感降到该值
+    NpcAffinityAtMost { affinity: i32 },
+    /// 存活至寿元上限的该百分比
+    SurviveAgePercent { percent: u32 },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AchievementDefinition {
+    pub id: String,
+    pub title: String,
+    pub description: String,
+    pub condition: AchievementCondition,
+}
+
+impl AchievementDefinition {
+    fn new(id: &str, title: &str, description: &str, condition: AchievementCondition) -> Self {
+        Self {
+            id: id.to_string(),
+            title: title.to_string(),
+            description: description.to_string(),
+            condition,
+        }
+    }
+
+    /// 内置成就，与剧本无关
+    pub fn builtin() -> Vec<Self> {
+        vec![
+            Self::new(
+                "first_breakthrough",
+                "初窥门径",
+                "第一次突破成功",
+                AchievementCondition::Breakthroughs { count: 1 },
+            ),
+            Self::new(
+                "five_breakthroughs",
+                "步步登高",
+                "一局之中突破成功五次",
+                AchievementCondition::Breakthroughs { count: 5 },
+            ),
+            Self::new(
+                "first_chapter",
+                "开篇",
+                "写完第一章",
+                AchievementCondition::ChaptersCompleted { count: 1 },
+            ),
+            Self::new(
+                "ten_chapters",
+                "十章成书",
+                "一局之中写完十章",
+                AchievementCondition::ChaptersCompleted { count: 10 },
+            ),
+            Self::new(
+                "kindred_spirit",
+                "知己",
+                "与一名 NPC 的好感达到 80",
+                AchievementCondition::NpcAffinityAtLeast { affinity: 80 },
+            ),
+            Self::new(
+                "sworn_enemy",
+                "宿怨",
+                "与一名 NPC 的好感跌至 -80",
+                AchievementCondition::NpcAffinityAtMost { affinity: -80 },
+            ),
+            Self::new(
+                "long_life",
+                "寿元将尽",
+                "存活至寿元上限的八成",
+                AchievementCondition::SurviveAgePercent { percent: 80 },
+            ),
+        ]
+    }
+}
+
+/// 判定成就所需的本局进度
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AchievementProgress {
+    pub breakthroughs: u32,
+    pub chapters_completed: u32,
+    pub best_npc_affinity: Option<i32>,
+    pub worst_npc_affinity: Option<i32>,
+    pub age_percent: u32,
+    pub alive: bool,
+}
+
+impl AchievementProgress {
+    /// 从事件日志统计突破与完结章节，从 NPC 关系与玩家寿元读取其余进度
+    pub fn observe<'a>(
+        events: &[GameEvent],
+        state: &GameState,
+        npcs: impl Iterator<Item = &'a NPC>,
+    ) -> Self {
+        let count = |event_type: &str| {
+            events
+                .iter()
+                .filter(|event| event.event_type.as_ref() == event_type)
+                .map(|event| event.repeat_count.max(1))
+                .sum::<u32>()
+        };
+        let affinities = npcs
+            .filter_map(|npc| npc.relationships.get(&state.player.id))
+            .map(|relationship| relationship.affinity)
+            .collect::<Vec<i32>>();
+        let lifespan = &state.player.stats.lifespan;
+        let age_percent = if lifespan.total_max_age() == 0 {
+            0
+        } else {
+            (u64::from(lifespan.current_age) * 100 / u64::from(lifespan.total_max_age())) as u32
+        };
+
+        Self {
+            breakthroughs: count(BREAKTHROUGH_EVENT),
+            chapters_completed: count(CHAPTER_COMPLETE_EVENT),
+            best_npc_affinity: affinities.iter().copied().max(),
+            worst_npc_affinity: affinities.iter().copied().min(),
+            age_percent,
+            alive: lifespan.is_alive(),
+        }
+    }
+
+    pub fn satisfies(&self, condition: &AchievementCondition) -> bool {
+        match *condition {
+            AchievementCondition::Breakthroughs { count } => self.breakthroughs >= count,
+            AchievementCondition::ChaptersCompleted { count } => self.chapters_completed >= count,
+            AchievementCondition::NpcAffinityAtLeast { affinity } => {
+                self.best_npc_affinity.is_some_and(|best| best >= affinity)
+            }
+            AchievementCondition::NpcAffinityAtMost { affinity } => {
+                self.worst_npc_affinity.is_some_and(|worst| worst <= affinity)
+            }
+            AchievementCondition::SurviveAgePercent { percent } => {
+                self.alive && self.age_percent >= percent
+            }
+        }
+    }
+}
+
+/// 已解锁的成就，记录首次解锁的对局
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UnlockedAchievement {
+    pub id: String,
+    pub title: String,
+    pub description: String,
+    pub script_id: String,
+    pub script_name: String,
+    /// 解锁时的 Unix 时间戳（秒）
+    pub unlocked_at: u64,
+}
+
+/// 跨局保存的成就簿
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AchievementBook {
+    pub unlocked: Vec<UnlockedAchievement>,
+}
+
+/// 成就列表中的一项，未解锁的成就也显示标题与条件
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AchievementStatus {
+    pub id: String,
+    pub title: String,
+    pub description: String,
+    pub unlocked: bool,
+    pub unlocked_at: Option<u64>,
+}
+
+impl AchievementBook {
+    pub fn is_unlocked(&self, id: &str) -> bool {
+        self.unlocked.iter().any(|entry| entry.id == id)
+    }
+
+    /// 解锁进度已满足且尚未解锁的成就，返回本次新解锁的成就
+    pub fn unlock_reached(
+        &mut self,
+        definitions: &[AchievementDefinition],
+        progress: &AchievementProgress,
+        state: &GameState,
+        now: u64,
+    ) -> Vec<UnlockedAchievement> {
+        let reached = definitions
+            .iter()
+            .filter(|definition| !self.is_unlocked(&definition.id))
+            .filter(|definition| progress.satisfies(&definition.condition))
+            .map(|definition| UnlockedAchievement {
+                id: definition.id.clone(),
+                title: definition.title.clone(),
+                description: definition.description.clone(),
+                script_id: state.script.id.clone(),
+                script_name: state.script.name.clone(),
+                unlocked_at: now,
+            })
+            .collect::<Vec<UnlockedAchievement>>();
+        self.unlocked.extend(reached.iter().cloned());
+        reached
+    }
+
+    /// 按定义顺序列出全部成就
+    pub fn statuses(&self, definitions: &[AchievementDefinition]) -> Vec<AchievementStatus> {
+        definitions
+            .iter()
+            .map(|definition| {
+                let entry = self.unlocked.iter().find(|entry| entry.id == definition.id);
+                AchievementStatus {
+                    id: definition.id.clone(),
+                    title: definition.title.clone(),
+                    description: definition.description.clone(),
+                    unlocked: entry.is_some(),
+                    unlocked_at: entry.map(|entry| entry.unlocked_at),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event_log::{EventImportance, EventLog};
+    use crate::game_engine::GameEngine;
+    use crate::models::CultivationRealm;
+    use crate::npc::{EmotionalState, NPCMemory, Personality, Relationship};
+    use std::collections::HashMap;
+    use crate::script::{Climate, Location, LocationKind};
+    use crate::script_manager::ScriptManager;
+
+    fn running_state() -> GameState {
+        let mut script = ScriptManager::new().blank_script();
+        script
+            .world_setting
+            .cultivation_realms
+            .push(CultivationRealm::new("练气".to_string(), 1, 0, 1.0));
+        script.world_setting.locations.push(Location {
+            id: "sect".to_string(),
+            name: "青云宗".to_string(),
+            description: String::new(),
+            spiritual_energy: 1.0,
+            kind: LocationKind::Wilderness,
+            climate: Climate::Temperate,
+        });
+        script.initial_state.starting_location = "sect".to_string();
+        let mut engine = GameEngine::new();
+        engine.initialize_game(script).unwrap()
+    }
+
+    #[test]
+    fn test_progress_counts_logged_milestones_and_relationships() {
+        let mut state = running_state();
+        let mut log = EventLog::new();
+        log.log_event(3, BREAKTHROUGH_EVENT, "突破至练气二层", EventImportance::Important);
+        log.log_event(5, CHAPTER_COMPLETE_EVENT, "「第1章」完结", EventImportance::Important);
+        log.log_event(6, "breakthrough_attempt", "突破失败", EventImportance::Important);
+
+        let npc = NPC {
+            id: "elder".to_string(),
+            name: "林长老".to_string(),
+            stats: state.player.stats.clone(),
+            personality: Personality {
+                traits: Vec::new(),
+                goals: Vec::new(),
+                values: Vec::new(),
+            },
+            memory: NPCMemory::default(),
+            relationships: HashMap::from([(
+                state.player.id.clone(),
+                Relationship {
+                    target_id: state.player.id.clone(),
+                    affinity: 85,
+                    trust: 40,
+                    history: Vec::new(),
+                },
+            )]),
+            secrets: Vec::new(),
+            location: None,
+            bio: String::new(),
+            emotions: EmotionalState::default(),
+        };
+        state.player.stats.lifespan.current_age = state.player.stats.lifespan.total_max_age() * 9 / 10;
+
+        let progress = AchievementProgress::observe(log.all_events(), &state, [&npc].into_iter());
+        assert_eq!(progress.breakthroughs, 1);
+        assert_eq!(progress.chapters_completed, 1);
+        assert_eq!(progress.best_npc_affinity, Some(85));
+        assert!(progress.satisfies(&AchievementCondition::NpcAffinityAtLeast { affinity: 80 }));
+        assert!(!progress.satisfies(&AchievementCondition::NpcAffinityAtMost { affinity: -80 }));
+        assert!(progress.satisfies(&AchievementCondition::SurviveAgePercent { percent: 80 }));
+    }
+
+    #[test]
+    fn test_book_unlocks_each_achievement_once() {
+        let state = running_state();
+        let progress = AchievementProgress {
+            breakthroughs: 1,
+            chapters_completed: 1,
+            ..AchievementProgress::default()
+        };
+        let definitions = AchievementDefinition::builtin();
+        let mut book = AchievementBook::default();
+
+        let unlocked = book.unlock_reached(&definitions, &progress, &state, 100);
+        assert_eq!(
+            unlocked.iter().map(|a| a.id.as_str()).collect::<Vec<&str>>(),
+            vec!["first_breakthrough", "first_chapter"]
+        );
+        assert!(book.unlock_reached(&definitions, &progress, &state, 200).is_empty());
+
+        let statuses = book.statuses(&definitions);
+        assert_eq!(statuses.len(), definitions.len());
+        assert_eq!(statuses[0].unlocked_at, Some(100));
+        assert!(!statuses[1].unlocked);
+    }
+}
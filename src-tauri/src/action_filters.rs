@@ -0,0 +1,188 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+
+const MAX_FILTER_KEYWORDS: usize = 200;
+const MAX_KEYWORD_CHARS: usize = 50;
+
+/// 内置的不合理行动关键词，未配置应用级过滤时使用
+pub const DEFAULT_BLOCKED_KEYWORDS: &[&str] = &[
+    "instant immortal",
+    "instantly become immortal",
+    "destroy the world",
+    "god mode",
+    "one punch kill everyone",
+    "一拳秒杀所有人",
+    "瞬间飞升",
+    "毁灭世界",
+    "无敌模式",
+];
+
+static RUNTIME_ACTION_FILTERS: OnceLock<Mutex<Option<ActionFilters>>> = OnceLock::new();
+
+fn filters_slot() -> &'static Mutex<Option<ActionFilters>> {
+    RUNTIME_ACTION_FILTERS.get_or_init(|| Mutex::new(None))
+}
+
+fn filters_file_path() -> PathBuf {
+    PathBuf::from(".nobody_action_filters.json")
+}
+
+/// 自由输入的关键词过滤：命中 `blocked` 的行动被拒绝，`allowed` 中的关键词解除同名屏蔽
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ActionFilters {
+    #[serde(default)]
+    pub blocked: Vec<String>,
+    #[serde(default)]
+    pub allowed: Vec<String>,
+}
+
+/// 过滤配置的作用范围
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ActionFilterScope {
+    App,
+    Script,
+}
+
+impl ActionFilters {
+    pub fn builtin() -> Self {
+        Self {
+            blocked: DEFAULT_BLOCKED_KEYWORDS.iter().map(|k| k.to_string()).collect(),
+            allowed: Vec::new(),
+        }
+    }
+
+    /// 校验并规范化关键词：去除首尾空白、转为小写并去重
+    pub fn normalized(&self) -> Result<Self, String> {
+        Ok(Self {
+            blocked: normalize_keywords(&self.blocked, "屏蔽")?,
+            allowed: normalize_keywords(&self.allowed, "放行")?,
+        })
+    }
+
+    /// 合并应用级与剧本级配置，剧本的放行词可以覆盖应用级屏蔽词
+    pub fn merged(app: &Self, script: &Self) -> Self {
+        let mut merged = app.clone();
+        for keyword in &script.blocked {
+            if !merged.blocked.contains(keyword) {
+                merged.blocked.push(keyword.clone());
+            }
+        }
+        for keyword in &script.allowed {
+            if !merged.allowed.contains(keyword) {
+                merged.allowed.push(keyword.clone());
+            }
+        }
+        merged
+    }
+
+    /// 返回输入命中且未被放行的第一个屏蔽词
+    pub fn blocked_keyword(&self, text: &str) -> Option<&str> {
+        let lower = text.to_lowercase();
+        self.blocked
+            .iter()
+            .filter(|keyword| !self.allowed.iter().any(|allowed| allowed.eq_ignore_ascii_case(keyword)))
+            .find(|keyword| lower.contains(&keyword.to_lowercase()))
+            .map(String::as_str)
+    }
+}
+
+pub(crate) fn normalize_keywords(keywords: &[String], label: &str) -> Result<Vec<String>, String> {
+    if keywords.len() > MAX_FILTER_KEYWORDS {
+        return Err(format!("{}关键词不能超过 {} 个", label, MAX_FILTER_KEYWORDS));
+    }
+    let mut seen = HashSet::new();
+    let mut normalized = Vec::new();
+    for keyword in keywords {
+        let keyword = keyword.trim().to_lowercase();
+        if keyword.is_empty() {
+            return Err(format!("{}关键词不能为空", label));
+        }
+        if keyword.chars().count() > MAX_KEYWORD_CHARS {
+            return Err(format!("{}关键词“{}”过长", label, keyword));
+        }
+        if keyword.chars().any(char::is_control) {
+            return Err(format!("{}关键词包含非法控制字符", label));
+        }
+        if seen.insert(keyword.clone()) {
+            normalized.push(keyword);
+        }
+    }
+    Ok(normalized)
+}
+
+/// 应用级过滤配置：运行时设置优先，其次读取配置文件，最后使用内置关键词
+pub fn app_action_filters() -> ActionFilters {
+    if let Some(filters) = filters_slot().lock().unwrap().clone() {
+        return filters;
+    }
+    load_filters_from_file().unwrap_or_else(ActionFilters::builtin)
+}
+
+/// 校验并保存应用级过滤配置，返回规范化后的配置
+pub fn set_app_action_filters(filters: &ActionFilters) -> Result<ActionFilters, String> {
+    let filters = filters.normalized()?;
+    *filters_slot().lock().unwrap() = Some(filters.clone());
+    persist_filters_to_disk(&filters)?;
+    Ok(filters)
+}
+
+fn load_filters_from_file() -> Option<ActionFilters> {
+    let content = fs::read_to_string(filters_file_path()).ok()?;
+    serde_json::from_str::<ActionFilters>(&content).ok()
+}
+
+fn persist_filters_to_disk(filters: &ActionFilters) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(filters).map_err(|e| e.to_string())?;
+    fs::write(filters_file_path(), content).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_filters_block_godlike_actions() {
+        let filters = ActionFilters::builtin();
+        assert_eq!(filters.blocked_keyword("我要瞬间飞升"), Some("瞬间飞升"));
+        assert_eq!(filters.blocked_keyword("Enable GOD MODE now"), Some("god mode"));
+        assert_eq!(filters.blocked_keyword("打坐修炼"), None);
+    }
+
+    #[test]
+    fn test_script_allowlist_overrides_app_blocklist() {
+        let script = ActionFilters {
+            blocked: vec!["夺舍".to_string()],
+            allowed: vec!["瞬间飞升".to_string()],
+        };
+        let merged = ActionFilters::merged(&ActionFilters::builtin(), &script);
+
+        assert_eq!(merged.blocked_keyword("我要瞬间飞升"), None);
+        assert_eq!(merged.blocked_keyword("毁灭世界"), Some("毁灭世界"));
+        assert_eq!(merged.blocked_keyword("夺舍长老"), Some("夺舍"));
+    }
+
+    #[test]
+    fn test_normalized_trims_dedupes_and_rejects_invalid() {
+        let filters = ActionFilters {
+            blocked: vec![" God Mode ".to_string(), "god mode".to_string()],
+            allowed: Vec::new(),
+        };
+        assert_eq!(filters.normalized().unwrap().blocked, vec!["god mode".to_string()]);
+
+        let empty = ActionFilters {
+            blocked: vec!["  ".to_string()],
+            allowed: Vec::new(),
+        };
+        assert!(empty.normalized().is_err());
+
+        let too_long = ActionFilters {
+            blocked: Vec::new(),
+            allowed: vec!["长".repeat(MAX_KEYWORD_CHARS + 1)],
+        };
+        assert!(too_long.normalized().is_err());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::formula::FormulaError;
 use crate::llm_service::LLMServiceError;
 use serde::{Deserialize, Serialize};
 use std::fmt;
@@ -66,6 +67,12 @@ impl From<LLMServiceError> for AppError {
     }
 }
 
+impl From<FormulaError> for AppError {
+    fn from(err: FormulaError) -> Self {
+        AppError::new(AppErrorKind::InvalidInput, err.to_string())
+    }
+}
+
 impl From<serde_json::Error> for AppError {
     fn from(err: serde_json::Error) -> Self {
         AppError::new(AppErrorKind::Parse, err.to_string())
@@ -0,0 +1,440 @@
+use crate::game_state::GameState;
+use crate::llm_service::{LLMRequest, LLMService, LLMSubsystem};
+use crate::plot_engine::PlotState;
+use crate::prompt_builder::{PromptBuilder, PromptConstraints, PromptContext, PromptTemplate};
+use crate::temperature_tuner::repetition_score;
+use crate::token_budget::{TokenBudget, TurnCall};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Duration;
+use std::sync::Arc;
+
+/// 每个故事弧覆盖的章节数，超过后重新规划
+pub const ARC_CHAPTER_SPAN: u32 = 3;
+const MAX_OFF_BEAT_SEGMENTS: u32 = 4;
+const BEAT_MATCH_THRESHOLD: f32 = 0.3;
+const MIN_BEATS: usize = 3;
+const MAX_BEATS: usize = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum ArcSource {
+    Template,
+    Llm,
+}
+
+/// 故事弧中的一个关键节拍
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ArcBeat {
+    pub title: String,
+    pub summary: String,
+    #[serde(default)]
+    pub reached: bool,
+}
+
+/// 接下来几章的剧情大纲：关键节拍、反派与预定高潮
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct StoryArc {
+    pub index: u32,
+    pub title: String,
+    pub beats: Vec<ArcBeat>,
+    pub antagonists: Vec<String>,
+    pub climax: String,
+    pub start_chapter: u32,
+    pub current_beat: usize,
+    /// 连续未贴合当前节拍的段落数
+    #[serde(default)]
+    pub off_beat_segments: u32,
+    pub source: ArcSource,
+}
+
+impl StoryArc {
+    pub fn current_beat(&self) -> Option<&ArcBeat> {
+        self.beats.get(self.current_beat)
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.current_beat >= self.beats.len()
+    }
+
+    pub fn is_off_course(&self) -> bool {
+        self.off_beat_segments >= MAX_OFF_BEAT_SEGMENTS
+    }
+
+    /// 根据新段落推进节拍，段落写到当前节拍时返回 true
+    pub fn observe_segment(&mut self, text: &str) -> bool {
+        let Some(beat) = self.beats.get_mut(self.current_beat) else {
+            return false;
+        };
+        let beat_text = format!("{}{}", beat.title, beat.summary);
+        if repetition_score(&beat_text, &[text.to_string()]) >= BEAT_MATCH_THRESHOLD {
+            beat.reached = true;
+            self.current_beat += 1;
+            self.off_beat_segments = 0;
+            true
+        } else {
+            self.off_beat_segments = self.off_beat_segments.saturating_add(1);
+            false
+        }
+    }
+
+    /// 供段落提示词引用的当前节拍说明
+    pub fn prompt_line(&self) -> Option<String> {
+        let beat = self.current_beat()?;
+        Some(format!(
+            "《{}》节拍 {}/{}：{}——{}；本弧高潮：{}",
+            self.title,
+            self.current_beat + 1,
+            self.beats.len(),
+            beat.title,
+            beat.summary,
+            self.climax
+        ))
+    }
+}
+
+/// 需要重新规划故事弧的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplanReason {
+    NoArc,
+    ChapterSpan,
+    Completed,
+    Deviated,
+}
+
+impl ReplanReason {
+    pub fn label(self) -> &'static str {
+        match self {
+            ReplanReason::NoArc => "开局规划",
+            ReplanReason::ChapterSpan => "章节跨度已满",
+            ReplanReason::Completed => "节拍已全部完成",
+            ReplanReason::Deviated => "剧情偏离大纲",
+        }
+    }
+}
+
+pub fn replan_reason(plot_state: &PlotState) -> Option<ReplanReason> {
+    let Some(arc) = &plot_state.story_arc else {
+        return Some(ReplanReason::NoArc);
+    };
+    if arc.is_complete() {
+        Some(ReplanReason::Completed)
+    } else if plot_state.current_chapter.index >= arc.start_chapter.saturating_add(ARC_CHAPTER_SPAN) {
+        Some(ReplanReason::ChapterSpan)
+    } else if arc.is_off_course() {
+        Some(ReplanReason::Deviated)
+    } else {
+        None
+    }
+}
+
+/// 在章节生成之前规划故事弧大纲
+pub struct ArcPlanner {
+    prompt_builder: PromptBuilder,
+    llm_service: Option<Arc<LLMService>>,
+    token_budget: Option<TokenBudget>,
+}
+
+impl ArcPlanner {
+    pub fn new() -> Self {
+        Self {
+            prompt_builder: PromptBuilder::default(),
+            llm_service: None,
+            token_budget: None,
+        }
+    }
+
+    pub fn with_llm_service(mut self, llm_service: Arc<LLMService>) -> Self {
+        self.llm_service = Some(llm_service);
+        self
+    }
+
+    /// 回合预算不足时跳过 LLM 规划，直接使用模板大纲
+    pub fn with_token_budget(mut self, token_budget: TokenBudget) -> Self {
+        self.token_budget = Some(token_budget);
+        self
+    }
+
+    /// 规划下一段故事弧，LLM 不可用或输出无法解析时使用模板大纲
+    pub async fn plan(
+        &self,
+        plot_state: &PlotState,
+        game_state: &GameState,
+        reason: ReplanReason,
+    ) -> StoryArc {
+        let index = plot_state.story_arc.as_ref().map(|arc| arc.index + 1).unwrap_or(1);
+        let start_chapter = plot_state.current_chapter.index;
+
+        if !cfg!(test) {
+            if let Some(llm_service) = &self.llm_service {
+                if let Some(arc) = self
+                    .plan_with_llm(llm_service, plot_state, game_state, reason, index)
+                    .await
+                {
+                    return arc;
+                }
+            }
+        }
+        template_arc(game_state, index, start_chapter)
+    }
+
+    async fn plan_with_llm(
+        &self,
+        llm_service: &LLMService,
+        plot_state: &PlotState,
+        game_state: &GameState,
+        reason: ReplanReason,
+        index: u32,
+    ) -> Option<StoryArc> {
+        let mut history_events = plot_state
+            .chapters
+            .iter()
+            .rev()
+            .take(3)
+            .filter(|chapter| !chapter.summary.is_empty())
+            .map(|chapter| format!("第{}章 {}：{}", chapter.index, chapter.title, chapter.summary))
+            .collect::<Vec<String>>();
+        history_events.reverse();
+        if let Some(latest) = plot_state.current_chapter.content.last() {
+            history_events.push(latest.chars().take(300).collect());
+        }
+
+        let prompt = self.prompt_builder.build_prompt_with_token_limit(
+            PromptTemplate::PlotGeneration,
+            &PromptContext {
+                scene: Some(format!(
+                    "为接下来约 {} 章规划第 {} 个故事弧（{}），当前为第 {} 章",
+                    ARC_CHAPTER_SPAN,
+                    index,
+                    reason.label(),
+                    plot_state.current_chapter.index
+                )),
+                location: Some(game_state.player.location.clone()),
+                actor_name: Some(game_state.player.name.clone()),
+                actor_realm: Some(game_state.player.stats.cultivation_realm.name.clone()),
+                actor_combat_power: None,
+                player_persona: plot_state.player_persona.summary(),
+                canon_facts: plot_state.canon_facts.prompt_lines(&history_events.join(" "), 6),
+                story_beat: None,
+                chapter_beat: None,
+                environment: None,
+                active_quests: Vec::new(),
+                relationships: Vec::new(),
+                status_effects: Vec::new(),
+                history_events,
+                world_setting_summary: Some(faction_summary(game_state)),
+            },
+            &PromptConstraints {
+                numerical_rules: Vec::new(),
+                world_rules: vec![
+                    "输出严格 JSON".to_string(),
+                    format!("beats 给出 {}-{} 个按时间顺序排列的关键节拍", MIN_BEATS, MAX_BEATS),
+                    "承接已发生的剧情，不得与既定事实矛盾".to_string(),
+                ],
+                output_schema_hint: Some(
+                    "{\"title\":\"string\",\"beats\":[{\"title\":\"string\",\"summary\":\"string\"}],\"antagonists\":[\"string\"],\"climax\":\"string\"}".to_string(),
+                ),
+            },
+            1200,
+        );
+        let max_tokens = match &self.token_budget {
+            Some(budget) => budget.allocate(TurnCall::ArcPlanning, &prompt, 600)?,
+            None => 600,
+        };
+
+        let response = tokio::time::timeout(
+            Duration::from_secs(45),
+            llm_service.generate(LLMRequest {
+                prompt,
+                max_tokens: Some(max_tokens),
+                temperature: Some(0.8),
+                subsystem: LLMSubsystem::Plot,
+                retry_policy: None,
+            }),
+        )
+        .await
+        .ok()?
+        .ok()?;
+
+        parse_arc(&response.text, index, plot_state.current_chapter.index)
+    }
+}
+
+impl Default for ArcPlanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn parse_arc(raw: &str, index: u32, start_chapter: u32) -> Option<StoryArc> {
+    let start = raw.find('{')?;
+    let end = raw.rfind('}')?;
+    let value: Value = serde_json::from_str(raw.get(start..=end)?).ok()?;
+    let text = |value: &Value, key: &str| {
+        value
+            .get(key)
+            .and_then(Value::as_str)
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+    };
+
+    let beats = value
+        .get("beats")?
+        .as_array()?
+        .iter()
+        .filter_map(|beat| {
+            Some(ArcBeat {
+                title: text(beat, "title")?,
+                summary: text(beat, "summary").unwrap_or_default(),
+                reached: false,
+            })
+        })
+        .take(MAX_BEATS)
+        .collect::<Vec<ArcBeat>>();
+    if beats.len() < MIN_BEATS {
+        return None;
+    }
+
+    Some(StoryArc {
+        index,
+        title: text(&value, "title")?,
+        beats,
+        antagonists: value
+            .get("antagonists")
+            .and_then(Value::as_array)
+            .map(|arr| {
+                arr.iter()
+                    .filter_map(|v| v.as_str().map(|s| s.trim().to_string()))
+                    .filter(|s| !s.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default(),
+        climax: text(&value, "climax")?,
+        start_chapter,
+        current_beat: 0,
+        off_beat_segments: 0,
+        source: ArcSource::Llm,
+    })
+}
+
+fn template_arc(game_state: &GameState, index: u32, start_chapter: u32) -> StoryArc {
+    let player = &game_state.player.name;
+    let location = game_state
+        .script
+        .world_setting
+        .locations
+        .iter()
+        .find(|l| l.id == game_state.player.location)
+        .map(|l| l.name.clone())
+        .unwrap_or_else(|| game_state.player.location.clone());
+    let factions = &game_state.script.world_setting.factions;
+    let antagonist = factions
+        .get(index as usize % factions.len().max(1))
+        .filter(|_| factions.len() > 1)
+        .map(|f| f.name.clone())
+        .unwrap_or_else(|| "幕后黑手".to_string());
+
+    let beat = |title: &str, summary: String| ArcBeat {
+        title: title.to_string(),
+        summary,
+        reached: false,
+    };
+    StoryArc {
+        index,
+        title: format!("{}风云", location),
+        beats: vec![
+            beat("暗流初现", format!("{}在{}察觉到{}的异动", player, location, antagonist)),
+            beat("历练求索", format!("{}外出历练，寻找破局的机缘与助力", player)),
+            beat("步步紧逼", format!("{}的图谋浮出水面，{}身陷险境", antagonist, player)),
+            beat("正面交锋", format!("{}与{}正面对决，了结恩怨", player, antagonist)),
+        ],
+        antagonists: vec![antagonist.clone()],
+        climax: format!("{}与{}的决战", player, antagonist),
+        start_chapter,
+        current_beat: 0,
+        off_beat_segments: 0,
+        source: ArcSource::Template,
+    }
+}
+
+fn faction_summary(game_state: &GameState) -> String {
+    let factions = game_state
+        .script
+        .world_setting
+        .factions
+        .iter()
+        .map(|f| f.name.as_str())
+        .collect::<Vec<&str>>();
+    if factions.is_empty() {
+        "修仙世界".to_string()
+    } else {
+        format!("修仙世界，主要势力：{}", factions.join("、"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plot_engine::Scene;
+
+    fn arc() -> StoryArc {
+        parse_arc(
+            r#"说明：{"title":"血色试炼","beats":[
+                {"title":"试炼开启","summary":"宗门大比开启，林逸报名参加"},
+                {"title":"秘境遇袭","summary":"林逸在秘境中遭遇黑风寨伏击"},
+                {"title":"真相大白","summary":"林逸揭开长老勾结黑风寨的阴谋"}
+            ],"antagonists":["黑风寨"],"climax":"大比决赛上揭穿长老"}"#,
+            1,
+            1,
+        )
+        .unwrap()
+    }
+
+    fn plot_state() -> PlotState {
+        PlotState::new(Scene::new(
+            "start".to_string(),
+            "第一章".to_string(),
+            "开篇".to_string(),
+            "青云宗".to_string(),
+        ))
+    }
+
+    #[test]
+    fn test_parse_arc_reads_outline() {
+        let arc = arc();
+        assert_eq!(arc.title, "血色试炼");
+        assert_eq!(arc.beats.len(), 3);
+        assert_eq!(arc.antagonists, vec!["黑风寨".to_string()]);
+        assert_eq!(arc.source, ArcSource::Llm);
+        assert!(arc.prompt_line().unwrap().contains("节拍 1/3：试炼开启"));
+        assert!(parse_arc(r#"{"title":"短","beats":[{"title":"a"}],"climax":"c"}"#, 1, 1).is_none());
+    }
+
+    #[test]
+    fn test_observe_segment_advances_beats_and_detects_deviation() {
+        let mut arc = arc();
+        assert!(arc.observe_segment("晨钟响起，宗门大比开启，林逸上前报名参加，众人侧目。"));
+        assert_eq!(arc.current_beat().unwrap().title, "秘境遇袭");
+
+        for _ in 0..MAX_OFF_BEAT_SEGMENTS {
+            assert!(!arc.observe_segment("他回到洞府，煮茶读书，一夜无话。"));
+        }
+        assert!(arc.is_off_course());
+    }
+
+    #[test]
+    fn test_replan_reason_follows_arc_lifecycle() {
+        let mut state = plot_state();
+        assert_eq!(replan_reason(&state), Some(ReplanReason::NoArc));
+
+        state.story_arc = Some(arc());
+        assert_eq!(replan_reason(&state), None);
+
+        state.current_chapter.index = 1 + ARC_CHAPTER_SPAN;
+        assert_eq!(replan_reason(&state), Some(ReplanReason::ChapterSpan));
+
+        state.current_chapter.index = 1;
+        state.story_arc.as_mut().unwrap().current_beat = 3;
+        assert_eq!(replan_reason(&state), Some(ReplanReason::Completed));
+    }
+}
@@ -0,0 +1,185 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use tokio::sync::Notify;
+
+/// 生成被取消时返回给前端的错误信息
+pub const GENERATION_CANCELLED: &str = "生成已取消";
+
+static IN_FLIGHT: OnceLock<Mutex<HashMap<String, CancellationToken>>> = OnceLock::new();
+
+tokio::task_local! {
+    static CURRENT_TOKEN: CancellationToken;
+}
+
+/// 可取消的生成开始时推送给前端的信息，前端凭 request_id 调用 `cancel_generation`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GenerationStarted {
+    pub request_id: String,
+    pub command: String,
+}
+
+/// 取消信号，克隆后共享同一状态
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<TokenState>,
+}
+
+#[derive(Debug, Default)]
+struct TokenState {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        self.inner.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// 等到取消为止，已取消时立即返回
+    pub async fn cancelled(&self) {
+        loop {
+            let notified = self.inner.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// 在本令牌的作用域内执行，期间 `LLMService` 的调用都会响应取消
+    pub async fn scope<F: Future>(&self, future: F) -> F::Output {
+        CURRENT_TOKEN.scope(self.clone(), future).await
+    }
+}
+
+/// 当前任务所在作用域的取消令牌
+pub fn current_token() -> Option<CancellationToken> {
+    CURRENT_TOKEN.try_with(CancellationToken::clone).ok()
+}
+
+fn in_flight() -> MutexGuard<'static, HashMap<String, CancellationToken>> {
+    let registry = IN_FLIGHT.get_or_init(|| Mutex::new(HashMap::new()));
+    match registry.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+/// 一次可取消的生成，结束（或被丢弃）时自动从登记表中移除
+#[derive(Debug)]
+pub struct Generation {
+    pub request_id: String,
+    token: CancellationToken,
+}
+
+impl Generation {
+    /// 登记一次生成；前端未给出 request_id 时生成新的。
+    /// 同一 request_id 仍在进行的旧生成会先被取消，由新的一次接替，免得旧生成再也无法取消
+    pub fn begin(request_id: Option<String>) -> Self {
+        let request_id = request_id
+            .map(|id| id.trim().to_string())
+            .filter(|id| !id.is_empty())
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let token = CancellationToken::new();
+        if let Some(superseded) = in_flight().insert(request_id.clone(), token.clone()) {
+            superseded.cancel();
+        }
+        Self { request_id, token }
+    }
+
+    /// 执行生成；被取消时丢弃进行中的 future（包括其中的 HTTP 请求）并返回取消错误
+    pub async fn run<T>(self, future: impl Future<Output = Result<T, String>>) -> Result<T, String> {
+        let token = self.token.clone();
+        token
+            .scope(async {
+                tokio::select! {
+                    biased;
+                    _ = token.cancelled() => Err(GENERATION_CANCELLED.to_string()),
+                    result = future => result,
+                }
+            })
+            .await
+    }
+}
+
+impl Drop for Generation {
+    /// 重试可能复用同一 request_id，登记表里已换成后来者的令牌时不能把它移除
+    fn drop(&mut self) {
+        let mut registry = in_flight();
+        if registry
+            .get(&self.request_id)
+            .is_some_and(|token| Arc::ptr_eq(&token.inner, &self.token.inner))
+        {
+            registry.remove(&self.request_id);
+        }
+    }
+}
+
+/// 取消进行中的生成，找不到（已结束或不存在）时返回 false
+pub fn cancel_generation(request_id: &str) -> bool {
+    match in_flight().get(request_id.trim()) {
+        Some(token) => {
+            token.cancel();
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_cancel_drops_in_flight_future() {
+        let generation = Generation::begin(Some("turn-1".to_string()));
+        let canceller = tokio::spawn(async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            cancel_generation("turn-1")
+        });
+        let result = generation
+            .run(async {
+                assert!(current_token().is_some());
+                tokio::time::sleep(Duration::from_secs(30)).await;
+                Ok("done")
+            })
+            .await;
+        assert!(canceller.await.unwrap());
+        assert_eq!(result, Err(GENERATION_CANCELLED.to_string()));
+        assert!(!cancel_generation("turn-1"));
+
+        let generation = Generation::begin(None);
+        assert!(!generation.request_id.is_empty());
+        assert_eq!(generation.run(async { Ok(1) }).await, Ok(1));
+        assert!(current_token().is_none());
+    }
+
+    #[test]
+    fn test_retry_with_same_id_stays_cancellable_after_earlier_run_ends() {
+        let earlier = Generation::begin(Some("turn-retry".to_string()));
+        let retry = Generation::begin(Some("turn-retry".to_string()));
+        assert!(earlier.token.is_cancelled());
+        assert!(!retry.token.is_cancelled());
+        drop(earlier);
+
+        assert!(cancel_generation("turn-retry"));
+        assert!(retry.token.is_cancelled());
+        drop(retry);
+        assert!(!cancel_generation("turn-retry"));
+    }
+}
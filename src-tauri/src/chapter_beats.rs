@@ -0,0 +1,78 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// 三幕式章节结构中的节拍，按 引入 → 冲突 → 转折 → 收束 的顺序推进
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum ChapterBeat {
+    Introduction,
+    Conflict,
+    Turn,
+    Resolution,
+}
+
+impl ChapterBeat {
+    pub const SEQUENCE: [ChapterBeat; 4] = [
+        ChapterBeat::Introduction,
+        ChapterBeat::Conflict,
+        ChapterBeat::Turn,
+        ChapterBeat::Resolution,
+    ];
+
+    /// 已完成 `completed` 个节拍时，下一段应写的节拍
+    pub fn at(completed: u8) -> Option<Self> {
+        Self::SEQUENCE.get(usize::from(completed)).copied()
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ChapterBeat::Introduction => "引入",
+            ChapterBeat::Conflict => "冲突",
+            ChapterBeat::Turn => "转折",
+            ChapterBeat::Resolution => "收束",
+        }
+    }
+
+    pub fn guidance(self) -> &'static str {
+        match self {
+            ChapterBeat::Introduction => "交代场景与人物处境，埋下本章的核心悬念",
+            ChapterBeat::Conflict => "让矛盾正面爆发，主角必须承受压力或付出代价",
+            ChapterBeat::Turn => "出现意料之外的变化，改变局势或主角的认知",
+            ChapterBeat::Resolution => "收拢本章矛盾，给出阶段性结果并留下后续钩子",
+        }
+    }
+
+    /// 供段落提示词引用的节拍说明
+    pub fn prompt_line(self) -> String {
+        let position = Self::SEQUENCE.iter().position(|beat| *beat == self).unwrap_or(0);
+        format!(
+            "本章结构节拍 {}/{}：{}——{}",
+            position + 1,
+            Self::SEQUENCE.len(),
+            self.label(),
+            self.guidance()
+        )
+    }
+}
+
+/// 本章节拍是否已全部完成
+pub fn beats_satisfied(completed: u8) -> bool {
+    usize::from(completed) >= ChapterBeat::SEQUENCE.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_beats_advance_in_order() {
+        assert_eq!(ChapterBeat::at(0), Some(ChapterBeat::Introduction));
+        assert_eq!(ChapterBeat::at(3), Some(ChapterBeat::Resolution));
+        assert_eq!(ChapterBeat::at(4), None);
+        assert!(!beats_satisfied(3));
+        assert!(beats_satisfied(4));
+        assert_eq!(
+            ChapterBeat::Turn.prompt_line(),
+            "本章结构节拍 3/4：转折——出现意料之外的变化，改变局势或主角的认知"
+        );
+    }
+}
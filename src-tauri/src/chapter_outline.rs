@@ -0,0 +1,294 @@
+use crate::arc_planner::{ArcSource, StoryArc};
+use crate::chapter_beats::ChapterBeat;
+use crate::game_state::GameState;
+use crate::llm_service::{LLMRequest, LLMService, LLMSubsystem};
+use crate::plot_engine::PlotState;
+use crate::prompt_builder::{PromptBuilder, PromptConstraints, PromptContext, PromptTemplate};
+use crate::token_budget::{TokenBudget, TurnCall};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+
+const MIN_OUTLINE_BEATS: usize = 3;
+const MAX_OUTLINE_BEATS: usize = 6;
+
+/// 章节大纲中的一个节拍，每段续写推进一个
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct OutlineBeat {
+    pub summary: String,
+    /// 写完该节拍后停下等待玩家抉择
+    #[serde(default)]
+    pub interaction: bool,
+}
+
+/// 本章开写前规划的大纲：节拍、互动点与预定结局
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ChapterOutline {
+    pub beats: Vec<OutlineBeat>,
+    pub planned_ending: String,
+    #[serde(default)]
+    pub beats_completed: usize,
+    pub source: ArcSource,
+}
+
+impl ChapterOutline {
+    /// 下一段应写的节拍
+    pub fn current_beat(&self) -> Option<&OutlineBeat> {
+        self.beats.get(self.beats_completed)
+    }
+
+    /// 下一段写完后大纲即全部完成
+    pub fn ends_with_next_segment(&self) -> bool {
+        self.beats_completed + 1 >= self.beats.len()
+    }
+
+    pub fn advance(&mut self) {
+        self.beats_completed = (self.beats_completed + 1).min(self.beats.len());
+    }
+
+    pub fn interaction_points(&self) -> usize {
+        self.beats.iter().filter(|beat| beat.interaction).count()
+    }
+
+    /// 供段落提示词引用的大纲节拍说明
+    pub fn prompt_line(&self) -> Option<String> {
+        let beat = self.current_beat()?;
+        Some(format!(
+            "本章大纲节拍 {}/{}：{}{}；本章预定结局：{}",
+            self.beats_completed + 1,
+            self.beats.len(),
+            beat.summary,
+            if beat.interaction { "（写到此处停下，等待玩家抉择）" } else { "" },
+            self.planned_ending
+        ))
+    }
+}
+
+/// 两段式续写的第一步：在本章第一段之前规划章节大纲
+pub struct ChapterOutliner {
+    prompt_builder: PromptBuilder,
+    llm_service: Option<Arc<LLMService>>,
+    token_budget: Option<TokenBudget>,
+}
+
+impl ChapterOutliner {
+    pub fn new() -> Self {
+        Self {
+            prompt_builder: PromptBuilder::default(),
+            llm_service: None,
+            token_budget: None,
+        }
+    }
+
+    pub fn with_llm_service(mut self, llm_service: Arc<LLMService>) -> Self {
+        self.llm_service = Some(llm_service);
+        self
+    }
+
+    /// 回合预算不足时跳过 LLM 规划，直接使用模板大纲
+    pub fn with_token_budget(mut self, token_budget: TokenBudget) -> Self {
+        self.token_budget = Some(token_budget);
+        self
+    }
+
+    /// 规划当前章节的大纲，LLM 不可用或输出无法解析时使用模板大纲
+    pub async fn plan(&self, plot_state: &PlotState, game_state: &GameState) -> ChapterOutline {
+        if !cfg!(test) {
+            if let Some(llm_service) = &self.llm_service {
+                if let Some(outline) = self.plan_with_llm(llm_service, plot_state, game_state).await {
+                    return outline;
+                }
+            }
+        }
+        template_outline(plot_state)
+    }
+
+    async fn plan_with_llm(
+        &self,
+        llm_service: &LLMService,
+        plot_state: &PlotState,
+        game_state: &GameState,
+    ) -> Option<ChapterOutline> {
+        let settings = &plot_state.settings;
+        let chapter = &plot_state.current_chapter;
+        let mut history_events = plot_state
+            .chapters
+            .last()
+            .filter(|previous| !previous.summary.is_empty())
+            .map(|previous| format!("第{}章 {}：{}", previous.index, previous.title, previous.summary))
+            .into_iter()
+            .collect::<Vec<String>>();
+        if let Some(latest) = chapter.content.last() {
+            history_events.push(latest.chars().take(300).collect());
+        }
+
+        let prompt = self.prompt_builder.build_prompt_with_token_limit(
+            PromptTemplate::PlotGeneration,
+            &PromptContext {
+                scene: Some(format!(
+                    "开写第 {} 章《{}》之前，先规划本章大纲",
+                    chapter.index, chapter.title
+                )),
+                location: Some(game_state.player.location.clone()),
+                actor_name: Some(game_state.player.name.clone()),
+                actor_realm: Some(game_state.player.stats.cultivation_realm.name.clone()),
+                actor_combat_power: None,
+                player_persona: plot_state.player_persona.summary(),
+                canon_facts: plot_state.canon_facts.prompt_lines(&history_events.join(" "), 6),
+                story_beat: plot_state.story_arc.as_ref().and_then(StoryArc::prompt_line),
+                chapter_beat: None,
+                environment: None,
+                active_quests: game_state.quests.prompt_lines(),
+                relationships: Vec::new(),
+                status_effects: game_state.player.stats.status_effects.prompt_lines(),
+                history_events,
+                world_setting_summary: None,
+            },
+            &PromptConstraints {
+                numerical_rules: Vec::new(),
+                world_rules: vec![
+                    "输出严格 JSON".to_string(),
+                    format!(
+                        "beats 给出 {}-{} 个按时间顺序排列的节拍，每个节拍对应一段约 {} 字的正文",
+                        MIN_OUTLINE_BEATS,
+                        MAX_OUTLINE_BEATS,
+                        settings.target_chapter_words_max / MAX_OUTLINE_BEATS as u32
+                    ),
+                    format!(
+                        "其中 {}-{} 个节拍的 interaction 为 true，表示写到此处停下让玩家抉择",
+                        settings.min_interactions_per_chapter, settings.max_interactions_per_chapter
+                    ),
+                    "最后一个节拍写到 planned_ending 为止，承接已发生的剧情".to_string(),
+                ],
+                output_schema_hint: Some(
+                    "{\"beats\":[{\"summary\":\"string\",\"interaction\":true|false}],\"planned_ending\":\"string\"}".to_string(),
+                ),
+            },
+            900,
+        );
+        let max_tokens = match &self.token_budget {
+            Some(budget) => budget.allocate(TurnCall::ChapterOutline, &prompt, 400)?,
+            None => 400,
+        };
+
+        let response = tokio::time::timeout(
+            Duration::from_secs(30),
+            llm_service.generate(LLMRequest {
+                prompt,
+                max_tokens: Some(max_tokens),
+                temperature: Some(0.7),
+                subsystem: LLMSubsystem::Plot,
+                retry_policy: None,
+            }),
+        )
+        .await
+        .ok()?
+        .ok()?;
+
+        parse_outline(&response.text)
+    }
+}
+
+impl Default for ChapterOutliner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn parse_outline(raw: &str) -> Option<ChapterOutline> {
+    let start = raw.find('{')?;
+    let end = raw.rfind('}')?;
+    let value: Value = serde_json::from_str(raw.get(start..=end)?).ok()?;
+
+    let beats = value
+        .get("beats")?
+        .as_array()?
+        .iter()
+        .filter_map(|beat| {
+            let summary = beat.get("summary")?.as_str()?.trim().to_string();
+            (!summary.is_empty()).then(|| OutlineBeat {
+                summary,
+                interaction: beat.get("interaction").and_then(Value::as_bool).unwrap_or(false),
+            })
+        })
+        .take(MAX_OUTLINE_BEATS)
+        .collect::<Vec<OutlineBeat>>();
+    if beats.len() < MIN_OUTLINE_BEATS {
+        return None;
+    }
+    let planned_ending = value
+        .get("planned_ending")?
+        .as_str()?
+        .trim()
+        .to_string();
+    if planned_ending.is_empty() {
+        return None;
+    }
+
+    Some(ChapterOutline {
+        beats,
+        planned_ending,
+        beats_completed: 0,
+        source: ArcSource::Llm,
+    })
+}
+
+/// 按三幕式节拍铺开的模板大纲，冲突与转折之后各设一个互动点
+fn template_outline(plot_state: &PlotState) -> ChapterOutline {
+    let beats = ChapterBeat::SEQUENCE
+        .iter()
+        .map(|beat| OutlineBeat {
+            summary: format!("{}：{}", beat.label(), beat.guidance()),
+            interaction: matches!(beat, ChapterBeat::Conflict | ChapterBeat::Turn),
+        })
+        .collect();
+    let planned_ending = plot_state
+        .story_arc
+        .as_ref()
+        .and_then(StoryArc::current_beat)
+        .map(|beat| format!("推进到「{}」：{}", beat.title, beat.summary))
+        .unwrap_or_else(|| "本章矛盾告一段落，并留下引出下一章的悬念".to_string());
+    ChapterOutline {
+        beats,
+        planned_ending,
+        beats_completed: 0,
+        source: ArcSource::Template,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_outline_tracks_beats_and_interaction_points() {
+        let mut outline = parse_outline(
+            r#"大纲如下：{"beats":[
+                {"summary":"林逸回到青云宗，得知大比提前","interaction":false},
+                {"summary":"师兄挑衅，林逸须决定是否应战","interaction":true},
+                {"summary":"比试中林逸察觉对手服了禁药"}
+            ],"planned_ending":"林逸将禁药之事禀报长老"}"#,
+        )
+        .unwrap();
+        assert_eq!(outline.interaction_points(), 1);
+        assert!(outline.prompt_line().unwrap().starts_with("本章大纲节拍 1/3"));
+        assert!(!outline.ends_with_next_segment());
+
+        outline.advance();
+        assert!(outline.prompt_line().unwrap().contains("等待玩家抉择"));
+        outline.advance();
+        assert!(outline.ends_with_next_segment());
+        outline.advance();
+        outline.advance();
+        assert_eq!(outline.beats_completed, 3);
+        assert!(outline.prompt_line().is_none());
+
+        assert!(parse_outline(r#"{"beats":[{"summary":"a"}],"planned_ending":"b"}"#).is_none());
+        assert!(parse_outline(
+            r#"{"beats":[{"summary":"a"},{"summary":"b"},{"summary":"c"}],"planned_ending":" "}"#
+        )
+        .is_none());
+    }
+}
@@ -0,0 +1,156 @@
+use crate::event_log::{EventImportance, GameEvent};
+use crate::game_state::GameState;
+use crate::models::{Element, Grade};
+use crate::npc::NPC;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+pub const CHARACTER_CARD_VERSION: &str = "1.0.0";
+const MAX_NOTABLE_DEEDS: usize = 5;
+const MAX_RELATIONSHIPS: usize = 8;
+
+/// 可分享的角色名片，也可作为其他世界导入NPC的种子
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CharacterCard {
+    pub version: String,
+    pub name: String,
+    pub realm: String,
+    pub realm_level: u32,
+    pub spiritual_root: SpiritualRootSummary,
+    pub age: u32,
+    pub combat_power: u64,
+    pub techniques: Vec<String>,
+    pub notable_deeds: Vec<NotableDeed>,
+    pub relationships: Vec<RelationshipSummary>,
+    pub world: String,
+    pub game_time: String,
+}
+
+/// 灵根摘要
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpiritualRootSummary {
+    pub element: Element,
+    pub grade: Grade,
+    pub affinity: f32,
+}
+
+/// 名片中的重要事迹
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NotableDeed {
+    pub timestamp: u64,
+    pub event_type: String,
+    pub description: String,
+}
+
+/// NPC对角色的关系摘要
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RelationshipSummary {
+    pub npc_id: String,
+    pub npc_name: String,
+    pub affinity: i32,
+    pub trust: i32,
+    pub label: String,
+}
+
+impl CharacterCard {
+    /// 根据游戏状态、事件和NPC关系生成名片
+    pub fn from_game<'a>(
+        state: &GameState,
+        events: &[GameEvent],
+        npcs: impl IntoIterator<Item = &'a NPC>,
+    ) -> Self {
+        let player = &state.player;
+        let realm = &player.stats.cultivation_realm;
+
+        let mut deeds = events
+            .iter()
+            .filter(|event| event.importance == EventImportance::Important)
+            .collect::<Vec<&GameEvent>>();
+        deeds.sort_by_key(|event| std::cmp::Reverse((event.timestamp, event.id)));
+        let notable_deeds = deeds
+            .into_iter()
+            .take(MAX_NOTABLE_DEEDS)
+            .map(|event| NotableDeed {
+                timestamp: event.timestamp,
+                event_type: event.event_type.to_string(),
+                description: event.description.to_string(),
+            })
+            .collect();
+
+        let mut relationships = npcs
+            .into_iter()
+            .filter_map(|npc| {
+                npc.relationships
+                    .get(&player.id)
+                    .map(|relationship| RelationshipSummary {
+                        npc_id: npc.id.clone(),
+                        npc_name: npc.name.clone(),
+                        affinity: relationship.affinity,
+                        trust: relationship.trust,
+                        label: relationship_label(relationship.affinity).to_string(),
+                    })
+            })
+            .collect::<Vec<RelationshipSummary>>();
+        relationships.sort_by(|a, b| {
+            b.affinity
+                .abs()
+                .cmp(&a.affinity.abs())
+                .then_with(|| a.npc_id.cmp(&b.npc_id))
+        });
+        relationships.truncate(MAX_RELATIONSHIPS);
+
+        Self {
+            version: CHARACTER_CARD_VERSION.to_string(),
+            name: player.name.clone(),
+            realm: format!("{}{}", realm.name, realm.sub_level_name()),
+            realm_level: realm.level,
+            spiritual_root: SpiritualRootSummary {
+                element: player.stats.spiritual_root.element.clone(),
+                grade: player.stats.spiritual_root.grade.clone(),
+                affinity: player.stats.spiritual_root.affinity,
+            },
+            age: player.stats.lifespan.current_age,
+            combat_power: player.stats.combat_power,
+            techniques: player.stats.techniques.iter().map(|t| t.name.clone()).collect(),
+            notable_deeds,
+            relationships,
+            world: state.script.name.clone(),
+            game_time: format!(
+                "{}年{}月{}日",
+                state.game_time.year, state.game_time.month, state.game_time.day
+            ),
+        }
+    }
+
+    /// 将名片以JSON格式写入文件
+    pub fn export_to_file(&self, path: impl AsRef<Path>) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+}
+
+fn relationship_label(affinity: i32) -> &'static str {
+    match affinity {
+        60.. => "挚友",
+        20..=59 => "友善",
+        -19..=19 => "中立",
+        -59..=-20 => "不睦",
+        _ => "仇敌",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relationship_label_ranges() {
+        assert_eq!(relationship_label(100), "挚友");
+        assert_eq!(relationship_label(30), "友善");
+        assert_eq!(relationship_label(0), "中立");
+        assert_eq!(relationship_label(-30), "不睦");
+        assert_eq!(relationship_label(-100), "仇敌");
+    }
+}
@@ -0,0 +1,289 @@
+use crate::game_state::GameState;
+use crate::models::{CharacterStats, CultivationRealm, BASE_COMBAT_POWER, MAX_INSIGHT};
+use crate::numerical_system::{NumericalSystem, StatChange};
+use crate::turn_pipeline::action_context;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// 属性变化记录保留的条数
+pub const MAX_STAT_HISTORY: usize = 20;
+
+/// 一次属性变化及其来由
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct StatHistoryEntry {
+    /// 发生时的游戏天数
+    pub timestamp: u64,
+    /// 引起变化的行动或交易
+    pub source: String,
+    pub change: StatChange,
+}
+
+/// 单门功法对战力的加成
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct TechniqueBonus {
+    pub id: String,
+    pub name: String,
+    pub proficiency: u32,
+    /// 与灵根属性的契合度
+    pub synergy: f32,
+    /// 对基础战力的加成比例
+    pub bonus: f32,
+}
+
+/// 战力构成：基础战力 = 基数 × 灵根品阶 × 亲和度 × 境界倍数 × 功法倍数，修炼所得另计
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CombatPowerBreakdown {
+    pub base: u64,
+    pub root_multiplier: f32,
+    /// 1 + 灵根亲和度
+    pub affinity_multiplier: f32,
+    pub realm_multiplier: f32,
+    pub technique_multiplier: f32,
+    pub techniques: Vec<TechniqueBonus>,
+    /// 灵根、境界与功法决定的战力下限
+    pub base_combat_power: u64,
+    /// 修炼与奇遇积累、高出下限的部分
+    pub accumulated: u64,
+    pub total: u64,
+}
+
+impl CombatPowerBreakdown {
+    pub fn of(stats: &CharacterStats) -> Self {
+        let root = &stats.spiritual_root.element;
+        let base_combat_power = stats.calculate_base_combat_power();
+        Self {
+            base: BASE_COMBAT_POWER,
+            root_multiplier: stats.spiritual_root.power_multiplier(),
+            affinity_multiplier: 1.0 + stats.spiritual_root.affinity,
+            realm_multiplier: stats.cultivation_realm.power_multiplier,
+            technique_multiplier: stats.technique_multiplier(),
+            techniques: stats
+                .techniques
+                .iter()
+                .map(|technique| TechniqueBonus {
+                    id: technique.id.clone(),
+                    name: technique.name.clone(),
+                    proficiency: technique.proficiency,
+                    synergy: technique.synergy(root),
+                    bonus: technique.power_bonus(root),
+                })
+                .collect(),
+            base_combat_power,
+            accumulated: stats.combat_power.saturating_sub(base_combat_power),
+            total: stats.combat_power,
+        }
+    }
+}
+
+/// 寿元与气血
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct LifespanView {
+    pub current_age: u32,
+    pub total_max_age: u32,
+    pub remaining_years: u32,
+    /// 剩余寿元占总寿元的百分比
+    pub remaining_percent: u32,
+    /// 步入暮年后衰退，修炼与突破收益按此打折
+    pub vitality: f32,
+    pub in_twilight: bool,
+}
+
+/// 当前对玩家生效的状态：负伤等临时状态，以及暮年、圆满等由属性推导的状态
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ActiveEffect {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+}
+
+/// 玩家属性面板：按数值系统推导的境界进度、战力构成、寿元与生效状态，前端无需重复实现公式
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CharacterSheet {
+    pub name: String,
+    /// 境界与小境界，如「筑基 中期」
+    pub realm: String,
+    /// 当前大境界内的进度，圆满期为 100
+    pub realm_progress_percent: u32,
+    /// 下一次突破的目标，已至最高境界的圆满期时为空
+    pub next_realm: Option<String>,
+    /// 突破成功率，已计入悟性加成
+    pub breakthrough_chance: f32,
+    /// 悟性积累的进度，积满时突破加成最大，冲关后清零
+    pub insight_percent: u32,
+    /// 修炼一次增长的战力
+    pub cultivation_gain: i64,
+    pub combat_power: CombatPowerBreakdown,
+    pub lifespan: LifespanView,
+    pub active_effects: Vec<ActiveEffect>,
+    /// 最近的属性变化，新的在前
+    pub recent_stat_changes: Vec<StatHistoryEntry>,
+}
+
+impl CharacterSheet {
+    pub fn build(state: &GameState, numerical_system: &NumericalSystem) -> Self {
+        let stats = &state.player.stats;
+        let realm = &stats.cultivation_realm;
+        let ladder = numerical_system.realm_ladder();
+        let next_major = ladder.next_major(realm);
+        let next_realm = match ladder.next_stage(realm) {
+            Some(next) => Some(ladder.display_name(&next)),
+            None => next_major.map(|major| {
+                ladder.display_name(&CultivationRealm {
+                    sub_level: 0,
+                    ..major.clone()
+                })
+            }),
+        };
+        let vitality = numerical_system.vitality(stats);
+
+        Self {
+            name: state.player.name.clone(),
+            realm: ladder.display_name(realm),
+            realm_progress_percent: ladder.progress_percent(realm),
+            next_realm,
+            breakthrough_chance: numerical_system.breakthrough_chance(stats),
+            insight_percent: stats.insight.min(MAX_INSIGHT) * 100 / MAX_INSIGHT,
+            cultivation_gain: numerical_system.cultivation_power_gain(stats, &action_context(state)),
+            combat_power: CombatPowerBreakdown::of(stats),
+            lifespan: LifespanView {
+                current_age: stats.lifespan.current_age,
+                total_max_age: stats.lifespan.total_max_age(),
+                remaining_years: stats.lifespan.remaining_years(),
+                remaining_percent: (stats.lifespan.remaining_ratio() * 100.0).round() as u32,
+                vitality,
+                in_twilight: stats.lifespan.in_twilight(),
+            },
+            active_effects: active_effects(
+                state,
+                vitality,
+                ladder.is_peak(realm) && next_major.is_some(),
+            ),
+            recent_stat_changes: state.stat_history.iter().rev().cloned().collect(),
+        }
+    }
+}
+
+fn active_effects(state: &GameState, vitality: f32, at_peak: bool) -> Vec<ActiveEffect> {
+    let stats = &state.player.stats;
+    let mut effects = stats
+        .status_effects
+        .iter()
+        .map(|status| {
+            effect(
+                status.kind.id(),
+                status.kind.label(),
+                format!(
+                    "{}，还剩 {} 天（{}）",
+                    status.kind.summary(),
+                    status.remaining_days,
+                    status.source
+                ),
+            )
+        })
+        .collect::<Vec<ActiveEffect>>();
+    if !stats.lifespan.is_alive() {
+        effects.push(effect("lifespan_exhausted", "寿元已尽", "气血降到下限，修炼与突破收益减半"));
+    } else if stats.lifespan.in_twilight() {
+        effects.push(effect(
+            "twilight",
+            "暮年",
+            format!("气血衰退至 {:.0}%，修炼与突破收益随之打折", vitality * 100.0),
+        ));
+    }
+    if at_peak {
+        effects.push(effect(
+            "realm_peak",
+            "圆满",
+            "可冲击下一大境界，需依次渡过雷劫与心魔劫",
+        ));
+    }
+    if state
+        .world_state
+        .faction_standings
+        .is_route_closed(&state.player.location)
+    {
+        effects.push(effect("war_zone", "战火封锁", "所在之地战火未熄，探索难有收获"));
+    }
+    effects
+}
+
+fn effect(id: &str, name: &str, description: impl Into<String>) -> ActiveEffect {
+    ActiveEffect {
+        id: id.to_string(),
+        name: name.to_string(),
+        description: description.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_engine::GameEngine;
+    use crate::models::{Lifespan, StatDelta};
+    use crate::numerical_system::PEAK_SUB_LEVEL;
+    use crate::script::Location;
+    use crate::script_manager::ScriptManager;
+
+    fn running_state() -> GameState {
+        let mut script = ScriptManager::new().blank_script();
+        script.world_setting.cultivation_realms = vec![
+            CultivationRealm::new("练气".to_string(), 1, 0, 1.0),
+            CultivationRealm::new("筑基".to_string(), 2, 0, 2.0),
+        ];
+        script.world_setting.locations.push(Location {
+            id: "sect".to_string(),
+            name: "青云宗".to_string(),
+            description: String::new(),
+            spiritual_energy: 1.0,
+            kind: Default::default(),
+            climate: Default::default(),
+        });
+        script.initial_state.starting_location = "sect".to_string();
+        let mut engine = GameEngine::new();
+        engine.initialize_game(script).unwrap();
+        engine.get_current_state().unwrap()
+    }
+
+    #[test]
+    fn test_sheet_matches_numerical_system() {
+        let mut state = running_state();
+        state.player.stats.cultivation_realm.sub_level = PEAK_SUB_LEVEL;
+        state.player.stats.lifespan = Lifespan::new(95, 100, 0);
+        state.player.stats.update_combat_power();
+        state
+            .player
+            .stats
+            .apply_stat_change(StatDelta::CombatPower(30));
+        state.record_stat_changes(
+            "闭关修炼",
+            &[StatChange {
+                stat_name: "combat_power".to_string(),
+                old_value: "100".to_string(),
+                new_value: "130".to_string(),
+            }],
+        );
+        let numerical_system =
+            NumericalSystem::new().with_realm_ladder(&state.script.world_setting);
+
+        let sheet = CharacterSheet::build(&state, &numerical_system);
+        assert_eq!(sheet.realm_progress_percent, 100);
+        assert_eq!(sheet.next_realm.as_deref(), Some("筑基 初期"));
+        assert_eq!(sheet.combat_power.accumulated, 30);
+        assert_eq!(
+            sheet.combat_power.base_combat_power + sheet.combat_power.accumulated,
+            sheet.combat_power.total
+        );
+        assert_eq!(
+            sheet.breakthrough_chance,
+            numerical_system.breakthrough_chance(&state.player.stats)
+        );
+        assert!(sheet.lifespan.in_twilight);
+        let ids = sheet
+            .active_effects
+            .iter()
+            .map(|effect| effect.id.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(ids, vec!["twilight", "realm_peak"]);
+        assert_eq!(sheet.recent_stat_changes[0].source, "闭关修炼");
+    }
+}
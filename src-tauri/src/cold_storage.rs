@@ -0,0 +1,240 @@
+use crate::event_log::GameEvent;
+use crate::plot_engine::{ChapterState, PlotState};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 低内存模式下的磁盘冷存储：已完结章节正文与归档事件写入磁盘，内存中只保留摘要
+#[derive(Debug, Clone)]
+pub struct ColdStorage {
+    directory: PathBuf,
+}
+
+impl ColdStorage {
+    pub fn new(directory: PathBuf) -> Self {
+        Self { directory }
+    }
+
+    /// 每个引擎会话使用独立目录，避免不同对局互相覆盖
+    pub fn for_session(session_id: u64) -> Self {
+        let home = std::env::var("HOME")
+            .or_else(|_| std::env::var("USERPROFILE"))
+            .unwrap_or_else(|_| ".".to_string());
+        let mut directory = PathBuf::from(home);
+        directory.push(".nobody");
+        directory.push("cold");
+        directory.push(session_dir_name(session_id));
+        Self { directory }
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// 同一根目录下全部会话的冷存储目录，包括本会话与之前运行遗留的目录
+    pub fn session_directories(&self) -> Vec<PathBuf> {
+        let Some(Ok(entries)) = self.directory.parent().map(fs::read_dir) else {
+            return Vec::new();
+        };
+        let mut directories = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                path.is_dir()
+                    && path
+                        .file_name()
+                        .and_then(|name| name.to_str())
+                        .is_some_and(is_session_dir_name)
+            })
+            .collect::<Vec<PathBuf>>();
+        directories.sort();
+        directories
+    }
+
+    fn chapter_path(&self, index: u32) -> PathBuf {
+        self.directory.join(format!("chapter_{}.json", index))
+    }
+
+    /// 将已完结章节的正文写入磁盘并从内存中移除，返回本次卸载的章节数
+    pub fn offload_chapters(&self, plot_state: &mut PlotState) -> Result<usize> {
+        let mut offloaded = 0;
+        for chapter in plot_state
+            .chapters
+            .iter_mut()
+            .filter(|chapter| !chapter.offloaded && !chapter.content.is_empty())
+        {
+            fs::create_dir_all(&self.directory)?;
+            fs::write(
+                self.chapter_path(chapter.index),
+                serde_json::to_vec(&chapter.content)?,
+            )?;
+            chapter.content = Vec::new();
+            chapter.offloaded = true;
+            offloaded += 1;
+        }
+        Ok(offloaded)
+    }
+
+    pub fn load_chapter_content(&self, index: u32) -> Result<Vec<String>> {
+        let path = self.chapter_path(index);
+        let bytes = fs::read(&path)
+            .map_err(|e| anyhow!("无法读取第{}章的冷存储正文: {}", index, e))?;
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    /// 返回正文完整的章节，已卸载时从磁盘读回
+    pub fn rehydrate_chapter(&self, chapter: &ChapterState) -> Result<ChapterState> {
+        let mut chapter = chapter.clone();
+        if chapter.offloaded {
+            chapter.content = self.load_chapter_content(chapter.index)?;
+            chapter.offloaded = false;
+        }
+        Ok(chapter)
+    }
+
+    /// 读回所有已卸载章节的正文，用于存档等需要完整剧情的场景
+    pub fn rehydrate_chapters(&self, plot_state: &mut PlotState) -> Result<()> {
+        for chapter in plot_state.chapters.iter_mut() {
+            if chapter.offloaded {
+                *chapter = self.rehydrate_chapter(chapter)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 将归档事件写入磁盘，返回文件名
+    pub fn spill_events(&self, events: &[GameEvent]) -> Result<String> {
+        let (Some(first), Some(last)) = (events.first(), events.last()) else {
+            return Err(anyhow!("没有需要写入冷存储的事件"));
+        };
+        fs::create_dir_all(&self.directory)?;
+        let file_name = format!("events_{}_{}.json", first.id, last.id);
+        fs::write(self.directory.join(&file_name), serde_json::to_vec(events)?)?;
+        Ok(file_name)
+    }
+
+    pub fn load_events(&self, file_name: &str) -> Result<Vec<GameEvent>> {
+        if file_name.contains(['/', '\\']) || file_name.contains("..") {
+            return Err(anyhow!("非法的冷存储文件名: {}", file_name));
+        }
+        let bytes = fs::read(self.directory.join(file_name))?;
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    /// 冷存储目录占用的磁盘字节数
+    pub fn disk_usage_bytes(&self) -> u64 {
+        let Ok(entries) = fs::read_dir(&self.directory) else {
+            return 0;
+        };
+        entries
+            .filter_map(|entry| entry.ok()?.metadata().ok())
+            .filter(|metadata| metadata.is_file())
+            .map(|metadata| metadata.len())
+            .sum()
+    }
+
+    /// 开始新对局或读档时清空旧会话的冷存储
+    pub fn clear(&self) -> Result<()> {
+        if self.directory.exists() {
+            fs::remove_dir_all(&self.directory)?;
+        }
+        Ok(())
+    }
+}
+
+fn session_dir_name(session_id: u64) -> String {
+    format!("{:016x}", session_id)
+}
+
+fn is_session_dir_name(name: &str) -> bool {
+    name.len() == 16 && name.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// 内存占用报告，字节数按 JSON 序列化大小估算
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryUsageReport {
+    pub low_memory_mode: bool,
+    pub chapters_in_memory: usize,
+    pub chapters_offloaded: usize,
+    pub chapter_bytes: u64,
+    pub events_in_memory: usize,
+    pub event_bytes: u64,
+    pub event_archives: usize,
+    pub archived_event_files: usize,
+    pub npc_memory_entries: usize,
+    pub npc_memory_bytes: u64,
+    pub estimated_total_bytes: u64,
+    pub offloaded_bytes_on_disk: u64,
+}
+
+pub fn estimated_bytes<T: Serialize + ?Sized>(value: &T) -> u64 {
+    serde_json::to_vec(value)
+        .map(|bytes| bytes.len() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event_log::EventImportance;
+    use crate::plot_engine::Scene;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    fn plot_state_with_chapters(count: u32) -> PlotState {
+        let mut state = PlotState::new(Scene::new(
+            "start".to_string(),
+            "第一章".to_string(),
+            "开篇".to_string(),
+            "青云宗".to_string(),
+        ));
+        for idx in 0..count {
+            state.append_segment(format!("第{}章的正文", idx + 1));
+            state.finalize_chapter(None, Some(format!("第{}章摘要", idx + 1)));
+        }
+        state
+    }
+
+    #[test]
+    fn test_offload_and_rehydrate_chapters() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = ColdStorage::new(temp_dir.path().join("cold"));
+        let mut state = plot_state_with_chapters(2);
+        let original = state.chapters.clone();
+
+        assert_eq!(storage.offload_chapters(&mut state).unwrap(), 2);
+        assert!(state.chapters.iter().all(|c| c.offloaded && c.content.is_empty()));
+        assert_eq!(state.chapters[1].summary, "第2章摘要");
+        assert_eq!(storage.offload_chapters(&mut state).unwrap(), 0);
+        assert!(storage.disk_usage_bytes() > 0);
+
+        let chapter = storage.rehydrate_chapter(&state.chapters[0]).unwrap();
+        assert_eq!(chapter, original[0]);
+
+        storage.rehydrate_chapters(&mut state).unwrap();
+        assert_eq!(state.chapters, original);
+    }
+
+    #[test]
+    fn test_spill_and_load_events() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = ColdStorage::new(temp_dir.path().to_path_buf());
+        let events = (1..=3)
+            .map(|id| GameEvent {
+                id,
+                timestamp: id,
+                event_type: Arc::from("story"),
+                description: Arc::from(format!("事件{}", id)),
+                importance: EventImportance::Normal,
+                repeat_count: 1,
+                last_timestamp: None,
+            })
+            .collect::<Vec<GameEvent>>();
+
+        let file_name = storage.spill_events(&events).unwrap();
+        assert_eq!(file_name, "events_1_3.json");
+        assert_eq!(storage.load_events(&file_name).unwrap(), events);
+        assert!(storage.load_events("../save_1.json").is_err());
+        assert!(storage.spill_events(&[]).is_err());
+    }
+}
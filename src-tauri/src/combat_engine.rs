@@ -0,0 +1,498 @@
+use crate::game_state::Character;
+use crate::llm_service::{LLMRequest, LLMService, LLMSubsystem};
+use crate::models::CharacterStats;
+use crate::npc::NPC;
+use crate::prompt_builder::{PromptBuilder, PromptConstraints, PromptContext, PromptTemplate};
+use crate::status_effects::{
+    StatusEffectKind, DEFEAT_INJURY_DAYS, POISON_DAYS, WOUNDED_HP_RATIO, WOUNDED_INJURY_DAYS,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// 超过该回合数仍未分出胜负时双方罢手
+pub const MAX_COMBAT_ROUNDS: u32 = 20;
+/// 施展一次功法消耗的真气
+pub const TECHNIQUE_QI_COST: u32 = 25;
+const TECHNIQUE_DAMAGE_MULTIPLIER: f32 = 1.8;
+const DEFEND_QI_RECOVERY: u32 = 10;
+/// 敌方气血低于该比例且真气不足时转为守御
+const ENEMY_GUARD_THRESHOLD: f32 = 0.25;
+
+/// 战斗中每回合可选的行动
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum CombatMove {
+    Attack,
+    Technique { name: String },
+    Defend,
+    Flee,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CombatStatus {
+    Ongoing,
+    Victory,
+    Defeat,
+    Fled,
+    Stalemate,
+}
+
+/// 参战一方，气血与真气等数值由角色属性推算
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Combatant {
+    pub id: String,
+    pub name: String,
+    pub realm: String,
+    pub hp: u32,
+    pub max_hp: u32,
+    pub qi: u32,
+    pub max_qi: u32,
+    pub attack: u32,
+    pub defense: u32,
+    /// 先手值，高者每回合先出手
+    pub initiative: u32,
+    pub techniques: Vec<String>,
+}
+
+impl Combatant {
+    pub fn from_stats(id: &str, name: &str, stats: &CharacterStats) -> Self {
+        let realm = &stats.cultivation_realm;
+        let affinity = stats.spiritual_root.affinity.clamp(0.0, 1.0);
+        let power = u32::try_from(stats.fighting_power()).unwrap_or(u32::MAX);
+        let max_hp = 100 + realm.level * 40 + power / 4;
+        let max_qi = 50 + realm.level * 30 + (affinity * 50.0) as u32;
+        Self {
+            id: id.to_string(),
+            name: name.to_string(),
+            realm: realm.name.clone(),
+            hp: max_hp,
+            max_hp,
+            qi: max_qi,
+            max_qi,
+            attack: 10 + power / 10,
+            defense: realm.level * 3 + power / 20,
+            initiative: realm.level * 10 + realm.sub_level * 3 + (affinity * 10.0) as u32,
+            techniques: stats.techniques.iter().map(|t| t.name.clone()).collect(),
+        }
+    }
+
+    pub fn is_down(&self) -> bool {
+        self.hp == 0
+    }
+
+    fn hp_ratio(&self) -> f32 {
+        self.hp as f32 / self.max_hp.max(1) as f32
+    }
+
+    fn can_use_technique(&self, name: &str) -> bool {
+        self.qi >= TECHNIQUE_QI_COST && self.techniques.iter().any(|t| t == name)
+    }
+}
+
+/// 一方在某回合中的一次出手
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CombatLogEntry {
+    pub actor_id: String,
+    pub action: CombatMove,
+    pub damage: u32,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CombatRound {
+    pub round: u32,
+    pub entries: Vec<CombatLogEntry>,
+    /// LLM 润色后的战斗描写，生成前为空
+    pub narration: Option<String>,
+}
+
+/// 一场进行中或已结束的战斗，随游戏状态存档
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CombatState {
+    pub started_day: u32,
+    pub player: Combatant,
+    pub enemy: Combatant,
+    pub status: CombatStatus,
+    pub rounds: Vec<CombatRound>,
+}
+
+impl CombatState {
+    pub fn new(player: &Character, npc: &NPC, day: u32) -> Self {
+        Self {
+            started_day: day,
+            player: Combatant::from_stats(&player.id, &player.name, &player.stats),
+            enemy: Combatant::from_stats(&npc.id, &npc.name, &npc.stats),
+            status: CombatStatus::Ongoing,
+            rounds: Vec::new(),
+        }
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.status != CombatStatus::Ongoing
+    }
+
+    /// 敌方行动：真气足够时轮流施展功法，气血见底时守御回气，否则普通攻击
+    pub fn enemy_move(&self) -> CombatMove {
+        let enemy = &self.enemy;
+        if enemy.qi >= TECHNIQUE_QI_COST && !enemy.techniques.is_empty() {
+            let name = enemy.techniques[self.rounds.len() % enemy.techniques.len()].clone();
+            return CombatMove::Technique { name };
+        }
+        if enemy.hp_ratio() < ENEMY_GUARD_THRESHOLD {
+            CombatMove::Defend
+        } else {
+            CombatMove::Attack
+        }
+    }
+
+    /// 结算一个回合：双方按先手值依次出手，守御在整个回合内生效
+    pub fn resolve_round(&mut self, player_move: CombatMove) -> Result<&CombatRound, String> {
+        if self.is_over() {
+            return Err("战斗已结束".to_string());
+        }
+        if let CombatMove::Technique { name } = &player_move {
+            if !self.player.techniques.iter().any(|t| t == name) {
+                return Err(format!("未掌握功法：{}", name));
+            }
+            if self.player.qi < TECHNIQUE_QI_COST {
+                return Err(format!("真气不足，施展功法需要 {} 点真气", TECHNIQUE_QI_COST));
+            }
+        }
+
+        let enemy_move = self.enemy_move();
+        let player_guarding = player_move == CombatMove::Defend;
+        let enemy_guarding = enemy_move == CombatMove::Defend;
+        let player_first = self.player.initiative >= self.enemy.initiative;
+        let turns = if player_first {
+            [(true, player_move), (false, enemy_move)]
+        } else {
+            [(false, enemy_move), (true, player_move)]
+        };
+
+        let mut entries = Vec::new();
+        for (is_player, action) in turns {
+            if self.is_over() {
+                break;
+            }
+            let guarding = if is_player { enemy_guarding } else { player_guarding };
+            let entry = self.act(is_player, action, guarding);
+            entries.push(entry);
+        }
+
+        let round = self.rounds.len() as u32 + 1;
+        if !self.is_over() && round >= MAX_COMBAT_ROUNDS {
+            self.status = CombatStatus::Stalemate;
+        }
+        self.rounds.push(CombatRound {
+            round,
+            entries,
+            narration: None,
+        });
+        Ok(self.rounds.last().expect("round just pushed"))
+    }
+
+    /// 战斗结束后玩家落下的伤病：战败重伤，险胜或脱身时气血见底也会负伤，挨过毒功则中毒
+    pub fn player_afflictions(&self) -> Vec<(StatusEffectKind, u32)> {
+        let mut afflictions = Vec::new();
+        match self.status {
+            CombatStatus::Defeat => afflictions.push((StatusEffectKind::Injured, DEFEAT_INJURY_DAYS)),
+            CombatStatus::Ongoing => return afflictions,
+            _ if self.player.hp_ratio() < WOUNDED_HP_RATIO => {
+                afflictions.push((StatusEffectKind::Injured, WOUNDED_INJURY_DAYS))
+            }
+            _ => {}
+        }
+        let poisoned = self.rounds.iter().flat_map(|round| &round.entries).any(|entry| {
+            entry.actor_id == self.enemy.id
+                && entry.damage > 0
+                && matches!(&entry.action, CombatMove::Technique { name } if name.contains('毒'))
+        });
+        if poisoned {
+            afflictions.push((StatusEffectKind::Poisoned, POISON_DAYS));
+        }
+        afflictions
+    }
+
+    pub fn set_narration(&mut self, round: u32, narration: String) {
+        if let Some(entry) = self.rounds.iter_mut().find(|r| r.round == round) {
+            entry.narration = Some(narration);
+        }
+    }
+
+    fn act(&mut self, is_player: bool, action: CombatMove, target_guarding: bool) -> CombatLogEntry {
+        let flee_succeeds = self.player.initiative >= self.enemy.initiative;
+        let (actor, target) = if is_player {
+            (&mut self.player, &mut self.enemy)
+        } else {
+            (&mut self.enemy, &mut self.player)
+        };
+
+        let (damage, description) = match &action {
+            CombatMove::Attack => {
+                let damage = strike(actor.attack as f32, target, target_guarding);
+                (damage, format!("{}出手攻向{}，造成{}点伤害。", actor.name, target.name, damage))
+            }
+            CombatMove::Technique { name } if actor.can_use_technique(name) => {
+                actor.qi -= TECHNIQUE_QI_COST;
+                let power = actor.attack as f32 * TECHNIQUE_DAMAGE_MULTIPLIER;
+                let damage = strike(power, target, target_guarding);
+                (damage, format!("{}施展{}，对{}造成{}点伤害。", actor.name, name, target.name, damage))
+            }
+            CombatMove::Technique { name } => {
+                let damage = strike(actor.attack as f32, target, target_guarding);
+                (damage, format!("{}运转{}不成，只得寻常一击，造成{}点伤害。", actor.name, name, damage))
+            }
+            CombatMove::Defend => {
+                let recovered = DEFEND_QI_RECOVERY.min(actor.max_qi - actor.qi);
+                actor.qi += recovered;
+                (0, format!("{}凝神守御，恢复了{}点真气。", actor.name, recovered))
+            }
+            CombatMove::Flee if flee_succeeds => {
+                (0, format!("{}抽身而退，脱离了战斗。", actor.name))
+            }
+            CombatMove::Flee => (0, format!("{}想要脱身，却被{}缠住。", actor.name, target.name)),
+        };
+        let actor_id = actor.id.clone();
+
+        if is_player && action == CombatMove::Flee && flee_succeeds {
+            self.status = CombatStatus::Fled;
+        } else if self.enemy.is_down() {
+            self.status = CombatStatus::Victory;
+        } else if self.player.is_down() {
+            self.status = CombatStatus::Defeat;
+        }
+
+        CombatLogEntry {
+            actor_id,
+            action,
+            damage,
+            description,
+        }
+    }
+}
+
+/// 减去对方一半防御后的伤害，对方守御时再减半，至少为 1
+fn strike(power: f32, target: &mut Combatant, guarding: bool) -> u32 {
+    let mut damage = (power - target.defense as f32 / 2.0).max(1.0);
+    if guarding {
+        damage /= 2.0;
+    }
+    let damage = (damage.round() as u32).max(1);
+    target.hp = target.hp.saturating_sub(damage);
+    damage
+}
+
+/// 把一回合的出手记录润色为战斗描写，LLM 不可用或输出为空时直接拼接记录
+pub async fn narrate_round(
+    llm_service: Option<&LLMService>,
+    state: &CombatState,
+    round: &CombatRound,
+) -> String {
+    if let Some(llm_service) = llm_service {
+        let request = LLMRequest {
+            prompt: build_narration_prompt(state, round),
+            max_tokens: Some(400),
+            temperature: Some(0.8),
+            subsystem: LLMSubsystem::Plot,
+            retry_policy: None,
+        };
+        if let Ok(response) = llm_service.generate(request).await {
+            let text = response.text.trim();
+            if !text.is_empty() {
+                return text.to_string();
+            }
+        }
+    }
+    fallback_narration(round)
+}
+
+pub fn build_narration_prompt(state: &CombatState, round: &CombatRound) -> String {
+    let scene = format!(
+        "Round {} between {} (HP {}/{}) and {} (HP {}/{}).\n{}",
+        round.round,
+        state.player.name,
+        state.player.hp,
+        state.player.max_hp,
+        state.enemy.name,
+        state.enemy.hp,
+        state.enemy.max_hp,
+        round
+            .entries
+            .iter()
+            .map(|e| e.description.as_str())
+            .collect::<Vec<&str>>()
+            .join("\n")
+    );
+    let context = PromptContext {
+        scene: Some(scene),
+        actor_name: Some(state.player.name.clone()),
+        actor_realm: Some(state.player.realm.clone()),
+        ..PromptContext::default()
+    };
+    let constraints = PromptConstraints {
+        numerical_rules: vec!["do not change any damage or HP numbers".to_string()],
+        world_rules: vec![
+            "write 2-4 sentences of Chinese prose".to_string(),
+            "describe only what the round log records".to_string(),
+        ],
+        output_schema_hint: None,
+    };
+
+    PromptBuilder::default().build_prompt_with_token_limit(
+        PromptTemplate::CombatNarration,
+        &context,
+        &constraints,
+        600,
+    )
+}
+
+pub fn fallback_narration(round: &CombatRound) -> String {
+    round
+        .entries
+        .iter()
+        .map(|e| e.description.as_str())
+        .collect::<Vec<&str>>()
+        .join("")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm_provider::ProviderKind;
+    use crate::llm_service::{LLMConfig, LLMResponse};
+    use crate::models::{CultivationRealm, Element, Grade, LearnedTechnique, Lifespan, SpiritualRoot};
+
+    fn combatant(id: &str, level: u32, techniques: &[&str]) -> Combatant {
+        let mut stats = CharacterStats::new(
+            SpiritualRoot {
+                element: Element::Fire,
+                grade: Grade::double(),
+                affinity: 0.5,
+                tier_multiplier: None,
+            },
+            CultivationRealm::new("炼气".to_string(), level, 0, level as f32),
+            Lifespan::new(20, 120, 0),
+        );
+        stats.techniques = techniques.iter().map(|t| LearnedTechnique::named(t)).collect();
+        Combatant::from_stats(id, id, &stats)
+    }
+
+    fn battle(player: Combatant, enemy: Combatant) -> CombatState {
+        CombatState {
+            started_day: 1,
+            player,
+            enemy,
+            status: CombatStatus::Ongoing,
+            rounds: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_stronger_side_acts_first_and_wins() {
+        let mut state = battle(combatant("player", 3, &["烈焰掌"]), combatant("bandit", 1, &[]));
+        assert!(state.player.initiative > state.enemy.initiative);
+
+        let round = state
+            .resolve_round(CombatMove::Technique {
+                name: "烈焰掌".to_string(),
+            })
+            .unwrap();
+        assert_eq!(round.entries[0].actor_id, "player");
+        assert!(round.entries[0].description.contains("烈焰掌"));
+        assert_eq!(state.player.qi, state.player.max_qi - TECHNIQUE_QI_COST);
+
+        while !state.is_over() {
+            state.resolve_round(CombatMove::Attack).unwrap();
+        }
+        assert_eq!(state.status, CombatStatus::Victory);
+        assert!(state.enemy.is_down());
+        assert!(state.resolve_round(CombatMove::Attack).is_err());
+    }
+
+    #[test]
+    fn test_defend_halves_damage_and_flee_needs_initiative() {
+        let mut guarded = battle(combatant("player", 1, &[]), combatant("elder", 3, &[]));
+        let mut open = guarded.clone();
+        guarded.resolve_round(CombatMove::Defend).unwrap();
+        open.resolve_round(CombatMove::Attack).unwrap();
+        assert!(guarded.player.hp > open.player.hp);
+
+        let mut slow = battle(combatant("player", 1, &[]), combatant("elder", 3, &[]));
+        slow.resolve_round(CombatMove::Flee).unwrap();
+        assert_eq!(slow.status, CombatStatus::Ongoing);
+
+        let mut fast = battle(combatant("player", 3, &[]), combatant("bandit", 1, &[]));
+        fast.resolve_round(CombatMove::Flee).unwrap();
+        assert_eq!(fast.status, CombatStatus::Fled);
+        assert_eq!(fast.rounds[0].entries.len(), 1);
+    }
+
+    #[test]
+    fn test_defeat_by_poison_technique_leaves_afflictions() {
+        let mut state = battle(combatant("player", 1, &[]), combatant("elder", 3, &["五毒掌"]));
+        assert!(state.player_afflictions().is_empty());
+        while !state.is_over() {
+            state.resolve_round(CombatMove::Attack).unwrap();
+        }
+        assert_eq!(state.status, CombatStatus::Defeat);
+        assert_eq!(
+            state.player_afflictions(),
+            vec![
+                (StatusEffectKind::Injured, DEFEAT_INJURY_DAYS),
+                (StatusEffectKind::Poisoned, POISON_DAYS)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_technique_is_rejected() {
+        let mut state = battle(combatant("player", 1, &[]), combatant("bandit", 1, &[]));
+        let err = state
+            .resolve_round(CombatMove::Technique {
+                name: "九天雷诀".to_string(),
+            })
+            .unwrap_err();
+        assert!(err.contains("九天雷诀"));
+        assert!(state.rounds.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_narrate_round_prefers_llm_and_falls_back() {
+        let mut state = battle(combatant("player", 2, &[]), combatant("bandit", 1, &[]));
+        state.resolve_round(CombatMove::Attack).unwrap();
+        let round = state.rounds[0].clone();
+
+        let fallback = narrate_round(None, &state, &round).await;
+        assert_eq!(fallback, fallback_narration(&round));
+
+        let llm_service = LLMService::new(LLMConfig {
+            endpoint: "https://example.com/v1/chat/completions".to_string(),
+            api_key: "test-key".to_string(),
+            model: "gpt-test".to_string(),
+            max_tokens: 1024,
+            temperature: 0.8,
+            provider_kind: ProviderKind::OpenAI,
+            retry_policy: Default::default(),
+        })
+        .unwrap();
+        llm_service.cache_response_for_request(
+            &LLMRequest {
+                prompt: build_narration_prompt(&state, &round),
+                max_tokens: Some(400),
+                temperature: Some(0.8),
+                subsystem: LLMSubsystem::Plot,
+                retry_policy: None,
+            },
+            &LLMResponse {
+                text: " 剑光乍起，山匪踉跄后退。 ".to_string(),
+                model: None,
+                finish_reason: None,
+                prompt_tokens: None,
+                completion_tokens: None,
+                total_tokens: None,
+            },
+        );
+        let narration = narrate_round(Some(&llm_service), &state, &round).await;
+        assert_eq!(narration, "剑光乍起，山匪踉跄后退。");
+    }
+}
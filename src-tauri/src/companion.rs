@@ -0,0 +1,352 @@
+use crate::game_state::GameState;
+use crate::llm_service::{LLMRequest, LLMService, LLMSubsystem};
+use crate::numerical_system::{Action, RealmLadder};
+use crate::plot_engine::PlayerOption;
+use crate::prompt_builder::{PromptBuilder, PromptConstraints, PromptContext, PromptTemplate};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+const RICH_SPIRITUAL_ENERGY: f32 = 1.5;
+const MAX_ADVICE_CHARS: usize = 160;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SuggestionRisk {
+    Low,
+    Medium,
+    High,
+}
+
+/// 对当前某个选项的推荐，只供参考，不会替玩家执行
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActionSuggestion {
+    pub option_id: usize,
+    pub option_uid: String,
+    pub description: String,
+    pub score: f32,
+    pub risk: SuggestionRisk,
+    pub reasons: Vec<String>,
+}
+
+/// 同伴给出的建议：按推荐度排序的选项与一句总结
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompanionAdvice {
+    pub suggestions: Vec<ActionSuggestion>,
+    pub advice: String,
+    /// 总结是否由 LLM 润色
+    pub phrased_by_llm: bool,
+}
+
+/// 按进行中的任务、境界、寿元、所在地灵气与对手战力为当前选项打分，推荐度高的在前；
+/// `npc_powers` 为已知 NPC 的战力
+pub fn rank_options(
+    state: &GameState,
+    options: &[PlayerOption],
+    npc_powers: &HashMap<String, u64>,
+) -> Vec<ActionSuggestion> {
+    let mut suggestions = options
+        .iter()
+        .map(|option| score_option(state, option, npc_powers))
+        .collect::<Vec<ActionSuggestion>>();
+    suggestions.sort_by(|a, b| b.score.total_cmp(&a.score));
+    suggestions
+}
+
+fn score_option(
+    state: &GameState,
+    option: &PlayerOption,
+    npc_powers: &HashMap<String, u64>,
+) -> ActionSuggestion {
+    let stats = &state.player.stats;
+    let lifespan_short = stats.lifespan.in_twilight();
+    let in_combat = state.combat.as_ref().is_some_and(|combat| !combat.is_over());
+
+    let mut score = 1.0;
+    let mut risk = SuggestionRisk::Low;
+    let mut reasons = Vec::new();
+
+    match &option.action {
+        Action::Breakthrough => {
+            let ladder = RealmLadder::from_world(&state.script.world_setting);
+            if ladder.is_peak(&stats.cultivation_realm) {
+                score += 2.0;
+                risk = SuggestionRisk::Medium;
+                reasons.push("修为已至圆满，正是冲关之时".to_string());
+            } else {
+                score -= 1.0;
+                risk = SuggestionRisk::High;
+                reasons.push("修为尚未圆满，强行突破风险很高".to_string());
+            }
+            if lifespan_short {
+                score += 2.0;
+                reasons.push("寿元所剩不多，突破可延寿".to_string());
+            }
+        }
+        Action::Cultivate => {
+            score += 0.5;
+            reasons.push("稳步积累修为".to_string());
+            let energy = state
+                .world_state
+                .locations
+                .get(&state.player.location)
+                .map(|location| location.spiritual_energy)
+                .unwrap_or(1.0);
+            if energy >= RICH_SPIRITUAL_ENERGY {
+                score += 1.0;
+                reasons.push("此地灵气充沛，修炼事半功倍".to_string());
+            }
+            if lifespan_short {
+                score += 0.5;
+            }
+            if in_combat {
+                score -= 2.0;
+                risk = SuggestionRisk::High;
+                reasons.push("战斗尚未结束，无暇修炼".to_string());
+            }
+        }
+        Action::Combat { target_id } => match npc_powers.get(target_id) {
+            Some(&enemy) if enemy > stats.combat_power.saturating_mul(3) / 2 => {
+                score -= 2.0;
+                risk = SuggestionRisk::High;
+                reasons.push("对手战力远胜于你".to_string());
+            }
+            Some(&enemy) if enemy <= stats.combat_power => {
+                score += 1.0;
+                risk = SuggestionRisk::Medium;
+                reasons.push("你的战力不逊于对手，胜算较大".to_string());
+            }
+            Some(_) => {
+                risk = SuggestionRisk::Medium;
+                reasons.push("双方战力相近，胜负难料".to_string());
+            }
+            None => {
+                risk = SuggestionRisk::Medium;
+                reasons.push("对手底细不明".to_string());
+            }
+        },
+        Action::Rest => {
+            if in_combat {
+                score -= 1.0;
+                risk = SuggestionRisk::Medium;
+                reasons.push("战斗中停手休整会给对手可乘之机".to_string());
+            } else if lifespan_short {
+                score -= 0.5;
+                reasons.push("寿元有限，不宜虚度光阴".to_string());
+            }
+        }
+        Action::LearnTechnique { .. } | Action::PracticeTechnique { .. } => {
+            score += 0.5;
+            reasons.push("精研功法，修炼与战力都能受益".to_string());
+            if in_combat {
+                score -= 2.0;
+                risk = SuggestionRisk::High;
+                reasons.push("战斗尚未结束，无暇参悟功法".to_string());
+            }
+        }
+        Action::UseItem { .. } => {
+            if lifespan_short {
+                score += 3.0;
+                reasons.push("寿元将尽，服丹可延寿".to_string());
+            } else {
+                score -= 0.5;
+                reasons.push("延寿丹药珍贵，不妨留待暮年再服".to_string());
+            }
+        }
+        Action::Custom { .. } => {}
+    }
+
+    for quest in state.quests.active() {
+        if shares_keyword(&option.description, &quest.title) {
+            score += 2.0;
+            reasons.push(format!("有助于推进任务「{}」", quest.title));
+        }
+    }
+    if reasons.is_empty() {
+        reasons.push("顺应眼下的局势".to_string());
+    }
+
+    ActionSuggestion {
+        option_id: option.id,
+        option_uid: option.uid.clone(),
+        description: option.description.clone(),
+        score,
+        risk,
+        reasons,
+    }
+}
+
+/// 两段文本是否有相同的两字词，任务标题与选项描述常用不同说法指同一件事
+fn shares_keyword(text: &str, title: &str) -> bool {
+    let bigrams = |s: &str| {
+        let chars = s
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .collect::<Vec<char>>();
+        chars
+            .windows(2)
+            .map(|pair| pair.iter().collect::<String>())
+            .collect::<HashSet<String>>()
+    };
+    !bigrams(text).is_disjoint(&bigrams(title))
+}
+
+/// 为排好序的推荐写一句总结，LLM 可用时交给同伴口吻润色
+pub async fn phrase_advice(
+    llm_service: Option<&LLMService>,
+    state: &GameState,
+    suggestions: Vec<ActionSuggestion>,
+) -> CompanionAdvice {
+    if let (Some(llm_service), Some(_)) = (llm_service, suggestions.first()) {
+        let request = LLMRequest {
+            prompt: build_companion_prompt(state, &suggestions),
+            max_tokens: Some(200),
+            temperature: Some(0.6),
+            subsystem: LLMSubsystem::Plot,
+            retry_policy: None,
+        };
+        if let Ok(response) = llm_service.generate(request).await {
+            let text = response.text.trim();
+            if !text.is_empty() {
+                return CompanionAdvice {
+                    advice: text.chars().take(MAX_ADVICE_CHARS).collect(),
+                    suggestions,
+                    phrased_by_llm: true,
+                };
+            }
+        }
+    }
+    fallback_advice(suggestions)
+}
+
+pub fn build_companion_prompt(state: &GameState, suggestions: &[ActionSuggestion]) -> String {
+    let context = PromptContext {
+        scene: Some(
+            suggestions
+                .iter()
+                .map(|s| format!("{} (score {:.1}): {}", s.description, s.score, s.reasons.join("; ")))
+                .collect::<Vec<String>>()
+                .join("\n"),
+        ),
+        location: Some(state.player.location.clone()),
+        actor_name: Some(state.player.name.clone()),
+        actor_realm: Some(state.player.stats.cultivation_realm.name.clone()),
+        actor_combat_power: Some(state.player.stats.combat_power),
+        active_quests: state.quests.prompt_lines(),
+        ..PromptContext::default()
+    };
+    let constraints = PromptConstraints {
+        numerical_rules: Vec::new(),
+        world_rules: vec![
+            "answer in Chinese, at most 2 sentences, as a companion talking to the player"
+                .to_string(),
+            "recommend the first option and mention its main reason".to_string(),
+            "only advise; never decide or describe the player taking the action".to_string(),
+        ],
+        output_schema_hint: None,
+    };
+
+    PromptBuilder::default().build_prompt_with_token_limit(
+        PromptTemplate::CompanionAdvice,
+        &context,
+        &constraints,
+        800,
+    )
+}
+
+pub fn fallback_advice(suggestions: Vec<ActionSuggestion>) -> CompanionAdvice {
+    let advice = match suggestions.first() {
+        Some(top) => format!("不妨{}：{}。", top.description, top.reasons.join("，")),
+        None => "眼下没有可供选择的行动。".to_string(),
+    };
+    CompanionAdvice {
+        suggestions,
+        advice,
+        phrased_by_llm: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_engine::GameEngine;
+    use crate::models::CultivationRealm;
+    use crate::numerical_system::PEAK_SUB_LEVEL;
+    use crate::quest_system::QuestUpdates;
+    use crate::script::{Climate, Location, LocationKind};
+    use crate::script_manager::ScriptManager;
+
+    fn option(id: usize, description: &str, action: Action) -> PlayerOption {
+        PlayerOption {
+            id,
+            uid: format!("uid-{}", id),
+            description: description.to_string(),
+            requirements: Vec::new(),
+            action,
+        }
+    }
+
+    fn running_state() -> GameState {
+        let mut script = ScriptManager::new().blank_script();
+        script
+            .world_setting
+            .cultivation_realms
+            .push(CultivationRealm::new("练气".to_string(), 1, 0, 1.0));
+        script.world_setting.locations.push(Location {
+            id: "sect".to_string(),
+            name: "青云宗".to_string(),
+            description: String::new(),
+            spiritual_energy: 1.0,
+            kind: LocationKind::Wilderness,
+            climate: Climate::Temperate,
+        });
+        script.initial_state.starting_location = "sect".to_string();
+        let mut engine = GameEngine::new();
+        engine.initialize_game(script).unwrap()
+    }
+
+    #[test]
+    fn test_rank_options_prefers_quests_and_avoids_stronger_enemies() {
+        let mut state = running_state();
+        state.player.stats.combat_power = 100;
+        state.quests.apply(
+            &QuestUpdates {
+                new_quests: vec!["寻找失踪的师兄".to_string()],
+                ..QuestUpdates::default()
+            },
+            1,
+        );
+        let options = vec![
+            option(0, "闭关修炼", Action::Cultivate),
+            option(1, "挑战血魔", Action::Combat { target_id: "demon".to_string() }),
+            option(2, "下山打听师兄的下落", Action::Custom { description: String::new() }),
+            option(3, "尝试突破", Action::Breakthrough),
+        ];
+        let npc_powers = HashMap::from([("demon".to_string(), 500)]);
+
+        let ranked = rank_options(&state, &options, &npc_powers);
+        assert_eq!(ranked[0].option_id, 2);
+        assert!(ranked[0].reasons[0].contains("寻找失踪的师兄"));
+        assert_eq!(ranked[1].option_id, 0);
+        let last_two = ranked[2..].iter().map(|s| s.option_id).collect::<Vec<usize>>();
+        assert!(last_two.contains(&1) && last_two.contains(&3));
+        assert!(ranked[2..].iter().all(|s| s.risk == SuggestionRisk::High));
+    }
+
+    #[test]
+    fn test_short_lifespan_pushes_breakthrough_and_fallback_names_top_option() {
+        let mut state = running_state();
+        state.player.stats.cultivation_realm.sub_level = PEAK_SUB_LEVEL;
+        state.player.stats.lifespan.current_age = state.player.stats.lifespan.total_max_age() - 1;
+        let options = vec![
+            option(0, "休息", Action::Rest),
+            option(1, "冲击瓶颈", Action::Breakthrough),
+        ];
+
+        let advice = fallback_advice(rank_options(&state, &options, &HashMap::new()));
+        assert_eq!(advice.suggestions[0].option_id, 1);
+        assert!(advice.advice.starts_with("不妨冲击瓶颈"));
+        assert!(advice.advice.contains("突破可延寿"));
+        assert!(!advice.phrased_by_llm);
+        assert_eq!(fallback_advice(Vec::new()).advice, "眼下没有可供选择的行动。");
+    }
+}
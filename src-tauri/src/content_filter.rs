@@ -0,0 +1,294 @@
+use crate::action_filters::normalize_keywords;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+
+/// 遮蔽违规词时使用的字符
+const REDACTION_CHAR: char = '□';
+
+/// 适中及以下尺度屏蔽的血腥描写
+const GRAPHIC_VIOLENCE_TERMS: &[&str] = &[
+    "血肉横飞",
+    "开膛破肚",
+    "肝脑涂地",
+    "脑浆",
+    "肠子",
+    "断肢",
+    "剥皮",
+    "碎尸",
+];
+/// 克制尺度额外屏蔽的直白杀戮描写
+const VIOLENCE_TERMS: &[&str] = &["鲜血", "斩首", "杀戮", "屠戮", "尸体", "血泊"];
+/// 适中及以下尺度屏蔽的露骨情爱描写
+const EXPLICIT_ROMANCE_TERMS: &[&str] = &["云雨", "宽衣解带", "赤身裸体", "床笫", "交欢"];
+/// 克制尺度额外屏蔽的亲昵描写
+const ROMANCE_TERMS: &[&str] = &["亲吻", "拥吻", "缠绵", "耳鬓厮磨", "相拥而眠"];
+
+static RUNTIME_CONTENT_FILTER: OnceLock<Mutex<Option<ContentFilterSettings>>> = OnceLock::new();
+
+fn filter_slot() -> &'static Mutex<Option<ContentFilterSettings>> {
+    RUNTIME_CONTENT_FILTER.get_or_init(|| Mutex::new(None))
+}
+
+fn filter_file_path() -> PathBuf {
+    PathBuf::from(".nobody_content_filter.json")
+}
+
+/// 描写尺度
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentIntensity {
+    /// 点到为止
+    Mild,
+    /// 不写血腥与露骨的细节
+    #[default]
+    Moderate,
+    Unrestricted,
+}
+
+impl ContentIntensity {
+    fn terms(self, explicit: &'static [&'static str], mild: &'static [&'static str]) -> Vec<&'static str> {
+        match self {
+            ContentIntensity::Mild => explicit.iter().chain(mild).copied().collect(),
+            ContentIntensity::Moderate => explicit.to_vec(),
+            ContentIntensity::Unrestricted => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentCategory {
+    BlockedWord,
+    Violence,
+    Romance,
+}
+
+impl ContentCategory {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ContentCategory::BlockedWord => "屏蔽词",
+            ContentCategory::Violence => "暴力",
+            ContentCategory::Romance => "情爱",
+        }
+    }
+}
+
+/// 生成文本中命中的一处违规
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentViolation {
+    pub category: ContentCategory,
+    pub term: String,
+}
+
+/// 用户对生成剧情的内容过滤设置
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContentFilterSettings {
+    pub enabled: bool,
+    /// 不允许出现在剧情中的词语
+    pub blocked_words: Vec<String>,
+    pub violence: ContentIntensity,
+    pub romance: ContentIntensity,
+}
+
+impl Default for ContentFilterSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            blocked_words: Vec::new(),
+            violence: ContentIntensity::Moderate,
+            romance: ContentIntensity::Moderate,
+        }
+    }
+}
+
+impl ContentFilterSettings {
+    /// 校验并规范化屏蔽词：去除首尾空白、转为小写并去重
+    pub fn normalized(&self) -> Result<Self, String> {
+        Ok(Self {
+            blocked_words: normalize_keywords(&self.blocked_words, "屏蔽")?,
+            ..self.clone()
+        })
+    }
+
+    fn rules(&self) -> Vec<(ContentCategory, &str)> {
+        if !self.enabled {
+            return Vec::new();
+        }
+        self.blocked_words
+            .iter()
+            .map(|word| (ContentCategory::BlockedWord, word.as_str()))
+            .chain(
+                self.violence
+                    .terms(GRAPHIC_VIOLENCE_TERMS, VIOLENCE_TERMS)
+                    .into_iter()
+                    .map(|term| (ContentCategory::Violence, term)),
+            )
+            .chain(
+                self.romance
+                    .terms(EXPLICIT_ROMANCE_TERMS, ROMANCE_TERMS)
+                    .into_iter()
+                    .map(|term| (ContentCategory::Romance, term)),
+            )
+            .collect()
+    }
+
+    /// 文本命中的违规词，每个词只报告一次
+    pub fn violations(&self, text: &str) -> Vec<ContentViolation> {
+        self.rules()
+            .into_iter()
+            .filter(|(_, term)| find_ignore_ascii_case(text, term).is_some())
+            .map(|(category, term)| ContentViolation {
+                category,
+                term: term.to_string(),
+            })
+            .collect()
+    }
+
+    /// 写入续写提示的内容约束
+    pub fn prompt_rules(&self) -> Vec<String> {
+        if !self.enabled {
+            return Vec::new();
+        }
+        let mut rules = Vec::new();
+        match self.violence {
+            ContentIntensity::Mild => rules.push("打斗点到为止，不写流血、伤亡细节".to_string()),
+            ContentIntensity::Moderate => rules.push("不写血腥残忍的细节".to_string()),
+            ContentIntensity::Unrestricted => {}
+        }
+        match self.romance {
+            ContentIntensity::Mild => rules.push("情感描写含蓄，不写亲昵举动".to_string()),
+            ContentIntensity::Moderate => rules.push("不写露骨的情爱描写".to_string()),
+            ContentIntensity::Unrestricted => {}
+        }
+        if !self.blocked_words.is_empty() {
+            rules.push(format!("不得出现以下词语：{}", self.blocked_words.join("、")));
+        }
+        rules
+    }
+
+    /// 文本违规后重新生成时使用的更严格约束
+    pub fn stricter_rules(&self, violations: &[ContentViolation]) -> Vec<String> {
+        let mut rules = self.prompt_rules();
+        rules.push(format!(
+            "上一稿因出现以下内容被退回，改写时不得再出现：{}",
+            violations
+                .iter()
+                .map(|v| format!("{}（{}）", v.term, v.category.label()))
+                .collect::<Vec<String>>()
+                .join("、")
+        ));
+        rules.push("宁可略写，也不要触碰上述内容".to_string());
+        rules
+    }
+
+    /// 把违规词替换为等长的遮蔽字符
+    pub fn redact(&self, text: &str) -> String {
+        let mut redacted = text.to_string();
+        for (_, term) in self.rules() {
+            while let Some(start) = find_ignore_ascii_case(&redacted, term) {
+                let mask = REDACTION_CHAR.to_string().repeat(term.chars().count());
+                redacted.replace_range(start..start + term.len(), &mask);
+            }
+        }
+        redacted
+    }
+}
+
+/// 忽略 ASCII 大小写查找子串的字节位置
+fn find_ignore_ascii_case(text: &str, term: &str) -> Option<usize> {
+    if term.is_empty() {
+        return None;
+    }
+    text.char_indices().map(|(idx, _)| idx).find(|&idx| {
+        text.get(idx..idx + term.len())
+            .is_some_and(|candidate| candidate.eq_ignore_ascii_case(term))
+    })
+}
+
+/// 应用级内容过滤设置：运行时设置优先，其次读取配置文件，最后使用默认设置
+pub fn app_content_filter() -> ContentFilterSettings {
+    if let Some(settings) = filter_slot().lock().unwrap().clone() {
+        return settings;
+    }
+    load_filter_from_file().unwrap_or_default()
+}
+
+/// 校验并保存内容过滤设置，返回规范化后的设置
+pub fn set_app_content_filter(settings: &ContentFilterSettings) -> Result<ContentFilterSettings, String> {
+    let settings = settings.normalized()?;
+    *filter_slot().lock().unwrap() = Some(settings.clone());
+    persist_filter_to_disk(&settings)?;
+    Ok(settings)
+}
+
+fn load_filter_from_file() -> Option<ContentFilterSettings> {
+    let content = fs::read_to_string(filter_file_path()).ok()?;
+    serde_json::from_str::<ContentFilterSettings>(&content).ok()
+}
+
+fn persist_filter_to_disk(settings: &ContentFilterSettings) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(settings).map_err(|e| e.to_string())?;
+    fs::write(filter_file_path(), content).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intensity_levels_select_terms() {
+        let text = "剑光过处鲜血四溅，血肉横飞。";
+        let moderate = ContentFilterSettings::default();
+        assert_eq!(
+            moderate.violations(text),
+            vec![ContentViolation {
+                category: ContentCategory::Violence,
+                term: "血肉横飞".to_string(),
+            }]
+        );
+
+        let mild = ContentFilterSettings {
+            violence: ContentIntensity::Mild,
+            ..ContentFilterSettings::default()
+        };
+        assert_eq!(mild.violations(text).len(), 2);
+
+        let unrestricted = ContentFilterSettings {
+            violence: ContentIntensity::Unrestricted,
+            ..ContentFilterSettings::default()
+        };
+        assert!(unrestricted.violations(text).is_empty());
+        let disabled = ContentFilterSettings {
+            enabled: false,
+            ..mild
+        };
+        assert!(disabled.violations(text).is_empty());
+        assert!(disabled.prompt_rules().is_empty());
+    }
+
+    #[test]
+    fn test_blocked_words_are_normalized_and_redacted() {
+        let settings = ContentFilterSettings {
+            blocked_words: vec![" Demon ".to_string(), "魔头".to_string(), "demon".to_string()],
+            ..ContentFilterSettings::default()
+        }
+        .normalized()
+        .unwrap();
+        assert_eq!(settings.blocked_words, vec!["demon".to_string(), "魔头".to_string()]);
+
+        let text = "那魔头自称 DEMON lord。";
+        let violations = settings.violations(text);
+        assert_eq!(violations.len(), 2);
+        assert_eq!(settings.redact(text), "那□□自称 □□□□□ lord。");
+        assert!(settings.stricter_rules(&violations).iter().any(|rule| rule.contains("魔头（屏蔽词）")));
+
+        let invalid = ContentFilterSettings {
+            blocked_words: vec!["  ".to_string()],
+            ..ContentFilterSettings::default()
+        };
+        assert!(invalid.normalized().is_err());
+    }
+}
@@ -0,0 +1,263 @@
+use crate::arc_planner::StoryArc;
+use crate::event_triage::BOOKKEEPING_EVENTS;
+use crate::facts::MAX_PROMPT_FACTS;
+use crate::game_state::GameState;
+use crate::plot_engine::PlotState;
+use crate::prompt_builder::{estimate_token_count, PromptContext};
+use crate::world_clock::WorldClock;
+
+/// 拼装出的上下文默认至多占用的 token 数
+pub const DEFAULT_CONTEXT_TOKENS: u32 = 400;
+/// 近期事件至多带上的条数
+const MAX_RECENT_EVENTS: usize = 8;
+
+/// 按完整的游戏与剧情状态拼装提示词上下文。剧情续写、NPC 对话与选项生成共用同一份，
+/// 避免各处各取一部分状态导致前后矛盾；超出预算时先舍弃较早的事件，再舍弃设定与关系
+pub struct ContextAssembler {
+    relationships: Vec<String>,
+    token_limit: u32,
+}
+
+impl ContextAssembler {
+    pub fn new() -> Self {
+        Self {
+            relationships: Vec::new(),
+            token_limit: DEFAULT_CONTEXT_TOKENS,
+        }
+    }
+
+    /// 人物关系概况由引擎从 NPC 关系网汇总，不在存档状态中
+    pub fn with_relationships(mut self, relationships: Vec<String>) -> Self {
+        self.relationships = relationships;
+        self
+    }
+
+    pub fn with_token_limit(mut self, token_limit: u32) -> Self {
+        self.token_limit = token_limit;
+        self
+    }
+
+    pub fn assemble(&self, game_state: &GameState, plot_state: &PlotState) -> PromptContext {
+        let player = &game_state.player;
+        let mut status_effects = player.stats.status_effects.prompt_lines();
+        status_effects.extend(game_state.karma.prompt_line());
+
+        let mut context = PromptContext {
+            scene: None,
+            location: Some(plot_state.current_scene.location.clone()),
+            environment: Some(WorldClock::for_player(game_state).prompt_line()),
+            actor_name: Some(player.name.clone()),
+            actor_realm: Some(player.stats.cultivation_realm.name.clone()),
+            actor_combat_power: Some(player.stats.combat_power),
+            player_persona: plot_state.player_persona.summary(),
+            canon_facts: plot_state
+                .canon_facts
+                .prompt_lines(&plot_state.current_scene.description, MAX_PROMPT_FACTS),
+            story_beat: plot_state
+                .story_arc
+                .as_ref()
+                .and_then(StoryArc::prompt_line),
+            chapter_beat: plot_state.chapter_beat_line(),
+            active_quests: game_state.quests.prompt_lines(),
+            relationships: self.relationships.clone(),
+            status_effects,
+            history_events: recent_events(game_state),
+            world_setting_summary: Some(format!(
+                "{}；{}",
+                character_sheet_line(game_state),
+                world_line(game_state)
+            )),
+        };
+        self.fit(&mut context);
+        context
+    }
+
+    fn fit(&self, context: &mut PromptContext) {
+        while estimated_tokens(context) > self.token_limit {
+            if !context.history_events.is_empty() {
+                context.history_events.remove(0);
+            } else if context.canon_facts.pop().is_none()
+                && context.relationships.pop().is_none()
+                && context.active_quests.pop().is_none()
+            {
+                break;
+            }
+        }
+    }
+}
+
+impl Default for ContextAssembler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 角色卡摘要：姓名、境界、年岁寿元、战力与灵根
+pub fn character_sheet_line(game_state: &GameState) -> String {
+    let player = &game_state.player;
+    let stats = &player.stats;
+    format!(
+        "{}，{}，{}岁（寿元{}），战力{}，{}",
+        player.name,
+        stats.cultivation_realm.name,
+        stats.lifespan.current_age,
+        stats.lifespan.total_max_age(),
+        stats.combat_power,
+        game_state
+            .script
+            .world_setting
+            .describe_root(&stats.spiritual_root)
+    )
+}
+
+fn world_line(game_state: &GameState) -> String {
+    let time = &game_state.game_time;
+    let mut line = format!(
+        "{}，第{}年{}月{}日",
+        game_state.script.name, time.year, time.month, time.day
+    );
+    let factions = &game_state.script.world_setting.factions;
+    if !factions.is_empty() {
+        line.push_str(&format!(
+            "，势力：{}",
+            factions
+                .iter()
+                .map(|faction| faction.name.as_str())
+                .collect::<Vec<&str>>()
+                .join("、")
+        ));
+    }
+    line
+}
+
+/// 最近发生的事，按时间先后，不含引擎自身的记账事件
+fn recent_events(game_state: &GameState) -> Vec<String> {
+    let mut events = game_state
+        .event_history
+        .iter()
+        .rev()
+        .filter(|event| !BOOKKEEPING_EVENTS.contains(&event.event_type.as_ref()))
+        .take(MAX_RECENT_EVENTS)
+        .map(|event| event.description.to_string())
+        .collect::<Vec<String>>();
+    events.reverse();
+    events
+}
+
+fn estimated_tokens(context: &PromptContext) -> u32 {
+    let fields = [
+        &context.scene,
+        &context.location,
+        &context.environment,
+        &context.actor_name,
+        &context.actor_realm,
+        &context.player_persona,
+        &context.story_beat,
+        &context.chapter_beat,
+        &context.world_setting_summary,
+    ];
+    let lists = [
+        &context.canon_facts,
+        &context.active_quests,
+        &context.relationships,
+        &context.status_effects,
+        &context.history_events,
+    ];
+    fields
+        .into_iter()
+        .flatten()
+        .chain(lists.into_iter().flatten())
+        .map(|text| estimate_token_count(text))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event_log::{EventImportance, GameEvent};
+    use crate::game_engine::GameEngine;
+    use crate::models::CultivationRealm;
+    use crate::script::{Climate, Location, LocationKind};
+    use crate::script_manager::ScriptManager;
+    use std::sync::Arc;
+
+    fn running_engine() -> GameEngine {
+        let mut script = ScriptManager::new().blank_script();
+        script
+            .world_setting
+            .cultivation_realms
+            .push(CultivationRealm::new("练气".to_string(), 1, 0, 1.0));
+        script.world_setting.locations.push(Location {
+            id: "cave".to_string(),
+            name: "洞府".to_string(),
+            description: String::new(),
+            spiritual_energy: 1.0,
+            kind: LocationKind::Wilderness,
+            climate: Climate::Temperate,
+        });
+        script.initial_state.starting_location = "cave".to_string();
+        let mut engine = GameEngine::new();
+        engine.initialize_game(script).unwrap();
+        engine.initialize_plot().unwrap();
+        engine
+    }
+
+    fn event(id: u64, event_type: &str, description: &str) -> GameEvent {
+        GameEvent {
+            id,
+            timestamp: id,
+            event_type: Arc::from(event_type),
+            description: Arc::from(description),
+            importance: EventImportance::Normal,
+            repeat_count: 1,
+            last_timestamp: None,
+        }
+    }
+
+    #[test]
+    fn test_assembles_full_state_and_drops_oldest_events_over_budget() {
+        let engine = running_engine();
+        let mut game_state = engine.get_current_state().unwrap();
+        let plot_state = engine.get_plot_state().unwrap();
+        game_state.event_history = (1..=12)
+            .map(|id| event(id, "player_action", &format!("第{}件事", id)))
+            .chain([event(13, "memory_decay", "记忆淡去")])
+            .collect();
+
+        let context = ContextAssembler::new()
+            .with_relationships(vec!["张长老对你颇为赏识".to_string()])
+            .assemble(&game_state, &plot_state);
+        assert_eq!(
+            context.actor_name.as_deref(),
+            Some(game_state.player.name.as_str())
+        );
+        assert_eq!(
+            context.actor_combat_power,
+            Some(game_state.player.stats.combat_power)
+        );
+        assert_eq!(
+            context.relationships,
+            vec!["张长老对你颇为赏识".to_string()]
+        );
+        assert_eq!(
+            context.environment,
+            Some(WorldClock::for_player(&game_state).prompt_line())
+        );
+        assert_eq!(context.history_events.len(), MAX_RECENT_EVENTS);
+        assert_eq!(context.history_events.last().unwrap(), "第12件事");
+        assert!(context
+            .world_setting_summary
+            .as_deref()
+            .unwrap()
+            .starts_with(&character_sheet_line(&game_state)));
+
+        let tight = ContextAssembler::new()
+            .with_relationships(vec!["张长老对你颇为赏识".to_string()])
+            .with_token_limit(estimated_tokens(&context) - 2);
+        let trimmed = tight.assemble(&game_state, &plot_state);
+        assert!(trimmed.history_events.len() < MAX_RECENT_EVENTS);
+        assert_eq!(trimmed.history_events.last().unwrap(), "第12件事");
+        assert_eq!(trimmed.relationships, context.relationships);
+        assert!(estimated_tokens(&trimmed) <= estimated_tokens(&context) - 2);
+    }
+}
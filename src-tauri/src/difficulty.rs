@@ -0,0 +1,170 @@
+use crate::loot::DropTable;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// 突破成功率修正的上下限
+const MAX_BREAKTHROUGH_MODIFIER: f32 = 0.5;
+const MIN_LIFESPAN_PRESSURE: f32 = 0.5;
+const MAX_LIFESPAN_PRESSURE: f32 = 3.0;
+/// 资源最稀缺时修炼收益保留的比例
+const SCARCE_YIELD_FLOOR: f32 = 0.5;
+
+/// 对局难度，剧本可给出默认值，开局后可随时调整，随对局存档保存
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct DifficultySettings {
+    /// 突破成功率的加减，范围 -0.5 到 0.5
+    pub breakthrough_modifier: f32,
+    /// 资源稀缺度，范围 0 到 1：越高修炼所得越少、战利品越常落空
+    pub resource_scarcity: f32,
+    /// 寿元压力，范围 0.5 到 3：岁月流逝时消耗寿元的倍数
+    pub lifespan_pressure: f32,
+    /// NPC 侵略性，范围 0 到 1：越高 NPC 越容易对事件出手干预
+    pub npc_aggression: f32,
+}
+
+impl Default for DifficultySettings {
+    fn default() -> Self {
+        Self::normal()
+    }
+}
+
+impl DifficultySettings {
+    pub fn easy() -> Self {
+        Self {
+            breakthrough_modifier: 0.15,
+            resource_scarcity: 0.0,
+            lifespan_pressure: 0.75,
+            npc_aggression: 0.25,
+        }
+    }
+
+    pub fn normal() -> Self {
+        Self {
+            breakthrough_modifier: 0.0,
+            resource_scarcity: 0.0,
+            lifespan_pressure: 1.0,
+            npc_aggression: 0.5,
+        }
+    }
+
+    pub fn hard() -> Self {
+        Self {
+            breakthrough_modifier: -0.15,
+            resource_scarcity: 0.5,
+            lifespan_pressure: 1.5,
+            npc_aggression: 0.8,
+        }
+    }
+
+    /// 校验各项是否在允许范围内
+    pub fn validate(&self) -> Result<(), String> {
+        for (value, min, max, name) in [
+            (
+                self.breakthrough_modifier,
+                -MAX_BREAKTHROUGH_MODIFIER,
+                MAX_BREAKTHROUGH_MODIFIER,
+                "突破修正",
+            ),
+            (self.resource_scarcity, 0.0, 1.0, "资源稀缺度"),
+            (
+                self.lifespan_pressure,
+                MIN_LIFESPAN_PRESSURE,
+                MAX_LIFESPAN_PRESSURE,
+                "寿元压力",
+            ),
+            (self.npc_aggression, 0.0, 1.0, "NPC 侵略性"),
+        ] {
+            if !value.is_finite() || value < min || value > max {
+                return Err(format!("{}须在 {} 到 {} 之间，当前为 {}", name, min, max, value));
+            }
+        }
+        Ok(())
+    }
+
+    /// 修炼收益的倍数，资源越稀缺越低
+    pub fn yield_multiplier(&self) -> f32 {
+        1.0 - (1.0 - SCARCE_YIELD_FLOOR) * self.resource_scarcity.clamp(0.0, 1.0)
+    }
+
+    /// 按寿元压力折算实际消耗的寿元年数
+    pub fn aging_years(&self, years: u32) -> u32 {
+        (years as f32 * self.lifespan_pressure).round() as u32
+    }
+
+    /// 按资源稀缺度加重落空权重后的掉落表，稀缺度为 1 时落空权重增加全部物品权重之和
+    pub fn scarce_table(&self, table: &DropTable) -> DropTable {
+        let entry_weight: u32 = table.entries.iter().map(|entry| entry.weight).sum();
+        let extra = (entry_weight as f32 * self.resource_scarcity.clamp(0.0, 1.0)).round() as u32;
+        DropTable {
+            empty_weight: table.empty_weight.saturating_add(extra),
+            ..table.clone()
+        }
+    }
+
+    /// 写入事件日志的单行说明
+    pub fn summary(&self) -> String {
+        format!(
+            "难度：突破修正 {:+.2}，资源稀缺度 {:.2}，寿元压力 {:.2}，NPC 侵略性 {:.2}",
+            self.breakthrough_modifier,
+            self.resource_scarcity,
+            self.lifespan_pressure,
+            self.npc_aggression
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_state::ItemType;
+    use crate::loot::{DropEntry, DropRarity, DropSource};
+
+    #[test]
+    fn test_validate_rejects_out_of_range_values() {
+        assert!(DifficultySettings::easy().validate().is_ok());
+        assert!(DifficultySettings::hard().validate().is_ok());
+        let invalid = DifficultySettings {
+            lifespan_pressure: 0.0,
+            ..DifficultySettings::default()
+        };
+        assert!(invalid.validate().unwrap_err().contains("寿元压力"));
+        let nan = DifficultySettings {
+            npc_aggression: f32::NAN,
+            ..DifficultySettings::default()
+        };
+        assert!(nan.validate().is_err());
+
+        let legacy: DifficultySettings = serde_json::from_str("{}").unwrap();
+        assert_eq!(legacy, DifficultySettings::normal());
+    }
+
+    #[test]
+    fn test_scarcity_and_pressure_scale_resources() {
+        let hard = DifficultySettings::hard();
+        assert_eq!(hard.yield_multiplier(), 0.75);
+        assert_eq!(hard.aging_years(10), 15);
+        assert_eq!(DifficultySettings::default().aging_years(10), 10);
+
+        let table = DropTable {
+            id: "herbs".to_string(),
+            source: DropSource::Location {
+                location_id: "forest".to_string(),
+            },
+            rolls: 1,
+            empty_weight: 2,
+            entries: vec![DropEntry {
+                item_id: "herb".to_string(),
+                name: "灵草".to_string(),
+                description: String::new(),
+                item_type: ItemType::Material,
+                weight: 8,
+                rarity: DropRarity::Common,
+                lifespan_bonus: 0,
+            }],
+            pity_threshold: None,
+        };
+        assert_eq!(hard.scarce_table(&table).empty_weight, 6);
+        assert_eq!(DifficultySettings::default().scarce_table(&table), table);
+    }
+}
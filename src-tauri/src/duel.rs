@@ -0,0 +1,483 @@
+use crate::game_state::{Character, Item, ItemType, WorldState};
+use crate::models::CharacterStats;
+use crate::npc::{PersonalityTrait, SecretKind, NPC};
+use crate::numerical_system::Action;
+use crate::plot_engine::{new_option_uid, PlayerOption};
+use crate::script::Faction;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// 宿怨值达到该值的 NPC 才会下战帖
+pub const RIVALRY_THRESHOLD: f32 = 0.55;
+/// 决斗选项的标记，前端据此高亮显示
+pub const DUEL_REQUIREMENT: &str = "决斗邀约";
+const CHALLENGE_COOLDOWN_DAYS: u32 = 10;
+const CHALLENGE_EXPIRY_DAYS: u32 = 3;
+const MAX_DUEL_RECORDS: usize = 30;
+const DECLINE_REPUTATION_PENALTY: i32 = -2;
+const CONTESTED_GOAL_KEYWORDS: &[&str] = &[
+    "宗门", "大比", "首席", "秘境", "机缘", "传承", "压过", "sect", "tournament", "inheritance",
+];
+
+/// 决斗的赌注
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum DuelStake {
+    Face,
+    Resources,
+    SectStanding,
+}
+
+impl DuelStake {
+    pub fn label(self) -> &'static str {
+        match self {
+            DuelStake::Face => "颜面",
+            DuelStake::Resources => "资源",
+            DuelStake::SectStanding => "宗门地位",
+        }
+    }
+
+    /// 胜负对势力声望的影响幅度
+    fn reputation_swing(self) -> i32 {
+        match self {
+            DuelStake::Face => 3,
+            DuelStake::Resources => 1,
+            DuelStake::SectStanding => 10,
+        }
+    }
+
+    /// 按挑战者性格选择赌注，无明显倾向时按日期轮换
+    fn for_npc(npc: &NPC, day: u32) -> Self {
+        let traits = &npc.personality.traits;
+        if traits.contains(&PersonalityTrait::Ambitious) {
+            DuelStake::SectStanding
+        } else if traits.contains(&PersonalityTrait::Aggressive) {
+            DuelStake::Face
+        } else if traits.contains(&PersonalityTrait::Scheming) {
+            DuelStake::Resources
+        } else {
+            [DuelStake::Face, DuelStake::Resources, DuelStake::SectStanding][day as usize % 3]
+        }
+    }
+}
+
+/// 宿敌发出的战帖，记录下帖时挑战者的属性供战斗结算使用
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct DuelChallenge {
+    pub id: u32,
+    pub challenger_id: String,
+    pub challenger_name: String,
+    pub challenger_stats: CharacterStats,
+    pub stake: DuelStake,
+    pub rivalry: f32,
+    pub issued_day: u32,
+    pub faction_id: Option<String>,
+}
+
+impl DuelChallenge {
+    pub fn is_expired(&self, current_day: u32) -> bool {
+        current_day > self.issued_day.saturating_add(CHALLENGE_EXPIRY_DAYS)
+    }
+
+    /// 呈现给玩家的应战选项
+    pub fn option(&self, id: usize) -> PlayerOption {
+        PlayerOption {
+            id,
+            uid: new_option_uid(),
+            description: format!(
+                "应下{}的战帖（赌注：{}）",
+                self.challenger_name,
+                self.stake.label()
+            ),
+            requirements: vec![DUEL_REQUIREMENT.to_string()],
+            action: Action::Combat {
+                target_id: self.challenger_id.clone(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum DuelResult {
+    Won,
+    Lost,
+    Declined,
+}
+
+/// 一场决斗的结算结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct DuelOutcome {
+    pub challenge_id: u32,
+    pub challenger_id: String,
+    pub challenger_name: String,
+    pub stake: DuelStake,
+    pub result: DuelResult,
+    pub day: u32,
+    pub faction_id: Option<String>,
+    pub reputation_change: i32,
+    /// 挑战者对玩家好感的变化
+    pub affinity_change: i32,
+    pub description: String,
+}
+
+/// 待应战的战帖与历史战绩，随世界状态保存
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct DuelBoard {
+    pub pending: Vec<DuelChallenge>,
+    pub last_challenged_day: HashMap<String, u32>,
+    pub records: Vec<DuelOutcome>,
+    pub next_id: u32,
+}
+
+impl DuelBoard {
+    /// 同一 NPC 没有未决战帖且已过冷却期时可再次下帖
+    pub fn can_challenge(&self, npc_id: &str, current_day: u32) -> bool {
+        !self.pending.iter().any(|c| c.challenger_id == npc_id)
+            && self
+                .last_challenged_day
+                .get(npc_id)
+                .is_none_or(|day| current_day >= day.saturating_add(CHALLENGE_COOLDOWN_DAYS))
+    }
+
+    pub fn issue(&mut self, mut challenge: DuelChallenge) -> DuelChallenge {
+        self.next_id = self.next_id.saturating_add(1);
+        challenge.id = self.next_id;
+        self.last_challenged_day
+            .insert(challenge.challenger_id.clone(), challenge.issued_day);
+        self.pending.push(challenge.clone());
+        challenge
+    }
+
+    pub fn take_pending(&mut self, npc_id: &str) -> Option<DuelChallenge> {
+        let idx = self.pending.iter().position(|c| c.challenger_id == npc_id)?;
+        Some(self.pending.remove(idx))
+    }
+
+    /// 移除过期未应的战帖
+    pub fn take_expired(&mut self, current_day: u32) -> Vec<DuelChallenge> {
+        let (expired, pending) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|c| c.is_expired(current_day));
+        self.pending = pending;
+        expired
+    }
+
+    fn record(&mut self, outcome: DuelOutcome) {
+        self.records.push(outcome);
+        if self.records.len() > MAX_DUEL_RECORDS {
+            let overflow = self.records.len() - MAX_DUEL_RECORDS;
+            self.records.drain(0..overflow);
+        }
+    }
+}
+
+/// NPC 对玩家的宿怨值（0-1）：好感越低、战力越接近、目标越冲突越高
+pub fn rivalry_score(npc: &NPC, player: &Character) -> f32 {
+    let hostility = npc
+        .relationships
+        .get(&player.id)
+        .map(|r| (-r.affinity).clamp(0, 100) as f32 / 100.0)
+        .unwrap_or(0.0);
+
+    let (npc_power, player_power) = (npc.stats.combat_power, player.stats.combat_power);
+    let parity = match npc_power.max(player_power) {
+        0 => 1.0,
+        max => npc_power.min(player_power) as f32 / max as f32,
+    };
+
+    let contested = npc.personality.goals.iter().any(|goal| {
+        let lower = goal.description.to_lowercase();
+        CONTESTED_GOAL_KEYWORDS.iter().any(|k| lower.contains(k))
+    });
+
+    hostility * 0.5 + parity * 0.3 + if contested { 0.2 } else { 0.0 }
+}
+
+/// 宿怨值达标时生成战帖，声望记在挑战者已揭露的效忠势力或首个势力名下
+pub fn challenge_from(
+    npc: &NPC,
+    player: &Character,
+    factions: &[Faction],
+    current_day: u32,
+) -> Option<DuelChallenge> {
+    let rivalry = rivalry_score(npc, player);
+    if rivalry < RIVALRY_THRESHOLD {
+        return None;
+    }
+
+    let allegiance = npc.revealed_secrets().into_iter().find_map(|secret| match &secret.kind {
+        SecretKind::HiddenAllegiance(name) => factions
+            .iter()
+            .find(|f| &f.name == name || &f.id == name)
+            .map(|f| f.id.clone()),
+        SecretKind::HiddenRealm(_) => None,
+    });
+
+    Some(DuelChallenge {
+        id: 0,
+        challenger_id: npc.id.clone(),
+        challenger_name: npc.name.clone(),
+        challenger_stats: npc.stats.clone(),
+        stake: DuelStake::for_npc(npc, current_day),
+        rivalry,
+        issued_day: current_day,
+        faction_id: allegiance.or_else(|| factions.first().map(|f| f.id.clone())),
+    })
+}
+
+/// 结算应战结果：调整势力声望、转移资源赌注并记入战绩
+pub fn settle_duel(
+    challenge: &DuelChallenge,
+    player_won: bool,
+    player: &mut Character,
+    world_state: &mut WorldState,
+    current_day: u32,
+) -> DuelOutcome {
+    let swing = challenge.stake.reputation_swing();
+    let reputation_change = if player_won { swing } else { -swing };
+    let name = &challenge.challenger_name;
+
+    let mut description = if player_won {
+        format!("你在与{}的决斗中获胜，赢下了{}之争。", name, challenge.stake.label())
+    } else {
+        format!("你败给了{}，输掉了{}之争。", name, challenge.stake.label())
+    };
+
+    if challenge.stake == DuelStake::Resources {
+        if player_won {
+            player.inventory.push(Item {
+                id: format!("duel_stake_{}", challenge.id),
+                name: format!("{}的赌注", name),
+                description: format!("决斗中从{}手中赢得的修炼资源。", name),
+                item_type: ItemType::Material,
+                lifespan_bonus: 0,
+            });
+            description.push_str(&format!("你收下了{}的赌注。", name));
+        } else if let Some(idx) = player
+            .inventory
+            .iter()
+            .rposition(|item| item.item_type == ItemType::Material)
+        {
+            let lost = player.inventory.remove(idx);
+            description.push_str(&format!("{}被对方取走。", lost.name));
+        }
+    }
+
+    let outcome = DuelOutcome {
+        challenge_id: challenge.id,
+        challenger_id: challenge.challenger_id.clone(),
+        challenger_name: name.clone(),
+        stake: challenge.stake,
+        result: if player_won {
+            DuelResult::Won
+        } else {
+            DuelResult::Lost
+        },
+        day: current_day,
+        faction_id: challenge.faction_id.clone(),
+        reputation_change,
+        // 正面交手后胜者赢得尊重，败者更添怨气。
+        affinity_change: if player_won { 5 } else { -5 },
+        description,
+    };
+    apply_reputation(world_state, &outcome);
+    world_state.duel_board.record(outcome.clone());
+    outcome
+}
+
+/// 战帖过期未应视为怯战，折损颜面
+pub fn settle_declined(
+    challenge: &DuelChallenge,
+    world_state: &mut WorldState,
+    current_day: u32,
+) -> DuelOutcome {
+    let outcome = DuelOutcome {
+        challenge_id: challenge.id,
+        challenger_id: challenge.challenger_id.clone(),
+        challenger_name: challenge.challenger_name.clone(),
+        stake: challenge.stake,
+        result: DuelResult::Declined,
+        day: current_day,
+        faction_id: challenge.faction_id.clone(),
+        reputation_change: DECLINE_REPUTATION_PENALTY,
+        affinity_change: -3,
+        description: format!("你未应{}的战帖，被人讥为怯战。", challenge.challenger_name),
+    };
+    apply_reputation(world_state, &outcome);
+    world_state.duel_board.record(outcome.clone());
+    outcome
+}
+
+fn apply_reputation(world_state: &mut WorldState, outcome: &DuelOutcome) {
+    if let Some(faction_id) = &outcome.faction_id {
+        let reputation = world_state
+            .faction_reputation
+            .entry(faction_id.clone())
+            .or_insert(0);
+        *reputation = (*reputation + outcome.reputation_change).clamp(-100, 100);
+    }
+}
+
+/// 用当前待应战的战帖替换选项列表中的决斗选项
+pub fn attach_duel_options(options: &mut Vec<PlayerOption>, pending: &[DuelChallenge]) {
+    options.retain(|option| !option.requirements.iter().any(|r| r == DUEL_REQUIREMENT));
+    for challenge in pending {
+        options.push(challenge.option(options.len()));
+    }
+    for (idx, option) in options.iter_mut().enumerate() {
+        option.id = idx;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CultivationRealm, Element, Grade, Lifespan, SpiritualRoot};
+    use crate::npc::{EmotionalState, Goal, NPCMemory, Personality, Relationship};
+
+    fn stats(combat_power: u64) -> CharacterStats {
+        CharacterStats {
+            spiritual_root: SpiritualRoot {
+                element: Element::Fire,
+                grade: Grade::double(),
+                affinity: 0.6,
+                tier_multiplier: None,
+            },
+            cultivation_realm: CultivationRealm::new("练气".to_string(), 1, 0, 1.0),
+            techniques: Vec::new(),
+            status_effects: Default::default(),
+            insight: 0,
+            lifespan: Lifespan {
+                current_age: 18,
+                max_age: 120,
+                realm_bonus: 0,
+            },
+            combat_power,
+        }
+    }
+
+    fn player() -> Character {
+        Character::new("player".to_string(), "林逸".to_string(), stats(100), "qingyun".to_string())
+    }
+
+    fn rival(affinity: i32, combat_power: u64) -> NPC {
+        let mut relationships = HashMap::new();
+        relationships.insert(
+            "player".to_string(),
+            Relationship {
+                target_id: "player".to_string(),
+                affinity,
+                trust: -20,
+                history: Vec::new(),
+            },
+        );
+        NPC {
+            id: "rival".to_string(),
+            name: "赵烈".to_string(),
+            stats: stats(combat_power),
+            personality: Personality {
+                traits: vec![PersonalityTrait::Ambitious],
+                goals: vec![Goal {
+                    description: "成为宗门首席弟子".to_string(),
+                    priority: 9,
+                }],
+                values: Vec::new(),
+            },
+            memory: NPCMemory::default(),
+            relationships,
+            secrets: Vec::new(),
+            location: None,
+            bio: String::new(),
+            emotions: EmotionalState::default(),
+        }
+    }
+
+    fn sect() -> Vec<Faction> {
+        vec![Faction {
+            id: "qingyun".to_string(),
+            name: "青云宗".to_string(),
+            description: String::new(),
+            power_level: 5,
+        }]
+    }
+
+    #[test]
+    fn test_rivalry_requires_hostility_and_parity() {
+        let player = player();
+        let hostile_peer = rival(-60, 110);
+        let friendly_peer = rival(40, 110);
+        let hostile_elder = rival(-60, 2000);
+
+        assert!(rivalry_score(&hostile_peer, &player) >= RIVALRY_THRESHOLD);
+        assert!(rivalry_score(&friendly_peer, &player) < RIVALRY_THRESHOLD);
+        assert!(rivalry_score(&hostile_elder, &player) < RIVALRY_THRESHOLD);
+        assert!(challenge_from(&friendly_peer, &player, &sect(), 1).is_none());
+
+        let challenge = challenge_from(&hostile_peer, &player, &sect(), 1).unwrap();
+        assert_eq!(challenge.stake, DuelStake::SectStanding);
+        assert_eq!(challenge.faction_id.as_deref(), Some("qingyun"));
+    }
+
+    #[test]
+    fn test_board_cooldown_and_expiry() {
+        let mut board = DuelBoard::default();
+        let challenge = challenge_from(&rival(-60, 110), &player(), &sect(), 5).unwrap();
+        assert!(board.can_challenge("rival", 5));
+
+        let issued = board.issue(challenge);
+        assert_eq!(issued.id, 1);
+        assert!(!board.can_challenge("rival", 6));
+        assert!(board.take_expired(8).is_empty());
+        assert_eq!(board.take_expired(9).len(), 1);
+        assert!(!board.can_challenge("rival", 14));
+        assert!(board.can_challenge("rival", 15));
+    }
+
+    #[test]
+    fn test_settle_duel_moves_reputation_and_resources() {
+        let mut player = player();
+        let mut world_state = WorldState::new();
+        let mut challenge = challenge_from(&rival(-60, 110), &player, &sect(), 1).unwrap();
+
+        let won = settle_duel(&challenge, true, &mut player, &mut world_state, 2);
+        assert_eq!(won.result, DuelResult::Won);
+        assert_eq!(world_state.faction_reputation["qingyun"], 10);
+
+        challenge.stake = DuelStake::Resources;
+        settle_duel(&challenge, true, &mut player, &mut world_state, 3);
+        assert_eq!(player.inventory.len(), 1);
+        let lost = settle_duel(&challenge, false, &mut player, &mut world_state, 4);
+        assert!(player.inventory.is_empty());
+        assert_eq!(lost.affinity_change, -5);
+
+        settle_declined(&challenge, &mut world_state, 5);
+        assert_eq!(world_state.faction_reputation["qingyun"], 8);
+        assert_eq!(world_state.duel_board.records.len(), 4);
+    }
+
+    #[test]
+    fn test_attach_duel_options_replaces_stale_entries() {
+        let challenge = challenge_from(&rival(-60, 110), &player(), &sect(), 1).unwrap();
+        let mut options = vec![PlayerOption {
+            id: 0,
+            uid: new_option_uid(),
+            description: "打坐修炼".to_string(),
+            requirements: vec![],
+            action: Action::Cultivate,
+        }];
+
+        attach_duel_options(&mut options, std::slice::from_ref(&challenge));
+        attach_duel_options(&mut options, std::slice::from_ref(&challenge));
+        assert_eq!(options.len(), 2);
+        assert_eq!(options[1].id, 1);
+        assert_eq!(
+            options[1].action,
+            Action::Combat {
+                target_id: "rival".to_string()
+            }
+        );
+
+        attach_duel_options(&mut options, &[]);
+        assert_eq!(options.len(), 1);
+    }
+}
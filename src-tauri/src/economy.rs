@@ -0,0 +1,245 @@
+use crate::numerical_system::StatChange;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// 货币与修炼资源的种类，灵石同时是交易用的货币
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ResourceKind {
+    SpiritStones,
+    Herbs,
+    Ores,
+}
+
+impl ResourceKind {
+    pub const ALL: [ResourceKind; 3] = [
+        ResourceKind::SpiritStones,
+        ResourceKind::Herbs,
+        ResourceKind::Ores,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ResourceKind::SpiritStones => "灵石",
+            ResourceKind::Herbs => "灵草",
+            ResourceKind::Ores => "矿石",
+        }
+    }
+
+    /// 写入 `StatChange` 的属性名
+    pub fn stat_name(self) -> &'static str {
+        match self {
+            ResourceKind::SpiritStones => "spirit_stones",
+            ResourceKind::Herbs => "herbs",
+            ResourceKind::Ores => "ores",
+        }
+    }
+}
+
+/// 一次结算的资源增减，负数为花费
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct ResourceDelta {
+    pub spirit_stones: i64,
+    pub herbs: i64,
+    pub ores: i64,
+}
+
+impl ResourceDelta {
+    pub fn of(kind: ResourceKind, amount: i64) -> Self {
+        let mut delta = Self::default();
+        *delta.slot(kind) = amount;
+        delta
+    }
+
+    pub fn get(&self, kind: ResourceKind) -> i64 {
+        match kind {
+            ResourceKind::SpiritStones => self.spirit_stones,
+            ResourceKind::Herbs => self.herbs,
+            ResourceKind::Ores => self.ores,
+        }
+    }
+
+    fn slot(&mut self, kind: ResourceKind) -> &mut i64 {
+        match kind {
+            ResourceKind::SpiritStones => &mut self.spirit_stones,
+            ResourceKind::Herbs => &mut self.herbs,
+            ResourceKind::Ores => &mut self.ores,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        ResourceKind::ALL.iter().all(|kind| self.get(*kind) == 0)
+    }
+
+    pub fn plus(mut self, other: ResourceDelta) -> Self {
+        for kind in ResourceKind::ALL {
+            *self.slot(kind) = self.get(kind).saturating_add(other.get(kind));
+        }
+        self
+    }
+
+    /// 花费部分的说明，如“灵石 40、灵草 1”；没有花费时为空
+    pub fn cost_label(&self) -> String {
+        ResourceKind::ALL
+            .iter()
+            .filter(|kind| self.get(**kind) < 0)
+            .map(|kind| format!("{} {}", kind.label(), self.get(*kind).unsigned_abs()))
+            .collect::<Vec<String>>()
+            .join("、")
+    }
+}
+
+/// 玩家持有的灵石与修炼资源
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct Resources {
+    pub spirit_stones: u64,
+    pub herbs: u64,
+    pub ores: u64,
+}
+
+impl Resources {
+    pub fn get(&self, kind: ResourceKind) -> u64 {
+        match kind {
+            ResourceKind::SpiritStones => self.spirit_stones,
+            ResourceKind::Herbs => self.herbs,
+            ResourceKind::Ores => self.ores,
+        }
+    }
+
+    fn slot(&mut self, kind: ResourceKind) -> &mut u64 {
+        match kind {
+            ResourceKind::SpiritStones => &mut self.spirit_stones,
+            ResourceKind::Herbs => &mut self.herbs,
+            ResourceKind::Ores => &mut self.ores,
+        }
+    }
+
+    /// 付不起时返回缺少的资源说明
+    pub fn check_affordable(&self, delta: &ResourceDelta) -> Result<(), String> {
+        let missing = ResourceKind::ALL
+            .iter()
+            .filter(|kind| {
+                delta.get(**kind) < 0 && self.get(**kind) < delta.get(**kind).unsigned_abs()
+            })
+            .map(|kind| {
+                format!(
+                    "{}需要 {}，现有 {}",
+                    kind.label(),
+                    delta.get(*kind).unsigned_abs(),
+                    self.get(*kind)
+                )
+            })
+            .collect::<Vec<String>>();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("资源不足：{}", missing.join("；")))
+        }
+    }
+
+    /// 按增减结算并返回各项变化；付不起时不做任何改动
+    pub fn apply(&mut self, delta: &ResourceDelta) -> Result<Vec<StatChange>, String> {
+        self.check_affordable(delta)?;
+        let mut changes = Vec::new();
+        for kind in ResourceKind::ALL {
+            let amount = delta.get(kind);
+            if amount == 0 {
+                continue;
+            }
+            let old = self.get(kind);
+            let new = if amount > 0 {
+                old.saturating_add(amount.unsigned_abs())
+            } else {
+                old - amount.unsigned_abs()
+            };
+            *self.slot(kind) = new;
+            changes.push(StatChange {
+                stat_name: kind.stat_name().to_string(),
+                old_value: old.to_string(),
+                new_value: new.to_string(),
+            });
+        }
+        Ok(changes)
+    }
+}
+
+/// 剧本的经济设定：开局资源、花费与收益的基数、市场基准价
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct EconomyConfig {
+    pub starting_resources: Resources,
+    /// 突破一层花费的灵石，乘以大境界序号与目标层数
+    pub breakthrough_stones: u64,
+    /// 冲击下一大境界时额外消耗的灵草，乘以大境界序号
+    pub tribulation_herbs: u64,
+    /// 功法售价，乘以功法要求的境界
+    pub technique_price_per_level: u64,
+    /// 战斗所得灵石，乘以大境界序号
+    pub combat_reward_stones: u64,
+    /// 一份灵草与一份矿石的市场基准价（灵石）
+    pub herb_price: u64,
+    pub ore_price: u64,
+}
+
+impl Default for EconomyConfig {
+    fn default() -> Self {
+        Self {
+            starting_resources: Resources {
+                spirit_stones: 100,
+                herbs: 3,
+                ores: 0,
+            },
+            breakthrough_stones: 10,
+            tribulation_herbs: 1,
+            technique_price_per_level: 30,
+            combat_reward_stones: 15,
+            herb_price: 5,
+            ore_price: 8,
+        }
+    }
+}
+
+impl EconomyConfig {
+    /// 资源的市场基准价，灵石本身为 1
+    pub fn base_price(&self, kind: ResourceKind) -> u64 {
+        match kind {
+            ResourceKind::SpiritStones => 1,
+            ResourceKind::Herbs => self.herb_price,
+            ResourceKind::Ores => self.ore_price,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_records_changes_and_rejects_unaffordable_costs() {
+        let mut resources = EconomyConfig::default().starting_resources;
+        let cost = ResourceDelta {
+            spirit_stones: -40,
+            herbs: -1,
+            ores: 0,
+        };
+        let changes = resources.apply(&cost).unwrap();
+        assert_eq!(resources.spirit_stones, 60);
+        assert_eq!(resources.herbs, 2);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].stat_name, "spirit_stones");
+        assert_eq!(changes[0].new_value, "60");
+        assert_eq!(cost.cost_label(), "灵石 40、灵草 1");
+
+        let expensive = ResourceDelta::of(ResourceKind::Ores, -2);
+        let error = resources.apply(&expensive).unwrap_err();
+        assert!(error.contains("矿石需要 2，现有 0"));
+        assert_eq!(resources.spirit_stones, 60);
+
+        resources
+            .apply(&ResourceDelta::of(ResourceKind::Ores, 5).plus(expensive))
+            .unwrap();
+        assert_eq!(resources.ores, 3);
+    }
+}
@@ -0,0 +1,589 @@
+use crate::combat_engine::CombatStatus;
+use crate::duel::DuelResult;
+use crate::event_log::EventImportance;
+use crate::formula::{Formula, FormulaError};
+use crate::game_state::GameState;
+use crate::llm_service::{LLMRequest, LLMService, LLMSubsystem};
+use crate::npc::NPC;
+use crate::numerical_system::RealmLadder;
+use crate::prompt_builder::{PromptBuilder, PromptConstraints, PromptContext, PromptTemplate};
+use crate::quest_system::QuestStatus;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// 没有剧本结局命中时使用的结局 id
+pub const DEFAULT_ENDING_ID: &str = "default";
+/// 结局条件可直接引用的变量
+pub const ENDING_VARIABLES: &[&str] = &[
+    "realm_level",
+    "realm_sub_level",
+    "combat_power",
+    "age",
+    "max_age",
+    "total_days",
+    "techniques",
+    "items",
+    "quests_completed",
+    "duels_won",
+    "duels_lost",
+    "died",
+    "lifespan_exhausted",
+    "ascended",
+];
+/// 按 id 展开的变量前缀：势力声望、NPC 好感与信任、已发生的世界事件
+pub const ENDING_VARIABLE_PREFIXES: &[&str] = &["rep_", "affinity_", "trust_", "event_"];
+const FINALE_HISTORY_EVENTS: usize = 8;
+/// 结局总结中列出的重要事件条数
+const SUMMARY_HIGHLIGHTS: usize = 10;
+
+/// 剧本定义的结局，条件为沙箱公式，结果非零即视为达成
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct EndingDefinition {
+    pub id: String,
+    pub title: String,
+    pub description: String,
+    /// 为空时总是达成，可作为兜底结局
+    #[serde(default)]
+    pub condition: String,
+    /// 同时达成多个结局时取优先级最高者，相同时取先定义者
+    #[serde(default)]
+    pub priority: i32,
+    /// 剧本目标：条件达成时自动结束本局；条件为空的结局不会自动触发
+    #[serde(default)]
+    pub ends_playthrough: bool,
+}
+
+/// 本局自动结束的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EndingCause {
+    /// 永久死亡规则下战败身死
+    Death,
+    LifespanExhausted,
+    /// 修至剧本最高境界圆满
+    Ascension,
+    GoalsCompleted,
+}
+
+impl EndingCause {
+    pub fn label(self) -> &'static str {
+        match self {
+            EndingCause::Death => "战败身死",
+            EndingCause::LifespanExhausted => "寿元耗尽",
+            EndingCause::Ascension => "飞升",
+            EndingCause::GoalsCompleted => "达成剧本目标",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EndingError {
+    DuplicateEnding(String),
+    InvalidCondition { ending_id: String, error: FormulaError },
+}
+
+impl fmt::Display for EndingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EndingError::DuplicateEnding(id) => write!(f, "duplicate ending '{id}'"),
+            EndingError::InvalidCondition { ending_id, error } => {
+                write!(f, "ending '{ending_id}' has an invalid condition: {error}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for EndingError {}
+
+pub fn validate_endings(endings: &[EndingDefinition]) -> Result<(), EndingError> {
+    let mut seen = HashSet::new();
+    for ending in endings {
+        if !seen.insert(ending.id.as_str()) {
+            return Err(EndingError::DuplicateEnding(ending.id.clone()));
+        }
+        compile_condition(&ending.condition).map_err(|error| EndingError::InvalidCondition {
+            ending_id: ending.id.clone(),
+            error,
+        })?;
+    }
+    Ok(())
+}
+
+fn compile_condition(condition: &str) -> Result<Option<Formula>, FormulaError> {
+    if condition.trim().is_empty() {
+        return Ok(None);
+    }
+    let formula = Formula::parse(condition)?;
+    if let Some(name) = formula.variables().into_iter().find(|name| {
+        !ENDING_VARIABLES.contains(&name.as_str())
+            && !ENDING_VARIABLE_PREFIXES
+                .iter()
+                .any(|prefix| name.len() > prefix.len() && name.starts_with(prefix))
+    }) {
+        return Err(FormulaError::UnknownVariable(name));
+    }
+    Ok(Some(formula))
+}
+
+/// 收集结局条件可用的变量；未出现的势力、NPC 与事件在求值时按 0 处理
+pub fn ending_variables<'a>(
+    state: &GameState,
+    npcs: impl Iterator<Item = &'a NPC>,
+) -> HashMap<String, f64> {
+    let stats = &state.player.stats;
+    let duels = &state.world_state.duel_board.records;
+    let mut variables = HashMap::from([
+        ("realm_level".to_string(), f64::from(stats.cultivation_realm.level)),
+        ("realm_sub_level".to_string(), f64::from(stats.cultivation_realm.sub_level)),
+        ("combat_power".to_string(), stats.combat_power as f64),
+        ("age".to_string(), f64::from(stats.lifespan.current_age)),
+        (
+            "max_age".to_string(),
+            f64::from(stats.lifespan.max_age + stats.lifespan.realm_bonus),
+        ),
+        ("total_days".to_string(), f64::from(state.game_time.total_days)),
+        ("techniques".to_string(), stats.techniques.len() as f64),
+        ("items".to_string(), state.player.inventory.len() as f64),
+        (
+            "quests_completed".to_string(),
+            state
+                .quests
+                .quests
+                .iter()
+                .filter(|quest| quest.status == QuestStatus::Completed)
+                .count() as f64,
+        ),
+        (
+            "duels_won".to_string(),
+            duels.iter().filter(|d| d.result == DuelResult::Won).count() as f64,
+        ),
+        (
+            "duels_lost".to_string(),
+            duels.iter().filter(|d| d.result == DuelResult::Lost).count() as f64,
+        ),
+        ("died".to_string(), flag(died(state))),
+        ("lifespan_exhausted".to_string(), flag(!stats.lifespan.is_alive())),
+        ("ascended".to_string(), flag(ascended(state))),
+    ]);
+    for (faction_id, reputation) in &state.world_state.faction_reputation {
+        variables.insert(format!("rep_{}", faction_id), f64::from(*reputation));
+    }
+    for npc in npcs {
+        if let Some(relationship) = npc.relationships.get(&state.player.id) {
+            variables.insert(format!("affinity_{}", npc.id), f64::from(relationship.affinity));
+            variables.insert(format!("trust_{}", npc.id), f64::from(relationship.trust));
+        }
+    }
+    for event in &state.world_state.global_events {
+        variables.insert(format!("event_{}", event.id), 1.0);
+    }
+    variables
+}
+
+fn flag(value: bool) -> f64 {
+    if value {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+fn died(state: &GameState) -> bool {
+    !state.house_rules.disable_permadeath
+        && state
+            .combat
+            .as_ref()
+            .is_some_and(|combat| combat.status == CombatStatus::Defeat)
+}
+
+fn ascended(state: &GameState) -> bool {
+    let realm = &state.player.stats.cultivation_realm;
+    let top_level = state
+        .script
+        .world_setting
+        .cultivation_realms
+        .iter()
+        .map(|realm| realm.level)
+        .max();
+    top_level.is_some_and(|level| {
+        realm.level >= level && RealmLadder::from_world(&state.script.world_setting).is_peak(realm)
+    })
+}
+
+/// 判断本局是否应当自动结束：续命房规下寿元耗尽不算结束；已有结局时不再触发
+pub fn detect_ending_cause(
+    state: &GameState,
+    endings: &[EndingDefinition],
+    variables: &HashMap<String, f64>,
+) -> Option<EndingCause> {
+    if state.ending.is_some() {
+        return None;
+    }
+    let is_set = |name: &str| variables.get(name).is_some_and(|value| *value != 0.0);
+    if is_set("died") {
+        Some(EndingCause::Death)
+    } else if is_set("lifespan_exhausted") && !state.house_rules.disable_permadeath {
+        Some(EndingCause::LifespanExhausted)
+    } else if is_set("ascended") {
+        Some(EndingCause::Ascension)
+    } else if endings.iter().any(|ending| {
+        ending.ends_playthrough
+            && !ending.condition.trim().is_empty()
+            && condition_met(&ending.condition, variables)
+    }) {
+        Some(EndingCause::GoalsCompleted)
+    } else {
+        None
+    }
+}
+
+/// 选出达成的结局中优先级最高者；条件无法求值的结局视为未达成，全部未达成时返回默认结局
+pub fn select_ending(
+    endings: &[EndingDefinition],
+    variables: &HashMap<String, f64>,
+) -> EndingDefinition {
+    let mut best: Option<&EndingDefinition> = None;
+    for ending in endings {
+        if !condition_met(&ending.condition, variables) {
+            continue;
+        }
+        if best.is_none_or(|current| ending.priority > current.priority) {
+            best = Some(ending);
+        }
+    }
+    best.cloned().unwrap_or_else(default_ending)
+}
+
+fn condition_met(condition: &str, variables: &HashMap<String, f64>) -> bool {
+    let formula = match compile_condition(condition) {
+        Ok(Some(formula)) => formula,
+        Ok(None) => return true,
+        Err(_) => return false,
+    };
+    let names = formula.variables();
+    let bound = names
+        .iter()
+        .map(|name| (name.as_str(), variables.get(name).copied().unwrap_or(0.0)))
+        .collect::<HashMap<&str, f64>>();
+    formula.evaluate(&bound).map(|value| value != 0.0).unwrap_or(false)
+}
+
+pub fn default_ending() -> EndingDefinition {
+    EndingDefinition {
+        id: DEFAULT_ENDING_ID.to_string(),
+        title: "道途未竟".to_string(),
+        description: "尘缘未了，大道犹远，这段修行就此告一段落。".to_string(),
+        condition: String::new(),
+        priority: i32::MIN,
+        ends_playthrough: false,
+    }
+}
+
+/// 本局达成的结局
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct AchievedEnding {
+    pub ending_id: String,
+    pub title: String,
+    pub finale: String,
+    pub day: u32,
+    /// 自动结束时的原因，玩家主动收场时为空
+    #[serde(default)]
+    pub cause: Option<EndingCause>,
+}
+
+/// 结局画面展示的本局总结
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EndingSummary {
+    pub ending: AchievedEnding,
+    pub script_name: String,
+    pub player_name: String,
+    pub realm: String,
+    pub age: u32,
+    pub total_days: u32,
+    pub play_time_secs: u64,
+    pub action_count: u32,
+    pub techniques: usize,
+    pub quests_completed: usize,
+    pub duels_won: usize,
+    pub duels_lost: usize,
+    /// 本局的重要事件，按发生先后排列
+    pub highlights: Vec<String>,
+}
+
+impl EndingSummary {
+    /// 本局尚未结束时返回 None
+    pub fn from_state(state: &GameState) -> Option<Self> {
+        let ending = state.ending.clone()?;
+        let stats = &state.player.stats;
+        let duels = &state.world_state.duel_board.records;
+        let important = state
+            .event_history
+            .iter()
+            .filter(|event| event.importance == EventImportance::Important)
+            .collect::<Vec<_>>();
+        Some(Self {
+            ending,
+            script_name: state.script.name.clone(),
+            player_name: state.player.name.clone(),
+            realm: stats.cultivation_realm.name.clone(),
+            age: stats.lifespan.current_age,
+            total_days: state.game_time.total_days,
+            play_time_secs: state.play_time_secs,
+            action_count: state.action_count,
+            techniques: stats.techniques.len(),
+            quests_completed: state
+                .quests
+                .quests
+                .iter()
+                .filter(|quest| quest.status == QuestStatus::Completed)
+                .count(),
+            duels_won: duels.iter().filter(|d| d.result == DuelResult::Won).count(),
+            duels_lost: duels.iter().filter(|d| d.result == DuelResult::Lost).count(),
+            highlights: important
+                .iter()
+                .skip(important.len().saturating_sub(SUMMARY_HIGHLIGHTS))
+                .map(|event| event.description.to_string())
+                .collect(),
+        })
+    }
+}
+
+/// 为结局写一段收尾正文，LLM 不可用或输出为空时使用结局描述
+pub async fn narrate_finale(
+    llm_service: Option<&LLMService>,
+    ending: &EndingDefinition,
+    cause: Option<EndingCause>,
+    state: &GameState,
+) -> String {
+    if let Some(llm_service) = llm_service {
+        let request = LLMRequest {
+            prompt: build_finale_prompt(ending, cause, state),
+            max_tokens: Some(800),
+            temperature: Some(0.8),
+            subsystem: LLMSubsystem::Plot,
+            retry_policy: None,
+        };
+        if let Ok(response) = llm_service.generate(request).await {
+            let text = response.text.trim();
+            if !text.is_empty() {
+                return text.to_string();
+            }
+        }
+    }
+    fallback_finale(ending, state)
+}
+
+pub fn build_finale_prompt(
+    ending: &EndingDefinition,
+    cause: Option<EndingCause>,
+    state: &GameState,
+) -> String {
+    let player = &state.player;
+    let mut scene = format!(
+        "Ending reached: {}\n{}\nDays played: {}",
+        ending.title, ending.description, state.game_time.total_days
+    );
+    if let Some(cause) = cause {
+        scene.push_str(&format!("\nThe journey ended because: {}", cause.label()));
+    }
+    let context = PromptContext {
+        scene: Some(scene),
+        location: Some(player.location.clone()),
+        actor_name: Some(player.name.clone()),
+        actor_realm: Some(player.stats.cultivation_realm.name.clone()),
+        actor_combat_power: Some(player.stats.combat_power),
+        history_events: state
+            .event_history
+            .iter()
+            .rev()
+            .take(FINALE_HISTORY_EVENTS)
+            .rev()
+            .map(|event| event.description.to_string())
+            .collect(),
+        world_setting_summary: Some(format!("script: {}", state.script.name)),
+        ..PromptContext::default()
+    };
+    let constraints = PromptConstraints {
+        numerical_rules: Vec::new(),
+        world_rules: vec![
+            "write the finale in Chinese prose, 3-6 paragraphs".to_string(),
+            "stay consistent with the ending title and the recorded events".to_string(),
+        ],
+        output_schema_hint: None,
+    };
+
+    PromptBuilder::default().build_prompt_with_token_limit(
+        PromptTemplate::EndingFinale,
+        &context,
+        &constraints,
+        1200,
+    )
+}
+
+pub fn fallback_finale(ending: &EndingDefinition, state: &GameState) -> String {
+    format!(
+        "【{}】{}{}以{}之身，走完了这{}日的道途。",
+        ending.title,
+        ending.description,
+        state.player.name,
+        state.player.stats.cultivation_realm.name,
+        state.game_time.total_days
+    )
+}
+
+/// 跨局保存的结局图鉴，记录每个剧本已达成的结局
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EndingGallery {
+    pub entries: Vec<GalleryEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GalleryEntry {
+    pub script_id: String,
+    pub script_name: String,
+    pub ending_id: String,
+    pub title: String,
+    pub times_achieved: u32,
+    /// 首次与最近达成的 Unix 时间戳（秒）
+    pub first_achieved_at: u64,
+    pub last_achieved_at: u64,
+}
+
+/// 图鉴中某剧本的一个结局位，未达成的结局不显示标题
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GallerySlot {
+    pub ending_id: String,
+    pub title: Option<String>,
+    pub achieved: bool,
+    pub times_achieved: u32,
+}
+
+/// 图鉴视图：全部已达成结局，以及当前剧本的结局位
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EndingGalleryView {
+    pub entries: Vec<GalleryEntry>,
+    pub current_script: Vec<GallerySlot>,
+}
+
+impl EndingGallery {
+    pub fn record(&mut self, script_id: &str, script_name: &str, ending: &AchievedEnding, now: u64) {
+        if let Some(entry) = self
+            .entries
+            .iter_mut()
+            .find(|e| e.script_id == script_id && e.ending_id == ending.ending_id)
+        {
+            entry.times_achieved += 1;
+            entry.last_achieved_at = now;
+            return;
+        }
+        self.entries.push(GalleryEntry {
+            script_id: script_id.to_string(),
+            script_name: script_name.to_string(),
+            ending_id: ending.ending_id.clone(),
+            title: ending.title.clone(),
+            times_achieved: 1,
+            first_achieved_at: now,
+            last_achieved_at: now,
+        });
+    }
+
+    /// 按剧本定义顺序列出结局位
+    pub fn slots_for(&self, script_id: &str, endings: &[EndingDefinition]) -> Vec<GallerySlot> {
+        endings
+            .iter()
+            .map(|ending| {
+                let entry = self
+                    .entries
+                    .iter()
+                    .find(|e| e.script_id == script_id && e.ending_id == ending.id);
+                GallerySlot {
+                    ending_id: ending.id.clone(),
+                    title: entry.map(|_| ending.title.clone()),
+                    achieved: entry.is_some(),
+                    times_achieved: entry.map_or(0, |e| e.times_achieved),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ending(id: &str, condition: &str, priority: i32) -> EndingDefinition {
+        EndingDefinition {
+            id: id.to_string(),
+            title: id.to_string(),
+            description: String::new(),
+            condition: condition.to_string(),
+            priority,
+            ends_playthrough: false,
+        }
+    }
+
+    #[test]
+    fn test_select_ending_prefers_highest_priority_match() {
+        let endings = vec![
+            ending("mortal", "", 0),
+            ending("ascension", "realm_level >= 5", 10),
+            ending("sect_master", "rep_azure_sect >= 50 && affinity_npc_elder_1 > 20", 5),
+        ];
+        let mut variables = HashMap::from([
+            ("realm_level".to_string(), 3.0),
+            ("rep_azure_sect".to_string(), 60.0),
+            ("affinity_npc_elder_1".to_string(), 30.0),
+        ]);
+        assert_eq!(select_ending(&endings, &variables).id, "sect_master");
+
+        variables.insert("realm_level".to_string(), 5.0);
+        assert_eq!(select_ending(&endings, &variables).id, "ascension");
+
+        // 未记录的好感按 0 计
+        variables.remove("affinity_npc_elder_1");
+        variables.insert("realm_level".to_string(), 1.0);
+        assert_eq!(select_ending(&endings, &variables).id, "mortal");
+        assert_eq!(select_ending(&endings[1..], &variables).id, DEFAULT_ENDING_ID);
+    }
+
+    #[test]
+    fn test_validate_endings_rejects_bad_conditions() {
+        assert!(validate_endings(&[ending("a", "event_demon_invasion == 1", 0)]).is_ok());
+        assert_eq!(
+            validate_endings(&[ending("a", "", 0), ending("a", "", 1)]),
+            Err(EndingError::DuplicateEnding("a".to_string()))
+        );
+        assert!(matches!(
+            validate_endings(&[ending("b", "gold > 3", 0)]),
+            Err(EndingError::InvalidCondition { .. })
+        ));
+        assert!(validate_endings(&[ending("c", "rep_ > 1", 0)]).is_err());
+    }
+
+    #[test]
+    fn test_gallery_counts_repeat_endings_and_hides_locked_titles() {
+        let endings = vec![ending("mortal", "", 0), ending("ascension", "realm_level >= 5", 1)];
+        let achieved = AchievedEnding {
+            ending_id: "mortal".to_string(),
+            title: "mortal".to_string(),
+            finale: String::new(),
+            day: 30,
+            cause: None,
+        };
+        let mut gallery = EndingGallery::default();
+        gallery.record("script_1", "青云志", &achieved, 100);
+        gallery.record("script_1", "青云志", &achieved, 200);
+
+        assert_eq!(gallery.entries.len(), 1);
+        assert_eq!(gallery.entries[0].times_achieved, 2);
+        assert_eq!(gallery.entries[0].first_achieved_at, 100);
+
+        let slots = gallery.slots_for("script_1", &endings);
+        assert!(slots[0].achieved);
+        assert_eq!(slots[1].title, None);
+        assert!(gallery.slots_for("script_2", &endings).iter().all(|s| !s.achieved));
+    }
+}
@@ -1,19 +1,58 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::sync::Arc;
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// 只与最近这么多条事件比较是否重复
+const DEDUPE_LOOKBACK: usize = 8;
+/// 与上一次出现相隔不超过该时间（游戏日）才会合并
+const DEDUPE_WINDOW: u64 = 3;
+/// 字符三元组的 Jaccard 相似度达到该值视为近似重复
+const DEDUPE_SIMILARITY: f64 = 0.8;
+const SHINGLE_SIZE: usize = 3;
+const ROLLING_HASH_BASE: u64 = 1_000_003;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum EventImportance {
     Normal,
     Important,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct GameEvent {
     pub id: u64,
     pub timestamp: u64,
     pub event_type: Arc<str>,
     pub description: Arc<str>,
     pub importance: EventImportance,
+    /// 合并进本条的近似重复事件总数（含本条）
+    #[serde(default = "default_repeat_count")]
+    pub repeat_count: u32,
+    /// 最近一次重复发生的时间，未合并过时为空
+    #[serde(default)]
+    pub last_timestamp: Option<u64>,
+}
+
+fn default_repeat_count() -> u32 {
+    1
+}
+
+impl GameEvent {
+    /// 供回顾与小说使用的文本，合并过的事件附上次数
+    pub fn summary_text(&self) -> String {
+        if self.repeat_count > 1 {
+            format!("{}（共{}次）", self.description, self.repeat_count)
+        } else {
+            self.description.to_string()
+        }
+    }
+
+    fn absorbs(&self, timestamp: u64, event_type: &str, shingles: &HashSet<u64>) -> bool {
+        self.importance == EventImportance::Normal
+            && self.event_type.as_ref() == event_type
+            && timestamp.saturating_sub(self.last_timestamp.unwrap_or(self.timestamp)) <= DEDUPE_WINDOW
+            && similarity(&shingle_hashes(&self.description), shingles) >= DEDUPE_SIMILARITY
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -23,6 +62,9 @@ pub struct EventArchive {
     pub total_events: usize,
     pub important_events: usize,
     pub summary: String,
+    /// 低内存模式下归档事件原文所在的冷存储文件
+    #[serde(default)]
+    pub spill_file: Option<String>,
 }
 
 #[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
@@ -56,12 +98,22 @@ impl EventLog {
         description: impl Into<String>,
         importance: EventImportance,
     ) -> GameEvent {
+        let event_type = event_type.into();
+        let description = description.into();
+        if importance == EventImportance::Normal {
+            if let Some(merged) = self.merge_repeat(timestamp, &event_type, &description) {
+                return merged;
+            }
+        }
+
         let event = GameEvent {
             id: self.next_id,
             timestamp,
-            event_type: Arc::from(event_type.into()),
-            description: Arc::from(description.into()),
+            event_type: Arc::from(event_type),
+            description: Arc::from(description),
             importance,
+            repeat_count: 1,
+            last_timestamp: None,
         };
 
         self.next_id = self.next_id.saturating_add(1);
@@ -69,6 +121,25 @@ impl EventLog {
         event
     }
 
+    /// 普通事件与近期同类事件近似重复时并入该条，重要事件始终逐条保留
+    fn merge_repeat(
+        &mut self,
+        timestamp: u64,
+        event_type: &str,
+        description: &str,
+    ) -> Option<GameEvent> {
+        let shingles = shingle_hashes(description);
+        let target = self
+            .events
+            .iter_mut()
+            .rev()
+            .take(DEDUPE_LOOKBACK)
+            .find(|event| event.absorbs(timestamp, event_type, &shingles))?;
+        target.repeat_count = target.repeat_count.saturating_add(1);
+        target.last_timestamp = Some(timestamp.max(target.last_timestamp.unwrap_or(target.timestamp)));
+        Some(target.clone())
+    }
+
     pub fn from_events(mut events: Vec<GameEvent>) -> Self {
         events.sort_by_key(|e| (e.timestamp, e.id));
         let next_id = events
@@ -84,6 +155,12 @@ impl EventLog {
         }
     }
 
+    /// 读档时恢复存档中的归档摘要
+    pub fn with_archives(mut self, archives: Vec<EventArchive>) -> Self {
+        self.archives = archives;
+        self
+    }
+
     pub fn all_events(&self) -> &[GameEvent] {
         &self.events
     }
@@ -115,6 +192,34 @@ impl EventLog {
             .collect()
     }
 
+    /// 编号大于 `after` 的普通事件，按发生先后排列
+    pub fn normal_events_after(&self, after: u64) -> Vec<GameEvent> {
+        self.events
+            .iter()
+            .filter(|event| event.id > after && event.importance == EventImportance::Normal)
+            .cloned()
+            .collect()
+    }
+
+    pub fn last_id(&self) -> u64 {
+        self.next_id.saturating_sub(1)
+    }
+
+    /// 把仍为普通、且描述未变的事件提升为重要，返回实际提升的事件
+    pub fn promote(&mut self, candidates: &[GameEvent]) -> Vec<GameEvent> {
+        let mut promoted = Vec::new();
+        for event in self.events.iter_mut() {
+            let matches = candidates
+                .iter()
+                .any(|candidate| candidate.id == event.id && candidate.description == event.description);
+            if matches && event.importance == EventImportance::Normal {
+                event.importance = EventImportance::Important;
+                promoted.push(event.clone());
+            }
+        }
+        promoted
+    }
+
     pub fn important_events(&self) -> Vec<GameEvent> {
         self.query_events(&EventFilter {
             importance: Some(EventImportance::Important),
@@ -127,6 +232,17 @@ impl EventLog {
         max_events: usize,
         max_important: usize,
         max_archives: usize,
+    ) {
+        self.archive_with_spill(max_events, max_important, max_archives, |_| None);
+    }
+
+    /// 与 `archive_if_needed` 相同，但归档前将事件原文交给 `spill` 写出，返回的文件名记录在归档中
+    pub fn archive_with_spill(
+        &mut self,
+        max_events: usize,
+        max_important: usize,
+        max_archives: usize,
+        mut spill: impl FnMut(&[GameEvent]) -> Option<String>,
     ) {
         if self.events.len() <= max_events {
             return;
@@ -146,14 +262,14 @@ impl EventLog {
         if important.len() > max_important {
             let overflow = important.len() - max_important;
             let archived = important.drain(0..overflow).collect::<Vec<_>>();
-            self.push_archive(&archived);
+            self.push_archive(&archived, &mut spill);
         }
 
         normal.sort_by_key(|e| (e.timestamp, e.id));
         if normal.len() > max_events {
             let overflow = normal.len() - max_events;
             let archived = normal.drain(0..overflow).collect::<Vec<_>>();
-            self.push_archive(&archived);
+            self.push_archive(&archived, &mut spill);
         }
 
         self.events = Vec::with_capacity(important.len() + normal.len());
@@ -167,7 +283,11 @@ impl EventLog {
         }
     }
 
-    fn push_archive(&mut self, archived: &[GameEvent]) {
+    fn push_archive(
+        &mut self,
+        archived: &[GameEvent],
+        spill: &mut impl FnMut(&[GameEvent]) -> Option<String>,
+    ) {
         if archived.is_empty() {
             return;
         }
@@ -188,6 +308,7 @@ impl EventLog {
             total_events,
             important_events,
             summary,
+            spill_file: spill(archived),
         });
     }
 
@@ -200,6 +321,38 @@ impl EventLog {
     }
 }
 
+/// 以滚动哈希计算字符三元组集合，短于三个字时整体作为一个元素
+fn shingle_hashes(text: &str) -> HashSet<u64> {
+    let chars = text.trim().chars().map(u64::from).collect::<Vec<u64>>();
+    if chars.len() < SHINGLE_SIZE {
+        return HashSet::from([chars
+            .iter()
+            .fold(0u64, |hash, c| hash.wrapping_mul(ROLLING_HASH_BASE).wrapping_add(*c))]);
+    }
+
+    let top = ROLLING_HASH_BASE.wrapping_pow(SHINGLE_SIZE as u32 - 1);
+    let mut hash = chars[..SHINGLE_SIZE]
+        .iter()
+        .fold(0u64, |hash, c| hash.wrapping_mul(ROLLING_HASH_BASE).wrapping_add(*c));
+    let mut hashes = HashSet::from([hash]);
+    for idx in SHINGLE_SIZE..chars.len() {
+        hash = hash
+            .wrapping_sub(chars[idx - SHINGLE_SIZE].wrapping_mul(top))
+            .wrapping_mul(ROLLING_HASH_BASE)
+            .wrapping_add(chars[idx]);
+        hashes.insert(hash);
+    }
+    hashes
+}
+
+fn similarity(a: &HashSet<u64>, b: &HashSet<u64>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 1.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -221,6 +374,24 @@ mod tests {
         assert_eq!(log.len(), 1);
     }
 
+    #[test]
+    fn test_promote_only_touches_unchanged_normal_events() {
+        let mut log = EventLog::new();
+        log.log_event(1, "player_free_text", "闲逛坊市", EventImportance::Normal);
+        log.log_event(2, "player_free_text", "与大师兄割袍断义", EventImportance::Normal);
+        let candidates = log.normal_events_after(0);
+        assert_eq!(candidates.len(), 2);
+
+        let mut stale = candidates[0].clone();
+        stale.description = Arc::from("另一件事");
+        let promoted = log.promote(&[stale, candidates[1].clone()]);
+        assert_eq!(promoted.len(), 1);
+        assert_eq!(promoted[0].id, 2);
+        assert_eq!(log.important_events().len(), 1);
+        assert!(log.promote(&candidates[1..]).is_empty());
+        assert_eq!(log.normal_events_after(log.last_id()).len(), 0);
+    }
+
     #[test]
     fn test_query_events_with_filters() {
         let mut log = EventLog::new();
@@ -247,6 +418,29 @@ mod tests {
         assert_eq!(ranged.len(), 2);
     }
 
+    #[test]
+    fn test_near_duplicate_events_are_merged_with_counts() {
+        let mut log = EventLog::new();
+        for day in 1..=5 {
+            log.log_event(day, "cultivation", "你在洞府中完成一次修炼", EventImportance::Normal);
+        }
+        log.log_event(6, "cultivation", "你在洞府中完成一次修炼，略有所得", EventImportance::Normal);
+        log.log_event(6, "combat", "你在洞府中完成一次修炼", EventImportance::Normal);
+        log.log_event(7, "breakthrough", "突破至筑基", EventImportance::Important);
+        log.log_event(7, "breakthrough", "突破至筑基", EventImportance::Important);
+        // 超出时间窗口后重新计数
+        log.log_event(20, "cultivation", "你在洞府中完成一次修炼", EventImportance::Normal);
+
+        let events = log.all_events();
+        assert_eq!(events.len(), 6);
+        assert_eq!(events[0].repeat_count, 5);
+        assert_eq!(events[0].last_timestamp, Some(5));
+        assert_eq!(events[0].summary_text(), "你在洞府中完成一次修炼（共5次）");
+        assert_eq!(events[1].repeat_count, 1);
+        assert_eq!(log.important_events().len(), 2);
+        assert_eq!(events[5].repeat_count, 1);
+    }
+
     #[test]
     fn test_archive_if_needed_moves_old_events() {
         let mut log = EventLog::new();
@@ -0,0 +1,235 @@
+use crate::event_log::GameEvent;
+use crate::llm_service::{LLMRequest, LLMService, LLMSubsystem};
+use crate::prompt_builder::{PromptBuilder, PromptConstraints, PromptContext, PromptTemplate};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+
+/// 攒够这么多条未复核的普通事件才复核一次
+pub const TRIAGE_BATCH_SIZE: usize = 8;
+const TRIAGE_MAX_TOKENS: u32 = 120;
+
+/// 引擎自身的记账事件，不参与复核
+pub const BOOKKEEPING_EVENTS: &[&str] = &["memory_decay", "memory_consolidation"];
+
+/// 规则兜底时视为关乎主线的字眼
+const PLOT_CRITICAL_KEYWORDS: &[&str] = &[
+    "陨落", "身死", "坐化", "灭门", "叛出", "背叛", "道侣", "拜师", "收徒", "传承", "秘境", "至宝",
+    "仇人", "深仇", "died", "killed", "betray", "inherit", "sworn",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TriageSource {
+    Llm,
+    /// LLM 不可用或回复无法解析时的关键词判定
+    Rules,
+}
+
+/// 一批普通事件的复核结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TriageReport {
+    /// 应提升为重要的事件编号
+    pub promoted_ids: Vec<u64>,
+    pub source: TriageSource,
+}
+
+/// 事件的重要性由各调用处写死，难免漏掉自由行动中真正关乎主线的几条；
+/// 这里把近期普通事件成批交给廉价的判定模型复核，挑出应提升为重要的
+pub struct EventTriage {
+    llm_service: Option<Arc<LLMService>>,
+    prompt_builder: PromptBuilder,
+}
+
+impl EventTriage {
+    pub fn new() -> Self {
+        Self {
+            llm_service: None,
+            prompt_builder: PromptBuilder::default(),
+        }
+    }
+
+    pub fn with_llm_service(mut self, llm_service: Arc<LLMService>) -> Self {
+        self.llm_service = Some(llm_service);
+        self
+    }
+
+    /// 一次调用复核整批事件，失败时按关键词判定
+    pub async fn review(&self, events: &[GameEvent]) -> TriageReport {
+        if let Some(llm_service) = &self.llm_service {
+            if let Ok(promoted_ids) = self.review_with_llm(llm_service, events).await {
+                return TriageReport {
+                    promoted_ids,
+                    source: TriageSource::Llm,
+                };
+            }
+        }
+        TriageReport {
+            promoted_ids: rule_promotions(events),
+            source: TriageSource::Rules,
+        }
+    }
+
+    async fn review_with_llm(
+        &self,
+        llm_service: &LLMService,
+        events: &[GameEvent],
+    ) -> Result<Vec<u64>, String> {
+        let response = llm_service
+            .generate(LLMRequest {
+                prompt: self.build_prompt(events),
+                max_tokens: Some(TRIAGE_MAX_TOKENS),
+                temperature: Some(0.1),
+                subsystem: LLMSubsystem::Validation,
+                retry_policy: None,
+            })
+            .await
+            .map_err(|e| e.to_string())?;
+
+        parse_promoted_ids(&response.text, events)
+    }
+
+    fn build_prompt(&self, events: &[GameEvent]) -> String {
+        let lines = events
+            .iter()
+            .map(|event| {
+                format!(
+                    "#{} 第{}日 [{}] {}",
+                    event.id, event.timestamp, event.event_type, event.description
+                )
+            })
+            .collect::<Vec<String>>();
+        self.prompt_builder.build_prompt_with_token_limit(
+            PromptTemplate::EventTriage,
+            &PromptContext {
+                scene: Some(format!("待复核的事件：\n{}", lines.join("\n"))),
+                ..PromptContext::default()
+            },
+            &PromptConstraints {
+                numerical_rules: Vec::new(),
+                world_rules: vec![
+                    "只输出严格 JSON".to_string(),
+                    "只挑改变人物关系、生死、门派格局或主角命运的事件，日常修炼、赶路、闲谈不算"
+                        .to_string(),
+                    "宁缺毋滥，没有则返回空数组".to_string(),
+                ],
+                output_schema_hint: Some("{\"important_ids\":[1,2]}".to_string()),
+            },
+            1200,
+        )
+    }
+}
+
+impl Default for EventTriage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 描述中带有生死、背叛、师承等字眼的事件视为关乎主线
+pub fn rule_promotions(events: &[GameEvent]) -> Vec<u64> {
+    events
+        .iter()
+        .filter(|event| {
+            let description = event.description.to_lowercase();
+            PLOT_CRITICAL_KEYWORDS
+                .iter()
+                .any(|keyword| description.contains(keyword))
+        })
+        .map(|event| event.id)
+        .collect()
+}
+
+/// 只接受本批次中的事件编号
+fn parse_promoted_ids(text: &str, events: &[GameEvent]) -> Result<Vec<u64>, String> {
+    let parsed: Value = serde_json::from_str(text.trim()).map_err(|e| e.to_string())?;
+    let ids = parsed
+        .get("important_ids")
+        .and_then(Value::as_array)
+        .ok_or_else(|| "important_ids must be a JSON array".to_string())?;
+    let mut promoted = ids
+        .iter()
+        .filter_map(Value::as_u64)
+        .filter(|id| events.iter().any(|event| event.id == *id))
+        .collect::<Vec<u64>>();
+    promoted.dedup();
+    Ok(promoted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event_log::EventImportance;
+    use crate::llm_provider::ProviderKind;
+    use crate::llm_service::{LLMConfig, LLMResponse};
+
+    fn event(id: u64, description: &str) -> GameEvent {
+        GameEvent {
+            id,
+            timestamp: id,
+            event_type: Arc::from("player_free_text"),
+            description: Arc::from(description),
+            importance: EventImportance::Normal,
+            repeat_count: 1,
+            last_timestamp: None,
+        }
+    }
+
+    fn llm_service(triage: &EventTriage, events: &[GameEvent], reply: &str) -> LLMService {
+        let llm_service = LLMService::new(LLMConfig {
+            endpoint: "https://example.com/v1/chat/completions".to_string(),
+            api_key: "test-key".to_string(),
+            model: "gpt-test".to_string(),
+            max_tokens: 1024,
+            temperature: 0.3,
+            provider_kind: ProviderKind::OpenAI,
+            retry_policy: Default::default(),
+        })
+        .unwrap();
+        llm_service.cache_response_for_request(
+            &LLMRequest {
+                prompt: triage.build_prompt(events),
+                max_tokens: Some(TRIAGE_MAX_TOKENS),
+                temperature: Some(0.1),
+                subsystem: LLMSubsystem::Validation,
+                retry_policy: None,
+            },
+            &LLMResponse {
+                text: reply.to_string(),
+                model: None,
+                finish_reason: None,
+                prompt_tokens: None,
+                completion_tokens: None,
+                total_tokens: None,
+            },
+        );
+        llm_service
+    }
+
+    #[tokio::test]
+    async fn test_llm_promotes_batch_events_and_rules_cover_bad_replies() {
+        let events = vec![
+            event(1, "在坊市闲逛"),
+            event(2, "当众撕毁师门契约，与大师兄割袍断义"),
+            event(3, "为报血海深仇连夜出山"),
+        ];
+
+        let triage = EventTriage::new();
+        let service = llm_service(&triage, &events, r#"{"important_ids":[2,99]}"#);
+        let report = triage
+            .with_llm_service(Arc::new(service))
+            .review(&events)
+            .await;
+        assert_eq!(report.source, TriageSource::Llm);
+        assert_eq!(report.promoted_ids, vec![2]);
+
+        let triage = EventTriage::new();
+        let service = llm_service(&triage, &events, "这几条都挺重要");
+        let report = triage
+            .with_llm_service(Arc::new(service))
+            .review(&events)
+            .await;
+        assert_eq!(report.source, TriageSource::Rules);
+        assert_eq!(report.promoted_ids, vec![3]);
+    }
+}
@@ -0,0 +1,371 @@
+use crate::event_log::EventImportance;
+use crate::game_state::GameState;
+use crate::rng::GameRng;
+use crate::script::Faction;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// 势力模拟每隔多少天推演一次
+pub const FACTION_TICK_DAYS: u32 = 7;
+/// 每次推演中势力实力随机涨落的最大幅度
+const MAX_POWER_DRIFT: u32 = 3;
+const MIN_FACTION_POWER: u32 = 1;
+/// 强弱悬殊的两方每次推演爆发战事的概率
+const WAR_CHANCE: f32 = 0.15;
+/// 强方实力至少为弱方的该倍数才会挑起战事
+const WAR_POWER_RATIO: f32 = 1.2;
+const WAR_DURATION_DAYS: u32 = 28;
+const MAX_ACTIVE_WARS: usize = 2;
+/// 战火波及之地的灵气保留比例
+const WAR_ENERGY_FACTOR: f32 = 0.5;
+/// 战事期间守方每次推演折损的实力，攻方折损一半
+const WAR_ATTRITION: u32 = 2;
+/// 战事结束时胜方所得、败方所失的实力
+const WAR_SPOILS: u32 = 5;
+
+/// 势力实力涨落写入事件日志的事件类型
+pub const FACTION_POWER_EVENT: &str = "faction_power";
+/// 势力开战与停战写入事件日志的事件类型
+pub const FACTION_WAR_EVENT: &str = "faction_war";
+
+/// 两个势力之间进行中的战事
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct FactionWar {
+    pub attacker_id: String,
+    pub defender_id: String,
+    /// 战火波及的地点，战事期间灵气骤减、往来道路封闭
+    pub location_id: String,
+    pub started_day: u32,
+    pub ends_day: u32,
+    /// 开战前该地的灵气，停战后恢复
+    pub original_energy: f32,
+}
+
+/// 各势力的当前实力与进行中的战事，随世界状态保存；未记录的势力沿用剧本中的 `power_level`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct FactionStandings {
+    pub power: HashMap<String, u32>,
+    pub wars: Vec<FactionWar>,
+    pub last_tick_day: u32,
+}
+
+impl FactionStandings {
+    pub fn power_of(&self, faction: &Faction) -> u32 {
+        self.power
+            .get(&faction.id)
+            .copied()
+            .unwrap_or(faction.power_level)
+    }
+
+    /// 该地点是否因战事封锁
+    pub fn is_route_closed(&self, location_id: &str) -> bool {
+        self.wars.iter().any(|war| war.location_id == location_id)
+    }
+
+    fn at_war(&self, a: &str, b: &str) -> bool {
+        self.wars.iter().any(|war| {
+            (war.attacker_id == a && war.defender_id == b)
+                || (war.attacker_id == b && war.defender_id == a)
+        })
+    }
+
+    fn shift(&mut self, faction: &Faction, delta: i32) -> i32 {
+        let old = self.power_of(faction);
+        let new = old
+            .saturating_add_signed(delta)
+            .max(MIN_FACTION_POWER);
+        self.power.insert(faction.id.clone(), new);
+        new as i32 - old as i32
+    }
+}
+
+/// 一次势力变化，同时写入续写背景与事件日志
+#[derive(Debug, Clone, PartialEq)]
+pub struct FactionChange {
+    pub event_type: &'static str,
+    pub description: String,
+    pub importance: EventImportance,
+}
+
+impl FactionChange {
+    fn power(description: String) -> Self {
+        Self {
+            event_type: FACTION_POWER_EVENT,
+            description,
+            importance: EventImportance::Normal,
+        }
+    }
+
+    fn war(description: String) -> Self {
+        Self {
+            event_type: FACTION_WAR_EVENT,
+            description,
+            importance: EventImportance::Important,
+        }
+    }
+}
+
+/// 按剧本事件调整势力实力，键为势力 id；未知的势力忽略
+pub fn apply_power_changes(
+    state: &mut GameState,
+    changes: &HashMap<String, i32>,
+    cause: &str,
+) -> Vec<FactionChange> {
+    let factions = state.script.world_setting.factions.clone();
+    let standings = &mut state.world_state.faction_standings;
+    let shifts = factions
+        .iter()
+        .filter_map(|faction| {
+            let delta = changes.get(&faction.id).copied().filter(|delta| *delta != 0)?;
+            Some((faction, standings.shift(faction, delta)))
+        })
+        .filter(|(_, applied)| *applied != 0)
+        .collect::<Vec<_>>();
+    if shifts.is_empty() {
+        return Vec::new();
+    }
+    vec![FactionChange::power(format!(
+        "势力消长（{}）：{}",
+        cause,
+        describe_shifts(&shifts)
+    ))]
+}
+
+/// 距上次推演已满一个周期时推演势力：结束到期战事、结算战损、随机涨落并可能爆发新的战事
+pub fn simulate(state: &mut GameState) -> Vec<FactionChange> {
+    let today = state.game_time.total_days;
+    let standings = &state.world_state.faction_standings;
+    if today < standings.last_tick_day.saturating_add(FACTION_TICK_DAYS) {
+        return Vec::new();
+    }
+    let mut rng = GameRng::new(state.rng.derive_seed(&format!("factions:{}", today)));
+    let factions = state.script.world_setting.factions.clone();
+    let faction = |id: &str| factions.iter().find(|faction| faction.id == id);
+    let location_name = |state: &GameState, id: &str| {
+        state
+            .world_state
+            .locations
+            .get(id)
+            .map(|location| location.name.clone())
+            .unwrap_or_else(|| id.to_string())
+    };
+    let mut changes = Vec::new();
+    state.world_state.faction_standings.last_tick_day = today;
+
+    let (ended, ongoing): (Vec<FactionWar>, Vec<FactionWar>) = std::mem::take(
+        &mut state.world_state.faction_standings.wars,
+    )
+    .into_iter()
+    .partition(|war| war.ends_day <= today);
+    state.world_state.faction_standings.wars = ongoing;
+
+    let truce = !ended.is_empty();
+    for war in ended {
+        if let Some(location) = state.world_state.locations.get_mut(&war.location_id) {
+            location.spiritual_energy = war.original_energy;
+        }
+        let (Some(attacker), Some(defender)) = (faction(&war.attacker_id), faction(&war.defender_id))
+        else {
+            continue;
+        };
+        let standings = &mut state.world_state.faction_standings;
+        let (winner, loser) = if standings.power_of(attacker) > standings.power_of(defender) {
+            (attacker, defender)
+        } else {
+            (defender, attacker)
+        };
+        standings.shift(winner, WAR_SPOILS as i32);
+        standings.shift(loser, -(WAR_SPOILS as i32));
+        changes.push(FactionChange::war(format!(
+            "{}与{}之战落幕，{}得胜；{}战火平息，道路重开",
+            attacker.name,
+            defender.name,
+            winner.name,
+            location_name(state, &war.location_id)
+        )));
+    }
+
+    let standings = &mut state.world_state.faction_standings;
+    for war in standings.wars.clone() {
+        if let Some(defender) = faction(&war.defender_id) {
+            standings.shift(defender, -(WAR_ATTRITION as i32));
+        }
+        if let Some(attacker) = faction(&war.attacker_id) {
+            standings.shift(attacker, -((WAR_ATTRITION / 2) as i32));
+        }
+    }
+
+    let shifts = factions
+        .iter()
+        .map(|faction| {
+            let delta = rng.range_u32(0, MAX_POWER_DRIFT * 2) as i32 - MAX_POWER_DRIFT as i32;
+            (faction, standings.shift(faction, delta))
+        })
+        .filter(|(_, applied)| *applied != 0)
+        .collect::<Vec<_>>();
+    if !shifts.is_empty() {
+        changes.push(FactionChange::power(format!(
+            "势力消长：{}",
+            describe_shifts(&shifts)
+        )));
+    }
+
+    // 刚停战的这一轮休养生息，不再起新的战事
+    if !truce {
+        changes.extend(maybe_start_war(state, &factions, &mut rng));
+    }
+    changes
+}
+
+/// 在实力最悬殊、尚未交战的一对势力间掷骰，成功则强方开战并波及一处未受战火的地点
+fn maybe_start_war(
+    state: &mut GameState,
+    factions: &[Faction],
+    rng: &mut GameRng,
+) -> Option<FactionChange> {
+    let standings = &state.world_state.faction_standings;
+    if standings.wars.len() >= MAX_ACTIVE_WARS {
+        return None;
+    }
+    let (attacker, defender, _) = factions
+        .iter()
+        .flat_map(|strong| factions.iter().map(move |weak| (strong, weak)))
+        .filter(|(strong, weak)| strong.id != weak.id && !standings.at_war(&strong.id, &weak.id))
+        .map(|(strong, weak)| {
+            let ratio = standings.power_of(strong) as f32 / standings.power_of(weak).max(1) as f32;
+            (strong, weak, ratio)
+        })
+        .filter(|(_, _, ratio)| *ratio >= WAR_POWER_RATIO)
+        .max_by(|a, b| a.2.total_cmp(&b.2))?;
+    if rng.range_f32(0.0, 1.0) >= WAR_CHANCE {
+        return None;
+    }
+
+    let mut candidates = state
+        .world_state
+        .locations
+        .keys()
+        .filter(|id| !standings.is_route_closed(id))
+        .cloned()
+        .collect::<Vec<String>>();
+    candidates.sort();
+    if candidates.is_empty() {
+        return None;
+    }
+    let location_id = candidates.swap_remove(rng.range_u32(0, candidates.len() as u32 - 1) as usize);
+    let location = state.world_state.locations.get_mut(&location_id)?;
+    let original_energy = location.spiritual_energy;
+    location.spiritual_energy *= WAR_ENERGY_FACTOR;
+    let location_name = location.name.clone();
+
+    let today = state.game_time.total_days;
+    state.world_state.faction_standings.wars.push(FactionWar {
+        attacker_id: attacker.id.clone(),
+        defender_id: defender.id.clone(),
+        location_id,
+        started_day: today,
+        ends_day: today + WAR_DURATION_DAYS,
+        original_energy,
+    });
+    Some(FactionChange::war(format!(
+        "{}向{}开战，战火波及{}，灵气骤减，往来道路封闭",
+        attacker.name, defender.name, location_name
+    )))
+}
+
+fn describe_shifts(shifts: &[(&Faction, i32)]) -> String {
+    shifts
+        .iter()
+        .map(|(faction, delta)| format!("{} {:+}", faction.name, delta))
+        .collect::<Vec<String>>()
+        .join("，")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_engine::GameEngine;
+    use crate::models::CultivationRealm;
+    use crate::script::{Climate, Location, LocationKind};
+    use crate::script_manager::ScriptManager;
+
+    fn state_with_factions(powers: &[(&str, u32)]) -> GameState {
+        let mut script = ScriptManager::new().blank_script();
+        script
+            .world_setting
+            .cultivation_realms
+            .push(CultivationRealm::new("练气".to_string(), 1, 0, 1.0));
+        script.world_setting.locations.push(Location {
+            id: "valley".to_string(),
+            name: "落霞谷".to_string(),
+            description: String::new(),
+            spiritual_energy: 1.2,
+            kind: LocationKind::Wilderness,
+            climate: Climate::Temperate,
+        });
+        script.initial_state.starting_location = "valley".to_string();
+        script.world_setting.factions = powers
+            .iter()
+            .map(|(id, power_level)| Faction {
+                id: id.to_string(),
+                name: id.to_string(),
+                description: String::new(),
+                power_level: *power_level,
+            })
+            .collect();
+        GameEngine::new().initialize_game(script).unwrap()
+    }
+
+    #[test]
+    fn test_scripted_changes_shift_power_without_going_below_minimum() {
+        let mut state = state_with_factions(&[("sect", 50), ("demons", 4)]);
+        let changes = HashMap::from([
+            ("sect".to_string(), 10),
+            ("demons".to_string(), -10),
+            ("ghost".to_string(), 5),
+        ]);
+
+        let lines = apply_power_changes(&mut state, &changes, "宗门大比");
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].description, "势力消长（宗门大比）：sect +10，demons -3");
+        let standings = &state.world_state.faction_standings;
+        let factions = &state.script.world_setting.factions;
+        assert_eq!(standings.power_of(&factions[0]), 60);
+        assert_eq!(standings.power_of(&factions[1]), MIN_FACTION_POWER);
+    }
+
+    #[test]
+    fn test_war_drains_location_until_it_ends() {
+        let mut state = state_with_factions(&[("sect", 90), ("demons", 10)]);
+        assert!(simulate(&mut state).is_empty());
+
+        // 推演是确定性的，逐周推进直到强方开战
+        let mut started = None;
+        for _ in 0..200 {
+            state.game_time.advance_days(FACTION_TICK_DAYS);
+            let changes = simulate(&mut state);
+            if let Some(change) = changes.iter().find(|c| c.description.contains("开战")) {
+                started = Some(change.clone());
+                break;
+            }
+        }
+        let started = started.expect("a lopsided rivalry should eventually erupt into war");
+        assert_eq!(started.event_type, FACTION_WAR_EVENT);
+        assert!(started.description.starts_with("sect向demons开战"));
+        let standings = &state.world_state.faction_standings;
+        assert!(standings.is_route_closed("valley"));
+        assert!((state.world_state.locations["valley"].spiritual_energy - 0.6).abs() < 1e-6);
+
+        let mut ended = false;
+        for _ in 0..(WAR_DURATION_DAYS / FACTION_TICK_DAYS) {
+            state.game_time.advance_days(FACTION_TICK_DAYS);
+            ended |= simulate(&mut state)
+                .iter()
+                .any(|change| change.description.contains("落幕，sect得胜"));
+        }
+        assert!(ended);
+        assert!(!state.world_state.faction_standings.is_route_closed("valley"));
+        assert!((state.world_state.locations["valley"].spiritual_energy - 1.2).abs() < 1e-6);
+    }
+}
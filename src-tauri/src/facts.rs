@@ -0,0 +1,241 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+pub const MAX_PROMPT_FACTS: usize = 6;
+const MAX_FACTS: usize = 200;
+const MAX_FACT_CHARS: usize = 40;
+
+const DEATH_MARKERS: &[&str] = &["已陨落", "陨落", "身亡", "战死", "已死", "坐化"];
+const FACT_MARKERS: &[&str] = &["拜入", "弟子", "叛出", "继任", "结为道侣", "被封印", "毁于"];
+const ALIVE_MARKERS: &[&str] = &["说道", "笑道", "开口", "走来", "出手", "点头", "微笑", "冷哼"];
+const RECALL_MARKERS: &[&str] = &["生前", "遗", "回忆", "梦", "幻象", "当年", "曾经"];
+const MEMBERSHIP_SEPARATORS: &[&str] = &["拜入", "是", "为"];
+
+/// 故事中已确立、不可更改的设定事实
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct CanonFact {
+    pub id: u64,
+    pub statement: String,
+    pub established_at: u64,
+}
+
+/// 单局游戏的设定事实库
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct FactStore {
+    facts: Vec<CanonFact>,
+    next_id: u64,
+}
+
+impl FactStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn facts(&self) -> &[CanonFact] {
+        &self.facts
+    }
+
+    pub fn len(&self) -> usize {
+        self.facts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.facts.is_empty()
+    }
+
+    /// 添加一条事实，重复、与已有事实矛盾或超出容量时返回 None
+    pub fn add_fact(&mut self, statement: &str, timestamp: u64) -> Option<CanonFact> {
+        let statement = statement.trim();
+        if statement.is_empty()
+            || self.facts.len() >= MAX_FACTS
+            || self.facts.iter().any(|f| f.statement == statement)
+            || !self.find_contradictions(statement).is_empty()
+        {
+            return None;
+        }
+
+        self.next_id = self.next_id.saturating_add(1);
+        let fact = CanonFact {
+            id: self.next_id,
+            statement: statement.to_string(),
+            established_at: timestamp,
+        };
+        self.facts.push(fact.clone());
+        Some(fact)
+    }
+
+    /// 从剧情文本中提取并记录新的事实
+    pub fn record_from_text(&mut self, text: &str, timestamp: u64) -> Vec<CanonFact> {
+        extract_fact_candidates(text)
+            .into_iter()
+            .filter_map(|candidate| self.add_fact(&candidate, timestamp))
+            .collect()
+    }
+
+    /// 按与查询文本的相关度选出事实，相关度相同时优先较新的事实
+    pub fn relevant_facts(&self, query: &str, limit: usize) -> Vec<&CanonFact> {
+        let query_bigrams = bigrams(query);
+        let mut scored = self
+            .facts
+            .iter()
+            .map(|fact| {
+                let score = bigrams(&fact.statement)
+                    .intersection(&query_bigrams)
+                    .count();
+                (score, fact)
+            })
+            .collect::<Vec<(usize, &CanonFact)>>();
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| b.1.id.cmp(&a.1.id)));
+        scored.into_iter().take(limit).map(|(_, fact)| fact).collect()
+    }
+
+    /// 只返回与查询文本至少有一处相同二字组的事实
+    pub fn matching_facts(&self, query: &str, limit: usize) -> Vec<&CanonFact> {
+        let query_bigrams = bigrams(query);
+        self.relevant_facts(query, limit)
+            .into_iter()
+            .filter(|fact| !bigrams(&fact.statement).is_disjoint(&query_bigrams))
+            .collect()
+    }
+
+    pub fn prompt_lines(&self, query: &str, limit: usize) -> Vec<String> {
+        self.relevant_facts(query, limit)
+            .into_iter()
+            .map(|fact| fact.statement.clone())
+            .collect()
+    }
+
+    /// 找出文本中与已有事实矛盾的句子
+    pub fn find_contradictions(&self, text: &str) -> Vec<String> {
+        let sentences = split_sentences(text);
+        let mut contradictions = Vec::new();
+
+        for fact in &self.facts {
+            if let Some(subject) = death_subject(&fact.statement) {
+                for sentence in &sentences {
+                    if sentence.contains(subject.as_str())
+                        && ALIVE_MARKERS.iter().any(|m| sentence.contains(m))
+                        && !RECALL_MARKERS.iter().any(|m| sentence.contains(m))
+                    {
+                        contradictions.push(format!("「{}」与「{}」矛盾", fact.statement, sentence));
+                    }
+                }
+            }
+
+            if let Some((subject, sect)) = membership(&fact.statement) {
+                for sentence in &sentences {
+                    if let Some((other_subject, other_sect)) = membership(sentence) {
+                        if other_subject == subject && other_sect != sect {
+                            contradictions
+                                .push(format!("「{}」与「{}」矛盾", fact.statement, sentence));
+                        }
+                    }
+                }
+            }
+        }
+
+        contradictions
+    }
+}
+
+fn split_sentences(text: &str) -> Vec<String> {
+    text.split(['。', '！', '？', '!', '?', '；', ';', '\n'])
+        .map(|s| {
+            s.trim()
+                .trim_matches(|c| matches!(c, '“' | '”' | '「' | '」' | '"'))
+                .trim()
+                .to_string()
+        })
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+fn extract_fact_candidates(text: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    split_sentences(text)
+        .into_iter()
+        .filter(|s| s.chars().count() <= MAX_FACT_CHARS)
+        .filter(|s| !s.contains('“') && !s.contains('”'))
+        .filter(|s| {
+            DEATH_MARKERS.iter().any(|m| s.contains(m)) || FACT_MARKERS.iter().any(|m| s.contains(m))
+        })
+        .filter(|s| seen.insert(s.clone()))
+        .collect()
+}
+
+fn death_subject(statement: &str) -> Option<String> {
+    DEATH_MARKERS.iter().find_map(|marker| {
+        let idx = statement.find(marker)?;
+        let subject = statement[..idx].trim().trim_end_matches('已').trim();
+        (subject.chars().count() >= 2).then(|| subject.to_string())
+    })
+}
+
+fn membership(statement: &str) -> Option<(String, String)> {
+    let idx = statement.find("弟子")?;
+    let prefix = &statement[..idx];
+    MEMBERSHIP_SEPARATORS.iter().find_map(|sep| {
+        let sep_idx = prefix.find(sep)?;
+        let subject = prefix[..sep_idx].trim();
+        let sect = prefix[sep_idx + sep.len()..].trim().trim_end_matches('的').trim();
+        (!subject.is_empty() && !sect.is_empty()).then(|| (subject.to_string(), sect.to_string()))
+    })
+}
+
+fn bigrams(text: &str) -> HashSet<(char, char)> {
+    let chars = text
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<Vec<char>>();
+    chars.windows(2).map(|w| (w[0], w[1])).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_from_text_extracts_short_declarative_facts() {
+        let mut store = FactStore::new();
+        let recorded = store.record_from_text(
+            "夜色沉沉。师尊已陨落。主角为青云宗弟子！他握紧了剑，心中默念着那句话，久久不能平静。",
+            3,
+        );
+
+        let statements = recorded.iter().map(|f| f.statement.as_str()).collect::<Vec<&str>>();
+        assert_eq!(statements, vec!["师尊已陨落", "主角为青云宗弟子"]);
+        assert!(store.record_from_text("师尊已陨落。", 4).is_empty());
+    }
+
+    #[test]
+    fn test_find_contradictions_detects_dead_character_speaking() {
+        let mut store = FactStore::new();
+        store.add_fact("师尊已陨落", 1).unwrap();
+
+        assert_eq!(store.find_contradictions("师尊缓缓走来，点头说道：很好。").len(), 1);
+        assert!(store.find_contradictions("他回忆起师尊生前说道的话。").is_empty());
+    }
+
+    #[test]
+    fn test_find_contradictions_detects_sect_change() {
+        let mut store = FactStore::new();
+        store.add_fact("主角为青云宗弟子", 1).unwrap();
+
+        assert_eq!(store.find_contradictions("主角是天剑门的弟子。").len(), 1);
+        assert!(store.find_contradictions("主角是青云宗的弟子。").is_empty());
+        assert!(store.add_fact("主角是天剑门弟子", 2).is_none());
+    }
+
+    #[test]
+    fn test_relevant_facts_prefers_overlap_then_recency() {
+        let mut store = FactStore::new();
+        store.add_fact("师尊已陨落", 1).unwrap();
+        store.add_fact("主角为青云宗弟子", 2).unwrap();
+        store.add_fact("魔教被封印于北荒", 3).unwrap();
+
+        let lines = store.prompt_lines("青云宗山门前", 2);
+        assert_eq!(lines[0], "主角为青云宗弟子");
+        assert_eq!(lines[1], "魔教被封印于北荒");
+    }
+}
@@ -0,0 +1,601 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+const MAX_FORMULA_LENGTH: usize = 512;
+const MAX_NESTING_DEPTH: usize = 32;
+const DEFAULT_MAX_STEPS: u32 = 10_000;
+
+/// 剧本公式求值时的资源上限。步数上限即时间上限：每步只做常数时间的运算，
+/// 公式长度又限制在 MAX_FORMULA_LENGTH 以内，求值耗时有确定的上界；
+/// 不用挂钟计时，同一输入在任何机器、任何负载下都得到同样的结果，种子重放才可复现
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FormulaLimits {
+    pub max_steps: u32,
+}
+
+impl Default for FormulaLimits {
+    fn default() -> Self {
+        Self {
+            max_steps: DEFAULT_MAX_STEPS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FormulaError {
+    TooLong(usize),
+    Syntax(String),
+    TooDeep,
+    UnknownVariable(String),
+    UnknownFunction(String),
+    WrongArity { function: String, expected: String, found: usize },
+    StepBudgetExceeded(u32),
+    NonFinite,
+}
+
+impl fmt::Display for FormulaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormulaError::TooLong(len) => {
+                write!(f, "formula is too long: {len} chars (max {MAX_FORMULA_LENGTH})")
+            }
+            FormulaError::Syntax(msg) => write!(f, "syntax error: {msg}"),
+            FormulaError::TooDeep => write!(f, "formula nesting exceeds {MAX_NESTING_DEPTH}"),
+            FormulaError::UnknownVariable(name) => write!(f, "variable not allowed: {name}"),
+            FormulaError::UnknownFunction(name) => write!(f, "function not allowed: {name}"),
+            FormulaError::WrongArity {
+                function,
+                expected,
+                found,
+            } => write!(
+                f,
+                "function {function} expects {expected} arguments, found {found}"
+            ),
+            FormulaError::StepBudgetExceeded(steps) => {
+                write!(f, "evaluation exceeded step budget of {steps}")
+            }
+            FormulaError::NonFinite => write!(f, "evaluation produced a non-finite number"),
+        }
+    }
+}
+
+impl std::error::Error for FormulaError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Number(f64),
+    Variable(String),
+    Negate(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+    Comma,
+}
+
+/// 已解析的沙箱公式，只允许算术、比较与白名单函数
+#[derive(Debug, Clone, PartialEq)]
+pub struct Formula {
+    source: String,
+    expr: Expr,
+}
+
+impl Formula {
+    pub fn parse(source: &str) -> Result<Self, FormulaError> {
+        let length = source.chars().count();
+        if length > MAX_FORMULA_LENGTH {
+            return Err(FormulaError::TooLong(length));
+        }
+
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.parse_expr(0)?;
+        if parser.pos != parser.tokens.len() {
+            return Err(FormulaError::Syntax(format!(
+                "unexpected token {:?}",
+                parser.tokens[parser.pos]
+            )));
+        }
+        check_functions(&expr)?;
+
+        Ok(Self {
+            source: source.trim().to_string(),
+            expr,
+        })
+    }
+
+    /// 解析公式并确认只引用白名单中的变量
+    pub fn parse_with_whitelist(source: &str, allowed: &[&str]) -> Result<Self, FormulaError> {
+        let formula = Self::parse(source)?;
+        if let Some(name) = formula
+            .variables()
+            .into_iter()
+            .find(|name| !allowed.contains(&name.as_str()))
+        {
+            return Err(FormulaError::UnknownVariable(name));
+        }
+        Ok(formula)
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn variables(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        collect_variables(&self.expr, &mut names);
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    pub fn evaluate(&self, variables: &HashMap<&str, f64>) -> Result<f64, FormulaError> {
+        self.evaluate_with_limits(variables, FormulaLimits::default())
+    }
+
+    pub fn evaluate_with_limits(
+        &self,
+        variables: &HashMap<&str, f64>,
+        limits: FormulaLimits,
+    ) -> Result<f64, FormulaError> {
+        let mut evaluator = Evaluator {
+            variables,
+            steps: 0,
+            max_steps: limits.max_steps,
+        };
+        let value = evaluator.eval(&self.expr)?;
+        if value.is_finite() {
+            Ok(value)
+        } else {
+            Err(FormulaError::NonFinite)
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, FormulaError> {
+    let chars = source.chars().collect::<Vec<char>>();
+    let mut tokens = Vec::new();
+    let mut idx = 0;
+
+    while idx < chars.len() {
+        let c = chars[idx];
+        if c.is_whitespace() {
+            idx += 1;
+            continue;
+        }
+
+        if c.is_ascii_digit() || c == '.' {
+            let start = idx;
+            while idx < chars.len() && (chars[idx].is_ascii_digit() || chars[idx] == '.') {
+                idx += 1;
+            }
+            let text = chars[start..idx].iter().collect::<String>();
+            let value = text
+                .parse::<f64>()
+                .map_err(|_| FormulaError::Syntax(format!("invalid number: {text}")))?;
+            tokens.push(Token::Number(value));
+            continue;
+        }
+
+        if c.is_ascii_alphabetic() || c == '_' {
+            let start = idx;
+            while idx < chars.len() && (chars[idx].is_ascii_alphanumeric() || chars[idx] == '_') {
+                idx += 1;
+            }
+            tokens.push(Token::Ident(chars[start..idx].iter().collect()));
+            continue;
+        }
+
+        let next = chars.get(idx + 1).copied();
+        let (token, width) = match (c, next) {
+            ('(', _) => (Token::LParen, 1),
+            (')', _) => (Token::RParen, 1),
+            (',', _) => (Token::Comma, 1),
+            ('=', Some('=')) => (Token::Op("=="), 2),
+            ('!', Some('=')) => (Token::Op("!="), 2),
+            ('<', Some('=')) => (Token::Op("<="), 2),
+            ('>', Some('=')) => (Token::Op(">="), 2),
+            ('&', Some('&')) => (Token::Op("&&"), 2),
+            ('|', Some('|')) => (Token::Op("||"), 2),
+            ('<', _) => (Token::Op("<"), 1),
+            ('>', _) => (Token::Op(">"), 1),
+            ('!', _) => (Token::Op("!"), 1),
+            ('+', _) => (Token::Op("+"), 1),
+            ('-', _) => (Token::Op("-"), 1),
+            ('*', _) => (Token::Op("*"), 1),
+            ('/', _) => (Token::Op("/"), 1),
+            ('%', _) => (Token::Op("%"), 1),
+            ('^', _) => (Token::Op("^"), 1),
+            _ => return Err(FormulaError::Syntax(format!("unexpected character '{c}'"))),
+        };
+        tokens.push(token);
+        idx += width;
+    }
+
+    if tokens.is_empty() {
+        return Err(FormulaError::Syntax("formula is empty".to_string()));
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek_op(&self) -> Option<&'static str> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(op)) => Some(op),
+            _ => None,
+        }
+    }
+
+    fn parse_expr(&mut self, depth: usize) -> Result<Expr, FormulaError> {
+        if depth > MAX_NESTING_DEPTH {
+            return Err(FormulaError::TooDeep);
+        }
+        self.parse_binary(0, depth)
+    }
+
+    // 优先级从低到高：|| → && → 比较 → 加减 → 乘除取余
+    fn parse_binary(&mut self, level: usize, depth: usize) -> Result<Expr, FormulaError> {
+        const LEVELS: [&[(&str, BinaryOp)]; 5] = [
+            &[("||", BinaryOp::Or)],
+            &[("&&", BinaryOp::And)],
+            &[
+                ("==", BinaryOp::Eq),
+                ("!=", BinaryOp::Ne),
+                ("<", BinaryOp::Lt),
+                ("<=", BinaryOp::Le),
+                (">", BinaryOp::Gt),
+                (">=", BinaryOp::Ge),
+            ],
+            &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+            &[("*", BinaryOp::Mul), ("/", BinaryOp::Div), ("%", BinaryOp::Rem)],
+        ];
+
+        if level == LEVELS.len() {
+            return self.parse_unary(depth);
+        }
+
+        let mut lhs = self.parse_binary(level + 1, depth)?;
+        while let Some(op) = self
+            .peek_op()
+            .and_then(|op| LEVELS[level].iter().find(|(sym, _)| *sym == op))
+            .map(|(_, op)| *op)
+        {
+            self.pos += 1;
+            let rhs = self.parse_binary(level + 1, depth)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self, depth: usize) -> Result<Expr, FormulaError> {
+        if depth > MAX_NESTING_DEPTH {
+            return Err(FormulaError::TooDeep);
+        }
+        match self.peek_op() {
+            Some("-") => {
+                self.pos += 1;
+                Ok(Expr::Negate(Box::new(self.parse_unary(depth + 1)?)))
+            }
+            Some("!") => {
+                self.pos += 1;
+                Ok(Expr::Not(Box::new(self.parse_unary(depth + 1)?)))
+            }
+            _ => self.parse_power(depth),
+        }
+    }
+
+    fn parse_power(&mut self, depth: usize) -> Result<Expr, FormulaError> {
+        let base = self.parse_primary(depth)?;
+        if self.peek_op() == Some("^") {
+            self.pos += 1;
+            let exponent = self.parse_unary(depth + 1)?;
+            return Ok(Expr::Binary(BinaryOp::Pow, Box::new(base), Box::new(exponent)));
+        }
+        Ok(base)
+    }
+
+    fn parse_primary(&mut self, depth: usize) -> Result<Expr, FormulaError> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| FormulaError::Syntax("unexpected end of formula".to_string()))?;
+        self.pos += 1;
+
+        match token {
+            Token::Number(value) => Ok(Expr::Number(value)),
+            Token::Ident(name) => {
+                if self.tokens.get(self.pos) != Some(&Token::LParen) {
+                    return Ok(match name.as_str() {
+                        "true" => Expr::Number(1.0),
+                        "false" => Expr::Number(0.0),
+                        _ => Expr::Variable(name),
+                    });
+                }
+                self.pos += 1;
+                let mut args = Vec::new();
+                if self.tokens.get(self.pos) == Some(&Token::RParen) {
+                    self.pos += 1;
+                    return Ok(Expr::Call(name, args));
+                }
+                loop {
+                    args.push(self.parse_expr(depth + 1)?);
+                    match self.tokens.get(self.pos) {
+                        Some(Token::Comma) => self.pos += 1,
+                        Some(Token::RParen) => {
+                            self.pos += 1;
+                            return Ok(Expr::Call(name, args));
+                        }
+                        _ => {
+                            return Err(FormulaError::Syntax(format!(
+                                "expected ',' or ')' in call to {name}"
+                            )))
+                        }
+                    }
+                }
+            }
+            Token::LParen => {
+                let inner = self.parse_expr(depth + 1)?;
+                if self.tokens.get(self.pos) != Some(&Token::RParen) {
+                    return Err(FormulaError::Syntax("missing ')'".to_string()));
+                }
+                self.pos += 1;
+                Ok(inner)
+            }
+            other => Err(FormulaError::Syntax(format!("unexpected token {:?}", other))),
+        }
+    }
+}
+
+fn check_functions(expr: &Expr) -> Result<(), FormulaError> {
+    match expr {
+        Expr::Number(_) | Expr::Variable(_) => Ok(()),
+        Expr::Negate(inner) | Expr::Not(inner) => check_functions(inner),
+        Expr::Binary(_, lhs, rhs) => {
+            check_functions(lhs)?;
+            check_functions(rhs)
+        }
+        Expr::Call(name, args) => {
+            let (min, max) = match name.as_str() {
+                "abs" | "floor" | "ceil" | "round" | "sqrt" => (1, 1),
+                "pow" => (2, 2),
+                "clamp" | "if" => (3, 3),
+                "min" | "max" => (1, usize::MAX),
+                _ => return Err(FormulaError::UnknownFunction(name.clone())),
+            };
+            if args.len() < min || args.len() > max {
+                let expected = if min == max {
+                    min.to_string()
+                } else {
+                    format!("at least {min}")
+                };
+                return Err(FormulaError::WrongArity {
+                    function: name.clone(),
+                    expected,
+                    found: args.len(),
+                });
+            }
+            args.iter().try_for_each(check_functions)
+        }
+    }
+}
+
+fn collect_variables(expr: &Expr, names: &mut Vec<String>) {
+    match expr {
+        Expr::Number(_) => {}
+        Expr::Variable(name) => names.push(name.clone()),
+        Expr::Negate(inner) | Expr::Not(inner) => collect_variables(inner, names),
+        Expr::Binary(_, lhs, rhs) => {
+            collect_variables(lhs, names);
+            collect_variables(rhs, names);
+        }
+        Expr::Call(_, args) => args.iter().for_each(|arg| collect_variables(arg, names)),
+    }
+}
+
+struct Evaluator<'a> {
+    variables: &'a HashMap<&'a str, f64>,
+    steps: u32,
+    max_steps: u32,
+}
+
+impl Evaluator<'_> {
+    fn tick(&mut self) -> Result<(), FormulaError> {
+        self.steps = self.steps.saturating_add(1);
+        if self.steps > self.max_steps {
+            return Err(FormulaError::StepBudgetExceeded(self.max_steps));
+        }
+        Ok(())
+    }
+
+    fn eval(&mut self, expr: &Expr) -> Result<f64, FormulaError> {
+        self.tick()?;
+        let value = match expr {
+            Expr::Number(value) => *value,
+            Expr::Variable(name) => *self
+                .variables
+                .get(name.as_str())
+                .ok_or_else(|| FormulaError::UnknownVariable(name.clone()))?,
+            Expr::Negate(inner) => -self.eval(inner)?,
+            Expr::Not(inner) => truth(self.eval(inner)? == 0.0),
+            Expr::Binary(BinaryOp::And, lhs, rhs) => {
+                truth(self.eval(lhs)? != 0.0 && self.eval(rhs)? != 0.0)
+            }
+            Expr::Binary(BinaryOp::Or, lhs, rhs) => {
+                truth(self.eval(lhs)? != 0.0 || self.eval(rhs)? != 0.0)
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                let a = self.eval(lhs)?;
+                let b = self.eval(rhs)?;
+                match op {
+                    BinaryOp::Add => a + b,
+                    BinaryOp::Sub => a - b,
+                    BinaryOp::Mul => a * b,
+                    BinaryOp::Div => a / b,
+                    BinaryOp::Rem => a % b,
+                    BinaryOp::Pow => a.powf(b),
+                    BinaryOp::Eq => truth(a == b),
+                    BinaryOp::Ne => truth(a != b),
+                    BinaryOp::Lt => truth(a < b),
+                    BinaryOp::Le => truth(a <= b),
+                    BinaryOp::Gt => truth(a > b),
+                    BinaryOp::Ge => truth(a >= b),
+                    BinaryOp::And | BinaryOp::Or => unreachable!("handled above"),
+                }
+            }
+            Expr::Call(name, args) if name == "if" => {
+                if self.eval(&args[0])? != 0.0 {
+                    self.eval(&args[1])?
+                } else {
+                    self.eval(&args[2])?
+                }
+            }
+            Expr::Call(name, args) => {
+                let values = args
+                    .iter()
+                    .map(|arg| self.eval(arg))
+                    .collect::<Result<Vec<f64>, FormulaError>>()?;
+                match name.as_str() {
+                    "abs" => values[0].abs(),
+                    "floor" => values[0].floor(),
+                    "ceil" => values[0].ceil(),
+                    "round" => values[0].round(),
+                    "sqrt" => values[0].sqrt(),
+                    "pow" => values[0].powf(values[1]),
+                    "clamp" => values[0].max(values[1]).min(values[2]),
+                    "min" => values.iter().copied().fold(f64::INFINITY, f64::min),
+                    "max" => values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+                    other => return Err(FormulaError::UnknownFunction(other.to_string())),
+                }
+            }
+        };
+
+        if value.is_finite() {
+            Ok(value)
+        } else {
+            Err(FormulaError::NonFinite)
+        }
+    }
+}
+
+fn truth(value: bool) -> f64 {
+    if value {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&'static str, f64)]) -> HashMap<&'static str, f64> {
+        pairs.iter().copied().collect()
+    }
+
+    #[test]
+    fn test_evaluate_arithmetic_and_precedence() {
+        let formula = Formula::parse("1 + 2 * 3 - 4 / 2").unwrap();
+        assert_eq!(formula.evaluate(&HashMap::new()).unwrap(), 5.0);
+
+        let formula = Formula::parse("-2 ^ 2 + (1 + 1) * 3").unwrap();
+        assert_eq!(formula.evaluate(&HashMap::new()).unwrap(), 2.0);
+    }
+
+    #[test]
+    fn test_evaluate_with_variables_and_functions() {
+        let formula =
+            Formula::parse("clamp(affinity * (1 - 0.1 * sub_level) + if(sub_level >= 3, -0.2, 0), 0, 1)")
+                .unwrap();
+        let value = formula
+            .evaluate(&vars(&[("affinity", 0.9), ("sub_level", 1.0)]))
+            .unwrap();
+        assert!((value - 0.81).abs() < 1e-9);
+        assert_eq!(formula.variables(), vec!["affinity", "sub_level"]);
+    }
+
+    #[test]
+    fn test_whitelist_rejects_unknown_variable() {
+        let err = Formula::parse_with_whitelist("affinity + secret", &["affinity"]).unwrap_err();
+        assert_eq!(err, FormulaError::UnknownVariable("secret".to_string()));
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_function_and_bad_syntax() {
+        assert!(matches!(
+            Formula::parse("exec(1)"),
+            Err(FormulaError::UnknownFunction(_))
+        ));
+        assert!(matches!(
+            Formula::parse("pow(1)"),
+            Err(FormulaError::WrongArity { .. })
+        ));
+        assert!(matches!(Formula::parse("1 +"), Err(FormulaError::Syntax(_))));
+        assert!(matches!(Formula::parse("(1 + 2"), Err(FormulaError::Syntax(_))));
+        assert!(matches!(Formula::parse("a; b"), Err(FormulaError::Syntax(_))));
+        assert!(matches!(Formula::parse(""), Err(FormulaError::Syntax(_))));
+    }
+
+    #[test]
+    fn test_parse_rejects_excessive_nesting_and_length() {
+        let nested = format!("{}1{}", "(".repeat(40), ")".repeat(40));
+        assert_eq!(Formula::parse(&nested), Err(FormulaError::TooDeep));
+
+        let long = vec!["1"; 400].join("+");
+        assert!(matches!(Formula::parse(&long), Err(FormulaError::TooLong(_))));
+    }
+
+    #[test]
+    fn test_step_budget_limits_evaluation() {
+        let formula = Formula::parse("max(1, 2, 3, 4, 5, 6, 7, 8)").unwrap();
+        let err = formula
+            .evaluate_with_limits(
+                &HashMap::new(),
+                FormulaLimits { max_steps: 4 },
+            )
+            .unwrap_err();
+        assert_eq!(err, FormulaError::StepBudgetExceeded(4));
+    }
+
+    #[test]
+    fn test_division_by_zero_is_rejected() {
+        let formula = Formula::parse("1 / x").unwrap();
+        assert_eq!(
+            formula.evaluate(&vars(&[("x", 0.0)])),
+            Err(FormulaError::NonFinite)
+        );
+    }
+}
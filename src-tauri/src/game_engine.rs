@@ -1,32 +1,163 @@
-﻿use crate::event_log::{EventImportance, EventLog};
-use crate::game_state::{Character, GameState, GameTime, WorldState};
-use crate::models::{CharacterStats, Element, Grade, Lifespan, SpiritualRoot};
-use crate::npc::{CoreValue, Goal, NPC, NPCMemory, Personality, PersonalityTrait};
+﻿use crate::event_log::{EventArchive, EventImportance, EventLog, GameEvent};
+use crate::achievements::{
+    AchievementDefinition, AchievementProgress, AchievementStatus, UnlockedAchievement,
+};
+use crate::action_filters::ActionFilters;
+use crate::character_card::CharacterCard;
+use crate::character_sheet::CharacterSheet;
+use crate::cold_storage::{estimated_bytes, ColdStorage, MemoryUsageReport};
+use crate::combat_engine::{CombatMove, CombatState, CombatStatus};
+use crate::companion::{rank_options, ActionSuggestion};
+use crate::ending::{
+    detect_ending_cause, ending_variables, select_ending, AchievedEnding, EndingCause,
+    EndingDefinition, EndingGalleryView, EndingSummary,
+};
+use crate::event_triage::{TriageReport, BOOKKEEPING_EVENTS, TRIAGE_BATCH_SIZE};
+use crate::facts::FactStore;
+use crate::duel::{attach_duel_options, challenge_from, DuelChallenge, DuelOutcome};
+use crate::game_state::{Character, GameState, GameTime, Item, WorldState};
+use crate::generation_failure::GenerationFailure;
+use crate::llm_runtime_config::shared_llm_service;
+use crate::llm_service::LLMService;
+use crate::loot::LootState;
+use crate::market::{Market, MarketReceipt};
+use crate::models::{CharacterStats, Element, LearnedTechnique, Lifespan, RootTier, SpiritualRoot};
+use crate::npc::{
+    CoreValue, EmotionalState, Goal, NPCDetail, NPCMemory, NPCProfile, Personality,
+    PersonalityTrait, NPC,
+};
+use crate::npc_alerts::{attach_interruption_options, NpcEventNotice};
 use crate::npc_engine::{NPCDecision, NPCEngine, NPCEvent};
-use crate::numerical_system::NumericalSystem;
-use crate::plot_engine::{PlotEngine, PlotState, Scene};
-use crate::save_load::{SaveData, SaveInfo, SaveLoadSystem};
-use crate::script::{Script, ScriptType};
-use crate::script_manager::ScriptManager;
+use crate::relationship_graph::RelationshipGraph;
+use crate::npc_factory::{default_archetype_mix, NPCArchetype, NPCFactory};
+use crate::difficulty::DifficultySettings;
+use crate::economy::{ResourceDelta, ResourceKind};
+use crate::house_rules::HouseRules;
+use crate::idle_progression::{IdleProgression, IdleReport, MAX_IDLE_DAYS};
+use crate::karma::Karma;
+use crate::memory_consolidation::{ConsolidationReport, MemoryJob};
+use crate::npc_dialogue::NPCDialogue;
+use crate::npc_inbox::NpcInbox;
+use crate::numerical_system::{LocationSurvey, NumericalSystem, RealmLadder, StatChange};
+use crate::plot_engine::{ChapterState, OpeningPlot, PlayerOption, PlotEngine, PlotState, Scene};
+use crate::quest_system::{Quest, QuestLog};
+use crate::rng::GameRng;
+use crate::provenance::render_transcript;
+use crate::save_load::{
+    AutosaveSettings, SaveData, SaveInfo, SaveJob, SaveLoadSystem, SaveProgress, SaveProgressTracker,
+};
+use crate::script::{LocationKind, Script, ScriptType};
+use crate::script_manager::{ScriptDraftReport, ScriptManager, ScriptSection};
+use crate::settings_store::{AppSettings, SettingsStore};
+use crate::script_reload::{hot_reload, ScriptReloadReport, ScriptWatcher};
+use crate::side_story::{merge_into, SideStoryOutcome, SIDE_STORY_EVENT};
+use crate::state_sync::{StateDelta, StateJournal};
+use crate::timeline_branch::{BranchIndex, BranchInfo, BranchStore};
+use crate::turn_pipeline::game_over_reason;
+use crate::turn_gate::{TurnGate, TurnStatus, TurnTicket};
+use crate::storage_manager::{
+    StorageCleanupPolicy, StorageCleanupResult, StorageManager, StorageReport,
+};
+use crate::world_bulletin::{BulletinDesk, WorldBulletin};
+use crate::world_clock::context_at;
 use anyhow::{anyhow, Result};
-use std::sync::{Arc, Mutex};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// 生成开篇所需的剧情引擎与主角信息，由 `GameEngine::opening_draft` 给出
+pub struct OpeningDraft {
+    plot_engine: PlotEngine,
+    player_name: String,
+    realm_name: String,
+    spiritual_root: String,
+    location: String,
+}
+
+impl OpeningDraft {
+    pub async fn generate(&self) -> OpeningPlot {
+        self.plot_engine
+            .generate_opening_plot_async(
+                &self.player_name,
+                &self.realm_name,
+                &self.spiritual_root,
+                &self.location,
+            )
+            .await
+    }
+
+    /// 同步等待与 `generate` 相同的流程
+    pub fn generate_blocking(&self) -> OpeningPlot {
+        self.plot_engine.generate_opening_plot(
+            &self.player_name,
+            &self.realm_name,
+            &self.spiritual_root,
+            &self.location,
+        )
+    }
+}
 
 /// 管理游戏状态和逻辑的主游戏引擎
 pub struct GameEngine {
-    state: Arc<Mutex<Option<GameState>>>,
-    plot_state: Arc<Mutex<Option<PlotState>>>,
+    state: Arc<RwLock<Option<GameState>>>,
+    plot_state: Arc<RwLock<Option<PlotState>>>,
+    /// 进入秘境时压栈的主线剧情，离开秘境时出栈恢复
+    plot_stack: Vec<PlotState>,
     script_manager: ScriptManager,
     numerical_system: NumericalSystem,
     plot_engine: PlotEngine,
     npc_engine: NPCEngine,
-    event_log: Arc<Mutex<EventLog>>,
+    event_log: Arc<RwLock<EventLog>>,
     save_load_system: SaveLoadSystem,
+    save_progress: SaveProgressTracker,
+    state_journal: Arc<RwLock<StateJournal>>,
+    cold_storage: ColdStorage,
+    low_memory_mode: bool,
+    /// 距上次自动存档完成的玩家行动数
+    actions_since_autosave: u32,
+    npc_inbox: NpcInbox,
+    /// 剧本编辑器中正在编辑的草稿
+    script_draft: Option<Script>,
+    /// 开发模式下监视的剧本文件
+    script_watcher: Option<ScriptWatcher>,
+    /// 上次计入游玩时长的时刻
+    play_clock: Instant,
+    /// 玩家指定的随机种子，之后的开局都使用该种子
+    game_seed: Option<u64>,
+    /// 新解锁、尚未推送给前端的成就
+    unlocked_achievements: Vec<UnlockedAchievement>,
+    /// 冲着玩家来的 NPC 主动事件，尚未推送给前端
+    npc_notices: Vec<NpcEventNotice>,
+    /// 注入剧情、NPC 与剧本子系统的共享 LLMService，LLM 配置变化时刷新
+    llm_service: Option<Arc<LLMService>>,
+    /// 游戏与剧情状态的写入序号；回合在锁外生成，提交时序号变了说明期间状态已被改动
+    turn_sequence: u64,
+    /// 玩家回合的进行状态，同一时间只允许一个回合生成
+    turn_gate: TurnGate,
+    /// 已交给重要性复核的最后一条事件编号
+    triaged_through: u64,
 }
 
 const EVENT_LOG_MAX_EVENTS: usize = 600;
 const EVENT_LOG_MAX_IMPORTANT: usize = 200;
 const EVENT_LOG_MAX_ARCHIVES: usize = 50;
+const LOW_MEMORY_MAX_EVENTS: usize = 150;
+const LOW_MEMORY_MAX_IMPORTANT: usize = 50;
+const LOW_MEMORY_SPILL_BATCH: usize = 50;
+const RANDOM_SCRIPT_CAST_SIZE: usize = 4;
+const DISCOVERY_CAST_SIZE: usize = 2;
+/// 两次行动间隔超过该秒数时视为离开，只计入该上限
+const MAX_UNATTENDED_PLAY_SECS: u64 = 600;
+/// 存档目录下存放时间线的子目录
+const TIMELINE_DIR: &str = "timelines";
+
+fn discovery_archetype_mix() -> Vec<(NPCArchetype, u32)> {
+    vec![
+        (NPCArchetype::RogueCultivator, 3),
+        (NPCArchetype::Rival, 1),
+        (NPCArchetype::FellowDisciple, 1),
+    ]
+}
 
 #[derive(Debug, Clone)]
 struct RandomStartProfile {
@@ -39,19 +170,56 @@ struct RandomStartProfile {
 
 impl GameEngine {
     pub fn new() -> Self {
-        Self {
-            state: Arc::new(Mutex::new(None)),
-            plot_state: Arc::new(Mutex::new(None)),
+        let mut engine = Self {
+            state: Arc::new(RwLock::new(None)),
+            plot_state: Arc::new(RwLock::new(None)),
+            plot_stack: Vec::new(),
             script_manager: ScriptManager::new(),
             numerical_system: NumericalSystem::new(),
             plot_engine: PlotEngine::new(),
             npc_engine: NPCEngine::new(),
-            event_log: Arc::new(Mutex::new(EventLog::new())),
+            event_log: Arc::new(RwLock::new(EventLog::new())),
             save_load_system: SaveLoadSystem::new(),
-        }
+            save_progress: SaveProgressTracker::new(),
+            state_journal: Arc::new(RwLock::new(StateJournal::new())),
+            cold_storage: ColdStorage::for_session(Self::random_seed()),
+            low_memory_mode: false,
+            actions_since_autosave: 0,
+            npc_inbox: NpcInbox::new(),
+            script_draft: None,
+            script_watcher: None,
+            play_clock: Instant::now(),
+            game_seed: None,
+            unlocked_achievements: Vec::new(),
+            npc_notices: Vec::new(),
+            llm_service: None,
+            turn_sequence: 0,
+            turn_gate: TurnGate::new(),
+            triaged_through: 0,
+        };
+        engine.refresh_llm_service();
+        engine
+    }
+
+    /// 按当前 LLM 配置取共享的 LLMService 并注入各子系统；配置未变时沿用同一客户端与响应缓存
+    pub fn refresh_llm_service(&mut self) {
+        self.llm_service = shared_llm_service();
+        self.plot_engine.set_llm_service(self.llm_service.clone());
+        self.npc_engine.set_llm_service(self.llm_service.clone());
+        self.script_manager.set_llm_service(self.llm_service.clone());
+    }
+
+    pub fn llm_service(&self) -> Option<Arc<LLMService>> {
+        self.llm_service.clone()
     }
 
-    fn random_seed() -> u64 {
+    /// 指定冷存储目录，测试时避免写入用户目录
+    pub fn with_cold_storage(mut self, cold_storage: ColdStorage) -> Self {
+        self.cold_storage = cold_storage;
+        self
+    }
+
+    pub(crate) fn random_seed() -> u64 {
         let nanos = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .map(|d| d.as_nanos() as u64)
@@ -61,64 +229,21 @@ impl GameEngine {
         nanos ^ addr_mix.rotate_left(17)
     }
 
-    fn rng_next(seed: &mut u64) -> u64 {
-        let mut x = *seed;
-        if x == 0 {
-            x = 0x9E37_79B9_7F4A_7C15;
-        }
-        x ^= x << 13;
-        x ^= x >> 7;
-        x ^= x << 17;
-        *seed = x;
-        x
-    }
-
-    fn rand_u32(seed: &mut u64, min: u32, max: u32) -> u32 {
-        if min >= max {
-            return min;
-        }
-        let span = (max - min + 1) as u64;
-        min + (Self::rng_next(seed) % span) as u32
-    }
-
-    fn rand_f32(seed: &mut u64, min: f32, max: f32) -> f32 {
-        if min >= max {
-            return min;
-        }
-        let val = (Self::rng_next(seed) as f64 / u64::MAX as f64) as f32;
-        min + (max - min) * val
-    }
-
-    fn choose_weighted_index(seed: &mut u64, weights: &[u32]) -> usize {
-        let total: u32 = weights.iter().sum();
-        if total == 0 {
-            return 0;
-        }
-        let mut roll = Self::rand_u32(seed, 1, total);
-        for (idx, weight) in weights.iter().enumerate() {
-            if *weight == 0 {
-                continue;
-            }
-            if roll <= *weight {
-                return idx;
-            }
-            roll -= *weight;
-        }
-        weights.len().saturating_sub(1)
-    }
-
-    fn random_root_grade(seed: &mut u64) -> Grade {
-        // 单灵根10%，双灵根30%，三灵根40%，杂灵根20%
-        match Self::choose_weighted_index(seed, &[10, 30, 40, 20]) {
-            0 => Grade::Heavenly,
-            1 => Grade::Double,
-            2 => Grade::Triple,
-            _ => Grade::Pseudo,
-        }
+    /// 按剧本灵根品阶的稀有度权重抽取品阶，剧本未定义品阶时使用内置的四种
+    fn random_root_tier(rng: &mut GameRng, tiers: &[RootTier]) -> RootTier {
+        let builtin;
+        let tiers = if tiers.is_empty() {
+            builtin = RootTier::builtin();
+            &builtin
+        } else {
+            tiers
+        };
+        let weights = tiers.iter().map(|t| t.rarity_weight).collect::<Vec<u32>>();
+        tiers[rng.weighted_index(&weights).min(tiers.len() - 1)].clone()
     }
 
-    fn random_element(seed: &mut u64) -> Element {
-        match Self::rand_u32(seed, 0, 4) {
+    fn random_element(rng: &mut GameRng) -> Element {
+        match rng.range_u32(0, 4) {
             0 => Element::Metal,
             1 => Element::Wood,
             2 => Element::Water,
@@ -127,14 +252,17 @@ impl GameEngine {
         }
     }
 
-    fn build_random_start_profile(&self, script: &Script) -> Option<RandomStartProfile> {
+    fn build_random_start_profile(
+        &self,
+        script: &Script,
+        rng: &mut GameRng,
+    ) -> Option<RandomStartProfile> {
         let mut realms = script.world_setting.cultivation_realms.clone();
         if realms.is_empty() {
             return None;
         }
         realms.sort_by_key(|r| r.level);
 
-        let mut seed = Self::random_seed();
         let realm_weights = match realms.len() {
             0 => vec![],
             1 => vec![100],
@@ -152,43 +280,36 @@ impl GameEngine {
                 w
             }
         };
-        let realm_idx = Self::choose_weighted_index(&mut seed, &realm_weights)
+        let realm_idx = rng
+            .weighted_index(&realm_weights)
             .min(realms.len().saturating_sub(1));
         let mut starting_realm = realms[realm_idx].clone();
-        starting_realm.sub_level = Self::rand_u32(&mut seed, 0, 2);
-
-        let grade = Self::random_root_grade(&mut seed);
-        let affinity = match grade {
-            Grade::Heavenly => Self::rand_f32(&mut seed, 0.90, 1.00),
-            Grade::Double => Self::rand_f32(&mut seed, 0.78, 0.92),
-            Grade::Triple => Self::rand_f32(&mut seed, 0.62, 0.82),
-            Grade::Pseudo => Self::rand_f32(&mut seed, 0.40, 0.70),
-        };
+        starting_realm.sub_level = rng.range_u32(0, 2).min(
+            RealmLadder::from_world(&script.world_setting).peak_sub_level(starting_realm.level),
+        );
+
+        let tier = Self::random_root_tier(rng, &script.world_setting.root_tiers);
+        let affinity = rng.range_f32(tier.affinity_min, tier.affinity_max);
         let spiritual_root = SpiritualRoot {
-            element: Self::random_element(&mut seed),
-            grade: grade.clone(),
+            element: Self::random_element(rng),
+            grade: tier.id.clone(),
             affinity,
+            tier_multiplier: Some(tier.power_multiplier),
         };
 
         let starting_location = if script.world_setting.locations.is_empty() {
             script.initial_state.starting_location.clone()
         } else {
-            let idx = Self::rand_u32(&mut seed, 0, script.world_setting.locations.len() as u32 - 1)
-                as usize;
+            let idx = rng.range_u32(0, script.world_setting.locations.len() as u32 - 1) as usize;
             script.world_setting.locations[idx].id.clone()
         };
 
         let base_age_min = 15 + (realm_idx as u32 * 2);
         let base_age_max = base_age_min + 8;
-        let starting_age = Self::rand_u32(&mut seed, base_age_min, base_age_max);
-        let grade_bonus = match grade {
-            Grade::Heavenly => 35,
-            Grade::Double => 22,
-            Grade::Triple => 12,
-            Grade::Pseudo => 0,
-        };
+        let starting_age = rng.range_u32(base_age_min, base_age_max);
+        let grade_bonus = tier.lifespan_bonus as i32;
         let realm_bonus = (starting_realm.level.saturating_sub(1) * 8) as i32;
-        let jitter = Self::rand_u32(&mut seed, 0, 10) as i32;
+        let jitter = rng.range_u32(0, 10) as i32;
         let mut max_age = 95 + grade_bonus + realm_bonus + jitter;
         let min_required = starting_age as i32 + 40;
         if max_age < min_required {
@@ -208,6 +329,7 @@ impl GameEngine {
     pub fn initialize_game(&mut self, script: Script) -> Result<GameState> {
         // 验证剧本
         self.script_manager.validate_script(&script)?;
+        self.numerical_system = NumericalSystem::with_config(&script.numerical_config)?;
 
         // 从初始状态创建玩家角色
         let mut starting_realm = script
@@ -219,13 +341,15 @@ impl GameEngine {
             .ok_or_else(|| anyhow!("剧本中未定义修炼境界"))?
             .clone();
         let mut player_spiritual_root = script.initial_state.player_spiritual_root.clone();
+        player_spiritual_root.bind_tier(&script.world_setting.root_tiers);
         let mut starting_location = script.initial_state.starting_location.clone();
         let mut starting_age = script.initial_state.starting_age;
         let mut max_age = 100u32;
+        let mut rng = GameRng::new(self.game_seed.unwrap_or_else(Self::random_seed));
 
         // 随机剧本每次开局都重新随机角色信息，避免固定模板体验。
         if script.script_type == ScriptType::RandomGenerated {
-            if let Some(profile) = self.build_random_start_profile(&script) {
+            if let Some(profile) = self.build_random_start_profile(&script, &mut rng) {
                 starting_realm = profile.starting_realm;
                 player_spiritual_root = profile.spiritual_root;
                 starting_location = profile.starting_location;
@@ -238,6 +362,8 @@ impl GameEngine {
             spiritual_root: player_spiritual_root.clone(),
             cultivation_realm: starting_realm.clone(),
             techniques: Vec::new(),
+            status_effects: Default::default(),
+            insight: 0,
             lifespan: Lifespan {
                 current_age: starting_age,
                 max_age,
@@ -262,17 +388,39 @@ impl GameEngine {
         // 初始化游戏时间
         let game_time = GameTime::new(1, 1, 1);
 
-        // 创建游戏状态
+        // 创建游戏状态；应用偏好中设置了难度时优先于剧本默认难度
+        let difficulty = self.app_settings().difficulty.unwrap_or(script.difficulty);
+        let resources = script.economy.starting_resources;
         let mut game_state = GameState {
             script,
             player,
             world_state,
             game_time,
             event_history: Vec::new(),
+            version: 0,
+            loot_state: LootState::with_seed(rng.next_u64()),
+            house_rules: HouseRules::default(),
+            difficulty,
+            quests: QuestLog::default(),
+            combat: None,
+            ending: None,
+            play_time_secs: 0,
+            action_count: 0,
+            rng,
+            resources,
+            market: None,
+            stat_history: Vec::new(),
+            karma: Karma::default(),
         };
 
+        // 旧对局的冷存储与时间线不再需要，清理失败不影响开局。
+        let _ = self.cold_storage.clear();
+        let _ = self.branch_store().reset();
+        self.actions_since_autosave = 0;
+        self.play_clock = Instant::now();
+        self.npc_inbox.clear();
         {
-            let mut log = self.event_log.lock().unwrap();
+            let mut log = self.event_log.write().unwrap();
             *log = EventLog::new();
             log.log_event(
                 u64::from(game_state.game_time.total_days),
@@ -281,115 +429,375 @@ impl GameEngine {
                 EventImportance::Important,
             );
             game_state.event_history = log.all_events().to_vec();
+            self.triaged_through = 0;
         }
 
         // 初始化新局 NPC，避免沿用旧局状态。
         self.initialize_npcs_for_new_game(&game_state);
 
         // 存储状态
-        let mut state_lock = self.state.lock().unwrap();
-        *state_lock = Some(game_state.clone());
-
-        Ok(game_state)
+        Ok(self.store_game_state(game_state))
     }
 
     /// 获取当前游戏状态
     pub fn get_current_state(&self) -> Result<GameState> {
-        let state_lock = self.state.lock().unwrap();
+        let state_lock = self.state.read().unwrap();
         state_lock
             .clone()
             .ok_or_else(|| anyhow!("游戏未初始化"))
     }
 
     /// 更新当前游戏状态
-    pub fn update_current_state(&self, new_state: GameState) -> Result<()> {
-        let mut state_lock = self.state.lock().unwrap();
-        *state_lock = Some(new_state);
+    pub fn update_current_state(&mut self, new_state: GameState) -> Result<()> {
+        self.store_game_state(new_state);
+        Ok(())
+    }
+
+    /// 获取指定版本之后的状态增量，版本过旧时返回完整状态
+    pub fn get_state_since(&self, version: u64) -> Result<StateDelta> {
+        let state = self.get_current_state()?;
+        let plot_state = self.plot_state.read().unwrap().clone();
+        let journal = self.state_journal.read().unwrap();
+        Ok(StateDelta::build(version, &journal, &state, plot_state.as_ref()))
+    }
+
+    /// 当前状态同步版本号
+    pub fn state_version(&self) -> u64 {
+        self.state_journal.read().unwrap().current_version()
+    }
+
+    /// 指定随机种子：之后的开局都使用该种子，进行中的对局也从该种子重新开始随机
+    pub fn set_game_seed(&mut self, seed: u64) -> Result<()> {
+        self.game_seed = Some(seed);
+        if let Ok(mut state) = self.get_current_state() {
+            state.rng = GameRng::new(seed);
+            state.loot_state.rng_seed = state.rng.next_u64();
+            self.update_current_state(state)?;
+        }
         Ok(())
     }
 
     /// 检查游戏是否已初始化
     pub fn is_initialized(&self) -> bool {
-        let state_lock = self.state.lock().unwrap();
+        let state_lock = self.state.read().unwrap();
         state_lock.is_some()
     }
 
     /// 保存游戏到存档槽
-    pub fn save_game(&self, slot_id: u32) -> Result<()> {
-        let state_lock = self.state.lock().unwrap();
-        let game_state = state_lock
-            .as_ref()
-            .ok_or_else(|| anyhow!("无法保存：游戏未初始化"))?;
+    pub fn save_game(&mut self, slot_id: u32) -> Result<()> {
+        self.prepare_save_job(slot_id)?.run(|_| {})
+    }
 
+    /// 在持锁期间快照存档数据，返回可在后台线程执行的存档任务
+    pub fn prepare_save_job(&mut self, slot_id: u32) -> Result<SaveJob> {
+        if !self.is_initialized() {
+            return Err(anyhow!("无法保存：游戏未初始化"));
+        }
         self.log_event(
-            u64::from(game_state.game_time.total_days),
+            self.current_timestamp(),
             "save",
             format!("已保存到槽位 {}", slot_id),
             EventImportance::Normal,
         );
+        Ok(SaveJob::new(
+            slot_id,
+            self.snapshot_save_data()?,
+            self.save_load_system.clone(),
+            self.save_progress.clone(),
+        ))
+    }
+
+    /// 当前对局的完整存档数据（游戏与剧情状态、NPC 名册、事件日志）
+    fn snapshot_save_data(&self) -> Result<SaveData> {
+        let state_lock = self.state.read().unwrap();
+        let game_state = state_lock
+            .as_ref()
+            .ok_or_else(|| anyhow!("无法保存：游戏未初始化"))?;
+
         let mut save_state = game_state.clone();
         save_state.event_history = self.snapshot_event_history();
-        let plot_snapshot = {
-            let plot_lock = self.plot_state.lock().unwrap();
+        save_state.play_time_secs += self.unrecorded_play_secs();
+        let mut plot_snapshot = {
+            let plot_lock = self.plot_state.read().unwrap();
             plot_lock.clone()
         };
-        let save_data = SaveData::from_game_state_with_plot(save_state, plot_snapshot);
-        self.save_load_system.save_game(slot_id, &save_data)?;
+        // 存档需包含完整章节正文，已卸载的章节从冷存储读回。
+        if let Some(plot_state) = plot_snapshot.as_mut() {
+            self.cold_storage.rehydrate_chapters(plot_state)?;
+        }
+        let mut plot_stack = self.plot_stack.clone();
+        for plot_state in plot_stack.iter_mut() {
+            self.cold_storage.rehydrate_chapters(plot_state)?;
+        }
+        let mut npcs = self.npc_engine.all_npcs().cloned().collect::<Vec<NPC>>();
+        npcs.sort_by(|a, b| a.id.cmp(&b.id));
+        // 低内存模式下写出的事件原文属于本会话的冷存储，读档时会被清理，存档只保留归档摘要。
+        let archives = self
+            .event_log
+            .read()
+            .unwrap()
+            .archives()
+            .iter()
+            .cloned()
+            .map(|archive| EventArchive {
+                spill_file: None,
+                ..archive
+            })
+            .collect();
+        Ok(SaveData::from_game_state_with_plot(save_state, plot_snapshot)
+            .with_npcs(npcs)
+            .with_event_archives(archives)
+            .with_plot_stack(plot_stack))
+    }
+
+    fn branch_store(&self) -> BranchStore {
+        BranchStore::new(self.save_load_system.save_directory().join(TIMELINE_DIR))
+    }
+
+    /// 每完成一章在当前时间线记录一次检查点，供日后从该章节边界分叉
+    pub fn record_chapter_checkpoint(&self) -> Result<bool> {
+        let Some(chapter) = self
+            .plot_state
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|plot_state| plot_state.chapters.len() as u32)
+        else {
+            return Ok(false);
+        };
+        let store = self.branch_store();
+        if chapter == 0 || store.has_checkpoint(chapter) {
+            return Ok(false);
+        }
+        store.record_checkpoint(chapter, &self.snapshot_save_data()?)?;
+        Ok(true)
+    }
 
-        Ok(())
+    /// 从当前时间线的章节边界分叉出新时间线，当前进度不受影响
+    pub fn create_branch(&mut self, name: &str, chapter: Option<u32>) -> Result<BranchInfo> {
+        let branch = self.branch_store().create_branch(name, chapter)?;
+        self.log_event(
+            self.current_timestamp(),
+            "timeline_branch",
+            format!("自第 {} 章结束处分出时间线「{}」", branch.forked_at_chapter, branch.name),
+            EventImportance::Normal,
+        );
+        Ok(branch)
+    }
+
+    pub fn list_branches(&self) -> BranchIndex {
+        self.branch_store().index()
+    }
+
+    /// 保存当前时间线的进度后切换到另一条时间线，继续它上次离开时的进度
+    pub fn switch_branch(&mut self, branch_id: &str) -> Result<GameState> {
+        let store = self.branch_store();
+        let index = store.index();
+        let target = index
+            .get(branch_id)
+            .cloned()
+            .ok_or_else(|| anyhow!("未找到时间线 {}", branch_id))?;
+        if index.active_id == branch_id {
+            return self.get_current_state();
+        }
+        store.save_head(&self.snapshot_save_data()?)?;
+        let save_data = store.switch_to(branch_id)?;
+        self.save_load_system.validate_save_data(&save_data)?;
+        self.apply_save_data(save_data, format!("已切换到时间线「{}」", target.name), None)
+    }
+
+    /// 计入一次玩家行动及距上次计时以来的游玩时长
+    pub fn record_player_action(&mut self, state: &mut GameState) {
+        state.play_time_secs += self.unrecorded_play_secs();
+        state.action_count = state.action_count.saturating_add(1);
+        self.play_clock = Instant::now();
+    }
+
+    fn unrecorded_play_secs(&self) -> u64 {
+        self.play_clock
+            .elapsed()
+            .as_secs()
+            .min(MAX_UNATTENDED_PLAY_SECS)
+    }
+
+    /// 查询异步存档任务的进度
+    pub fn get_save_progress(&self, ticket: u64) -> Result<SaveProgress> {
+        self.save_progress
+            .get(ticket)
+            .ok_or_else(|| anyhow!("未找到存档任务 {}", ticket))
+    }
+
+    /// 获取存档系统副本，便于在后台线程执行读档I/O
+    /// 记录一次玩家行动，达到自动存档间隔时返回写入下一个轮换槽位的存档任务
+    pub fn autosave_job_if_due(&mut self) -> Result<Option<SaveJob>> {
+        let settings = self.save_load_system.autosave_settings();
+        if !settings.enabled {
+            return Ok(None);
+        }
+        self.actions_since_autosave = self.actions_since_autosave.saturating_add(1);
+        if self.actions_since_autosave < settings.interval_actions {
+            return Ok(None);
+        }
+        self.completion_save_job()
+    }
+
+    /// 本局结束时不论间隔立即自动存档，存档列表据此标记为已完结
+    pub fn completion_save_job(&mut self) -> Result<Option<SaveJob>> {
+        if !self.save_load_system.autosave_settings().enabled {
+            return Ok(None);
+        }
+        self.actions_since_autosave = 0;
+        let slot_id = self.save_load_system.next_autosave_slot()?;
+        self.prepare_save_job(slot_id).map(Some)
+    }
+
+    pub fn autosave_settings(&self) -> AutosaveSettings {
+        self.save_load_system.autosave_settings()
+    }
+
+    pub fn set_autosave_settings(&self, settings: AutosaveSettings) -> Result<AutosaveSettings> {
+        self.save_load_system.save_autosave_settings(&settings)?;
+        Ok(settings)
+    }
+
+    pub fn list_autosaves(&self) -> Result<Vec<SaveInfo>> {
+        self.save_load_system.list_autosaves()
+    }
+
+    fn settings_store(&self) -> SettingsStore {
+        SettingsStore::new(self.save_load_system.save_directory())
+    }
+
+    /// 跨对局保留的应用偏好，新开对局时作为剧情设置与难度的默认值
+    pub fn app_settings(&self) -> AppSettings {
+        self.settings_store().load()
+    }
+
+    pub fn set_app_settings(&self, settings: AppSettings) -> Result<AppSettings> {
+        self.settings_store().save(&settings)?;
+        Ok(settings)
+    }
+
+    pub fn save_load_system(&self) -> SaveLoadSystem {
+        self.save_load_system.clone()
     }
 
     /// 从存档槽加载游戏
     pub fn load_game(&mut self, slot_id: u32) -> Result<GameState> {
         let save_data = self.save_load_system.load_game(slot_id)?;
+        self.apply_loaded_save(slot_id, save_data, None)
+    }
+
+    /// 将已读取的存档数据应用到引擎；旧存档缺少剧情时优先使用事先生成好的 `opening`，
+    /// 免得持有引擎写锁时等待 LLM
+    pub fn apply_loaded_save(
+        &mut self,
+        slot_id: u32,
+        save_data: SaveData,
+        opening: Option<OpeningPlot>,
+    ) -> Result<GameState> {
+        self.apply_save_data(save_data, format!("已从槽位 {} 读取存档", slot_id), opening)
+    }
+
+    fn apply_save_data(
+        &mut self,
+        save_data: SaveData,
+        load_message: String,
+        opening: Option<OpeningPlot>,
+    ) -> Result<GameState> {
         let mut game_state = save_data.game_state;
+        let _ = self.cold_storage.clear();
+        self.actions_since_autosave = 0;
+        self.play_clock = Instant::now();
+        self.npc_inbox.clear();
+        self.plot_stack = save_data.plot_stack;
+        // 旧存档不含 NPC 名册，沿用当前名册
+        if !save_data.npcs.is_empty() {
+            self.npc_engine = NPCEngine::new();
+            self.npc_engine.set_llm_service(self.llm_service.clone());
+            for npc in save_data.npcs {
+                self.npc_engine.insert_npc(npc);
+            }
+        }
+        self.npc_engine.set_difficulty(&game_state.difficulty);
         {
-            let mut log = self.event_log.lock().unwrap();
-            *log = EventLog::from_events(game_state.event_history.clone());
+            let mut log = self.event_log.write().unwrap();
+            *log = EventLog::from_events(game_state.event_history.clone())
+                .with_archives(save_data.event_archives);
             log.log_event(
                 u64::from(game_state.game_time.total_days),
                 "load",
-                format!("已从槽位 {} 读取存档", slot_id),
+                load_message,
                 EventImportance::Important,
             );
             game_state.event_history = log.all_events().to_vec();
+            // 存档中的事件读档前已复核过，只复核此后新记下的
+            self.triaged_through = log.last_id();
         }
 
         // 存储加载的状态
-        let mut state_lock = self.state.lock().unwrap();
-        *state_lock = Some(game_state.clone());
-        drop(state_lock);
+        let game_state = self.store_game_state(game_state);
 
         // 优先恢复存档中的剧情状态，避免读档后剧情丢失。
         if let Some(saved_plot_state) = save_data.plot_state {
-            let mut plot_lock = self.plot_state.lock().unwrap();
-            *plot_lock = Some(saved_plot_state);
+            self.store_plot_state(saved_plot_state);
         } else {
             // 兼容旧存档：若无剧情状态，则重建默认开篇。
-            self.initialize_plot()?;
+            match opening {
+                Some(opening) => {
+                    self.initialize_plot_with_opening(
+                        opening.text.clone(),
+                        opening.player_options(),
+                    )?;
+                }
+                None => {
+                    self.initialize_plot()?;
+                }
+            }
         }
 
         Ok(game_state)
     }
 
-    /// 初始化剧情状态
+    /// 初始化剧情状态；同步等待与 `OpeningDraft::generate` 相同的开篇流程
     pub fn initialize_plot(&mut self) -> Result<PlotState> {
         let game_state = self
             .state
-            .lock()
+            .read()
             .unwrap()
             .as_ref()
             .cloned()
             .ok_or_else(|| anyhow!("无法初始化剧情：游戏未初始化"))?;
 
-        let opening_text = self.plot_engine.generate_opening_plot(
-            &game_state.player.name,
-            &game_state.player.stats.cultivation_realm.name,
-            &format!("{:?}", game_state.player.stats.spiritual_root.element),
-            &game_state.player.location,
-        );
+        let opening = self.opening_draft(&game_state).generate_blocking();
+        self.initialize_plot_with_opening(opening.text.clone(), opening.player_options())
+    }
 
-        self.initialize_plot_with_opening(opening_text, None)
+    /// 按给定状态准备开篇，取出后可以不持有引擎锁等待生成；读档时存档尚未应用到引擎
+    pub fn opening_draft(&self, game_state: &GameState) -> OpeningDraft {
+        let recap = game_state
+            .script
+            .story_so_far
+            .as_ref()
+            .map(|story| story.recap.clone());
+        OpeningDraft {
+            plot_engine: self
+                .plot_engine
+                .clone()
+                .with_language(
+                    self.app_settings()
+                        .plot_settings_for(game_state.script.language)
+                        .language,
+                )
+                .with_story_recap(recap),
+            player_name: game_state.player.name.clone(),
+            realm_name: game_state.player.stats.cultivation_realm.name.clone(),
+            spiritual_root: game_state
+                .script
+                .world_setting
+                .describe_root(&game_state.player.stats.spiritual_root),
+            location: game_state.player.location.clone(),
+        }
     }
 
     pub fn initialize_plot_with_opening(
@@ -399,15 +807,26 @@ impl GameEngine {
     ) -> Result<PlotState> {
         let game_state = self
             .state
-            .lock()
+            .read()
             .unwrap()
             .as_ref()
             .cloned()
             .ok_or_else(|| anyhow!("无法初始化剧情：游戏未初始化"))?;
-
+        let settings = self.app_settings().plot_settings_for(game_state.script.language);
+        self.plot_engine.set_language(settings.language);
+
+        // 从小说中途接续时，前情提要放在开篇之前，章节沿用原著的序号与标题
+        let story_so_far = game_state.script.story_so_far.clone();
+        let opening_text = match &story_so_far {
+            Some(story) => format!("{}\n\n{}", story.recap, opening_text),
+            None => opening_text,
+        };
+        let chapter_title = story_so_far
+            .as_ref()
+            .map_or_else(|| "第一章".to_string(), |story| story.start_chapter_title.clone());
         let mut initial_scene = Scene::new(
             "start".to_string(),
-            "第一章".to_string(),
+            chapter_title.clone(),
             opening_text.clone(),
             game_state.player.location,
         );
@@ -427,11 +846,19 @@ impl GameEngine {
         }
 
         let mut plot_state = PlotState::new(initial_scene);
+        // 剧情设置取自应用偏好，叙事语言优先跟随剧本的本地化语言
+        plot_state.settings = settings;
+        if let Some(story) = story_so_far {
+            plot_state.current_chapter = ChapterState::new(story.start_chapter, chapter_title);
+            for summary in story.chapter_summaries {
+                plot_state.add_to_history(summary);
+            }
+        }
         plot_state.append_segment(opening_text);
 
         // 存储剧情状态
-        let mut plot_lock = self.plot_state.lock().unwrap();
-        *plot_lock = Some(plot_state.clone());
+        self.plot_stack.clear();
+        let plot_state = self.store_plot_state(plot_state);
 
         self.log_event(
             self.current_timestamp(),
@@ -446,44 +873,314 @@ impl GameEngine {
 
     /// 获取当前剧情状态
     pub fn get_plot_state(&self) -> Result<PlotState> {
-        let plot_lock = self.plot_state.lock().unwrap();
+        let plot_lock = self.plot_state.read().unwrap();
         plot_lock
             .clone()
             .ok_or_else(|| anyhow!("剧情未初始化"))
     }
 
+    /// 最近一次剧情生成失败的结构化信息
+    pub fn get_last_failure(&self) -> Result<Option<GenerationFailure>> {
+        Ok(self.get_plot_state()?.last_generation_failure)
+    }
+
     /// 更新剧情状态
-    pub fn update_plot_state(&self, new_plot_state: PlotState) -> Result<()> {
-        let mut plot_lock = self.plot_state.lock().unwrap();
-        *plot_lock = Some(new_plot_state);
+    pub fn update_plot_state(&mut self, new_plot_state: PlotState) -> Result<()> {
+        self.store_plot_state(new_plot_state);
         Ok(())
     }
 
-    pub fn update_plot_settings(&self, settings: crate::plot_engine::PlotSettings) -> Result<PlotState> {
-        let mut plot_lock = self.plot_state.lock().unwrap();
-        let state = plot_lock
-            .as_mut()
-            .ok_or_else(|| anyhow!("剧情未初始化"))?;
+    pub fn update_plot_settings(&mut self, settings: crate::plot_engine::PlotSettings) -> Result<PlotState> {
+        let mut state = self.get_plot_state()?;
         state.settings = settings;
-        Ok(state.clone())
+        Ok(self.store_plot_state(state))
+    }
+
+    /// 把校验过的自拟选项追加到当前场景的选项末尾
+    pub fn add_custom_option(&mut self, mut option: PlayerOption) -> Result<PlayerOption> {
+        let mut state = self.get_plot_state()?;
+        option.id = state.current_scene.available_options.len();
+        state.current_scene.available_options.push(option.clone());
+        self.store_plot_state(state);
+        Ok(option)
+    }
+
+    /// 更新当前剧本的自由输入过滤配置，随存档保存
+    pub fn update_script_action_filters(&mut self, filters: &ActionFilters) -> Result<ActionFilters> {
+        let filters = filters.normalized().map_err(|e| anyhow!(e))?;
+        let mut state = self.get_current_state()?;
+        state.script.action_filters = filters.clone();
+        self.store_game_state(state);
+        Ok(filters)
+    }
+
+    /// 更新本局房规，随存档保存；每次变更都记入事件日志
+    pub fn update_house_rules(&mut self, house_rules: HouseRules) -> Result<HouseRules> {
+        let mut state = self.get_current_state()?;
+        if state.house_rules == house_rules {
+            return Ok(house_rules);
+        }
+        let timestamp = u64::from(state.game_time.total_days);
+        state.house_rules = house_rules;
+        self.store_game_state(state);
+        self.log_event(
+            timestamp,
+            "house_rules",
+            house_rules
+                .summary()
+                .unwrap_or_else(|| "房规：恢复标准规则".to_string()),
+            EventImportance::Important,
+        );
+        self.sync_event_history_to_state();
+        Ok(house_rules)
+    }
+
+    /// 调整本局难度，随存档保存并立即影响数值结算与 NPC 反应；每次变更都记入事件日志
+    pub fn set_difficulty(&mut self, difficulty: DifficultySettings) -> Result<DifficultySettings> {
+        difficulty.validate().map_err(|e| anyhow!(e))?;
+        let mut state = self.get_current_state()?;
+        self.npc_engine.set_difficulty(&difficulty);
+        if state.difficulty == difficulty {
+            return Ok(difficulty);
+        }
+        let timestamp = u64::from(state.game_time.total_days);
+        state.difficulty = difficulty;
+        self.store_game_state(state);
+        self.log_event(
+            timestamp,
+            "difficulty",
+            difficulty.summary(),
+            EventImportance::Important,
+        );
+        self.sync_event_history_to_state();
+        Ok(difficulty)
+    }
+
+    /// 放弃进行中的任务
+    pub fn abandon_quest(&mut self, quest_id: &str) -> Result<Quest> {
+        let mut state = self.get_current_state()?;
+        let day = state.game_time.total_days;
+        let quest = state
+            .quests
+            .abandon(quest_id, day)
+            .ok_or_else(|| anyhow!("任务不存在或已结束: {}", quest_id))?;
+        self.store_game_state(state);
+        self.log_event(
+            u64::from(day),
+            "quest_abandoned",
+            format!("放弃任务：{}", quest.title),
+            EventImportance::Normal,
+        );
+        self.sync_event_history_to_state();
+        Ok(quest)
+    }
+
+    /// 闭关快进若干日：不经剧情续写，按数值系统结算被动修炼、年岁与世界推演
+    pub fn advance_time_idle(&mut self, days: u32) -> Result<IdleReport> {
+        if days == 0 || days > MAX_IDLE_DAYS {
+            return Err(anyhow!("闭关天数须在 1 到 {} 日之间", MAX_IDLE_DAYS));
+        }
+        let mut state = self.get_current_state()?;
+        if let Some(reason) = game_over_reason(&state) {
+            return Err(anyhow!(reason));
+        }
+        let numerical_system = NumericalSystem::with_config(&state.script.numerical_config)?
+            .with_house_rules(&state.house_rules)
+            .with_difficulty(&state.difficulty)
+            .with_techniques(&state.script.world_setting.techniques)
+            .with_alignment(state.karma.alignment())
+            .with_inventory(&state.player.inventory);
+        let report = IdleProgression::new().simulate(&mut state, &numerical_system, days);
+        state.record_stat_changes("闭关", &report.stat_changes);
+        let timestamp = u64::from(state.game_time.total_days);
+        self.store_game_state(state);
+
+        self.log_event(
+            timestamp,
+            "idle_retreat",
+            report.summary.clone(),
+            EventImportance::Normal,
+        );
+        self.enqueue_npc_events(timestamp, &report.events());
+        self.sync_event_history_to_state();
+        Ok(report)
+    }
+
+    /// 把闭关的叙述作为插叙写入剧情
+    pub fn record_idle_narration(&mut self, report: &IdleReport) -> Result<()> {
+        let mut plot_state = self.get_plot_state()?;
+        for text in report.interludes() {
+            plot_state.append_interlude(text);
+        }
+        self.store_plot_state(plot_state);
+        Ok(())
+    }
+
+    /// 进入秘境：主线剧情压栈，换上秘境自己的剧情状态
+    pub fn enter_side_story(&mut self, realm_id: &str) -> Result<PlotState> {
+        let game_state = self.get_current_state()?;
+        let main_plot = self.get_plot_state()?;
+        if main_plot.side_story.is_some() {
+            return Err(anyhow!("已身在秘境之中"));
+        }
+        let realm = game_state
+            .script
+            .secret_realms
+            .iter()
+            .find(|realm| realm.id == realm_id)
+            .ok_or_else(|| anyhow!("未找到秘境 {}", realm_id))?;
+        if !realm.is_open_to(&game_state) {
+            return Err(anyhow!("此时无法进入{}", realm.name));
+        }
+
+        let realm_plot = realm.plot_state(&main_plot, &game_state);
+        self.log_event(
+            self.current_timestamp(),
+            SIDE_STORY_EVENT,
+            format!("踏入{}", realm.name),
+            EventImportance::Important,
+        );
+        self.sync_event_history_to_state();
+        self.plot_stack.push(main_plot);
+        Ok(self.store_plot_state(realm_plot))
+    }
+
+    /// 离开秘境：秘境中的段落与收获并回出栈的主线剧情；属性与背包的变化在秘境回合中已直接结算
+    pub fn exit_side_story(&mut self) -> Result<SideStoryOutcome> {
+        let realm_plot = self.get_plot_state()?;
+        let side_story = realm_plot
+            .side_story
+            .clone()
+            .ok_or_else(|| anyhow!("当前不在秘境之中"))?;
+        let mut main_plot = self
+            .plot_stack
+            .pop()
+            .ok_or_else(|| anyhow!("找不到进入秘境前的主线剧情"))?;
+        let outcome = side_story.outcome(&self.get_current_state()?);
+        merge_into(&mut main_plot, realm_plot, &outcome);
+        self.log_event(
+            self.current_timestamp(),
+            SIDE_STORY_EVENT,
+            outcome.summary.clone(),
+            EventImportance::Important,
+        );
+        self.sync_event_history_to_state();
+        self.store_plot_state(main_plot);
+        Ok(outcome)
+    }
+
+    pub fn in_side_story(&self) -> bool {
+        !self.plot_stack.is_empty()
+    }
+
+    /// 开始一个玩家回合，返回的凭据在回合结束前一直持有；已有回合在生成时报错
+    pub fn begin_turn(&self) -> Result<TurnTicket> {
+        self.turn_gate.begin()
+    }
+
+    pub fn turn_status(&self) -> TurnStatus {
+        self.turn_gate.status()
+    }
+
+    /// 当前的状态写入序号，回合开始时记下，提交时用于拒绝过期的回写
+    pub fn turn_sequence(&self) -> u64 {
+        self.turn_sequence
+    }
+
+    /// 回合开始后状态已被其他命令改动时拒绝提交，避免回合的回写覆盖这些改动
+    pub fn check_turn_sequence(&self, sequence: u64) -> Result<()> {
+        let current = self.turn_sequence();
+        if current != sequence {
+            return Err(anyhow!(
+                "游戏状态在本回合生成期间已被其他操作修改（序号 {} → {}），本回合未提交，请重试",
+                sequence,
+                current
+            ));
+        }
+        Ok(())
+    }
+
+    /// 写入游戏状态并登记增量同步版本
+    fn store_game_state(&mut self, new_state: GameState) -> GameState {
+        self.turn_sequence += 1;
+        self.record_game_state(new_state)
+    }
+
+    /// 只登记版本、不推进写入序号，用于从事件日志派生的字段
+    fn record_game_state(&mut self, mut new_state: GameState) -> GameState {
+        let mut state_lock = self.state.write().unwrap();
+        let mut journal = self.state_journal.write().unwrap();
+        new_state.version = journal.record_game_change(state_lock.as_ref(), &new_state);
+        *state_lock = Some(new_state.clone());
+        new_state
+    }
+
+    /// 写入剧情状态并登记增量同步版本
+    fn store_plot_state(&mut self, new_plot_state: PlotState) -> PlotState {
+        self.turn_sequence += 1;
+        self.record_plot_state(new_plot_state)
     }
 
+    /// 只登记版本、不推进写入序号，用于后台 NPC 插叙；回合提交时会把插叙并回
+    fn record_plot_state(&mut self, mut new_plot_state: PlotState) -> PlotState {
+        if self.low_memory_mode {
+            // 写盘失败时保留内存中的正文，下次写入时重试。
+            let _ = self.cold_storage.offload_chapters(&mut new_plot_state);
+        }
+        let mut plot_lock = self.plot_state.write().unwrap();
+        let mut journal = self.state_journal.write().unwrap();
+        new_plot_state.version = journal.record_plot_change(plot_lock.as_ref(), &new_plot_state);
+        *plot_lock = Some(new_plot_state.clone());
+        new_plot_state
+    }
 
+    /// 立即处理剧情事件的 NPC 反应
     pub fn process_npc_reactions_for_events(
         &mut self,
         events: &[String],
     ) -> Result<Vec<NPCDecision>> {
+        self.enqueue_npc_events(self.current_timestamp(), events);
+        self.drain_npc_inbox()
+    }
+
+    /// 回合内只把剧情事件放入 NPC 收件箱，处理推迟到世界推进或后台任务
+    pub fn enqueue_npc_events(&mut self, timestamp: u64, events: &[String]) {
+        for event_text in events {
+            self.npc_inbox.enqueue_story_event(timestamp, event_text.clone());
+        }
+    }
+
+    /// 开启插叙时，第一件找上门的 NPC 事件改写当前选项，玩家下一回合须先回应
+    pub(crate) fn interrupt_with(&mut self, notice: &NpcEventNotice) {
+        let Ok(mut plot_state) = self.get_plot_state() else {
+            return;
+        };
+        if !plot_state.settings.npc_interruptions || plot_state.npc_interruption.is_some() {
+            return;
+        }
+        attach_interruption_options(&mut plot_state.current_scene.available_options, notice);
+        plot_state.is_waiting_for_input = true;
+        plot_state.npc_interruption = Some(notice.clone());
+        self.record_plot_state(plot_state);
+    }
+
+    /// 取出尚未推送的 NPC 主动事件
+    pub fn take_npc_notices(&mut self) -> Vec<NpcEventNotice> {
+        std::mem::take(&mut self.npc_notices)
+    }
+
+    pub fn pending_npc_events(&self) -> usize {
+        self.npc_inbox.pending_len()
+    }
+
+    /// 处理收件箱中的全部事件，反应摘要留待并入下一回合的剧情上下文
+    pub fn drain_npc_inbox(&mut self) -> Result<Vec<NPCDecision>> {
+        let events = self.npc_inbox.take_pending();
+        if events.is_empty() {
+            return Ok(Vec::new());
+        }
         let mut all_decisions = Vec::new();
-        for (idx, event_text) in events.iter().enumerate() {
-            let event = NPCEvent {
-                timestamp: idx as u64 + 1,
-                description: event_text.clone(),
-                involved_npc_ids: Vec::new(),
-                importance: 0.7,
-                emotional_impact: 0.2,
-                affinity_impact: 1,
-                trust_impact: 1,
-            };
+        for event in events {
             self.log_event(
                 event.timestamp,
                 "story_event",
@@ -498,6 +1195,25 @@ impl GameEngine {
                     format!("{} -> {}", decision.npc_id, decision.action),
                     EventImportance::Normal,
                 );
+                let name = self
+                    .npc_engine
+                    .get_npc(&decision.npc_id)
+                    .map(|npc| npc.name.clone())
+                    .unwrap_or_else(|| decision.npc_id.clone());
+                self.npc_inbox.record(format!("{}：{}", name, decision.action));
+                if let Some(notice) = NpcEventNotice::from_decision(decision, &name, event.timestamp) {
+                    self.interrupt_with(&notice);
+                    self.npc_notices.push(notice);
+                }
+            }
+            for revelation in self.npc_engine.reveal_secrets(&event) {
+                self.npc_inbox.record(revelation.segment.clone());
+                self.log_event(
+                    revelation.timestamp,
+                    "secret_revealed",
+                    revelation.segment,
+                    EventImportance::Important,
+                );
             }
             all_decisions.extend(decisions);
         }
@@ -505,28 +1221,708 @@ impl GameEngine {
         Ok(all_decisions)
     }
 
-    fn initialize_npcs_for_new_game(&mut self, game_state: &GameState) {
-        self.npc_engine = NPCEngine::new();
+    /// NPC 档案，情绪先衰减到当前游戏日
+    pub fn get_npc_profile(&mut self, npc_id: &str) -> Result<NPCProfile> {
+        let game_state = self.get_current_state()?;
+        self.npc_engine
+            .decay_emotions(u64::from(game_state.game_time.total_days));
+        self.npc_engine
+            .get_npc(npc_id)
+            .map(|npc| npc.profile(&game_state.player.id))
+            .ok_or_else(|| anyhow!("NPC不存在: {}", npc_id))
+    }
 
-        let npc = NPC {
-            id: "npc_elder_1".to_string(),
-            name: "Sect Elder".to_string(),
-            stats: CharacterStats {
-                spiritual_root: game_state.player.stats.spiritual_root.clone(),
-                cultivation_realm: game_state.player.stats.cultivation_realm.clone(),
-                techniques: vec!["Guidance".to_string()],
-                lifespan: Lifespan {
-                    current_age: 80,
-                    max_age: 180,
-                    realm_bonus: 40,
-                },
-                combat_power: game_state.player.stats.combat_power.saturating_add(200),
+    /// 本局全部 NPC 的档案，按名字排序
+    pub fn list_npcs(&mut self) -> Result<Vec<NPCProfile>> {
+        let game_state = self.get_current_state()?;
+        self.npc_engine
+            .decay_emotions(u64::from(game_state.game_time.total_days));
+        let mut profiles = self
+            .npc_engine
+            .all_npcs()
+            .map(|npc| npc.profile(&game_state.player.id))
+            .collect::<Vec<NPCProfile>>();
+        profiles.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.id.cmp(&b.id)));
+        Ok(profiles)
+    }
+
+    /// NPC 的完整资料，包括目标、人际关系与最近的记忆
+    pub fn get_npc_detail(&mut self, npc_id: &str) -> Result<NPCDetail> {
+        let game_state = self.get_current_state()?;
+        self.npc_engine
+            .decay_emotions(u64::from(game_state.game_time.total_days));
+        let mut names = self
+            .npc_engine
+            .all_npcs()
+            .map(|npc| (npc.id.clone(), npc.name.clone()))
+            .collect::<HashMap<String, String>>();
+        names.insert(game_state.player.id.clone(), game_state.player.name.clone());
+        self.npc_engine
+            .get_npc(npc_id)
+            .map(|npc| npc.detail(&game_state.player.id, &names))
+            .ok_or_else(|| anyhow!("NPC不存在: {}", npc_id))
+    }
+
+    /// 对话对象的快照与玩家 id；对话生成在引擎锁外进行
+    pub fn dialogue_partner(&self, npc_id: &str) -> Result<(NPC, String)> {
+        let game_state = self.get_current_state()?;
+        let npc = self
+            .npc_engine
+            .get_npc(npc_id)
+            .cloned()
+            .ok_or_else(|| anyhow!("NPC不存在: {}", npc_id))?;
+        Ok((npc, game_state.player.id))
+    }
+
+    /// 把一轮对话写入 NPC 的关系与记忆，并记入事件日志
+    pub fn record_dialogue(&mut self, message: &str, dialogue: &NPCDialogue) -> Result<()> {
+        let game_state = self.get_current_state()?;
+        let timestamp = u64::from(game_state.game_time.total_days);
+        let exchange = format!("玩家：{} / {}：{}", message, dialogue.npc_name, dialogue.text);
+
+        self.update_player_relationship(
+            &dialogue.npc_id,
+            &game_state.player.id,
+            dialogue.affinity_delta,
+            dialogue.trust_delta,
+            &exchange,
+            timestamp,
+        );
+        let shift = (dialogue.affinity_delta.abs() + dialogue.trust_delta.abs()) as f32;
+        self.npc_engine.update_npc_memory(
+            &dialogue.npc_id,
+            &NPCEvent {
+                timestamp,
+                description: exchange.clone(),
+                involved_npc_ids: vec![dialogue.npc_id.clone()],
+                importance: (0.3 + shift * 0.03).min(0.9),
+                emotional_impact: dialogue.affinity_delta as f32 / 10.0,
+                affinity_impact: dialogue.affinity_delta,
+                trust_impact: dialogue.trust_delta,
             },
-            personality: Personality {
-                traits: vec![PersonalityTrait::Calm, PersonalityTrait::Righteous],
-                goals: vec![Goal {
-                    description: "Guide young disciples".to_string(),
-                    priority: 8,
+        );
+        self.log_event(timestamp, "npc_dialogue", exchange, EventImportance::Normal);
+        self.sync_event_history_to_state();
+        Ok(())
+    }
+
+    /// 与 NPC 开战；上一场战斗未结束时不能开启新的战斗
+    pub fn start_combat(&mut self, target_id: &str) -> Result<CombatState> {
+        let mut state = self.get_current_state()?;
+        if state.ending.is_some() {
+            return Err(anyhow!("本局已经结束"));
+        }
+        if state.combat.as_ref().is_some_and(|combat| !combat.is_over()) {
+            return Err(anyhow!("当前战斗尚未结束"));
+        }
+        let npc = self
+            .npc_engine
+            .get_npc(target_id)
+            .ok_or_else(|| anyhow!("NPC不存在: {}", target_id))?;
+        let day = state.game_time.total_days;
+        let combat = CombatState::new(&state.player, npc, day);
+        let description = format!("与{}交手", npc.name);
+        state.combat = Some(combat.clone());
+        self.store_game_state(state);
+        self.log_event(
+            u64::from(day),
+            "combat_started",
+            description,
+            EventImportance::Normal,
+        );
+        self.sync_event_history_to_state();
+        Ok(combat)
+    }
+
+    pub fn get_combat_state(&self) -> Result<Option<CombatState>> {
+        Ok(self.get_current_state()?.combat)
+    }
+
+    /// 结算玩家本回合的出手，战斗结束时把胜负写入事件日志与对手记忆
+    pub fn combat_round(&mut self, player_move: CombatMove) -> Result<CombatState> {
+        let mut state = self.get_current_state()?;
+        let mut combat = state
+            .combat
+            .take()
+            .filter(|combat| !combat.is_over())
+            .ok_or_else(|| anyhow!("当前没有进行中的战斗"))?;
+        combat.resolve_round(player_move).map_err(|e| anyhow!(e))?;
+        let player_id = state.player.id.clone();
+        let day = state.game_time.total_days;
+        let source = format!("与{}交手", combat.enemy.name);
+        for (kind, days) in combat.player_afflictions() {
+            state.player.stats.status_effects.apply(kind, days, source.clone());
+        }
+        state.combat = Some(combat.clone());
+        self.store_game_state(state);
+
+        if combat.is_over() {
+            self.conclude_combat(&combat, &player_id, u64::from(day));
+        }
+        Ok(combat)
+    }
+
+    /// 写入某回合的战斗描写；描写在引擎锁外生成
+    pub fn record_combat_narration(&mut self, round: u32, narration: String) -> Result<CombatState> {
+        let mut state = self.get_current_state()?;
+        let combat = state
+            .combat
+            .as_mut()
+            .ok_or_else(|| anyhow!("当前没有战斗"))?;
+        combat.set_narration(round, narration);
+        let combat = combat.clone();
+        self.store_game_state(state);
+        Ok(combat)
+    }
+
+    fn conclude_combat(&mut self, combat: &CombatState, player_id: &str, timestamp: u64) {
+        let enemy = &combat.enemy;
+        let (summary, importance, emotional_impact) = match combat.status {
+            CombatStatus::Victory => (format!("击败了{}", enemy.name), EventImportance::Important, -0.6),
+            CombatStatus::Defeat => (format!("败于{}之手", enemy.name), EventImportance::Important, 0.3),
+            CombatStatus::Fled => (format!("从与{}的战斗中脱身", enemy.name), EventImportance::Normal, 0.1),
+            _ => (format!("与{}未分胜负", enemy.name), EventImportance::Normal, 0.0),
+        };
+
+        // 刀兵相见总会伤及交情，落败一方更添怨气。
+        let affinity_delta = if combat.status == CombatStatus::Victory { -15 } else { -8 };
+        self.update_player_relationship(
+            &enemy.id,
+            player_id,
+            affinity_delta,
+            -5,
+            &summary,
+            timestamp,
+        );
+        self.npc_engine.update_npc_memory(
+            &enemy.id,
+            &NPCEvent {
+                timestamp,
+                description: format!("玩家{}", summary),
+                involved_npc_ids: vec![enemy.id.clone()],
+                importance: 0.8,
+                emotional_impact,
+                affinity_impact: affinity_delta,
+                trust_impact: -5,
+            },
+        );
+        self.log_event(timestamp, "combat_ended", summary, importance);
+        self.sync_event_history_to_state();
+    }
+
+    /// 新建空白剧本草稿，替换之前未完成的草稿
+    pub fn create_blank_script(&mut self) -> ScriptDraftReport {
+        let draft = self.script_manager.blank_script();
+        let report = self.script_manager.draft_report(&draft);
+        self.script_draft = Some(draft);
+        report
+    }
+
+    /// 替换草稿中的一个部分并重新校验；JSON 与该部分结构不符时草稿保持不变
+    pub fn update_script_section(
+        &mut self,
+        section: ScriptSection,
+        value: serde_json::Value,
+    ) -> Result<ScriptDraftReport> {
+        let draft = self
+            .script_draft
+            .as_mut()
+            .ok_or_else(|| anyhow!("尚未创建剧本草稿"))?;
+        let mut updated = draft.clone();
+        self.script_manager
+            .update_script_section(&mut updated, section, value)?;
+        *draft = updated;
+        Ok(self.script_manager.draft_report(draft))
+    }
+
+    pub fn validate_script_draft(&self) -> Result<ScriptDraftReport> {
+        self.script_draft
+            .as_ref()
+            .map(|draft| self.script_manager.draft_report(draft))
+            .ok_or_else(|| anyhow!("尚未创建剧本草稿"))
+    }
+
+    /// 开始监视本局剧本文件以便热更新；已在监视时只替换路径并返回 false
+    pub fn watch_script(&mut self, file_path: &str) -> Result<bool> {
+        self.get_current_state()?;
+        let started = self.script_watcher.is_none();
+        self.script_watcher = Some(ScriptWatcher::new(file_path));
+        Ok(started)
+    }
+
+    pub fn unwatch_script(&mut self) {
+        self.script_watcher = None;
+    }
+
+    pub fn is_watching_script(&self) -> bool {
+        self.script_watcher.is_some()
+    }
+
+    /// 剧本文件有改动时按本局语言重新加载并热更新，没有改动时返回 None
+    pub fn poll_script_reload(&mut self) -> Result<Option<ScriptReloadReport>> {
+        let Some(watcher) = self.script_watcher.as_mut() else {
+            return Ok(None);
+        };
+        if !watcher.poll_changed() {
+            return Ok(None);
+        }
+        let file_path = watcher.path().to_string_lossy().to_string();
+        let language = self.get_current_state()?.script.language;
+        let loaded = match language {
+            Some(language) => self.script_manager.load_custom_script_in(&file_path, language),
+            None => self.script_manager.load_custom_script(&file_path),
+        };
+        match loaded {
+            Ok(updated) => self.reload_script(&updated).map(Some),
+            Err(e) => {
+                let report = ScriptReloadReport::failed(e.to_string());
+                self.log_reload(&report);
+                Ok(Some(report))
+            }
+        }
+    }
+
+    /// 把修改后的剧本热更新进当前对局，不重新开局
+    pub fn reload_script(&mut self, updated: &Script) -> Result<ScriptReloadReport> {
+        let mut state = self.get_current_state()?;
+        let report = hot_reload(&mut state, updated);
+        if !report.applied.is_empty() {
+            self.store_game_state(state);
+        }
+        self.log_reload(&report);
+        Ok(report)
+    }
+
+    fn log_reload(&mut self, report: &ScriptReloadReport) {
+        if let Some(summary) = report.summary() {
+            self.log_event(
+                self.current_timestamp(),
+                "script_reload",
+                summary,
+                EventImportance::Normal,
+            );
+            self.sync_event_history_to_state();
+        }
+    }
+
+    /// 旁白答疑所需的当前状态与设定事实副本；回答在引擎锁外生成，不回写任何状态
+    pub fn narrator_briefing(&self) -> Result<(GameState, FactStore)> {
+        let state = self.get_current_state()?;
+        let facts = self
+            .get_plot_state()
+            .map(|plot_state| plot_state.canon_facts)
+            .unwrap_or_default();
+        Ok((state, facts))
+    }
+
+    /// 为当前场景的选项按规则打分排序，只读状态，不会执行任何选项
+    pub fn suggest_next_action(&self) -> Result<(GameState, Vec<ActionSuggestion>)> {
+        let state = self.get_current_state()?;
+        let options = self.get_plot_state()?.current_scene.available_options;
+        let npc_powers = self
+            .npc_engine
+            .all_npcs()
+            .map(|npc| (npc.id.clone(), npc.stats.combat_power))
+            .collect();
+        let suggestions = rank_options(&state, &options, &npc_powers);
+        Ok((state, suggestions))
+    }
+
+    /// 玩家所在城镇的市集：本批货仍在时沿用已有货架，否则按地点与轮换批次生成；吆喝在引擎锁外润色
+    pub fn market_for_visit(&self) -> Result<Market> {
+        let state = self.get_current_state()?;
+        Self::open_market(&state)
+    }
+
+    /// 存下润色后的市集；玩家已离开该城镇或货已轮换时不再保存
+    pub fn store_market(&mut self, market: Market) -> Result<Market> {
+        let mut state = self.get_current_state()?;
+        if !market.is_current(&state.player.location, state.game_time.total_days) {
+            return Err(anyhow!("{}的市集已经换了一批货", market.location_name));
+        }
+        let day = state.game_time.total_days;
+        let first_visit = !state
+            .market
+            .as_ref()
+            .is_some_and(|stored| stored.is_current(&market.location_id, day));
+        state.market = Some(market.clone());
+        self.store_game_state(state);
+        if first_visit {
+            self.log_event(
+                u64::from(day),
+                "market_visit",
+                format!("逛了{}的市集", market.location_name),
+                EventImportance::Normal,
+            );
+            self.sync_event_history_to_state();
+        }
+        Ok(market)
+    }
+
+    /// 以标价买下市集中的一件货品
+    pub fn buy_market_item(&mut self, item_id: &str) -> Result<MarketReceipt> {
+        let mut state = self.get_current_state()?;
+        let mut market = Self::open_market(&state)?;
+        let listing = market
+            .listing_mut(item_id)
+            .ok_or_else(|| anyhow!("市集没有这件货品: {}", item_id))?;
+        if listing.stock == 0 {
+            return Err(anyhow!("{}已经售罄", listing.item.name));
+        }
+        let cost = ResourceDelta::of(ResourceKind::SpiritStones, -(listing.price as i64));
+        let mut stat_changes = state.resources.apply(&cost).map_err(|e| anyhow!(e))?;
+        listing.stock -= 1;
+        let (item, price) = (listing.item.clone(), listing.price);
+        let description = format!("在{}以 {} 灵石买下{}", market.location_name, price, item.name);
+        stat_changes.push(Self::inventory_change(&state.player.inventory, 1));
+        state.player.inventory.push(item);
+        self.settle_trade(state, market, description, stat_changes)
+    }
+
+    /// 把背包中的一件物品卖给市集
+    pub fn sell_market_item(&mut self, item_id: &str) -> Result<MarketReceipt> {
+        let mut state = self.get_current_state()?;
+        let market = Self::open_market(&state)?;
+        let idx = state
+            .player
+            .inventory
+            .iter()
+            .position(|item| item.id == item_id)
+            .ok_or_else(|| anyhow!("背包中没有这件物品: {}", item_id))?;
+        let price = market.sell_price(&state.player.inventory[idx]);
+        let mut stat_changes = vec![Self::inventory_change(&state.player.inventory, -1)];
+        let item = state.player.inventory.remove(idx);
+        let earnings = ResourceDelta::of(ResourceKind::SpiritStones, price as i64);
+        stat_changes.extend(state.resources.apply(&earnings).map_err(|e| anyhow!(e))?);
+        let description = format!("在{}把{}卖了 {} 灵石", market.location_name, item.name, price);
+        self.settle_trade(state, market, description, stat_changes)
+    }
+
+    /// 按市集报价买卖灵草、矿石等资源
+    pub fn trade_market_resource(
+        &mut self,
+        kind: ResourceKind,
+        quantity: u64,
+        buying: bool,
+    ) -> Result<MarketReceipt> {
+        let mut state = self.get_current_state()?;
+        let market = Self::open_market(&state)?;
+        let delta = Self::market_numerics(&state)?
+            .trade_delta(kind, quantity, buying)
+            .ok_or_else(|| anyhow!("无法买卖{} {} 份", kind.label(), quantity))?;
+        let stat_changes = state.resources.apply(&delta).map_err(|e| anyhow!(e))?;
+        let stones = delta.get(ResourceKind::SpiritStones).unsigned_abs();
+        let description = if buying {
+            format!("在{}花 {} 灵石买入{} {} 份", market.location_name, stones, kind.label(), quantity)
+        } else {
+            format!("在{}卖出{} {} 份，得 {} 灵石", market.location_name, kind.label(), quantity, stones)
+        };
+        self.settle_trade(state, market, description, stat_changes)
+    }
+
+    fn open_market(state: &GameState) -> Result<Market> {
+        if state.ending.is_some() {
+            return Err(anyhow!("本局已经结束"));
+        }
+        let location = state
+            .script
+            .world_setting
+            .locations
+            .iter()
+            .find(|location| location.id == state.player.location)
+            .ok_or_else(|| anyhow!("地点不存在: {}", state.player.location))?;
+        if location.kind != LocationKind::City {
+            return Err(anyhow!("{}不是城镇，没有市集", location.name));
+        }
+        if let Some(market) = state
+            .market
+            .as_ref()
+            .filter(|market| market.is_current(&location.id, state.game_time.total_days))
+        {
+            return Ok(market.clone());
+        }
+        Ok(Market::generate(state, location, &Self::market_numerics(state)?))
+    }
+
+    fn market_numerics(state: &GameState) -> Result<NumericalSystem> {
+        Ok(NumericalSystem::with_config(&state.script.numerical_config)?
+            .with_difficulty(&state.difficulty)
+            .with_economy(&state.script.economy))
+    }
+
+    fn inventory_change(inventory: &[Item], delta: i64) -> StatChange {
+        StatChange {
+            stat_name: "inventory".to_string(),
+            old_value: inventory.len().to_string(),
+            new_value: (inventory.len() as i64 + delta).to_string(),
+        }
+    }
+
+    fn settle_trade(
+        &mut self,
+        mut state: GameState,
+        market: Market,
+        description: String,
+        stat_changes: Vec<StatChange>,
+    ) -> Result<MarketReceipt> {
+        let day = state.game_time.total_days;
+        state.market = Some(market.clone());
+        state.record_stat_changes("市集交易", &stat_changes);
+        self.store_game_state(state);
+        self.log_event(
+            u64::from(day),
+            "market_trade",
+            description.clone(),
+            EventImportance::Normal,
+        );
+        self.sync_event_history_to_state();
+        Ok(MarketReceipt {
+            description,
+            stat_changes,
+            market,
+        })
+    }
+
+    /// 本局是否满足自动结束的条件：战败身死、寿元耗尽、飞升或达成剧本目标
+    pub fn ending_cause(&self) -> Result<Option<EndingCause>> {
+        let state = self.get_current_state()?;
+        let variables = ending_variables(&state, self.npc_engine.all_npcs());
+        Ok(detect_ending_cause(&state, &state.script.endings, &variables))
+    }
+
+    /// 按剧本的结局条件选出本局结局，连同自动结束的原因与当前状态返回；终章在引擎锁外生成
+    pub fn pending_ending(&self) -> Result<(EndingDefinition, Option<EndingCause>, GameState)> {
+        let state = self.get_current_state()?;
+        if state.ending.is_some() {
+            return Err(anyhow!("本局已经结束"));
+        }
+        let variables = ending_variables(&state, self.npc_engine.all_npcs());
+        let cause = detect_ending_cause(&state, &state.script.endings, &variables);
+        Ok((select_ending(&state.script.endings, &variables), cause, state))
+    }
+
+    /// 记下本局结局，把终章写为最后一章，并计入跨局的结局图鉴
+    pub fn record_ending(
+        &mut self,
+        ending: &EndingDefinition,
+        cause: Option<EndingCause>,
+        finale: String,
+    ) -> Result<AchievedEnding> {
+        let mut state = self.get_current_state()?;
+        if state.ending.is_some() {
+            return Err(anyhow!("本局已经结束"));
+        }
+        let day = state.game_time.total_days;
+        let achieved = AchievedEnding {
+            ending_id: ending.id.clone(),
+            title: ending.title.clone(),
+            finale,
+            day,
+            cause,
+        };
+        state.ending = Some(achieved.clone());
+        let (script_id, script_name) = (state.script.id.clone(), state.script.name.clone());
+        self.store_game_state(state);
+        if let Ok(mut plot_state) = self.get_plot_state() {
+            plot_state.write_epilogue(&ending.title, &ending.description, achieved.finale.clone());
+            self.store_plot_state(plot_state);
+        }
+        self.log_event(
+            u64::from(day),
+            "ending",
+            format!("达成结局：{}", achieved.title),
+            EventImportance::Important,
+        );
+        self.sync_event_history_to_state();
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let mut gallery = self.save_load_system.ending_gallery();
+        gallery.record(&script_id, &script_name, &achieved, now);
+        self.save_load_system.save_ending_gallery(&gallery)?;
+        Ok(achieved)
+    }
+
+    /// 已结束对局的总结；本局尚未结束时报错
+    pub fn ending_summary(&self) -> Result<EndingSummary> {
+        EndingSummary::from_state(&self.get_current_state()?).ok_or_else(|| anyhow!("本局尚未结束"))
+    }
+
+    pub fn ending_gallery(&self) -> EndingGalleryView {
+        let gallery = self.save_load_system.ending_gallery();
+        let current_script = self
+            .get_current_state()
+            .map(|state| gallery.slots_for(&state.script.id, &state.script.endings))
+            .unwrap_or_default();
+        EndingGalleryView {
+            entries: gallery.entries,
+            current_script,
+        }
+    }
+
+    /// 先按当前游戏日衰减 NPC 记忆，再取需要整合记忆的 NPC；整合在引擎锁外进行
+    pub fn memory_consolidation_jobs(&mut self) -> Vec<MemoryJob> {
+        let forgotten = self.npc_engine.decay_memories(self.current_timestamp());
+        if forgotten > 0 {
+            self.log_event(
+                self.current_timestamp(),
+                "memory_decay",
+                format!("NPC 淡忘了 {} 条琐碎记忆", forgotten),
+                EventImportance::Normal,
+            );
+        }
+        self.npc_engine.memory_jobs()
+    }
+
+    pub fn apply_memory_consolidation(&mut self, jobs: &[MemoryJob], report: &ConsolidationReport) {
+        let applied = self.npc_engine.apply_memory_summaries(jobs, report);
+        if applied > 0 {
+            self.log_event(
+                self.current_timestamp(),
+                "memory_consolidation",
+                format!("整合了 {} 名 NPC 的长期记忆（LLM 调用 {} 次）", applied, report.llm_calls),
+                EventImportance::Normal,
+            );
+            self.sync_event_history_to_state();
+        }
+    }
+
+    /// 未复核的普通事件攒够一批时取出交给 `EventTriage`，不足一批时返回空
+    pub fn event_triage_batch(&mut self) -> Vec<GameEvent> {
+        let log = self.event_log.read().unwrap();
+        let mut pending = log
+            .normal_events_after(self.triaged_through)
+            .into_iter()
+            .filter(|event| !BOOKKEEPING_EVENTS.contains(&event.event_type.as_ref()))
+            .collect::<Vec<GameEvent>>();
+        if pending.len() < TRIAGE_BATCH_SIZE {
+            return Vec::new();
+        }
+        self.triaged_through = log.last_id();
+        let overflow = pending.len().saturating_sub(TRIAGE_BATCH_SIZE * 2);
+        pending.drain(..overflow);
+        pending
+    }
+
+    /// 把复核认定关乎主线的事件提升为重要，并交给 NPC 收件箱
+    pub fn apply_event_triage(&mut self, batch: &[GameEvent], report: &TriageReport) {
+        let candidates = batch
+            .iter()
+            .filter(|event| report.promoted_ids.contains(&event.id))
+            .cloned()
+            .collect::<Vec<GameEvent>>();
+        let promoted = self.event_log.write().unwrap().promote(&candidates);
+        if promoted.is_empty() {
+            return;
+        }
+        for event in &promoted {
+            self.npc_inbox
+                .enqueue_key_event(event.timestamp, event.description.to_string());
+        }
+        self.sync_event_history_to_state();
+    }
+
+    /// 取出上一批 NPC 反应摘要，供下一回合的剧情续写参考
+    pub fn take_npc_digest(&mut self) -> Vec<String> {
+        self.npc_inbox.take_digest()
+    }
+
+    /// 剧情续写参考的人物关系概况
+    pub fn relationship_prompt_lines(&self, player_id: &str) -> Vec<String> {
+        self.npc_engine.relationship_prompt_lines(player_id)
+    }
+
+    /// 寿元已尽的 NPC 名字，续写中不应再登场
+    pub fn departed_npc_names(&self) -> Vec<String> {
+        let mut names = self
+            .npc_engine
+            .all_npcs()
+            .filter(|npc| !npc.stats.lifespan.is_alive())
+            .map(|npc| npc.name.clone())
+            .collect::<Vec<String>>();
+        names.sort();
+        names
+    }
+
+    /// 玩家与全部 NPC 之间的关系网
+    pub fn relationship_graph(&self) -> Result<RelationshipGraph> {
+        let state = self.get_current_state()?;
+        Ok(self
+            .npc_engine
+            .relationship_graph(&state.player.id, &state.player.name))
+    }
+
+    /// 按事件日志、NPC 关系与寿元检查成就，新解锁的写入跨局成就簿并留待推送给前端
+    pub fn check_achievements(&mut self) -> Result<Vec<UnlockedAchievement>> {
+        let state = self.get_current_state()?;
+        let progress = {
+            let log = self.event_log.read().unwrap();
+            AchievementProgress::observe(log.all_events(), &state, self.npc_engine.all_npcs())
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let mut book = self.save_load_system.achievement_book();
+        let unlocked =
+            book.unlock_reached(&AchievementDefinition::builtin(), &progress, &state, now);
+        if !unlocked.is_empty() {
+            self.save_load_system.save_achievement_book(&book)?;
+            self.unlocked_achievements.extend(unlocked.iter().cloned());
+        }
+        Ok(unlocked)
+    }
+
+    /// 取出尚未推送的新解锁成就
+    pub fn take_unlocked_achievements(&mut self) -> Vec<UnlockedAchievement> {
+        std::mem::take(&mut self.unlocked_achievements)
+    }
+
+    pub fn achievements(&self) -> Vec<AchievementStatus> {
+        self.save_load_system
+            .achievement_book()
+            .statuses(&AchievementDefinition::builtin())
+    }
+
+    fn initialize_npcs_for_new_game(&mut self, game_state: &GameState) {
+        self.npc_engine = NPCEngine::new().with_difficulty(&game_state.difficulty);
+        self.npc_engine.set_llm_service(self.llm_service.clone());
+
+        // 剧本定义了人物时按定义登场，否则只安排一位默认的宗门长老。
+        let definitions = &game_state.script.world_setting.npcs;
+        if !definitions.is_empty() {
+            let mut factory = self.npc_factory_for(game_state, &game_state.player.location);
+            for definition in definitions {
+                if let Ok(npc) = factory.npc_from_definition(definition) {
+                    self.npc_engine.insert_npc(npc);
+                }
+            }
+            return;
+        }
+
+        let npc = NPC {
+            id: "npc_elder_1".to_string(),
+            name: "Sect Elder".to_string(),
+            stats: CharacterStats {
+                spiritual_root: game_state.player.stats.spiritual_root.clone(),
+                cultivation_realm: game_state.player.stats.cultivation_realm.clone(),
+                techniques: vec![LearnedTechnique::named("Guidance")],
+                status_effects: Default::default(),
+                insight: 0,
+                lifespan: Lifespan {
+                    current_age: 80,
+                    max_age: 180,
+                    realm_bonus: 40,
+                },
+                combat_power: game_state.player.stats.combat_power.saturating_add(200),
+            },
+            personality: Personality {
+                traits: vec![PersonalityTrait::Calm, PersonalityTrait::Righteous],
+                goals: vec![Goal {
+                    description: "Guide young disciples".to_string(),
+                    priority: 8,
                 }],
                 values: vec![CoreValue {
                     name: "Order".to_string(),
@@ -535,41 +1931,449 @@ impl GameEngine {
             },
             memory: NPCMemory::default(),
             relationships: std::collections::HashMap::new(),
+            secrets: Vec::new(),
+            location: Some(game_state.player.location.clone()),
+            bio: String::new(),
+            emotions: EmotionalState::default(),
         };
 
         self.npc_engine.insert_npc(npc);
+
+        // 随机剧本按原型模板生成一批配角，丰富开局人物。
+        if game_state.script.script_type == ScriptType::RandomGenerated {
+            let cast = self
+                .npc_factory_for(game_state, &game_state.player.location)
+                .generate_npc_cast(RANDOM_SCRIPT_CAST_SIZE, &default_archetype_mix());
+            if let Ok(cast) = cast {
+                for npc in cast {
+                    self.npc_engine.insert_npc(npc);
+                }
+            }
+        }
+    }
+
+    fn npc_factory_for(&self, game_state: &GameState, location_id: &str) -> NPCFactory {
+        let realms = game_state.script.world_setting.cultivation_realms.clone();
+        let reference_realm = realms
+            .iter()
+            .position(|realm| realm.name == game_state.player.stats.cultivation_realm.name)
+            .unwrap_or(0);
+        NPCFactory::new(realms, game_state.rng.derive_seed(location_id))
+            .with_root_tiers(game_state.script.world_setting.root_tiers.clone())
+            .with_realm_ladder(&game_state.script.world_setting.realm_ladder)
+            .with_reference_realm(reference_realm)
+            .with_player_id(game_state.player.id.clone())
+            .at_location(location_id)
+    }
+
+    /// 首次到达某地点时生成驻留NPC，已有NPC的地点不会重复生成
+    pub fn populate_location_on_discovery(&mut self, location_id: &str) -> Result<Vec<NPC>> {
+        let game_state = self.get_current_state()?;
+        if self
+            .npc_engine
+            .all_npcs()
+            .any(|npc| npc.location.as_deref() == Some(location_id))
+        {
+            return Ok(Vec::new());
+        }
+
+        let cast = self
+            .npc_factory_for(&game_state, location_id)
+            .generate_npc_cast(DISCOVERY_CAST_SIZE, &discovery_archetype_mix())
+            .map_err(|e| anyhow!(e))?;
+        for npc in &cast {
+            self.npc_engine.insert_npc(npc.clone());
+        }
+        Ok(cast)
     }
     /// 列出存档槽信息
+    /// 距上期满一个周期时按模板汇编新一期世界快报
+    pub fn publish_bulletin_if_due(&mut self) -> Result<Option<WorldBulletin>> {
+        let mut state = self.get_current_state()?;
+        let board = &state.world_state.bulletin_board;
+        if !board.is_due(state.game_time.total_days) {
+            return Ok(None);
+        }
+
+        let issue = board.next_issue();
+        let npcs = self.npc_engine.all_npcs().collect::<Vec<&NPC>>();
+        let bulletin = BulletinDesk::new().compile(&state, &npcs, issue);
+        state.world_state.bulletin_board.publish(bulletin.clone());
+        self.store_game_state(state);
+
+        if let Ok(mut plot_state) = self.get_plot_state() {
+            if plot_state.settings.bulletin_segments_enabled {
+                plot_state.append_interlude(bulletin.body.clone());
+                self.store_plot_state(plot_state);
+            }
+        }
+
+        self.log_event(
+            self.current_timestamp(),
+            "world_bulletin",
+            bulletin.title.clone(),
+            EventImportance::Normal,
+        );
+        self.sync_event_history_to_state();
+        Ok(Some(bulletin))
+    }
+
+    /// 宿怨达标且已过冷却期的 NPC 向玩家下战帖，每次至多一封，应战选项追加到当前选项中
+    pub fn issue_duel_challenge(&mut self) -> Result<Option<DuelChallenge>> {
+        let mut state = self.get_current_state()?;
+        let current_day = state.game_time.total_days;
+        let board = &state.world_state.duel_board;
+        let mut candidates = self
+            .npc_engine
+            .all_npcs()
+            .filter(|npc| board.can_challenge(&npc.id, current_day))
+            .filter_map(|npc| {
+                challenge_from(
+                    npc,
+                    &state.player,
+                    &state.script.world_setting.factions,
+                    current_day,
+                )
+            })
+            .collect::<Vec<DuelChallenge>>();
+        candidates.sort_by(|a, b| {
+            b.rivalry
+                .total_cmp(&a.rivalry)
+                .then_with(|| a.challenger_id.cmp(&b.challenger_id))
+        });
+        let Some(challenge) = candidates.into_iter().next() else {
+            return Ok(None);
+        };
+
+        let challenge = state.world_state.duel_board.issue(challenge);
+        let pending = state.world_state.duel_board.pending.clone();
+        self.store_game_state(state);
+
+        if let Ok(mut plot_state) = self.get_plot_state() {
+            if !plot_state.current_scene.available_options.is_empty() {
+                attach_duel_options(&mut plot_state.current_scene.available_options, &pending);
+                self.store_plot_state(plot_state);
+            }
+        }
+
+        self.log_event(
+            u64::from(current_day),
+            "duel_challenge",
+            format!(
+                "{}向你下了战帖，赌上{}",
+                challenge.challenger_name,
+                challenge.stake.label()
+            ),
+            EventImportance::Important,
+        );
+        self.npc_notices
+            .push(NpcEventNotice::from_challenge(&challenge, u64::from(current_day)));
+        self.sync_event_history_to_state();
+        Ok(Some(challenge))
+    }
+
+    /// 决斗结果影响挑战者对玩家的好感
+    pub fn apply_duel_outcome(&mut self, outcome: &DuelOutcome, timestamp: u64) {
+        self.update_player_relationship(
+            &outcome.challenger_id,
+            "player",
+            outcome.affinity_change,
+            0,
+            &outcome.description,
+            timestamp,
+        );
+    }
+
+    /// NPC 对玩家的好感变化按玩家业力与 NPC 秉性调整后写入关系
+    fn update_player_relationship(
+        &mut self,
+        npc_id: &str,
+        player_id: &str,
+        affinity_delta: i32,
+        trust_delta: i32,
+        event: &str,
+        timestamp: u64,
+    ) {
+        let karma = self
+            .get_current_state()
+            .map(|state| state.karma)
+            .unwrap_or_default();
+        let affinity_delta = self
+            .npc_engine
+            .get_npc(npc_id)
+            .map_or(affinity_delta, |npc| karma.adjust_affinity(npc, affinity_delta));
+        self.npc_engine.update_relationship(
+            npc_id,
+            player_id,
+            affinity_delta,
+            trust_delta,
+            event,
+            timestamp,
+        );
+    }
+
+    /// 获取指定期数的世界快报，未指定时返回最新一期
+    pub fn get_world_bulletin(&self, issue: Option<u32>) -> Result<Option<WorldBulletin>> {
+        let state = self.get_current_state()?;
+        let board = &state.world_state.bulletin_board;
+        Ok(match issue {
+            Some(issue) => board.get(issue).cloned(),
+            None => board.latest().cloned(),
+        })
+    }
+
+    /// 用润色后的版本替换已发行的快报
+    pub fn store_polished_bulletin(&mut self, bulletin: WorldBulletin) -> Result<()> {
+        let mut state = self.get_current_state()?;
+        if state.world_state.bulletin_board.replace(bulletin) {
+            self.store_game_state(state);
+        }
+        Ok(())
+    }
+
     pub fn list_saves(&self) -> Result<Vec<SaveInfo>> {
         self.save_load_system.list_saves()
     }
 
+    /// 按本局数值配置推导玩家的属性面板
+    pub fn character_sheet(&self) -> Result<CharacterSheet> {
+        let state = self.get_current_state()?;
+        let numerical_system = NumericalSystem::with_config(&state.script.numerical_config)?
+            .with_house_rules(&state.house_rules)
+            .with_difficulty(&state.difficulty)
+            .with_techniques(&state.script.world_setting.techniques)
+            .with_realm_ladder(&state.script.world_setting)
+            .with_inventory(&state.player.inventory);
+        Ok(CharacterSheet::build(&state, &numerical_system))
+    }
+
+    /// 估算各处修炼、休息的成效，灵气最盛处在前
+    pub fn survey_locations(&self) -> Result<Vec<LocationSurvey>> {
+        let state = self.get_current_state()?;
+        let numerical_system = NumericalSystem::with_config(&state.script.numerical_config)?
+            .with_difficulty(&state.difficulty)
+            .with_techniques(&state.script.world_setting.techniques);
+        let stats = &state.player.stats;
+        let mut surveys = state
+            .world_state
+            .locations
+            .values()
+            .map(|location| {
+                numerical_system.survey_location(
+                    stats,
+                    location,
+                    &context_at(&state, &location.id),
+                    location.id == state.player.location,
+                )
+            })
+            .collect::<Vec<LocationSurvey>>();
+        surveys.sort_by(|a, b| {
+            b.efficiency
+                .total_cmp(&a.efficiency)
+                .then_with(|| a.location_id.cmp(&b.location_id))
+        });
+        Ok(surveys)
+    }
+
+    /// 生成当前角色的名片
+    pub fn build_character_card(&self) -> Result<CharacterCard> {
+        let game_state = self
+            .get_current_state()
+            .map_err(|_| anyhow!("无法导出角色名片：游戏未初始化"))?;
+        let events = self.snapshot_event_history();
+        Ok(CharacterCard::from_game(
+            &game_state,
+            &events,
+            self.npc_engine.all_npcs(),
+        ))
+    }
+
+    /// 导出角色名片到JSON文件
+    pub fn export_character_card(&self, path: impl AsRef<std::path::Path>) -> Result<CharacterCard> {
+        let card = self.build_character_card()?;
+        card.export_to_file(path)?;
+        Ok(card)
+    }
+
     pub fn log_event(
-        &self,
+        &mut self,
         timestamp: u64,
         event_type: impl Into<String>,
         description: impl Into<String>,
         importance: EventImportance,
     ) {
-        let mut log = self.event_log.lock().unwrap();
+        let mut log = self.event_log.write().unwrap();
         log.log_event(timestamp, event_type, description, importance);
-        log.archive_if_needed(
-            EVENT_LOG_MAX_EVENTS,
-            EVENT_LOG_MAX_IMPORTANT,
-            EVENT_LOG_MAX_ARCHIVES,
-        );
+        if self.low_memory_mode {
+            // 攒满一批再写盘，避免每条事件一个文件；归档只剩摘要，不再按数量淘汰以免丢失磁盘上的原文。
+            if log.len() >= LOW_MEMORY_MAX_EVENTS + LOW_MEMORY_SPILL_BATCH {
+                log.archive_with_spill(
+                    LOW_MEMORY_MAX_EVENTS,
+                    LOW_MEMORY_MAX_IMPORTANT,
+                    usize::MAX,
+                    |events| self.cold_storage.spill_events(events).ok(),
+                );
+            }
+        } else {
+            log.archive_if_needed(
+                EVENT_LOG_MAX_EVENTS,
+                EVENT_LOG_MAX_IMPORTANT,
+                EVENT_LOG_MAX_ARCHIVES,
+            );
+        }
+    }
+
+    pub fn is_low_memory_mode(&self) -> bool {
+        self.low_memory_mode
+    }
+
+    /// 开启后已完结章节与归档事件写入磁盘；关闭时读回全部章节正文
+    pub fn set_low_memory_mode(&mut self, enabled: bool) -> Result<()> {
+        self.low_memory_mode = enabled;
+        let Ok(mut plot_state) = self.get_plot_state() else {
+            return Ok(());
+        };
+        if enabled {
+            self.cold_storage.offload_chapters(&mut plot_state)?;
+        } else {
+            self.cold_storage.rehydrate_chapters(&mut plot_state)?;
+        }
+        self.store_plot_state(plot_state);
+        Ok(())
     }
 
-    fn snapshot_event_history(&self) -> Vec<crate::event_log::GameEvent> {
-        let log = self.event_log.lock().unwrap();
+    /// 获取已完结章节，正文已卸载时从冷存储读回
+    pub fn get_chapter(&self, index: u32) -> Result<ChapterState> {
+        let plot_state = self.get_plot_state()?;
+        let chapter = plot_state
+            .chapters
+            .iter()
+            .find(|chapter| chapter.index == index)
+            .ok_or_else(|| anyhow!("未找到第{}章", index))?;
+        self.cold_storage.rehydrate_chapter(chapter)
+    }
+
+    /// 逐段对局记录（含进行中的章节），可选附带每段的生成溯源；小说导出不受影响
+    /// 小说润色的素材：文风设置与已写成的全部剧情片段（含当前章节），按先后排列
+    pub fn novel_polish_sources(&self) -> Result<(String, Vec<String>)> {
+        let mut plot_state = self.get_plot_state()?;
+        self.cold_storage.rehydrate_chapters(&mut plot_state)?;
+        let segments = plot_state
+            .chapters
+            .into_iter()
+            .chain(std::iter::once(plot_state.current_chapter))
+            .flat_map(|chapter| chapter.content)
+            .collect();
+        Ok((plot_state.settings.novel_style, segments))
+    }
+
+    pub fn build_transcript(&self, include_provenance: bool) -> Result<String> {
+        let mut plot_state = self
+            .get_plot_state()
+            .map_err(|_| anyhow!("无法导出对局记录：剧情未初始化"))?;
+        self.cold_storage.rehydrate_chapters(&mut plot_state)?;
+        let mut chapters = plot_state.chapters;
+        if !plot_state.current_chapter.content.is_empty() {
+            chapters.push(plot_state.current_chapter);
+        }
+        let transcript = render_transcript(&chapters, include_provenance);
+        // 非标准规则的对局在记录开头注明房规
+        Ok(match self.get_current_state()?.house_rules.summary() {
+            Some(summary) => format!("〔{}〕\n\n{}", summary, transcript),
+            None => transcript,
+        })
+    }
+
+    pub fn export_transcript(
+        &self,
+        path: impl AsRef<std::path::Path>,
+        include_provenance: bool,
+    ) -> Result<()> {
+        let transcript = self.build_transcript(include_provenance)?;
+        std::fs::write(path, transcript)?;
+        Ok(())
+    }
+
+    /// 完整事件历史：冷存储中的归档事件加上内存中的事件
+    pub fn full_event_history(&self) -> Result<Vec<GameEvent>> {
+        let log = self.event_log.read().unwrap();
+        let mut history = Vec::new();
+        for file_name in log.archives().iter().filter_map(|a| a.spill_file.as_deref()) {
+            history.extend(self.cold_storage.load_events(file_name)?);
+        }
+        history.extend_from_slice(log.all_events());
+        history.sort_by_key(|e| (e.timestamp, e.id));
+        Ok(history)
+    }
+
+    /// 存档与冷存储缓存的磁盘占用
+    pub fn storage_report(&self) -> Result<StorageReport> {
+        StorageManager::new(&self.save_load_system, &self.cold_storage).report()
+    }
+
+    /// 按策略删除过期缓存并压缩已完结的存档
+    pub fn run_storage_cleanup(
+        &self,
+        policy: &StorageCleanupPolicy,
+    ) -> Result<StorageCleanupResult> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        StorageManager::new(&self.save_load_system, &self.cold_storage).cleanup(policy, now)
+    }
+
+    /// 估算章节、事件与 NPC 记忆的内存占用，用于验证低内存模式效果
+    pub fn get_memory_usage_report(&self) -> MemoryUsageReport {
+        let mut report = MemoryUsageReport {
+            low_memory_mode: self.low_memory_mode,
+            offloaded_bytes_on_disk: self.cold_storage.disk_usage_bytes(),
+            ..MemoryUsageReport::default()
+        };
+
+        if let Some(plot_state) = self.plot_state.read().unwrap().as_ref() {
+            report.chapters_offloaded = plot_state.chapters.iter().filter(|c| c.offloaded).count();
+            report.chapters_in_memory = plot_state.chapters.len() - report.chapters_offloaded;
+            report.chapter_bytes = estimated_bytes(&plot_state.chapters)
+                + estimated_bytes(&plot_state.current_chapter);
+            report.estimated_total_bytes += estimated_bytes(plot_state);
+        }
+        if let Some(game_state) = self.state.read().unwrap().as_ref() {
+            report.estimated_total_bytes += estimated_bytes(game_state);
+        }
+        {
+            let log = self.event_log.read().unwrap();
+            report.events_in_memory = log.len();
+            report.event_bytes = estimated_bytes(log.all_events()) + estimated_bytes(log.archives());
+            report.event_archives = log.archives().len();
+            report.archived_event_files = log
+                .archives()
+                .iter()
+                .filter(|archive| archive.spill_file.is_some())
+                .count();
+            report.estimated_total_bytes += report.event_bytes;
+        }
+        for npc in self.npc_engine.all_npcs() {
+            report.npc_memory_entries += npc.memory.short_term.len()
+                + npc.memory.long_term.len()
+                + npc.memory.important_events.len();
+            report.npc_memory_bytes += estimated_bytes(&npc.memory);
+        }
+        report.estimated_total_bytes += report.npc_memory_bytes;
+        report
+    }
+
+    fn snapshot_event_history(&self) -> Vec<GameEvent> {
+        let log = self.event_log.read().unwrap();
         log.all_events().to_vec()
     }
 
-    fn sync_event_history_to_state(&self) {
+    /// 事件记录由事件日志派生，后台处理 NPC 事件时同步它不应使进行中的回合作废
+    pub(crate) fn sync_event_history_to_state(&mut self) {
         let history = self.snapshot_event_history();
-        let mut state_lock = self.state.lock().unwrap();
-        if let Some(ref mut state) = *state_lock {
+        if let Ok(mut state) = self.get_current_state() {
             state.event_history = history;
+            self.record_game_state(state);
         }
     }
 
@@ -591,8 +2395,11 @@ mod tests {
     use super::*;
     use crate::models::{CultivationRealm, Element, Grade, SpiritualRoot};
     use crate::numerical_system::Action;
-    use crate::plot_engine::{PlayerOption, PlotSettings};
-    use crate::script::{InitialState, Location, ScriptType, WorldSetting};
+    use crate::plot_engine::{new_option_uid, PlayerOption, PlotSettings};
+    use crate::script::{Climate, InitialState, Location, LocationKind, ScriptType, WorldSetting};
+    use crate::side_story::SecretRealm;
+    use crate::temperature_tuner::TemperatureBounds;
+    use crate::timeline_branch::MAIN_BRANCH_ID;
 
     fn create_test_script() -> Script {
         let mut world_setting = WorldSetting::new();
@@ -606,12 +2413,16 @@ mod tests {
                 name: "Azure Cloud Sect".to_string(),
                 description: "A peaceful cultivation sect".to_string(),
                 spiritual_energy: 1.0,
+                kind: LocationKind::Wilderness,
+                climate: Climate::Temperate,
             },
             Location {
                 id: "city".to_string(),
                 name: "Mortal City".to_string(),
                 description: "A bustling mortal city".to_string(),
                 spiritual_energy: 0.1,
+                kind: LocationKind::City,
+                climate: Climate::Temperate,
             },
         ];
 
@@ -619,8 +2430,9 @@ mod tests {
             player_name: "Test Player".to_string(),
             player_spiritual_root: SpiritualRoot {
                 element: Element::Fire,
-                grade: Grade::Heavenly,
+                grade: Grade::heavenly(),
                 affinity: 0.9,
+                tier_multiplier: None,
             },
             starting_location: "sect".to_string(),
             starting_age: 16,
@@ -701,7 +2513,7 @@ mod tests {
             game_state.player.stats.spiritual_root.element,
             Element::Fire
         );
-        assert_eq!(game_state.player.stats.spiritual_root.grade, Grade::Heavenly);
+        assert_eq!(game_state.player.stats.spiritual_root.grade, Grade::heavenly());
         assert_eq!(game_state.player.stats.cultivation_realm.name, "Qi Condensation");
         assert!(game_state.player.stats.combat_power > 0);
         assert!(game_state.player.inventory.is_empty());
@@ -720,6 +2532,21 @@ mod tests {
         assert!(game_state.world_state.global_events.is_empty());
     }
 
+    #[test]
+    fn test_survey_locations_ranks_by_spiritual_energy() {
+        let mut engine = GameEngine::new();
+        engine.initialize_game(create_test_script()).unwrap();
+
+        let surveys = engine.survey_locations().unwrap();
+        assert_eq!(surveys.len(), 2);
+        assert_eq!(surveys[0].location_id, "sect");
+        assert!(surveys[0].current);
+        assert!(!surveys[1].current);
+        assert!(surveys[0].efficiency > surveys[1].efficiency);
+        assert!(surveys[0].cultivation_gain >= surveys[1].cultivation_gain);
+        assert!(surveys[0].rest_recovery_days > surveys[1].rest_recovery_days);
+    }
+
     #[test]
     fn test_plot_initialization() {
         let mut engine = GameEngine::new();
@@ -742,6 +2569,46 @@ mod tests {
         assert_eq!(retrieved_plot.current_scene.id, "start");
     }
 
+    #[test]
+    fn test_branch_forks_at_chapter_boundary_without_touching_main_timeline() {
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let mut engine = GameEngine::new();
+        engine.save_load_system = SaveLoadSystem::with_directory(temp_dir.path().to_path_buf());
+        engine.initialize_game(create_test_script()).unwrap();
+        engine.initialize_plot().unwrap();
+
+        assert!(!engine.record_chapter_checkpoint().unwrap());
+        assert!(engine.create_branch("另一种选择", None).is_err());
+
+        let mut plot_state = engine.get_plot_state().unwrap();
+        plot_state.finalize_chapter(None, Some("初入宗门".to_string()));
+        engine.update_plot_state(plot_state).unwrap();
+        assert!(engine.record_chapter_checkpoint().unwrap());
+        assert!(!engine.record_chapter_checkpoint().unwrap());
+
+        let mut state = engine.get_current_state().unwrap();
+        state.player.location = "city".to_string();
+        engine.update_current_state(state).unwrap();
+
+        let branch = engine.create_branch("另一种选择", None).unwrap();
+        assert_eq!(branch.forked_at_chapter, 1);
+        assert_eq!(branch.parent_id.as_deref(), Some(MAIN_BRANCH_ID));
+        assert!(engine.create_branch("另一种选择", Some(1)).is_err());
+        assert!(engine.create_branch("第二章", Some(2)).is_err());
+
+        let forked = engine.switch_branch(&branch.id).unwrap();
+        assert_eq!(forked.player.location, "sect");
+        assert_eq!(engine.get_plot_state().unwrap().chapters.len(), 1);
+        assert_eq!(engine.list_branches().active_id, branch.id);
+
+        let main = engine.switch_branch(MAIN_BRANCH_ID).unwrap();
+        assert_eq!(main.player.location, "city");
+        assert!(engine.switch_branch("missing").is_err());
+        assert_eq!(engine.list_branches().branches.len(), 2);
+    }
+
     #[test]
     fn test_save_game() {
         use tempfile::TempDir;
@@ -758,6 +2625,57 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_side_story_stack_survives_save_and_restores_main_plot() {
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let mut engine = GameEngine::new();
+        engine.save_load_system = SaveLoadSystem::with_directory(temp_dir.path().to_path_buf());
+        let mut script = create_test_script();
+        script.secret_realms = vec![SecretRealm {
+            id: "sword_cave".to_string(),
+            name: "剑冢".to_string(),
+            description: "石壁上刻满残缺的剑痕。".to_string(),
+            location_id: "sect".to_string(),
+            min_realm_level: 2,
+            max_turns: 3,
+        }];
+        engine.initialize_game(script).unwrap();
+        engine.initialize_plot().unwrap();
+        let main_plot = engine.get_plot_state().unwrap();
+        assert!(engine.enter_side_story("sword_cave").is_err());
+        assert!(engine.exit_side_story().is_err());
+
+        let mut state = engine.get_current_state().unwrap();
+        state.script.secret_realms[0].min_realm_level = 1;
+        engine.update_current_state(state).unwrap();
+        let realm_plot = engine.enter_side_story("sword_cave").unwrap();
+        assert_eq!(realm_plot.current_scene.name, "剑冢");
+        assert!(engine.enter_side_story("sword_cave").is_err());
+
+        engine.save_game(1).unwrap();
+        engine.initialize_plot().unwrap();
+        assert!(!engine.in_side_story());
+        engine.load_game(1).unwrap();
+        assert!(engine.in_side_story());
+
+        let outcome = engine.exit_side_story().unwrap();
+        assert!(!outcome.forced);
+        assert_eq!(outcome.turns_spent, 0);
+        let restored = engine.get_plot_state().unwrap();
+        assert_eq!(restored.current_scene.id, main_plot.current_scene.id);
+        assert_eq!(
+            restored.current_chapter.content.last(),
+            Some(&outcome.summary)
+        );
+        assert!(restored
+            .current_chapter
+            .content
+            .contains(&"石壁上刻满残缺的剑痕。".to_string()));
+        assert!(!engine.in_side_story());
+    }
+
     #[test]
     fn test_save_without_initialization() {
         use tempfile::TempDir;
@@ -851,6 +2769,71 @@ mod tests {
                 >= game_state.player.stats.lifespan.current_age + 40
         );
         assert!(game_state.player.stats.combat_power > 0);
+        assert_eq!(engine.npc_engine.all_npcs().count(), 1 + RANDOM_SCRIPT_CAST_SIZE);
+    }
+
+    #[test]
+    fn test_same_seed_replays_random_start() {
+        let start = |seed: u64| {
+            let mut engine = GameEngine::new();
+            engine.set_game_seed(seed).unwrap();
+            let state = engine.initialize_game(create_random_script()).unwrap();
+            let mut npc_ids = engine
+                .npc_engine
+                .all_npcs()
+                .map(|npc| npc.id.clone())
+                .collect::<Vec<String>>();
+            npc_ids.sort();
+            (state, npc_ids)
+        };
+
+        let (first, first_npcs) = start(20240601);
+        let (replay, replay_npcs) = start(20240601);
+        assert_eq!(first.player.stats, replay.player.stats);
+        assert_eq!(first.player.location, replay.player.location);
+        assert_eq!(first.loot_state, replay.loot_state);
+        assert_eq!(first.rng, replay.rng);
+        assert_eq!(first.rng.seed, 20240601);
+        assert_eq!(first_npcs, replay_npcs);
+
+        let mut engine = GameEngine::new();
+        engine.initialize_game(create_random_script()).unwrap();
+        engine.set_game_seed(7).unwrap();
+        let reseeded = engine.get_current_state().unwrap();
+        assert_eq!(reseeded.rng.seed, 7);
+        assert_ne!(reseeded.loot_state, first.loot_state);
+    }
+
+    #[test]
+    fn test_random_start_and_npcs_use_script_root_tiers() {
+        let mut engine = GameEngine::new();
+        let mut script = create_random_script();
+        script.world_setting.root_tiers = vec![RootTier {
+            id: Grade::new("Chaos"),
+            name: "混沌灵根".to_string(),
+            rarity_weight: 1,
+            affinity_min: 0.2,
+            affinity_max: 0.3,
+            lifespan_bonus: 200,
+            power_multiplier: 5.0,
+        }];
+        script.initial_state.player_spiritual_root.grade = Grade::new("Chaos");
+        let game_state = engine.initialize_game(script).unwrap();
+
+        let root = &game_state.player.stats.spiritual_root;
+        assert_eq!(root.grade, Grade::new("Chaos"));
+        assert!((0.2..=0.3).contains(&root.affinity));
+        assert_eq!(root.power_multiplier(), 5.0);
+        assert!(game_state.player.stats.lifespan.max_age >= 295);
+        assert!(engine
+            .npc_engine
+            .all_npcs()
+            .all(|npc| npc.id == "player" || npc.stats.spiritual_root.grade == Grade::new("Chaos")));
+        assert!(game_state
+            .script
+            .world_setting
+            .describe_root(root)
+            .ends_with("混沌灵根"));
     }
 
     #[test]
@@ -879,6 +2862,36 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_legacy_save_uses_opening_drafted_before_load() {
+        let mut engine = GameEngine::new();
+        engine.initialize_game(create_test_script()).unwrap();
+        let mut save_data = engine.snapshot_save_data().unwrap();
+        save_data.plot_state = None;
+
+        let draft = engine.opening_draft(&save_data.game_state);
+        assert_eq!(
+            tauri::async_runtime::block_on(draft.generate()),
+            draft.generate_blocking()
+        );
+        let opening = OpeningPlot {
+            text: "旧档开篇".to_string(),
+            options: vec!["下山历练".to_string(), "闭关修炼".to_string()],
+        };
+        engine.apply_loaded_save(1, save_data, Some(opening)).unwrap();
+
+        let plot = engine.get_plot_state().unwrap();
+        assert_eq!(plot.current_scene.description, "旧档开篇");
+        assert_eq!(
+            plot.current_scene
+                .available_options
+                .iter()
+                .map(|option| option.description.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["下山历练", "闭关修炼"]
+        );
+    }
+
     #[test]
     fn test_initialize_plot_with_custom_opening_options() {
         let mut engine = GameEngine::new();
@@ -887,6 +2900,7 @@ mod tests {
 
         let custom_options = vec![PlayerOption {
             id: 0,
+            uid: new_option_uid(),
             description: "自定义开局选项".to_string(),
             requirements: vec![],
             action: Action::Rest,
@@ -901,44 +2915,223 @@ mod tests {
         assert_eq!(plot_state.current_scene.available_options[0].description, "自定义开局选项");
     }
 
-    #[test]
-    fn test_update_plot_settings_requires_initialized_plot() {
-        let engine = GameEngine::new();
-        let result = engine.update_plot_settings(PlotSettings::default());
-        assert!(result.is_err());
+    #[test]
+    fn test_initialize_plot_continues_from_novel_chapter() {
+        let mut engine = GameEngine::new();
+        let mut script = create_test_script();
+        script.story_so_far = Some(crate::script::StoryRecap {
+            start_chapter: 3,
+            start_chapter_title: "第三章 下山".to_string(),
+            recap: "【前情提要】第一章 入门：林风拜入青云宗。".to_string(),
+            chapter_summaries: vec![
+                "第一章 入门：林风拜入青云宗。".to_string(),
+                "第二章 筑基：林风闭关百日。".to_string(),
+            ],
+        });
+        engine.initialize_game(script).unwrap();
+
+        let plot_state = engine.initialize_plot().unwrap();
+        assert_eq!(plot_state.current_chapter.index, 3);
+        assert_eq!(plot_state.current_chapter.title, "第三章 下山");
+        assert_eq!(plot_state.plot_history.len(), 3);
+        assert_eq!(plot_state.plot_history[1], "第二章 筑基：林风闭关百日。");
+        let opening = &plot_state.plot_history[2];
+        assert!(opening.starts_with("【前情提要】"));
+        assert!(opening.contains("【接续】"));
+    }
+
+    #[test]
+    fn test_update_plot_settings_requires_initialized_plot() {
+        let mut engine = GameEngine::new();
+        let result = engine.update_plot_settings(PlotSettings::default());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_update_plot_settings_success() {
+        let mut engine = GameEngine::new();
+        let script = create_test_script();
+        engine.initialize_game(script).unwrap();
+        engine.initialize_plot().unwrap();
+
+        let settings = PlotSettings {
+            recap_enabled: false,
+            novel_style: "纪实风格".to_string(),
+            min_interactions_per_chapter: 2,
+            max_interactions_per_chapter: 4,
+            target_chapter_words_min: 1500,
+            target_chapter_words_max: 2500,
+            bulletin_segments_enabled: true,
+            temperature_bounds: TemperatureBounds { min: 0.4, max: 0.9 },
+            three_act_structure: true,
+            outline_planning: true,
+            npc_interruptions: true,
+            llm_judge_threshold: 0.8,
+            language: crate::script::ScriptLanguage::En,
+        };
+
+        let updated = engine.update_plot_settings(settings.clone()).unwrap();
+        assert_eq!(updated.settings, settings);
+    }
+
+    #[test]
+    fn test_update_plot_state_replaces_state() {
+        let mut engine = GameEngine::new();
+        let script = create_test_script();
+        engine.initialize_game(script).unwrap();
+        let mut plot = engine.initialize_plot().unwrap();
+        plot.current_scene.name = "更新后的章节".to_string();
+
+        engine.update_plot_state(plot.clone()).unwrap();
+        let current = engine.get_plot_state().unwrap();
+        assert_eq!(current.current_scene.name, "更新后的章节");
+    }
+
+    #[test]
+    fn test_get_state_since_returns_only_changed_sections() {
+        let mut engine = GameEngine::new();
+        engine.initialize_game(create_test_script()).unwrap();
+        let plot = engine.initialize_plot().unwrap();
+        let version = engine.state_version();
+        assert!(plot.version > 0 && plot.version <= version);
+
+        assert!(engine.get_state_since(version).unwrap().is_empty());
+
+        let mut state = engine.get_current_state().unwrap();
+        state.player.stats.combat_power += 10;
+        engine.update_current_state(state).unwrap();
+        engine.log_event(2, "cultivate", "闭关修炼", EventImportance::Normal);
+        engine.sync_event_history_to_state();
+
+        let delta = engine.get_state_since(version).unwrap();
+        assert!(!delta.full_resync);
+        assert!(delta.player.is_some());
+        assert!(delta.plot_state.is_none());
+        assert!(delta.script.is_none());
+        assert!(delta.event_history.is_none());
+        assert_eq!(delta.new_events.len(), 1);
+        assert_eq!(delta.new_events[0].event_type.as_ref(), "cultivate");
+        assert_eq!(delta.version, engine.state_version());
+    }
+
+    #[test]
+    fn test_publish_bulletin_when_period_elapsed() {
+        let mut engine = GameEngine::new();
+        engine.initialize_game(create_test_script()).unwrap();
+        let mut plot = engine.initialize_plot().unwrap();
+        plot.settings.bulletin_segments_enabled = true;
+        engine.update_plot_state(plot).unwrap();
+
+        assert!(engine.publish_bulletin_if_due().unwrap().is_none());
+
+        let mut state = engine.get_current_state().unwrap();
+        state.game_time.advance_days(6);
+        state.world_state.global_events.push(crate::game_state::GlobalEvent {
+            id: "beast_tide".to_string(),
+            name: "兽潮".to_string(),
+            description: "北山妖兽成群下山".to_string(),
+            timestamp: 5,
+        });
+        engine.update_current_state(state).unwrap();
+
+        let bulletin = engine.publish_bulletin_if_due().unwrap().unwrap();
+        assert_eq!(bulletin.issue, 1);
+        assert!(bulletin.headlines[0].contains("兽潮"));
+        assert!(engine.publish_bulletin_if_due().unwrap().is_none());
+        assert_eq!(engine.get_world_bulletin(None).unwrap(), Some(bulletin.clone()));
+
+        let plot = engine.get_plot_state().unwrap();
+        assert_eq!(plot.current_chapter.content.last(), Some(&bulletin.body));
+    }
+
+    #[test]
+    fn test_rival_issues_duel_challenge_as_special_option() {
+        let mut engine = GameEngine::new();
+        let state = engine.initialize_game(create_test_script()).unwrap();
+        let mut plot = engine.initialize_plot().unwrap();
+        plot.current_scene.available_options = vec![crate::plot_engine::PlayerOption {
+            id: 0,
+            uid: new_option_uid(),
+            description: "打坐修炼".to_string(),
+            requirements: Vec::new(),
+            action: crate::numerical_system::Action::Cultivate,
+        }];
+        engine.update_plot_state(plot).unwrap();
+
+        let mut relationships = std::collections::HashMap::new();
+        relationships.insert(
+            "player".to_string(),
+            crate::npc::Relationship {
+                target_id: "player".to_string(),
+                affinity: -70,
+                trust: -30,
+                history: Vec::new(),
+            },
+        );
+        engine.npc_engine.insert_npc(NPC {
+            id: "npc_rival".to_string(),
+            name: "赵烈".to_string(),
+            stats: state.player.stats.clone(),
+            personality: Personality {
+                traits: vec![PersonalityTrait::Aggressive],
+                goals: vec![Goal {
+                    description: "压过玩家一头".to_string(),
+                    priority: 9,
+                }],
+                values: Vec::new(),
+            },
+            memory: NPCMemory::default(),
+            relationships,
+            secrets: Vec::new(),
+            location: None,
+            bio: String::new(),
+            emotions: EmotionalState::default(),
+        });
+
+        let challenge = engine.issue_duel_challenge().unwrap().unwrap();
+        assert_eq!(challenge.challenger_id, "npc_rival");
+        assert!(engine.issue_duel_challenge().unwrap().is_none());
+        let notices = engine.take_npc_notices();
+        assert_eq!(notices.len(), 1);
+        assert_eq!(notices[0].kind, crate::npc_alerts::NpcEventKind::DuelChallenge);
+
+        let options = engine.get_plot_state().unwrap().current_scene.available_options;
+        assert_eq!(options.len(), 2);
+        assert!(options[1].description.contains("赵烈"));
+        let state = engine.get_current_state().unwrap();
+        assert_eq!(state.world_state.duel_board.pending.len(), 1);
+        assert!(state
+            .event_history
+            .iter()
+            .any(|e| e.event_type.as_ref() == "duel_challenge"));
+
+        let mut world_state = state.world_state.clone();
+        let outcome = crate::duel::settle_declined(&challenge, &mut world_state, 5);
+        engine.apply_duel_outcome(&outcome, 5);
+        let rival = engine.npc_engine.get_npc("npc_rival").unwrap();
+        assert_eq!(rival.relationships["player"].affinity, -73);
     }
 
     #[test]
-    fn test_update_plot_settings_success() {
+    fn test_get_state_since_unknown_version_forces_full_resync() {
         let mut engine = GameEngine::new();
-        let script = create_test_script();
-        engine.initialize_game(script).unwrap();
-        engine.initialize_plot().unwrap();
-
-        let settings = PlotSettings {
-            recap_enabled: false,
-            novel_style: "纪实风格".to_string(),
-            min_interactions_per_chapter: 2,
-            max_interactions_per_chapter: 4,
-            target_chapter_words_min: 1500,
-            target_chapter_words_max: 2500,
-        };
+        engine.initialize_game(create_test_script()).unwrap();
 
-        let updated = engine.update_plot_settings(settings.clone()).unwrap();
-        assert_eq!(updated.settings, settings);
+        let delta = engine.get_state_since(engine.state_version() + 5).unwrap();
+        assert!(delta.full_resync);
+        assert!(delta.player.is_some());
+        assert!(delta.event_history.is_some());
     }
 
     #[test]
-    fn test_update_plot_state_replaces_state() {
+    fn test_unchanged_update_keeps_state_version() {
         let mut engine = GameEngine::new();
-        let script = create_test_script();
-        engine.initialize_game(script).unwrap();
-        let mut plot = engine.initialize_plot().unwrap();
-        plot.current_scene.name = "更新后的章节".to_string();
+        engine.initialize_game(create_test_script()).unwrap();
+        let version = engine.state_version();
 
-        engine.update_plot_state(plot.clone()).unwrap();
-        let current = engine.get_plot_state().unwrap();
-        assert_eq!(current.current_scene.name, "更新后的章节");
+        let state = engine.get_current_state().unwrap();
+        engine.update_current_state(state).unwrap();
+        assert_eq!(engine.state_version(), version);
     }
 }
 
@@ -948,7 +3141,7 @@ mod tests {
 mod integration_tests {
     use super::*;
     use crate::models::{CultivationRealm, Element, Grade, SpiritualRoot};
-    use crate::script::{InitialState, Location, ScriptType, WorldSetting};
+    use crate::script::{Climate, InitialState, Location, LocationKind, ScriptType, WorldSetting};
     use tempfile::TempDir;
 
     fn create_test_script() -> Script {
@@ -963,12 +3156,16 @@ mod integration_tests {
                 name: "青云宗".to_string(),
                 description: "一个和平的修仙宗门".to_string(),
                 spiritual_energy: 1.0,
+                kind: LocationKind::Wilderness,
+                climate: Climate::Temperate,
             },
             Location {
                 id: "city".to_string(),
                 name: "凡人城市".to_string(),
                 description: "繁华的凡人城市".to_string(),
                 spiritual_energy: 0.1,
+                kind: LocationKind::City,
+                climate: Climate::Temperate,
             },
         ];
 
@@ -976,8 +3173,9 @@ mod integration_tests {
             player_name: "测试玩家".to_string(),
             player_spiritual_root: SpiritualRoot {
                 element: Element::Fire,
-                grade: Grade::Heavenly,
+                grade: Grade::heavenly(),
                 affinity: 0.9,
+                tier_multiplier: None,
             },
             starting_location: "sect".to_string(),
             starting_age: 16,
@@ -1011,7 +3209,7 @@ mod integration_tests {
 
         // 2. 修改游戏状态（模拟游戏进行）
         {
-            let mut state_lock = engine.state.lock().unwrap();
+            let mut state_lock = engine.state.write().unwrap();
             if let Some(ref mut state) = *state_lock {
                 state.player.stats.lifespan.current_age = 20;
                 state.game_time.year = 2;
@@ -1089,7 +3287,7 @@ mod integration_tests {
         for i in 1..=5 {
             // 修改状态
             {
-                let mut state_lock = engine.state.lock().unwrap();
+                let mut state_lock = engine.state.write().unwrap();
                 if let Some(ref mut state) = *state_lock {
                     state.game_time.year = i;
                 }
@@ -1125,10 +3323,10 @@ mod integration_tests {
 
         // 修改多个方面的状态
         {
-            let mut state_lock = engine.state.lock().unwrap();
+            let mut state_lock = engine.state.write().unwrap();
             if let Some(ref mut state) = *state_lock {
                 state.player.stats.lifespan.current_age = 25;
-                state.player.stats.techniques.push("火球术".to_string());
+                state.player.stats.techniques.push(LearnedTechnique::named("火球术"));
                 state.player.location = "city".to_string();
                 state.game_time.year = 3;
                 state.game_time.month = 6;
@@ -1143,7 +3341,7 @@ mod integration_tests {
         // 验证所有数据都被保留
         assert_eq!(loaded.player.stats.lifespan.current_age, 25);
         assert_eq!(loaded.player.stats.techniques.len(), 1);
-        assert_eq!(loaded.player.stats.techniques[0], "火球术");
+        assert_eq!(loaded.player.stats.techniques[0].name, "火球术");
         assert_eq!(loaded.player.location, "city");
         assert_eq!(loaded.game_time.year, 3);
         assert_eq!(loaded.game_time.month, 6);
@@ -1184,6 +3382,637 @@ mod integration_tests {
             .iter()
             .any(|e| e.event_type.as_ref() == "npc_reaction" && !e.description.is_empty()));
     }
+
+    #[test]
+    fn test_npc_inbox_drains_into_digest() {
+        let mut engine = GameEngine::new();
+        let script = create_test_script();
+        engine.initialize_game(script).unwrap();
+
+        let events = vec!["battle erupted near the sect gate".to_string()];
+        engine.enqueue_npc_events(engine.current_timestamp(), &events);
+        assert_eq!(engine.pending_npc_events(), 1);
+        assert!(engine.take_npc_digest().is_empty());
+
+        let reactions = engine.drain_npc_inbox().unwrap();
+        assert!(!reactions.is_empty());
+        assert_eq!(engine.pending_npc_events(), 0);
+        let digest = engine.take_npc_digest();
+        assert!(!digest.is_empty());
+        assert!(engine.take_npc_digest().is_empty());
+        assert!(engine.drain_npc_inbox().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_pressing_npc_reaction_is_pushed_and_interrupts_options() {
+        let mut engine = GameEngine::new();
+        let state = engine.initialize_game(create_test_script()).unwrap();
+        let mut plot = engine.initialize_plot().unwrap();
+        plot.settings.npc_interruptions = true;
+        plot.current_scene.available_options = vec![crate::plot_engine::PlayerOption {
+            id: 0,
+            uid: crate::plot_engine::new_option_uid(),
+            description: "打坐修炼".to_string(),
+            requirements: Vec::new(),
+            action: crate::numerical_system::Action::Cultivate,
+        }];
+        engine.update_plot_state(plot).unwrap();
+        engine.npc_engine.insert_npc(NPC {
+            id: "npc_elder".to_string(),
+            name: "严长老".to_string(),
+            stats: state.player.stats.clone(),
+            personality: Personality {
+                traits: vec![PersonalityTrait::Aggressive],
+                goals: Vec::new(),
+                values: Vec::new(),
+            },
+            memory: NPCMemory::default(),
+            relationships: std::collections::HashMap::new(),
+            secrets: Vec::new(),
+            location: None,
+            bio: String::new(),
+            emotions: EmotionalState::default(),
+        });
+
+        let events = vec!["主角在宗门大比上当众羞辱执法弟子".to_string()];
+        engine.process_npc_reactions_for_events(&events).unwrap();
+
+        let notices = engine.take_npc_notices();
+        let notice = notices.iter().find(|n| n.npc_id == "npc_elder").unwrap();
+        assert!(notice.summary.starts_with("严长老"));
+        assert!(engine.take_npc_notices().is_empty());
+
+        let plot = engine.get_plot_state().unwrap();
+        assert_eq!(plot.npc_interruption.as_ref(), notices.first());
+        assert!(plot.current_scene.available_options[0]
+            .requirements
+            .iter()
+            .any(|r| r == crate::npc_alerts::INTERRUPTION_REQUIREMENT));
+        assert!(!plot
+            .current_scene
+            .available_options
+            .iter()
+            .any(|option| option.description == "打坐修炼"));
+    }
+
+    #[test]
+    fn test_populate_location_on_discovery_only_once() {
+        let mut engine = GameEngine::new();
+        engine.initialize_game(create_test_script()).unwrap();
+
+        let first = engine.populate_location_on_discovery("city").unwrap();
+        assert!(!first.is_empty());
+        assert!(first.iter().all(|npc| npc.location.as_deref() == Some("city")));
+
+        let second = engine.populate_location_on_discovery("city").unwrap();
+        assert!(second.is_empty());
+    }
+
+    #[test]
+    fn test_event_triage_promotes_batch_and_notifies_npcs() {
+        let mut engine = GameEngine::new();
+        engine.initialize_game(create_test_script()).unwrap();
+        for day in 0..TRIAGE_BATCH_SIZE as u64 - 1 {
+            engine.log_event(day, "player_action", format!("第{}次打坐", day), EventImportance::Normal);
+        }
+        assert!(engine.event_triage_batch().is_empty());
+
+        engine.log_event(9, "player_free_text", "与大师兄割袍断义", EventImportance::Normal);
+        let batch = engine.event_triage_batch();
+        assert_eq!(batch.len(), TRIAGE_BATCH_SIZE);
+        assert!(engine.event_triage_batch().is_empty());
+
+        let pending = engine.pending_npc_events();
+        let report = TriageReport {
+            promoted_ids: vec![batch.last().unwrap().id],
+            source: crate::event_triage::TriageSource::Llm,
+        };
+        engine.apply_event_triage(&batch, &report);
+        let state = engine.get_current_state().unwrap();
+        let promoted = state.event_history.iter().find(|e| e.id == report.promoted_ids[0]).unwrap();
+        assert_eq!(promoted.importance, EventImportance::Important);
+        assert_eq!(engine.pending_npc_events(), pending + 1);
+    }
+
+    #[test]
+    fn test_export_character_card_includes_important_deeds() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut engine = GameEngine::new();
+        engine.initialize_game(create_test_script()).unwrap();
+        engine.log_event(3, "breakthrough", "突破至筑基期", EventImportance::Important);
+        engine.log_event(4, "rest", "静坐调息", EventImportance::Normal);
+
+        let output = temp_dir.path().join("card.json");
+        let card = engine.export_character_card(&output).unwrap();
+
+        assert_eq!(card.name, "测试玩家");
+        assert_eq!(card.notable_deeds[0].event_type, "breakthrough");
+        assert!(card.notable_deeds.iter().all(|deed| deed.event_type != "rest"));
+        let content = std::fs::read_to_string(&output).unwrap();
+        let restored: CharacterCard = serde_json::from_str(&content).unwrap();
+        assert_eq!(restored, card);
+    }
+
+    #[test]
+    fn test_export_transcript_includes_provenance_on_request() {
+        use crate::provenance::{FallbackKind, SegmentProvenance};
+
+        let temp_dir = TempDir::new().unwrap();
+        let mut engine = GameEngine::new();
+        engine.initialize_game(create_test_script()).unwrap();
+        engine.initialize_plot().unwrap();
+        let mut plot_state = engine.get_plot_state().unwrap();
+        plot_state.append_segment_with_provenance(
+            "山门初试，少年一剑惊四座。".to_string(),
+            Some(SegmentProvenance::fallback(FallbackKind::Preset)),
+        );
+        engine.update_plot_state(plot_state).unwrap();
+
+        let output = temp_dir.path().join("transcript.txt");
+        engine.export_transcript(&output, false).unwrap();
+        let plain = std::fs::read_to_string(&output).unwrap();
+        assert!(plain.contains("一剑惊四座"));
+        assert!(!plain.contains("溯源"));
+
+        let audited = engine.build_transcript(true).unwrap();
+        assert!(audited.contains("回退 预设文本"));
+    }
+
+    #[test]
+    fn test_house_rules_recorded_in_save_and_transcript() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut engine = GameEngine::new();
+        engine.save_load_system = SaveLoadSystem::with_directory(temp_dir.path().to_path_buf());
+        engine.initialize_game(create_test_script()).unwrap();
+        engine.initialize_plot().unwrap();
+        let mut plot_state = engine.get_plot_state().unwrap();
+        plot_state.append_segment("少年当众口出狂言。".to_string());
+        engine.update_plot_state(plot_state).unwrap();
+        assert!(!engine.build_transcript(false).unwrap().contains("房规"));
+
+        let rules = HouseRules {
+            skip_reasonableness_check: true,
+            ..HouseRules::default()
+        };
+        engine.update_house_rules(rules).unwrap();
+        let state = engine.get_current_state().unwrap();
+        assert!(state
+            .event_history
+            .iter()
+            .any(|e| e.event_type.as_ref() == "house_rules"));
+        assert!(engine
+            .build_transcript(false)
+            .unwrap()
+            .starts_with("〔房规：关闭合理性判定〕"));
+
+        engine.save_game(1).unwrap();
+        let saves = engine.list_saves().unwrap();
+        assert_eq!(saves[0].house_rules, vec!["关闭合理性判定".to_string()]);
+        engine.update_house_rules(HouseRules::default()).unwrap();
+        engine.load_game(1).unwrap();
+        assert_eq!(engine.get_current_state().unwrap().house_rules, rules);
+    }
+
+    #[test]
+    fn test_script_difficulty_is_default_and_set_difficulty_is_logged() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut engine = GameEngine::new();
+        engine.save_load_system = SaveLoadSystem::with_directory(temp_dir.path().to_path_buf());
+        let mut script = create_test_script();
+        script.difficulty = DifficultySettings::easy();
+        let state = engine.initialize_game(script).unwrap();
+        assert_eq!(state.difficulty, DifficultySettings::easy());
+
+        let invalid = DifficultySettings {
+            resource_scarcity: 2.0,
+            ..DifficultySettings::hard()
+        };
+        assert!(engine.set_difficulty(invalid).is_err());
+        engine.set_difficulty(DifficultySettings::hard()).unwrap();
+        let state = engine.get_current_state().unwrap();
+        assert_eq!(state.difficulty, DifficultySettings::hard());
+        assert!(state
+            .event_history
+            .iter()
+            .any(|e| e.event_type.as_ref() == "difficulty" && e.description.contains("寿元压力 1.50")));
+
+        engine.save_game(1).unwrap();
+        engine.set_difficulty(DifficultySettings::normal()).unwrap();
+        engine.load_game(1).unwrap();
+        assert_eq!(engine.get_current_state().unwrap().difficulty, DifficultySettings::hard());
+    }
+
+    #[test]
+    fn test_app_settings_are_defaults_for_new_games() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut engine = GameEngine::new();
+        engine.save_load_system = SaveLoadSystem::with_directory(temp_dir.path().to_path_buf());
+        let mut settings = AppSettings::default();
+        settings.plot_settings.novel_style = "古典章回体".to_string();
+        settings.plot_settings.outline_planning = true;
+        settings.plot_settings.language = crate::script::ScriptLanguage::En;
+        settings.difficulty = Some(DifficultySettings::hard());
+        engine.set_app_settings(settings).unwrap();
+
+        let mut script = create_test_script();
+        script.difficulty = DifficultySettings::easy();
+        let state = engine.initialize_game(script).unwrap();
+        assert_eq!(state.difficulty, DifficultySettings::hard());
+        let plot = engine.initialize_plot().unwrap();
+        assert_eq!(plot.settings.novel_style, "古典章回体");
+        assert!(plot.settings.outline_planning);
+        assert_eq!(plot.settings.language, crate::script::ScriptLanguage::En);
+
+        let mut reopened = GameEngine::new();
+        reopened.save_load_system = SaveLoadSystem::with_directory(temp_dir.path().to_path_buf());
+        assert_eq!(reopened.app_settings().plot_settings.novel_style, "古典章回体");
+    }
+
+    #[test]
+    fn test_npc_emotions_survive_save_and_show_in_profile() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut engine = GameEngine::new();
+        engine.save_load_system = SaveLoadSystem::with_directory(temp_dir.path().to_path_buf());
+        engine.initialize_game(create_test_script()).unwrap();
+        engine.initialize_plot().unwrap();
+        let today = u64::from(engine.get_current_state().unwrap().game_time.total_days);
+        engine.npc_engine.update_npc_emotions(
+            "npc_elder_1",
+            &crate::npc_engine::NPCEvent {
+                timestamp: today,
+                description: "有人当众挑衅宗门".to_string(),
+                involved_npc_ids: vec!["npc_elder_1".to_string()],
+                importance: 0.9,
+                emotional_impact: -0.8,
+                affinity_impact: 0,
+                trust_impact: 0,
+            },
+        );
+
+        engine.save_game(1).unwrap();
+        engine.npc_engine = NPCEngine::new();
+        engine.load_game(1).unwrap();
+
+        let profile = engine.get_npc_profile("npc_elder_1").unwrap();
+        assert_eq!(profile.dominant_emotion.as_deref(), Some("愤怒"));
+        assert!(profile.emotions.anger > 0.8);
+        assert!(engine.get_npc_profile("missing").is_err());
+    }
+
+    #[test]
+    fn test_script_npc_roster_starts_with_relationships() {
+        let mut script = create_test_script();
+        script.world_setting.npcs = serde_json::from_value(serde_json::json!([
+            {
+                "id": "master_lin",
+                "name": "林师父",
+                "realm_level": 2,
+                "traits": ["Righteous"],
+                "goals": [{ "description": "护住宗门", "priority": 9 }],
+                "relationships": [{ "target_id": "player", "affinity": 40, "trust": 30 }]
+            },
+            {
+                "id": "rival_han",
+                "name": "韩师兄",
+                "location": "city",
+                "traits": ["Ambitious", "Scheming"],
+                "relationships": [
+                    { "target_id": "player", "affinity": -20 },
+                    { "target_id": "master_lin", "affinity": 60, "trust": 50 }
+                ]
+            }
+        ]))
+        .unwrap();
+        let mut engine = GameEngine::new();
+        engine.initialize_game(script).unwrap();
+
+        let roster = engine.list_npcs().unwrap();
+        assert_eq!(
+            roster.iter().map(|p| p.id.as_str()).collect::<Vec<&str>>(),
+            vec!["master_lin", "rival_han"]
+        );
+        assert_eq!(roster[0].realm, "筑基");
+        assert_eq!(roster[0].location.as_deref(), Some("sect"));
+        assert_eq!((roster[0].affinity, roster[0].trust), (40, 30));
+
+        let detail = engine.get_npc_detail("rival_han").unwrap();
+        assert_eq!(detail.profile.location.as_deref(), Some("city"));
+        assert_eq!(detail.relationships.len(), 2);
+        assert_eq!(detail.relationships[0].target_name, "林师父");
+        assert_eq!(detail.relationships[1].target_name, "测试玩家");
+        assert_eq!(detail.relationships[1].affinity, -20);
+        assert!(engine.get_npc_detail("npc_elder_1").is_err());
+    }
+
+    #[test]
+    fn test_record_dialogue_updates_relationship_memory_and_log() {
+        let mut engine = GameEngine::new();
+        engine.initialize_game(create_test_script()).unwrap();
+        let (npc, player_id) = engine.dialogue_partner("npc_elder_1").unwrap();
+        let dialogue = crate::npc_dialogue::fallback_dialogue(
+            &npc,
+            &player_id,
+            "多谢前辈指点",
+            crate::script::ScriptLanguage::Zh,
+        );
+
+        engine.record_dialogue("多谢前辈指点", &dialogue).unwrap();
+
+        let (npc, _) = engine.dialogue_partner("npc_elder_1").unwrap();
+        let relationship = npc.relationships.get(&player_id).unwrap();
+        assert_eq!(relationship.affinity, 1);
+        assert!(npc
+            .memory
+            .short_term
+            .iter()
+            .any(|m| m.event.starts_with("玩家：多谢前辈指点")));
+        assert!(engine
+            .get_current_state()
+            .unwrap()
+            .event_history
+            .iter()
+            .any(|e| e.event_type.as_ref() == "npc_dialogue"));
+        assert!(engine.dialogue_partner("missing").is_err());
+    }
+
+    #[test]
+    fn test_narrator_briefing_is_read_only() {
+        let mut engine = GameEngine::new();
+        engine.initialize_game(create_test_script()).unwrap();
+        let before = engine.get_current_state().unwrap();
+
+        let (state, facts) = engine.narrator_briefing().unwrap();
+        let sources = crate::narrator::grounding_sources(
+            "这片世界有何境界？",
+            &state.script.world_setting,
+            &facts,
+        );
+        let answer = crate::narrator::fallback_answer("这片世界有何境界？", sources);
+
+        assert!(!answer.answer.is_empty());
+        assert_eq!(engine.get_current_state().unwrap(), before);
+        assert!(GameEngine::new().narrator_briefing().is_err());
+    }
+
+    #[test]
+    fn test_combat_runs_to_conclusion_and_sours_relationship() {
+        let mut engine = GameEngine::new();
+        engine.initialize_game(create_test_script()).unwrap();
+        assert!(engine.combat_round(CombatMove::Attack).is_err());
+
+        let combat = engine.start_combat("npc_elder_1").unwrap();
+        assert_eq!(combat.enemy.id, "npc_elder_1");
+        assert!(engine.start_combat("npc_elder_1").is_err());
+
+        let mut combat = combat;
+        while !combat.is_over() {
+            combat = engine.combat_round(CombatMove::Attack).unwrap();
+        }
+        let combat = engine
+            .record_combat_narration(1, "刀光剑影。".to_string())
+            .unwrap();
+        assert_eq!(combat.rounds[0].narration.as_deref(), Some("刀光剑影。"));
+        assert_eq!(engine.get_combat_state().unwrap(), Some(combat));
+
+        let (npc, player_id) = engine.dialogue_partner("npc_elder_1").unwrap();
+        assert!(npc.relationships.get(&player_id).unwrap().affinity < 0);
+        assert!(engine
+            .get_current_state()
+            .unwrap()
+            .event_history
+            .iter()
+            .any(|e| e.event_type.as_ref() == "combat_ended"));
+        assert!(engine.combat_round(CombatMove::Attack).is_err());
+        assert!(engine.start_combat("npc_elder_1").is_ok());
+    }
+
+    #[test]
+    fn test_abandon_quest_updates_state_and_log() {
+        let mut engine = GameEngine::new();
+        engine.initialize_game(create_test_script()).unwrap();
+        let mut state = engine.get_current_state().unwrap();
+        state.quests.apply(
+            &crate::quest_system::QuestUpdates {
+                new_quests: vec!["护送商队出城".to_string()],
+                ..Default::default()
+            },
+            1,
+        );
+        engine.update_current_state(state).unwrap();
+
+        let quest = engine.abandon_quest("quest_1").unwrap();
+        assert_eq!(quest.status, crate::quest_system::QuestStatus::Abandoned);
+        let state = engine.get_current_state().unwrap();
+        assert_eq!(state.quests.active().count(), 0);
+        assert!(state
+            .event_history
+            .iter()
+            .any(|e| e.event_type.as_ref() == "quest_abandoned"));
+        assert!(engine.abandon_quest("quest_1").is_err());
+    }
+
+    #[test]
+    fn test_ending_is_selected_recorded_and_added_to_gallery() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut engine = GameEngine::new();
+        engine.save_load_system = SaveLoadSystem::with_directory(temp_dir.path().to_path_buf());
+        let mut script = create_test_script();
+        script.endings = vec![
+            EndingDefinition {
+                id: "recluse".to_string(),
+                title: "山中隐士".to_string(),
+                description: "你远离纷争，隐居山林。".to_string(),
+                condition: String::new(),
+                priority: 0,
+                ends_playthrough: false,
+            },
+            EndingDefinition {
+                id: "ascension".to_string(),
+                title: "白日飞升".to_string(),
+                description: "你渡劫成仙。".to_string(),
+                condition: "realm_level >= 9".to_string(),
+                priority: 10,
+                ends_playthrough: false,
+            },
+        ];
+        engine.initialize_game(script).unwrap();
+        assert!(engine.ending_summary().is_err());
+
+        let (ending, cause, state) = engine.pending_ending().unwrap();
+        assert_eq!(ending.id, "recluse");
+        assert_eq!(cause, None);
+        let finale = crate::ending::fallback_finale(&ending, &state);
+        let achieved = engine.record_ending(&ending, cause, finale).unwrap();
+        assert!(achieved.finale.starts_with("【山中隐士】"));
+        assert_eq!(engine.ending_summary().unwrap().ending, achieved);
+
+        assert!(engine.pending_ending().is_err());
+        assert_eq!(engine.get_current_state().unwrap().ending, Some(achieved));
+        let gallery = engine.ending_gallery();
+        assert_eq!(gallery.entries.len(), 1);
+        assert!(gallery.current_script[0].achieved);
+        assert!(!gallery.current_script[1].achieved);
+        assert_eq!(gallery.current_script[1].title, None);
+    }
+
+    #[test]
+    fn test_ending_causes_are_detected_and_epilogue_closes_the_story() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut engine = GameEngine::new();
+        engine.save_load_system = SaveLoadSystem::with_directory(temp_dir.path().to_path_buf());
+        let mut script = create_test_script();
+        script.endings = vec![EndingDefinition {
+            id: "veteran".to_string(),
+            title: "身经百战".to_string(),
+            description: "你赢下了三场决斗。".to_string(),
+            condition: "duels_won >= 3 || lifespan_exhausted".to_string(),
+            priority: 0,
+            ends_playthrough: true,
+        }];
+        engine.initialize_game(script).unwrap();
+        engine.initialize_plot().unwrap();
+        assert_eq!(engine.ending_cause().unwrap(), None);
+
+        let mut state = engine.get_current_state().unwrap();
+        let realms = &state.script.world_setting.cultivation_realms;
+        let top = realms.iter().max_by_key(|realm| realm.level).unwrap().clone();
+        let mut ascended = state.clone();
+        ascended.player.stats.cultivation_realm = top;
+        ascended.player.stats.cultivation_realm.sub_level = crate::numerical_system::PEAK_SUB_LEVEL;
+        engine.update_current_state(ascended).unwrap();
+        assert_eq!(engine.ending_cause().unwrap(), Some(EndingCause::Ascension));
+
+        state.player.stats.lifespan.current_age = state.player.stats.lifespan.total_max_age();
+        engine.update_current_state(state.clone()).unwrap();
+        assert_eq!(engine.ending_cause().unwrap(), Some(EndingCause::LifespanExhausted));
+
+        state.house_rules.disable_permadeath = true;
+        engine.update_current_state(state).unwrap();
+        assert_eq!(engine.ending_cause().unwrap(), Some(EndingCause::GoalsCompleted));
+
+        let (ending, cause, _) = engine.pending_ending().unwrap();
+        engine
+            .record_ending(&ending, cause, "大道至此，尘埃落定。".to_string())
+            .unwrap();
+        let plot_state = engine.get_plot_state().unwrap();
+        assert_eq!(plot_state.current_chapter.title, "终章·身经百战");
+        assert_eq!(plot_state.current_chapter.content, vec!["大道至此，尘埃落定。".to_string()]);
+        assert!(plot_state.current_scene.available_options.is_empty());
+        assert_eq!(engine.ending_cause().unwrap(), None);
+        let summary = engine.ending_summary().unwrap();
+        assert_eq!(summary.ending.cause, Some(EndingCause::GoalsCompleted));
+        assert!(summary.highlights.iter().any(|h| h.contains("身经百战")));
+    }
+
+    #[test]
+    fn test_city_market_buys_and_sells_against_spirit_stones() {
+        let mut engine = GameEngine::new();
+        engine.initialize_game(create_test_script()).unwrap();
+        assert!(engine.market_for_visit().unwrap_err().to_string().contains("不是城镇"));
+
+        let mut state = engine.get_current_state().unwrap();
+        state.player.location = "city".to_string();
+        engine.update_current_state(state).unwrap();
+        let market = engine.store_market(engine.market_for_visit().unwrap()).unwrap();
+        assert_eq!(engine.market_for_visit().unwrap(), market);
+
+        let listing = market
+            .listings
+            .iter()
+            .find(|listing| listing.price <= 100)
+            .unwrap()
+            .clone();
+        let receipt = engine.buy_market_item(&listing.item.id).unwrap();
+        let state = engine.get_current_state().unwrap();
+        assert_eq!(state.resources.spirit_stones, 100 - listing.price);
+        assert_eq!(state.player.inventory, vec![listing.item.clone()]);
+        assert_eq!(
+            receipt.market.listing(&listing.item.id).unwrap().stock,
+            listing.stock - 1
+        );
+        assert!(state
+            .event_history
+            .iter()
+            .any(|event| &*event.event_type == "market_trade"));
+
+        engine.sell_market_item(&listing.item.id).unwrap();
+        let state = engine.get_current_state().unwrap();
+        assert!(state.player.inventory.is_empty());
+        assert_eq!(
+            state.resources.spirit_stones,
+            100 - listing.price + listing.price / 2
+        );
+
+        let herbs = state.resources.herbs;
+        engine.trade_market_resource(ResourceKind::Herbs, 2, true).unwrap();
+        assert_eq!(engine.get_current_state().unwrap().resources.herbs, herbs + 2);
+        assert!(engine
+            .trade_market_resource(ResourceKind::SpiritStones, 1, true)
+            .is_err());
+        assert!(engine
+            .trade_market_resource(ResourceKind::Herbs, 1000, true)
+            .unwrap_err()
+            .to_string()
+            .contains("资源不足"));
+    }
+
+    #[test]
+    fn test_logged_milestones_unlock_achievements_once_across_games() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut engine = GameEngine::new();
+        engine.save_load_system = SaveLoadSystem::with_directory(temp_dir.path().to_path_buf());
+        engine.initialize_game(create_test_script()).unwrap();
+        assert!(engine.check_achievements().unwrap().is_empty());
+
+        engine.log_event(3, "breakthrough", "突破至练气二层", EventImportance::Important);
+        let unlocked = engine.check_achievements().unwrap();
+        assert_eq!(unlocked.len(), 1);
+        assert_eq!(unlocked[0].id, "first_breakthrough");
+        assert_eq!(unlocked[0].script_id, "test");
+        assert_eq!(engine.take_unlocked_achievements(), unlocked);
+        assert!(engine.take_unlocked_achievements().is_empty());
+
+        engine.initialize_game(create_test_script()).unwrap();
+        engine.log_event(3, "breakthrough", "突破至练气二层", EventImportance::Important);
+        assert!(engine.check_achievements().unwrap().is_empty());
+        let statuses = engine.achievements();
+        assert!(statuses.iter().find(|s| s.id == "first_breakthrough").unwrap().unlocked);
+        assert!(!statuses.iter().find(|s| s.id == "first_chapter").unwrap().unlocked);
+    }
+
+    #[test]
+    fn test_autosave_job_due_after_configured_actions() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut engine = GameEngine::new();
+        engine.save_load_system = SaveLoadSystem::with_directory(temp_dir.path().to_path_buf());
+        engine.initialize_game(create_test_script()).unwrap();
+        engine
+            .set_autosave_settings(AutosaveSettings {
+                enabled: true,
+                interval_actions: 2,
+            })
+            .unwrap();
+
+        assert!(engine.autosave_job_if_due().unwrap().is_none());
+        let job = engine.autosave_job_if_due().unwrap().unwrap();
+        assert_eq!(job.slot_id, crate::save_load::AUTOSAVE_FIRST_SLOT);
+        job.run(|_| {}).unwrap();
+        assert!(engine.autosave_job_if_due().unwrap().is_none());
+
+        let autosaves = engine.list_autosaves().unwrap();
+        assert_eq!(autosaves.len(), 1);
+        assert!(engine.list_saves().unwrap().is_empty());
+        engine.load_game(autosaves[0].slot_id).unwrap();
+
+        engine
+            .set_autosave_settings(AutosaveSettings {
+                enabled: false,
+                interval_actions: 1,
+            })
+            .unwrap();
+        assert!(engine.autosave_job_if_due().unwrap().is_none());
+    }
+
     #[test]
     fn test_load_updates_engine_state() {
         // 测试加载正确更新引擎状态
@@ -1198,7 +4027,7 @@ mod integration_tests {
         engine1.initialize_game(script).unwrap();
         
         {
-            let mut state_lock = engine1.state.lock().unwrap();
+            let mut state_lock = engine1.state.write().unwrap();
             if let Some(ref mut state) = *state_lock {
                 state.player.stats.lifespan.current_age = 30;
             }
@@ -1222,10 +4051,52 @@ mod integration_tests {
         let state = engine2.get_current_state().unwrap();
         assert_eq!(state.player.stats.lifespan.current_age, 30);
     }
-}
 
+    #[test]
+    fn test_low_memory_mode_offloads_and_rehydrates() {
+        use crate::cold_storage::ColdStorage;
+        use tempfile::TempDir;
 
+        let temp_dir = TempDir::new().unwrap();
+        let mut engine = GameEngine::new()
+            .with_cold_storage(ColdStorage::new(temp_dir.path().join("cold")));
+        engine.save_load_system = SaveLoadSystem::with_directory(temp_dir.path().join("saves"));
+        engine.initialize_game(create_test_script()).unwrap();
+        engine.initialize_plot().unwrap();
+        engine.set_low_memory_mode(true).unwrap();
 
+        let mut plot_state = engine.get_plot_state().unwrap();
+        plot_state.append_segment("山门初试，少年一剑惊四座。".to_string());
+        plot_state.finalize_chapter(None, Some("初入宗门".to_string()));
+        engine.update_plot_state(plot_state).unwrap();
 
+        let stored = engine.get_plot_state().unwrap();
+        assert!(stored.chapters.iter().all(|c| c.offloaded && c.content.is_empty()));
+        let chapter = engine.get_chapter(stored.chapters[0].index).unwrap();
+        assert!(chapter.content.iter().any(|c| c.contains("一剑惊四座")));
 
+        for idx in 0..200 {
+            engine.log_event(idx, "cultivation", format!("修炼{}", idx), EventImportance::Normal);
+        }
+        let report = engine.get_memory_usage_report();
+        assert!(report.low_memory_mode);
+        assert_eq!(report.chapters_in_memory, 0);
+        assert_eq!(report.chapters_offloaded, 1);
+        assert!(report.events_in_memory < LOW_MEMORY_MAX_EVENTS + LOW_MEMORY_SPILL_BATCH);
+        assert!(report.archived_event_files > 0);
+        assert!(report.offloaded_bytes_on_disk > 0);
+        let history = engine.full_event_history().unwrap();
+        assert!(history.len() > report.events_in_memory);
+        assert!(history.iter().any(|e| e.description.as_ref() == "修炼0"));
 
+        engine.save_game(1).unwrap();
+        let save_data = engine.save_load_system.load_game(1).unwrap();
+        let saved_chapter = &save_data.plot_state.unwrap().chapters[0];
+        assert!(!saved_chapter.offloaded);
+        assert_eq!(saved_chapter.content, chapter.content);
+
+        engine.set_low_memory_mode(false).unwrap();
+        let restored = engine.get_plot_state().unwrap();
+        assert_eq!(restored.chapters[0].content, chapter.content);
+    }
+}
@@ -1,21 +1,92 @@
-﻿use crate::event_log::GameEvent;
+﻿use crate::duel::DuelBoard;
+use crate::character_sheet::{StatHistoryEntry, MAX_STAT_HISTORY};
+use crate::faction_war::FactionStandings;
+use crate::combat_engine::CombatState;
+use crate::difficulty::DifficultySettings;
+use crate::economy::Resources;
+use crate::ending::AchievedEnding;
+use crate::event_log::GameEvent;
+use crate::house_rules::HouseRules;
+use crate::karma::Karma;
+use crate::quest_system::QuestLog;
+use crate::loot::LootState;
+use crate::market::Market;
 use crate::models::CharacterStats;
+use crate::numerical_system::StatChange;
+use crate::rng::GameRng;
 use crate::script::{Location, Script};
+use crate::world_bulletin::BulletinBoard;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 /// 包含所有游戏数据的主游戏状态
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
 pub struct GameState {
     pub script: Script,
     pub player: Character,
     pub world_state: WorldState,
     pub game_time: GameTime,
     pub event_history: Vec<GameEvent>,
+    #[serde(default)]
+    pub version: u64,
+    #[serde(default)]
+    pub loot_state: LootState,
+    /// 本局生效的房规
+    #[serde(default)]
+    pub house_rules: HouseRules,
+    /// 本局难度
+    #[serde(default)]
+    pub difficulty: DifficultySettings,
+    /// 剧情中接取的任务
+    #[serde(default)]
+    pub quests: QuestLog,
+    /// 当前或最近一场战斗
+    #[serde(default)]
+    pub combat: Option<CombatState>,
+    /// 本局达成的结局，达成后本局结束
+    #[serde(default)]
+    pub ending: Option<AchievedEnding>,
+    /// 本局累计的实际游玩时长（秒）
+    #[serde(default)]
+    pub play_time_secs: u64,
+    /// 本局玩家执行过的行动数
+    #[serde(default)]
+    pub action_count: u32,
+    /// 本局的随机数状态
+    #[serde(default)]
+    pub rng: GameRng,
+    /// 玩家持有的灵石与修炼资源
+    #[serde(default)]
+    pub resources: Resources,
+    /// 最近光顾的城镇市集
+    #[serde(default)]
+    pub market: Option<Market>,
+    /// 最近的属性变化，供属性面板回顾
+    #[serde(default)]
+    pub stat_history: Vec<StatHistoryEntry>,
+    /// 玩家的业力与善恶取向
+    #[serde(default)]
+    pub karma: Karma,
+}
+
+impl GameState {
+    /// 记下一次行动或交易带来的属性变化，只保留最近若干条
+    pub fn record_stat_changes(&mut self, source: &str, changes: &[StatChange]) {
+        let timestamp = u64::from(self.game_time.total_days);
+        self.stat_history
+            .extend(changes.iter().cloned().map(|change| StatHistoryEntry {
+                timestamp,
+                source: source.to_string(),
+                change,
+            }));
+        let overflow = self.stat_history.len().saturating_sub(MAX_STAT_HISTORY);
+        self.stat_history.drain(..overflow);
+    }
 }
 
 /// 角色数据结构
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
 pub struct Character {
     pub id: String,
     pub name: String,
@@ -25,16 +96,26 @@ pub struct Character {
 }
 
 /// 角色背包中的物品
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
 pub struct Item {
     pub id: String,
     pub name: String,
     pub description: String,
     pub item_type: ItemType,
+    /// 服用后增加的寿元年数，仅丹药有效
+    #[serde(default)]
+    pub lifespan_bonus: u32,
+}
+
+impl Item {
+    /// 可服用延寿的丹药
+    pub fn extends_lifespan(&self) -> bool {
+        self.item_type == ItemType::Medicine && self.lifespan_bonus > 0
+    }
 }
 
 /// 物品类型枚举
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
 pub enum ItemType {
     Technique,
     Artifact,
@@ -43,14 +124,24 @@ pub enum ItemType {
 }
 
 /// 包含地点和全局事件的世界状态
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
 pub struct WorldState {
     pub locations: HashMap<String, Location>,
     pub global_events: Vec<GlobalEvent>,
+    #[serde(default)]
+    pub bulletin_board: BulletinBoard,
+    #[serde(default)]
+    pub duel_board: DuelBoard,
+    /// 玩家在各势力中的声望，键为势力 id
+    #[serde(default)]
+    pub faction_reputation: HashMap<String, i32>,
+    /// 势力的当前实力与进行中的战事
+    #[serde(default)]
+    pub faction_standings: FactionStandings,
 }
 
 /// 影响世界的全局事件
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
 pub struct GlobalEvent {
     pub id: String,
     pub name: String,
@@ -59,7 +150,7 @@ pub struct GlobalEvent {
 }
 
 /// 游戏时间追踪
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
 pub struct GameTime {
     pub year: u32,
     pub month: u32,
@@ -116,6 +207,10 @@ impl WorldState {
         Self {
             locations: HashMap::new(),
             global_events: Vec::new(),
+            bulletin_board: BulletinBoard::default(),
+            duel_board: DuelBoard::default(),
+            faction_reputation: HashMap::new(),
+            faction_standings: FactionStandings::default(),
         }
     }
 
@@ -128,6 +223,10 @@ impl WorldState {
         Self {
             locations,
             global_events: Vec::new(),
+            bulletin_board: BulletinBoard::default(),
+            duel_board: DuelBoard::default(),
+            faction_reputation: HashMap::new(),
+            faction_standings: FactionStandings::default(),
         }
     }
 }
@@ -142,17 +241,20 @@ impl Default for WorldState {
 mod tests {
     use super::*;
     use crate::models::{CultivationRealm, Element, Grade, Lifespan, SpiritualRoot};
-    use crate::script::{InitialState, ScriptType, WorldSetting};
+    use crate::script::{Climate, InitialState, LocationKind, ScriptType, WorldSetting};
 
     fn create_test_character() -> Character {
         let stats = CharacterStats {
             spiritual_root: SpiritualRoot {
                 element: Element::Fire,
-                grade: Grade::Heavenly,
+                grade: Grade::heavenly(),
                 affinity: 0.8,
+                tier_multiplier: None,
             },
             cultivation_realm: CultivationRealm::new("Qi Condensation".to_string(), 1, 0, 1.0),
             techniques: Vec::new(),
+            status_effects: Default::default(),
+            insight: 0,
             lifespan: Lifespan {
                 current_age: 16,
                 max_age: 100,
@@ -221,12 +323,16 @@ mod tests {
                 name: "Azure Cloud Sect".to_string(),
                 description: "A peaceful cultivation sect".to_string(),
                 spiritual_energy: 1.0,
+                kind: LocationKind::Wilderness,
+                climate: Climate::Temperate,
             },
             Location {
                 id: "city".to_string(),
                 name: "Mortal City".to_string(),
                 description: "A bustling mortal city".to_string(),
                 spiritual_energy: 0.1,
+                kind: LocationKind::City,
+                climate: Climate::Temperate,
             },
         ];
 
@@ -234,8 +340,9 @@ mod tests {
             player_name: "Test".to_string(),
             player_spiritual_root: SpiritualRoot {
                 element: Element::Fire,
-                grade: Grade::Heavenly,
+                grade: Grade::heavenly(),
                 affinity: 0.8,
+                tier_multiplier: None,
             },
             starting_location: "sect".to_string(),
             starting_age: 16,
@@ -270,14 +377,17 @@ mod tests {
             name: "Azure Cloud Sect".to_string(),
             description: "A peaceful cultivation sect".to_string(),
             spiritual_energy: 1.0,
+            kind: LocationKind::Wilderness,
+            climate: Climate::Temperate,
         }];
 
         let initial_state = InitialState {
             player_name: "Test".to_string(),
             player_spiritual_root: SpiritualRoot {
                 element: Element::Fire,
-                grade: Grade::Heavenly,
+                grade: Grade::heavenly(),
                 affinity: 0.8,
+                tier_multiplier: None,
             },
             starting_location: "sect".to_string(),
             starting_age: 16,
@@ -297,6 +407,20 @@ mod tests {
             world_state,
             game_time,
             event_history: Vec::new(),
+            version: 0,
+            loot_state: LootState::default(),
+            house_rules: HouseRules::default(),
+            difficulty: DifficultySettings::default(),
+            quests: QuestLog::default(),
+            combat: None,
+            ending: None,
+            play_time_secs: 0,
+            action_count: 0,
+            rng: GameRng::new(1),
+            resources: Resources::default(),
+            market: None,
+            stat_history: Vec::new(),
+            karma: Karma::default(),
         };
 
         // 测试序列化
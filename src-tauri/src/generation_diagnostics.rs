@@ -0,0 +1,185 @@
+use crate::llm_service::LLMResponse;
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// 续写正文最终采用的解析方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ParsePath {
+    /// 输出是合法 JSON，按结构化字段读取
+    StrictJson,
+    /// JSON 不完整，从原文中抢救出字段
+    Salvage,
+    /// 不是 JSON，整段作为纯文本正文
+    PlainText,
+    /// LLM 不可用或输出无法使用，改用预设文本
+    Preset,
+}
+
+impl ParsePath {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ParsePath::StrictJson => "严格 JSON",
+            ParsePath::Salvage => "抢救解析",
+            ParsePath::PlainText => "纯文本",
+            ParsePath::Preset => "预设文本",
+        }
+    }
+}
+
+/// 最近一次剧情续写的生成诊断，供调试面板查看生成质量
+#[derive(Debug, Clone, Default, PartialEq, Serialize, JsonSchema)]
+pub struct GenerationDiagnostics {
+    pub model: Option<String>,
+    pub prompt_tokens: Option<u32>,
+    pub completion_tokens: Option<u32>,
+    pub total_tokens: Option<u32>,
+    /// 从发出请求到拿到可用输出的耗时
+    pub latency_ms: Option<u64>,
+    pub retry_count: u32,
+    pub parse_path: Option<ParsePath>,
+    pub option_source: Option<String>,
+    /// 回退、校验未通过与调参等提示，按发生先后
+    pub warnings: Vec<String>,
+}
+
+impl GenerationDiagnostics {
+    /// 按 LLM 响应记下模型与 token 用量；响应未报告模型时沿用配置中的模型名
+    pub fn from_response(response: &LLMResponse, configured_model: Option<&str>) -> Self {
+        Self {
+            model: response
+                .model
+                .clone()
+                .or_else(|| configured_model.map(str::to_string)),
+            prompt_tokens: response.prompt_tokens,
+            completion_tokens: response.completion_tokens,
+            total_tokens: response.total_tokens,
+            ..Self::default()
+        }
+    }
+
+    pub fn preset(warning: impl Into<String>) -> Self {
+        let mut diagnostics = Self {
+            parse_path: Some(ParsePath::Preset),
+            ..Self::default()
+        };
+        diagnostics.warn(warning);
+        diagnostics
+    }
+
+    pub fn warn(&mut self, warning: impl Into<String>) {
+        let warning = warning.into();
+        if !warning.trim().is_empty() {
+            self.warnings.push(warning);
+        }
+    }
+
+    /// 单行摘要，生成出现问题时直接展示给玩家
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(model) = &self.model {
+            parts.push(format!("模型 {}", model));
+        }
+        if let Some(path) = self.parse_path {
+            parts.push(format!("解析 {}", path.label()));
+        }
+        if let Some(total) = self.total_tokens {
+            parts.push(format!("{} tokens", total));
+        }
+        if let Some(latency) = self.latency_ms {
+            parts.push(format!("耗时 {} ms", latency));
+        }
+        if self.retry_count > 0 {
+            parts.push(format!("重试 {} 次", self.retry_count));
+        }
+        if let Some(source) = &self.option_source {
+            parts.push(format!("选项来源：{}", source));
+        }
+        parts.extend(self.warnings.iter().cloned());
+        parts.join("；")
+    }
+}
+
+// 旧存档中的诊断是一段拼接好的文本
+impl<'de> Deserialize<'de> for GenerationDiagnostics {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Text(String),
+            Entry {
+                #[serde(default)]
+                model: Option<String>,
+                #[serde(default)]
+                prompt_tokens: Option<u32>,
+                #[serde(default)]
+                completion_tokens: Option<u32>,
+                #[serde(default)]
+                total_tokens: Option<u32>,
+                #[serde(default)]
+                latency_ms: Option<u64>,
+                #[serde(default)]
+                retry_count: u32,
+                #[serde(default)]
+                parse_path: Option<ParsePath>,
+                #[serde(default)]
+                option_source: Option<String>,
+                #[serde(default)]
+                warnings: Vec<String>,
+            },
+        }
+
+        Ok(match Repr::deserialize(deserializer)? {
+            Repr::Text(text) => Self {
+                warnings: text
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+                ..Self::default()
+            },
+            Repr::Entry {
+                model,
+                prompt_tokens,
+                completion_tokens,
+                total_tokens,
+                latency_ms,
+                retry_count,
+                parse_path,
+                option_source,
+                warnings,
+            } => Self {
+                model,
+                prompt_tokens,
+                completion_tokens,
+                total_tokens,
+                latency_ms,
+                retry_count,
+                parse_path,
+                option_source,
+                warnings,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_legacy_text_diagnostics_become_warnings() {
+        let legacy: GenerationDiagnostics =
+            serde_json::from_str(r#""回退：超时\n故事弧规划：第1弧""#).unwrap();
+        assert_eq!(legacy.warnings, vec!["回退：超时", "故事弧规划：第1弧"]);
+        assert!(legacy.parse_path.is_none());
+
+        let mut diagnostics = GenerationDiagnostics::preset("LLM 不可用");
+        diagnostics.option_source = Some("规则".to_string());
+        let restored: GenerationDiagnostics =
+            serde_json::from_str(&serde_json::to_string(&diagnostics).unwrap()).unwrap();
+        assert_eq!(restored, diagnostics);
+        assert_eq!(restored.summary(), "解析 预设文本；选项来源：规则；LLM 不可用");
+    }
+}
@@ -0,0 +1,155 @@
+use crate::llm_service::LLMServiceError;
+use crate::response_validator::ValidationError;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+const BUDGET_MARKERS: &[&str] = &["max_tokens", "context_length", "context length", "token limit", "quota", "insufficient"];
+const AUTH_MARKERS: &[&str] = &["401", "403", "unauthorized", "api key", "api_key", "invalid_api_key"];
+
+/// 生成失败的类别
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum FailureCategory {
+    MissingConfig,
+    Authentication,
+    Timeout,
+    BudgetExceeded,
+    InvalidResponse,
+    Network,
+    ApiError,
+    Cancelled,
+}
+
+impl FailureCategory {
+    pub fn likely_cause(self) -> &'static str {
+        match self {
+            FailureCategory::MissingConfig => "未配置可用的 LLM 服务",
+            FailureCategory::Authentication => "API Key 无效、过期或无权访问该模型",
+            FailureCategory::Timeout => "模型响应超时，可能是输出过长或网络拥堵",
+            FailureCategory::BudgetExceeded => "请求超出模型的 token 上限或账户额度",
+            FailureCategory::InvalidResponse => "模型返回的内容不符合预期的 JSON 结构",
+            FailureCategory::Network => "无法连接到模型服务",
+            FailureCategory::ApiError => "模型服务返回了错误",
+            FailureCategory::Cancelled => "本次生成已被取消",
+        }
+    }
+
+    pub fn suggested_action(self) -> &'static str {
+        match self {
+            FailureCategory::MissingConfig => "在设置中填写 API 地址、模型名称与 API Key",
+            FailureCategory::Authentication => "检查 API Key 是否正确，以及账户是否有该模型的权限",
+            FailureCategory::Timeout => "降低 max_tokens 或稍后重试",
+            FailureCategory::BudgetExceeded => "降低 max_tokens，或切换上下文更长的模型并检查账户余额",
+            FailureCategory::InvalidResponse => "重试一次，若仍失败请切换指令遵循能力更强的模型",
+            FailureCategory::Network => "检查网络连接与 API 地址是否可访问",
+            FailureCategory::ApiError => "查看错误详情，必要时切换模型或稍后重试",
+            FailureCategory::Cancelled => "需要时重新发起即可",
+        }
+    }
+}
+
+/// 结构化的生成失败信息，附带可能原因与建议操作
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct GenerationFailure {
+    pub category: FailureCategory,
+    pub stage: String,
+    pub detail: String,
+    pub likely_cause: String,
+    pub suggested_action: String,
+}
+
+impl GenerationFailure {
+    pub fn new(category: FailureCategory, stage: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self {
+            category,
+            stage: stage.into(),
+            detail: detail.into(),
+            likely_cause: category.likely_cause().to_string(),
+            suggested_action: category.suggested_action().to_string(),
+        }
+    }
+
+    pub fn from_llm_error(stage: impl Into<String>, error: &LLMServiceError) -> Self {
+        let category = match error {
+            LLMServiceError::InvalidConfig(_) => FailureCategory::MissingConfig,
+            LLMServiceError::Timeout => FailureCategory::Timeout,
+            LLMServiceError::Cancelled => FailureCategory::Cancelled,
+            LLMServiceError::Http(_) => FailureCategory::Network,
+            LLMServiceError::InvalidResponse(_) => FailureCategory::InvalidResponse,
+            LLMServiceError::InvalidRequest(msg) | LLMServiceError::Api(msg) => {
+                classify_api_message(msg)
+            }
+        };
+        Self::new(category, stage, error.to_string())
+    }
+
+    pub fn from_validation_error(stage: impl Into<String>, error: &ValidationError) -> Self {
+        Self::new(FailureCategory::InvalidResponse, stage, error.to_string())
+    }
+
+    /// 用于诊断文本的一行摘要
+    pub fn summary(&self) -> String {
+        format!("{}失败：{}（{}）", self.stage, self.likely_cause, self.detail)
+    }
+}
+
+impl fmt::Display for GenerationFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}；建议：{}", self.summary(), self.suggested_action)
+    }
+}
+
+impl std::error::Error for GenerationFailure {}
+
+fn classify_api_message(message: &str) -> FailureCategory {
+    let lower = message.to_lowercase();
+    if AUTH_MARKERS.iter().any(|m| lower.contains(m)) {
+        FailureCategory::Authentication
+    } else if BUDGET_MARKERS.iter().any(|m| lower.contains(m)) {
+        FailureCategory::BudgetExceeded
+    } else {
+        FailureCategory::ApiError
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_llm_error_classifies_categories() {
+        let timeout = GenerationFailure::from_llm_error("剧情续写", &LLMServiceError::Timeout);
+        assert_eq!(timeout.category, FailureCategory::Timeout);
+        assert!(timeout.suggested_action.contains("max_tokens"));
+
+        let auth = GenerationFailure::from_llm_error(
+            "剧情续写",
+            &LLMServiceError::Api("401 Unauthorized: invalid api key".to_string()),
+        );
+        assert_eq!(auth.category, FailureCategory::Authentication);
+
+        let budget = GenerationFailure::from_llm_error(
+            "剧情续写",
+            &LLMServiceError::Api("maximum context_length exceeded".to_string()),
+        );
+        assert_eq!(budget.category, FailureCategory::BudgetExceeded);
+
+        let other = GenerationFailure::from_llm_error(
+            "剧情续写",
+            &LLMServiceError::Api("internal server error".to_string()),
+        );
+        assert_eq!(other.category, FailureCategory::ApiError);
+    }
+
+    #[test]
+    fn test_display_includes_cause_and_suggestion() {
+        let failure = GenerationFailure::from_validation_error(
+            "剧情续写",
+            &ValidationError::InvalidJson("expected value".to_string()),
+        );
+        let text = failure.to_string();
+        assert!(text.contains("剧情续写失败"));
+        assert!(text.contains("JSON"));
+        assert!(text.contains("建议："));
+    }
+}
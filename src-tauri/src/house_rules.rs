@@ -0,0 +1,57 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// 玩家自定的对局规则，随对局存档保存；默认全部关闭，即标准规则
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct HouseRules {
+    /// 跳过自由输入的合理性判定（LLM 判定与突破条件检查），屏蔽词过滤仍然生效
+    pub skip_reasonableness_check: bool,
+    /// 允许越阶：战斗中境界差距不再压制低境界一方，突破可跨越多个层次
+    pub allow_cross_realm_feats: bool,
+    /// 关闭永久死亡：寿元耗尽时续命而非身死
+    pub disable_permadeath: bool,
+}
+
+impl HouseRules {
+    pub fn is_standard(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// 已开启规则的中文名称
+    pub fn enabled_labels(&self) -> Vec<&'static str> {
+        [
+            (self.skip_reasonableness_check, "关闭合理性判定"),
+            (self.allow_cross_realm_feats, "允许越阶"),
+            (self.disable_permadeath, "关闭永久死亡"),
+        ]
+        .into_iter()
+        .filter_map(|(enabled, label)| enabled.then_some(label))
+        .collect()
+    }
+
+    /// 单行说明，标准规则时为空
+    pub fn summary(&self) -> Option<String> {
+        (!self.is_standard()).then(|| format!("房规：{}", self.enabled_labels().join("，")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_lists_enabled_rules() {
+        assert_eq!(HouseRules::default().summary(), None);
+
+        let rules = HouseRules {
+            skip_reasonableness_check: true,
+            disable_permadeath: true,
+            ..HouseRules::default()
+        };
+        assert_eq!(rules.summary().unwrap(), "房规：关闭合理性判定，关闭永久死亡");
+
+        let legacy: HouseRules = serde_json::from_str("{}").unwrap();
+        assert!(legacy.is_standard());
+    }
+}
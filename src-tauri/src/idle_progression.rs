@@ -0,0 +1,356 @@
+use crate::faction_war;
+use crate::game_state::GameState;
+use crate::llm_service::{LLMRequest, LLMService, LLMSubsystem};
+use crate::models::StatDelta;
+use crate::numerical_system::{NumericalSystem, StatChange};
+use crate::prompt_builder::{PromptBuilder, PromptConstraints, PromptContext, PromptTemplate};
+use crate::turn_pipeline::action_context;
+use crate::world_timeline::{event_line, fire_due_events};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// 一次闭关至多一年
+pub const MAX_IDLE_DAYS: u32 = 360;
+/// 闭关每日所得为专心修炼一次的四分之一
+const IDLE_YIELD_RATIO: f32 = 0.25;
+const MAX_MONTH_EVENTS: usize = 4;
+const MAX_MONTH_NARRATION_CHARS: usize = 300;
+
+/// 闭关中的一个月（不足一月的按实际天数计）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct IdleMonth {
+    pub year: u32,
+    pub month: u32,
+    pub days: u32,
+    pub combat_power_gain: u64,
+    /// 本月发生的世界大事、势力变化与状态解除
+    pub events: Vec<String>,
+    pub summary: String,
+    /// 摘要是否由 LLM 写成
+    pub narrated: bool,
+}
+
+/// 一次闭关的结算
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct IdleReport {
+    pub requested_days: u32,
+    pub days: u32,
+    pub combat_power_gain: u64,
+    pub stat_changes: Vec<StatChange>,
+    pub months: Vec<IdleMonth>,
+    /// 提前出关的原因
+    pub interrupted: Option<String>,
+    pub summary: String,
+}
+
+impl IdleReport {
+    /// 写入剧情的段落：有 LLM 逐月叙述时用叙述，否则只写一句总结
+    pub fn interludes(&self) -> Vec<String> {
+        if self.months.iter().any(|month| month.narrated) {
+            self.months
+                .iter()
+                .map(|month| month.summary.clone())
+                .collect()
+        } else {
+            vec![self.summary.clone()]
+        }
+    }
+
+    /// 闭关期间发生的事，交给 NPC 收件箱
+    pub fn events(&self) -> Vec<String> {
+        self.months
+            .iter()
+            .flat_map(|month| month.events.iter().cloned())
+            .collect()
+    }
+}
+
+/// 闭关快进：不经剧情续写，按数值系统逐日结算被动修炼、年岁与世界推演
+pub struct IdleProgression {
+    prompt_builder: PromptBuilder,
+    llm_service: Option<Arc<LLMService>>,
+}
+
+impl IdleProgression {
+    pub fn new() -> Self {
+        Self {
+            prompt_builder: PromptBuilder::default(),
+            llm_service: None,
+        }
+    }
+
+    pub fn with_llm_service(mut self, llm_service: Arc<LLMService>) -> Self {
+        self.llm_service = Some(llm_service);
+        self
+    }
+
+    /// 逐日推进：战力按在闭关之处修炼所得的一小部分增长，状态到期解除，跨年增长年岁，
+    /// 剧本大事照常发生；寿元耗尽时提前出关
+    pub fn simulate(
+        &self,
+        state: &mut GameState,
+        numerical_system: &NumericalSystem,
+        days: u32,
+    ) -> IdleReport {
+        let start_power = state.player.stats.combat_power;
+        let mut stat_changes = Vec::new();
+        let mut months: Vec<IdleMonth> = Vec::new();
+        let mut pending_gain = 0.0f32;
+        let mut interrupted = None;
+        let context = action_context(state);
+        let mut elapsed = 0;
+
+        while elapsed < days {
+            let stats = &mut state.player.stats;
+            if !stats.lifespan.is_alive() {
+                interrupted = Some("寿元耗尽，被迫出关".to_string());
+                break;
+            }
+            let (year, month) = (state.game_time.year, state.game_time.month);
+            if months.last().map(|m| (m.year, m.month)) != Some((year, month)) {
+                months.push(IdleMonth {
+                    year,
+                    month,
+                    days: 0,
+                    combat_power_gain: 0,
+                    events: Vec::new(),
+                    summary: String::new(),
+                    narrated: false,
+                });
+            }
+            let mut events = Vec::new();
+
+            pending_gain +=
+                numerical_system.cultivation_power_gain(stats, &context) as f32 * IDLE_YIELD_RATIO;
+            let gain = pending_gain.floor();
+            pending_gain -= gain;
+            let before = stats.combat_power;
+            if gain >= 1.0 {
+                stats.apply_stat_change(StatDelta::CombatPower(gain as i64));
+            }
+            let gained = stats.combat_power.saturating_sub(before);
+
+            state.game_time.advance_days(1);
+            events.extend(
+                stats
+                    .status_effects
+                    .tick(1)
+                    .iter()
+                    .map(|kind| kind.recovery_note().to_string()),
+            );
+            if state.game_time.year > year {
+                stat_changes
+                    .extend(numerical_system.update_lifespan(stats, state.game_time.year - year));
+            }
+            for event in fire_due_events(state) {
+                events.push(event_line(&event));
+                let power_changes = state
+                    .script
+                    .world_setting
+                    .timeline_events
+                    .iter()
+                    .find(|definition| definition.id == event.id)
+                    .map(|definition| definition.power_changes.clone())
+                    .unwrap_or_default();
+                events.extend(
+                    faction_war::apply_power_changes(state, &power_changes, &event.name)
+                        .into_iter()
+                        .map(|change| change.description),
+                );
+            }
+            events.extend(
+                faction_war::simulate(state)
+                    .into_iter()
+                    .map(|change| change.description),
+            );
+
+            if let Some(current) = months.last_mut() {
+                current.days += 1;
+                current.combat_power_gain += gained;
+                current.events.extend(events);
+            }
+            elapsed += 1;
+        }
+
+        let end_power = state.player.stats.combat_power;
+        if end_power != start_power {
+            stat_changes.insert(
+                0,
+                StatChange {
+                    stat_name: "combat_power".to_string(),
+                    old_value: start_power.to_string(),
+                    new_value: end_power.to_string(),
+                },
+            );
+        }
+        for month in &mut months {
+            month.summary = month_summary(month);
+        }
+        let summary = format!(
+            "{}闭关{}日，战力 {} → {}{}",
+            state.player.name,
+            elapsed,
+            start_power,
+            end_power,
+            interrupted
+                .as_ref()
+                .map(|reason| format!("，{}", reason))
+                .unwrap_or_default()
+        );
+        IdleReport {
+            requested_days: days,
+            days: elapsed,
+            combat_power_gain: end_power.saturating_sub(start_power),
+            stat_changes,
+            months,
+            interrupted,
+            summary,
+        }
+    }
+
+    /// 每个月用 LLM 写一段闭关见闻，失败的月份保留模板摘要
+    pub async fn narrate(&self, mut report: IdleReport, player_name: &str) -> IdleReport {
+        if cfg!(test) {
+            return report;
+        }
+        let Some(llm_service) = &self.llm_service else {
+            return report;
+        };
+
+        for month in &mut report.months {
+            let prompt = self.prompt_builder.build_prompt_with_token_limit(
+                PromptTemplate::PlotGeneration,
+                &PromptContext {
+                    scene: Some(format!(
+                        "{}闭关的第{}年{}月，共{}日",
+                        player_name, month.year, month.month, month.days
+                    )),
+                    location: None,
+                    actor_name: Some(player_name.to_string()),
+                    actor_realm: None,
+                    actor_combat_power: None,
+                    player_persona: None,
+                    canon_facts: Vec::new(),
+                    story_beat: None,
+                    chapter_beat: None,
+                    environment: None,
+                    active_quests: Vec::new(),
+                    relationships: Vec::new(),
+                    status_effects: Vec::new(),
+                    history_events: std::iter::once(month.summary.clone())
+                        .chain(month.events.iter().cloned())
+                        .collect(),
+                    world_setting_summary: None,
+                },
+                &PromptConstraints {
+                    numerical_rules: vec![format!("本月战力提升 {}", month.combat_power_gain)],
+                    world_rules: vec![
+                        "只写一段概述，主角始终在闭关，不得安排新的遭遇".to_string(),
+                        "不超过 150 字".to_string(),
+                    ],
+                    output_schema_hint: Some("纯文本，不要 JSON".to_string()),
+                },
+                600,
+            );
+            if let Ok(response) = llm_service
+                .generate(LLMRequest {
+                    prompt,
+                    max_tokens: Some(300),
+                    temperature: Some(0.8),
+                    subsystem: LLMSubsystem::Plot,
+                    retry_policy: None,
+                })
+                .await
+            {
+                let text = response.text.trim();
+                if !text.is_empty() {
+                    month.summary = text.chars().take(MAX_MONTH_NARRATION_CHARS).collect();
+                    month.narrated = true;
+                }
+            }
+        }
+        report
+    }
+}
+
+impl Default for IdleProgression {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn month_summary(month: &IdleMonth) -> String {
+    let mut summary = format!(
+        "第{}年{}月，闭关{}日，战力提升 {}",
+        month.year, month.month, month.days, month.combat_power_gain
+    );
+    if !month.events.is_empty() {
+        let events = month
+            .events
+            .iter()
+            .take(MAX_MONTH_EVENTS)
+            .cloned()
+            .collect::<Vec<String>>();
+        summary.push_str(&format!("；其间{}", events.join("；")));
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_engine::GameEngine;
+    use crate::game_state::GameTime;
+    use crate::models::CultivationRealm;
+    use crate::script::{Climate, Location, LocationKind};
+    use crate::script_manager::ScriptManager;
+
+    fn running_state() -> GameState {
+        let mut script = ScriptManager::new().blank_script();
+        script
+            .world_setting
+            .cultivation_realms
+            .push(CultivationRealm::new("练气".to_string(), 1, 0, 1.0));
+        script.world_setting.locations.push(Location {
+            id: "cave".to_string(),
+            name: "洞府".to_string(),
+            description: String::new(),
+            spiritual_energy: 1.0,
+            kind: LocationKind::Wilderness,
+            climate: Climate::Temperate,
+        });
+        script.initial_state.starting_location = "cave".to_string();
+        GameEngine::new().initialize_game(script).unwrap()
+    }
+
+    #[test]
+    fn test_idle_retreat_grows_slower_than_cultivation_and_ages() {
+        let mut state = running_state();
+        state.game_time = GameTime::new(1, 11, 20);
+        state.player.stats.combat_power = 1000;
+        let start_power = state.player.stats.combat_power;
+        let start_age = state.player.stats.lifespan.current_age;
+        let numerical_system = NumericalSystem::new();
+        let daily_cultivation = numerical_system.cultivation_power_gain(&state.player.stats, &action_context(&state));
+
+        let report = IdleProgression::new().simulate(&mut state, &numerical_system, 45);
+        assert_eq!(report.days, 45);
+        assert_eq!(
+            state.game_time.total_days,
+            GameTime::new(1, 11, 20).total_days + 45
+        );
+        assert_eq!(state.player.stats.lifespan.current_age, start_age + 1);
+        assert!(report.combat_power_gain > 0);
+        assert!(report.combat_power_gain < daily_cultivation as u64 * 45);
+        assert_eq!(
+            report.stat_changes[0].new_value,
+            state.player.stats.combat_power.to_string()
+        );
+        assert_eq!(report.months.iter().map(|m| m.days).sum::<u32>(), 45);
+        assert_eq!(report.months.len(), 3);
+        assert!(report.months[0].summary.starts_with("第1年11月，闭关11日"));
+        assert_eq!(report.interludes(), vec![report.summary.clone()]);
+        assert!(start_power < state.player.stats.combat_power);
+    }
+}
@@ -0,0 +1,214 @@
+use crate::npc::{PersonalityTrait, NPC};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+const KARMA_STEP: i32 = 6;
+const MAX_KARMA: i32 = 100;
+/// 业力越过该值即被视为正道或魔道中人
+const ALIGNMENT_THRESHOLD: i32 = 30;
+const MAX_RECENT_SHIFTS: usize = 5;
+const MAX_SOURCE_CHARS: usize = 40;
+
+const RIGHTEOUS_DEEDS: &[&str] = &[
+    "救", "行侠", "扶危", "济困", "除魔", "斩妖", "护送", "布施", "饶", "help", "save",
+    "protect", "spare", "heal",
+];
+const DEMONIC_DEEDS: &[&str] = &[
+    "魔功", "血祭", "炼魂", "吞噬", "屠", "灭口", "滥杀", "抢夺", "夺舍", "sacrifice",
+    "slaughter", "murder", "devour",
+];
+
+/// 行事的善恶取向
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Alignment {
+    Righteous,
+    #[default]
+    Neutral,
+    Demonic,
+}
+
+impl Alignment {
+    pub fn label(self) -> &'static str {
+        match self {
+            Alignment::Righteous => "正道",
+            Alignment::Neutral => "中立",
+            Alignment::Demonic => "魔道",
+        }
+    }
+
+    /// 解析 LLM 给出的取向
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "righteous" | "正道" => Some(Alignment::Righteous),
+            "neutral" | "中立" => Some(Alignment::Neutral),
+            "demonic" | "魔道" => Some(Alignment::Demonic),
+            _ => None,
+        }
+    }
+}
+
+/// 按关键词判断一件事的善恶，正邪兼有或都不沾时返回 None
+pub fn classify_deed(text: &str) -> Option<Alignment> {
+    let lower = text.to_lowercase();
+    let righteous = RIGHTEOUS_DEEDS.iter().any(|k| lower.contains(k));
+    let demonic = DEMONIC_DEEDS.iter().any(|k| lower.contains(k));
+    match (righteous, demonic) {
+        (true, false) => Some(Alignment::Righteous),
+        (false, true) => Some(Alignment::Demonic),
+        _ => None,
+    }
+}
+
+/// 一次改变业力的行动
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct KarmaShift {
+    pub day: u32,
+    pub alignment: Alignment,
+    pub source: String,
+}
+
+/// 玩家的业力：正数行善、负数作恶，左右 NPC 的好感、可修习的功法与剧情走向
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Karma {
+    pub score: i32,
+    #[serde(default)]
+    pub recent: Vec<KarmaShift>,
+}
+
+impl Karma {
+    pub fn alignment(&self) -> Alignment {
+        if self.score >= ALIGNMENT_THRESHOLD {
+            Alignment::Righteous
+        } else if self.score <= -ALIGNMENT_THRESHOLD {
+            Alignment::Demonic
+        } else {
+            Alignment::Neutral
+        }
+    }
+
+    /// 记下一次行动的善恶，中立的行动不改变业力
+    pub fn record(&mut self, day: u32, alignment: Alignment, source: &str) {
+        let step = match alignment {
+            Alignment::Righteous => KARMA_STEP,
+            Alignment::Demonic => -KARMA_STEP,
+            Alignment::Neutral => return,
+        };
+        self.score = (self.score + step).clamp(-MAX_KARMA, MAX_KARMA);
+        self.recent.push(KarmaShift {
+            day,
+            alignment,
+            source: source.trim().chars().take(MAX_SOURCE_CHARS).collect(),
+        });
+        let overflow = self.recent.len().saturating_sub(MAX_RECENT_SHIFTS);
+        self.recent.drain(..overflow);
+    }
+
+    /// 续写提示中的业力说明，尚无善恶之举时为空
+    pub fn prompt_line(&self) -> Option<String> {
+        let last = self.recent.last()?;
+        let reaction = match self.alignment() {
+            Alignment::Righteous => "正道中人多有敬重，魔修视之为眼中钉",
+            Alignment::Neutral => "世人尚看不清其正邪",
+            Alignment::Demonic => "正道人士避之不及或欲除之，魔道中人引为同类",
+        };
+        Some(format!(
+            "业力：{}（{}），{}；最近一次：{}「{}」",
+            self.alignment().label(),
+            self.score,
+            reaction,
+            last.alignment.label(),
+            last.source
+        ))
+    }
+
+    /// 按玩家业力与 NPC 秉性调整好感变化：正直者亲近正道、厌恶魔道，工于心计者与魔道投契
+    pub fn adjust_affinity(&self, npc: &NPC, delta: i32) -> i32 {
+        let has_trait = |wanted: PersonalityTrait| npc.personality.traits.contains(&wanted);
+        let factor = match self.alignment() {
+            Alignment::Righteous if has_trait(PersonalityTrait::Righteous) && delta > 0 => 1.5,
+            Alignment::Demonic if has_trait(PersonalityTrait::Righteous) => {
+                if delta > 0 {
+                    0.5
+                } else {
+                    1.5
+                }
+            }
+            Alignment::Demonic if has_trait(PersonalityTrait::Scheming) && delta > 0 => 1.5,
+            _ => 1.0,
+        };
+        (delta as f32 * factor).round() as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CharacterStats, CultivationRealm, Element, Grade, Lifespan, SpiritualRoot};
+    use crate::npc::{EmotionalState, NPCMemory, Personality};
+    use std::collections::HashMap;
+
+    fn npc(traits: Vec<PersonalityTrait>) -> NPC {
+        NPC {
+            id: "elder".to_string(),
+            name: "严长老".to_string(),
+            stats: CharacterStats {
+                spiritual_root: SpiritualRoot {
+                    element: Element::Metal,
+                    grade: Grade::double(),
+                    affinity: 0.8,
+                    tier_multiplier: None,
+                },
+                cultivation_realm: CultivationRealm::new("筑基".to_string(), 2, 0, 1.5),
+                techniques: Vec::new(),
+                status_effects: Default::default(),
+                insight: 0,
+                lifespan: Lifespan {
+                    current_age: 80,
+                    max_age: 200,
+                    realm_bonus: 0,
+                },
+                combat_power: 500,
+            },
+            personality: Personality {
+                traits,
+                goals: Vec::new(),
+                values: Vec::new(),
+            },
+            memory: NPCMemory::default(),
+            relationships: HashMap::new(),
+            secrets: Vec::new(),
+            location: None,
+            bio: String::new(),
+            emotions: EmotionalState::default(),
+        }
+    }
+
+    #[test]
+    fn test_karma_tracks_alignment_and_sways_affinity() {
+        assert_eq!(classify_deed("出手救下被围攻的村民"), Some(Alignment::Righteous));
+        assert_eq!(classify_deed("以血祭之法修炼魔功"), Some(Alignment::Demonic));
+        assert_eq!(classify_deed("打坐修炼"), None);
+        assert_eq!(Alignment::parse(" Demonic "), Some(Alignment::Demonic));
+
+        let mut karma = Karma::default();
+        assert!(karma.prompt_line().is_none());
+        karma.record(1, Alignment::Neutral, "打坐修炼");
+        assert_eq!(karma.score, 0);
+
+        for day in 0..6 {
+            karma.record(day, Alignment::Demonic, "以血祭之法修炼魔功");
+        }
+        assert_eq!(karma.score, -36);
+        assert_eq!(karma.alignment(), Alignment::Demonic);
+        assert_eq!(karma.recent.len(), MAX_RECENT_SHIFTS);
+        assert!(karma.prompt_line().unwrap().starts_with("业力：魔道（-36）"));
+
+        let righteous = npc(vec![PersonalityTrait::Righteous]);
+        let schemer = npc(vec![PersonalityTrait::Scheming]);
+        assert_eq!(karma.adjust_affinity(&righteous, 10), 5);
+        assert_eq!(karma.adjust_affinity(&righteous, -10), -15);
+        assert_eq!(karma.adjust_affinity(&schemer, 10), 15);
+        assert_eq!(Karma::default().adjust_affinity(&righteous, 10), 10);
+    }
+}
@@ -1,31 +1,93 @@
-﻿pub mod game_engine;
+﻿pub mod achievements;
+pub mod character_card;
+pub mod character_sheet;
+pub mod chapter_beats;
+pub mod chapter_outline;
+pub mod action_filters;
+pub mod arc_planner;
+pub mod cancellation;
+pub mod cold_storage;
+pub mod combat_engine;
+pub mod companion;
+pub mod content_filter;
+pub mod context_assembler;
+pub mod difficulty;
+pub mod duel;
+pub mod economy;
+pub mod ending;
+pub mod game_engine;
 pub mod game_state;
+pub mod generation_diagnostics;
+pub mod generation_failure;
+pub mod house_rules;
+pub mod idle_progression;
+pub mod karma;
 pub mod event_log;
+pub mod event_triage;
+pub mod faction_war;
+pub mod facts;
+pub mod formula;
 pub mod app_error;
+pub mod llm_provider;
 pub mod llm_runtime_config;
 pub mod llm_service;
+pub mod llm_trace;
+pub mod loot;
+pub mod market;
+pub mod memory_consolidation;
 pub mod memory_manager;
 pub mod models;
+pub mod narrator;
 pub mod npc;
+pub mod npc_alerts;
+pub mod npc_dialogue;
 pub mod npc_engine;
+pub mod npc_factory;
+pub mod npc_inbox;
 pub mod novel_generator;
 pub mod novel_parser;
 pub mod numerical_system;
+pub mod player_persona;
 pub mod plot_engine;
 pub mod prompt_builder;
+pub mod provenance;
+pub mod quest_system;
+pub mod relationship_graph;
 pub mod response_validator;
+pub mod rng;
 pub mod save_load;
+pub mod scene_image;
 pub mod script;
+pub mod script_import;
 pub mod script_manager;
+pub mod script_reload;
+pub mod script_wizard;
+pub mod settings_store;
+pub mod side_story;
+pub mod state_schema;
+pub mod state_sync;
+pub mod status_effects;
+pub mod storage_manager;
 pub mod tauri_commands;
+pub mod temperature_tuner;
+pub mod timeline_branch;
+pub mod token_budget;
+pub mod turn_gate;
+pub mod turn_pipeline;
+pub mod world_bulletin;
+pub mod world_clock;
+pub mod world_timeline;
 
 use game_engine::GameEngine;
-use std::sync::Mutex;
+use tokio::sync::RwLock;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    // 初始化游戏引擎
-    let game_engine = Mutex::new(GameEngine::new());
+    // 初始化游戏引擎，并按保存的应用偏好设置模型路由
+    let mut game_engine = GameEngine::new();
+    llm_runtime_config::set_llm_model_routes(game_engine.app_settings().llm_routing);
+    game_engine.refresh_llm_service();
+    let game_engine = RwLock::new(game_engine);
 
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
@@ -34,25 +96,98 @@ pub fn run() {
         .manage(game_engine)
         .invoke_handler(tauri::generate_handler![
             tauri_commands::initialize_game,
+            tauri_commands::set_game_seed,
             tauri_commands::execute_player_action,
+            tauri_commands::preview_player_action,
+            tauri_commands::add_custom_option,
+            tauri_commands::advance_time_idle,
             tauri_commands::get_game_state,
+            tauri_commands::get_state_since,
+            tauri_commands::get_last_failure,
+            tauri_commands::get_world_bulletin,
+            tauri_commands::get_state_schema,
             tauri_commands::save_game,
+            tauri_commands::get_save_progress,
             tauri_commands::load_game,
             tauri_commands::list_save_slots,
+            tauri_commands::list_autosaves,
+            tauri_commands::load_autosave,
+            tauri_commands::create_branch,
+            tauri_commands::list_branches,
+            tauri_commands::switch_branch,
+            tauri_commands::autosave_settings,
+            tauri_commands::get_app_settings,
+            tauri_commands::get_turn_status,
+            tauri_commands::update_app_settings,
+            tauri_commands::export_saves_manifest,
+            tauri_commands::verify_saves_against_manifest,
+            tauri_commands::export_save,
+            tauri_commands::import_save,
             tauri_commands::load_script,
+            tauri_commands::load_script_from_url,
             tauri_commands::generate_random_script,
+            tauri_commands::suggest_world_themes,
+            tauri_commands::suggest_player_names,
+            tauri_commands::generate_script_from_answers,
+            tauri_commands::create_blank_script,
+            tauri_commands::update_script_section,
+            tauri_commands::validate_script_draft,
+            tauri_commands::start_script_hot_reload,
+            tauri_commands::stop_script_hot_reload,
+            tauri_commands::poll_script_reload,
             tauri_commands::parse_novel_characters,
             tauri_commands::load_existing_novel,
             tauri_commands::get_player_options,
             tauri_commands::initialize_plot,
             tauri_commands::get_plot_state,
+            tauri_commands::get_chapter,
+            tauri_commands::set_low_memory_mode,
+            tauri_commands::get_memory_usage_report,
+            tauri_commands::get_storage_report,
+            tauri_commands::run_storage_cleanup,
             tauri_commands::update_plot_settings,
             tauri_commands::generate_novel,
             tauri_commands::export_novel,
+            tauri_commands::get_character_sheet,
+            tauri_commands::survey_location,
+            tauri_commands::export_character_card,
+            tauri_commands::export_transcript,
             tauri_commands::set_llm_config,
             tauri_commands::clear_llm_config,
+            tauri_commands::get_action_filters,
+            tauri_commands::update_action_filters,
+            tauri_commands::get_content_filter_settings,
+            tauri_commands::set_content_filter_settings,
+            tauri_commands::house_rules,
+            tauri_commands::set_difficulty,
+            tauri_commands::get_npc_profile,
+            tauri_commands::get_npcs,
+            tauri_commands::get_npc_detail,
+            tauri_commands::get_relationship_graph,
+            tauri_commands::talk_to_npc,
+            tauri_commands::ask_narrator,
+            tauri_commands::suggest_next_action,
+            tauri_commands::start_combat,
+            tauri_commands::combat_action,
+            tauri_commands::get_combat_state,
+            tauri_commands::reach_ending,
+            tauri_commands::get_ending_summary,
+            tauri_commands::visit_market,
+            tauri_commands::buy_market_item,
+            tauri_commands::sell_market_item,
+            tauri_commands::trade_resource,
+            tauri_commands::get_ending_gallery,
+            tauri_commands::get_achievements,
+            tauri_commands::get_quests,
+            tauri_commands::abandon_quest,
+            tauri_commands::enter_side_story,
+            tauri_commands::exit_side_story,
             tauri_commands::get_llm_config_status,
             tauri_commands::test_llm_connection,
+            tauri_commands::get_llm_traces,
+            tauri_commands::cancel_generation,
+            tauri_commands::clear_llm_traces,
+            tauri_commands::get_generation_diagnostics,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,447 @@
+use crate::llm_service::{ChatMessage, ChatRole, LLMResponse, LLMServiceError, ResponseSchema};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// LLM 服务商的接口格式，决定请求体、鉴权头与响应解析方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderKind {
+    /// OpenAI chat-completions 及兼容接口
+    #[default]
+    #[serde(rename = "openai")]
+    OpenAI,
+    /// Anthropic Messages API
+    Anthropic,
+    /// Google Gemini generateContent
+    Gemini,
+    /// Ollama 本地 /api/chat
+    Ollama,
+}
+
+impl ProviderKind {
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_lowercase().as_str() {
+            "openai" => Some(ProviderKind::OpenAI),
+            "anthropic" => Some(ProviderKind::Anthropic),
+            "gemini" => Some(ProviderKind::Gemini),
+            "ollama" => Some(ProviderKind::Ollama),
+            _ => None,
+        }
+    }
+
+    /// 本地服务无需 API Key
+    pub fn requires_api_key(&self) -> bool {
+        !matches!(self, ProviderKind::Ollama)
+    }
+}
+
+/// 发往服务商的 HTTP 请求：地址、鉴权头与请求体
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProviderRequest {
+    pub url: String,
+    pub headers: Vec<(&'static str, String)>,
+    pub payload: Value,
+}
+
+pub fn build_request(
+    kind: ProviderKind,
+    endpoint: &str,
+    api_key: &str,
+    model: &str,
+    messages: &[ChatMessage],
+    max_tokens: u32,
+    temperature: f32,
+) -> ProviderRequest {
+    match kind {
+        ProviderKind::OpenAI => ProviderRequest {
+            url: endpoint.to_string(),
+            headers: vec![("Authorization", format!("Bearer {}", api_key))],
+            payload: json!({
+                "model": model,
+                "messages": messages,
+                "max_tokens": max_tokens,
+                "temperature": temperature,
+                "stream": false
+            }),
+        },
+        ProviderKind::Anthropic => {
+            let (system, turns) = split_system(messages);
+            let turns = turns
+                .iter()
+                .map(|m| json!({ "role": m.role, "content": m.content }))
+                .collect::<Vec<Value>>();
+            let mut payload = json!({
+                "model": model,
+                "messages": turns,
+                "max_tokens": max_tokens,
+                "temperature": temperature
+            });
+            if let Some(system) = system {
+                payload["system"] = Value::String(system);
+            }
+            ProviderRequest {
+                url: endpoint.to_string(),
+                headers: vec![
+                    ("x-api-key", api_key.to_string()),
+                    ("anthropic-version", ANTHROPIC_VERSION.to_string()),
+                ],
+                payload,
+            }
+        }
+        ProviderKind::Gemini => {
+            let (system, turns) = split_system(messages);
+            let contents = turns
+                .iter()
+                .map(|m| {
+                    let role = if m.role == ChatRole::Assistant { "model" } else { "user" };
+                    json!({ "role": role, "parts": [{ "text": m.content }] })
+                })
+                .collect::<Vec<Value>>();
+            let mut payload = json!({
+                "contents": contents,
+                "generationConfig": {
+                    "maxOutputTokens": max_tokens,
+                    "temperature": temperature
+                }
+            });
+            if let Some(system) = system {
+                payload["systemInstruction"] = json!({ "parts": [{ "text": system }] });
+            }
+            ProviderRequest {
+                // 端点可写作 .../models/{model}:generateContent，由配置的模型名填充
+                url: endpoint.replace("{model}", model),
+                headers: vec![("x-goog-api-key", api_key.to_string())],
+                payload,
+            }
+        }
+        ProviderKind::Ollama => {
+            let mut headers = Vec::new();
+            if !api_key.trim().is_empty() {
+                headers.push(("Authorization", format!("Bearer {}", api_key)));
+            }
+            ProviderRequest {
+                url: endpoint.to_string(),
+                headers,
+                payload: json!({
+                    "model": model,
+                    "messages": messages,
+                    "stream": false,
+                    "options": {
+                        "num_predict": max_tokens,
+                        "temperature": temperature
+                    }
+                }),
+            }
+        }
+    }
+}
+
+/// 要求服务商按 JSON Schema 输出：OpenAI 使用 `response_format`，Anthropic 强制调用同名工具，
+/// Gemini 只声明 JSON 输出，Ollama 使用 `format`
+pub fn apply_response_schema(kind: ProviderKind, request: &mut ProviderRequest, schema: &ResponseSchema) {
+    match kind {
+        ProviderKind::OpenAI => {
+            request.payload["response_format"] = json!({
+                "type": "json_schema",
+                "json_schema": { "name": schema.name, "schema": schema.schema }
+            });
+        }
+        ProviderKind::Anthropic => {
+            request.payload["tools"] = json!([{
+                "name": schema.name,
+                "description": "Return the structured result",
+                "input_schema": schema.schema
+            }]);
+            request.payload["tool_choice"] = json!({ "type": "tool", "name": schema.name });
+        }
+        ProviderKind::Gemini => {
+            request.payload["generationConfig"]["responseMimeType"] =
+                Value::String("application/json".to_string());
+        }
+        ProviderKind::Ollama => {
+            request.payload["format"] = schema.schema.clone();
+        }
+    }
+}
+
+pub fn parse_response(kind: ProviderKind, value: Value) -> Result<LLMResponse, LLMServiceError> {
+    match kind {
+        ProviderKind::OpenAI => parse_openai(value),
+        ProviderKind::Anthropic => {
+            let text = tool_input_text(value.get("content"))
+                .unwrap_or_else(|| join_text_parts(value.get("content")));
+            Ok(LLMResponse {
+                text: require_text(text)?,
+                model: str_at(&value, "/model"),
+                finish_reason: str_at(&value, "/stop_reason"),
+                prompt_tokens: u32_at(&value, "/usage/input_tokens"),
+                completion_tokens: u32_at(&value, "/usage/output_tokens"),
+                total_tokens: sum_tokens(
+                    u32_at(&value, "/usage/input_tokens"),
+                    u32_at(&value, "/usage/output_tokens"),
+                ),
+            })
+        }
+        ProviderKind::Gemini => {
+            let text = join_text_parts(value.pointer("/candidates/0/content/parts"));
+            Ok(LLMResponse {
+                text: require_text(text)?,
+                model: str_at(&value, "/modelVersion"),
+                finish_reason: str_at(&value, "/candidates/0/finishReason"),
+                prompt_tokens: u32_at(&value, "/usageMetadata/promptTokenCount"),
+                completion_tokens: u32_at(&value, "/usageMetadata/candidatesTokenCount"),
+                total_tokens: u32_at(&value, "/usageMetadata/totalTokenCount"),
+            })
+        }
+        ProviderKind::Ollama => {
+            let text = str_at(&value, "/message/content").unwrap_or_default();
+            Ok(LLMResponse {
+                text: require_text(text)?,
+                model: str_at(&value, "/model"),
+                finish_reason: str_at(&value, "/done_reason"),
+                prompt_tokens: u32_at(&value, "/prompt_eval_count"),
+                completion_tokens: u32_at(&value, "/eval_count"),
+                total_tokens: sum_tokens(
+                    u32_at(&value, "/prompt_eval_count"),
+                    u32_at(&value, "/eval_count"),
+                ),
+            })
+        }
+    }
+}
+
+fn parse_openai(value: Value) -> Result<LLMResponse, LLMServiceError> {
+    let text = value
+        .pointer("/choices/0/message/content")
+        .and_then(Value::as_str)
+        .or_else(|| value.pointer("/choices/0/text").and_then(Value::as_str))
+        .or_else(|| value.get("output_text").and_then(Value::as_str))
+        .unwrap_or_default()
+        .to_string();
+
+    Ok(LLMResponse {
+        text: require_text(text)?,
+        model: str_at(&value, "/model"),
+        finish_reason: str_at(&value, "/choices/0/finish_reason"),
+        prompt_tokens: u32_at(&value, "/usage/prompt_tokens"),
+        completion_tokens: u32_at(&value, "/usage/completion_tokens"),
+        total_tokens: u32_at(&value, "/usage/total_tokens"),
+    })
+}
+
+/// Anthropic 与 Gemini 不接受 system 角色的消息，系统提示需单独放入请求体
+fn split_system(messages: &[ChatMessage]) -> (Option<String>, Vec<&ChatMessage>) {
+    let system = messages
+        .iter()
+        .filter(|m| m.role == ChatRole::System)
+        .map(|m| m.content.as_str())
+        .collect::<Vec<&str>>();
+    let turns = messages
+        .iter()
+        .filter(|m| m.role != ChatRole::System)
+        .collect::<Vec<&ChatMessage>>();
+    let system = (!system.is_empty()).then(|| system.join("\n\n"));
+    (system, turns)
+}
+
+fn join_text_parts(parts: Option<&Value>) -> String {
+    parts
+        .and_then(Value::as_array)
+        .map(|parts| {
+            parts
+                .iter()
+                .filter_map(|part| part.get("text").and_then(Value::as_str))
+                .collect::<Vec<&str>>()
+                .join("")
+        })
+        .unwrap_or_default()
+}
+
+/// 工具调用的参数即结构化结果，序列化为 JSON 文本
+fn tool_input_text(parts: Option<&Value>) -> Option<String> {
+    parts?
+        .as_array()?
+        .iter()
+        .find(|part| part.get("type").and_then(Value::as_str) == Some("tool_use"))
+        .and_then(|part| part.get("input"))
+        .map(Value::to_string)
+}
+
+fn require_text(text: String) -> Result<String, LLMServiceError> {
+    let text = text.trim().to_string();
+    if text.is_empty() {
+        return Err(LLMServiceError::InvalidResponse(
+            "unable to locate text content".to_string(),
+        ));
+    }
+    Ok(text)
+}
+
+fn str_at(value: &Value, pointer: &str) -> Option<String> {
+    value
+        .pointer(pointer)
+        .and_then(Value::as_str)
+        .map(|s| s.to_string())
+}
+
+fn u32_at(value: &Value, pointer: &str) -> Option<u32> {
+    value
+        .pointer(pointer)
+        .and_then(Value::as_u64)
+        .and_then(|v| u32::try_from(v).ok())
+}
+
+fn sum_tokens(prompt: Option<u32>, completion: Option<u32>) -> Option<u32> {
+    Some(prompt?.saturating_add(completion?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_messages() -> Vec<ChatMessage> {
+        vec![
+            ChatMessage {
+                role: ChatRole::System,
+                content: "你是修仙小说的叙述者".to_string(),
+            },
+            ChatMessage::assistant("上一段"),
+            ChatMessage::user("继续"),
+        ]
+    }
+
+    #[test]
+    fn test_anthropic_request_moves_system_prompt() {
+        let request = build_request(
+            ProviderKind::Anthropic,
+            "https://api.anthropic.com/v1/messages",
+            "key",
+            "claude-test",
+            &sample_messages(),
+            256,
+            0.5,
+        );
+
+        assert_eq!(request.payload["system"], "你是修仙小说的叙述者");
+        assert_eq!(request.payload["messages"].as_array().unwrap().len(), 2);
+        assert_eq!(request.payload["messages"][0]["role"], "assistant");
+        assert!(request
+            .headers
+            .iter()
+            .any(|(name, value)| *name == "x-api-key" && value == "key"));
+    }
+
+    #[test]
+    fn test_gemini_request_uses_model_role_and_endpoint_template() {
+        let request = build_request(
+            ProviderKind::Gemini,
+            "https://generativelanguage.googleapis.com/v1beta/models/{model}:generateContent",
+            "key",
+            "gemini-test",
+            &sample_messages(),
+            256,
+            0.5,
+        );
+
+        assert!(request.url.ends_with("/models/gemini-test:generateContent"));
+        assert_eq!(request.payload["contents"][0]["role"], "model");
+        assert_eq!(request.payload["contents"][1]["parts"][0]["text"], "继续");
+        assert_eq!(request.payload["generationConfig"]["maxOutputTokens"], 256);
+        assert_eq!(
+            request.payload["systemInstruction"]["parts"][0]["text"],
+            "你是修仙小说的叙述者"
+        );
+    }
+
+    #[test]
+    fn test_parse_provider_responses() {
+        let anthropic = parse_response(
+            ProviderKind::Anthropic,
+            json!({
+                "model": "claude-test",
+                "content": [{ "type": "text", "text": "剑光" }, { "type": "text", "text": "一闪" }],
+                "stop_reason": "end_turn",
+                "usage": { "input_tokens": 10, "output_tokens": 5 }
+            }),
+        )
+        .unwrap();
+        assert_eq!(anthropic.text, "剑光一闪");
+        assert_eq!(anthropic.total_tokens, Some(15));
+
+        let gemini = parse_response(
+            ProviderKind::Gemini,
+            json!({
+                "candidates": [{
+                    "content": { "role": "model", "parts": [{ "text": "山风骤起" }] },
+                    "finishReason": "STOP"
+                }],
+                "usageMetadata": { "promptTokenCount": 8, "candidatesTokenCount": 4, "totalTokenCount": 12 }
+            }),
+        )
+        .unwrap();
+        assert_eq!(gemini.text, "山风骤起");
+        assert_eq!(gemini.finish_reason.as_deref(), Some("STOP"));
+
+        let ollama = parse_response(
+            ProviderKind::Ollama,
+            json!({
+                "model": "qwen-test",
+                "message": { "role": "assistant", "content": " 灵气翻涌 " },
+                "done_reason": "stop",
+                "prompt_eval_count": 6,
+                "eval_count": 3
+            }),
+        )
+        .unwrap();
+        assert_eq!(ollama.text, "灵气翻涌");
+        assert_eq!(ollama.completion_tokens, Some(3));
+
+        assert!(parse_response(ProviderKind::Anthropic, json!({ "content": [] })).is_err());
+    }
+
+    #[test]
+    fn test_response_schema_uses_native_structured_output() {
+        let schema = ResponseSchema {
+            name: "Verdict".to_string(),
+            schema: json!({ "type": "object", "properties": { "ok": { "type": "boolean" } } }),
+        };
+
+        let mut openai = build_request(
+            ProviderKind::OpenAI,
+            "https://example.com",
+            "key",
+            "gpt-test",
+            &sample_messages(),
+            256,
+            0.5,
+        );
+        apply_response_schema(ProviderKind::OpenAI, &mut openai, &schema);
+        assert_eq!(openai.payload["response_format"]["type"], "json_schema");
+        assert_eq!(openai.payload["response_format"]["json_schema"]["name"], "Verdict");
+
+        let mut anthropic = build_request(
+            ProviderKind::Anthropic,
+            "https://example.com",
+            "key",
+            "claude-test",
+            &sample_messages(),
+            256,
+            0.5,
+        );
+        apply_response_schema(ProviderKind::Anthropic, &mut anthropic, &schema);
+        assert_eq!(anthropic.payload["tool_choice"]["name"], "Verdict");
+        assert_eq!(anthropic.payload["tools"][0]["input_schema"], schema.schema);
+
+        let parsed = parse_response(
+            ProviderKind::Anthropic,
+            json!({
+                "content": [{ "type": "tool_use", "name": "Verdict", "input": { "ok": true } }],
+                "stop_reason": "tool_use"
+            }),
+        )
+        .unwrap();
+        assert_eq!(parsed.text, "{\"ok\":true}");
+    }
+}
@@ -1,15 +1,29 @@
-﻿use crate::llm_service::LLMConfig;
+﻿use crate::llm_provider::ProviderKind;
+use crate::llm_service::{LLMConfig, LLMService, ModelRoutes, RetryPolicy};
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
-use std::sync::{Mutex, OnceLock};
+use std::sync::{Arc, Mutex, OnceLock};
 
 static RUNTIME_LLM_CONFIG: OnceLock<Mutex<Option<LLMConfig>>> = OnceLock::new();
+static MODEL_ROUTES: OnceLock<Mutex<ModelRoutes>> = OnceLock::new();
+/// 各子系统共用的 LLMService 及构建它时的配置与模型路由
+type SharedService = Option<(LLMConfig, ModelRoutes, Arc<LLMService>)>;
+
+static SHARED_LLM_SERVICE: OnceLock<Mutex<SharedService>> = OnceLock::new();
 
 fn config_slot() -> &'static Mutex<Option<LLMConfig>> {
     RUNTIME_LLM_CONFIG.get_or_init(|| Mutex::new(None))
 }
 
+fn routes_slot() -> &'static Mutex<ModelRoutes> {
+    MODEL_ROUTES.get_or_init(|| Mutex::new(ModelRoutes::new()))
+}
+
+fn shared_service_slot() -> &'static Mutex<SharedService> {
+    SHARED_LLM_SERVICE.get_or_init(|| Mutex::new(None))
+}
+
 fn config_file_path() -> PathBuf {
     PathBuf::from(".nobody_llm_config.json")
 }
@@ -22,6 +36,8 @@ pub struct LLMConfigStatus {
     pub model: Option<String>,
     pub max_tokens: Option<u32>,
     pub temperature: Option<f32>,
+    pub provider_kind: Option<ProviderKind>,
+    pub retry_policy: Option<RetryPolicy>,
 }
 
 pub fn set_runtime_llm_config(config: LLMConfig) {
@@ -42,12 +58,47 @@ pub fn get_runtime_llm_config() -> Option<LLMConfig> {
     guard.clone()
 }
 
+/// 设置按子系统改用的模型，下次取用共享服务时生效
+pub fn set_llm_model_routes(routes: ModelRoutes) {
+    *routes_slot().lock().unwrap() = routes;
+}
+
+pub fn get_llm_model_routes() -> ModelRoutes {
+    routes_slot().lock().unwrap().clone()
+}
+
 pub fn resolve_llm_config() -> Option<LLMConfig> {
     get_runtime_llm_config()
         .or_else(load_llm_config_from_file)
         .or_else(load_llm_config_from_env)
 }
 
+/// 按当前生效的配置返回共享的 LLMService；配置与模型路由不变时复用同一个 HTTP 客户端与响应缓存，变化后才重建
+pub fn shared_llm_service() -> Option<Arc<LLMService>> {
+    let config = resolve_llm_config();
+    let routes = get_llm_model_routes();
+    let mut guard = match shared_service_slot().lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let Some(config) = config else {
+        *guard = None;
+        return None;
+    };
+    if let Some((cached_config, cached_routes, service)) = guard.as_ref() {
+        if *cached_config == config && *cached_routes == routes {
+            return Some(service.clone());
+        }
+    }
+    let service = Arc::new(
+        LLMService::new(config.clone())
+            .ok()?
+            .with_model_routes(routes.clone()),
+    );
+    *guard = Some((config, routes, service.clone()));
+    Some(service)
+}
+
 pub fn get_llm_config_status() -> LLMConfigStatus {
     if let Some(cfg) = get_runtime_llm_config() {
         return LLMConfigStatus {
@@ -57,6 +108,8 @@ pub fn get_llm_config_status() -> LLMConfigStatus {
             model: Some(cfg.model),
             max_tokens: Some(cfg.max_tokens),
             temperature: Some(cfg.temperature),
+            provider_kind: Some(cfg.provider_kind),
+            retry_policy: Some(cfg.retry_policy),
         };
     }
 
@@ -68,6 +121,8 @@ pub fn get_llm_config_status() -> LLMConfigStatus {
             model: Some(cfg.model),
             max_tokens: Some(cfg.max_tokens),
             temperature: Some(cfg.temperature),
+            provider_kind: Some(cfg.provider_kind),
+            retry_policy: Some(cfg.retry_policy),
         };
     }
 
@@ -79,6 +134,8 @@ pub fn get_llm_config_status() -> LLMConfigStatus {
             model: Some(cfg.model),
             max_tokens: Some(cfg.max_tokens),
             temperature: Some(cfg.temperature),
+            provider_kind: Some(cfg.provider_kind),
+            retry_policy: Some(cfg.retry_policy),
         };
     }
 
@@ -89,6 +146,8 @@ pub fn get_llm_config_status() -> LLMConfigStatus {
         model: None,
         max_tokens: None,
         temperature: None,
+        provider_kind: None,
+        retry_policy: None,
     }
 }
 
@@ -104,6 +163,17 @@ fn load_llm_config_from_env() -> Option<LLMConfig> {
         .ok()
         .and_then(|v| v.parse::<f32>().ok())
         .unwrap_or(0.7);
+    let provider_kind = std::env::var("NOBODY_LLM_PROVIDER")
+        .ok()
+        .and_then(|v| ProviderKind::parse(&v))
+        .unwrap_or_default();
+    let defaults = RetryPolicy::default();
+    let retry_policy = RetryPolicy {
+        timeout_secs: env_number("NOBODY_LLM_TIMEOUT_SECS").unwrap_or(defaults.timeout_secs),
+        max_retries: env_number("NOBODY_LLM_MAX_RETRIES").unwrap_or(defaults.max_retries),
+        retry_backoff_ms: env_number("NOBODY_LLM_RETRY_BACKOFF_MS")
+            .unwrap_or(defaults.retry_backoff_ms),
+    };
 
     Some(LLMConfig {
         endpoint,
@@ -111,9 +181,15 @@ fn load_llm_config_from_env() -> Option<LLMConfig> {
         model,
         max_tokens,
         temperature,
+        provider_kind,
+        retry_policy,
     })
 }
 
+fn env_number<T: std::str::FromStr>(name: &str) -> Option<T> {
+    std::env::var(name).ok().and_then(|v| v.trim().parse::<T>().ok())
+}
+
 fn load_llm_config_from_file() -> Option<LLMConfig> {
     let path = config_file_path();
     let content = fs::read_to_string(path).ok()?;
@@ -1,10 +1,17 @@
-﻿use crate::prompt_builder::estimate_token_count;
+﻿use crate::cancellation::{self, CancellationToken};
+use crate::llm_provider::{self, ProviderKind};
+use crate::llm_trace::{self, LlmTrace};
+use crate::prompt_builder::estimate_token_count;
 use reqwest::Client;
+use schemars::gen::SchemaSettings;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use serde_json::{json, Value};
-use std::collections::HashMap;
+use serde_json::Value;
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
-use std::hash::{Hash, Hasher};
+use std::hash::{BuildHasher, Hash, Hasher};
 use std::sync::Mutex;
 use std::time::{Duration, Instant};
 
@@ -13,6 +20,65 @@ const DEFAULT_CACHE_MAX_ENTRIES: usize = 512;
 const DEFAULT_CACHE_TTL_SECS: u64 = 600;
 const DEFAULT_MAX_RETRIES: u32 = 2;
 const DEFAULT_RETRY_BACKOFF_MS: u64 = 200;
+const MAX_HISTORY_MESSAGES: usize = 8;
+const MAX_TIMEOUT_SECS: u64 = 600;
+const MAX_RETRIES: u32 = 10;
+const MAX_RETRY_BACKOFF_MS: u64 = 60_000;
+
+/// 请求超时与失败重试策略；第 n 次重试前等待 `retry_backoff_ms * 2^(n-1)`，并在其一半到全部之间随机抖动
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct RetryPolicy {
+    pub timeout_secs: u64,
+    pub max_retries: u32,
+    pub retry_backoff_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            timeout_secs: DEFAULT_TIMEOUT_SECS,
+            max_retries: DEFAULT_MAX_RETRIES,
+            retry_backoff_ms: DEFAULT_RETRY_BACKOFF_MS,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn validate(&self) -> Result<(), LLMServiceError> {
+        if !(1..=MAX_TIMEOUT_SECS).contains(&self.timeout_secs) {
+            return Err(LLMServiceError::InvalidConfig(format!(
+                "timeout_secs must be in range [1, {MAX_TIMEOUT_SECS}]"
+            )));
+        }
+        if self.max_retries > MAX_RETRIES {
+            return Err(LLMServiceError::InvalidConfig(format!(
+                "max_retries must not exceed {MAX_RETRIES}"
+            )));
+        }
+        if self.retry_backoff_ms > MAX_RETRY_BACKOFF_MS {
+            return Err(LLMServiceError::InvalidConfig(format!(
+                "retry_backoff_ms must not exceed {MAX_RETRY_BACKOFF_MS}"
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs)
+    }
+
+    /// 第 `attempt` 次重试前的等待时间，`jitter` 取 0 到 1，0 为基准值的一半、1 为基准值
+    pub fn backoff_delay(&self, attempt: u32, jitter: f64) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(16);
+        let base = self
+            .retry_backoff_ms
+            .saturating_mul(1 << exponent)
+            .min(MAX_RETRY_BACKOFF_MS);
+        let jittered = base as f64 * (0.5 + 0.5 * jitter.clamp(0.0, 1.0));
+        Duration::from_millis(jittered.round() as u64)
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct LLMConfig {
@@ -21,6 +87,12 @@ pub struct LLMConfig {
     pub model: String,
     pub max_tokens: u32,
     pub temperature: f32,
+    /// 接口格式，旧配置文件缺省时按 OpenAI 兼容接口处理
+    #[serde(default)]
+    pub provider_kind: ProviderKind,
+    /// 超时与重试，旧配置文件缺省时为 30 秒、重试 2 次、退避 200 毫秒
+    #[serde(default)]
+    pub retry_policy: RetryPolicy,
 }
 
 impl LLMConfig {
@@ -30,7 +102,7 @@ impl LLMConfig {
                 "endpoint must not be empty".to_string(),
             ));
         }
-        if self.api_key.trim().is_empty() {
+        if self.provider_kind.requires_api_key() && self.api_key.trim().is_empty() {
             return Err(LLMServiceError::InvalidConfig(
                 "api_key must not be empty".to_string(),
             ));
@@ -50,16 +122,136 @@ impl LLMConfig {
                 "temperature must be in range [0.0, 2.0]".to_string(),
             ));
         }
+        self.retry_policy.validate()?;
 
         Ok(())
     }
 }
 
+/// 发起 LLM 调用的子系统，用于调用追踪与按子系统选择模型
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum LLMSubsystem {
+    Plot,
+    Npc,
+    Script,
+    Validation,
+    Novel,
+    #[default]
+    Other,
+}
+
+/// 按子系统改用的模型，未列出的子系统使用配置中的模型
+pub type ModelRoutes = BTreeMap<LLMSubsystem, String>;
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct LLMRequest {
     pub prompt: String,
     pub max_tokens: Option<u32>,
     pub temperature: Option<f32>,
+    #[serde(default)]
+    pub subsystem: LLMSubsystem,
+    /// 覆盖配置中的超时与重试策略
+    #[serde(default)]
+    pub retry_policy: Option<RetryPolicy>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatRole {
+    System,
+    User,
+    Assistant,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ChatMessage {
+    pub role: ChatRole,
+    pub content: String,
+}
+
+impl ChatMessage {
+    pub fn user(content: impl Into<String>) -> Self {
+        Self {
+            role: ChatRole::User,
+            content: content.into(),
+        }
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self {
+            role: ChatRole::Assistant,
+            content: content.into(),
+        }
+    }
+}
+
+/// 带系统提示与简短对话历史的请求，`prompt` 作为最后一条用户消息发送
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LLMChatRequest {
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    #[serde(default)]
+    pub history: Vec<ChatMessage>,
+    pub prompt: String,
+    pub max_tokens: Option<u32>,
+    pub temperature: Option<f32>,
+    #[serde(default)]
+    pub subsystem: LLMSubsystem,
+    #[serde(default)]
+    pub retry_policy: Option<RetryPolicy>,
+}
+
+impl LLMChatRequest {
+    /// 按 system → 历史（保留最近几条）→ 当前提示的顺序组装消息
+    pub fn messages(&self) -> Vec<ChatMessage> {
+        let mut messages = Vec::new();
+        if let Some(system_prompt) = self
+            .system_prompt
+            .as_ref()
+            .filter(|s| !s.trim().is_empty())
+        {
+            messages.push(ChatMessage {
+                role: ChatRole::System,
+                content: system_prompt.clone(),
+            });
+        }
+
+        let history = self
+            .history
+            .iter()
+            .filter(|m| m.role != ChatRole::System && !m.content.trim().is_empty())
+            .collect::<Vec<&ChatMessage>>();
+        let skip = history.len().saturating_sub(MAX_HISTORY_MESSAGES);
+        messages.extend(history.into_iter().skip(skip).cloned());
+
+        messages.push(ChatMessage::user(self.prompt.clone()));
+        messages
+    }
+
+    /// 实际发送的消息内容的哈希，用于段落溯源
+    pub fn prompt_hash(&self) -> String {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        for message in self.messages() {
+            (message.role as u8).hash(&mut hasher);
+            message.content.hash(&mut hasher);
+        }
+        format!("{:016x}", hasher.finish())
+    }
+}
+
+impl From<LLMRequest> for LLMChatRequest {
+    fn from(request: LLMRequest) -> Self {
+        Self {
+            system_prompt: None,
+            history: Vec::new(),
+            prompt: request.prompt,
+            max_tokens: request.max_tokens,
+            temperature: request.temperature,
+            subsystem: request.subsystem,
+            retry_policy: request.retry_policy,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -72,6 +264,33 @@ pub struct LLMResponse {
     pub total_tokens: Option<u32>,
 }
 
+/// 结构化输出要求的 JSON Schema，`name` 同时用作 OpenAI 的 schema 名与 Anthropic 的工具名
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResponseSchema {
+    pub name: String,
+    pub schema: Value,
+}
+
+impl ResponseSchema {
+    pub fn for_type<T: JsonSchema>() -> Self {
+        let mut settings = SchemaSettings::draft07();
+        settings.inline_subschemas = true;
+        settings.meta_schema = None;
+        let root = settings.into_generator().into_root_schema_for::<T>();
+        Self {
+            name: T::schema_name(),
+            schema: serde_json::to_value(root.schema).unwrap_or(Value::Bool(true)),
+        }
+    }
+}
+
+/// 结构化调用的结果；`data` 为空时调用方仍可从 `response.text` 中抢救字段
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructuredResponse<T> {
+    pub data: Option<T>,
+    pub response: LLMResponse,
+}
+
 #[derive(Debug)]
 pub enum LLMServiceError {
     InvalidConfig(String),
@@ -80,6 +299,7 @@ pub enum LLMServiceError {
     Api(String),
     InvalidResponse(String),
     Timeout,
+    Cancelled,
 }
 
 impl fmt::Display for LLMServiceError {
@@ -91,6 +311,7 @@ impl fmt::Display for LLMServiceError {
             LLMServiceError::Api(msg) => write!(f, "llm api returned error: {msg}"),
             LLMServiceError::InvalidResponse(msg) => write!(f, "invalid llm response: {msg}"),
             LLMServiceError::Timeout => write!(f, "llm request timed out"),
+            LLMServiceError::Cancelled => write!(f, "llm request cancelled"),
         }
     }
 }
@@ -109,6 +330,7 @@ impl From<reqwest::Error> for LLMServiceError {
 
 pub struct LLMService {
     pub api_config: LLMConfig,
+    model_routes: ModelRoutes,
     client: Client,
     cache: Mutex<ResponseCache>,
 }
@@ -118,7 +340,7 @@ impl LLMService {
         api_config.validate()?;
 
         let client = Client::builder()
-            .timeout(Duration::from_secs(DEFAULT_TIMEOUT_SECS))
+            .timeout(api_config.retry_policy.timeout())
             .build()
             .map_err(LLMServiceError::Http)?;
 
@@ -129,12 +351,89 @@ impl LLMService {
 
         Ok(Self {
             api_config,
+            model_routes: ModelRoutes::new(),
             client,
             cache,
         })
     }
 
+    /// 让指定子系统改用其他模型，其余连接参数沿用配置
+    pub fn with_model_routes(mut self, model_routes: ModelRoutes) -> Self {
+        self.model_routes = model_routes;
+        self
+    }
+
+    /// 该子系统实际使用的模型
+    pub fn model_for(&self, subsystem: LLMSubsystem) -> &str {
+        self.model_routes
+            .get(&subsystem)
+            .map(String::as_str)
+            .filter(|model| !model.trim().is_empty())
+            .unwrap_or(&self.api_config.model)
+    }
+
     pub async fn generate(&self, request: LLMRequest) -> Result<LLMResponse, LLMServiceError> {
+        self.generate_chat(request.into()).await
+    }
+
+    /// 与 `generate` 相同，但令牌取消时立即放弃请求并返回 `Cancelled`
+    pub async fn generate_cancellable(
+        &self,
+        request: LLMRequest,
+        token: &CancellationToken,
+    ) -> Result<LLMResponse, LLMServiceError> {
+        token.scope(self.generate(request)).await
+    }
+
+    /// 发送请求并把提示、原始回复、用量与耗时记入调用追踪
+    pub async fn generate_chat(&self, request: LLMChatRequest) -> Result<LLMResponse, LLMServiceError> {
+        self.send_traced(&request, None).await
+    }
+
+    /// 按 `T` 的 JSON Schema 请求结构化输出，服务商不支持或输出不合 Schema 时回退到抢救解析
+    pub async fn generate_structured<T: DeserializeOwned + JsonSchema>(
+        &self,
+        request: impl Into<LLMChatRequest>,
+    ) -> Result<StructuredResponse<T>, LLMServiceError> {
+        let schema = ResponseSchema::for_type::<T>();
+        let response = self.send_traced(&request.into(), Some(&schema)).await?;
+        Ok(StructuredResponse {
+            data: parse_structured(&response.text),
+            response,
+        })
+    }
+
+    async fn send_traced(
+        &self,
+        request: &LLMChatRequest,
+        schema: Option<&ResponseSchema>,
+    ) -> Result<LLMResponse, LLMServiceError> {
+        let started = Instant::now();
+        // 处在可取消作用域内时，取消会丢弃进行中的 HTTP 请求
+        let result = match cancellation::current_token() {
+            Some(token) if token.is_cancelled() => Err(LLMServiceError::Cancelled),
+            Some(token) => tokio::select! {
+                biased;
+                _ = token.cancelled() => Err(LLMServiceError::Cancelled),
+                result = self.send_chat(request, schema) => result,
+            },
+            None => self.send_chat(request, schema).await,
+        };
+        llm_trace::record_trace(LlmTrace::from_call(
+            request,
+            self.model_for(request.subsystem),
+            &result,
+            started.elapsed(),
+        ));
+        result.map(|(response, _)| response)
+    }
+
+    /// 返回回复以及是否命中缓存
+    async fn send_chat(
+        &self,
+        request: &LLMChatRequest,
+        schema: Option<&ResponseSchema>,
+    ) -> Result<(LLMResponse, bool), LLMServiceError> {
         if request.prompt.trim().is_empty() {
             return Err(LLMServiceError::InvalidRequest(
                 "prompt must not be empty".to_string(),
@@ -158,7 +457,11 @@ impl LLMService {
             ));
         }
 
-        let estimated_prompt_tokens = estimate_token_count(&request.prompt);
+        let messages = request.messages();
+        let estimated_prompt_tokens = messages
+            .iter()
+            .map(|m| estimate_token_count(&m.content))
+            .sum::<u32>();
         let prompt_limit = max_tokens.saturating_mul(64);
         if estimated_prompt_tokens > prompt_limit {
             return Err(LLMServiceError::InvalidRequest(format!(
@@ -167,38 +470,49 @@ impl LLMService {
             )));
         }
 
-        let request_hash = self.build_request_hash(&request.prompt, max_tokens, temperature);
+        let model = self.model_for(request.subsystem);
+        let request_hash = self.build_request_hash(model, &messages, max_tokens, temperature);
         if let Some(cached) = self.get_cached_response(&request_hash) {
-            return Ok(cached);
+            return Ok((cached, true));
         }
 
-        let payload = json!({
-            "model": self.api_config.model,
-            "messages": [
-                { "role": "user", "content": request.prompt }
-            ],
-            "max_tokens": max_tokens,
-            "temperature": temperature,
-            "stream": false
-        });
+        let mut provider_request = llm_provider::build_request(
+            self.api_config.provider_kind,
+            &self.api_config.endpoint,
+            &self.api_config.api_key,
+            model,
+            &messages,
+            max_tokens,
+            temperature,
+        );
+        if let Some(schema) = schema {
+            llm_provider::apply_response_schema(
+                self.api_config.provider_kind,
+                &mut provider_request,
+                schema,
+            );
+        }
 
+        let policy = request.retry_policy.unwrap_or(self.api_config.retry_policy);
+        policy.validate()?;
         let mut attempt = 0;
         loop {
             attempt += 1;
-            let response = self
+            let mut http_request = self
                 .client
-                .post(&self.api_config.endpoint)
-                .bearer_auth(&self.api_config.api_key)
-                .json(&payload)
-                .send()
-                .await;
+                .post(&provider_request.url)
+                .timeout(policy.timeout());
+            for (name, value) in &provider_request.headers {
+                http_request = http_request.header(*name, value);
+            }
+            let response = http_request.json(&provider_request.payload).send().await;
 
             let response = match response {
                 Ok(resp) => resp,
                 Err(err) => {
                     let err = LLMServiceError::from(err);
-                    if attempt <= DEFAULT_MAX_RETRIES && is_retryable_error(&err) {
-                        backoff_sleep(attempt).await;
+                    if attempt <= policy.max_retries && is_retryable_error(&err) {
+                        backoff_sleep(&policy, attempt).await;
                         continue;
                     }
                     return Err(err);
@@ -212,17 +526,17 @@ impl LLMService {
                     .await
                     .unwrap_or_else(|_| "failed to read error response body".to_string());
                 let err = LLMServiceError::Api(format!("status={status} body={body}"));
-                if attempt <= DEFAULT_MAX_RETRIES && is_retryable_status(status.as_u16()) {
-                    backoff_sleep(attempt).await;
+                if attempt <= policy.max_retries && is_retryable_status(status.as_u16()) {
+                    backoff_sleep(&policy, attempt).await;
                     continue;
                 }
                 return Err(err);
             }
 
             let value: Value = response.json().await?;
-            let parsed = Self::parse_response(value)?;
+            let parsed = llm_provider::parse_response(self.api_config.provider_kind, value)?;
             self.cache_response(&request_hash, &parsed);
-            return Ok(parsed);
+            return Ok((parsed, false));
         }
     }
 
@@ -235,7 +549,9 @@ impl LLMService {
     pub fn cache_response_for_request(&self, request: &LLMRequest, response: &LLMResponse) {
         let max_tokens = request.max_tokens.unwrap_or(self.api_config.max_tokens);
         let temperature = request.temperature.unwrap_or(self.api_config.temperature);
-        let request_hash = self.build_request_hash(&request.prompt, max_tokens, temperature);
+        let messages = LLMChatRequest::from(request.clone()).messages();
+        let model = self.model_for(request.subsystem);
+        let request_hash = self.build_request_hash(model, &messages, max_tokens, temperature);
         self.cache_response(&request_hash, response);
     }
 
@@ -243,11 +559,20 @@ impl LLMService {
         self.with_cache(|cache| cache.get(request_hash))
     }
 
-    fn build_request_hash(&self, prompt: &str, max_tokens: u32, temperature: f32) -> String {
+    fn build_request_hash(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        max_tokens: u32,
+        temperature: f32,
+    ) -> String {
         let mut hasher = std::collections::hash_map::DefaultHasher::new();
         self.api_config.endpoint.hash(&mut hasher);
-        self.api_config.model.hash(&mut hasher);
-        prompt.hash(&mut hasher);
+        model.hash(&mut hasher);
+        for message in messages {
+            (message.role as u8).hash(&mut hasher);
+            message.content.hash(&mut hasher);
+        }
         max_tokens.hash(&mut hasher);
         temperature.to_bits().hash(&mut hasher);
         format!("{:016x}", hasher.finish())
@@ -262,50 +587,43 @@ impl LLMService {
             }
         }
     }
+}
 
-    fn parse_response(value: Value) -> Result<LLMResponse, LLMServiceError> {
-        let text = value
-            .pointer("/choices/0/message/content")
-            .and_then(Value::as_str)
-            .or_else(|| value.pointer("/choices/0/text").and_then(Value::as_str))
-            .or_else(|| value.get("output_text").and_then(Value::as_str))
-            .map(|s| s.trim().to_string())
-            .filter(|s| !s.is_empty())
-            .ok_or_else(|| {
-                LLMServiceError::InvalidResponse("unable to locate text content".to_string())
-            })?;
-
-        let model = value
-            .get("model")
-            .and_then(Value::as_str)
-            .map(|s| s.to_string());
-        let finish_reason = value
-            .pointer("/choices/0/finish_reason")
-            .and_then(Value::as_str)
-            .map(|s| s.to_string());
-
-        let prompt_tokens = value
-            .pointer("/usage/prompt_tokens")
-            .and_then(Value::as_u64)
-            .and_then(|v| u32::try_from(v).ok());
-        let completion_tokens = value
-            .pointer("/usage/completion_tokens")
-            .and_then(Value::as_u64)
-            .and_then(|v| u32::try_from(v).ok());
-        let total_tokens = value
-            .pointer("/usage/total_tokens")
-            .and_then(Value::as_u64)
-            .and_then(|v| u32::try_from(v).ok());
-
-        Ok(LLMResponse {
-            text,
-            model,
-            finish_reason,
-            prompt_tokens,
-            completion_tokens,
-            total_tokens,
-        })
+/// 解析结构化输出：先按完整 JSON 解析，失败时剥离代码块或截取首尾大括号之间的内容再试
+pub fn parse_structured<T: DeserializeOwned>(raw: &str) -> Option<T> {
+    serde_json::from_str(raw.trim())
+        .ok()
+        .or_else(|| serde_json::from_value(salvage_json(raw)?).ok())
+}
+
+/// 从夹杂 Markdown 代码块或说明文字的回复中取出 JSON 值
+pub fn salvage_json(raw: &str) -> Option<Value> {
+    let trimmed = raw.trim();
+    if trimmed.is_empty() {
+        return None;
     }
+
+    let mut candidate = trimmed.to_string();
+    if trimmed.starts_with("```") {
+        let mut lines = trimmed.lines();
+        let _ = lines.next();
+        candidate = lines.collect::<Vec<&str>>().join("\n");
+        if let Some(stripped) = candidate.strip_suffix("```") {
+            candidate = stripped.trim().to_string();
+        }
+    }
+    let candidate = candidate.trim();
+
+    if let Ok(value) = serde_json::from_str::<Value>(candidate) {
+        return Some(value);
+    }
+
+    let start = candidate.find('{')?;
+    let end = candidate.rfind('}')?;
+    if start >= end {
+        return None;
+    }
+    serde_json::from_str::<Value>(&candidate[start..=end]).ok()
 }
 
 fn is_retryable_status(status: u16) -> bool {
@@ -335,9 +653,16 @@ fn is_retryable_error(err: &LLMServiceError) -> bool {
     }
 }
 
-async fn backoff_sleep(attempt: u32) {
-    let backoff = DEFAULT_RETRY_BACKOFF_MS.saturating_mul(attempt as u64);
-    tokio::time::sleep(Duration::from_millis(backoff)).await;
+async fn backoff_sleep(policy: &RetryPolicy, attempt: u32) {
+    tokio::time::sleep(policy.backoff_delay(attempt, jitter_fraction(attempt))).await;
+}
+
+/// 0 到 1 之间的随机数，用于错开多个同时失败的请求的重试时间
+fn jitter_fraction(attempt: u32) -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    attempt.hash(&mut hasher);
+    Instant::now().hash(&mut hasher);
+    hasher.finish() as f64 / u64::MAX as f64
 }
 
 #[derive(Debug, Clone)]
@@ -429,6 +754,8 @@ mod tests {
             model: "gpt-test".to_string(),
             max_tokens: 512,
             temperature: 0.7,
+            provider_kind: ProviderKind::OpenAI,
+            retry_policy: RetryPolicy::default(),
         }
     }
 
@@ -449,6 +776,16 @@ mod tests {
         assert!(cfg.validate().is_err());
     }
 
+    #[test]
+    fn test_api_key_optional_only_for_ollama() {
+        let mut cfg = valid_config();
+        cfg.api_key = String::new();
+        assert!(cfg.validate().is_err());
+
+        cfg.provider_kind = ProviderKind::Ollama;
+        assert!(cfg.validate().is_ok());
+    }
+
     #[test]
     fn test_parse_chat_style_response() {
         let raw = json!({
@@ -467,7 +804,7 @@ mod tests {
             }
         });
 
-        let parsed = LLMService::parse_response(raw).unwrap();
+        let parsed = llm_provider::parse_response(ProviderKind::OpenAI, raw).unwrap();
         assert_eq!(parsed.text, "hello cultivator");
         assert_eq!(parsed.model, Some("gpt-test".to_string()));
         assert_eq!(parsed.total_tokens, Some(20));
@@ -485,10 +822,35 @@ mod tests {
             ]
         });
 
-        let parsed = LLMService::parse_response(raw).unwrap();
+        let parsed = llm_provider::parse_response(ProviderKind::OpenAI, raw).unwrap();
         assert_eq!(parsed.text, "plain completion response");
     }
 
+    #[test]
+    fn test_parse_structured_salvages_wrapped_json() {
+        #[derive(Debug, Deserialize, PartialEq, JsonSchema)]
+        struct Verdict {
+            reasonable: bool,
+        }
+
+        let fenced = "```json\n{\"reasonable\": true}\n```";
+        assert_eq!(
+            parse_structured::<Verdict>(fenced),
+            Some(Verdict { reasonable: true })
+        );
+        let chatty = "判定如下：{\"reasonable\": false} 以上。";
+        assert_eq!(
+            parse_structured::<Verdict>(chatty),
+            Some(Verdict { reasonable: false })
+        );
+        assert_eq!(parse_structured::<Verdict>("{\"segment\": 1}"), None);
+
+        let schema = ResponseSchema::for_type::<Verdict>();
+        assert_eq!(schema.name, "Verdict");
+        assert_eq!(schema.schema["properties"]["reasonable"]["type"], "boolean");
+        assert!(schema.schema.get("$schema").is_none());
+    }
+
     #[test]
     fn test_parse_response_without_text_fails() {
         let raw = json!({
@@ -501,7 +863,7 @@ mod tests {
             ]
         });
 
-        let result = LLMService::parse_response(raw);
+        let result = llm_provider::parse_response(ProviderKind::OpenAI, raw);
         assert!(matches!(result, Err(LLMServiceError::InvalidResponse(_))));
     }
 
@@ -513,12 +875,101 @@ mod tests {
             prompt: long_prompt,
             max_tokens: Some(3),
             temperature: Some(0.7),
+            subsystem: LLMSubsystem::Other,
+            retry_policy: None,
         };
 
         let result = service.generate(request).await;
         assert!(matches!(result, Err(LLMServiceError::InvalidRequest(_))));
     }
 
+    #[test]
+    fn test_retry_policy_backs_off_exponentially_with_jitter() {
+        let policy = RetryPolicy {
+            timeout_secs: 10,
+            max_retries: 3,
+            retry_backoff_ms: 200,
+        };
+        assert_eq!(policy.backoff_delay(1, 1.0), Duration::from_millis(200));
+        assert_eq!(policy.backoff_delay(3, 1.0), Duration::from_millis(800));
+        assert_eq!(policy.backoff_delay(3, 0.0), Duration::from_millis(400));
+        assert_eq!(policy.backoff_delay(30, 1.0), Duration::from_millis(MAX_RETRY_BACKOFF_MS));
+        assert!((0.0..=1.0).contains(&jitter_fraction(1)));
+
+        let mut config = valid_config();
+        config.retry_policy.timeout_secs = 0;
+        assert!(matches!(config.validate(), Err(LLMServiceError::InvalidConfig(_))));
+        let legacy: LLMConfig = serde_json::from_value(json!({
+            "endpoint": "https://example.com",
+            "api_key": "k",
+            "model": "m",
+            "max_tokens": 16,
+            "temperature": 0.5
+        }))
+        .unwrap();
+        assert_eq!(legacy.retry_policy, RetryPolicy::default());
+    }
+
+    #[tokio::test]
+    async fn test_generate_cancellable_aborts_before_sending() {
+        let service = LLMService::new(valid_config()).unwrap();
+        let token = CancellationToken::new();
+        token.cancel();
+        let request = LLMRequest {
+            prompt: "继续".to_string(),
+            max_tokens: Some(64),
+            temperature: Some(0.7),
+            subsystem: LLMSubsystem::Other,
+            retry_policy: None,
+        };
+
+        let result = service.generate_cancellable(request, &token).await;
+        assert!(matches!(result, Err(LLMServiceError::Cancelled)));
+    }
+
+    #[test]
+    fn test_chat_request_orders_system_history_and_prompt() {
+        let history = (0..10)
+            .map(|idx| ChatMessage::assistant(format!("段落{idx}")))
+            .chain(std::iter::once(ChatMessage {
+                role: ChatRole::System,
+                content: "ignored".to_string(),
+            }))
+            .collect::<Vec<ChatMessage>>();
+        let request = LLMChatRequest {
+            system_prompt: Some("你是修仙小说的叙事者".to_string()),
+            history,
+            prompt: "继续".to_string(),
+            max_tokens: None,
+            temperature: None,
+            subsystem: LLMSubsystem::Other,
+            retry_policy: None,
+        };
+
+        let messages = request.messages();
+        assert_eq!(messages.len(), MAX_HISTORY_MESSAGES + 2);
+        assert_eq!(messages[0].role, ChatRole::System);
+        assert_eq!(messages[1].content, "段落2");
+        assert_eq!(messages.last().unwrap(), &ChatMessage::user("继续"));
+        assert_eq!(
+            serde_json::to_value(&messages[0]).unwrap(),
+            json!({ "role": "system", "content": "你是修仙小说的叙事者" })
+        );
+    }
+
+    #[test]
+    fn test_plain_request_converts_to_single_user_message() {
+        let request = LLMChatRequest::from(LLMRequest {
+            prompt: "hello".to_string(),
+            max_tokens: Some(10),
+            temperature: Some(0.5),
+            subsystem: LLMSubsystem::Other,
+            retry_policy: None,
+        });
+        assert_eq!(request.messages(), vec![ChatMessage::user("hello")]);
+        assert_eq!(request.max_tokens, Some(10));
+    }
+
     #[test]
     fn test_cache_hit_returns_cached_response() {
         let service = LLMService::new(valid_config()).unwrap();
@@ -536,6 +987,20 @@ mod tests {
         assert_eq!(cached, Some(response));
     }
 
+    #[test]
+    fn test_model_routes_pick_model_per_subsystem() {
+        let service = LLMService::new(valid_config()).unwrap().with_model_routes(
+            [
+                (LLMSubsystem::Npc, "gpt-mini".to_string()),
+                (LLMSubsystem::Novel, " ".to_string()),
+            ]
+            .into(),
+        );
+        assert_eq!(service.model_for(LLMSubsystem::Npc), "gpt-mini");
+        assert_eq!(service.model_for(LLMSubsystem::Plot), "gpt-test");
+        assert_eq!(service.model_for(LLMSubsystem::Novel), "gpt-test");
+    }
+
     #[test]
     fn test_cache_expiry_removes_entry() {
         let mut cache = ResponseCache::new(10, Duration::from_millis(10));
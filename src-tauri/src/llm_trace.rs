@@ -0,0 +1,215 @@
+use crate::llm_service::{ChatRole, LLMChatRequest, LLMResponse, LLMServiceError, LLMSubsystem};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// 环形缓冲区保留的调用记录条数
+pub const MAX_LLM_TRACES: usize = 200;
+pub const DEFAULT_TRACE_LIMIT: usize = 50;
+
+static LLM_TRACES: Mutex<LlmTraceLog> = Mutex::new(LlmTraceLog::new());
+
+/// 一次 LLM 调用的完整记录，供排查生成问题
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LlmTrace {
+    pub id: u64,
+    /// 记录时的 Unix 时间戳（秒）
+    pub recorded_at: u64,
+    pub subsystem: LLMSubsystem,
+    pub model: String,
+    /// 实际发送的消息，按角色逐条拼接
+    pub prompt: String,
+    pub response: Option<String>,
+    pub error: Option<String>,
+    pub cached: bool,
+    pub latency_ms: u64,
+    pub prompt_tokens: Option<u32>,
+    pub completion_tokens: Option<u32>,
+    pub total_tokens: Option<u32>,
+}
+
+impl LlmTrace {
+    pub fn from_call(
+        request: &LLMChatRequest,
+        model: &str,
+        result: &Result<(LLMResponse, bool), LLMServiceError>,
+        latency: Duration,
+    ) -> Self {
+        let prompt = request
+            .messages()
+            .iter()
+            .map(|message| {
+                let role = match message.role {
+                    ChatRole::System => "system",
+                    ChatRole::User => "user",
+                    ChatRole::Assistant => "assistant",
+                };
+                format!("[{}]\n{}", role, message.content)
+            })
+            .collect::<Vec<String>>()
+            .join("\n\n");
+        let response = result.as_ref().ok().map(|(response, _)| response);
+
+        Self {
+            id: 0,
+            recorded_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            subsystem: request.subsystem,
+            model: model.to_string(),
+            prompt,
+            response: response.map(|r| r.text.clone()),
+            error: result.as_ref().err().map(|e| e.to_string()),
+            cached: matches!(result, Ok((_, true))),
+            latency_ms: u64::try_from(latency.as_millis()).unwrap_or(u64::MAX),
+            prompt_tokens: response.and_then(|r| r.prompt_tokens),
+            completion_tokens: response.and_then(|r| r.completion_tokens),
+            total_tokens: response.and_then(|r| r.total_tokens),
+        }
+    }
+}
+
+/// 最近若干次 LLM 调用的环形缓冲区
+#[derive(Debug)]
+pub struct LlmTraceLog {
+    traces: VecDeque<LlmTrace>,
+    next_id: u64,
+}
+
+impl LlmTraceLog {
+    pub const fn new() -> Self {
+        Self {
+            traces: VecDeque::new(),
+            next_id: 1,
+        }
+    }
+
+    pub fn push(&mut self, mut trace: LlmTrace) {
+        trace.id = self.next_id;
+        self.next_id = self.next_id.saturating_add(1);
+        self.traces.push_back(trace);
+        while self.traces.len() > MAX_LLM_TRACES {
+            self.traces.pop_front();
+        }
+    }
+
+    /// 最新的记录在前
+    pub fn recent(&self, limit: usize) -> Vec<LlmTrace> {
+        self.traces.iter().rev().take(limit).cloned().collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.traces.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.traces.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.traces.is_empty()
+    }
+}
+
+impl Default for LlmTraceLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn trace_log() -> MutexGuard<'static, LlmTraceLog> {
+    match LLM_TRACES.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+pub fn record_trace(trace: LlmTrace) {
+    trace_log().push(trace);
+}
+
+pub fn recent_traces(limit: usize) -> Vec<LlmTrace> {
+    trace_log().recent(limit)
+}
+
+pub fn clear_traces() {
+    trace_log().clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm_service::LLMRequest;
+
+    fn request(prompt: &str) -> LLMChatRequest {
+        LLMChatRequest {
+            system_prompt: Some("你是叙事者".to_string()),
+            subsystem: LLMSubsystem::Npc,
+            ..LLMChatRequest::from(LLMRequest {
+                prompt: prompt.to_string(),
+                max_tokens: None,
+                temperature: None,
+                subsystem: LLMSubsystem::Other,
+                retry_policy: None,
+            })
+        }
+    }
+
+    #[test]
+    fn test_trace_records_prompt_response_and_usage() {
+        let response = LLMResponse {
+            text: "好".to_string(),
+            model: None,
+            finish_reason: None,
+            prompt_tokens: Some(12),
+            completion_tokens: Some(3),
+            total_tokens: Some(15),
+        };
+        let trace = LlmTrace::from_call(
+            &request("你好"),
+            "gpt-test",
+            &Ok((response, true)),
+            Duration::from_millis(42),
+        );
+
+        assert_eq!(trace.subsystem, LLMSubsystem::Npc);
+        assert_eq!(trace.prompt, "[system]\n你是叙事者\n\n[user]\n你好");
+        assert_eq!(trace.response.as_deref(), Some("好"));
+        assert!(trace.cached);
+        assert_eq!(trace.latency_ms, 42);
+        assert_eq!(trace.total_tokens, Some(15));
+
+        let failed = LlmTrace::from_call(
+            &request("你好"),
+            "gpt-test",
+            &Err(LLMServiceError::Timeout),
+            Duration::from_secs(30),
+        );
+        assert_eq!(failed.error.as_deref(), Some("llm request timed out"));
+        assert!(failed.response.is_none() && !failed.cached);
+    }
+
+    #[test]
+    fn test_trace_log_keeps_newest_entries() {
+        let mut log = LlmTraceLog::new();
+        let trace = LlmTrace::from_call(
+            &request("你好"),
+            "gpt-test",
+            &Err(LLMServiceError::Timeout),
+            Duration::ZERO,
+        );
+        for _ in 0..MAX_LLM_TRACES + 5 {
+            log.push(trace.clone());
+        }
+
+        assert_eq!(log.len(), MAX_LLM_TRACES);
+        let recent = log.recent(2);
+        assert_eq!(recent[0].id, MAX_LLM_TRACES as u64 + 5);
+        assert_eq!(recent[1].id, MAX_LLM_TRACES as u64 + 4);
+
+        log.clear();
+        assert!(log.is_empty());
+    }
+}
@@ -0,0 +1,361 @@
+use crate::game_state::{Item, ItemType};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+const FALLBACK_SEED: u64 = 0x9E37_79B9_7F4A_7C15;
+
+/// 掉落表的触发来源
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DropSource {
+    Location { location_id: String },
+    EnemyTier { tier: u32 },
+    /// 在秘境中探索时触发
+    SecretRealm { realm_id: String },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum DropRarity {
+    #[default]
+    Common,
+    Rare,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct DropEntry {
+    pub item_id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub item_type: ItemType,
+    pub weight: u32,
+    #[serde(default)]
+    pub rarity: DropRarity,
+    /// 掉落丹药服用后增加的寿元年数
+    #[serde(default)]
+    pub lifespan_bonus: u32,
+}
+
+impl DropEntry {
+    pub fn to_item(&self) -> Item {
+        Item {
+            id: self.item_id.clone(),
+            name: self.name.clone(),
+            description: self.description.clone(),
+            item_type: self.item_type.clone(),
+            lifespan_bonus: self.lifespan_bonus,
+        }
+    }
+}
+
+/// 剧本定义的掉落表，按地点、敌人档位或秘境触发
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct DropTable {
+    pub id: String,
+    pub source: DropSource,
+    #[serde(default = "default_rolls")]
+    pub rolls: u32,
+    /// 每次抽取落空的权重
+    #[serde(default)]
+    pub empty_weight: u32,
+    pub entries: Vec<DropEntry>,
+    /// 连续多少次未出稀有物品后必出稀有
+    #[serde(default)]
+    pub pity_threshold: Option<u32>,
+}
+
+fn default_rolls() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LootError {
+    DuplicateTable(String),
+    EmptyTable(String),
+    ZeroWeight { table_id: String, item_id: String },
+    UnknownLocation { table_id: String, location_id: String },
+    PityWithoutRare(String),
+}
+
+impl fmt::Display for LootError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LootError::DuplicateTable(id) => write!(f, "duplicate drop table '{id}'"),
+            LootError::EmptyTable(id) => write!(f, "drop table '{id}' has no entries"),
+            LootError::ZeroWeight { table_id, item_id } => {
+                write!(f, "drop table '{table_id}' entry '{item_id}' has zero weight")
+            }
+            LootError::UnknownLocation {
+                table_id,
+                location_id,
+            } => write!(
+                f,
+                "drop table '{table_id}' references unknown location '{location_id}'"
+            ),
+            LootError::PityWithoutRare(id) => {
+                write!(f, "drop table '{id}' sets a pity threshold but has no rare entries")
+            }
+        }
+    }
+}
+
+impl std::error::Error for LootError {}
+
+/// 校验剧本中的掉落表
+pub fn validate_drop_tables(tables: &[DropTable], location_ids: &[&str]) -> Result<(), LootError> {
+    let mut seen = HashSet::new();
+    for table in tables {
+        if !seen.insert(table.id.as_str()) {
+            return Err(LootError::DuplicateTable(table.id.clone()));
+        }
+        if table.entries.is_empty() {
+            return Err(LootError::EmptyTable(table.id.clone()));
+        }
+        if let Some(entry) = table.entries.iter().find(|e| e.weight == 0) {
+            return Err(LootError::ZeroWeight {
+                table_id: table.id.clone(),
+                item_id: entry.item_id.clone(),
+            });
+        }
+        if let DropSource::Location { location_id } = &table.source {
+            if !location_ids.contains(&location_id.as_str()) {
+                return Err(LootError::UnknownLocation {
+                    table_id: table.id.clone(),
+                    location_id: location_id.clone(),
+                });
+            }
+        }
+        if table.pity_threshold.is_some()
+            && !table.entries.iter().any(|e| e.rarity == DropRarity::Rare)
+        {
+            return Err(LootError::PityWithoutRare(table.id.clone()));
+        }
+    }
+    Ok(())
+}
+
+pub fn table_for_location<'a>(tables: &'a [DropTable], location_id: &str) -> Option<&'a DropTable> {
+    tables.iter().find(|table| {
+        matches!(&table.source, DropSource::Location { location_id: id } if id == location_id)
+    })
+}
+
+/// 取不高于敌人档位的最高档掉落表
+pub fn table_for_enemy_tier(tables: &[DropTable], tier: u32) -> Option<&DropTable> {
+    tables
+        .iter()
+        .filter_map(|table| match table.source {
+            DropSource::EnemyTier { tier: table_tier } if table_tier <= tier => {
+                Some((table_tier, table))
+            }
+            _ => None,
+        })
+        .max_by_key(|(table_tier, _)| *table_tier)
+        .map(|(_, table)| table)
+}
+
+/// 一次掉落结算的结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct LootRoll {
+    pub table_id: String,
+    pub items: Vec<Item>,
+    pub pity_triggered: bool,
+}
+
+impl LootRoll {
+    /// 供剧情叙述使用的拾取描述，没有掉落时返回 None
+    pub fn narration(&self) -> Option<String> {
+        if self.items.is_empty() {
+            return None;
+        }
+        Some(format!(
+            "拾得{}",
+            self.items
+                .iter()
+                .map(|item| item.name.as_str())
+                .collect::<Vec<&str>>()
+                .join("、")
+        ))
+    }
+}
+
+/// 掉落随机数状态与稀有保底计数，随游戏状态保存
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct LootState {
+    pub rng_seed: u64,
+    #[serde(default)]
+    pub pity_counters: HashMap<String, u32>,
+}
+
+impl LootState {
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            rng_seed: seed,
+            pity_counters: HashMap::new(),
+        }
+    }
+
+    /// 按掉落表抽取物品，并更新保底计数
+    pub fn roll(&mut self, table: &DropTable) -> LootRoll {
+        let mut items = Vec::new();
+        let mut pity_triggered = false;
+
+        for _ in 0..table.rolls {
+            let counter = self.pity_counters.get(&table.id).copied().unwrap_or(0);
+            let force_rare = table
+                .pity_threshold
+                .is_some_and(|threshold| counter.saturating_add(1) >= threshold);
+
+            let entry = if force_rare {
+                pity_triggered = true;
+                self.pick(table.entries.iter().filter(|e| e.rarity == DropRarity::Rare), 0)
+            } else {
+                self.pick(table.entries.iter(), table.empty_weight)
+            };
+
+            let got_rare = entry.is_some_and(|e| e.rarity == DropRarity::Rare);
+            if table.pity_threshold.is_some() {
+                let next = if got_rare { 0 } else { counter.saturating_add(1) };
+                self.pity_counters.insert(table.id.clone(), next);
+            }
+            if let Some(entry) = entry {
+                items.push(entry.to_item());
+            }
+        }
+
+        LootRoll {
+            table_id: table.id.clone(),
+            items,
+            pity_triggered,
+        }
+    }
+
+    fn pick<'a>(
+        &mut self,
+        entries: impl Iterator<Item = &'a DropEntry> + Clone,
+        empty_weight: u32,
+    ) -> Option<&'a DropEntry> {
+        let total = entries
+            .clone()
+            .map(|e| u64::from(e.weight))
+            .sum::<u64>()
+            + u64::from(empty_weight);
+        if total == 0 {
+            return None;
+        }
+
+        let mut point = self.next_u64() % total;
+        for entry in entries {
+            let weight = u64::from(entry.weight);
+            if point < weight {
+                return Some(entry);
+            }
+            point -= weight;
+        }
+        None
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut x = if self.rng_seed == 0 {
+            FALLBACK_SEED
+        } else {
+            self.rng_seed
+        };
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.rng_seed = x;
+        x
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(item_id: &str, weight: u32, rarity: DropRarity) -> DropEntry {
+        DropEntry {
+            item_id: item_id.to_string(),
+            name: item_id.to_string(),
+            description: String::new(),
+            item_type: ItemType::Material,
+            weight,
+            rarity,
+            lifespan_bonus: 0,
+        }
+    }
+
+    fn table(pity_threshold: Option<u32>) -> DropTable {
+        DropTable {
+            id: "stone_forest".to_string(),
+            source: DropSource::Location {
+                location_id: "stone_forest".to_string(),
+            },
+            rolls: 1,
+            empty_weight: 0,
+            entries: vec![
+                entry("下品灵石", 1000, DropRarity::Common),
+                entry("玄铁精", 1, DropRarity::Rare),
+            ],
+            pity_threshold,
+        }
+    }
+
+    #[test]
+    fn test_same_seed_rolls_same_items() {
+        let mut a = LootState::with_seed(42);
+        let mut b = LootState::with_seed(42);
+        for _ in 0..10 {
+            assert_eq!(a.roll(&table(None)), b.roll(&table(None)));
+        }
+    }
+
+    #[test]
+    fn test_pity_guarantees_rare_and_resets() {
+        let mut state = LootState::with_seed(7);
+        let table = table(Some(3));
+
+        let rolls = (0..3).map(|_| state.roll(&table)).collect::<Vec<LootRoll>>();
+        assert!(rolls[2].pity_triggered);
+        assert_eq!(rolls[2].items[0].name, "玄铁精");
+        assert_eq!(state.pity_counters.get("stone_forest"), Some(&0));
+    }
+
+    #[test]
+    fn test_narration_lists_items() {
+        let mut state = LootState::with_seed(1);
+        let roll = state.roll(&table(None));
+        assert_eq!(roll.narration().unwrap(), format!("拾得{}", roll.items[0].name));
+
+        let empty = LootRoll {
+            table_id: "t".to_string(),
+            items: Vec::new(),
+            pity_triggered: false,
+        };
+        assert!(empty.narration().is_none());
+    }
+
+    #[test]
+    fn test_table_for_enemy_tier_picks_highest_not_above() {
+        let mut low = table(None);
+        low.id = "tier1".to_string();
+        low.source = DropSource::EnemyTier { tier: 1 };
+        let mut high = table(None);
+        high.id = "tier3".to_string();
+        high.source = DropSource::EnemyTier { tier: 3 };
+        let tables = vec![low, high];
+
+        assert_eq!(table_for_enemy_tier(&tables, 2).unwrap().id, "tier1");
+        assert_eq!(table_for_enemy_tier(&tables, 5).unwrap().id, "tier3");
+        assert!(table_for_enemy_tier(&tables, 0).is_none());
+    }
+
+    #[test]
+    fn test_validate_drop_tables_rejects_unknown_location() {
+        let result = validate_drop_tables(&[table(None)], &["sect_valley"]);
+        assert!(matches!(result, Err(LootError::UnknownLocation { .. })));
+        assert!(validate_drop_tables(&[table(Some(5))], &["stone_forest"]).is_ok());
+    }
+}
@@ -0,0 +1,329 @@
+use crate::difficulty::DifficultySettings;
+use crate::economy::ResourceKind;
+use crate::game_state::{GameState, Item, ItemType};
+use crate::llm_service::{LLMRequest, LLMService, LLMSubsystem};
+use crate::loot::{DropEntry, DropRarity};
+use crate::numerical_system::{NumericalSystem, StatChange};
+use crate::prompt_builder::{PromptBuilder, PromptConstraints, PromptContext, PromptTemplate};
+use crate::rng::GameRng;
+use crate::script::{Location, Script};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// 市集每隔多少天换一批货
+pub const MARKET_ROTATION_DAYS: u32 = 30;
+/// 每批上架的货品种数
+const MARKET_LISTINGS: usize = 4;
+/// 标价在基准价上下浮动的幅度
+const PRICE_JITTER: f32 = 0.2;
+const RARE_PRICE_MULTIPLIER: u64 = 3;
+const MAX_COMMON_STOCK: u32 = 3;
+
+/// 市集架上的一种货品
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct MarketListing {
+    pub item: Item,
+    pub rarity: DropRarity,
+    /// 买入单价（灵石）
+    pub price: u64,
+    pub stock: u32,
+    /// 摊主的吆喝，有 LLM 时由其润色
+    pub pitch: String,
+}
+
+/// 灵草、矿石等资源的收购与出售单价
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ResourceQuote {
+    pub kind: ResourceKind,
+    pub buy_price: u64,
+    pub sell_price: u64,
+}
+
+/// 城镇中的市集，货品按地点与轮换批次确定，同一批内价格不变
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Market {
+    pub location_id: String,
+    pub location_name: String,
+    /// 第几批货，每 `MARKET_ROTATION_DAYS` 天轮换一次
+    pub rotation: u32,
+    pub listings: Vec<MarketListing>,
+    pub resource_quotes: Vec<ResourceQuote>,
+    /// 吆喝是否已由 LLM 润色
+    #[serde(default)]
+    pub flavored: bool,
+}
+
+/// 一笔市集交易的结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MarketReceipt {
+    pub description: String,
+    pub stat_changes: Vec<StatChange>,
+    pub market: Market,
+}
+
+pub fn rotation_for_day(total_days: u32) -> u32 {
+    total_days / MARKET_ROTATION_DAYS
+}
+
+/// 货品的基准价（灵石）：按种类定价，丹药按延寿年数加价，稀有货三倍
+pub fn base_item_price(item: &Item, rarity: DropRarity) -> u64 {
+    let base = match item.item_type {
+        ItemType::Material => 10,
+        ItemType::Medicine => 30 + u64::from(item.lifespan_bonus) * 5,
+        ItemType::Artifact => 80,
+        ItemType::Technique => 120,
+    };
+    match rarity {
+        DropRarity::Common => base,
+        DropRarity::Rare => base * RARE_PRICE_MULTIPLIER,
+    }
+}
+
+impl Market {
+    /// 按本局种子、地点与轮换批次生成货架，同一批次总得到同样的货品与标价
+    pub fn generate(state: &GameState, location: &Location, numerical: &NumericalSystem) -> Self {
+        let rotation = rotation_for_day(state.game_time.total_days);
+        let mut rng = GameRng::new(
+            state
+                .rng
+                .derive_seed(&format!("market:{}:{}", location.id, rotation)),
+        );
+        let mut catalog = catalog(&state.script);
+        let mut listings = Vec::new();
+        while listings.len() < MARKET_LISTINGS && !catalog.is_empty() {
+            let weights = catalog.iter().map(|entry| entry.weight.max(1)).collect::<Vec<u32>>();
+            let entry = catalog.remove(rng.weighted_index(&weights));
+            listings.push(list_entry(&entry, &state.difficulty, &mut rng));
+        }
+        let resource_quotes = ResourceKind::ALL
+            .iter()
+            .filter(|kind| **kind != ResourceKind::SpiritStones)
+            .map(|kind| ResourceQuote {
+                kind: *kind,
+                buy_price: numerical.market_price(*kind, true),
+                sell_price: numerical.market_price(*kind, false),
+            })
+            .collect();
+
+        Self {
+            location_id: location.id.clone(),
+            location_name: location.name.clone(),
+            rotation,
+            listings,
+            resource_quotes,
+            flavored: false,
+        }
+    }
+
+    /// 货架是否仍是该地点本批次的货
+    pub fn is_current(&self, location_id: &str, total_days: u32) -> bool {
+        self.location_id == location_id && self.rotation == rotation_for_day(total_days)
+    }
+
+    pub fn listing(&self, item_id: &str) -> Option<&MarketListing> {
+        self.listings.iter().find(|listing| listing.item.id == item_id)
+    }
+
+    pub fn listing_mut(&mut self, item_id: &str) -> Option<&mut MarketListing> {
+        self.listings
+            .iter_mut()
+            .find(|listing| listing.item.id == item_id)
+    }
+
+    /// 把背包物品卖给市集的价格：架上有同样货品时为标价的一半，否则为基准价的一半
+    pub fn sell_price(&self, item: &Item) -> u64 {
+        let price = self
+            .listing(&item.id)
+            .map(|listing| listing.price)
+            .unwrap_or_else(|| base_item_price(item, DropRarity::Common));
+        (price / 2).max(1)
+    }
+}
+
+/// 市集可进的货：剧本掉落表中的全部物品，剧本没有掉落表时用默认货品
+fn catalog(script: &Script) -> Vec<DropEntry> {
+    let mut seen = HashSet::new();
+    let entries = script
+        .drop_tables
+        .iter()
+        .flat_map(|table| table.entries.iter())
+        .filter(|entry| seen.insert(entry.item_id.clone()))
+        .cloned()
+        .collect::<Vec<DropEntry>>();
+    if entries.is_empty() {
+        default_catalog()
+    } else {
+        entries
+    }
+}
+
+fn default_catalog() -> Vec<DropEntry> {
+    let entry = |item_id: &str, name: &str, description: &str, item_type, weight, rarity, lifespan_bonus| {
+        DropEntry {
+            item_id: item_id.to_string(),
+            name: name.to_string(),
+            description: description.to_string(),
+            item_type,
+            weight,
+            rarity,
+            lifespan_bonus,
+        }
+    };
+    vec![
+        entry("market_qi_pill", "聚气丹", "助修士凝聚灵气的常见丹药", ItemType::Medicine, 10, DropRarity::Common, 0),
+        entry("market_iron_essence", "玄铁精", "炼器常用的精铁", ItemType::Material, 10, DropRarity::Common, 0),
+        entry("market_jade_charm", "护身玉佩", "可挡下一次致命攻击的玉佩", ItemType::Artifact, 4, DropRarity::Common, 0),
+        entry("market_longevity_pill", "延寿丹", "服之可延寿十载", ItemType::Medicine, 2, DropRarity::Rare, 10),
+    ]
+}
+
+fn list_entry(entry: &DropEntry, difficulty: &DifficultySettings, rng: &mut GameRng) -> MarketListing {
+    let item = entry.to_item();
+    let scarcity = difficulty.resource_scarcity.clamp(0.0, 1.0);
+    let jitter = rng.range_f32(1.0 - PRICE_JITTER, 1.0 + PRICE_JITTER);
+    let price = (base_item_price(&item, entry.rarity) as f32 * (1.0 + scarcity) * jitter)
+        .round()
+        .max(1.0) as u64;
+    let stock = match entry.rarity {
+        DropRarity::Common => rng.range_u32(1, MAX_COMMON_STOCK),
+        DropRarity::Rare => 1,
+    };
+    MarketListing {
+        pitch: fallback_pitch(&item, price),
+        item,
+        rarity: entry.rarity,
+        price,
+        stock,
+    }
+}
+
+pub fn fallback_pitch(item: &Item, price: u64) -> String {
+    if item.description.is_empty() {
+        format!("{}，{} 灵石一件。", item.name, price)
+    } else {
+        format!("{}：{}，{} 灵石一件。", item.name, item.description, price)
+    }
+}
+
+/// 请 LLM 为货架写摊主吆喝，只改吆喝，货品与标价不变；没有 LLM 或返回行数不足时保留原样
+pub async fn flavor_market(llm_service: Option<&LLMService>, mut market: Market) -> Market {
+    if market.flavored || market.listings.is_empty() {
+        return market;
+    }
+    if let Some(llm_service) = llm_service {
+        let request = LLMRequest {
+            prompt: build_market_prompt(&market),
+            max_tokens: Some(400),
+            temperature: Some(0.9),
+            subsystem: LLMSubsystem::Plot,
+            retry_policy: None,
+        };
+        if let Ok(response) = llm_service.generate(request).await {
+            let pitches = response
+                .text
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .collect::<Vec<&str>>();
+            if pitches.len() >= market.listings.len() {
+                for (listing, pitch) in market.listings.iter_mut().zip(pitches) {
+                    listing.pitch = pitch.to_string();
+                }
+                market.flavored = true;
+            }
+        }
+    }
+    market
+}
+
+pub fn build_market_prompt(market: &Market) -> String {
+    let scene = market
+        .listings
+        .iter()
+        .map(|listing| {
+            format!(
+                "- {} ({:?}): {} | price {} spirit stones",
+                listing.item.name, listing.item.item_type, listing.item.description, listing.price
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+    let context = PromptContext {
+        scene: Some(format!("Goods on sale at the market:\n{}", scene)),
+        location: Some(market.location_name.clone()),
+        ..PromptContext::default()
+    };
+    let constraints = PromptConstraints {
+        numerical_rules: vec!["do not change any price or invent new goods".to_string()],
+        world_rules: vec![
+            "write one short Chinese sentence per item, in the listed order".to_string(),
+            "output exactly one line per item with no numbering".to_string(),
+        ],
+        output_schema_hint: None,
+    };
+
+    PromptBuilder::default().build_prompt_with_token_limit(
+        PromptTemplate::MarketFlavor,
+        &context,
+        &constraints,
+        600,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_engine::GameEngine;
+    use crate::models::CultivationRealm;
+    use crate::script::{Climate, LocationKind};
+    use crate::script_manager::ScriptManager;
+
+    fn city_state() -> (GameState, Location) {
+        let town = Location {
+            id: "town".to_string(),
+            name: "凡人镇".to_string(),
+            description: String::new(),
+            spiritual_energy: 0.3,
+            kind: LocationKind::City,
+            climate: Climate::Temperate,
+        };
+        let mut script = ScriptManager::new().blank_script();
+        script
+            .world_setting
+            .cultivation_realms
+            .push(CultivationRealm::new("练气".to_string(), 1, 0, 1.0));
+        script.world_setting.locations.push(town.clone());
+        script.initial_state.starting_location = "town".to_string();
+        let mut engine = GameEngine::new();
+        (engine.initialize_game(script).unwrap(), town)
+    }
+
+    #[test]
+    fn test_stock_and_prices_are_deterministic_per_rotation() {
+        let (mut state, town) = city_state();
+        let numerical = NumericalSystem::new();
+        let market = Market::generate(&state, &town, &numerical);
+        assert_eq!(market.listings.len(), MARKET_LISTINGS);
+        assert_eq!(market, Market::generate(&state, &town, &numerical));
+        assert!(market.is_current("town", MARKET_ROTATION_DAYS - 1));
+        assert!(!market.is_current("town", MARKET_ROTATION_DAYS));
+        for listing in &market.listings {
+            let base = base_item_price(&listing.item, listing.rarity) as f32;
+            assert!(listing.price as f32 >= (base * 0.8).floor());
+            assert!(listing.price as f32 <= (base * 1.2).ceil());
+            assert!(listing.stock >= 1);
+        }
+        let herbs = market.resource_quotes[0];
+        assert_eq!(herbs.kind, ResourceKind::Herbs);
+        assert_eq!((herbs.buy_price, herbs.sell_price), (5, 2));
+
+        let pill = market.listing("market_longevity_pill").unwrap();
+        assert_eq!(pill.rarity, DropRarity::Rare);
+        assert_eq!(market.sell_price(&pill.item), pill.price / 2);
+
+        state.difficulty = DifficultySettings::hard();
+        let scarce = Market::generate(&state, &town, &numerical);
+        assert!(scarce.listings[0].price > market.listings[0].price);
+    }
+}
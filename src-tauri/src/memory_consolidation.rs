@@ -0,0 +1,439 @@
+use crate::llm_service::{LLMRequest, LLMService, LLMSubsystem};
+use crate::memory_manager::fallback_summary;
+use crate::npc::MemoryEntry;
+use crate::prompt_builder::{PromptBuilder, PromptConstraints, PromptContext, PromptTemplate};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// 单个提示中合并整理记忆的 NPC 数
+pub const DEFAULT_MEMORY_BATCH_SIZE: usize = 5;
+const SUMMARY_TOKENS_PER_NPC: u32 = 120;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryJobKind {
+    /// 合并过多的非重要长期记忆
+    #[default]
+    Consolidate,
+    /// 把重要或情绪强烈的短期记忆转入长期记忆
+    Promote,
+}
+
+/// 一名 NPC 待整合的记忆
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemoryJob {
+    pub npc_id: String,
+    pub npc_name: String,
+    pub entries: Vec<MemoryEntry>,
+    #[serde(default)]
+    pub kind: MemoryJobKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SummarySource {
+    /// 批量提示中的分节结果
+    Batch,
+    /// 批量结果缺失或无法解析后的单独调用
+    Individual,
+    /// LLM 不可用时的规则摘要
+    Fallback,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemorySummary {
+    pub npc_id: String,
+    pub summary: String,
+    pub source: SummarySource,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConsolidationReport {
+    pub summaries: Vec<MemorySummary>,
+    pub llm_calls: u32,
+}
+
+/// 把多名 NPC 的记忆整合合并到同一提示中，避免大事件后逐个调用 LLM 触发限流
+pub struct MemoryConsolidator {
+    llm_service: Option<Arc<LLMService>>,
+    prompt_builder: PromptBuilder,
+    batch_size: usize,
+}
+
+impl MemoryConsolidator {
+    pub fn new() -> Self {
+        Self {
+            llm_service: None,
+            prompt_builder: PromptBuilder::default(),
+            batch_size: DEFAULT_MEMORY_BATCH_SIZE,
+        }
+    }
+
+    pub fn with_llm_service(mut self, llm_service: Arc<LLMService>) -> Self {
+        self.llm_service = Some(llm_service);
+        self
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// 依次处理每批 NPC；批量结果缺少的 NPC 改为单独调用，仍失败时使用规则摘要
+    pub async fn consolidate(&self, jobs: &[MemoryJob]) -> ConsolidationReport {
+        let mut report = ConsolidationReport::default();
+        let Some(llm_service) = &self.llm_service else {
+            report.summaries = jobs.iter().map(fallback_for).collect();
+            return report;
+        };
+
+        for chunk in jobs.chunks(self.batch_size) {
+            let mut batch = HashMap::new();
+            if chunk.len() > 1 {
+                report.llm_calls += 1;
+                batch = self
+                    .summarize_batch(llm_service, chunk)
+                    .await
+                    .unwrap_or_default();
+            }
+
+            for job in chunk {
+                if let Some(summary) = batch.remove(&job.npc_id) {
+                    report.summaries.push(MemorySummary {
+                        npc_id: job.npc_id.clone(),
+                        summary,
+                        source: SummarySource::Batch,
+                    });
+                    continue;
+                }
+
+                report.llm_calls += 1;
+                report.summaries.push(
+                    match self.summarize_single(llm_service, job).await {
+                        Ok(summary) => MemorySummary {
+                            npc_id: job.npc_id.clone(),
+                            summary,
+                            source: SummarySource::Individual,
+                        },
+                        Err(_) => fallback_for(job),
+                    },
+                );
+            }
+        }
+
+        report
+    }
+
+    async fn summarize_batch(
+        &self,
+        llm_service: &LLMService,
+        jobs: &[MemoryJob],
+    ) -> Result<HashMap<String, String>, String> {
+        let response = llm_service
+            .generate(LLMRequest {
+                prompt: self.build_batch_prompt(jobs),
+                max_tokens: Some(SUMMARY_TOKENS_PER_NPC.saturating_mul(jobs.len() as u32)),
+                temperature: Some(0.3),
+                subsystem: LLMSubsystem::Npc,
+                retry_policy: None,
+            })
+            .await
+            .map_err(|e| e.to_string())?;
+
+        parse_batch_summaries(&response.text, jobs)
+    }
+
+    async fn summarize_single(
+        &self,
+        llm_service: &LLMService,
+        job: &MemoryJob,
+    ) -> Result<String, String> {
+        let response = llm_service
+            .generate(LLMRequest {
+                prompt: self.build_single_prompt(job),
+                max_tokens: Some(SUMMARY_TOKENS_PER_NPC),
+                temperature: Some(0.3),
+                subsystem: LLMSubsystem::Npc,
+                retry_policy: None,
+            })
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let parsed: Value = serde_json::from_str(&response.text).map_err(|e| e.to_string())?;
+        parsed
+            .get("summary")
+            .and_then(Value::as_str)
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(ToString::to_string)
+            .ok_or_else(|| "memory summary missing".to_string())
+    }
+
+    fn build_batch_prompt(&self, jobs: &[MemoryJob]) -> String {
+        let sections = jobs.iter().map(memory_section).collect::<Vec<String>>();
+        let context = PromptContext {
+            scene: Some(format!(
+                "Summarize the memories of each NPC section separately.\n{}",
+                sections.join("\n")
+            )),
+            location: None,
+            actor_name: None,
+            actor_realm: None,
+            actor_combat_power: None,
+            player_persona: None,
+            canon_facts: Vec::new(),
+            story_beat: None,
+            chapter_beat: None,
+            environment: None,
+            active_quests: Vec::new(),
+            relationships: Vec::new(),
+            status_effects: Vec::new(),
+            history_events: Vec::new(),
+            world_setting_summary: None,
+        };
+        let constraints = PromptConstraints {
+            numerical_rules: Vec::new(),
+            world_rules: vec![
+                "respond in strict JSON only".to_string(),
+                "one summary per npc_id, at most 60 Chinese characters each".to_string(),
+                "never mix memories between sections".to_string(),
+            ],
+            output_schema_hint: Some(
+                "{\"summaries\":[{\"npc_id\":\"string\",\"summary\":\"string\"}]}".to_string(),
+            ),
+        };
+
+        self.prompt_builder.build_prompt_with_token_limit(
+            PromptTemplate::MemoryConsolidation,
+            &context,
+            &constraints,
+            2400,
+        )
+    }
+
+    fn build_single_prompt(&self, job: &MemoryJob) -> String {
+        let context = PromptContext {
+            scene: Some(memory_section(job)),
+            location: None,
+            actor_name: Some(job.npc_name.clone()),
+            actor_realm: None,
+            actor_combat_power: None,
+            player_persona: None,
+            canon_facts: Vec::new(),
+            story_beat: None,
+            chapter_beat: None,
+            environment: None,
+            active_quests: Vec::new(),
+            relationships: Vec::new(),
+            status_effects: Vec::new(),
+            history_events: Vec::new(),
+            world_setting_summary: None,
+        };
+        let constraints = PromptConstraints {
+            numerical_rules: Vec::new(),
+            world_rules: vec![
+                "respond in strict JSON only".to_string(),
+                "summary at most 60 Chinese characters".to_string(),
+            ],
+            output_schema_hint: Some("{\"summary\":\"string\"}".to_string()),
+        };
+
+        self.prompt_builder.build_prompt_with_token_limit(
+            PromptTemplate::MemoryConsolidation,
+            &context,
+            &constraints,
+            600,
+        )
+    }
+}
+
+impl Default for MemoryConsolidator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn memory_section(job: &MemoryJob) -> String {
+    let mut section = format!("### npc_id: {} ({})", job.npc_id, job.npc_name);
+    for entry in &job.entries {
+        section.push_str(&format!("\n- day {}: {}", entry.timestamp, entry.event));
+    }
+    section
+}
+
+fn fallback_for(job: &MemoryJob) -> MemorySummary {
+    MemorySummary {
+        npc_id: job.npc_id.clone(),
+        summary: fallback_summary(&job.entries),
+        source: SummarySource::Fallback,
+    }
+}
+
+/// 只接受本批次中的 npc_id，重复项以第一条为准
+fn parse_batch_summaries(text: &str, jobs: &[MemoryJob]) -> Result<HashMap<String, String>, String> {
+    let parsed: Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    let items = parsed
+        .get("summaries")
+        .and_then(Value::as_array)
+        .ok_or_else(|| "batch summaries must be a JSON array".to_string())?;
+
+    let mut summaries = HashMap::new();
+    for item in items {
+        let npc_id = item.get("npc_id").and_then(Value::as_str).unwrap_or_default();
+        let summary = item
+            .get("summary")
+            .and_then(Value::as_str)
+            .map(str::trim)
+            .unwrap_or_default();
+        if summary.is_empty() || !jobs.iter().any(|job| job.npc_id == npc_id) {
+            continue;
+        }
+        summaries
+            .entry(npc_id.to_string())
+            .or_insert_with(|| summary.to_string());
+    }
+    Ok(summaries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm_provider::ProviderKind;
+    use crate::llm_service::{LLMConfig, LLMResponse};
+
+    fn job(npc_id: &str) -> MemoryJob {
+        MemoryJob {
+            npc_id: npc_id.to_string(),
+            npc_name: format!("NPC {}", npc_id),
+            entries: vec![
+                MemoryEntry {
+                    timestamp: 1,
+                    event: format!("{} guarded the gate", npc_id),
+                    importance: 0.3,
+                    emotional_impact: 0.1,
+                },
+                MemoryEntry {
+                    timestamp: 2,
+                    event: format!("{} traded herbs", npc_id),
+                    importance: 0.4,
+                    emotional_impact: 0.2,
+                },
+            ],
+            kind: MemoryJobKind::Consolidate,
+        }
+    }
+
+    fn llm_service() -> LLMService {
+        LLMService::new(LLMConfig {
+            endpoint: "https://example.com/v1/chat/completions".to_string(),
+            api_key: "test-key".to_string(),
+            model: "gpt-test".to_string(),
+            max_tokens: 1024,
+            temperature: 0.3,
+            provider_kind: ProviderKind::OpenAI,
+            retry_policy: Default::default(),
+        })
+        .unwrap()
+    }
+
+    fn cache(llm_service: &LLMService, prompt: String, max_tokens: u32, text: &str) {
+        llm_service.cache_response_for_request(
+            &LLMRequest {
+                prompt,
+                max_tokens: Some(max_tokens),
+                temperature: Some(0.3),
+                subsystem: LLMSubsystem::Npc,
+                retry_policy: None,
+            },
+            &LLMResponse {
+                text: text.to_string(),
+                model: None,
+                finish_reason: None,
+                prompt_tokens: None,
+                completion_tokens: None,
+                total_tokens: None,
+            },
+        );
+    }
+
+    #[tokio::test]
+    async fn test_batch_summaries_cut_llm_calls() {
+        let jobs = (0..10).map(|i| job(&format!("n{}", i))).collect::<Vec<MemoryJob>>();
+        let llm_service = llm_service();
+        let consolidator = MemoryConsolidator::new();
+        for chunk in jobs.chunks(DEFAULT_MEMORY_BATCH_SIZE) {
+            let items = chunk
+                .iter()
+                .map(|job| {
+                    serde_json::json!({ "npc_id": job.npc_id, "summary": format!("{}守门换药", job.npc_id) })
+                })
+                .collect::<Vec<Value>>();
+            cache(
+                &llm_service,
+                consolidator.build_batch_prompt(chunk),
+                SUMMARY_TOKENS_PER_NPC * chunk.len() as u32,
+                &serde_json::json!({ "summaries": items }).to_string(),
+            );
+        }
+
+        let report = consolidator
+            .with_llm_service(Arc::new(llm_service))
+            .consolidate(&jobs)
+            .await;
+
+        assert_eq!(report.llm_calls, 2);
+        assert_eq!(report.summaries.len(), 10);
+        assert!(report
+            .summaries
+            .iter()
+            .all(|s| s.source == SummarySource::Batch && s.summary.ends_with("守门换药")));
+    }
+
+    #[tokio::test]
+    async fn test_missing_sections_fall_back_to_individual_calls() {
+        let jobs = vec![job("a"), job("b"), job("c")];
+        let llm_service = llm_service();
+        let consolidator = MemoryConsolidator::new();
+        cache(
+            &llm_service,
+            consolidator.build_batch_prompt(&jobs),
+            SUMMARY_TOKENS_PER_NPC * 3,
+            r#"{"summaries":[{"npc_id":"a","summary":"a 的往事"},{"npc_id":"stranger","summary":"无关"}]}"#,
+        );
+        cache(
+            &llm_service,
+            consolidator.build_single_prompt(&jobs[1]),
+            SUMMARY_TOKENS_PER_NPC,
+            r#"{"summary":"b 的往事"}"#,
+        );
+        cache(
+            &llm_service,
+            consolidator.build_single_prompt(&jobs[2]),
+            SUMMARY_TOKENS_PER_NPC,
+            "抱歉，无法整理",
+        );
+
+        let report = consolidator
+            .with_llm_service(Arc::new(llm_service))
+            .consolidate(&jobs)
+            .await;
+
+        assert_eq!(report.llm_calls, 3);
+        let sources = report
+            .summaries
+            .iter()
+            .map(|s| (s.npc_id.as_str(), s.source))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sources,
+            vec![
+                ("a", SummarySource::Batch),
+                ("b", SummarySource::Individual),
+                ("c", SummarySource::Fallback),
+            ]
+        );
+        assert!(report.summaries[2].summary.starts_with("往事2则："));
+    }
+}
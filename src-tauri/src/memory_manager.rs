@@ -1,10 +1,28 @@
 ﻿use crate::npc::{MemoryEntry, NPCMemory};
 
+/// 长期记忆超过该条数时触发整合
+const DEFAULT_CONSOLIDATION_THRESHOLD: usize = 40;
+/// 单次整合最多合并的记忆条数
+const MAX_CONSOLIDATED_ENTRIES: usize = 12;
+const FALLBACK_SUMMARY_EVENTS: usize = 3;
+/// 非重要记忆每过一天保留的重要度比例
+const MEMORY_DAILY_RETENTION: f32 = 0.97;
+/// 重要度衰减到该值以下的记忆被遗忘
+const FORGET_IMPORTANCE: f32 = 0.05;
+/// 短期记忆达到该重要度或情绪强度即转入长期记忆
+const PROMOTION_IMPORTANCE: f32 = 0.6;
+const PROMOTION_EMOTION: f32 = 0.7;
+/// 单次转入长期记忆的最多条数
+const MAX_PROMOTED_ENTRIES: usize = 6;
+/// 重要事件保留上限，避免长局中提示无限增长
+const MAX_IMPORTANT_EVENTS: usize = 30;
+
 #[derive(Debug, Clone)]
 pub struct MemoryManager {
     short_term_limit: usize,
     long_term_limit: usize,
     important_threshold: f32,
+    consolidation_threshold: usize,
 }
 
 impl MemoryManager {
@@ -13,10 +31,129 @@ impl MemoryManager {
             short_term_limit: short_term_limit.max(1),
             long_term_limit: long_term_limit.max(1),
             important_threshold,
+            consolidation_threshold: DEFAULT_CONSOLIDATION_THRESHOLD,
+        }
+    }
+
+    pub fn with_consolidation_threshold(mut self, threshold: usize) -> Self {
+        self.consolidation_threshold = threshold.max(2);
+        self
+    }
+
+    /// 待整合的长期记忆：长期记忆过多时取最早的非重要条目，重要事件保持原文
+    pub fn consolidation_candidates(&self, memory: &NPCMemory) -> Vec<MemoryEntry> {
+        if memory.long_term.len() <= self.consolidation_threshold {
+            return Vec::new();
+        }
+
+        let mut candidates = memory
+            .long_term
+            .iter()
+            .filter(|entry| entry.importance < self.important_threshold)
+            .cloned()
+            .collect::<Vec<MemoryEntry>>();
+        candidates.sort_by_key(|entry| entry.timestamp);
+        candidates.truncate(MAX_CONSOLIDATED_ENTRIES);
+        if candidates.len() < 2 {
+            return Vec::new();
+        }
+        candidates
+    }
+
+    /// 按距上次结算经过的天数衰减非重要记忆，返回被遗忘的条数；情绪强烈的记忆等待转入长期记忆，不衰减
+    pub fn decay_memories(&self, memory: &mut NPCMemory, timestamp: u64) -> usize {
+        let elapsed = timestamp.saturating_sub(memory.last_decayed);
+        memory.last_decayed = memory.last_decayed.max(timestamp);
+        if elapsed == 0 {
+            return 0;
+        }
+
+        let retention = MEMORY_DAILY_RETENTION.powi(elapsed.min(365) as i32);
+        let before = memory.short_term.len() + memory.long_term.len();
+        for entries in [&mut memory.short_term, &mut memory.long_term] {
+            for entry in entries.iter_mut() {
+                if entry.importance < self.important_threshold
+                    && entry.emotional_impact.abs() < PROMOTION_EMOTION
+                {
+                    entry.importance *= retention;
+                }
+            }
+            entries.retain(|entry| entry.importance >= FORGET_IMPORTANCE);
         }
+        before - memory.short_term.len() - memory.long_term.len()
+    }
+
+    /// 待转入长期记忆的短期记忆：重要度或情绪强度较高、尚未进入长期记忆的条目，按时间先后
+    pub fn promotion_candidates(&self, memory: &NPCMemory) -> Vec<MemoryEntry> {
+        let mut candidates = memory
+            .short_term
+            .iter()
+            .filter(|entry| {
+                entry.importance >= PROMOTION_IMPORTANCE
+                    || entry.emotional_impact.abs() >= PROMOTION_EMOTION
+            })
+            .filter(|entry| {
+                !memory
+                    .long_term
+                    .iter()
+                    .any(|m| m.timestamp == entry.timestamp && m.event == entry.event)
+            })
+            .cloned()
+            .collect::<Vec<MemoryEntry>>();
+        candidates.sort_by_key(|entry| entry.timestamp);
+        candidates.truncate(MAX_PROMOTED_ENTRIES);
+        candidates
+    }
+
+    /// 把转入的短期记忆以一行摘要写入长期记忆，保留其中最强烈的情绪
+    pub fn apply_promotion(&self, memory: &mut NPCMemory, promoted: &[MemoryEntry], summary: &str) {
+        let summary = summary.trim();
+        if promoted.is_empty() || summary.is_empty() {
+            return;
+        }
+
+        memory.short_term.retain(|entry| {
+            !promoted
+                .iter()
+                .any(|m| m.timestamp == entry.timestamp && m.event == entry.event)
+        });
+        memory.long_term.push(MemoryEntry {
+            timestamp: promoted.iter().map(|m| m.timestamp).max().unwrap_or(0),
+            event: summary.to_string(),
+            importance: promoted.iter().map(|m| m.importance).fold(0.0, f32::max),
+            emotional_impact: promoted
+                .iter()
+                .map(|m| m.emotional_impact)
+                .max_by(|a, b| a.abs().total_cmp(&b.abs()))
+                .unwrap_or(0.0),
+        });
+        self.compress_memories(memory);
+    }
+
+    /// 用一条摘要替换被整合的记忆
+    pub fn apply_consolidation(&self, memory: &mut NPCMemory, merged: &[MemoryEntry], summary: &str) {
+        let summary = summary.trim();
+        if merged.is_empty() || summary.is_empty() {
+            return;
+        }
+
+        memory.long_term.retain(|entry| {
+            !merged
+                .iter()
+                .any(|m| m.timestamp == entry.timestamp && m.event == entry.event)
+        });
+        memory.long_term.push(MemoryEntry {
+            timestamp: merged.iter().map(|m| m.timestamp).max().unwrap_or(0),
+            event: summary.to_string(),
+            importance: merged.iter().map(|m| m.importance).fold(0.0, f32::max),
+            emotional_impact: merged.iter().map(|m| m.emotional_impact).sum::<f32>()
+                / merged.len() as f32,
+        });
+        self.compress_memories(memory);
     }
 
     pub fn add_memory(&self, memory: &mut NPCMemory, entry: MemoryEntry) {
+        self.decay_memories(memory, entry.timestamp);
         if entry.importance >= self.important_threshold {
             memory.important_events.push(entry.clone());
             memory.long_term.push(entry.clone());
@@ -57,6 +194,7 @@ impl MemoryManager {
         memory.important_events.dedup_by(|a, b| {
             a.timestamp == b.timestamp && a.event == b.event
         });
+        memory.important_events.truncate(MAX_IMPORTANT_EVENTS);
     }
 
     pub fn retrieve_relevant_memories(
@@ -90,6 +228,18 @@ impl MemoryManager {
     }
 }
 
+/// LLM 不可用时的整合摘要：保留最重要的几件事
+pub fn fallback_summary(entries: &[MemoryEntry]) -> String {
+    let mut ranked = entries.iter().collect::<Vec<&MemoryEntry>>();
+    ranked.sort_by(|a, b| b.importance.partial_cmp(&a.importance).unwrap_or(std::cmp::Ordering::Equal));
+    let highlights = ranked
+        .iter()
+        .take(FALLBACK_SUMMARY_EVENTS)
+        .map(|entry| entry.event.as_str())
+        .collect::<Vec<&str>>();
+    format!("往事{}则：{}", entries.len(), highlights.join("；"))
+}
+
 impl Default for MemoryManager {
     fn default() -> Self {
         Self::new(20, 200, 0.75)
@@ -123,6 +273,62 @@ mod tests {
         assert!(!memory.important_events.is_empty());
     }
 
+    #[test]
+    fn test_consolidation_replaces_old_minor_memories() {
+        let manager = MemoryManager::default().with_consolidation_threshold(4);
+        let mut memory = NPCMemory::default();
+        for ts in 1..=5 {
+            memory.long_term.push(entry(ts, &format!("minor event {}", ts), 0.3, 0.2));
+        }
+        memory.long_term.push(entry(6, "master died", 0.9, -0.8));
+
+        let candidates = manager.consolidation_candidates(&memory);
+        assert_eq!(candidates.len(), 5);
+        assert!(candidates.iter().all(|c| c.event != "master died"));
+
+        manager.apply_consolidation(&mut memory, &candidates, &fallback_summary(&candidates));
+        assert_eq!(memory.long_term.len(), 2);
+        assert!(memory.long_term.iter().any(|m| m.event == "master died"));
+        let summary = memory.long_term.iter().find(|m| m.timestamp == 5).unwrap();
+        assert!(summary.event.starts_with("往事5则："));
+        assert!(manager.consolidation_candidates(&memory).is_empty());
+    }
+
+    #[test]
+    fn test_decay_forgets_minor_memories_and_promotes_emotional_ones() {
+        let manager = MemoryManager::default();
+        let mut memory = NPCMemory::default();
+        memory.short_term.push(entry(1, "swept the courtyard", 0.2, 0.0));
+        memory.short_term.push(entry(2, "saw the player kill a rival", 0.3, -0.9));
+        memory.long_term.push(entry(3, "master died", 0.9, -0.8));
+
+        assert_eq!(manager.decay_memories(&mut memory, 10), 0);
+        assert!(memory.short_term[0].importance < 0.2);
+        assert_eq!(manager.decay_memories(&mut memory, 10), 0);
+        assert_eq!(manager.decay_memories(&mut memory, 200), 1);
+        assert_eq!(memory.short_term.len(), 1);
+        assert_eq!(memory.long_term[0].importance, 0.9);
+
+        let candidates = manager.promotion_candidates(&memory);
+        assert_eq!(candidates.len(), 1);
+        manager.apply_promotion(&mut memory, &candidates, "亲眼见到玩家斩杀宿敌");
+        assert!(memory.short_term.is_empty());
+        let promoted = memory.long_term.iter().find(|m| m.timestamp == 2).unwrap();
+        assert_eq!(promoted.event, "亲眼见到玩家斩杀宿敌");
+        assert_eq!(promoted.emotional_impact, -0.9);
+        assert!(manager.promotion_candidates(&memory).is_empty());
+    }
+
+    #[test]
+    fn test_important_events_are_capped() {
+        let manager = MemoryManager::default();
+        let mut memory = NPCMemory::default();
+        for ts in 0..(MAX_IMPORTANT_EVENTS as u64 + 10) {
+            manager.add_memory(&mut memory, entry(ts, &format!("battle {}", ts), 0.9, 0.5));
+        }
+        assert_eq!(memory.important_events.len(), MAX_IMPORTANT_EVENTS);
+    }
+
     #[test]
     fn test_retrieve_relevant_memories() {
         let manager = MemoryManager::default();
@@ -1,7 +1,17 @@
-﻿use serde::{Deserialize, Serialize};
+﻿use crate::numerical_system::StatChange;
+use crate::status_effects::StatusEffects;
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// 功法熟练度上限（圆满）
+pub const MAX_TECHNIQUE_PROFICIENCY: u32 = 100;
+/// 每门功法在入门时提供的战力加成比例，熟练度圆满时翻倍
+const TECHNIQUE_POWER_BONUS: f32 = 0.05;
+/// 基础战力的基数，再乘以灵根、境界与功法倍数
+pub const BASE_COMBAT_POWER: u64 = 100;
 
 /// 灵根元素类型
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub enum Element {
     Metal,    // 金
     Wood,     // 木
@@ -13,25 +23,166 @@ pub enum Element {
     Ice,      // 冰
 }
 
-/// 灵根品质
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub enum Grade {
-    Heavenly,      // 天灵根（单灵根）
-    Pseudo,        // 伪灵根（四灵根或五灵根）
-    Triple,        // 三灵根
-    Double,        // 双灵根
+impl Element {
+    /// 所属的五行，风雷归木，冰归水
+    fn phase(&self) -> Element {
+        match self {
+            Element::Thunder | Element::Wind => Element::Wood,
+            Element::Ice => Element::Water,
+            other => other.clone(),
+        }
+    }
+
+    /// 五行相生：木生火、火生土、土生金、金生水、水生木
+    fn generates(&self) -> Element {
+        match self.phase() {
+            Element::Wood => Element::Fire,
+            Element::Fire => Element::Earth,
+            Element::Earth => Element::Metal,
+            Element::Metal => Element::Water,
+            _ => Element::Wood,
+        }
+    }
+
+    /// 五行相克：木克土、土克水、水克火、火克金、金克木
+    fn overcomes(&self) -> Element {
+        match self.phase() {
+            Element::Wood => Element::Earth,
+            Element::Earth => Element::Water,
+            Element::Water => Element::Fire,
+            Element::Fire => Element::Metal,
+            _ => Element::Wood,
+        }
+    }
+
+    /// 以该灵根修习某属性功法的效率倍数：同属性最佳，同源或相生次之，相克最差
+    pub fn synergy_with(&self, technique: &Element) -> f32 {
+        if self == technique {
+            1.3
+        } else if self.phase() == technique.phase() || self.generates() == technique.phase() {
+            1.15
+        } else if self.overcomes() == technique.phase() || technique.overcomes() == self.phase() {
+            0.85
+        } else {
+            1.0
+        }
+    }
+}
+
+/// 灵根品质，对应世界设定中某个灵根品阶的 id
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(transparent)]
+pub struct Grade(pub String);
+
+impl Grade {
+    pub fn new(id: impl Into<String>) -> Self {
+        Self(id.into())
+    }
+
+    /// 天灵根（单灵根）
+    pub fn heavenly() -> Self {
+        Self::new("Heavenly")
+    }
+
+    /// 双灵根
+    pub fn double() -> Self {
+        Self::new("Double")
+    }
+
+    /// 三灵根
+    pub fn triple() -> Self {
+        Self::new("Triple")
+    }
+
+    /// 伪灵根（四灵根或五灵根）
+    pub fn pseudo() -> Self {
+        Self::new("Pseudo")
+    }
+
+    pub fn id(&self) -> &str {
+        &self.0
+    }
+}
+
+/// 灵根品阶，由剧本定义，决定随机开局与 NPC 生成时的灵根分布
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct RootTier {
+    pub id: Grade,
+    pub name: String,
+    /// 抽取权重，为 0 时不会被随机抽中
+    pub rarity_weight: u32,
+    pub affinity_min: f32, // 亲和度下限
+    pub affinity_max: f32, // 亲和度上限
+    /// 随机开局时追加的寿元年数
+    pub lifespan_bonus: u32,
+    pub power_multiplier: f32, // 战力倍数
+}
+
+impl RootTier {
+    fn new(
+        id: Grade,
+        name: &str,
+        rarity_weight: u32,
+        affinity: (f32, f32),
+        lifespan_bonus: u32,
+        power_multiplier: f32,
+    ) -> Self {
+        Self {
+            id,
+            name: name.to_string(),
+            rarity_weight,
+            affinity_min: affinity.0,
+            affinity_max: affinity.1,
+            lifespan_bonus,
+            power_multiplier,
+        }
+    }
+
+    /// 未定义灵根品阶的剧本沿用的四种传统品阶
+    pub fn builtin() -> Vec<RootTier> {
+        vec![
+            RootTier::new(Grade::heavenly(), "天灵根", 10, (0.90, 1.00), 35, 3.0),
+            RootTier::new(Grade::double(), "双灵根", 30, (0.78, 0.92), 22, 2.0),
+            RootTier::new(Grade::triple(), "三灵根", 40, (0.62, 0.82), 12, 1.5),
+            RootTier::new(Grade::pseudo(), "伪灵根", 20, (0.40, 0.70), 0, 1.0),
+        ]
+    }
+
+    pub fn find<'a>(tiers: &'a [RootTier], grade: &Grade) -> Option<&'a RootTier> {
+        tiers.iter().find(|tier| &tier.id == grade)
+    }
 }
 
 /// 灵根
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct SpiritualRoot {
     pub element: Element,  // 元素
     pub grade: Grade,      // 品质
     pub affinity: f32,     // 亲和度 (0.0-1.0)
+    /// 灵根品阶的战力倍数，创建角色时取自世界设定；为空时按内置品阶推算
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tier_multiplier: Option<f32>,
+}
+
+impl SpiritualRoot {
+    pub fn power_multiplier(&self) -> f32 {
+        self.tier_multiplier
+            .or_else(|| {
+                RootTier::find(&RootTier::builtin(), &self.grade).map(|tier| tier.power_multiplier)
+            })
+            .unwrap_or(1.0)
+    }
+
+    /// 按世界设定的灵根品阶记下战力倍数，品阶未定义时保持不变
+    pub fn bind_tier(&mut self, tiers: &[RootTier]) {
+        if let Some(tier) = RootTier::find(tiers, &self.grade) {
+            self.tier_multiplier = Some(tier.power_multiplier);
+        }
+    }
 }
 
 /// 修炼境界
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct CultivationRealm {
     pub name: String,              // 境界名称
     pub level: u32,                // 境界等级
@@ -60,8 +211,31 @@ impl CultivationRealm {
     }
 }
 
+/// 剧本定义的某一大境界的小境界划分，如练气一至九层、筑基初中后期
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct RealmStages {
+    /// 对应的境界等级
+    pub level: u32,
+    /// 小境界层数，最后一层即圆满，再突破便冲击下一大境界
+    pub stages: u32,
+    /// 各层名称，为空时按“第N层”称呼
+    #[serde(default)]
+    pub stage_names: Vec<String>,
+    /// 冲击该境界所需的最低战力，不足时突破必定失败
+    #[serde(default)]
+    pub min_combat_power: u64,
+    /// 渡劫晋入该境界后增加的寿元，缺省按境界等级计
+    #[serde(default)]
+    pub lifespan_bonus: Option<u32>,
+}
+
+/// 剩余寿元低于总寿元的该比例时步入暮年，气血开始衰退
+pub const TWILIGHT_LIFESPAN_RATIO: f32 = 0.2;
+/// 悟性积累的上限
+pub const MAX_INSIGHT: u32 = 100;
+
 /// 寿元
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct Lifespan {
     pub current_age: u32,    // 当前年龄
     pub max_age: u32,        // 基础寿元
@@ -88,16 +262,137 @@ impl Lifespan {
     pub fn remaining_years(&self) -> u32 {
         self.total_max_age().saturating_sub(self.current_age)
     }
+
+    /// 剩余寿元占总寿元的比例，总寿元为 0 时视为 0
+    pub fn remaining_ratio(&self) -> f32 {
+        if self.total_max_age() == 0 {
+            return 0.0;
+        }
+        self.remaining_years() as f32 / self.total_max_age() as f32
+    }
+
+    /// 尚在人世但已步入暮年
+    pub fn in_twilight(&self) -> bool {
+        self.is_alive() && self.remaining_ratio() < TWILIGHT_LIFESPAN_RATIO
+    }
+}
+
+/// 通过 apply_stat_change 施加的属性增减
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "stat", content = "amount", rename_all = "snake_case")]
+pub enum StatDelta {
+    /// 战力增减，不会低于灵根与境界决定的基础战力
+    CombatPower(i64),
+    /// 年岁增长，至多到寿元上限
+    Age(u32),
+    /// 追加的寿元年数
+    LifespanBonus(u32),
+}
+
+/// 已习得的功法
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct LearnedTechnique {
+    pub id: String,
+    pub name: String,
+    pub element: Option<Element>,
+    pub required_realm_level: u32,
+    /// 熟练度 (0-100)
+    pub proficiency: u32,
+}
+
+impl LearnedTechnique {
+    /// 只有名称的功法，用于旧存档与剧本外的功法
+    pub fn named(name: &str) -> Self {
+        Self {
+            id: name.to_string(),
+            name: name.to_string(),
+            element: None,
+            required_realm_level: 0,
+            proficiency: 0,
+        }
+    }
+
+    pub fn proficiency_name(&self) -> &str {
+        match self.proficiency {
+            0..=19 => "入门",
+            20..=49 => "小成",
+            50..=79 => "大成",
+            80..=99 => "精通",
+            _ => "圆满",
+        }
+    }
+
+    pub fn is_mastered(&self) -> bool {
+        self.proficiency >= MAX_TECHNIQUE_PROFICIENCY
+    }
+
+    /// 与灵根属性的契合度，无属性功法为 1.0
+    pub fn synergy(&self, root: &Element) -> f32 {
+        self.element
+            .as_ref()
+            .map_or(1.0, |element| root.synergy_with(element))
+    }
+
+    /// 对基础战力的加成比例，随熟练度与契合度提高
+    pub fn power_bonus(&self, root: &Element) -> f32 {
+        let mastery = self.proficiency.min(MAX_TECHNIQUE_PROFICIENCY) as f32
+            / MAX_TECHNIQUE_PROFICIENCY as f32;
+        TECHNIQUE_POWER_BONUS * (1.0 + mastery) * self.synergy(root)
+    }
+}
+
+// 旧存档中的功法只记了名称
+impl<'de> Deserialize<'de> for LearnedTechnique {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Name(String),
+            Entry {
+                id: String,
+                name: String,
+                #[serde(default)]
+                element: Option<Element>,
+                #[serde(default)]
+                required_realm_level: u32,
+                #[serde(default)]
+                proficiency: u32,
+            },
+        }
+
+        Ok(match Repr::deserialize(deserializer)? {
+            Repr::Name(name) => Self::named(&name),
+            Repr::Entry {
+                id,
+                name,
+                element,
+                required_realm_level,
+                proficiency,
+            } => Self {
+                id,
+                name,
+                element,
+                required_realm_level,
+                proficiency: proficiency.min(MAX_TECHNIQUE_PROFICIENCY),
+            },
+        })
+    }
 }
 
 /// 角色属性
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct CharacterStats {
     pub spiritual_root: SpiritualRoot,       // 灵根
     pub cultivation_realm: CultivationRealm, // 修炼境界
-    pub techniques: Vec<String>,             // 已学功法
+    pub techniques: Vec<LearnedTechnique>,   // 已学功法
     pub lifespan: Lifespan,                  // 寿元
     pub combat_power: u64,                   // 战力
+    /// 负伤、顿悟等临时状态
+    #[serde(default)]
+    pub status_effects: StatusEffects,
+    /// 修炼、奇遇与剧情感悟积累的悟性，不直接展示，冲击瓶颈时消耗以提高成功率
+    #[serde(default)]
+    pub insight: u32,
 }
 
 impl CharacterStats {
@@ -106,36 +401,214 @@ impl CharacterStats {
         cultivation_realm: CultivationRealm,
         lifespan: Lifespan,
     ) -> Self {
-        let combat_power = Self::calculate_base_combat_power(&spiritual_root, &cultivation_realm);
-        Self {
+        let mut stats = Self {
             spiritual_root,
             cultivation_realm,
             techniques: Vec::new(),
             lifespan,
-            combat_power,
-        }
+            combat_power: 0,
+            status_effects: StatusEffects::default(),
+            insight: 0,
+        };
+        stats.update_combat_power();
+        stats
     }
 
-    fn calculate_base_combat_power(
-        spiritual_root: &SpiritualRoot,
-        realm: &CultivationRealm,
-    ) -> u64 {
-        let base = 100u64;
-        let grade_multiplier = match spiritual_root.grade {
-            Grade::Heavenly => 3.0,  // 天灵根
-            Grade::Double => 2.0,    // 双灵根
-            Grade::Triple => 1.5,    // 三灵根
-            Grade::Pseudo => 1.0,    // 伪灵根
-        };
-        let affinity_bonus = 1.0 + spiritual_root.affinity;
-        let realm_power = realm.power_multiplier;
+    pub fn gain_insight(&mut self, amount: u32) {
+        self.insight = self.insight.saturating_add(amount).min(MAX_INSIGHT);
+    }
 
-        (base as f32 * grade_multiplier * affinity_bonus * realm_power) as u64
+    /// 冲击瓶颈时耗尽积累的悟性，返回消耗的数量
+    pub fn spend_insight(&mut self) -> u32 {
+        std::mem::take(&mut self.insight)
+    }
+
+    /// 灵根、境界与已学功法决定的基础战力
+    pub fn calculate_base_combat_power(&self) -> u64 {
+        let base = BASE_COMBAT_POWER;
+        let grade_multiplier = self.spiritual_root.power_multiplier();
+        let affinity_bonus = 1.0 + self.spiritual_root.affinity;
+        let realm_power = self.cultivation_realm.power_multiplier;
+
+        (base as f32 * grade_multiplier * affinity_bonus * realm_power * self.technique_multiplier())
+            as u64
+    }
+
+    /// 已学功法对战力与修炼的总倍数，未学功法时为 1.0
+    pub fn technique_multiplier(&self) -> f32 {
+        1.0 + self
+            .techniques
+            .iter()
+            .map(|technique| technique.power_bonus(&self.spiritual_root.element))
+            .sum::<f32>()
+    }
+
+    /// 受伤病等状态影响后实际可发挥的战力
+    pub fn fighting_power(&self) -> u64 {
+        (self.combat_power as f32 * self.status_effects.combat_multiplier()) as u64
     }
 
     pub fn update_combat_power(&mut self) {
-        self.combat_power =
-            Self::calculate_base_combat_power(&self.spiritual_root, &self.cultivation_realm);
+        self.combat_power = self.calculate_base_combat_power();
+    }
+
+    /// 修改基础战力的来源，保留修炼积累的战力并记下战力变化
+    fn rebase_combat_power(&mut self, change: impl FnOnce(&mut Self)) -> Option<StatChange> {
+        let old_power = self.combat_power;
+        let surplus = old_power.saturating_sub(self.calculate_base_combat_power());
+        change(self);
+        self.combat_power = self.calculate_base_combat_power().saturating_add(surplus);
+        (self.combat_power != old_power).then(|| StatChange {
+            stat_name: "combat_power".to_string(),
+            old_value: old_power.to_string(),
+            new_value: self.combat_power.to_string(),
+        })
+    }
+
+    /// 施加一次属性增减并维持不变式，返回实际发生的变化
+    pub fn apply_stat_change(&mut self, delta: StatDelta) -> Option<StatChange> {
+        let (stat_name, old_value, new_value) = match delta {
+            StatDelta::CombatPower(amount) => {
+                let old = self.combat_power;
+                let floor = self.calculate_base_combat_power();
+                let target = if amount >= 0 {
+                    old.saturating_add(amount as u64)
+                } else {
+                    old.saturating_sub(amount.unsigned_abs())
+                };
+                self.combat_power = target.max(floor);
+                ("combat_power", old, self.combat_power)
+            }
+            StatDelta::Age(years) => {
+                let old = self.lifespan.current_age;
+                self.lifespan.current_age = old
+                    .saturating_add(years)
+                    .min(self.lifespan.total_max_age().max(old));
+                ("age", u64::from(old), u64::from(self.lifespan.current_age))
+            }
+            StatDelta::LifespanBonus(years) => {
+                let old = self.lifespan.realm_bonus;
+                self.lifespan.realm_bonus = old.saturating_add(years);
+                (
+                    "lifespan_realm_bonus",
+                    u64::from(old),
+                    u64::from(self.lifespan.realm_bonus),
+                )
+            }
+        };
+        (old_value != new_value).then(|| StatChange {
+            stat_name: stat_name.to_string(),
+            old_value: old_value.to_string(),
+            new_value: new_value.to_string(),
+        })
+    }
+
+    /// 晋升到更高的境界，保留修炼积累的战力并按新境界重算
+    pub fn advance_realm(&mut self, realm: CultivationRealm) -> Vec<StatChange> {
+        let current = &self.cultivation_realm;
+        if (realm.level, realm.sub_level) <= (current.level, current.sub_level) {
+            return Vec::new();
+        }
+
+        let mut changes = vec![if realm.level == current.level {
+            StatChange {
+                stat_name: "realm_sub_level".to_string(),
+                old_value: current.sub_level.to_string(),
+                new_value: realm.sub_level.to_string(),
+            }
+        } else {
+            StatChange {
+                stat_name: "cultivation_realm".to_string(),
+                old_value: current.name.clone(),
+                new_value: realm.name.clone(),
+            }
+        }];
+        changes.extend(self.rebase_combat_power(|stats| stats.cultivation_realm = realm));
+        changes
+    }
+
+    /// 揭露隐藏的真实境界：无论高低都改为该境界，并保留修炼积累的战力按新境界重算
+    pub fn reveal_realm(&mut self, realm: CultivationRealm) -> Vec<StatChange> {
+        let current = &self.cultivation_realm;
+        if (realm.level, realm.sub_level) == (current.level, current.sub_level)
+            && realm.power_multiplier == current.power_multiplier
+        {
+            return Vec::new();
+        }
+
+        let mut changes = Vec::new();
+        if realm.name != current.name {
+            changes.push(StatChange {
+                stat_name: "cultivation_realm".to_string(),
+                old_value: current.name.clone(),
+                new_value: realm.name.clone(),
+            });
+        } else if realm.sub_level != current.sub_level {
+            changes.push(StatChange {
+                stat_name: "realm_sub_level".to_string(),
+                old_value: current.sub_level.to_string(),
+                new_value: realm.sub_level.to_string(),
+            });
+        }
+        changes.extend(self.rebase_combat_power(|stats| stats.cultivation_realm = realm));
+        changes
+    }
+
+    /// 修为跌落到同一境界的较低子等级，保留修炼积累的战力并按跌落后的境界重算
+    pub fn regress_sub_level(&mut self, sub_level: u32) -> Vec<StatChange> {
+        let current = &self.cultivation_realm;
+        if sub_level >= current.sub_level {
+            return Vec::new();
+        }
+
+        let mut realm = current.clone();
+        realm.power_multiplier /= 1.2f32.powi((current.sub_level - sub_level) as i32);
+        realm.sub_level = sub_level;
+        let mut changes = vec![StatChange {
+            stat_name: "realm_sub_level".to_string(),
+            old_value: current.sub_level.to_string(),
+            new_value: sub_level.to_string(),
+        }];
+        changes.extend(self.rebase_combat_power(|stats| stats.cultivation_realm = realm));
+        changes
+    }
+
+    pub fn technique(&self, id: &str) -> Option<&LearnedTechnique> {
+        self.techniques.iter().find(|t| t.id == id)
+    }
+
+    /// 习得功法并按新的功法加成重算战力，名称为空或已掌握时不做改动
+    pub fn learn_technique(&mut self, technique: LearnedTechnique) -> Vec<StatChange> {
+        if technique.name.trim().is_empty() || self.technique(&technique.id).is_some() {
+            return Vec::new();
+        }
+        let old_count = self.techniques.len();
+        let mut changes = vec![StatChange {
+            stat_name: "techniques".to_string(),
+            old_value: old_count.to_string(),
+            new_value: (old_count + 1).to_string(),
+        }];
+        changes.extend(self.rebase_combat_power(|stats| stats.techniques.push(technique)));
+        changes
+    }
+
+    /// 提升已学功法的熟练度，至多到圆满
+    pub fn practice_technique(&mut self, id: &str, gain: u32) -> Vec<StatChange> {
+        let Some(index) = self.techniques.iter().position(|t| t.id == id) else {
+            return Vec::new();
+        };
+        let old = self.techniques[index].proficiency;
+        let new = old.saturating_add(gain).min(MAX_TECHNIQUE_PROFICIENCY);
+        if new == old {
+            return Vec::new();
+        }
+        let mut changes = vec![StatChange {
+            stat_name: format!("technique_proficiency:{}", id),
+            old_value: old.to_string(),
+            new_value: new.to_string(),
+        }];
+        changes.extend(self.rebase_combat_power(|stats| stats.techniques[index].proficiency = new));
+        changes
     }
 }
 
@@ -156,6 +629,9 @@ mod tests {
         let lifespan = Lifespan::new(150, 100, 50);
         assert!(!lifespan.is_alive());
         assert_eq!(lifespan.remaining_years(), 0);
+        assert!(!lifespan.in_twilight());
+        assert!(Lifespan::new(130, 100, 50).in_twilight());
+        assert!(!Lifespan::new(120, 100, 50).in_twilight());
     }
 
     #[test]
@@ -171,8 +647,9 @@ mod tests {
     fn test_character_stats_combat_power() {
         let spiritual_root = SpiritualRoot {
             element: Element::Fire,
-            grade: Grade::Heavenly,
+            grade: Grade::heavenly(),
             affinity: 0.8,
+            tier_multiplier: None,
         };
         let realm = CultivationRealm::new("练气".to_string(), 1, 0, 1.0);
         let lifespan = Lifespan::new(20, 100, 0);
@@ -180,6 +657,103 @@ mod tests {
         let stats = CharacterStats::new(spiritual_root, realm, lifespan);
         assert!(stats.combat_power > 0);
     }
+
+    fn test_stats() -> CharacterStats {
+        let spiritual_root = SpiritualRoot {
+            element: Element::Water,
+            grade: Grade::double(),
+            affinity: 0.5,
+            tier_multiplier: None,
+        };
+        let realm = CultivationRealm::new("练气".to_string(), 1, 0, 1.0);
+        CharacterStats::new(spiritual_root, realm, Lifespan::new(20, 100, 10))
+    }
+
+    #[test]
+    fn test_apply_stat_change_enforces_invariants() {
+        let mut stats = test_stats();
+        let base = stats.combat_power;
+
+        let change = stats.apply_stat_change(StatDelta::CombatPower(30)).unwrap();
+        assert_eq!(change.new_value, (base + 30).to_string());
+        stats.apply_stat_change(StatDelta::CombatPower(-1000));
+        assert_eq!(stats.combat_power, base);
+        assert!(stats.apply_stat_change(StatDelta::CombatPower(-1)).is_none());
+
+        stats.apply_stat_change(StatDelta::Age(500));
+        assert_eq!(stats.lifespan.current_age, 110);
+        assert!(!stats.lifespan.is_alive());
+        stats.apply_stat_change(StatDelta::LifespanBonus(5));
+        assert_eq!(stats.lifespan.remaining_years(), 5);
+    }
+
+    #[test]
+    fn test_advance_realm_keeps_cultivation_surplus() {
+        let mut stats = test_stats();
+        stats.apply_stat_change(StatDelta::CombatPower(40));
+        let next = CultivationRealm::new("练气".to_string(), 1, 1, 1.5);
+
+        let changes = stats.advance_realm(next.clone());
+        assert_eq!(changes[0].stat_name, "realm_sub_level");
+        assert_eq!(stats.cultivation_realm, next);
+        assert_eq!(stats.combat_power, 450 + 40);
+
+        let lower = CultivationRealm::new("练气".to_string(), 1, 0, 1.0);
+        assert!(stats.advance_realm(lower).is_empty());
+        let changes = stats.advance_realm(CultivationRealm::new("筑基".to_string(), 2, 0, 2.0));
+        assert_eq!(changes[0].new_value, "筑基");
+    }
+
+    #[test]
+    fn test_learn_technique_rejects_duplicates() {
+        let mut stats = test_stats();
+        assert!(!stats.learn_technique(LearnedTechnique::named("水龙吟")).is_empty());
+        assert!(stats.learn_technique(LearnedTechnique::named("水龙吟")).is_empty());
+        assert!(stats.learn_technique(LearnedTechnique::named("  ")).is_empty());
+        assert_eq!(stats.techniques, vec![LearnedTechnique::named("水龙吟")]);
+    }
+
+    #[test]
+    fn test_techniques_raise_power_by_proficiency_and_synergy() {
+        let mut stats = test_stats();
+        stats.apply_stat_change(StatDelta::CombatPower(30));
+        let before = stats.combat_power;
+        let technique = |id: &str, element: Element| LearnedTechnique {
+            element: Some(element),
+            ..LearnedTechnique::named(id)
+        };
+
+        let changes = stats.learn_technique(technique("water", Element::Water));
+        assert_eq!(changes[0].new_value, "1");
+        // 修炼积累的 30 点战力在重算后保留
+        assert!(stats.combat_power > before);
+        assert_eq!(stats.combat_power, stats.calculate_base_combat_power() + 30);
+        let learned = stats.combat_power;
+
+        let changes = stats.practice_technique("water", 150);
+        assert_eq!(changes[0].new_value, MAX_TECHNIQUE_PROFICIENCY.to_string());
+        assert!(stats.combat_power > learned);
+        assert_eq!(stats.techniques[0].proficiency_name(), "圆满");
+        assert!(stats.practice_technique("water", 5).is_empty());
+        assert!(stats.practice_technique("missing", 5).is_empty());
+
+        let root = Element::Water;
+        assert!(technique("a", Element::Water).synergy(&root) > technique("b", Element::Wood).synergy(&root));
+        assert_eq!(technique("c", Element::Ice).synergy(&root), 1.15);
+        assert_eq!(technique("d", Element::Fire).synergy(&root), 0.85);
+        assert_eq!(LearnedTechnique::named("e").synergy(&root), 1.0);
+    }
+
+    #[test]
+    fn test_legacy_technique_names_deserialize() {
+        let techniques: Vec<LearnedTechnique> = serde_json::from_str(
+            r#"["火球术", {"id": "sword", "name": "青云剑诀", "proficiency": 30}]"#,
+        )
+        .unwrap();
+        assert_eq!(techniques[0], LearnedTechnique::named("火球术"));
+        assert_eq!(techniques[1].id, "sword");
+        assert_eq!(techniques[1].proficiency_name(), "小成");
+    }
 }
 
 // 数据模型的属性测试
@@ -204,10 +778,10 @@ mod property_tests {
 
     fn arb_grade() -> impl Strategy<Value = Grade> {
         prop_oneof![
-            Just(Grade::Heavenly),
-            Just(Grade::Pseudo),
-            Just(Grade::Triple),
-            Just(Grade::Double),
+            Just(Grade::heavenly()),
+            Just(Grade::pseudo()),
+            Just(Grade::triple()),
+            Just(Grade::double()),
         ]
     }
 
@@ -217,6 +791,7 @@ mod property_tests {
                 element,
                 grade,
                 affinity,
+                tier_multiplier: None,
             }
         })
     }
@@ -317,8 +892,9 @@ mod property_tests {
         let stats = CharacterStats::new(
             SpiritualRoot {
                 element: Element::Fire,
-                grade: Grade::Heavenly,
+                grade: Grade::heavenly(),
                 affinity: 0.8,
+                tier_multiplier: None,
             },
             CultivationRealm::new("练气".to_string(), 1, 0, 1.0),
             Lifespan::new(20, 100, 50),
@@ -336,25 +912,26 @@ mod property_tests {
         let mut stats = CharacterStats::new(
             SpiritualRoot {
                 element: Element::Water,
-                grade: Grade::Double,
+                grade: Grade::double(),
                 affinity: 0.6,
+                tier_multiplier: None,
             },
             CultivationRealm::new("筑基".to_string(), 2, 2, 2.5),
             Lifespan::new(50, 150, 100),
         );
 
-        stats.techniques.push("水龙吟".to_string());
-        stats.techniques.push("冰魄术".to_string());
-        stats.techniques.push("寒冰诀".to_string());
+        stats.techniques.push(LearnedTechnique::named("水龙吟"));
+        stats.techniques.push(LearnedTechnique::named("冰魄术"));
+        stats.techniques.push(LearnedTechnique::named("寒冰诀"));
 
         let json = serde_json::to_string(&stats).unwrap();
         let restored: CharacterStats = serde_json::from_str(&json).unwrap();
 
         assert_eq!(stats, restored);
         assert_eq!(restored.techniques.len(), 3);
-        assert_eq!(restored.techniques[0], "水龙吟");
-        assert_eq!(restored.techniques[1], "冰魄术");
-        assert_eq!(restored.techniques[2], "寒冰诀");
+        assert_eq!(restored.techniques[0].name, "水龙吟");
+        assert_eq!(restored.techniques[1].name, "冰魄术");
+        assert_eq!(restored.techniques[2].name, "寒冰诀");
     }
 
     #[test]
@@ -362,8 +939,9 @@ mod property_tests {
         let stats = CharacterStats::new(
             SpiritualRoot {
                 element: Element::Thunder,
-                grade: Grade::Heavenly,
+                grade: Grade::heavenly(),
                 affinity: 0.95,
+                tier_multiplier: None,
             },
             CultivationRealm::new("金丹".to_string(), 3, 3, 5.0),
             Lifespan::new(100, 200, 300),
@@ -381,8 +959,9 @@ mod property_tests {
     fn test_json_format_is_readable() {
         let spiritual_root = SpiritualRoot {
             element: Element::Fire,
-            grade: Grade::Heavenly,
+            grade: Grade::heavenly(),
             affinity: 0.8,
+            tier_multiplier: None,
         };
 
         let json = serde_json::to_string_pretty(&spiritual_root).unwrap();
@@ -0,0 +1,179 @@
+use crate::facts::{FactStore, MAX_PROMPT_FACTS};
+use crate::game_state::GameState;
+use crate::llm_service::{LLMRequest, LLMService, LLMSubsystem};
+use crate::prompt_builder::{PromptBuilder, PromptConstraints, PromptContext, PromptTemplate};
+use crate::script::WorldSetting;
+use serde::{Deserialize, Serialize};
+
+/// 玩家单次提问的最大字数
+pub const MAX_QUESTION_CHARS: usize = 200;
+const MAX_SETTING_SOURCES: usize = 4;
+const UNKNOWN_ANSWER: &str = "天机未显，现有记载中查不到与此相关的内容。";
+
+/// 旁白对玩家提问的回答，只供参考，不写入设定事实也不推进剧情
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NarratorAnswer {
+    pub question: String,
+    pub answer: String,
+    /// 作为依据的设定事实与世界设定条目
+    pub sources: Vec<String>,
+}
+
+/// 依据设定事实与世界设定回答提问，LLM 不可用时直接引用相关条目
+pub async fn answer_question(
+    llm_service: Option<&LLMService>,
+    question: &str,
+    state: &GameState,
+    facts: &FactStore,
+) -> NarratorAnswer {
+    let sources = grounding_sources(question, &state.script.world_setting, facts);
+    if let Some(llm_service) = llm_service {
+        let request = LLMRequest {
+            prompt: build_narrator_prompt(question, state, &sources),
+            max_tokens: Some(400),
+            temperature: Some(0.3),
+            subsystem: LLMSubsystem::Plot,
+            retry_policy: None,
+        };
+        if let Ok(response) = llm_service.generate(request).await {
+            let text = response.text.trim();
+            if !text.is_empty() {
+                return NarratorAnswer {
+                    question: question.to_string(),
+                    answer: text.to_string(),
+                    sources,
+                };
+            }
+        }
+    }
+    fallback_answer(question, sources)
+}
+
+/// 与提问相关的设定事实，以及名称出现在提问中的地点、势力、功法与境界
+pub fn grounding_sources(question: &str, world: &WorldSetting, facts: &FactStore) -> Vec<String> {
+    let mut sources = facts
+        .matching_facts(question, MAX_PROMPT_FACTS)
+        .into_iter()
+        .map(|fact| fact.statement.clone())
+        .collect::<Vec<String>>();
+    let settings = world
+        .locations
+        .iter()
+        .map(|l| (&l.name, l.description.as_str()))
+        .chain(world.factions.iter().map(|f| (&f.name, f.description.as_str())))
+        .chain(world.techniques.iter().map(|t| (&t.name, t.description.as_str())))
+        .chain(world.cultivation_realms.iter().map(|r| (&r.name, "")))
+        .filter(|(name, _)| !name.is_empty() && question.contains(name.as_str()))
+        .take(MAX_SETTING_SOURCES)
+        .map(|(name, description)| {
+            if description.is_empty() {
+                format!("{}：本世界的修炼境界之一", name)
+            } else {
+                format!("{}：{}", name, description)
+            }
+        });
+    sources.extend(settings);
+    sources
+}
+
+pub fn build_narrator_prompt(question: &str, state: &GameState, sources: &[String]) -> String {
+    let world = &state.script.world_setting;
+    let context = PromptContext {
+        scene: Some(format!("The player asks the narrator: \"{}\"", question)),
+        location: Some(state.player.location.clone()),
+        actor_name: Some(state.player.name.clone()),
+        actor_realm: Some(state.player.stats.cultivation_realm.name.clone()),
+        canon_facts: sources.to_vec(),
+        world_setting_summary: Some(format!(
+            "script: {}; locations: {}; factions: {}",
+            state.script.name,
+            world
+                .locations
+                .iter()
+                .map(|l| l.name.as_str())
+                .collect::<Vec<&str>>()
+                .join(", "),
+            world
+                .factions
+                .iter()
+                .map(|f| f.name.as_str())
+                .collect::<Vec<&str>>()
+                .join(", ")
+        )),
+        ..PromptContext::default()
+    };
+    let constraints = PromptConstraints {
+        numerical_rules: Vec::new(),
+        world_rules: vec![
+            "answer in Chinese, at most 3 sentences".to_string(),
+            "only state what the canon facts and world setting support".to_string(),
+            "if the facts do not cover the question, say it is unknown instead of inventing"
+                .to_string(),
+            "do not advance the plot or describe new events".to_string(),
+        ],
+        output_schema_hint: None,
+    };
+
+    PromptBuilder::default().build_prompt_with_token_limit(
+        PromptTemplate::NarratorQuery,
+        &context,
+        &constraints,
+        800,
+    )
+}
+
+pub fn fallback_answer(question: &str, sources: Vec<String>) -> NarratorAnswer {
+    let answer = if sources.is_empty() {
+        UNKNOWN_ANSWER.to_string()
+    } else {
+        format!("据记载：{}", sources.join("；"))
+    };
+    NarratorAnswer {
+        question: question.to_string(),
+        answer,
+        sources,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script::Faction;
+
+    fn world() -> WorldSetting {
+        let mut world = WorldSetting::new();
+        world.factions.push(Faction {
+            id: "qingyun".to_string(),
+            name: "青云宗".to_string(),
+            description: "正道大宗，门下七位长老".to_string(),
+            power_level: 80,
+        });
+        world
+    }
+
+    #[test]
+    fn test_grounding_sources_use_matching_facts_and_setting() {
+        let mut facts = FactStore::new();
+        facts.add_fact("李长老拜入青云宗", 1);
+        facts.add_fact("血魔宗毁于大火", 2);
+
+        let sources = grounding_sources("青云宗有几位长老？", &world(), &facts);
+        assert_eq!(
+            sources,
+            vec![
+                "李长老拜入青云宗".to_string(),
+                "青云宗：正道大宗，门下七位长老".to_string()
+            ]
+        );
+        assert!(grounding_sources("今天天气如何", &world(), &facts).is_empty());
+    }
+
+    #[test]
+    fn test_fallback_answer_admits_unknown() {
+        let answer = fallback_answer("谁是天下第一", Vec::new());
+        assert_eq!(answer.answer, UNKNOWN_ANSWER);
+
+        let answer = fallback_answer("青云宗", vec!["青云宗：正道大宗".to_string()]);
+        assert_eq!(answer.answer, "据记载：青云宗：正道大宗");
+    }
+}
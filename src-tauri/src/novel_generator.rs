@@ -1,10 +1,15 @@
 ﻿use crate::event_log::GameEvent;
-use crate::llm_runtime_config::resolve_llm_config;
-use crate::llm_service::{LLMRequest, LLMService};
+use crate::llm_runtime_config::shared_llm_service;
+use crate::llm_service::{LLMRequest, LLMService, LLMSubsystem};
 use crate::prompt_builder::{PromptBuilder, PromptConstraints, PromptContext, PromptTemplate};
 use crate::response_validator::{ResponseValidator, ValidationConstraints};
+use crate::scene_image::{illustration_markers, IllustrationMarker};
 use serde::{Deserialize, Serialize};
 use std::path::Path;
+use std::sync::Arc;
+
+const POLISH_MAX_TOKENS: u32 = 1600;
+const POLISH_PROMPT_TOKENS: u32 = 3000;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Chapter {
@@ -12,6 +17,67 @@ pub struct Chapter {
     pub title: String,
     pub content: String,
     pub source_event_ids: Vec<u64>,
+    /// 章节开头与高潮段落的插图标记
+    #[serde(default)]
+    pub illustrations: Vec<IllustrationMarker>,
+    /// 正文是否经过润色（结合剧情片段按文风改写）
+    #[serde(default)]
+    pub polished: bool,
+}
+
+impl Chapter {
+    fn new(index: u32, title: String, content: String, source_event_ids: Vec<u64>) -> Self {
+        let illustrations = illustration_markers(index, &content);
+        Self {
+            index,
+            title,
+            content,
+            source_event_ids,
+            illustrations,
+            polished: false,
+        }
+    }
+}
+
+/// 润色所需的素材：文风与按时间先后排列的剧情片段
+#[derive(Debug, Clone, Default, PartialEq)]
+struct NovelPolish {
+    style: String,
+    plot_segments: Vec<String>,
+}
+
+/// 小说导出格式，按文件扩展名区分
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NovelExportFormat {
+    PlainText,
+    Markdown,
+}
+
+impl NovelExportFormat {
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("md") => NovelExportFormat::Markdown,
+            _ => NovelExportFormat::PlainText,
+        }
+    }
+
+    /// 未指定时是否输出插图标记：Markdown 以注释嵌入不影响阅读，默认输出；纯文本默认不输出
+    pub fn illustrations_by_default(self) -> bool {
+        matches!(self, NovelExportFormat::Markdown)
+    }
+
+    fn render_marker(self, marker: &IllustrationMarker) -> String {
+        match self {
+            NovelExportFormat::PlainText => format!(
+                "〔插图 {}｜{}｜画面：{}〕",
+                marker.scene_id, marker.caption, marker.image_prompt
+            ),
+            NovelExportFormat::Markdown => format!(
+                "<!-- illustration {} -->",
+                serde_json::to_string(marker).unwrap_or_default()
+            ),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -22,25 +88,36 @@ pub struct Novel {
 }
 
 pub struct NovelGenerator {
-    llm_service: Option<LLMService>,
+    llm_service: Option<Arc<LLMService>>,
     prompt_builder: PromptBuilder,
     response_validator: ResponseValidator,
     chapter_event_batch_size: usize,
+    polish: Option<NovelPolish>,
 }
 
 impl NovelGenerator {
     pub fn new() -> Self {
         Self {
-            llm_service: Self::initialize_llm_service_from_env(),
+            llm_service: shared_llm_service(),
             prompt_builder: PromptBuilder::default(),
             response_validator: ResponseValidator::default(),
             chapter_event_batch_size: 8,
+            polish: None,
         }
     }
 
-    fn initialize_llm_service_from_env() -> Option<LLMService> {
-        let cfg = resolve_llm_config()?;
-        LLMService::new(cfg).ok()
+    pub fn with_llm_service(mut self, llm_service: Arc<LLMService>) -> Self {
+        self.llm_service = Some(llm_service);
+        self
+    }
+
+    /// 开启润色：每章把事件与同期的剧情片段一并交给 LLM，按 `style` 写成连贯的正文
+    pub fn with_polish(mut self, style: impl Into<String>, plot_segments: Vec<String>) -> Self {
+        self.polish = Some(NovelPolish {
+            style: style.into(),
+            plot_segments,
+        });
+        self
     }
 
     pub async fn generate_novel(
@@ -55,22 +132,44 @@ impl NovelGenerator {
         if ordered_events.is_empty() {
             return Ok(Novel {
                 title,
-                chapters: vec![Chapter {
-                    index: 1,
-                    title: "第1章：静水初澜".to_string(),
-                    content: "尚无重大事件发生，你的修行旅程正等待展开。".to_string(),
-                    source_event_ids: Vec::new(),
-                }],
+                chapters: vec![Chapter::new(
+                    1,
+                    "第1章：静水初澜".to_string(),
+                    "尚无重大事件发生，你的修行旅程正等待展开。".to_string(),
+                    Vec::new(),
+                )],
                 total_events: 0,
             });
         }
 
-        let mut chapters = Vec::new();
-        for (idx, chunk) in ordered_events
+        let chunks = ordered_events
             .chunks(self.chapter_event_batch_size.max(1))
-            .enumerate()
-        {
-            let chapter = self.generate_chapter((idx + 1) as u32, chunk).await?;
+            .collect::<Vec<&[GameEvent]>>();
+        let mut chapters = Vec::new();
+        for (idx, chunk) in chunks.iter().enumerate() {
+            let chapter_index = (idx + 1) as u32;
+            let polished = match &self.polish {
+                Some(polish) => {
+                    let segments = segments_for_chunk(&polish.plot_segments, idx, chunks.len());
+                    self.polish_chapter(chapter_index, chunk, segments, &polish.style)
+                        .await
+                }
+                None => None,
+            };
+            let chapter = match polished {
+                Some(content) => {
+                    let mut chapter = Chapter::new(
+                        chapter_index,
+                        chapter_title(chapter_index),
+                        content,
+                        chunk.iter().map(|e| e.id).collect(),
+                    );
+                    chapter.polished = true;
+                    chapter
+                }
+                // 润色失败的章节回到原有的生成方式
+                None => self.generate_chapter(chapter_index, chunk).await?,
+            };
             chapters.push(chapter);
         }
 
@@ -87,23 +186,13 @@ impl NovelGenerator {
         events: &[GameEvent],
     ) -> Result<Chapter, String> {
         let source_event_ids = events.iter().map(|e| e.id).collect::<Vec<u64>>();
-        let title = format!("第{}章：命途流转", chapter_index);
-
-        if let Some(content) = self.generate_chapter_with_llm(chapter_index, events).await {
-            return Ok(Chapter {
-                index: chapter_index,
-                title,
-                content,
-                source_event_ids,
-            });
-        }
+        let title = chapter_title(chapter_index);
 
-        Ok(Chapter {
-            index: chapter_index,
-            title,
-            content: self.generate_chapter_fallback(events),
-            source_event_ids,
-        })
+        let content = match self.generate_chapter_with_llm(chapter_index, events).await {
+            Some(content) => content,
+            None => self.generate_chapter_fallback(events),
+        };
+        Ok(Chapter::new(chapter_index, title, content, source_event_ids))
     }
 
     async fn generate_chapter_with_llm(
@@ -118,7 +207,7 @@ impl NovelGenerator {
         let llm_service = self.llm_service.as_ref()?;
         let event_lines = events
             .iter()
-            .map(|e| format!("[t={}] {}: {}", e.timestamp, e.event_type, e.description))
+            .map(|e| format!("[t={}] {}: {}", e.timestamp, e.event_type, e.summary_text()))
             .collect::<Vec<String>>()
             .join("\n");
 
@@ -130,6 +219,14 @@ impl NovelGenerator {
                 actor_name: Some("player".to_string()),
                 actor_realm: None,
                 actor_combat_power: None,
+                player_persona: None,
+                canon_facts: Vec::new(),
+                story_beat: None,
+                chapter_beat: None,
+                environment: None,
+                active_quests: Vec::new(),
+                relationships: Vec::new(),
+                status_effects: Vec::new(),
                 history_events: vec![event_lines],
                 world_setting_summary: Some(
                     "修仙小说文风，保留事件顺序，章节结尾留出后续发展空间".to_string(),
@@ -152,6 +249,8 @@ impl NovelGenerator {
                 prompt,
                 max_tokens: Some(500),
                 temperature: Some(0.8),
+                subsystem: LLMSubsystem::Novel,
+                retry_policy: None,
             })
             .await
             .ok()?;
@@ -177,6 +276,79 @@ impl NovelGenerator {
         }
     }
 
+    /// 以事件为骨架、剧情片段为血肉，按文风改写成一章正文；失败时返回 None
+    async fn polish_chapter(
+        &self,
+        chapter_index: u32,
+        events: &[GameEvent],
+        plot_segments: &[String],
+        style: &str,
+    ) -> Option<String> {
+        let llm_service = self.llm_service.as_ref()?;
+        let response = llm_service
+            .generate(LLMRequest {
+                prompt: self.build_polish_prompt(chapter_index, events, plot_segments, style),
+                max_tokens: Some(POLISH_MAX_TOKENS),
+                temperature: Some(0.8),
+                subsystem: LLMSubsystem::Novel,
+                retry_policy: None,
+            })
+            .await
+            .ok()?;
+
+        self.response_validator
+            .validate_response(
+                &response,
+                &ValidationConstraints {
+                    require_json: false,
+                    max_realm_level: None,
+                    min_combat_power: None,
+                    max_combat_power: None,
+                    max_current_age: None,
+                },
+            )
+            .ok()?;
+
+        let text = response.text.trim();
+        (!text.is_empty()).then(|| text.to_string())
+    }
+
+    fn build_polish_prompt(
+        &self,
+        chapter_index: u32,
+        events: &[GameEvent],
+        plot_segments: &[String],
+        style: &str,
+    ) -> String {
+        self.prompt_builder.build_prompt_with_token_limit(
+            PromptTemplate::NovelPolish,
+            &PromptContext {
+                scene: Some(format!(
+                    "第 {} 章的剧情片段：\n{}",
+                    chapter_index,
+                    plot_segments.join("\n")
+                )),
+                actor_name: Some("player".to_string()),
+                history_events: events
+                    .iter()
+                    .map(|e| format!("第{}日：{}", e.timestamp, e.summary_text()))
+                    .collect(),
+                world_setting_summary: Some(format!("文风：{}", style)),
+                ..PromptContext::default()
+            },
+            &PromptConstraints {
+                numerical_rules: vec!["保持事件的时间顺序与因果，不得增删事件".to_string()],
+                world_rules: vec![
+                    "仅输出纯文本正文，不要标题".to_string(),
+                    "剧情片段中的对白与细节尽量保留，片段之间补上过渡".to_string(),
+                    "字数控制在 600-1500 字".to_string(),
+                ],
+                output_schema_hint: None,
+            },
+            POLISH_PROMPT_TOKENS,
+        )
+    }
+
     fn generate_chapter_fallback(&self, events: &[GameEvent]) -> String {
         if events.is_empty() {
             return "这一章尚未掀起波澜，主角在平静中积蓄力量。".to_string();
@@ -187,7 +359,9 @@ impl NovelGenerator {
         for event in events {
             lines.push(format!(
                 "第{}日：{}（{}）",
-                event.timestamp, event.description, event.event_type
+                event.timestamp,
+                event.summary_text(),
+                event.event_type
             ));
         }
         lines.push("故事尚未结束，你的下一次选择将决定后续走向。".to_string());
@@ -195,18 +369,45 @@ impl NovelGenerator {
     }
 
     pub fn export_to_file(&self, novel: &Novel, file_path: impl AsRef<Path>) -> Result<(), String> {
+        self.export_to_file_with(novel, file_path, None)
+    }
+
+    /// 按扩展名选择导出格式；`include_illustrations` 为空时采用该格式的默认设置
+    pub fn export_to_file_with(
+        &self,
+        novel: &Novel,
+        file_path: impl AsRef<Path>,
+        include_illustrations: Option<bool>,
+    ) -> Result<(), String> {
         let path = file_path.as_ref();
         if let Some(parent) = path.parent() {
             std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
         }
 
+        let format = NovelExportFormat::from_path(path);
+        let include_illustrations =
+            include_illustrations.unwrap_or_else(|| format.illustrations_by_default());
+        let heading = |level: &str| match format {
+            NovelExportFormat::PlainText => String::new(),
+            NovelExportFormat::Markdown => format!("{} ", level),
+        };
+
         let mut content = String::new();
-        content.push_str(&format!("{}\n\n", novel.title));
+        content.push_str(&format!("{}{}\n\n", heading("#"), novel.title));
         content.push_str(&format!("事件总数：{}\n\n", novel.total_events));
 
         for chapter in &novel.chapters {
-            content.push_str(&format!("第{}章 - {}\n", chapter.index, chapter.title));
-            content.push_str(&chapter.content);
+            content.push_str(&format!(
+                "{}第{}章 - {}\n",
+                heading("##"),
+                chapter.index,
+                chapter.title
+            ));
+            if include_illustrations {
+                content.push_str(&render_with_markers(chapter, format));
+            } else {
+                content.push_str(&chapter.content);
+            }
             content.push_str("\n\n");
         }
 
@@ -214,12 +415,44 @@ impl NovelGenerator {
     }
 }
 
+/// 在标记所指段落之前插入插图标记，段落序号只计非空行
+fn render_with_markers(chapter: &Chapter, format: NovelExportFormat) -> String {
+    let mut lines = Vec::new();
+    let mut paragraph_index = 0;
+    for line in chapter.content.lines() {
+        if !line.trim().is_empty() {
+            lines.extend(
+                chapter
+                    .illustrations
+                    .iter()
+                    .filter(|marker| marker.paragraph_index == paragraph_index)
+                    .map(|marker| format.render_marker(marker)),
+            );
+            paragraph_index += 1;
+        }
+        lines.push(line.to_string());
+    }
+    lines.join("\n")
+}
+
 impl Default for NovelGenerator {
     fn default() -> Self {
         Self::new()
     }
 }
 
+fn chapter_title(chapter_index: u32) -> String {
+    format!("第{}章：命途流转", chapter_index)
+}
+
+/// 剧情片段与事件都按时间先后排列，按章数等分给各章
+fn segments_for_chunk(segments: &[String], chunk: usize, chunk_count: usize) -> &[String] {
+    let chunk_count = chunk_count.max(1);
+    let start = segments.len() * chunk / chunk_count;
+    let end = segments.len() * (chunk + 1) / chunk_count;
+    &segments[start..end]
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -232,6 +465,8 @@ mod tests {
             event_type: std::sync::Arc::from(event_type),
             description: std::sync::Arc::from(description),
             importance: EventImportance::Normal,
+            repeat_count: 1,
+            last_timestamp: None,
         }
     }
 
@@ -253,6 +488,59 @@ mod tests {
         assert!(!novel.chapters.is_empty());
     }
 
+    #[tokio::test]
+    async fn test_polish_weaves_plot_segments_into_chapter() {
+        let llm_service = LLMService::new(crate::llm_service::LLMConfig {
+            endpoint: "https://example.com/v1/chat/completions".to_string(),
+            api_key: "test-key".to_string(),
+            model: "gpt-test".to_string(),
+            max_tokens: 2048,
+            temperature: 0.8,
+            provider_kind: crate::llm_provider::ProviderKind::OpenAI,
+            retry_policy: Default::default(),
+        })
+        .unwrap();
+        let events = vec![
+            test_event(1, 1, "cultivation", "Player cultivated at dawn"),
+            test_event(2, 2, "combat", "Player won a duel"),
+        ];
+        let segments = vec![
+            "晨光中，少年盘膝而坐。".to_string(),
+            "擂台上剑光一闪。".to_string(),
+        ];
+        let generator = NovelGenerator::new().with_polish("古典章回体", segments.clone());
+        llm_service.cache_response_for_request(
+            &LLMRequest {
+                prompt: generator.build_polish_prompt(1, &events, &segments, "古典章回体"),
+                max_tokens: Some(POLISH_MAX_TOKENS),
+                temperature: Some(0.8),
+                subsystem: LLMSubsystem::Novel,
+                retry_policy: None,
+            },
+            &crate::llm_service::LLMResponse {
+                text: "话说少年晨起打坐，午后登台比剑，一剑定胜负。".to_string(),
+                model: None,
+                finish_reason: None,
+                prompt_tokens: None,
+                completion_tokens: None,
+                total_tokens: None,
+            },
+        );
+
+        let novel = generator
+            .with_llm_service(Arc::new(llm_service))
+            .generate_novel("Road to Immortality", &events)
+            .await
+            .unwrap();
+        assert!(novel.chapters[0].polished);
+        assert!(novel.chapters[0].content.starts_with("话说少年"));
+        assert_eq!(novel.chapters[0].source_event_ids, vec![1, 2]);
+
+        let segments = (0..5).map(|i| i.to_string()).collect::<Vec<String>>();
+        assert_eq!(segments_for_chunk(&segments, 0, 2), &segments[..2]);
+        assert_eq!(segments_for_chunk(&segments, 1, 2), &segments[2..]);
+    }
+
     #[tokio::test]
     async fn test_generate_chapter_contains_event_content() {
         let generator = NovelGenerator::new();
@@ -276,6 +564,8 @@ mod tests {
                 title: "Beginning".to_string(),
                 content: "A quiet dawn over the sect.".to_string(),
                 source_event_ids: vec![1],
+                illustrations: Vec::new(),
+                polished: false,
             }],
             total_events: 1,
         };
@@ -290,6 +580,41 @@ mod tests {
         assert!(text.contains("Export Test"));
         assert!(text.contains("Beginning"));
     }
+
+    #[test]
+    fn test_export_illustration_markers_per_format() {
+        let generator = NovelGenerator::new();
+        let novel = Novel {
+            title: "插图测试".to_string(),
+            chapters: vec![Chapter::new(
+                1,
+                "雷劫".to_string(),
+                "山门晨雾未散。\n\n雷劫骤降，他拔剑迎战！".to_string(),
+                vec![1],
+            )],
+            total_events: 1,
+        };
+        assert_eq!(novel.chapters[0].illustrations.len(), 2);
+
+        let dir = tempfile::tempdir().unwrap();
+        let plain = dir.path().join("novel.txt");
+        generator.export_to_file(&novel, &plain).unwrap();
+        let plain = std::fs::read_to_string(plain).unwrap();
+        assert!(!plain.contains("〔插图"));
+
+        let marked = dir.path().join("marked.txt");
+        generator.export_to_file_with(&novel, &marked, Some(true)).unwrap();
+        let marked = std::fs::read_to_string(marked).unwrap();
+        assert!(marked.contains("〔插图 ch1-p0｜山门晨雾未散｜画面："));
+        assert!(marked.find("〔插图 ch1-p1").unwrap() < marked.find("雷劫骤降").unwrap());
+
+        let markdown = dir.path().join("novel.md");
+        generator.export_to_file(&novel, &markdown).unwrap();
+        let markdown = std::fs::read_to_string(markdown).unwrap();
+        assert!(markdown.starts_with("# 插图测试"));
+        assert_eq!(markdown.matches("<!-- illustration {").count(), 2);
+        assert!(markdown.contains("\"placement\":\"climax\""));
+    }
 }
 
 #[cfg(test)]
@@ -305,6 +630,8 @@ mod property_tests {
             event_type: std::sync::Arc::from("event"),
             description: std::sync::Arc::from(desc),
             importance: EventImportance::Normal,
+            repeat_count: 1,
+            last_timestamp: None,
         }
     }
 
@@ -381,6 +708,8 @@ mod property_tests {
                     title: format!("Chapter {}", idx + 1),
                     content: format!("{} {}", body, idx),
                     source_event_ids: vec![idx as u64 + 1],
+                    illustrations: Vec::new(),
+                    polished: false,
                 })
                 .collect::<Vec<Chapter>>();
 
@@ -1,12 +1,68 @@
-﻿use crate::llm_runtime_config::resolve_llm_config;
-use crate::llm_service::{LLMRequest, LLMService};
+﻿use crate::llm_runtime_config::shared_llm_service;
+use crate::llm_service::{LLMRequest, LLMService, LLMSubsystem};
 use crate::prompt_builder::{PromptBuilder, PromptConstraints, PromptContext, PromptTemplate};
 use crate::response_validator::{ResponseValidator, ValidationConstraints};
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, HashMap};
 use std::path::Path;
+use std::sync::Arc;
 use std::time::Duration;
 
+/// 章节标题中「第」与「章」之间可出现的数字
+const CHAPTER_NUMERALS: &str = "零〇一二两三四五六七八九十百千0123456789０１２３４５６７８９";
+const CHAPTER_MARKERS: [char; 3] = ['章', '回', '节'];
+/// 超过该长度的行不视为章节标题
+const MAX_CHAPTER_HEADING_CHARS: usize = 40;
+/// 章节摘录的字数
+const CHAPTER_EXCERPT_CHARS: usize = 80;
+/// 推断出的人名、地名与境界至少要出现的次数
+const MIN_ENTITY_MENTIONS: usize = 2;
+const MAX_CHARACTERS: usize = 12;
+const MAX_PLACES: usize = 12;
+/// 交给 LLM 的章节摘录条数
+const LLM_CHAPTER_DIGEST_LIMIT: usize = 30;
+
+/// 人名开头的常见姓氏，刻意略去「方」「高」「云」等常作普通字用的姓
+const COMMON_SURNAMES: &str = "赵钱孙李周吴郑王冯陈褚卫蒋沈韩杨朱秦许何吕施张孔曹严华魏陶姜谢邹柏窦苏潘葛范彭鲁韦苗俞袁柳鲍唐费薛雷贺倪汤罗郝齐康伍余顾孟黄萧尹姚邵汪祁毛狄臧戴宋庞熊纪舒屈项祝董梁杜阮蓝闵贾江颜郭林钟徐邱骆蔡胡凌霍虞柯卢莫丁邓洪崔龚邢裴陆翁荀甄靳焦侯仲宁仇栾甘厉符刘詹龙叶黎蒲卓蔺乔谭姬申冉雍桂燕温庄晏柴瞿阎慕艾廖曾沙鞠聂敖冷辛楚欧";
+/// 紧跟在说话人之后的词
+const SPEECH_MARKERS: [&str; 13] = [
+    "道：", "道:", "说道", "笑道", "问道", "喝道", "叹道", "说：", "冷笑", "心想", "点头", "摇头", "沉声",
+];
+/// 动作用字，出现在候选人名中说明截到了动词
+const NAME_BREAKING_CHARS: &str = "道说笑问喝叹冷心点摇沉又也便就却还都在把被对向";
+/// 地名结尾：地理与建筑
+const PLACE_SUFFIXES: &str = "山峰谷城镇村岛林洞湖崖坊殿";
+/// 势力结尾：宗门与帮会，也作地点
+const FACTION_SUFFIXES: &str = "宗门派阁宫教盟帮寺";
+/// 地名中不会出现的虚词，向前截取地名时遇到即停
+const PLACE_STOP_CHARS: &str = "的了在到去从是向往回入进出和与上下一这那个着过把被将离至赶前来里中于便就也都又才已说道见他她我你们之其后为以及而";
+/// 常见的修仙境界，由低到高；同一层的别名写在一起
+const KNOWN_REALMS: [&[&str]; 9] = [
+    &["炼气", "练气"],
+    &["筑基"],
+    &["结丹", "金丹"],
+    &["元婴"],
+    &["化神"],
+    &["炼虚", "练虚"],
+    &["合体"],
+    &["大乘"],
+    &["渡劫"],
+];
+/// 「××境」「××期」这类境界名的结尾
+const REALM_SUFFIXES: [char; 2] = ['境', '期'];
+/// 以这些字收尾的词多是小境界或普通名词（初期、时期、心境），不算境界名
+const REALM_STEM_BLOCKLIST: &str = "初中后末前时星日假周学心环意险梦秘仙绝困这那此";
+
+/// 按「第X章」切分出的章节
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NovelChapter {
+    pub number: u32,
+    pub title: String,
+    /// 章节正文开头的摘录
+    pub excerpt: String,
+    pub char_count: usize,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ParsedNovelData {
     pub title: String,
@@ -14,10 +70,17 @@ pub struct ParsedNovelData {
     pub characters: Vec<String>,
     pub locations: Vec<String>,
     pub key_events: Vec<String>,
+    #[serde(default)]
+    pub chapters: Vec<NovelChapter>,
+    /// 由低到高的境界名
+    #[serde(default)]
+    pub realms: Vec<String>,
+    #[serde(default)]
+    pub factions: Vec<String>,
 }
 
 pub struct NovelParser {
-    llm_service: Option<LLMService>,
+    llm_service: Option<Arc<LLMService>>,
     prompt_builder: PromptBuilder,
     response_validator: ResponseValidator,
 }
@@ -27,16 +90,12 @@ impl NovelParser {
 
     pub fn new() -> Self {
         Self {
-            llm_service: Self::initialize_llm_service_from_env(),
+            llm_service: shared_llm_service(),
             prompt_builder: PromptBuilder::default(),
             response_validator: ResponseValidator::default(),
         }
     }
 
-    fn initialize_llm_service_from_env() -> Option<LLMService> {
-        let cfg = resolve_llm_config()?;
-        LLMService::new(cfg).ok()
-    }
     pub fn parse_novel_file(&self, file_path: impl AsRef<Path>) -> Result<ParsedNovelData, String> {
         let path = file_path.as_ref();
         let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
@@ -54,8 +113,8 @@ impl NovelParser {
         }
 
         let mut parsed = self.parse_with_rules(title, content);
-        if let Some(refined) = self.parse_with_llm(title, content) {
-            parsed = refined;
+        if let Some(refined) = self.parse_with_llm(title, content, &parsed.chapters) {
+            parsed = refined.merged_over(parsed);
         }
         Ok(parsed)
     }
@@ -67,6 +126,7 @@ impl NovelParser {
             .collect::<BTreeSet<String>>()
             .into_iter()
             .collect::<Vec<String>>();
+        let characters = with_inferred(characters, infer_character_names(content), MAX_CHARACTERS);
 
         let locations = extract_named_items(content, "Location:")
             .into_iter()
@@ -74,6 +134,13 @@ impl NovelParser {
             .collect::<BTreeSet<String>>()
             .into_iter()
             .collect::<Vec<String>>();
+        let places = infer_place_names(content);
+        let factions = places
+            .iter()
+            .filter(|name| name.ends_with(|c: char| FACTION_SUFFIXES.contains(c)))
+            .cloned()
+            .collect::<Vec<String>>();
+        let locations = with_inferred(locations, places, MAX_PLACES);
 
         let key_events = content
             .lines()
@@ -108,15 +175,32 @@ impl NovelParser {
             characters,
             locations,
             key_events,
+            chapters: split_chapters(title, content),
+            realms: infer_realm_ladder(content),
+            factions,
         }
     }
 
-    fn parse_with_llm(&self, title: &str, content: &str) -> Option<ParsedNovelData> {
+    fn parse_with_llm(
+        &self,
+        title: &str,
+        content: &str,
+        chapters: &[NovelChapter],
+    ) -> Option<ParsedNovelData> {
         if cfg!(test) {
             return None;
         }
         let llm_service = self.llm_service.as_ref()?;
 
+        let mut history_events = vec![summarize_text(content, 1200)];
+        if chapters.len() > 1 {
+            history_events.extend(
+                chapters
+                    .iter()
+                    .take(LLM_CHAPTER_DIGEST_LIMIT)
+                    .map(|chapter| format!("{}：{}", chapter.title, chapter.excerpt)),
+            );
+        }
         let prompt = self.prompt_builder.build_prompt_with_token_limit(
             PromptTemplate::ScriptGeneration,
             &PromptContext {
@@ -125,18 +209,31 @@ impl NovelParser {
                 actor_name: None,
                 actor_realm: None,
                 actor_combat_power: None,
-                history_events: vec![summarize_text(content, 1200)],
-                world_setting_summary: Some("提取角色、地点、世界观摘要、关键事件，输出 JSON".to_string()),
+                player_persona: None,
+                canon_facts: Vec::new(),
+                story_beat: None,
+                chapter_beat: None,
+                environment: None,
+                active_quests: Vec::new(),
+                relationships: Vec::new(),
+                status_effects: Vec::new(),
+                history_events,
+                world_setting_summary: Some(
+                    "提取角色、地点、势力、由低到高的修炼境界、世界观摘要、关键事件，输出 JSON"
+                        .to_string(),
+                ),
             },
             &PromptConstraints {
                 numerical_rules: vec![],
                 world_rules: vec![
                     "只输出严格 JSON，不要 markdown".to_string(),
-                    "字段必须包含: world_summary,characters,locations,key_events".to_string(),
+                    "字段必须包含: world_summary,characters,locations,key_events,realms,factions"
+                        .to_string(),
+                    "realms 按由低到高排列，书中未提及境界时给空数组".to_string(),
                     "所有字段内容用中文".to_string(),
                 ],
                 output_schema_hint: Some(
-                    "{\"world_summary\":\"string\",\"characters\":[\"string\"],\"locations\":[\"string\"],\"key_events\":[\"string\"]}".to_string(),
+                    "{\"world_summary\":\"string\",\"characters\":[\"string\"],\"locations\":[\"string\"],\"key_events\":[\"string\"],\"realms\":[\"string\"],\"factions\":[\"string\"]}".to_string(),
                 ),
             },
             700,
@@ -148,8 +245,10 @@ impl NovelParser {
                 Duration::from_secs(Self::PARSE_LLM_TIMEOUT_SECS),
                 llm_service.generate(LLMRequest {
                     prompt,
-                    max_tokens: Some(350),
+                    max_tokens: Some(450),
                     temperature: Some(0.2),
+                    subsystem: LLMSubsystem::Script,
+                    retry_policy: None,
                 }),
             ))
             .ok()?
@@ -188,6 +287,18 @@ impl NovelParser {
             .iter()
             .filter_map(|v| v.as_str().map(ToString::to_string))
             .collect::<Vec<String>>();
+        let optional_list = |field: &str| {
+            value
+                .get(field)
+                .and_then(|v| v.as_array())
+                .map(|items| {
+                    items
+                        .iter()
+                        .filter_map(|v| v.as_str().map(ToString::to_string))
+                        .collect::<Vec<String>>()
+                })
+                .unwrap_or_default()
+        };
 
         Some(ParsedNovelData {
             title: title.to_string(),
@@ -195,10 +306,31 @@ impl NovelParser {
             characters,
             locations,
             key_events,
+            chapters: Vec::new(),
+            realms: optional_list("realms"),
+            factions: optional_list("factions"),
         })
     }
 }
 
+impl ParsedNovelData {
+    /// LLM 的解析结果优先；章节切分始终沿用规则结果，LLM 未给出的列表也以规则结果补上
+    fn merged_over(mut self, rules: ParsedNovelData) -> Self {
+        self.chapters = rules.chapters;
+        for (refined, fallback) in [
+            (&mut self.characters, rules.characters),
+            (&mut self.locations, rules.locations),
+            (&mut self.realms, rules.realms),
+            (&mut self.factions, rules.factions),
+        ] {
+            if refined.is_empty() {
+                *refined = fallback;
+            }
+        }
+        self
+    }
+}
+
 impl Default for NovelParser {
     fn default() -> Self {
         Self::new()
@@ -222,6 +354,220 @@ fn summarize_text(text: &str, max_chars: usize) -> String {
     text.chars().take(max_chars).collect::<String>()
 }
 
+/// 明确标注的条目在前，推断出的条目按出现次数补足到上限
+fn with_inferred(labelled: Vec<String>, inferred: Vec<String>, limit: usize) -> Vec<String> {
+    let mut items = labelled;
+    for name in inferred {
+        if items.len() >= limit {
+            break;
+        }
+        if !items.contains(&name) {
+            items.push(name);
+        }
+    }
+    items
+}
+
+fn is_han(c: char) -> bool {
+    ('\u{4e00}'..='\u{9fff}').contains(&c)
+}
+
+/// 按「第X章」「第X回」切分章节；没有章节标题时整篇算作一章
+fn split_chapters(title: &str, content: &str) -> Vec<NovelChapter> {
+    let mut chapters: Vec<(u32, String, String)> = Vec::new();
+    for line in content.lines() {
+        if let Some((number, heading)) = chapter_heading(line) {
+            let number = number.unwrap_or(chapters.len() as u32 + 1);
+            chapters.push((number, heading, String::new()));
+        } else if let Some((_, _, body)) = chapters.last_mut() {
+            body.push_str(line.trim());
+        }
+    }
+    if chapters.is_empty() {
+        chapters.push((1, title.to_string(), content.lines().map(str::trim).collect()));
+    }
+
+    chapters
+        .into_iter()
+        .map(|(number, title, body)| NovelChapter {
+            number,
+            title,
+            excerpt: summarize_text(&body, CHAPTER_EXCERPT_CHARS),
+            char_count: body.chars().count(),
+        })
+        .collect()
+}
+
+/// 识别章节标题行，返回章节序号（数字无法解析时为空）与整行标题
+fn chapter_heading(line: &str) -> Option<(Option<u32>, String)> {
+    let heading = line.trim();
+    if heading.chars().count() > MAX_CHAPTER_HEADING_CHARS {
+        return None;
+    }
+    let rest = heading.strip_prefix('第')?;
+    let numeral = rest
+        .chars()
+        .take_while(|c| CHAPTER_NUMERALS.contains(*c))
+        .collect::<String>();
+    if numeral.is_empty() || !rest[numeral.len()..].starts_with(CHAPTER_MARKERS) {
+        return None;
+    }
+    Some((numeral_value(&numeral), heading.to_string()))
+}
+
+/// 解析阿拉伯数字（含全角）或「一百二十三」「一二三」式的中文数字
+fn numeral_value(numeral: &str) -> Option<u32> {
+    let digit = |c: char| match c {
+        '0'..='9' => c.to_digit(10),
+        '０'..='９' => Some(c as u32 - '０' as u32),
+        '零' | '〇' => Some(0),
+        '一' => Some(1),
+        '二' | '两' => Some(2),
+        '三' => Some(3),
+        '四' => Some(4),
+        '五' => Some(5),
+        '六' => Some(6),
+        '七' => Some(7),
+        '八' => Some(8),
+        '九' => Some(9),
+        _ => None,
+    };
+    let unit = |c: char| match c {
+        '十' => Some(10),
+        '百' => Some(100),
+        '千' => Some(1000),
+        _ => None,
+    };
+
+    if !numeral.chars().any(|c| unit(c).is_some()) {
+        return numeral
+            .chars()
+            .try_fold(0u32, |value, c| value.checked_mul(10)?.checked_add(digit(c)?));
+    }
+    let mut total = 0;
+    let mut pending = None;
+    for c in numeral.chars() {
+        if let Some(multiplier) = unit(c) {
+            total += pending.take().unwrap_or(1) * multiplier;
+        } else {
+            pending = Some(digit(c)?);
+        }
+    }
+    Some(total + pending.unwrap_or(0))
+}
+
+/// 统计出现在「××道：」「××笑道」等说话处、以常见姓氏开头的二三字人名
+fn infer_character_names(content: &str) -> Vec<String> {
+    let char_indices = content.char_indices().collect::<Vec<(usize, char)>>();
+    let chars = char_indices.iter().map(|(_, c)| *c).collect::<Vec<char>>();
+    let mut candidates = BTreeSet::new();
+    for marker in SPEECH_MARKERS {
+        for (byte_index, _) in content.match_indices(marker) {
+            let end = char_indices.partition_point(|(offset, _)| *offset < byte_index);
+            for len in [2, 3] {
+                let Some(start) = end.checked_sub(len) else {
+                    continue;
+                };
+                let name = &chars[start..end];
+                if COMMON_SURNAMES.contains(name[0])
+                    && name.iter().all(|c| is_han(*c) && !NAME_BREAKING_CHARS.contains(*c))
+                {
+                    candidates.insert(name.iter().collect::<String>());
+                }
+            }
+        }
+    }
+    rank_by_mentions(content, candidates, |longer, shorter| longer.starts_with(shorter))
+}
+
+/// 统计以山、城、宗、门等结尾的三四字地名，向前截取时遇到虚词即停
+fn infer_place_names(content: &str) -> Vec<String> {
+    let chars = content.chars().collect::<Vec<char>>();
+    let mut candidates = BTreeSet::new();
+    for (index, c) in chars.iter().enumerate() {
+        if !PLACE_SUFFIXES.contains(*c) && !FACTION_SUFFIXES.contains(*c) {
+            continue;
+        }
+        let start = (index.saturating_sub(3)..index)
+            .rev()
+            .take_while(|&i| {
+                let c = chars[i];
+                is_han(c)
+                    && !PLACE_STOP_CHARS.contains(c)
+                    && !PLACE_SUFFIXES.contains(c)
+                    && !FACTION_SUFFIXES.contains(c)
+            })
+            .last();
+        if let Some(start) = start.filter(|start| index - start >= 2) {
+            candidates.insert(chars[start..=index].iter().collect::<String>());
+        }
+    }
+    rank_by_mentions(content, candidates, |longer, shorter| longer.ends_with(shorter))
+}
+
+/// 按出现次数排序，去掉不足次数的候选；一个候选包含另一个时，次数更多的一方胜出
+fn rank_by_mentions(
+    content: &str,
+    candidates: BTreeSet<String>,
+    contains: impl Fn(&str, &str) -> bool,
+) -> Vec<String> {
+    let counts = candidates
+        .into_iter()
+        .map(|name| {
+            let count = content.matches(name.as_str()).count();
+            (name, count)
+        })
+        .filter(|(_, count)| *count >= MIN_ENTITY_MENTIONS)
+        .collect::<HashMap<String, usize>>();
+    let mut names = counts
+        .iter()
+        .filter(|&(name, &count)| {
+            !counts.iter().any(|(other, &other_count)| {
+                other != name
+                    && ((contains(name, other) && other_count > count)
+                        || (contains(other, name) && other_count == count))
+            })
+        })
+        .map(|(name, count)| (name.clone(), *count, content.find(name.as_str())))
+        .collect::<Vec<(String, usize, Option<usize>)>>();
+    names.sort_by(|a, b| b.1.cmp(&a.1).then(a.2.cmp(&b.2)));
+    names.into_iter().map(|(name, _, _)| name).collect()
+}
+
+/// 书中提到的常见境界按高低排列；认出不足两层时，改按首次出现的顺序取「××境」「××期」
+fn infer_realm_ladder(content: &str) -> Vec<String> {
+    let known = KNOWN_REALMS
+        .iter()
+        .filter_map(|aliases| aliases.iter().find(|alias| content.contains(*alias)))
+        .map(|alias| alias.to_string())
+        .collect::<Vec<String>>();
+    if known.len() >= 2 {
+        return known;
+    }
+
+    let chars = content.chars().collect::<Vec<char>>();
+    let mut checked = BTreeSet::new();
+    let mut ladder: Vec<String> = Vec::new();
+    for window in chars.windows(3) {
+        let (stem, suffix) = (&window[..2], window[2]);
+        if !REALM_SUFFIXES.contains(&suffix)
+            || !stem.iter().all(|c| is_han(*c) && !PLACE_STOP_CHARS.contains(*c))
+            || REALM_STEM_BLOCKLIST.contains(stem[1])
+        {
+            continue;
+        }
+        let name = stem.iter().collect::<String>();
+        if checked.insert(name.clone()) && content.matches(name.as_str()).count() >= MIN_ENTITY_MENTIONS {
+            ladder.push(name);
+        }
+    }
+    if ladder.len() >= 2 {
+        ladder
+    } else {
+        known
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -253,5 +599,42 @@ mod tests {
         assert!(parsed.characters.iter().any(|c| c.contains("韩青")));
         assert!(parsed.locations.iter().any(|l| l.contains("青云宗")));
     }
-}
 
+    #[test]
+    fn test_chapters_entities_and_realms_come_from_plain_prose() {
+        let parser = NovelParser::new();
+        let text = "序\n山村少年自幼听说修仙的传闻。\n\
+            第一章 山村少年\n韩青站在青牛镇口，望着远处的落云山。韩青笑道：“总有一日我要拜入青云宗。”\n\
+            苏婉摇头道：“青云宗只收练气以上的弟子。”\n\
+            第二回　入门\n韩青来到青云宗，拜在落云山下。苏婉说道：“筑基之后方可下山。”\n\
+            第十二章：筑基\n韩青心想：“结丹尚远。”韩青又道：“先回青牛镇。”\n";
+
+        let parsed = parser.parse_novel_text("凡人", text).unwrap();
+        let chapters = parsed
+            .chapters
+            .iter()
+            .map(|chapter| (chapter.number, chapter.title.as_str()))
+            .collect::<Vec<(u32, &str)>>();
+        assert_eq!(
+            chapters,
+            vec![(1, "第一章 山村少年"), (2, "第二回　入门"), (12, "第十二章：筑基")]
+        );
+        assert!(parsed.chapters[0].excerpt.starts_with("韩青站在青牛镇口"));
+
+        assert_eq!(parsed.characters, vec!["韩青", "苏婉"]);
+        assert_eq!(parsed.realms, vec!["练气", "筑基", "结丹"]);
+        assert_eq!(parsed.factions, vec!["青云宗"]);
+        for place in ["青云宗", "落云山", "青牛镇"] {
+            assert!(parsed.locations.iter().any(|l| l == place), "{:?}", parsed.locations);
+        }
+    }
+
+    #[test]
+    fn test_numeral_value_reads_chinese_and_arabic_numbers() {
+        assert_eq!(numeral_value("一百二十三"), Some(123));
+        assert_eq!(numeral_value("十五"), Some(15));
+        assert_eq!(numeral_value("两千零八"), Some(2008));
+        assert_eq!(numeral_value("一〇一"), Some(101));
+        assert_eq!(numeral_value("４２"), Some(42));
+    }
+}
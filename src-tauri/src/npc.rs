@@ -1,4 +1,5 @@
-﻿use crate::models::CharacterStats;
+﻿use crate::models::{CharacterStats, CultivationRealm};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -10,6 +11,15 @@ pub struct NPC {
     pub personality: Personality,
     pub memory: NPCMemory,
     pub relationships: HashMap<String, Relationship>,
+    #[serde(default)]
+    pub secrets: Vec<NPCSecret>,
+    #[serde(default)]
+    pub location: Option<String>,
+    #[serde(default)]
+    pub bio: String,
+    /// 由事件引起的短期情绪，随时间衰减，区别于长期性格
+    #[serde(default)]
+    pub emotions: EmotionalState,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -19,7 +29,7 @@ pub struct Personality {
     pub values: Vec<CoreValue>,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub enum PersonalityTrait {
     Calm,
     Aggressive,
@@ -29,13 +39,13 @@ pub enum PersonalityTrait {
     Scheming,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct Goal {
     pub description: String,
     pub priority: u8,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct CoreValue {
     pub name: String,
     pub weight: f32,
@@ -46,6 +56,9 @@ pub struct NPCMemory {
     pub short_term: Vec<MemoryEntry>,
     pub long_term: Vec<MemoryEntry>,
     pub important_events: Vec<MemoryEntry>,
+    /// 上次结算记忆衰减时的游戏日
+    #[serde(default)]
+    pub last_decayed: u64,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -72,12 +85,260 @@ pub struct InteractionRecord {
     pub trust_change: i32,
 }
 
+/// NPC的秘密，揭露前不会进入任何提示词上下文
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct NPCSecret {
+    pub id: String,
+    pub kind: SecretKind,
+    pub triggers: Vec<RevealTrigger>,
+    /// 剧本作者预设的揭露片段，缺省时自动生成
+    #[serde(default)]
+    pub reveal_text: Option<String>,
+    #[serde(default)]
+    pub revealed_at: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub enum SecretKind {
+    HiddenRealm(CultivationRealm),
+    HiddenAllegiance(String),
+}
+
+/// 秘密揭露条件，满足任意一个即可触发；剧本中的 target_id 可写 "player" 指代玩家
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub enum RevealTrigger {
+    TrustAtLeast { target_id: String, threshold: i32 },
+    EventKeyword(String),
+}
+
+impl NPCSecret {
+    pub fn is_revealed(&self) -> bool {
+        self.revealed_at.is_some()
+    }
+}
+
+impl NPC {
+    /// 已揭露、玩家可见的秘密
+    pub fn revealed_secrets(&self) -> Vec<&NPCSecret> {
+        self.secrets.iter().filter(|s| s.is_revealed()).collect()
+    }
+
+    /// 玩家视角的人物档案
+    pub fn profile(&self, player_id: &str) -> NPCProfile {
+        let relationship = self.relationships.get(player_id);
+        NPCProfile {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            realm: self.stats.cultivation_realm.name.clone(),
+            location: self.location.clone(),
+            bio: self.bio.clone(),
+            traits: self.personality.traits.clone(),
+            affinity: relationship.map(|r| r.affinity).unwrap_or(0),
+            trust: relationship.map(|r| r.trust).unwrap_or(0),
+            emotions: self.emotions.clone(),
+            dominant_emotion: self.emotions.dominant().map(|e| e.label().to_string()),
+        }
+    }
+
+    /// 完整人物资料；`names` 为关系对象的 id 到名字的映射，缺失时显示 id
+    pub fn detail(&self, player_id: &str, names: &HashMap<String, String>) -> NPCDetail {
+        let mut relationships = self
+            .relationships
+            .values()
+            .map(|relationship| RelationshipSummary {
+                target_id: relationship.target_id.clone(),
+                target_name: names
+                    .get(&relationship.target_id)
+                    .cloned()
+                    .unwrap_or_else(|| relationship.target_id.clone()),
+                affinity: relationship.affinity,
+                trust: relationship.trust,
+            })
+            .collect::<Vec<RelationshipSummary>>();
+        relationships.sort_by(|a, b| a.target_id.cmp(&b.target_id));
+        let mut revealed_secrets = self
+            .secrets
+            .iter()
+            .filter_map(|secret| {
+                secret.revealed_at.map(|revealed_at| RevealedSecret {
+                    id: secret.id.clone(),
+                    kind: secret.kind.clone(),
+                    reveal_text: secret.reveal_text.clone(),
+                    revealed_at,
+                })
+            })
+            .collect::<Vec<RevealedSecret>>();
+        revealed_secrets.sort_by_key(|secret| secret.revealed_at);
+
+        NPCDetail {
+            profile: self.profile(player_id),
+            age: self.stats.lifespan.current_age,
+            max_age: self.stats.lifespan.total_max_age(),
+            combat_power: self.stats.combat_power,
+            techniques: self.stats.techniques.iter().map(|t| t.name.clone()).collect(),
+            goals: self.personality.goals.clone(),
+            values: self.personality.values.clone(),
+            relationships,
+            recent_memories: self
+                .memory
+                .short_term
+                .iter()
+                .rev()
+                .take(DETAIL_MEMORY_COUNT)
+                .map(|entry| entry.event.clone())
+                .collect(),
+            revealed_secrets,
+        }
+    }
+}
+
+/// 人物资料中列出的最近记忆条数
+const DETAIL_MEMORY_COUNT: usize = 5;
+
+/// get_npc_profile 返回的人物档案
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NPCProfile {
+    pub id: String,
+    pub name: String,
+    pub realm: String,
+    pub location: Option<String>,
+    pub bio: String,
+    pub traits: Vec<PersonalityTrait>,
+    /// 对玩家的好感与信任
+    pub affinity: i32,
+    pub trust: i32,
+    pub emotions: EmotionalState,
+    /// 当前主导情绪的中文名，平静时为空
+    pub dominant_emotion: Option<String>,
+}
+
+/// 人物资料中的一条人际关系
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RelationshipSummary {
+    pub target_id: String,
+    pub target_name: String,
+    pub affinity: i32,
+    pub trust: i32,
+}
+
+/// get_npc_detail 返回的完整人物资料，未揭露的秘密不会出现
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NPCDetail {
+    pub profile: NPCProfile,
+    pub age: u32,
+    pub max_age: u32,
+    pub combat_power: u64,
+    pub techniques: Vec<String>,
+    pub goals: Vec<Goal>,
+    pub values: Vec<CoreValue>,
+    /// 与玩家及其他 NPC 的关系，按对象 id 排序
+    pub relationships: Vec<RelationshipSummary>,
+    /// 最近的短期记忆，新的在前
+    pub recent_memories: Vec<String>,
+    /// 已揭露的秘密，按揭露先后排列
+    pub revealed_secrets: Vec<RevealedSecret>,
+}
+
+/// 人物资料中的一条已揭露秘密，不含揭露条件
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RevealedSecret {
+    pub id: String,
+    pub kind: SecretKind,
+    pub reveal_text: Option<String>,
+    pub revealed_at: u64,
+}
+
+/// 情绪值低于该阈值时视为平静
+pub const EMOTION_THRESHOLD: f32 = 0.3;
+/// 每过一天情绪保留的比例
+const EMOTION_DAILY_RETENTION: f32 = 0.8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Emotion {
+    Anger,
+    Fear,
+    Joy,
+    Grief,
+}
+
+impl Emotion {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Emotion::Anger => "愤怒",
+            Emotion::Fear => "恐惧",
+            Emotion::Joy => "喜悦",
+            Emotion::Grief => "悲伤",
+        }
+    }
+}
+
+/// NPC 的短期情绪，各项取值 0.0-1.0
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmotionalState {
+    pub anger: f32,
+    pub fear: f32,
+    pub joy: f32,
+    pub grief: f32,
+    /// 上次结算衰减时的游戏日
+    pub last_updated: u64,
+}
+
+impl EmotionalState {
+    fn value_mut(&mut self, emotion: Emotion) -> &mut f32 {
+        match emotion {
+            Emotion::Anger => &mut self.anger,
+            Emotion::Fear => &mut self.fear,
+            Emotion::Joy => &mut self.joy,
+            Emotion::Grief => &mut self.grief,
+        }
+    }
+
+    pub fn value(&self, emotion: Emotion) -> f32 {
+        match emotion {
+            Emotion::Anger => self.anger,
+            Emotion::Fear => self.fear,
+            Emotion::Joy => self.joy,
+            Emotion::Grief => self.grief,
+        }
+    }
+
+    pub fn stir(&mut self, emotion: Emotion, amount: f32) {
+        let value = self.value_mut(emotion);
+        *value = (*value + amount).clamp(0.0, 1.0);
+    }
+
+    /// 按经过的天数衰减到 `timestamp`，时间不会倒退
+    pub fn decay_to(&mut self, timestamp: u64) {
+        let elapsed = timestamp.saturating_sub(self.last_updated);
+        if elapsed > 0 {
+            let retention = EMOTION_DAILY_RETENTION.powi(elapsed.min(365) as i32);
+            for emotion in [Emotion::Anger, Emotion::Fear, Emotion::Joy, Emotion::Grief] {
+                *self.value_mut(emotion) *= retention;
+            }
+        }
+        self.last_updated = self.last_updated.max(timestamp);
+    }
+
+    /// 超过阈值的最强烈情绪
+    pub fn dominant(&self) -> Option<Emotion> {
+        [Emotion::Anger, Emotion::Fear, Emotion::Joy, Emotion::Grief]
+            .into_iter()
+            .map(|emotion| (emotion, self.value(emotion)))
+            .filter(|(_, value)| *value >= EMOTION_THRESHOLD)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(emotion, _)| emotion)
+    }
+}
+
 impl NPCMemory {
     pub fn new() -> Self {
         Self {
             short_term: Vec::new(),
             long_term: Vec::new(),
             important_events: Vec::new(),
+            last_decayed: 0,
         }
     }
 }
@@ -97,8 +358,9 @@ mod tests {
         CharacterStats::new(
             SpiritualRoot {
                 element: Element::Fire,
-                grade: Grade::Double,
+                grade: Grade::double(),
                 affinity: 0.7,
+                tier_multiplier: None,
             },
             CultivationRealm::new("Qi Condensation".to_string(), 1, 1, 1.2),
             Lifespan::new(20, 120, 20),
@@ -132,10 +394,31 @@ mod tests {
             },
             memory: NPCMemory::default(),
             relationships: HashMap::new(),
+            secrets: Vec::new(),
+            location: None,
+            bio: String::new(),
+            emotions: EmotionalState::default(),
         };
 
         let json = serde_json::to_string(&npc).unwrap();
         let restored: NPC = serde_json::from_str(&json).unwrap();
         assert_eq!(npc, restored);
     }
+
+    #[test]
+    fn test_emotions_decay_and_pick_dominant() {
+        let mut emotions = EmotionalState::default();
+        emotions.stir(Emotion::Anger, 0.9);
+        emotions.stir(Emotion::Joy, 0.4);
+        assert_eq!(emotions.dominant(), Some(Emotion::Anger));
+
+        emotions.decay_to(5);
+        assert!((emotions.anger - 0.9 * 0.8f32.powi(5)).abs() < 1e-4);
+        assert_eq!(emotions.dominant(), None);
+
+        emotions.decay_to(3);
+        assert_eq!(emotions.last_updated, 5);
+        emotions.stir(Emotion::Grief, 2.0);
+        assert_eq!(emotions.grief, 1.0);
+    }
 }
@@ -0,0 +1,160 @@
+use crate::duel::{DuelChallenge, DUEL_REQUIREMENT};
+use crate::npc_engine::NPCDecision;
+use crate::numerical_system::Action;
+use crate::plot_engine::{new_option_uid, PlayerOption};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// 插叙回应选项的要求标记，下一次替换选项时据此识别
+pub const INTERRUPTION_REQUIREMENT: &str = "NPC找上门";
+
+/// NPC 主动事件的来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NpcEventKind {
+    /// 剧情事件激起的 NPC 反应（插手、质问、报复）
+    Reaction,
+    /// 宿怨达标的 NPC 下战帖
+    DuelChallenge,
+}
+
+/// 推送给前端的 NPC 主动事件（Tauri 事件 `npc-event` 的负载）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct NpcEventNotice {
+    pub kind: NpcEventKind,
+    pub npc_id: String,
+    pub npc_name: String,
+    pub action: String,
+    /// 给玩家看的一句话描述
+    pub summary: String,
+    pub reason: String,
+    pub timestamp: u64,
+}
+
+impl NpcEventNotice {
+    /// 冲着玩家来的反应才推送，旁观、应和之类留在剧情背景里
+    pub fn from_decision(decision: &NPCDecision, npc_name: &str, timestamp: u64) -> Option<Self> {
+        let label = pressing_action_label(&decision.action)?;
+        Some(Self {
+            kind: NpcEventKind::Reaction,
+            npc_id: decision.npc_id.clone(),
+            npc_name: npc_name.to_string(),
+            action: decision.action.clone(),
+            summary: format!("{}{}", npc_name, label),
+            reason: decision.reason.clone(),
+            timestamp,
+        })
+    }
+
+    pub fn from_challenge(challenge: &DuelChallenge, timestamp: u64) -> Self {
+        Self {
+            kind: NpcEventKind::DuelChallenge,
+            npc_id: challenge.challenger_id.clone(),
+            npc_name: challenge.challenger_name.clone(),
+            action: "duel_challenge".to_string(),
+            summary: format!(
+                "{}下了战帖（赌注：{}）",
+                challenge.challenger_name,
+                challenge.stake.label()
+            ),
+            reason: format!("宿怨值 {:.2}", challenge.rivalry),
+            timestamp,
+        }
+    }
+
+    /// 续写时插入的背景，要求下一段从 NPC 找上门写起
+    pub fn prompt_line(&self) -> String {
+        format!("插叙：{}，主角须当面回应", self.summary)
+    }
+
+    /// 插叙场景的回应选项：直面或设法回避
+    pub fn response_options(&self) -> Vec<PlayerOption> {
+        [
+            format!("直面{}，看其来意", self.npc_name),
+            format!("设法避开{}", self.npc_name),
+        ]
+        .into_iter()
+        .map(|description| PlayerOption {
+            id: 0,
+            uid: new_option_uid(),
+            description: description.clone(),
+            requirements: vec![INTERRUPTION_REQUIREMENT.to_string()],
+            action: Action::Custom { description },
+        })
+        .collect()
+    }
+}
+
+/// 需要玩家回应的 NPC 反应及其说法
+fn pressing_action_label(action: &str) -> Option<&'static str> {
+    match action {
+        "intervene" => Some("出面插手此事"),
+        "confront" => Some("找上门来当面质问"),
+        "retaliate" => Some("扬言要报复"),
+        _ => None,
+    }
+}
+
+/// 把选项换成插叙的回应选项，待应的决斗选项保留在后
+pub fn attach_interruption_options(options: &mut Vec<PlayerOption>, notice: &NpcEventNotice) {
+    options.retain(|option| option.requirements.iter().any(|r| r == DUEL_REQUIREMENT));
+    options.splice(0..0, notice.response_options());
+    for (idx, option) in options.iter_mut().enumerate() {
+        option.id = idx;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decision(action: &str) -> NPCDecision {
+        NPCDecision {
+            npc_id: "elder".to_string(),
+            action: action.to_string(),
+            reason: "Reaction to event: 主角击败内门弟子 | driven by 愤怒".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_only_pressing_reactions_become_notices_and_interrupt_options() {
+        assert!(NpcEventNotice::from_decision(&decision("observe"), "林长老", 3).is_none());
+        let notice = NpcEventNotice::from_decision(&decision("confront"), "林长老", 3).unwrap();
+        assert_eq!(notice.kind, NpcEventKind::Reaction);
+        assert_eq!(notice.summary, "林长老找上门来当面质问");
+        assert!(notice.prompt_line().contains("主角须当面回应"));
+
+        let mut options = vec![
+            PlayerOption {
+                id: 0,
+                uid: new_option_uid(),
+                description: "继续修炼".to_string(),
+                requirements: Vec::new(),
+                action: Action::Cultivate,
+            },
+            PlayerOption {
+                id: 1,
+                uid: new_option_uid(),
+                description: "应下王师兄的战帖".to_string(),
+                requirements: vec![DUEL_REQUIREMENT.to_string()],
+                action: Action::Combat {
+                    target_id: "wang".to_string(),
+                },
+            },
+        ];
+        attach_interruption_options(&mut options, &notice);
+        let descriptions = options
+            .iter()
+            .map(|option| option.description.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(
+            descriptions,
+            vec!["直面林长老，看其来意", "设法避开林长老", "应下王师兄的战帖"]
+        );
+        assert_eq!(options[2].id, 2);
+        assert_eq!(
+            serde_json::to_value(&notice).unwrap()["kind"],
+            serde_json::json!("reaction")
+        );
+    }
+}
@@ -0,0 +1,296 @@
+use crate::context_assembler::DEFAULT_CONTEXT_TOKENS;
+use crate::llm_service::{LLMRequest, LLMService, LLMSubsystem};
+use crate::npc::{PersonalityTrait, NPC};
+use crate::prompt_builder::{PromptBuilder, PromptConstraints, PromptContext, PromptTemplate};
+use crate::script::ScriptLanguage;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// 玩家单句发言的最大字数
+pub const MAX_PLAYER_MESSAGE_CHARS: usize = 200;
+/// 一次对话对好感与信任的最大影响
+const MAX_DIALOGUE_DELTA: i32 = 10;
+const POLITE_KEYWORDS: &[&str] = &["请", "前辈", "多谢", "谢谢", "拜见", "please", "thank"];
+const RUDE_KEYWORDS: &[&str] = &["滚", "废物", "蠢", "找死", "老东西", "idiot", "fool"];
+
+/// NPC 对玩家一句话的回应
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NPCDialogue {
+    pub npc_id: String,
+    pub npc_name: String,
+    pub text: String,
+    pub affinity_delta: i32,
+    pub trust_delta: i32,
+}
+
+/// 由性格、记忆与对玩家的关系组织对话，LLM 不可用或输出无法解析时按规则回应
+pub async fn converse(
+    llm_service: Option<&LLMService>,
+    npc: &NPC,
+    player_id: &str,
+    message: &str,
+    game_context: &PromptContext,
+    language: ScriptLanguage,
+) -> NPCDialogue {
+    if let Some(llm_service) = llm_service {
+        let request = LLMRequest {
+            prompt: build_dialogue_prompt(npc, player_id, message, game_context, language),
+            max_tokens: Some(300),
+            temperature: Some(0.8),
+            subsystem: LLMSubsystem::Npc,
+            retry_policy: None,
+        };
+        if let Ok(response) = llm_service.generate(request).await {
+            if let Ok(dialogue) = parse_dialogue(npc, &response.text) {
+                return dialogue;
+            }
+        }
+    }
+    fallback_dialogue(npc, player_id, message, language)
+}
+
+/// `game_context` 为 `ContextAssembler` 拼装的玩家与世界状态，NPC 据此回应而不与剧情矛盾
+pub fn build_dialogue_prompt(
+    npc: &NPC,
+    player_id: &str,
+    message: &str,
+    game_context: &PromptContext,
+    language: ScriptLanguage,
+) -> String {
+    let relationship = npc.relationships.get(player_id);
+    let mut scene = format!(
+        "The player says to {}: \"{}\"\nRelationship with player: affinity {}, trust {}.",
+        npc.name,
+        message,
+        relationship.map(|r| r.affinity).unwrap_or(0),
+        relationship.map(|r| r.trust).unwrap_or(0),
+    );
+    if let Some(emotion) = npc.emotions.dominant() {
+        scene.push_str(&format!(" Current emotion: {:?}.", emotion));
+    }
+    if !npc.bio.is_empty() {
+        scene.push_str(&format!("\nBio: {}", npc.bio));
+    }
+
+    let goals = format!(
+        "traits: {:?}; goals: {}",
+        npc.personality.traits,
+        npc.personality
+            .goals
+            .iter()
+            .map(|g| g.description.as_str())
+            .collect::<Vec<&str>>()
+            .join(", ")
+    );
+    let context = PromptContext {
+        scene: Some(scene),
+        location: npc.location.clone().or_else(|| game_context.location.clone()),
+        actor_name: Some(npc.name.clone()),
+        actor_realm: Some(npc.stats.cultivation_realm.name.clone()),
+        actor_combat_power: None,
+        history_events: npc
+            .memory
+            .short_term
+            .iter()
+            .rev()
+            .take(5)
+            .map(|m| m.event.clone())
+            .collect(),
+        world_setting_summary: Some(match &game_context.world_setting_summary {
+            Some(world) => format!("{}; player and world: {}", goals, world),
+            None => goals,
+        }),
+        ..game_context.clone()
+    };
+    let constraints = PromptConstraints {
+        numerical_rules: vec![format!(
+            "affinity_delta and trust_delta are integers in [-{max}, {max}]",
+            max = MAX_DIALOGUE_DELTA
+        )],
+        world_rules: vec![
+            "respond in strict JSON only".to_string(),
+            format!(
+                "text is the NPC's spoken reply in {}, in character",
+                language.english_name()
+            ),
+            "never reveal secrets the NPC would hide".to_string(),
+        ],
+        output_schema_hint: Some(
+            "{\"text\":\"string\",\"affinity_delta\":0,\"trust_delta\":0}".to_string(),
+        ),
+    };
+
+    PromptBuilder::default()
+        .with_language(language)
+        .build_prompt_with_token_limit(
+        PromptTemplate::NpcDialogue,
+        &context,
+        &constraints,
+        600 + DEFAULT_CONTEXT_TOKENS,
+    )
+}
+
+pub fn parse_dialogue(npc: &NPC, raw: &str) -> Result<NPCDialogue, String> {
+    let parsed: Value = serde_json::from_str(raw.trim()).map_err(|e| e.to_string())?;
+    let text = parsed
+        .get("text")
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .ok_or_else(|| "dialogue missing text".to_string())?;
+    let delta = |key: &str| {
+        parsed
+            .get(key)
+            .and_then(Value::as_i64)
+            .map(|v| v.clamp(-i64::from(MAX_DIALOGUE_DELTA), i64::from(MAX_DIALOGUE_DELTA)) as i32)
+            .unwrap_or(0)
+    };
+
+    Ok(NPCDialogue {
+        npc_id: npc.id.clone(),
+        npc_name: npc.name.clone(),
+        text: text.to_string(),
+        affinity_delta: delta("affinity_delta"),
+        trust_delta: delta("trust_delta"),
+    })
+}
+
+/// 按语气与现有好感给出回应：有礼略增好感，出言不逊则好感与信任下降
+pub fn fallback_dialogue(
+    npc: &NPC,
+    player_id: &str,
+    message: &str,
+    language: ScriptLanguage,
+) -> NPCDialogue {
+    let lower = message.to_lowercase();
+    let rude = RUDE_KEYWORDS.iter().any(|k| lower.contains(k));
+    let polite = !rude && POLITE_KEYWORDS.iter().any(|k| lower.contains(k));
+    let affinity = npc
+        .relationships
+        .get(player_id)
+        .map(|r| r.affinity)
+        .unwrap_or(0);
+    let aggressive = npc.personality.traits.contains(&PersonalityTrait::Aggressive);
+
+    let (zh, en) = if rude && aggressive {
+        (
+            "放肆！再多说一个字，休怪我剑下无情。",
+            "Insolence! One more word and my sword will show no mercy.",
+        )
+    } else if rude {
+        ("道友慎言。今日之言，我记下了。", "Mind your tongue, fellow daoist. I will remember this.")
+    } else if affinity >= 30 {
+        ("是你啊，有什么需要尽管开口。", "Ah, it's you. Just say the word if you need anything.")
+    } else if affinity <= -30 {
+        ("我与你无话可说。", "I have nothing to say to you.")
+    } else if polite {
+        ("道友客气了，有何指教？", "You are too courteous, fellow daoist. What can I do for you?")
+    } else {
+        ("嗯，说吧。", "Hm. Speak.")
+    };
+    let text = match language {
+        ScriptLanguage::Zh => zh,
+        ScriptLanguage::En => en,
+    };
+    let (affinity_delta, trust_delta) = if rude {
+        (if aggressive { -5 } else { -3 }, -2)
+    } else if polite {
+        (1, 0)
+    } else {
+        (0, 0)
+    };
+
+    NPCDialogue {
+        npc_id: npc.id.clone(),
+        npc_name: npc.name.clone(),
+        text: text.to_string(),
+        affinity_delta,
+        trust_delta,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CharacterStats, CultivationRealm, Element, Grade, Lifespan, SpiritualRoot};
+    use crate::npc::{EmotionalState, NPCMemory, Personality};
+    use std::collections::HashMap;
+
+    fn npc(traits: Vec<PersonalityTrait>) -> NPC {
+        NPC {
+            id: "npc_elder".to_string(),
+            name: "外门长老".to_string(),
+            stats: CharacterStats::new(
+                SpiritualRoot {
+                    element: Element::Metal,
+                    grade: Grade::double(),
+                    affinity: 0.6,
+                    tier_multiplier: None,
+                },
+                CultivationRealm::new("筑基".to_string(), 2, 1, 2.0),
+                Lifespan::new(120, 300, 0),
+            ),
+            personality: Personality {
+                traits,
+                goals: Vec::new(),
+                values: Vec::new(),
+            },
+            memory: NPCMemory::new(),
+            relationships: HashMap::new(),
+            secrets: Vec::new(),
+            location: None,
+            bio: String::new(),
+            emotions: EmotionalState::default(),
+        }
+    }
+
+    #[test]
+    fn test_parse_dialogue_clamps_deltas() {
+        let dialogue = parse_dialogue(
+            &npc(Vec::new()),
+            r#"{"text":" 你倒是有几分胆色。 ","affinity_delta":50,"trust_delta":-3}"#,
+        )
+        .unwrap();
+
+        assert_eq!(dialogue.text, "你倒是有几分胆色。");
+        assert_eq!(dialogue.affinity_delta, MAX_DIALOGUE_DELTA);
+        assert_eq!(dialogue.trust_delta, -3);
+        assert!(parse_dialogue(&npc(Vec::new()), r#"{"text":""}"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fallback_dialogue_follows_tone_and_temper() {
+        let calm = npc(vec![PersonalityTrait::Calm]);
+        let world = PromptContext::default();
+        let polite = converse(None, &calm, "player", "拜见前辈", &world, ScriptLanguage::Zh).await;
+        assert_eq!((polite.affinity_delta, polite.trust_delta), (1, 0));
+
+        let hothead = npc(vec![PersonalityTrait::Aggressive]);
+        let rude = converse(None, &hothead, "player", "老东西，让开", &world, ScriptLanguage::Zh).await;
+        assert_eq!((rude.affinity_delta, rude.trust_delta), (-5, -2));
+        assert!(rude.text.contains("放肆"));
+        let rude_en =
+            converse(None, &hothead, "player", "out of my way, fool", &world, ScriptLanguage::En).await;
+        assert_eq!((rude_en.affinity_delta, rude_en.trust_delta), (-5, -2));
+        assert!(rude_en.text.starts_with("Insolence"));
+
+        let prompt = build_dialogue_prompt(&calm, "player", "拜见前辈", &world, ScriptLanguage::Zh);
+        assert!(prompt.contains("拜见前辈"));
+        assert!(prompt.contains("affinity 0, trust 0"));
+        let world = PromptContext {
+            location: Some("青云峰".to_string()),
+            active_quests: vec!["寻回失窃的玉简".to_string()],
+            status_effects: vec!["重伤未愈".to_string()],
+            world_setting_summary: Some("林远，筑基，战力1200".to_string()),
+            ..PromptContext::default()
+        };
+        let prompt = build_dialogue_prompt(&calm, "player", "拜见前辈", &world, ScriptLanguage::Zh);
+        assert!(prompt.contains("Location: 青云峰"));
+        assert!(prompt.contains("寻回失窃的玉简"));
+        assert!(prompt.contains("重伤未愈"));
+        assert!(prompt.contains("player and world: 林远"));
+        let prompt = build_dialogue_prompt(&calm, "player", "greetings", &world, ScriptLanguage::En);
+        assert!(prompt.contains("reply in English"));
+        assert!(prompt.contains("输出语言必须为英文"));
+    }
+}
@@ -1,11 +1,49 @@
-use crate::llm_service::{LLMRequest, LLMResponse, LLMService};
+use crate::difficulty::DifficultySettings;
+use crate::llm_service::{parse_structured, LLMRequest, LLMResponse, LLMService, LLMSubsystem};
+use crate::memory_consolidation::{ConsolidationReport, MemoryJob, MemoryJobKind};
 use crate::memory_manager::MemoryManager;
-use crate::npc::{InteractionRecord, MemoryEntry, NPC, Relationship};
+use crate::npc::{
+    Emotion, InteractionRecord, MemoryEntry, NPCSecret, RevealTrigger, SecretKind, NPC, Relationship,
+};
+use crate::relationship_graph::{RelationshipGraph, RelationshipNodeKind};
 use crate::prompt_builder::{PromptBuilder, PromptConstraints, PromptContext, PromptTemplate};
 use crate::response_validator::{ResponseValidator, ValidationConstraints};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 use std::collections::HashMap;
+use std::sync::Arc;
+
+/// 好感达到该值视为盟友
+pub const ALLY_AFFINITY: i32 = 50;
+/// 剧情提示中判定敌对的默认敌意阈值，好感不高于其相反数视为敌人
+pub const DEFAULT_HOSTILITY_THRESHOLD: i32 = 50;
+/// 剧情提示中关系概况的最多条数
+const MAX_RELATIONSHIP_PROMPT_LINES: usize = 6;
+
+/// 标准难度下 NPC 强烈反应所需的事件重要度
+const BASE_REACTION_THRESHOLD: f32 = 0.8;
+/// 侵略性达到该值时，非谨慎的 NPC 也会出手干预
+const EMBOLDENED_AGGRESSION: f32 = 0.8;
+
+/// 事件描述中引发各类情绪的关键词
+const EMOTION_KEYWORDS: &[(Emotion, &[&str])] = &[
+    (
+        Emotion::Anger,
+        &["挑衅", "羞辱", "背叛", "偷袭", "抢夺", "provoke", "insult", "betray", "ambush"],
+    ),
+    (
+        Emotion::Fear,
+        &["危险", "强敌", "追杀", "魔修", "天劫", "danger", "threat", "demon", "hunted"],
+    ),
+    (
+        Emotion::Joy,
+        &["突破", "胜利", "奖励", "喜讯", "breakthrough", "victory", "reward", "celebrat"],
+    ),
+    (
+        Emotion::Grief,
+        &["陨落", "身死", "牺牲", "失去", "death", "died", "fallen", "mourn"],
+    ),
+];
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct NPCEvent {
@@ -25,12 +63,45 @@ pub struct NPCDecision {
     pub reason: String,
 }
 
+/// 单个 NPC 决策的结构化输出
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct DecisionPayload {
+    action: String,
+    reason: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(default)]
+struct BatchDecisionItem {
+    npc_id: String,
+    action: String,
+    reason: String,
+}
+
+/// 批量决策的结构化输出；结构化输出要求顶层为对象，旧格式的裸数组在回退解析时兼容
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(default)]
+struct BatchDecisionPayload {
+    decisions: Vec<BatchDecisionItem>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SecretRevelation {
+    pub npc_id: String,
+    pub npc_name: String,
+    pub secret_id: String,
+    pub timestamp: u64,
+    pub segment: String,
+}
+
 pub struct NPCEngine {
     npcs: HashMap<String, NPC>,
     memory_manager: MemoryManager,
-    llm_service: Option<LLMService>,
+    llm_service: Option<Arc<LLMService>>,
     prompt_builder: PromptBuilder,
     response_validator: ResponseValidator,
+    /// 难度设置中的 NPC 侵略性，0 到 1
+    aggression: f32,
 }
 
 impl NPCEngine {
@@ -41,24 +112,40 @@ impl NPCEngine {
             llm_service: None,
             prompt_builder: PromptBuilder::default(),
             response_validator: ResponseValidator::default(),
+            aggression: DifficultySettings::normal().npc_aggression,
         }
     }
 
     pub fn with_npcs(npcs: HashMap<String, NPC>) -> Self {
         Self {
             npcs,
-            memory_manager: MemoryManager::default(),
-            llm_service: None,
-            prompt_builder: PromptBuilder::default(),
-            response_validator: ResponseValidator::default(),
+            ..Self::new()
         }
     }
 
-    pub fn with_llm_service(mut self, llm_service: LLMService) -> Self {
+    pub fn with_llm_service(mut self, llm_service: Arc<LLMService>) -> Self {
         self.llm_service = Some(llm_service);
         self
     }
 
+    pub fn set_llm_service(&mut self, llm_service: Option<Arc<LLMService>>) {
+        self.llm_service = llm_service;
+    }
+
+    pub fn with_difficulty(mut self, difficulty: &DifficultySettings) -> Self {
+        self.set_difficulty(difficulty);
+        self
+    }
+
+    pub fn set_difficulty(&mut self, difficulty: &DifficultySettings) {
+        self.aggression = difficulty.npc_aggression.clamp(0.0, 1.0);
+    }
+
+    /// 事件重要度达到该值时 NPC 会作出强烈反应，侵略性越高门槛越低
+    fn reaction_threshold(&self) -> f32 {
+        BASE_REACTION_THRESHOLD + (DifficultySettings::normal().npc_aggression - self.aggression) * 0.4
+    }
+
     pub fn process_event(&mut self, event: &NPCEvent) -> Vec<NPCDecision> {
         let mut decisions = Vec::new();
 
@@ -73,6 +160,7 @@ impl NPCEngine {
                 continue;
             }
 
+            self.update_npc_emotions(&npc_id, event);
             self.update_npc_memory(&npc_id, event);
             decisions.push(self.generate_reaction_decision(&npc_id, event));
         }
@@ -82,6 +170,27 @@ impl NPCEngine {
         decisions
     }
 
+    /// 先把情绪衰减到事件发生时，再按事件内容激起新的情绪
+    pub fn update_npc_emotions(&mut self, npc_id: &str, event: &NPCEvent) {
+        let Some(npc) = self.npcs.get_mut(npc_id) else {
+            return;
+        };
+
+        npc.emotions.decay_to(event.timestamp);
+        let intensity = event.importance.clamp(0.0, 1.0) * 0.6
+            + event.emotional_impact.abs().clamp(0.0, 1.0) * 0.4;
+        for emotion in event_emotions(&event.description) {
+            npc.emotions.stir(emotion, intensity);
+        }
+    }
+
+    /// 世界推进时让所有 NPC 的情绪随时间平复
+    pub fn decay_emotions(&mut self, timestamp: u64) {
+        for npc in self.npcs.values_mut() {
+            npc.emotions.decay_to(timestamp);
+        }
+    }
+
     pub fn update_npc_memory(&mut self, npc_id: &str, event: &NPCEvent) {
         let Some(npc) = self.npcs.get_mut(npc_id) else {
             return;
@@ -97,6 +206,79 @@ impl NPCEngine {
         self.memory_manager.add_memory(&mut npc.memory, entry);
     }
 
+    /// 按经过的游戏日衰减所有 NPC 的非重要记忆，返回被遗忘的条数
+    pub fn decay_memories(&mut self, timestamp: u64) -> usize {
+        self.npcs
+            .values_mut()
+            .map(|npc| self.memory_manager.decay_memories(&mut npc.memory, timestamp))
+            .sum()
+    }
+
+    /// 需要整合记忆的 NPC，按 id 排序；每名 NPC 至多一项，长期记忆过多时优先合并
+    pub fn memory_jobs(&self) -> Vec<MemoryJob> {
+        let mut jobs = self
+            .npcs
+            .values()
+            .filter_map(|npc| {
+                let consolidate = self.memory_manager.consolidation_candidates(&npc.memory);
+                let (entries, kind) = if consolidate.is_empty() {
+                    (
+                        self.memory_manager.promotion_candidates(&npc.memory),
+                        MemoryJobKind::Promote,
+                    )
+                } else {
+                    (consolidate, MemoryJobKind::Consolidate)
+                };
+                (!entries.is_empty()).then(|| MemoryJob {
+                    npc_id: npc.id.clone(),
+                    npc_name: npc.name.clone(),
+                    entries,
+                    kind,
+                })
+            })
+            .collect::<Vec<MemoryJob>>();
+        jobs.sort_by(|a, b| a.npc_id.cmp(&b.npc_id));
+        jobs
+    }
+
+    /// 写回整合摘要；整合期间记忆已变化的 NPC 跳过，返回实际整合的 NPC 数
+    pub fn apply_memory_summaries(&mut self, jobs: &[MemoryJob], report: &ConsolidationReport) -> usize {
+        let mut applied = 0;
+        for summary in &report.summaries {
+            let Some(job) = jobs.iter().find(|job| job.npc_id == summary.npc_id) else {
+                continue;
+            };
+            let Some(npc) = self.npcs.get_mut(&summary.npc_id) else {
+                continue;
+            };
+            let source = match job.kind {
+                MemoryJobKind::Consolidate => &npc.memory.long_term,
+                MemoryJobKind::Promote => &npc.memory.short_term,
+            };
+            let unchanged = job.entries.iter().all(|entry| {
+                source
+                    .iter()
+                    .any(|m| m.timestamp == entry.timestamp && m.event == entry.event)
+            });
+            if !unchanged {
+                continue;
+            }
+            match job.kind {
+                MemoryJobKind::Consolidate => self.memory_manager.apply_consolidation(
+                    &mut npc.memory,
+                    &job.entries,
+                    &summary.summary,
+                ),
+                MemoryJobKind::Promote => {
+                    self.memory_manager
+                        .apply_promotion(&mut npc.memory, &job.entries, &summary.summary)
+                }
+            }
+            applied += 1;
+        }
+        applied
+    }
+
     pub fn update_relationship(
         &mut self,
         npc_id: &str,
@@ -130,14 +312,127 @@ impl NPCEngine {
         });
     }
 
+    /// 检查所有未揭露的秘密，满足条件的秘密会被揭露且只揭露一次
+    pub fn reveal_secrets(&mut self, event: &NPCEvent) -> Vec<SecretRevelation> {
+        let mut npc_ids = self.npcs.keys().cloned().collect::<Vec<String>>();
+        npc_ids.sort();
+
+        let mut revelations = Vec::new();
+        for npc_id in npc_ids {
+            let Some(npc) = self.npcs.get_mut(&npc_id) else {
+                continue;
+            };
+
+            for idx in 0..npc.secrets.len() {
+                let secret = &npc.secrets[idx];
+                if secret.is_revealed() || !secret_triggered(secret, &npc.relationships, event) {
+                    continue;
+                }
+
+                let segment = build_reveal_segment(&npc.name, secret);
+                if let SecretKind::HiddenRealm(realm) = &secret.kind {
+                    npc.stats.reveal_realm(realm.clone());
+                }
+                npc.secrets[idx].revealed_at = Some(event.timestamp);
+
+                revelations.push(SecretRevelation {
+                    npc_id: npc.id.clone(),
+                    npc_name: npc.name.clone(),
+                    secret_id: npc.secrets[idx].id.clone(),
+                    timestamp: event.timestamp,
+                    segment,
+                });
+            }
+        }
+
+        revelations
+    }
+
     pub fn get_npc(&self, npc_id: &str) -> Option<&NPC> {
         self.npcs.get(npc_id)
     }
 
+    pub fn all_npcs(&self) -> impl Iterator<Item = &NPC> {
+        self.npcs.values()
+    }
+
     pub fn insert_npc(&mut self, npc: NPC) {
         self.npcs.insert(npc.id.clone(), npc);
     }
 
+    /// 与 `id`（玩家或 NPC）交好的 NPC：任一方向的好感达到 `ALLY_AFFINITY`，按名字排序
+    pub fn allies_of(&self, id: &str) -> Vec<&NPC> {
+        self.related_npcs(id, |affinity| affinity >= ALLY_AFFINITY)
+    }
+
+    /// 与 `id` 敌对的 NPC：任一方向的好感不高于 `-hostility_threshold`，按名字排序
+    pub fn enemies_of(&self, id: &str, hostility_threshold: i32) -> Vec<&NPC> {
+        let threshold = hostility_threshold.abs();
+        self.related_npcs(id, |affinity| affinity <= -threshold)
+    }
+
+    fn related_npcs(&self, id: &str, matches: impl Fn(i32) -> bool) -> Vec<&NPC> {
+        let own = self.npcs.get(id);
+        let mut related = self
+            .npcs
+            .values()
+            .filter(|npc| npc.id != id)
+            .filter(|npc| {
+                npc.relationships
+                    .get(id)
+                    .is_some_and(|relationship| matches(relationship.affinity))
+                    || own
+                        .and_then(|own| own.relationships.get(&npc.id))
+                        .is_some_and(|relationship| matches(relationship.affinity))
+            })
+            .collect::<Vec<&NPC>>();
+        related.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.id.cmp(&b.id)));
+        related
+    }
+
+    pub fn relationship_graph(&self, player_id: &str, player_name: &str) -> RelationshipGraph {
+        RelationshipGraph::build(self.npcs.values(), player_id, player_name)
+    }
+
+    /// 写入剧情提示的关系概况：玩家的盟友与仇敌，以及 NPC 之间的强烈好恶
+    pub fn relationship_prompt_lines(&self, player_id: &str) -> Vec<String> {
+        let names = |npcs: Vec<&NPC>| {
+            npcs.iter()
+                .map(|npc| npc.name.as_str())
+                .collect::<Vec<&str>>()
+                .join("、")
+        };
+        let mut lines = Vec::new();
+        let allies = self.allies_of(player_id);
+        if !allies.is_empty() {
+            lines.push(format!("与玩家交好：{}", names(allies)));
+        }
+        let enemies = self.enemies_of(player_id, DEFAULT_HOSTILITY_THRESHOLD);
+        if !enemies.is_empty() {
+            lines.push(format!("敌视玩家：{}", names(enemies)));
+        }
+
+        let graph = self.relationship_graph(player_id, "");
+        let npc_name = |id: &str| {
+            graph
+                .node(id)
+                .filter(|node| node.kind == RelationshipNodeKind::Npc)
+                .map(|node| node.name.clone())
+        };
+        for edge in &graph.edges {
+            let (Some(source), Some(target)) = (npc_name(&edge.source), npc_name(&edge.target)) else {
+                continue;
+            };
+            if edge.affinity >= ALLY_AFFINITY {
+                lines.push(format!("{}与{}交好", source, target));
+            } else if edge.affinity <= -DEFAULT_HOSTILITY_THRESHOLD {
+                lines.push(format!("{}敌视{}", source, target));
+            }
+        }
+        lines.truncate(MAX_RELATIONSHIP_PROMPT_LINES);
+        lines
+    }
+
     pub async fn autonomous_npc_actions(&self) -> Vec<NPCDecision> {
         let npc_ids = self.npcs.keys().cloned().collect::<Vec<String>>();
         if npc_ids.is_empty() {
@@ -196,20 +491,22 @@ impl NPCEngine {
         situation: &str,
     ) -> Result<NPCDecision, String> {
         let prompt = self.build_npc_decision_prompt(npc, situation);
-        let response = llm_service
-            .generate(LLMRequest {
+        let structured = llm_service
+            .generate_structured::<DecisionPayload>(LLMRequest {
                 prompt,
                 max_tokens: Some(200),
                 temperature: Some(0.6),
+                subsystem: LLMSubsystem::Npc,
+                retry_policy: None,
             })
             .await
             .map_err(|e| e.to_string())?;
 
         self.response_validator
             .validate_response(
-                &response,
+                &structured.response,
                 &ValidationConstraints {
-                    require_json: true,
+                    require_json: false,
                     max_realm_level: None,
                     min_combat_power: None,
                     max_combat_power: None,
@@ -218,30 +515,36 @@ impl NPCEngine {
             )
             .map_err(|e| e.to_string())?;
 
-        let parsed: Value = serde_json::from_str(&response.text).map_err(|e| e.to_string())?;
-        let action = parsed
-            .get("action")
-            .and_then(Value::as_str)
-            .ok_or_else(|| "LLM decision missing action".to_string())?;
-        let reason = parsed
-            .get("reason")
-            .and_then(Value::as_str)
-            .ok_or_else(|| "LLM decision missing reason".to_string())?;
+        let decision = structured
+            .data
+            .ok_or_else(|| "LLM decision missing action or reason".to_string())?;
 
         Ok(NPCDecision {
             npc_id: npc.id.clone(),
-            action: action.to_string(),
-            reason: reason.to_string(),
+            action: decision.action,
+            reason: decision.reason,
         })
     }
 
     fn build_npc_decision_prompt(&self, npc: &NPC, situation: &str) -> String {
+        let scene = match emotion_note(npc) {
+            Some(note) => format!("{}\n{}", situation, note),
+            None => situation.to_string(),
+        };
         let context = PromptContext {
-            scene: Some(situation.to_string()),
+            scene: Some(scene),
             location: None,
             actor_name: Some(npc.name.clone()),
             actor_realm: Some(npc.stats.cultivation_realm.name.clone()),
             actor_combat_power: Some(npc.stats.combat_power),
+            player_persona: None,
+            canon_facts: Vec::new(),
+            story_beat: None,
+            chapter_beat: None,
+            environment: None,
+            active_quests: Vec::new(),
+            relationships: Vec::new(),
+            status_effects: Vec::new(),
             history_events: npc
                 .memory
                 .short_term
@@ -283,14 +586,22 @@ impl NPCEngine {
 
         for npc_id in npc_ids {
             let Some(npc) = self.npcs.get(npc_id) else { continue };
-            npc_summaries.push(format!(
+            let mut summary = format!(
                 "npc_id: {}, name: {}, realm: {}, combat_power: {}, traits: {:?}",
                 npc.id,
                 npc.name,
                 npc.stats.cultivation_realm.name,
                 npc.stats.combat_power,
                 npc.personality.traits
-            ));
+            );
+            if let Some(emotion) = npc.emotions.dominant() {
+                summary.push_str(&format!(
+                    ", emotion: {:?} {:.2}",
+                    emotion,
+                    npc.emotions.value(emotion)
+                ));
+            }
+            npc_summaries.push(summary);
             npc_refs.push(npc);
         }
 
@@ -300,50 +611,35 @@ impl NPCEngine {
 
         let prompt = self.build_npc_batch_prompt(&npc_summaries, situation);
 
-        let response = llm_service
-            .generate(LLMRequest {
+        let structured = llm_service
+            .generate_structured::<BatchDecisionPayload>(LLMRequest {
                 prompt: prompt.clone(),
                 max_tokens: Some(350),
                 temperature: Some(0.6),
+                subsystem: LLMSubsystem::Npc,
+                retry_policy: None,
             })
             .await
             .map_err(|e| e.to_string())?;
 
-        let parsed: Value = serde_json::from_str(&response.text).map_err(|e| e.to_string())?;
-        let arr = parsed
-            .as_array()
-            .ok_or_else(|| "batch decision must be JSON array".to_string())?;
+        let items = match structured.data {
+            Some(payload) => payload.decisions,
+            None => parse_structured::<Vec<BatchDecisionItem>>(&structured.response.text)
+                .ok_or_else(|| "batch decision must contain a decisions array".to_string())?,
+        };
 
         let mut decisions = Vec::new();
         let mut seen = HashMap::new();
-        for item in arr {
-            let npc_id = item
-                .get("npc_id")
-                .and_then(Value::as_str)
-                .unwrap_or_default()
-                .to_string();
-            if npc_id.is_empty() || seen.contains_key(&npc_id) {
-                continue;
-            }
-            let action = item
-                .get("action")
-                .and_then(Value::as_str)
-                .unwrap_or_default()
-                .to_string();
-            let reason = item
-                .get("reason")
-                .and_then(Value::as_str)
-                .unwrap_or_default()
-                .to_string();
-
-            if action.is_empty() {
+        for item in items {
+            let npc_id = item.npc_id;
+            if npc_id.is_empty() || seen.contains_key(&npc_id) || item.action.is_empty() {
                 continue;
             }
 
             let decision = NPCDecision {
                 npc_id: npc_id.clone(),
-                action,
-                reason,
+                action: item.action,
+                reason: item.reason,
             };
             seen.insert(npc_id.clone(), decision.clone());
             decisions.push(decision);
@@ -373,6 +669,14 @@ impl NPCEngine {
             actor_name: None,
             actor_realm: None,
             actor_combat_power: None,
+            player_persona: None,
+            canon_facts: Vec::new(),
+            story_beat: None,
+            chapter_beat: None,
+            environment: None,
+            active_quests: Vec::new(),
+            relationships: Vec::new(),
+            status_effects: Vec::new(),
             history_events: Vec::new(),
             world_setting_summary: Some(format!(
                 "Generate decisions for each npc in list. NPCs: {}",
@@ -386,11 +690,12 @@ impl NPCEngine {
             ],
             world_rules: vec![
                 "respond in strict JSON only".to_string(),
-                "return an array of objects".to_string(),
+                "return an object whose decisions field is an array of objects".to_string(),
                 "each object keys: npc_id, action, reason".to_string(),
             ],
             output_schema_hint: Some(
-                "[{\"npc_id\":\"string\",\"action\":\"string\",\"reason\":\"string\"}]".to_string(),
+                "{\"decisions\":[{\"npc_id\":\"string\",\"action\":\"string\",\"reason\":\"string\"}]}"
+                    .to_string(),
             ),
         };
 
@@ -411,11 +716,9 @@ impl NPCEngine {
     ) {
         let prompt = self.build_npc_decision_prompt(npc, situation);
         let response = LLMResponse {
-            text: format!(
-                "{{\"action\":\"{}\",\"reason\":\"{}\"}}",
-                decision.action, decision.reason
-            ),
-            model: Some(llm_service.api_config.model.clone()),
+            text: serde_json::json!({ "action": decision.action, "reason": decision.reason })
+                .to_string(),
+            model: Some(llm_service.model_for(LLMSubsystem::Npc).to_string()),
             finish_reason: Some("prewarm".to_string()),
             prompt_tokens: None,
             completion_tokens: None,
@@ -426,6 +729,8 @@ impl NPCEngine {
                 prompt,
                 max_tokens: Some(200),
                 temperature: Some(0.6),
+                subsystem: LLMSubsystem::Npc,
+                retry_policy: None,
             },
             &response,
         );
@@ -469,12 +774,17 @@ impl NPCEngine {
             decision.reason.push_str(" | adjusted for cautious personality");
         }
 
-        if is_aggressive && decision.action == "observe_and_plan" {
+        let emboldened = !is_cautious && self.aggression >= EMBOLDENED_AGGRESSION;
+        if (is_aggressive || emboldened) && decision.action == "observe_and_plan" {
             decision.action = "intervene".to_string();
-            decision.reason.push_str(" | adjusted for aggressive personality");
+            decision.reason.push_str(if is_aggressive {
+                " | adjusted for aggressive personality"
+            } else {
+                " | adjusted for difficulty aggression"
+            });
         }
 
-        Ok(decision)
+        Ok(apply_emotional_bias(npc, decision))
     }
 
     fn generate_reaction_decision(&self, npc_id: &str, event: &NPCEvent) -> NPCDecision {
@@ -497,8 +807,8 @@ impl NPCEngine {
             .iter()
             .any(|t| matches!(t, crate::npc::PersonalityTrait::Cautious));
 
-        let action = if event.importance >= 0.8 {
-            if has_aggressive {
+        let action = if event.importance >= self.reaction_threshold() {
+            if has_aggressive || (!has_cautious && self.aggression >= EMBOLDENED_AGGRESSION) {
                 "intervene"
             } else if has_cautious {
                 "observe_carefully"
@@ -511,11 +821,14 @@ impl NPCEngine {
             "acknowledge"
         };
 
-        NPCDecision {
-            npc_id: npc_id.to_string(),
-            action: action.to_string(),
-            reason: format!("Reaction to event: {}", event.description),
-        }
+        apply_emotional_bias(
+            npc,
+            NPCDecision {
+                npc_id: npc_id.to_string(),
+                action: action.to_string(),
+                reason: format!("Reaction to event: {}", event.description),
+            },
+        )
     }
 
     fn apply_pairwise_relationship_updates(&mut self, event: &NPCEvent) {
@@ -544,6 +857,91 @@ impl Default for NPCEngine {
     }
 }
 
+fn event_emotions(description: &str) -> Vec<Emotion> {
+    let lower = description.to_lowercase();
+    EMOTION_KEYWORDS
+        .iter()
+        .filter(|(_, keywords)| keywords.iter().any(|keyword| lower.contains(keyword)))
+        .map(|(emotion, _)| *emotion)
+        .collect()
+}
+
+/// 提示词中的情绪说明，平静时为空
+fn emotion_note(npc: &NPC) -> Option<String> {
+    let emotion = npc.emotions.dominant()?;
+    Some(format!(
+        "Current emotion: {:?} ({:.2}). Angry NPCs escalate, fearful NPCs retreat, grieving NPCs withdraw, joyful NPCs are generous.",
+        emotion,
+        npc.emotions.value(emotion)
+    ))
+}
+
+/// 强烈情绪压过性格给出的行动：愤怒使冲突升级，恐惧使其退避
+fn apply_emotional_bias(npc: &NPC, mut decision: NPCDecision) -> NPCDecision {
+    let Some(emotion) = npc.emotions.dominant() else {
+        return decision;
+    };
+
+    let biased = match (emotion, decision.action.as_str()) {
+        (Emotion::Anger, "acknowledge" | "observe" | "observe_and_plan") => Some("confront"),
+        (Emotion::Anger, "respond" | "observe_carefully") => Some("intervene"),
+        (Emotion::Anger, "intervene" | "prepare_defense") => Some("retaliate"),
+        (Emotion::Fear, "intervene" | "respond") => Some("observe_carefully"),
+        (Emotion::Fear, "acknowledge" | "observe" | "observe_and_plan") => Some("withdraw"),
+        (Emotion::Grief, "acknowledge" | "observe") => Some("mourn"),
+        (Emotion::Joy, "acknowledge") => Some("celebrate"),
+        _ => None,
+    };
+    if let Some(action) = biased {
+        decision.action = action.to_string();
+        decision
+            .reason
+            .push_str(&format!(" | driven by {}", emotion.label()));
+    }
+    decision
+}
+
+fn secret_triggered(
+    secret: &NPCSecret,
+    relationships: &HashMap<String, Relationship>,
+    event: &NPCEvent,
+) -> bool {
+    secret.triggers.iter().any(|trigger| match trigger {
+        RevealTrigger::TrustAtLeast {
+            target_id,
+            threshold,
+        } => relationships
+            .get(target_id)
+            .is_some_and(|relationship| relationship.trust >= *threshold),
+        RevealTrigger::EventKeyword(keyword) => {
+            !keyword.trim().is_empty()
+                && event
+                    .description
+                    .to_lowercase()
+                    .contains(&keyword.trim().to_lowercase())
+        }
+    })
+}
+
+fn build_reveal_segment(npc_name: &str, secret: &NPCSecret) -> String {
+    if let Some(text) = secret.reveal_text.as_ref().filter(|t| !t.trim().is_empty()) {
+        return text.clone();
+    }
+
+    match &secret.kind {
+        SecretKind::HiddenRealm(realm) => format!(
+            "{}周身气息骤然暴涨，先前的伪装如薄冰般碎裂——其真实境界竟是{}{}！",
+            npc_name,
+            realm.name,
+            realm.sub_level_name()
+        ),
+        SecretKind::HiddenAllegiance(faction) => format!(
+            "{}终于卸下伪装，冷冷道出真相：自始至终，其效忠的都是{}。",
+            npc_name, faction
+        ),
+    }
+}
+
 fn clamp_i32(value: i32, min: i32, max: i32) -> i32 {
     value.max(min).min(max)
 }
@@ -551,9 +949,10 @@ fn clamp_i32(value: i32, min: i32, max: i32) -> i32 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::llm_provider::ProviderKind;
     use crate::llm_service::LLMConfig;
     use crate::models::{CultivationRealm, Element, Grade, Lifespan, SpiritualRoot};
-    use crate::npc::{CoreValue, Goal, NPCMemory, Personality, PersonalityTrait};
+    use crate::npc::{CoreValue, EmotionalState, Goal, NPCMemory, Personality, PersonalityTrait};
 
     fn test_npc(id: &str, aggressive: bool) -> NPC {
         let traits = if aggressive {
@@ -568,8 +967,9 @@ mod tests {
             stats: crate::models::CharacterStats::new(
                 SpiritualRoot {
                     element: Element::Fire,
-                    grade: Grade::Double,
+                    grade: Grade::double(),
                     affinity: 0.7,
+                    tier_multiplier: None,
                 },
                 CultivationRealm::new("Qi Condensation".to_string(), 1, 0, 1.0),
                 Lifespan::new(20, 120, 20),
@@ -587,6 +987,10 @@ mod tests {
             },
             memory: NPCMemory::default(),
             relationships: HashMap::new(),
+            secrets: Vec::new(),
+            location: None,
+            bio: String::new(),
+            emotions: EmotionalState::default(),
         }
     }
 
@@ -613,6 +1017,271 @@ mod tests {
         assert!(decisions.iter().any(|d| d.npc_id == "b"));
     }
 
+    #[test]
+    fn test_allies_enemies_and_relationship_prompt_lines() {
+        let mut engine = NPCEngine::with_npcs(HashMap::from([
+            ("a".to_string(), test_npc("a", true)),
+            ("b".to_string(), test_npc("b", false)),
+            ("c".to_string(), test_npc("c", false)),
+        ]));
+        engine.update_relationship("a", "player", -60, 0, "当众受辱", 1);
+        engine.update_relationship("b", "player", 70, 20, "救命之恩", 1);
+        engine.update_relationship("c", "a", 55, 10, "结为道侣", 2);
+        engine.update_relationship("a", "b", -80, -20, "夺宝之仇", 3);
+
+        let ids = |npcs: Vec<&NPC>| npcs.into_iter().map(|n| n.id.clone()).collect::<Vec<String>>();
+        assert_eq!(ids(engine.allies_of("player")), vec!["b".to_string()]);
+        assert_eq!(ids(engine.allies_of("a")), vec!["c".to_string()]);
+        assert_eq!(ids(engine.enemies_of("b", 50)), vec!["a".to_string()]);
+        assert!(engine.enemies_of("b", 90).is_empty());
+
+        assert_eq!(
+            engine.relationship_prompt_lines("player"),
+            vec![
+                "与玩家交好：NPC b".to_string(),
+                "敌视玩家：NPC a".to_string(),
+                "NPC a敌视NPC b".to_string(),
+                "NPC c与NPC a交好".to_string(),
+            ]
+        );
+        let graph = engine.relationship_graph("player", "少年");
+        assert_eq!(graph.nodes.len(), 4);
+        assert_eq!(graph.edges.len(), 4);
+    }
+
+    #[test]
+    fn test_difficulty_aggression_lowers_reaction_threshold() {
+        let mut calm = test_npc("calm", false);
+        calm.personality.traits = vec![PersonalityTrait::Calm];
+        let event = NPCEvent {
+            importance: 0.7,
+            ..plain_event(1, "A caravan arrives at the market")
+        };
+
+        let mut normal = NPCEngine::with_npcs(HashMap::from([("calm".to_string(), calm.clone())]));
+        assert_eq!(normal.process_event(&event)[0].action, "acknowledge");
+
+        let mut hard = NPCEngine::with_npcs(HashMap::from([("calm".to_string(), calm)]))
+            .with_difficulty(&DifficultySettings::hard());
+        assert_eq!(hard.process_event(&event)[0].action, "intervene");
+    }
+
+    fn secret_npc() -> NPC {
+        let mut npc = test_npc("a", false);
+        npc.secrets = vec![
+            NPCSecret {
+                id: "true_realm".to_string(),
+                kind: SecretKind::HiddenRealm(CultivationRealm::new(
+                    "Golden Core".to_string(),
+                    3,
+                    2,
+                    4.0,
+                )),
+                triggers: vec![RevealTrigger::TrustAtLeast {
+                    target_id: "player".to_string(),
+                    threshold: 50,
+                }],
+                reveal_text: None,
+                revealed_at: None,
+            },
+            NPCSecret {
+                id: "allegiance".to_string(),
+                kind: SecretKind::HiddenAllegiance("Blood Moon Sect".to_string()),
+                triggers: vec![RevealTrigger::EventKeyword("blood moon".to_string())],
+                reveal_text: Some("The mask falls away.".to_string()),
+                revealed_at: None,
+            },
+        ];
+        npc
+    }
+
+    fn plain_event(timestamp: u64, description: &str) -> NPCEvent {
+        NPCEvent {
+            timestamp,
+            description: description.to_string(),
+            involved_npc_ids: Vec::new(),
+            importance: 0.5,
+            emotional_impact: 0.0,
+            affinity_impact: 0,
+            trust_impact: 0,
+        }
+    }
+
+    #[test]
+    fn test_secret_revealed_by_trust_threshold_only_once() {
+        let mut engine = NPCEngine::new();
+        engine.insert_npc(secret_npc());
+        let disguised_power = engine.get_npc("a").unwrap().stats.combat_power;
+
+        assert!(engine.reveal_secrets(&plain_event(1, "quiet day")).is_empty());
+
+        engine.update_relationship("a", "player", 0, 60, "saved life", 2);
+        let revelations = engine.reveal_secrets(&plain_event(3, "quiet day"));
+        assert_eq!(revelations.len(), 1);
+        assert_eq!(revelations[0].secret_id, "true_realm");
+        assert!(revelations[0].segment.contains("Golden Core"));
+
+        let npc = engine.get_npc("a").unwrap();
+        assert_eq!(npc.stats.cultivation_realm.name, "Golden Core");
+        assert_eq!(npc.revealed_secrets().len(), 1);
+        assert_eq!(npc.stats.combat_power, npc.stats.calculate_base_combat_power());
+        assert!(npc.stats.combat_power > disguised_power);
+
+        assert!(engine.reveal_secrets(&plain_event(4, "quiet day")).is_empty());
+    }
+
+    #[test]
+    fn test_revealing_a_lower_true_realm_recomputes_combat_power() {
+        let mut npc = secret_npc();
+        npc.stats.cultivation_realm = CultivationRealm::new("Nascent Soul".to_string(), 4, 0, 8.0);
+        npc.stats.update_combat_power();
+        let bluffing_power = npc.stats.combat_power;
+        let mut engine = NPCEngine::new();
+        engine.insert_npc(npc);
+
+        engine.update_relationship("a", "player", 0, 60, "saved life", 2);
+        assert_eq!(engine.reveal_secrets(&plain_event(3, "quiet day")).len(), 1);
+
+        let npc = engine.get_npc("a").unwrap();
+        assert_eq!(npc.stats.cultivation_realm.name, "Golden Core");
+        assert_eq!(npc.stats.combat_power, npc.stats.calculate_base_combat_power());
+        assert!(npc.stats.combat_power < bluffing_power);
+    }
+
+    #[test]
+    fn test_npc_detail_lists_only_revealed_secrets() {
+        let mut engine = NPCEngine::new();
+        engine.insert_npc(secret_npc());
+        let names = HashMap::new();
+        let hidden = engine.get_npc("a").unwrap().detail("player", &names);
+        assert!(hidden.revealed_secrets.is_empty());
+
+        engine.reveal_secrets(&plain_event(5, "The Blood Moon rises"));
+        let detail = engine.get_npc("a").unwrap().detail("player", &names);
+        assert_eq!(detail.revealed_secrets.len(), 1);
+        assert_eq!(detail.revealed_secrets[0].id, "allegiance");
+        assert_eq!(detail.revealed_secrets[0].revealed_at, 5);
+        assert_eq!(
+            detail.revealed_secrets[0].kind,
+            SecretKind::HiddenAllegiance("Blood Moon Sect".to_string())
+        );
+        let json = serde_json::to_string(&detail).unwrap();
+        assert!(!json.contains("true_realm"));
+        assert!(!json.contains("Golden Core"));
+    }
+
+    #[test]
+    fn test_secret_revealed_by_event_keyword_uses_reveal_text() {
+        let mut engine = NPCEngine::new();
+        engine.insert_npc(secret_npc());
+
+        let revelations = engine.reveal_secrets(&plain_event(5, "The Blood Moon rises"));
+        assert_eq!(revelations.len(), 1);
+        assert_eq!(revelations[0].secret_id, "allegiance");
+        assert_eq!(revelations[0].segment, "The mask falls away.");
+    }
+
+    #[test]
+    fn test_angry_npc_escalates_until_emotion_decays() {
+        let mut engine = NPCEngine::new();
+        engine.insert_npc(test_npc("b", false));
+        let insult = NPCEvent {
+            timestamp: 1,
+            description: "The player insulted the sect in public".to_string(),
+            involved_npc_ids: vec!["b".to_string()],
+            importance: 0.9,
+            emotional_impact: -0.7,
+            affinity_impact: -5,
+            trust_impact: -3,
+        };
+
+        let decisions = engine.process_event(&insult);
+        assert_eq!(decisions[0].action, "intervene");
+        assert!(decisions[0].reason.contains("driven by 愤怒"));
+        assert_eq!(
+            engine.get_npc("b").unwrap().emotions.dominant(),
+            Some(Emotion::Anger)
+        );
+
+        engine.decay_emotions(11);
+        let calm = NPCEvent {
+            timestamp: 11,
+            description: "A merchant caravan passes by".to_string(),
+            ..insult
+        };
+        let decisions = engine.process_event(&calm);
+        assert_eq!(decisions[0].action, "observe_carefully");
+    }
+
+    #[tokio::test]
+    async fn test_memory_summaries_skip_npcs_whose_memory_changed() {
+        let mut engine = NPCEngine::new();
+        for id in ["a", "b"] {
+            let mut npc = test_npc(id, false);
+            npc.memory.long_term = (1..=45)
+                .map(|ts| MemoryEntry {
+                    timestamp: ts,
+                    event: format!("patrol {}", ts),
+                    importance: 0.3,
+                    emotional_impact: 0.0,
+                })
+                .collect();
+            engine.insert_npc(npc);
+        }
+
+        let jobs = engine.memory_jobs();
+        assert_eq!(jobs.iter().map(|j| j.npc_id.as_str()).collect::<Vec<_>>(), vec!["a", "b"]);
+        let report = crate::memory_consolidation::MemoryConsolidator::new()
+            .consolidate(&jobs)
+            .await;
+        assert_eq!(report.llm_calls, 0);
+
+        engine.npcs.get_mut("b").unwrap().memory.long_term.clear();
+        assert_eq!(engine.apply_memory_summaries(&jobs, &report), 1);
+        let a = engine.get_npc("a").unwrap();
+        assert_eq!(a.memory.long_term.len(), 45 - jobs[0].entries.len() + 1);
+        assert!(a.memory.long_term.iter().any(|m| m.event.starts_with("往事")));
+        assert!(engine.get_npc("b").unwrap().memory.long_term.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_emotional_memories_are_promoted_after_decay() {
+        let mut engine = NPCEngine::new();
+        engine.insert_npc(test_npc("a", false));
+        for (ts, description, importance, impact) in [
+            (1, "watered the herbs", 0.2, 0.0),
+            (2, "player saved her from a beast", 0.4, 0.9),
+        ] {
+            engine.update_npc_memory(
+                "a",
+                &NPCEvent {
+                    timestamp: ts,
+                    description: description.to_string(),
+                    involved_npc_ids: vec!["a".to_string()],
+                    importance,
+                    emotional_impact: impact,
+                    affinity_impact: 0,
+                    trust_impact: 0,
+                },
+            );
+        }
+
+        assert_eq!(engine.decay_memories(300), 1);
+        let jobs = engine.memory_jobs();
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].kind, MemoryJobKind::Promote);
+        let report = crate::memory_consolidation::MemoryConsolidator::new()
+            .consolidate(&jobs)
+            .await;
+        assert_eq!(engine.apply_memory_summaries(&jobs, &report), 1);
+
+        let a = engine.get_npc("a").unwrap();
+        assert!(a.memory.short_term.is_empty());
+        assert_eq!(a.memory.long_term.len(), 1);
+        assert!(a.memory.long_term[0].event.starts_with("往事1则："));
+        assert!(engine.memory_jobs().is_empty());
+    }
+
     #[test]
     fn test_update_relationship_clamps_values() {
         let mut engine = NPCEngine::new();
@@ -708,10 +1377,12 @@ mod tests {
             model: "gpt-test".to_string(),
             max_tokens: 256,
             temperature: 0.6,
+            provider_kind: ProviderKind::OpenAI,
+            retry_policy: Default::default(),
         })
         .unwrap();
 
-        let mut engine = NPCEngine::new().with_llm_service(llm_service);
+        let mut engine = NPCEngine::new().with_llm_service(Arc::new(llm_service));
         engine.insert_npc(test_npc("a", false));
         let npc = engine.get_npc("a").unwrap().clone();
 
@@ -725,16 +1396,18 @@ mod tests {
         engine.prewarm_npc_decision_cache(service, &npc, situation, &decision);
 
         let prompt = engine.build_npc_decision_prompt(&npc, situation);
-        let response = service
-            .generate(LLMRequest {
+        let structured = service
+            .generate_structured::<DecisionPayload>(LLMRequest {
                 prompt,
                 max_tokens: Some(200),
                 temperature: Some(0.6),
+                subsystem: LLMSubsystem::Npc,
+                retry_policy: None,
             })
             .await
             .unwrap();
 
-        assert!(response.text.contains("observe_and_plan"));
+        assert_eq!(structured.data.unwrap().action, "observe_and_plan");
     }
 }
 
@@ -742,7 +1415,7 @@ mod tests {
 mod property_tests {
     use super::*;
     use crate::models::{CultivationRealm, Element, Grade, Lifespan, SpiritualRoot};
-    use crate::npc::{CoreValue, Goal, NPCMemory, Personality, PersonalityTrait};
+    use crate::npc::{CoreValue, EmotionalState, Goal, NPCMemory, Personality, PersonalityTrait};
     use proptest::prelude::*;
 
     fn basic_npc(id: &str) -> NPC {
@@ -752,8 +1425,9 @@ mod property_tests {
             stats: crate::models::CharacterStats::new(
                 SpiritualRoot {
                     element: Element::Water,
-                    grade: Grade::Double,
+                    grade: Grade::double(),
                     affinity: 0.6,
+                    tier_multiplier: None,
                 },
                 CultivationRealm::new("Qi Condensation".to_string(), 1, 0, 1.0),
                 Lifespan::new(20, 100, 10),
@@ -771,6 +1445,10 @@ mod property_tests {
             },
             memory: NPCMemory::default(),
             relationships: HashMap::new(),
+            secrets: Vec::new(),
+            location: None,
+            bio: String::new(),
+            emotions: EmotionalState::default(),
         }
     }
 
@@ -826,8 +1504,8 @@ mod property_tests {
 
             let npc = engine.get_npc("n1").unwrap();
             let rel = npc.relationships.get("n2").unwrap();
-            prop_assert_eq!(rel.affinity, affinity_delta.max(-100).min(100));
-            prop_assert_eq!(rel.trust, trust_delta.max(-100).min(100));
+            prop_assert_eq!(rel.affinity, affinity_delta.clamp(-100, 100));
+            prop_assert_eq!(rel.trust, trust_delta.clamp(-100, 100));
             prop_assert_eq!(rel.history.len(), 1);
         }
     }
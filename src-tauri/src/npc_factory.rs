@@ -0,0 +1,708 @@
+use crate::llm_service::{LLMRequest, LLMService, LLMSubsystem};
+use crate::models::{
+    CharacterStats, CultivationRealm, Element, Grade, Lifespan, RealmStages, RootTier,
+    SpiritualRoot,
+};
+use crate::npc::{
+    CoreValue, EmotionalState, Goal, NPCMemory, NPCSecret, Personality, PersonalityTrait,
+    Relationship, RevealTrigger, SecretKind, NPC,
+};
+use crate::numerical_system::RealmLadder;
+use crate::prompt_builder::{PromptBuilder, PromptConstraints, PromptContext, PromptTemplate};
+use crate::script::{NpcDefinition, PLAYER_RELATIONSHIP_TARGET};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+const SURNAMES: &[&str] = &[
+    "林", "韩", "萧", "叶", "苏", "陆", "沈", "顾", "秦", "厉", "白", "慕容", "南宫", "上官",
+];
+const GIVEN_NAMES: &[&str] = &[
+    "青云", "寒月", "长风", "若雪", "玄", "无涯", "紫烟", "星河", "墨", "清歌", "凌霄", "听雨",
+    "惊鸿", "不凡",
+];
+const MAX_FLAVOR_CHARS: usize = 80;
+/// 剧本未给出灵根时随机亲和度的范围
+const DEFINED_NPC_AFFINITY: (f32, f32) = (0.4, 0.8);
+
+/// NPC原型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum NPCArchetype {
+    FellowDisciple,
+    Rival,
+    SectMaster,
+    RogueCultivator,
+}
+
+/// 原型的参数范围，境界以参考境界为基准的偏移表示
+#[derive(Debug, Clone, Copy)]
+pub struct ArchetypeTemplate {
+    pub realm_offset: (i32, i32),
+    pub age: (u32, u32),
+    pub lifespan: (u32, u32),
+    pub root_affinity: (f32, f32),
+    pub affinity_to_player: (i32, i32),
+    pub trust_to_player: (i32, i32),
+    pub traits: &'static [PersonalityTrait],
+    pub goals: &'static [&'static str],
+    pub values: &'static [(&'static str, f32)],
+}
+
+impl NPCArchetype {
+    pub fn label(&self) -> &'static str {
+        match self {
+            NPCArchetype::FellowDisciple => "师兄弟",
+            NPCArchetype::Rival => "对头",
+            NPCArchetype::SectMaster => "掌门",
+            NPCArchetype::RogueCultivator => "散修",
+        }
+    }
+
+    fn key(&self) -> &'static str {
+        match self {
+            NPCArchetype::FellowDisciple => "disciple",
+            NPCArchetype::Rival => "rival",
+            NPCArchetype::SectMaster => "master",
+            NPCArchetype::RogueCultivator => "rogue",
+        }
+    }
+
+    pub fn template(&self) -> ArchetypeTemplate {
+        match self {
+            NPCArchetype::FellowDisciple => ArchetypeTemplate {
+                realm_offset: (0, 1),
+                age: (14, 30),
+                lifespan: (100, 150),
+                root_affinity: (0.4, 0.8),
+                affinity_to_player: (10, 40),
+                trust_to_player: (5, 30),
+                traits: &[
+                    PersonalityTrait::Calm,
+                    PersonalityTrait::Righteous,
+                    PersonalityTrait::Cautious,
+                    PersonalityTrait::Ambitious,
+                ],
+                goals: &["在宗门大比中崭露头角", "早日筑基", "守护同门"],
+                values: &[("同门情谊", 0.8), ("宗门荣誉", 0.7)],
+            },
+            NPCArchetype::Rival => ArchetypeTemplate {
+                realm_offset: (0, 1),
+                age: (15, 35),
+                lifespan: (100, 160),
+                root_affinity: (0.5, 0.9),
+                affinity_to_player: (-50, -15),
+                trust_to_player: (-40, -10),
+                traits: &[
+                    PersonalityTrait::Aggressive,
+                    PersonalityTrait::Ambitious,
+                    PersonalityTrait::Scheming,
+                ],
+                goals: &["压过玩家一头", "夺取秘境机缘", "成为宗门首席弟子"],
+                values: &[("胜负", 0.9), ("名声", 0.7)],
+            },
+            NPCArchetype::SectMaster => ArchetypeTemplate {
+                realm_offset: (2, 3),
+                age: (150, 400),
+                lifespan: (500, 800),
+                root_affinity: (0.7, 1.0),
+                affinity_to_player: (0, 20),
+                trust_to_player: (0, 15),
+                traits: &[
+                    PersonalityTrait::Calm,
+                    PersonalityTrait::Righteous,
+                    PersonalityTrait::Scheming,
+                ],
+                goals: &["延续宗门道统", "培养下一代传人", "抵御外敌"],
+                values: &[("秩序", 0.9), ("宗门存续", 1.0)],
+            },
+            NPCArchetype::RogueCultivator => ArchetypeTemplate {
+                realm_offset: (-1, 1),
+                age: (20, 90),
+                lifespan: (120, 200),
+                root_affinity: (0.2, 0.7),
+                affinity_to_player: (-10, 15),
+                trust_to_player: (-20, 10),
+                traits: &[
+                    PersonalityTrait::Cautious,
+                    PersonalityTrait::Scheming,
+                    PersonalityTrait::Aggressive,
+                    PersonalityTrait::Calm,
+                ],
+                goals: &["寻找突破的机缘", "积攒灵石", "躲避仇家追杀"],
+                values: &[("自由", 0.9), ("利益", 0.8)],
+            },
+        }
+    }
+}
+
+/// 按原型模板批量生成NPC
+pub struct NPCFactory {
+    realms: Vec<CultivationRealm>,
+    root_tiers: Vec<RootTier>,
+    realm_ladder: RealmLadder,
+    reference_realm_index: usize,
+    player_id: String,
+    location: Option<String>,
+    seed: u64,
+    next_serial: u32,
+    llm_service: Option<Arc<LLMService>>,
+    prompt_builder: PromptBuilder,
+}
+
+impl NPCFactory {
+    pub fn new(realms: Vec<CultivationRealm>, seed: u64) -> Self {
+        Self {
+            realm_ladder: RealmLadder::new(&realms, &[]),
+            realms,
+            root_tiers: RootTier::builtin(),
+            reference_realm_index: 0,
+            player_id: "player".to_string(),
+            location: None,
+            seed,
+            next_serial: 0,
+            llm_service: None,
+            prompt_builder: PromptBuilder::default(),
+        }
+    }
+
+    /// 使用剧本定义的灵根品阶，默认为内置的四种
+    pub fn with_root_tiers(mut self, root_tiers: Vec<RootTier>) -> Self {
+        if !root_tiers.is_empty() {
+            self.root_tiers = root_tiers;
+        }
+        self
+    }
+
+    /// 使用剧本定义的小境界划分，决定 NPC 的小境界范围与称呼
+    pub fn with_realm_ladder(mut self, stages: &[RealmStages]) -> Self {
+        self.realm_ladder = RealmLadder::new(&self.realms, stages);
+        self
+    }
+
+    pub fn with_reference_realm(mut self, realm_index: usize) -> Self {
+        self.reference_realm_index = realm_index;
+        self
+    }
+
+    pub fn with_player_id(mut self, player_id: impl Into<String>) -> Self {
+        self.player_id = player_id.into();
+        self
+    }
+
+    pub fn at_location(mut self, location_id: impl Into<String>) -> Self {
+        self.location = Some(location_id.into());
+        self
+    }
+
+    pub fn with_llm_service(mut self, llm_service: Arc<LLMService>) -> Self {
+        self.llm_service = Some(llm_service);
+        self
+    }
+
+    /// 按原型权重生成一批经过校验的NPC
+    pub fn generate_npc_cast(
+        &mut self,
+        count: usize,
+        archetype_mix: &[(NPCArchetype, u32)],
+    ) -> Result<Vec<NPC>, String> {
+        if self.realms.is_empty() {
+            return Err("世界设定中没有修炼境界，无法生成NPC".to_string());
+        }
+        let weights = archetype_mix.iter().map(|(_, w)| *w).collect::<Vec<u32>>();
+        if count > 0 && weights.iter().all(|w| *w == 0) {
+            return Err("原型权重不能全部为 0".to_string());
+        }
+
+        let mut used_names = HashSet::new();
+        let mut cast = Vec::with_capacity(count);
+        for _ in 0..count {
+            let archetype = archetype_mix[self.choose_weighted_index(&weights)].0;
+            let npc = self.generate_npc(archetype, &mut used_names);
+            validate_npc(&npc, &self.realm_ladder)?;
+            cast.push(npc);
+        }
+        Ok(cast)
+    }
+
+    /// 按世界观的境界阶梯校正剧本写出的境界，使揭露后的战力与同境界NPC一致
+    fn ladder_realm(&self, realm: &CultivationRealm) -> CultivationRealm {
+        let Some(known) = self.realms.iter().find(|known| known.level == realm.level) else {
+            return realm.clone();
+        };
+        let mut known = known.clone();
+        known.sub_level = realm
+            .sub_level
+            .min(self.realm_ladder.peak_sub_level(known.level));
+        known
+    }
+
+    /// 按剧本定义创建有名有姓的NPC，未给出境界时取参考境界，未给出灵根时按品阶随机
+    pub fn npc_from_definition(&mut self, definition: &NpcDefinition) -> Result<NPC, String> {
+        let realm = match definition.realm_level {
+            Some(level) => self.realms.iter().find(|realm| realm.level == level),
+            None => self
+                .realms
+                .get(self.reference_realm_index)
+                .or(self.realms.first()),
+        };
+        let Some(realm) = realm else {
+            return Err(format!("NPC {} 的境界不存在", definition.name));
+        };
+        let mut realm = realm.clone();
+        realm.sub_level = definition
+            .sub_level
+            .min(self.realm_ladder.peak_sub_level(realm.level));
+        let spiritual_root = match &definition.spiritual_root {
+            Some(root) => root.clone(),
+            None => self.random_root(DEFINED_NPC_AFFINITY),
+        };
+        let stats = CharacterStats::new(
+            spiritual_root,
+            realm,
+            Lifespan::new(definition.age, definition.max_age, 0),
+        );
+
+        let relationships = definition
+            .relationships
+            .iter()
+            .map(|relationship| {
+                let target_id = self.resolve_target(&relationship.target_id);
+                (
+                    target_id.clone(),
+                    Relationship {
+                        target_id,
+                        affinity: relationship.affinity,
+                        trust: relationship.trust,
+                        history: Vec::new(),
+                    },
+                )
+            })
+            .collect::<HashMap<String, Relationship>>();
+        let secrets = definition
+            .secrets
+            .iter()
+            .map(|secret| NPCSecret {
+                kind: match &secret.kind {
+                    SecretKind::HiddenRealm(hidden) => {
+                        SecretKind::HiddenRealm(self.ladder_realm(hidden))
+                    }
+                    SecretKind::HiddenAllegiance(faction) => {
+                        SecretKind::HiddenAllegiance(faction.clone())
+                    }
+                },
+                triggers: secret
+                    .triggers
+                    .iter()
+                    .map(|trigger| match trigger {
+                        RevealTrigger::TrustAtLeast {
+                            target_id,
+                            threshold,
+                        } => RevealTrigger::TrustAtLeast {
+                            target_id: self.resolve_target(target_id),
+                            threshold: *threshold,
+                        },
+                        RevealTrigger::EventKeyword(keyword) => {
+                            RevealTrigger::EventKeyword(keyword.clone())
+                        }
+                    })
+                    .collect(),
+                revealed_at: None,
+                ..secret.clone()
+            })
+            .collect::<Vec<NPCSecret>>();
+        let traits = if definition.traits.is_empty() {
+            vec![PersonalityTrait::Calm]
+        } else {
+            definition.traits.clone()
+        };
+
+        let npc = NPC {
+            id: definition.id.clone(),
+            name: definition.name.clone(),
+            stats,
+            personality: Personality {
+                traits,
+                goals: definition.goals.clone(),
+                values: definition.values.clone(),
+            },
+            memory: NPCMemory::default(),
+            relationships,
+            secrets,
+            location: definition.location.clone().or_else(|| self.location.clone()),
+            bio: definition.bio.clone(),
+            emotions: EmotionalState::default(),
+        };
+        validate_npc(&npc, &self.realm_ladder)?;
+        Ok(npc)
+    }
+
+    /// 使用LLM为NPC补充人物简介，失败时保留模板简介
+    pub async fn add_flavor_text(&self, npcs: &mut [NPC]) {
+        if cfg!(test) {
+            return;
+        }
+        let Some(llm_service) = &self.llm_service else {
+            return;
+        };
+
+        for npc in npcs.iter_mut() {
+            let prompt = self.prompt_builder.build_prompt_with_token_limit(
+                PromptTemplate::NpcDecision,
+                &PromptContext {
+                    scene: Some("为该 NPC 写一句人物简介".to_string()),
+                    location: npc.location.clone(),
+                    actor_name: Some(npc.name.clone()),
+                    actor_realm: Some(npc.stats.cultivation_realm.name.clone()),
+                    actor_combat_power: Some(npc.stats.combat_power),
+                    player_persona: None,
+                    canon_facts: Vec::new(),
+                    story_beat: None,
+                    chapter_beat: None,
+                    environment: None,
+                    active_quests: Vec::new(),
+                    relationships: Vec::new(),
+                    status_effects: Vec::new(),
+                    history_events: Vec::new(),
+                    world_setting_summary: Some(npc.bio.clone()),
+                },
+                &PromptConstraints {
+                    numerical_rules: Vec::new(),
+                    world_rules: vec!["只输出一句中文简介，不超过 60 字".to_string()],
+                    output_schema_hint: Some("纯文本，不要 JSON".to_string()),
+                },
+                300,
+            );
+            if let Ok(response) = llm_service
+                .generate(LLMRequest {
+                    prompt,
+                    max_tokens: Some(120),
+                    temperature: Some(0.9),
+                    subsystem: LLMSubsystem::Npc,
+                    retry_policy: None,
+                })
+                .await
+            {
+                let text = response.text.trim();
+                if !text.is_empty() {
+                    npc.bio = text.chars().take(MAX_FLAVOR_CHARS).collect();
+                }
+            }
+        }
+    }
+
+    fn generate_npc(&mut self, archetype: NPCArchetype, used_names: &mut HashSet<String>) -> NPC {
+        let template = archetype.template();
+        self.next_serial = self.next_serial.saturating_add(1);
+
+        let max_index = self.realms.len() as i32 - 1;
+        let base = self.reference_realm_index.min(max_index as usize) as i32;
+        let offset = self.rand_i32(template.realm_offset.0, template.realm_offset.1);
+        let realm_index = (base + offset).clamp(0, max_index) as usize;
+        let mut realm = self.realms[realm_index].clone();
+        realm.sub_level = self.rand_u32(0, self.realm_ladder.peak_sub_level(realm.level));
+
+        let max_age = self.rand_u32(template.lifespan.0, template.lifespan.1);
+        let age = self
+            .rand_u32(template.age.0, template.age.1)
+            .min(max_age.saturating_sub(1));
+        let spiritual_root = self.random_root(template.root_affinity);
+        let stats = CharacterStats::new(spiritual_root, realm, Lifespan::new(age, max_age, 0));
+
+        let trait_count = self.rand_u32(1, 2) as usize;
+        let mut traits = Vec::new();
+        for _ in 0..trait_count {
+            let candidate = template.traits[self.rand_index(template.traits.len())].clone();
+            if !traits.contains(&candidate) {
+                traits.push(candidate);
+            }
+        }
+        let goal = template.goals[self.rand_index(template.goals.len())];
+
+        let name = self.unique_name(used_names);
+        let bio = format!(
+            "{}，{}{}修士，志在{}。",
+            archetype.label(),
+            stats.cultivation_realm.name,
+            self.realm_ladder.stage_name(&stats.cultivation_realm),
+            goal
+        );
+
+        let mut relationships = std::collections::HashMap::new();
+        relationships.insert(
+            self.player_id.clone(),
+            Relationship {
+                target_id: self.player_id.clone(),
+                affinity: self.rand_i32(template.affinity_to_player.0, template.affinity_to_player.1),
+                trust: self.rand_i32(template.trust_to_player.0, template.trust_to_player.1),
+                history: Vec::new(),
+            },
+        );
+
+        NPC {
+            id: format!("npc_{}_{}_{}", archetype.key(), self.seed % 10_000, self.next_serial),
+            name,
+            stats,
+            personality: Personality {
+                traits,
+                goals: vec![Goal {
+                    description: goal.to_string(),
+                    priority: self.rand_u32(5, 9) as u8,
+                }],
+                values: template
+                    .values
+                    .iter()
+                    .map(|(name, weight)| CoreValue {
+                        name: name.to_string(),
+                        weight: *weight,
+                    })
+                    .collect(),
+            },
+            memory: NPCMemory::default(),
+            relationships,
+            secrets: Vec::new(),
+            location: self.location.clone(),
+            bio,
+            emotions: EmotionalState::default(),
+        }
+    }
+
+    /// 剧本用 "player" 指代玩家，落地时换成本局玩家的实际 ID
+    fn resolve_target(&self, target_id: &str) -> String {
+        if target_id == PLAYER_RELATIONSHIP_TARGET {
+            self.player_id.clone()
+        } else {
+            target_id.to_string()
+        }
+    }
+
+    fn unique_name(&mut self, used_names: &mut HashSet<String>) -> String {
+        for _ in 0..8 {
+            let name = format!(
+                "{}{}",
+                SURNAMES[self.rand_index(SURNAMES.len())],
+                GIVEN_NAMES[self.rand_index(GIVEN_NAMES.len())]
+            );
+            if used_names.insert(name.clone()) {
+                return name;
+            }
+        }
+        let name = format!("无名修士{}", self.next_serial);
+        used_names.insert(name.clone());
+        name
+    }
+
+    fn rng_next(&mut self) -> u64 {
+        let mut x = self.seed;
+        if x == 0 {
+            x = 0x9E37_79B9_7F4A_7C15;
+        }
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.seed = x;
+        x
+    }
+
+    fn rand_u32(&mut self, min: u32, max: u32) -> u32 {
+        if min >= max {
+            return min;
+        }
+        let span = (max - min + 1) as u64;
+        min + (self.rng_next() % span) as u32
+    }
+
+    fn rand_i32(&mut self, min: i32, max: i32) -> i32 {
+        if min >= max {
+            return min;
+        }
+        let span = (max - min + 1) as u64;
+        min + (self.rng_next() % span) as i32
+    }
+
+    fn rand_f32(&mut self, min: f32, max: f32) -> f32 {
+        if min >= max {
+            return min;
+        }
+        let val = (self.rng_next() as f64 / u64::MAX as f64) as f32;
+        min + (max - min) * val
+    }
+
+    fn rand_index(&mut self, len: usize) -> usize {
+        if len == 0 {
+            return 0;
+        }
+        (self.rng_next() % len as u64) as usize
+    }
+
+    fn choose_weighted_index(&mut self, weights: &[u32]) -> usize {
+        let total: u32 = weights.iter().sum();
+        if total == 0 {
+            return 0;
+        }
+        let mut roll = self.rand_u32(1, total);
+        for (idx, weight) in weights.iter().enumerate() {
+            if *weight == 0 {
+                continue;
+            }
+            if roll <= *weight {
+                return idx;
+            }
+            roll -= *weight;
+        }
+        weights.len().saturating_sub(1)
+    }
+
+    fn random_root(&mut self, affinity: (f32, f32)) -> SpiritualRoot {
+        let tier = self.random_tier();
+        SpiritualRoot {
+            element: self.random_element(),
+            grade: tier.as_ref().map_or_else(Grade::pseudo, |tier| tier.id.clone()),
+            affinity: self.rand_f32(affinity.0, affinity.1),
+            tier_multiplier: tier.map(|tier| tier.power_multiplier),
+        }
+    }
+
+    fn random_tier(&mut self) -> Option<RootTier> {
+        let weights = self
+            .root_tiers
+            .iter()
+            .map(|tier| tier.rarity_weight)
+            .collect::<Vec<u32>>();
+        let index = self.choose_weighted_index(&weights);
+        self.root_tiers.get(index).cloned()
+    }
+
+    fn random_element(&mut self) -> Element {
+        match self.rand_u32(0, 4) {
+            0 => Element::Metal,
+            1 => Element::Wood,
+            2 => Element::Water,
+            3 => Element::Fire,
+            _ => Element::Earth,
+        }
+    }
+}
+
+/// 默认的原型配比，适用于随机剧本开局
+pub fn default_archetype_mix() -> Vec<(NPCArchetype, u32)> {
+    vec![
+        (NPCArchetype::FellowDisciple, 4),
+        (NPCArchetype::Rival, 2),
+        (NPCArchetype::SectMaster, 1),
+        (NPCArchetype::RogueCultivator, 3),
+    ]
+}
+
+/// 校验生成的NPC数值是否自洽
+pub fn validate_npc(npc: &NPC, ladder: &RealmLadder) -> Result<(), String> {
+    if npc.id.trim().is_empty() || npc.name.trim().is_empty() {
+        return Err("NPC 的 id 与名称不能为空".to_string());
+    }
+    if !ladder
+        .realms()
+        .iter()
+        .any(|realm| realm.name == npc.stats.cultivation_realm.name)
+    {
+        return Err(format!(
+            "NPC {} 的境界不存在: {}",
+            npc.name, npc.stats.cultivation_realm.name
+        ));
+    }
+    let realm = &npc.stats.cultivation_realm;
+    if realm.sub_level > ladder.peak_sub_level(realm.level) {
+        return Err(format!("NPC {} 的小境界无效", npc.name));
+    }
+    if !npc.stats.lifespan.is_alive() {
+        return Err(format!("NPC {} 的年龄超过寿元", npc.name));
+    }
+    if !(0.0..=1.0).contains(&npc.stats.spiritual_root.affinity) {
+        return Err(format!("NPC {} 的灵根亲和度无效", npc.name));
+    }
+    if npc.personality.traits.is_empty() {
+        return Err(format!("NPC {} 缺少性格特征", npc.name));
+    }
+    for relationship in npc.relationships.values() {
+        if !(-100..=100).contains(&relationship.affinity) || !(-100..=100).contains(&relationship.trust) {
+            return Err(format!("NPC {} 的关系数值越界", npc.name));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_realms() -> Vec<CultivationRealm> {
+        vec![
+            CultivationRealm::new("练气".to_string(), 1, 0, 1.0),
+            CultivationRealm::new("筑基".to_string(), 2, 0, 2.0),
+            CultivationRealm::new("金丹".to_string(), 3, 0, 4.0),
+            CultivationRealm::new("元婴".to_string(), 4, 0, 8.0),
+        ]
+    }
+
+    #[test]
+    fn test_generate_npc_cast_produces_valid_unique_npcs() {
+        let realms = test_realms();
+        let mut factory = NPCFactory::new(realms.clone(), 42).at_location("sect");
+        let cast = factory
+            .generate_npc_cast(12, &default_archetype_mix())
+            .unwrap();
+
+        assert_eq!(cast.len(), 12);
+        let ids = cast.iter().map(|n| n.id.clone()).collect::<HashSet<String>>();
+        let names = cast.iter().map(|n| n.name.clone()).collect::<HashSet<String>>();
+        assert_eq!(ids.len(), 12);
+        assert_eq!(names.len(), 12);
+        for npc in &cast {
+            assert!(validate_npc(npc, &RealmLadder::new(&realms, &[])).is_ok());
+            assert_eq!(npc.location.as_deref(), Some("sect"));
+            assert!(npc.relationships.contains_key("player"));
+            assert!(!npc.bio.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_sect_master_outranks_reference_realm() {
+        let mut factory = NPCFactory::new(test_realms(), 7);
+        let cast = factory
+            .generate_npc_cast(5, &[(NPCArchetype::SectMaster, 1)])
+            .unwrap();
+
+        assert!(cast.iter().all(|npc| npc.stats.cultivation_realm.level >= 3));
+    }
+
+    #[test]
+    fn test_rival_starts_hostile() {
+        let mut factory = NPCFactory::new(test_realms(), 99);
+        let cast = factory
+            .generate_npc_cast(3, &[(NPCArchetype::Rival, 1)])
+            .unwrap();
+
+        assert!(cast
+            .iter()
+            .all(|npc| npc.relationships["player"].affinity < 0));
+    }
+
+    #[test]
+    fn test_generate_npc_cast_rejects_invalid_input() {
+        let mut empty = NPCFactory::new(Vec::new(), 1);
+        assert!(empty.generate_npc_cast(1, &default_archetype_mix()).is_err());
+
+        let mut factory = NPCFactory::new(test_realms(), 1);
+        assert!(factory
+            .generate_npc_cast(1, &[(NPCArchetype::Rival, 0)])
+            .is_err());
+    }
+
+    #[test]
+    fn test_same_seed_is_deterministic() {
+        let mut a = NPCFactory::new(test_realms(), 1234);
+        let mut b = NPCFactory::new(test_realms(), 1234);
+        assert_eq!(
+            a.generate_npc_cast(4, &default_archetype_mix()).unwrap(),
+            b.generate_npc_cast(4, &default_archetype_mix()).unwrap()
+        );
+    }
+}
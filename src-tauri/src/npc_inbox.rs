@@ -0,0 +1,96 @@
+use crate::npc_engine::NPCEvent;
+use std::collections::VecDeque;
+
+/// 每回合带入下一回合剧情上下文的 NPC 反应条数上限
+const MAX_DIGEST_LINES: usize = 8;
+
+/// NPC 事件收件箱：回合内只入队，世界推进或后台任务中再统一处理，
+/// 处理结果汇总为摘要并入下一回合的剧情上下文
+#[derive(Debug, Clone, Default)]
+pub struct NpcInbox {
+    pending: VecDeque<NPCEvent>,
+    digest: Vec<String>,
+}
+
+impl NpcInbox {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 将剧情事件入队，等待 NPC 处理
+    pub fn enqueue_story_event(&mut self, timestamp: u64, description: impl Into<String>) {
+        self.pending.push_back(NPCEvent {
+            timestamp,
+            description: description.into(),
+            involved_npc_ids: Vec::new(),
+            importance: 0.7,
+            emotional_impact: 0.2,
+            affinity_impact: 1,
+            trust_impact: 1,
+        });
+    }
+
+    /// 复核后认定关乎主线的事件，NPC 记得更牢、反应更强
+    pub fn enqueue_key_event(&mut self, timestamp: u64, description: impl Into<String>) {
+        self.pending.push_back(NPCEvent {
+            timestamp,
+            description: description.into(),
+            involved_npc_ids: Vec::new(),
+            importance: 0.9,
+            emotional_impact: 0.4,
+            affinity_impact: 1,
+            trust_impact: 1,
+        });
+    }
+
+    pub fn pending_len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn take_pending(&mut self) -> Vec<NPCEvent> {
+        self.pending.drain(..).collect()
+    }
+
+    /// 记录一条处理结果，只保留最近的若干条
+    pub fn record(&mut self, line: String) {
+        self.digest.push(line);
+        let overflow = self.digest.len().saturating_sub(MAX_DIGEST_LINES);
+        self.digest.drain(..overflow);
+    }
+
+    /// 取出尚未带入剧情的处理结果
+    pub fn take_digest(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.digest)
+    }
+
+    pub fn clear(&mut self) {
+        self.pending.clear();
+        self.digest.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inbox_queues_events_and_caps_digest() {
+        let mut inbox = NpcInbox::new();
+        inbox.enqueue_story_event(3, "宗门大比开始");
+        inbox.enqueue_story_event(3, "长老闭关");
+        assert_eq!(inbox.pending_len(), 2);
+
+        let events = inbox.take_pending();
+        assert_eq!(events[0].description, "宗门大比开始");
+        assert_eq!(events[1].timestamp, 3);
+        assert_eq!(inbox.pending_len(), 0);
+
+        for idx in 0..MAX_DIGEST_LINES + 2 {
+            inbox.record(format!("反应{}", idx));
+        }
+        let digest = inbox.take_digest();
+        assert_eq!(digest.len(), MAX_DIGEST_LINES);
+        assert_eq!(digest[0], "反应2");
+        assert!(inbox.take_digest().is_empty());
+    }
+}
@@ -1,13 +1,63 @@
-﻿use crate::models::{CharacterStats, CultivationRealm, Grade, SpiritualRoot};
+﻿use crate::difficulty::DifficultySettings;
+use crate::economy::{EconomyConfig, ResourceDelta, ResourceKind};
+use crate::formula::{Formula, FormulaError};
+use crate::game_state::Item;
+use crate::house_rules::HouseRules;
+use crate::karma::Alignment;
+use crate::models::{
+    CharacterStats, CultivationRealm, LearnedTechnique, RealmStages, SpiritualRoot, StatDelta,
+    MAX_INSIGHT, TWILIGHT_LIFESPAN_RATIO,
+};
+use crate::rng::GameRng;
+use crate::script::{Location, NumericalConfig, Technique, WorldSetting};
+use crate::status_effects::{
+    StatusEffectKind, MISHAP_AFFLICTION_DAYS, REST_RECOVERY_DAYS, TRIBULATION_AFFLICTION_DAYS,
+};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// 突破成功率公式可引用的变量
+pub const BREAKTHROUGH_FORMULA_VARIABLES: &[&str] = &[
+    "affinity",
+    "root_multiplier",
+    "sub_level",
+    "realm_level",
+    "combat_power",
+    "age",
+    "technique_count",
+    "technique_multiplier",
+    "items",
+    "difficulty",
+];
+
+/// 修炼进度公式可引用的变量
+pub const CULTIVATION_FORMULA_VARIABLES: &[&str] = &[
+    "affinity",
+    "root_multiplier",
+    "sub_level",
+    "realm_level",
+    "combat_power",
+    "age",
+    "technique_count",
+    "technique_multiplier",
+    "items",
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub enum Action {
     Cultivate,
     Combat { target_id: String },
     Breakthrough,
     Rest,
     Custom { description: String },
+    /// 修习剧本中的功法，需达到功法要求的境界
+    LearnTechnique { technique_id: String },
+    /// 勤练已学功法，提升熟练度
+    PracticeTechnique { technique_id: String },
+    /// 服用背包中的丹药
+    UseItem { item_id: String },
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -15,9 +65,12 @@ pub struct Context {
     pub location: String,
     pub time_of_day: String,
     pub weather: Option<String>,
+    pub season: Option<String>,
+    /// 所在地点的灵气浓度，1.0 为寻常
+    pub spiritual_energy: f32,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct ActionResult {
     pub success: bool,
     pub description: String,
@@ -25,13 +78,281 @@ pub struct ActionResult {
     pub events: Vec<String>,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct StatChange {
     pub stat_name: String,
     pub old_value: String,
     pub new_value: String,
 }
 
+/// 行动失手的后果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MishapKind {
+    /// 突破失败后真气反噬，经脉受创
+    BreakthroughBacklash,
+    /// 战斗中负伤败退
+    CombatInjury,
+    /// 修炼时岔了气，走火入魔
+    CultivationDeviation,
+}
+
+impl MishapKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            MishapKind::BreakthroughBacklash => "冲关反噬",
+            MishapKind::CombatInjury => "负伤败退",
+            MishapKind::CultivationDeviation => "修炼岔气",
+        }
+    }
+
+    /// 失手时折损的战力比例
+    fn power_loss_ratio(&self) -> f32 {
+        match self {
+            MishapKind::BreakthroughBacklash => 0.1,
+            MishapKind::CombatInjury => 0.05,
+            MishapKind::CultivationDeviation => 0.03,
+        }
+    }
+}
+
+/// 一次行动的风险：失手的概率与后果
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ActionRisk {
+    pub kind: MishapKind,
+    pub chance: f32,
+}
+
+/// 在某处修炼、休息的成效估算
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct LocationSurvey {
+    pub location_id: String,
+    pub name: String,
+    pub spiritual_energy: f32,
+    /// 当日天气
+    pub weather: Option<String>,
+    /// 相对寻常之地的修炼效率倍率
+    pub efficiency: f32,
+    /// 在此修炼一次预计增长的战力
+    pub cultivation_gain: i64,
+    /// 在此休息一次额外缩短伤病的天数
+    pub rest_recovery_days: u32,
+    /// 是否为角色当前所在之处
+    pub current: bool,
+}
+
+/// 天劫的关卡，依次为雷劫与心魔劫
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TribulationStageKind {
+    /// 考验肉身，战力越接近下一境界越容易渡过
+    Lightning,
+    /// 考验道心，灵根亲和度越高越容易渡过
+    InnerDemon,
+}
+
+impl TribulationStageKind {
+    pub const ALL: [TribulationStageKind; 2] =
+        [TribulationStageKind::Lightning, TribulationStageKind::InnerDemon];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TribulationStageKind::Lightning => "雷劫",
+            TribulationStageKind::InnerDemon => "心魔劫",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct TribulationStage {
+    pub kind: TribulationStageKind,
+    pub chance: f32,
+    pub passed: bool,
+}
+
+/// 一次渡劫的经过，某一关失败后不再进行后续关卡
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct TribulationOutcome {
+    /// 渡劫成功后晋入的境界（初期）
+    pub target_realm: CultivationRealm,
+    pub stages: Vec<TribulationStage>,
+    pub passed: bool,
+    /// 渡劫成功后增加的寿元，由境界阶梯决定
+    #[serde(default)]
+    pub lifespan_bonus: u32,
+}
+
+impl TribulationOutcome {
+    /// 未能渡过的关卡
+    pub fn failed_stage(&self) -> Option<TribulationStageKind> {
+        self.stages.iter().find(|stage| !stage.passed).map(|stage| stage.kind)
+    }
+
+    /// 结算渡劫结果：成功晋升境界并增寿；雷劫失败受伤折寿，心魔劫失败修为跌落一层并走火入魔
+    pub fn apply(&self, actor: &mut CharacterStats) -> Vec<StatChange> {
+        match self.failed_stage() {
+            None => {
+                let mut changes = actor.advance_realm(self.target_realm.clone());
+                changes.extend(
+                    actor.apply_stat_change(StatDelta::LifespanBonus(self.lifespan_bonus)),
+                );
+                changes
+            }
+            Some(TribulationStageKind::Lightning) => {
+                let injury = (actor.combat_power as f32 * LIGHTNING_INJURY_RATIO).round() as i64;
+                actor.status_effects.apply(
+                    StatusEffectKind::Injured,
+                    TRIBULATION_AFFLICTION_DAYS,
+                    "雷劫重创",
+                );
+                actor
+                    .apply_stat_change(StatDelta::CombatPower(-injury))
+                    .into_iter()
+                    .chain(actor.apply_stat_change(StatDelta::Age(LIGHTNING_INJURY_YEARS)))
+                    .collect()
+            }
+            Some(TribulationStageKind::InnerDemon) => {
+                actor.status_effects.apply(
+                    StatusEffectKind::QiDeviation,
+                    TRIBULATION_AFFLICTION_DAYS,
+                    "心魔反噬",
+                );
+                actor.regress_sub_level(actor.cultivation_realm.sub_level.saturating_sub(1))
+            }
+        }
+    }
+
+    /// 写入行动结果的一句总结
+    pub fn summary(&self) -> String {
+        match self.failed_stage() {
+            None => format!("历经天劫，晋入{}！", self.target_realm.name),
+            Some(TribulationStageKind::Lightning) => format!(
+                "未能渡过雷劫，冲击{}失败，元气大伤，折寿 {} 年。",
+                self.target_realm.name, LIGHTNING_INJURY_YEARS
+            ),
+            Some(TribulationStageKind::InnerDemon) => format!(
+                "未能勘破心魔，冲击{}失败，修为跌落一层。",
+                self.target_realm.name
+            ),
+        }
+    }
+}
+
+/// 剧情中触发的感悟、机缘等大事积累的悟性
+pub fn insight_from_events(events: &[String]) -> u32 {
+    let notable = events
+        .iter()
+        .filter(|event| {
+            INSIGHT_EVENT_KEYWORDS
+                .iter()
+                .any(|keyword| event.contains(keyword))
+        })
+        .count() as u32;
+    notable.saturating_mul(INSIGHT_PER_NOTABLE_EVENT)
+}
+
+/// 剧本境界中紧接当前境界的下一大境界
+pub fn next_major_realm<'a>(
+    realms: &'a [CultivationRealm],
+    current: &CultivationRealm,
+) -> Option<&'a CultivationRealm> {
+    realms
+        .iter()
+        .filter(|realm| realm.level > current.level)
+        .min_by_key(|realm| realm.level)
+}
+
+/// 境界阶梯：剧本的大境界与各自的小境界划分，圆满判定、下一层与下一大境界都由它给出
+#[derive(Debug, Clone, Default)]
+pub struct RealmLadder {
+    realms: Vec<CultivationRealm>,
+    stages: Vec<RealmStages>,
+}
+
+impl RealmLadder {
+    pub fn new(realms: &[CultivationRealm], stages: &[RealmStages]) -> Self {
+        Self {
+            realms: realms.to_vec(),
+            stages: stages.to_vec(),
+        }
+    }
+
+    pub fn from_world(world: &WorldSetting) -> Self {
+        Self::new(&world.cultivation_realms, &world.realm_ladder)
+    }
+
+    pub fn realms(&self) -> &[CultivationRealm] {
+        &self.realms
+    }
+
+    fn stages_of(&self, level: u32) -> Option<&RealmStages> {
+        self.stages.iter().find(|stages| stages.level == level)
+    }
+
+    /// 该境界圆满时的子等级，未定义划分的境界为圆满期
+    pub fn peak_sub_level(&self, level: u32) -> u32 {
+        self.stages_of(level)
+            .map_or(PEAK_SUB_LEVEL, |stages| stages.stages.max(1) - 1)
+    }
+
+    pub fn is_peak(&self, realm: &CultivationRealm) -> bool {
+        realm.sub_level >= self.peak_sub_level(realm.level)
+    }
+
+    /// 小境界名称：剧本命名、“第N层”，未定义划分时为初期至圆满期
+    pub fn stage_name(&self, realm: &CultivationRealm) -> String {
+        match self.stages_of(realm.level) {
+            Some(stages) => stages
+                .stage_names
+                .get(realm.sub_level as usize)
+                .cloned()
+                .unwrap_or_else(|| format!("第{}层", realm.sub_level + 1)),
+            None => realm.sub_level_name().to_string(),
+        }
+    }
+
+    pub fn display_name(&self, realm: &CultivationRealm) -> String {
+        format!("{} {}", realm.name, self.stage_name(realm))
+    }
+
+    /// 当前大境界内的修行进度百分比
+    pub fn progress_percent(&self, realm: &CultivationRealm) -> u32 {
+        let peak = self.peak_sub_level(realm.level);
+        if peak == 0 {
+            return 100;
+        }
+        realm.sub_level.min(peak) * 100 / peak
+    }
+
+    /// 同一大境界内的下一层，圆满时为空
+    pub fn next_stage(&self, realm: &CultivationRealm) -> Option<CultivationRealm> {
+        if self.is_peak(realm) {
+            return None;
+        }
+        let mut next = realm.clone();
+        next.sub_level += 1;
+        next.power_multiplier *= 1.2;
+        Some(next)
+    }
+
+    pub fn next_major(&self, realm: &CultivationRealm) -> Option<&CultivationRealm> {
+        next_major_realm(&self.realms, realm)
+    }
+
+    /// 冲击该境界所需的最低战力
+    pub fn min_combat_power(&self, level: u32) -> u64 {
+        self.stages_of(level)
+            .map_or(0, |stages| stages.min_combat_power)
+    }
+
+    /// 渡劫晋入该境界后增加的寿元
+    pub fn lifespan_bonus(&self, level: u32) -> u32 {
+        self.stages_of(level)
+            .and_then(|stages| stages.lifespan_bonus)
+            .unwrap_or_else(|| TRIBULATION_LIFESPAN_PER_LEVEL.saturating_mul(level))
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CombatResult {
     pub winner_id: String,
@@ -40,12 +361,97 @@ pub struct CombatResult {
     pub description: String,
 }
 
+#[derive(Clone)]
 pub struct NumericalSystem {
     realm_rules: RealmRules,
+    formulas: ScriptFormulas,
+    /// 剧本中可修习的功法
+    techniques: Vec<Technique>,
+    difficulty: DifficultySettings,
+    economy: EconomyConfig,
+    /// 玩家业力的取向，决定魔道功法能否修习
+    alignment: Alignment,
+    realm_ladder: RealmLadder,
+    /// 玩家背包中的物品数，公式变量 items
+    item_count: usize,
+    /// 公式求值失败的记录，克隆间共享，由回合流水线取出写入生成诊断
+    formula_failures: Arc<Mutex<Vec<String>>>,
 }
 
+#[derive(Clone)]
 struct RealmRules {
     breakthrough_difficulty: f32,
+    /// 房规允许越阶时，境界差距不再压制低境界一方
+    allow_cross_realm_feats: bool,
+}
+
+/// 圆满期的子等级，再突破即冲击下一大境界
+pub const PEAK_SUB_LEVEL: u32 = 3;
+/// 寿元耗尽时气血衰退到的下限
+const MIN_VITALITY: f32 = 0.5;
+/// 天劫每一关成功率的上下限
+const MIN_TRIBULATION_CHANCE: f32 = 0.05;
+const MAX_TRIBULATION_CHANCE: f32 = 0.95;
+/// 渡劫成功后每个境界等级增加的寿元
+const TRIBULATION_LIFESPAN_PER_LEVEL: u32 = 30;
+/// 悟性积满时为突破成功率带来的加成
+const MAX_INSIGHT_BONUS: f32 = 0.5;
+/// 修炼与外出历练每回合积累的悟性
+const INSIGHT_PER_CULTIVATION: u32 = 3;
+const INSIGHT_PER_ADVENTURE: u32 = 2;
+/// 剧情中出现感悟、机缘一类的大事时积累的悟性
+const INSIGHT_PER_NOTABLE_EVENT: u32 = 10;
+const INSIGHT_EVENT_KEYWORDS: &[&str] = &["顿悟", "感悟", "机缘", "传承", "奇遇", "参悟"];
+/// 行动失手概率的上限
+const MAX_MISHAP_CHANCE: f32 = 0.6;
+/// 灵气浓度折算修炼效率时的上下限，灵气枯竭之地也不至于毫无寸进
+const MIN_SPIRITUAL_ENERGY_EFFICIENCY: f32 = 0.2;
+const MAX_SPIRITUAL_ENERGY_EFFICIENCY: f32 = 3.0;
+/// 夜深人静时吐纳更易入定
+const NIGHT_EFFICIENCY_BONUS: f32 = 1.1;
+const NIGHT_KEYWORDS: &[&str] = &["night", "midnight", "夜", "子时"];
+/// 风雨扰人心神，雷暴更甚
+const RAIN_EFFICIENCY: f32 = 0.9;
+const STORM_EFFICIENCY: f32 = 0.75;
+const RAIN_KEYWORDS: &[&str] = &["rain", "snow", "雨", "雪"];
+const STORM_KEYWORDS: &[&str] = &["storm", "thunder", "雷", "暴"];
+/// 春日生发略增修炼效率，寒冬略减
+const SPRING_EFFICIENCY: f32 = 1.05;
+const WINTER_EFFICIENCY: f32 = 0.95;
+const SPRING_KEYWORDS: &[&str] = &["spring", "春"];
+const WINTER_KEYWORDS: &[&str] = &["winter", "冬"];
+/// 雷暴之中修炼、斗法更易失手
+const STORM_MISHAP_CHANCE: f32 = 0.05;
+/// 雷劫失败时折损的战力比例与折寿年数
+const LIGHTNING_INJURY_RATIO: f32 = 0.2;
+const LIGHTNING_INJURY_YEARS: u32 = 5;
+
+#[derive(Clone, Default)]
+struct ScriptFormulas {
+    breakthrough_chance: Option<Formula>,
+    cultivation_progress: Option<Formula>,
+}
+
+impl ScriptFormulas {
+    fn compile(config: &NumericalConfig) -> Result<Self, FormulaError> {
+        let compile = |source: &Option<String>, allowed: &[&str]| {
+            source
+                .as_deref()
+                .filter(|s| !s.trim().is_empty())
+                .map(|s| Formula::parse_with_whitelist(s, allowed))
+                .transpose()
+        };
+        Ok(Self {
+            breakthrough_chance: compile(
+                &config.breakthrough_chance,
+                BREAKTHROUGH_FORMULA_VARIABLES,
+            )?,
+            cultivation_progress: compile(
+                &config.cultivation_progress,
+                CULTIVATION_FORMULA_VARIABLES,
+            )?,
+        })
+    }
 }
 
 impl Default for NumericalSystem {
@@ -59,10 +465,215 @@ impl NumericalSystem {
         Self {
             realm_rules: RealmRules {
                 breakthrough_difficulty: 0.5,
+                allow_cross_realm_feats: false,
             },
+            formulas: ScriptFormulas::default(),
+            techniques: Vec::new(),
+            difficulty: DifficultySettings::default(),
+            economy: EconomyConfig::default(),
+            alignment: Alignment::default(),
+            realm_ladder: RealmLadder::default(),
+            item_count: 0,
+            formula_failures: Arc::default(),
+        }
+    }
+
+    /// 使用剧本中的数值公式创建数值系统，公式非法时返回错误
+    pub fn with_config(config: &NumericalConfig) -> Result<Self, FormulaError> {
+        let mut system = Self::new();
+        system.formulas = ScriptFormulas::compile(config)?;
+        Ok(system)
+    }
+
+    pub fn with_house_rules(mut self, house_rules: &HouseRules) -> Self {
+        self.realm_rules.allow_cross_realm_feats = house_rules.allow_cross_realm_feats;
+        self
+    }
+
+    pub fn with_difficulty(mut self, difficulty: &DifficultySettings) -> Self {
+        self.difficulty = *difficulty;
+        self
+    }
+
+    pub fn with_techniques(mut self, techniques: &[Technique]) -> Self {
+        self.techniques = techniques.to_vec();
+        self
+    }
+
+    pub fn with_alignment(mut self, alignment: Alignment) -> Self {
+        self.alignment = alignment;
+        self
+    }
+
+    pub fn with_economy(mut self, economy: &EconomyConfig) -> Self {
+        self.economy = economy.clone();
+        self
+    }
+
+    pub fn with_realm_ladder(mut self, world: &WorldSetting) -> Self {
+        self.realm_ladder = RealmLadder::from_world(world);
+        self
+    }
+
+    pub fn with_inventory(mut self, inventory: &[Item]) -> Self {
+        self.item_count = inventory.len();
+        self
+    }
+
+    /// 取出自上次以来的公式求值失败记录
+    pub fn take_formula_failures(&self) -> Vec<String> {
+        match self.formula_failures.lock() {
+            Ok(mut failures) => std::mem::take(&mut *failures),
+            Err(poisoned) => std::mem::take(&mut *poisoned.into_inner()),
         }
     }
 
+    pub fn realm_ladder(&self) -> &RealmLadder {
+        &self.realm_ladder
+    }
+
+    /// 功法售价，按功法要求的境界计
+    pub fn technique_price(&self, technique: &Technique) -> u64 {
+        self.economy.technique_price_per_level * u64::from(technique.required_realm_level.max(1))
+    }
+
+    /// 行动的资源花费（负数）：突破按境界与目标层数花费灵石，冲击大境界另耗灵草；修习功法需购买
+    pub fn action_cost(&self, actor: &CharacterStats, action: &Action) -> ResourceDelta {
+        let realm = &actor.cultivation_realm;
+        let level = u64::from(realm.level.max(1));
+        match action {
+            Action::Breakthrough => {
+                let stones =
+                    self.economy.breakthrough_stones * level * u64::from(realm.sub_level + 1);
+                let mut cost = ResourceDelta::of(ResourceKind::SpiritStones, -(stones as i64));
+                if self.realm_ladder.is_peak(realm) {
+                    cost.herbs = -((self.economy.tribulation_herbs * level) as i64);
+                }
+                cost
+            }
+            Action::LearnTechnique { technique_id } => self
+                .technique(technique_id)
+                .map(|technique| {
+                    ResourceDelta::of(
+                        ResourceKind::SpiritStones,
+                        -(self.technique_price(technique) as i64),
+                    )
+                })
+                .unwrap_or_default(),
+            _ => ResourceDelta::default(),
+        }
+    }
+
+    /// 行动成功后的资源收益，受资源稀缺度影响
+    pub fn action_earnings(&self, actor: &CharacterStats, action: &Action) -> ResourceDelta {
+        let level = u64::from(actor.cultivation_realm.level.max(1));
+        match action {
+            Action::Combat { .. } => {
+                let stones = (self.economy.combat_reward_stones * level) as f32
+                    * self.difficulty.yield_multiplier();
+                ResourceDelta::of(ResourceKind::SpiritStones, stones.round() as i64)
+            }
+            _ => ResourceDelta::default(),
+        }
+    }
+
+    /// 市场单价（灵石）：买入价随资源稀缺度上浮，卖出价为买入价的一半
+    pub fn market_price(&self, kind: ResourceKind, buying: bool) -> u64 {
+        let scarcity = self.difficulty.resource_scarcity.clamp(0.0, 1.0);
+        let buy = (self.economy.base_price(kind) as f32 * (1.0 + scarcity)).round() as u64;
+        if buying {
+            buy.max(1)
+        } else {
+            (buy / 2).max(1)
+        }
+    }
+
+    /// 按市场价买卖资源的结算；灵石本身不能买卖
+    pub fn trade_delta(
+        &self,
+        kind: ResourceKind,
+        quantity: u64,
+        buying: bool,
+    ) -> Option<ResourceDelta> {
+        if kind == ResourceKind::SpiritStones || quantity == 0 {
+            return None;
+        }
+        let total = (self.market_price(kind, buying) * quantity) as i64;
+        let (goods, stones) = if buying {
+            (quantity as i64, -total)
+        } else {
+            (-(quantity as i64), total)
+        };
+        Some(
+            ResourceDelta::of(kind, goods)
+                .plus(ResourceDelta::of(ResourceKind::SpiritStones, stones)),
+        )
+    }
+
+    pub fn technique(&self, id: &str) -> Option<&Technique> {
+        self.techniques.iter().find(|t| t.id == id)
+    }
+
+    /// 角色尚未习得且境界已满足要求的功法，魔道功法须业力落入魔道
+    pub fn learnable_techniques<'a>(
+        &'a self,
+        actor: &'a CharacterStats,
+    ) -> impl Iterator<Item = &'a Technique> + 'a {
+        self.techniques.iter().filter(move |technique| {
+            actor.technique(&technique.id).is_none()
+                && technique.required_realm_level <= actor.cultivation_realm.level
+                && (!technique.demonic || self.alignment == Alignment::Demonic)
+        })
+    }
+
+    pub fn validate_config(config: &NumericalConfig) -> Result<(), FormulaError> {
+        ScriptFormulas::compile(config).map(|_| ())
+    }
+
+    /// 公式求值失败（超出步数、非有限值）时记下原因并回退到内置规则，不中断回合
+    fn evaluate_formula(
+        &self,
+        name: &str,
+        formula: Option<&Formula>,
+        actor: &CharacterStats,
+    ) -> Option<f64> {
+        match formula?.evaluate(&self.formula_variables(actor)) {
+            Ok(value) => Some(value),
+            Err(error) => {
+                let note = format!("剧本公式 {} 求值失败，已回退到内置规则：{}", name, error);
+                match self.formula_failures.lock() {
+                    Ok(mut failures) => failures.push(note),
+                    Err(poisoned) => poisoned.into_inner().push(note),
+                }
+                None
+            }
+        }
+    }
+
+    fn formula_variables(&self, actor: &CharacterStats) -> HashMap<&'static str, f64> {
+        HashMap::from([
+            ("affinity", f64::from(actor.spiritual_root.affinity)),
+            (
+                "root_multiplier",
+                f64::from(actor.spiritual_root.power_multiplier()),
+            ),
+            ("sub_level", f64::from(actor.cultivation_realm.sub_level)),
+            ("realm_level", f64::from(actor.cultivation_realm.level)),
+            ("combat_power", actor.combat_power as f64),
+            ("age", f64::from(actor.lifespan.current_age)),
+            ("technique_count", actor.techniques.len() as f64),
+            (
+                "technique_multiplier",
+                f64::from(actor.technique_multiplier()),
+            ),
+            ("items", self.item_count as f64),
+            (
+                "difficulty",
+                f64::from(self.realm_rules.breakthrough_difficulty),
+            ),
+        ])
+    }
+
     pub fn calculate_action_result(
         &self,
         actor: &CharacterStats,
@@ -90,15 +701,294 @@ impl NumericalSystem {
                 stat_changes: vec![],
                 events: vec![],
             },
+            Action::LearnTechnique { technique_id } => {
+                self.calculate_learn_technique_result(actor, technique_id)
+            }
+            Action::PracticeTechnique { technique_id } => {
+                self.calculate_practice_technique_result(actor, technique_id)
+            }
+            // 丹药是否在背包中、有何药效由回合结算时依背包判定
+            Action::UseItem { item_id } => ActionResult {
+                success: true,
+                description: format!("你取出{}服下。", item_id),
+                stat_changes: vec![],
+                events: vec![],
+            },
+        }
+    }
+
+    /// 行动失手的风险：突破失败可能反噬，战斗可能负伤败退，修炼可能岔气；
+    /// 灵根、气血、功法、已有伤病、地点灵气与难度都会左右概率
+    pub fn action_risk(
+        &self,
+        actor: &CharacterStats,
+        action: &Action,
+        context: &Context,
+    ) -> Option<ActionRisk> {
+        let affinity = actor.spiritual_root.affinity.clamp(0.0, 1.0);
+        let frailty = 1.0 - self.vitality(actor);
+        let storm = if Self::is_stormy(context) {
+            STORM_MISHAP_CHANCE
+        } else {
+            0.0
+        };
+        let (kind, chance) = match action {
+            Action::Breakthrough => (
+                MishapKind::BreakthroughBacklash,
+                0.5 - 0.3 * affinity + 0.3 * frailty - self.difficulty.breakthrough_modifier,
+            ),
+            Action::Combat { .. } => {
+                let wounded = if actor.status_effects.has(StatusEffectKind::Injured) {
+                    0.15
+                } else {
+                    0.0
+                };
+                (
+                    MishapKind::CombatInjury,
+                    0.2 + 0.3 * frailty + 0.2 * self.difficulty.npc_aggression + wounded + storm
+                        - 0.1 * (actor.technique_multiplier() - 1.0),
+                )
+            }
+            Action::Cultivate => {
+                let deviated = if actor.status_effects.has(StatusEffectKind::QiDeviation) {
+                    0.1
+                } else {
+                    0.0
+                };
+                (
+                    MishapKind::CultivationDeviation,
+                    0.03 + 0.06 * (1.0 - affinity)
+                        + 0.1 * (1.0 - context.spiritual_energy).max(0.0)
+                        + 0.05 * self.difficulty.resource_scarcity
+                        + deviated
+                        + storm,
+                )
+            }
+            _ => return None,
+        };
+        Some(ActionRisk {
+            kind,
+            chance: chance.clamp(0.0, MAX_MISHAP_CHANCE),
+        })
+    }
+
+    /// 掷定行动是否失手；突破只在失败时才可能反噬
+    pub fn roll_mishap(
+        &self,
+        actor: &CharacterStats,
+        action: &Action,
+        context: &Context,
+        result: &ActionResult,
+        rng: &mut GameRng,
+    ) -> Option<MishapKind> {
+        let risk = self.action_risk(actor, action, context)?;
+        if risk.kind == MishapKind::BreakthroughBacklash && result.success {
+            return None;
+        }
+        (rng.range_f32(0.0, 1.0) < risk.chance).then_some(risk.kind)
+    }
+
+    /// 结算失手：折损战力并留下伤病，行动判为失败
+    pub fn suffer_mishap(
+        &self,
+        actor: &mut CharacterStats,
+        kind: MishapKind,
+        result: &mut ActionResult,
+    ) {
+        let loss = (actor.combat_power as f32 * kind.power_loss_ratio()).round() as i64;
+        result
+            .stat_changes
+            .extend(actor.apply_stat_change(StatDelta::CombatPower(-loss)));
+        let (status, event) = match kind {
+            MishapKind::BreakthroughBacklash => {
+                (StatusEffectKind::Injured, "冲关不成，真气反噬，经脉受创")
+            }
+            MishapKind::CombatInjury => (StatusEffectKind::Injured, "激战中身负重伤，只得败退"),
+            MishapKind::CultivationDeviation => {
+                (StatusEffectKind::QiDeviation, "吐纳时岔了气，真气逆行")
+            }
+        };
+        actor
+            .status_effects
+            .apply(status, MISHAP_AFFLICTION_DAYS, kind.label());
+        result.success = false;
+        result.description = format!("{} {}。", result.description, event);
+        result.events.push(event.to_string());
+    }
+
+    /// 气血：步入暮年后随剩余寿元线性衰退，寿元耗尽时降到下限，修炼收益与突破成功率随之打折
+    pub fn vitality(&self, actor: &CharacterStats) -> f32 {
+        let ratio = actor.lifespan.remaining_ratio();
+        if ratio >= TWILIGHT_LIFESPAN_RATIO {
+            return 1.0;
+        }
+        MIN_VITALITY + (1.0 - MIN_VITALITY) * ratio / TWILIGHT_LIFESPAN_RATIO
+    }
+
+    /// 所在之处的修炼效率倍率：以灵气浓度为主，夜深人静、春日略有增益，风雨雷暴、寒冬有损
+    pub fn location_efficiency(&self, context: &Context) -> f32 {
+        let energy = context.spiritual_energy.clamp(
+            MIN_SPIRITUAL_ENERGY_EFFICIENCY,
+            MAX_SPIRITUAL_ENERGY_EFFICIENCY,
+        );
+        let time_of_day = context.time_of_day.to_lowercase();
+        let night = if NIGHT_KEYWORDS.iter().any(|k| time_of_day.contains(k)) {
+            NIGHT_EFFICIENCY_BONUS
+        } else {
+            1.0
+        };
+        let weather = context.weather.as_deref().map(str::to_lowercase);
+        let weather = match weather.as_deref() {
+            Some(w) if STORM_KEYWORDS.iter().any(|k| w.contains(k)) => STORM_EFFICIENCY,
+            Some(w) if RAIN_KEYWORDS.iter().any(|k| w.contains(k)) => RAIN_EFFICIENCY,
+            _ => 1.0,
+        };
+        let season = context.season.as_deref().map(str::to_lowercase);
+        let season = match season.as_deref() {
+            Some(s) if SPRING_KEYWORDS.iter().any(|k| s.contains(k)) => SPRING_EFFICIENCY,
+            Some(s) if WINTER_KEYWORDS.iter().any(|k| s.contains(k)) => WINTER_EFFICIENCY,
+            _ => 1.0,
+        };
+        energy * night * weather * season
+    }
+
+    fn is_stormy(context: &Context) -> bool {
+        context.weather.as_deref().is_some_and(|weather| {
+            let weather = weather.to_lowercase();
+            STORM_KEYWORDS.iter().any(|k| weather.contains(k))
+        })
+    }
+
+    /// 修炼一次增长的战力，已学功法越多越精、所在之处灵气越浓，增长越快
+    pub fn cultivation_power_gain(&self, actor: &CharacterStats, context: &Context) -> i64 {
+        let gain = actor.combat_power as f32
+            * 0.03
+            * actor.technique_multiplier()
+            * self.difficulty.yield_multiplier()
+            * self.vitality(actor)
+            * actor.status_effects.cultivation_multiplier()
+            * self.location_efficiency(context);
+        (gain.round() as i64).max(1)
+    }
+
+    /// 休息一次额外缩短伤病的天数，灵气充沛之地调息更快
+    pub fn rest_recovery_days(&self, context: &Context) -> u32 {
+        ((REST_RECOVERY_DAYS as f32 * self.location_efficiency(context)).round() as u32).max(1)
+    }
+
+    /// 估算在某处修炼、休息的成效，供玩家权衡是否动身前往
+    pub fn survey_location(
+        &self,
+        actor: &CharacterStats,
+        location: &Location,
+        context: &Context,
+        current: bool,
+    ) -> LocationSurvey {
+        LocationSurvey {
+            location_id: location.id.clone(),
+            name: location.name.clone(),
+            spiritual_energy: location.spiritual_energy,
+            weather: context.weather.clone(),
+            efficiency: self.location_efficiency(context),
+            cultivation_gain: self.cultivation_power_gain(actor, context),
+            rest_recovery_days: self.rest_recovery_days(context),
+            current,
+        }
+    }
+
+    /// 勤练一次增长的熟练度，灵根亲和度越高、与功法属性越契合，增长越快
+    pub fn proficiency_gain(&self, actor: &CharacterStats, technique: &LearnedTechnique) -> u32 {
+        let base = 4.0 + actor.spiritual_root.affinity.clamp(0.0, 1.0) * 8.0;
+        ((base * technique.synergy(&actor.spiritual_root.element)).round() as u32).max(1)
+    }
+
+    fn failed_result(description: String) -> ActionResult {
+        ActionResult {
+            success: false,
+            description,
+            stat_changes: vec![],
+            events: vec![],
+        }
+    }
+
+    fn calculate_learn_technique_result(
+        &self,
+        actor: &CharacterStats,
+        technique_id: &str,
+    ) -> ActionResult {
+        let Some(technique) = self.technique(technique_id) else {
+            return Self::failed_result(format!("未找到功法「{}」的传承。", technique_id));
+        };
+        if actor.technique(technique_id).is_some() {
+            return Self::failed_result(format!("你早已习得《{}》。", technique.name));
+        }
+        if technique.required_realm_level > actor.cultivation_realm.level {
+            return Self::failed_result(format!(
+                "《{}》需要第 {} 境界方可修习，你的修为尚浅。",
+                technique.name, technique.required_realm_level
+            ));
+        }
+        if technique.demonic && self.alignment != Alignment::Demonic {
+            return Self::failed_result(format!(
+                "《{}》乃魔道功法，心性未入魔道者无从参悟。",
+                technique.name
+            ));
+        }
+        let synergy = technique
+            .element
+            .as_ref()
+            .map_or(1.0, |element| actor.spiritual_root.element.synergy_with(element));
+        ActionResult {
+            success: true,
+            description: if synergy > 1.0 {
+                format!("你习得了《{}》，功法与灵根相合，运转如意。", technique.name)
+            } else if synergy < 1.0 {
+                format!("你习得了《{}》，只是功法与灵根相克，修习颇为艰涩。", technique.name)
+            } else {
+                format!("你习得了《{}》。", technique.name)
+            },
+            stat_changes: vec![],
+            events: vec![format!("习得功法《{}》", technique.name)],
+        }
+    }
+
+    fn calculate_practice_technique_result(
+        &self,
+        actor: &CharacterStats,
+        technique_id: &str,
+    ) -> ActionResult {
+        let Some(technique) = actor.technique(technique_id) else {
+            return Self::failed_result(format!("你尚未习得功法「{}」。", technique_id));
+        };
+        if technique.is_mastered() {
+            return Self::failed_result(format!("《{}》已臻圆满，再练也难有寸进。", technique.name));
+        }
+        ActionResult {
+            success: true,
+            description: format!(
+                "你勤练《{}》，熟练度提升了 {}。",
+                technique.name,
+                self.proficiency_gain(actor, technique)
+            ),
+            stat_changes: vec![],
+            events: vec![],
         }
     }
 
     fn calculate_cultivation_result(
         &self,
         actor: &CharacterStats,
-        _context: &Context,
+        context: &Context,
     ) -> ActionResult {
-        let progress = actor.spiritual_root.affinity * 10.0;
+        let default_progress = actor.spiritual_root.affinity * 10.0 * actor.technique_multiplier();
+        let progress = self
+            .evaluate_formula("cultivation_progress", self.formulas.cultivation_progress.as_ref(), actor)
+            .map(|v| v as f32)
+            .unwrap_or(default_progress)
+            * self.difficulty.yield_multiplier()
+            * self.vitality(actor)
+            * actor.status_effects.cultivation_multiplier()
+            * self.location_efficiency(context);
         ActionResult {
             success: true,
             description: format!("修炼成功，修行进度提升至 {:.1}%", progress),
@@ -107,17 +997,74 @@ impl NumericalSystem {
         }
     }
 
-    fn calculate_breakthrough_result(&self, actor: &CharacterStats) -> ActionResult {
-        let success_chance =
+    /// 悟性带来的突破成功率加成，随积累线性增长
+    pub fn insight_bonus(&self, actor: &CharacterStats) -> f32 {
+        actor.insight.min(MAX_INSIGHT) as f32 / MAX_INSIGHT as f32 * MAX_INSIGHT_BONUS
+    }
+
+    /// 一次行动积累的悟性：修炼与战斗、自行探索等历练
+    pub fn insight_gain(&self, action: &Action) -> u32 {
+        match action {
+            Action::Cultivate => INSIGHT_PER_CULTIVATION,
+            Action::Combat { .. } | Action::Custom { .. } => INSIGHT_PER_ADVENTURE,
+            _ => 0,
+        }
+    }
+
+    /// 突破成功率：剧本公式或灵根亲和度，按气血、悟性与难度修正
+    pub fn breakthrough_chance(&self, actor: &CharacterStats) -> f32 {
+        let default_chance =
             actor.spiritual_root.affinity * (1.0 - self.realm_rules.breakthrough_difficulty);
-        let success = success_chance > 0.3;
+        let success_chance = self
+            .evaluate_formula("breakthrough_chance", self.formulas.breakthrough_chance.as_ref(), actor)
+            .map(|v| v.clamp(0.0, 1.0) as f32)
+            .unwrap_or(default_chance);
+        (success_chance * self.vitality(actor)
+            + self.insight_bonus(actor)
+            + self.difficulty.breakthrough_modifier
+            + actor.status_effects.breakthrough_modifier())
+        .clamp(0.0, 1.0)
+    }
+
+    /// 圆满期冲击下一大境界时战力未达剧本要求，返回阻碍突破的原因
+    pub fn breakthrough_blocker(&self, actor: &CharacterStats) -> Option<String> {
+        let realm = &actor.cultivation_realm;
+        if !self.realm_ladder.is_peak(realm) {
+            return None;
+        }
+        let target = self.realm_ladder.next_major(realm)?;
+        let required = self.realm_ladder.min_combat_power(target.level);
+        (actor.combat_power < required)
+            .then(|| format!("战力不足 {}，根基未稳，无法冲击{}。", required, target.name))
+    }
+
+    /// 预估突破结果：成功率高于三成视为可成，实际成败在回合结算时按成功率掷定
+    fn calculate_breakthrough_result(&self, actor: &CharacterStats) -> ActionResult {
+        let blocker = self.breakthrough_blocker(actor);
+        let success = blocker.is_none() && self.breakthrough_chance(actor) > 0.3;
+        self.breakthrough_result(actor, success, blocker)
+    }
+
+    /// 冲击瓶颈：按成功率掷骰决定成败，战力未达要求时必定失败
+    pub fn attempt_breakthrough(&self, actor: &CharacterStats, rng: &mut GameRng) -> ActionResult {
+        let blocker = self.breakthrough_blocker(actor);
+        let success =
+            blocker.is_none() && rng.range_f32(0.0, 1.0) < self.breakthrough_chance(actor);
+        self.breakthrough_result(actor, success, blocker)
+    }
 
+    fn breakthrough_result(
+        &self,
+        actor: &CharacterStats,
+        success: bool,
+        blocker: Option<String>,
+    ) -> ActionResult {
         ActionResult {
             success,
             description: if success {
                 format!("突破成功，你已触及 {} 的更高层次！", actor.cultivation_realm.name)
             } else {
-                "突破失败，积累仍不足，需要继续修炼。".to_string()
+                blocker.unwrap_or_else(|| "突破失败，积累仍不足，需要继续修炼。".to_string())
             },
             stat_changes: vec![],
             events: if success {
@@ -135,21 +1082,78 @@ impl NumericalSystem {
     ) -> bool {
         let current = &character.cultivation_realm;
         
+        if self.realm_rules.allow_cross_realm_feats {
+            return (target_realm.level, target_realm.sub_level) > (current.level, current.sub_level);
+        }
         if target_realm.level == current.level {
-            target_realm.sub_level == current.sub_level + 1 && target_realm.sub_level <= 3
+            target_realm.sub_level == current.sub_level + 1
+                && target_realm.sub_level <= self.realm_ladder.peak_sub_level(current.level)
         } else if target_realm.level == current.level + 1 {
-            current.sub_level == 3 && target_realm.sub_level == 0
+            self.realm_ladder.is_peak(current) && target_realm.sub_level == 0
         } else {
             false
         }
     }
 
+    /// 天劫某一关的成功率：雷劫看当前境界倍率与下一境界之比，心魔劫看灵根亲和度，均受难度修正
+    pub fn tribulation_chance(
+        &self,
+        actor: &CharacterStats,
+        target_realm: &CultivationRealm,
+        kind: TribulationStageKind,
+    ) -> f32 {
+        let chance = match kind {
+            TribulationStageKind::Lightning => {
+                let readiness = actor.cultivation_realm.power_multiplier
+                    / target_realm.power_multiplier.max(0.1);
+                (0.3 + 0.6 * readiness.min(1.0)) * actor.technique_multiplier()
+            }
+            TribulationStageKind::InnerDemon => {
+                0.45 + 0.45 * actor.spiritual_root.affinity.clamp(0.0, 1.0)
+            }
+        };
+        (chance + self.difficulty.breakthrough_modifier)
+            .clamp(MIN_TRIBULATION_CHANCE, MAX_TRIBULATION_CHANCE)
+    }
+
+    /// 依次闯过天劫各关，任一关失败即告终止；只判定成败，不修改角色属性
+    pub fn run_tribulation(
+        &self,
+        actor: &CharacterStats,
+        target_realm: &CultivationRealm,
+        rng: &mut GameRng,
+    ) -> TribulationOutcome {
+        let mut stages = Vec::new();
+        for kind in TribulationStageKind::ALL {
+            let chance = self.tribulation_chance(actor, target_realm, kind);
+            let passed = rng.range_f32(0.0, 1.0) < chance;
+            stages.push(TribulationStage {
+                kind,
+                chance,
+                passed,
+            });
+            if !passed {
+                break;
+            }
+        }
+        TribulationOutcome {
+            target_realm: CultivationRealm {
+                sub_level: 0,
+                ..target_realm.clone()
+            },
+            passed: stages.iter().all(|stage| stage.passed),
+            stages,
+            lifespan_bonus: self.realm_ladder.lifespan_bonus(target_realm.level),
+        }
+    }
+
     pub fn calculate_combat_outcome(
         &self,
         attacker: &CharacterStats,
         defender: &CharacterStats,
     ) -> CombatResult {
-        let power_diff = attacker.combat_power as i64 - defender.combat_power as i64;
+        let power_diff = self.effective_combat_power(attacker, defender) as i64
+            - self.effective_combat_power(defender, attacker) as i64;
         
         let (winner_id, loser_id, damage) = if power_diff > 0 {
             ("attacker".to_string(), "defender".to_string(), (power_diff / 10) as u32)
@@ -168,8 +1172,25 @@ impl NumericalSystem {
         }
     }
 
-    pub fn update_lifespan(&self, character: &mut CharacterStats, time_passed: u32) {
-        character.lifespan.current_age += time_passed;
+    /// 按伤病等状态折算战力；允许越阶时，低境界一方按双方境界倍率之比补足战力
+    fn effective_combat_power(&self, actor: &CharacterStats, opponent: &CharacterStats) -> u64 {
+        let actor_realm = &actor.cultivation_realm;
+        let opponent_realm = &opponent.cultivation_realm;
+        let power = actor.fighting_power();
+        if !self.realm_rules.allow_cross_realm_feats || opponent_realm.level <= actor_realm.level {
+            return power;
+        }
+        let ratio = opponent_realm.power_multiplier.max(0.1) / actor_realm.power_multiplier.max(0.1);
+        (power as f32 * ratio.max(1.0)) as u64
+    }
+
+    /// 岁月流逝，按难度的寿元压力增长年岁，返回实际发生的变化
+    pub fn update_lifespan(
+        &self,
+        character: &mut CharacterStats,
+        time_passed: u32,
+    ) -> Option<StatChange> {
+        character.apply_stat_change(StatDelta::Age(self.difficulty.aging_years(time_passed)))
     }
 
     pub fn calculate_initial_combat_power(
@@ -179,12 +1200,7 @@ impl NumericalSystem {
     ) -> u64 {
         let base_power = 100.0f32;
         let affinity_multiplier = spiritual_root.affinity.max(0.1);
-        let grade_multiplier = match spiritual_root.grade {
-            Grade::Heavenly => 3.0,
-            Grade::Double => 2.0,
-            Grade::Triple => 1.5,
-            Grade::Pseudo => 1.0,
-        };
+        let grade_multiplier = spiritual_root.power_multiplier();
         let realm_multiplier = realm.power_multiplier.max(0.1);
 
         (base_power * affinity_multiplier * grade_multiplier * realm_multiplier) as u64
@@ -194,19 +1210,31 @@ impl NumericalSystem {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::{Element, Grade, Lifespan, SpiritualRoot};
+    use crate::game_state::ItemType;
+    use crate::models::{Element, Grade, Lifespan, SpiritualRoot, MAX_TECHNIQUE_PROFICIENCY};
 
     fn create_test_character() -> CharacterStats {
         let spiritual_root = SpiritualRoot {
             element: Element::Fire,
-            grade: Grade::Heavenly,
+            grade: Grade::heavenly(),
             affinity: 0.8,
+            tier_multiplier: None,
         };
         let realm = CultivationRealm::new("Qi Condensation".to_string(), 1, 0, 1.0);
         let lifespan = Lifespan::new(20, 100, 50);
         CharacterStats::new(spiritual_root, realm, lifespan)
     }
 
+    fn ordinary_context() -> Context {
+        Context {
+            location: "Cave".to_string(),
+            time_of_day: "Day".to_string(),
+            weather: None,
+            season: None,
+            spiritual_energy: 1.0,
+        }
+    }
+
     #[test]
     fn test_calculate_action_result_cultivate() {
         let system = NumericalSystem::new();
@@ -215,6 +1243,8 @@ mod tests {
             location: "Cave".to_string(),
             time_of_day: "Night".to_string(),
             weather: None,
+            season: None,
+            spiritual_energy: 1.0,
         };
 
         let result = system.calculate_action_result(&character, &Action::Cultivate, &context);
@@ -230,6 +1260,8 @@ mod tests {
             location: "Sect".to_string(),
             time_of_day: "Day".to_string(),
             weather: None,
+            season: None,
+            spiritual_energy: 1.0,
         };
 
         let result = system.calculate_action_result(&character, &Action::Cultivate, &context);
@@ -244,6 +1276,8 @@ mod tests {
             location: "Sect".to_string(),
             time_of_day: "Day".to_string(),
             weather: None,
+            season: None,
+            spiritual_energy: 1.0,
         };
 
         let result = system.calculate_action_result(&character, &Action::Rest, &context);
@@ -259,6 +1293,8 @@ mod tests {
             location: "Sect".to_string(),
             time_of_day: "Day".to_string(),
             weather: None,
+            season: None,
+            spiritual_energy: 1.0,
         };
 
         let result = system.calculate_action_result(
@@ -280,6 +1316,8 @@ mod tests {
             location: "Arena".to_string(),
             time_of_day: "Noon".to_string(),
             weather: Some("Sunny".to_string()),
+            season: None,
+            spiritual_energy: 1.0,
         };
 
         let result = system.calculate_action_result(
@@ -329,6 +1367,29 @@ mod tests {
         assert!(result.damage_dealt > 0);
     }
 
+    #[test]
+    fn test_cross_realm_house_rule_lifts_realm_gap() {
+        let mut attacker = create_test_character();
+        attacker.combat_power = 100;
+        let mut defender = create_test_character();
+        defender.cultivation_realm = CultivationRealm::new("Foundation".to_string(), 2, 0, 2.0);
+        defender.combat_power = 150;
+
+        let standard = NumericalSystem::new();
+        assert_eq!(standard.calculate_combat_outcome(&attacker, &defender).winner_id, "defender");
+        let skipped = CultivationRealm::new("Core".to_string(), 3, 0, 4.0);
+        assert!(!standard.validate_realm_breakthrough(&attacker, &skipped));
+
+        let rules = HouseRules {
+            allow_cross_realm_feats: true,
+            ..HouseRules::default()
+        };
+        let relaxed = NumericalSystem::new().with_house_rules(&rules);
+        assert_eq!(relaxed.calculate_combat_outcome(&attacker, &defender).winner_id, "attacker");
+        assert!(relaxed.validate_realm_breakthrough(&attacker, &skipped));
+        assert!(!relaxed.validate_realm_breakthrough(&attacker, &attacker.cultivation_realm));
+    }
+
     #[test]
     fn test_calculate_combat_outcome_defender_wins() {
         let system = NumericalSystem::new();
@@ -364,6 +1425,96 @@ mod tests {
 
         system.update_lifespan(&mut character, 10);
         assert_eq!(character.lifespan.current_age, initial_age + 10);
+
+        let hard = NumericalSystem::new().with_difficulty(&DifficultySettings::hard());
+        let change = hard.update_lifespan(&mut character, 10).unwrap();
+        assert_eq!(character.lifespan.current_age, initial_age + 25);
+        assert_eq!(change.new_value, (initial_age + 25).to_string());
+    }
+
+    #[test]
+    fn test_twilight_years_drain_vitality() {
+        let system = NumericalSystem::new();
+        let mut character = create_test_character();
+        character.combat_power = 1000;
+        assert_eq!(system.vitality(&character), 1.0);
+        let prime_gain = system.cultivation_power_gain(&character, &ordinary_context());
+
+        character.lifespan.current_age = character.lifespan.total_max_age() - 15;
+        assert!((system.vitality(&character) - 0.75).abs() < 1e-6);
+        assert!(system.cultivation_power_gain(&character, &ordinary_context()) < prime_gain);
+
+        character.lifespan.current_age = character.lifespan.total_max_age();
+        assert_eq!(system.vitality(&character), MIN_VITALITY);
+    }
+
+    #[test]
+    fn test_resource_costs_earnings_and_market_prices() {
+        let thunder_law = Technique {
+            id: "thunder_law".to_string(),
+            name: "雷法".to_string(),
+            description: String::new(),
+            required_realm_level: 3,
+            element: None,
+            demonic: false,
+        };
+        let system = NumericalSystem::new().with_techniques(&[thunder_law]);
+        let mut character = create_test_character();
+
+        assert_eq!(system.action_cost(&character, &Action::Breakthrough).spirit_stones, -10);
+        character.cultivation_realm.sub_level = PEAK_SUB_LEVEL;
+        let peak = system.action_cost(&character, &Action::Breakthrough);
+        assert_eq!((peak.spirit_stones, peak.herbs), (-40, -1));
+        let learn = Action::LearnTechnique {
+            technique_id: "thunder_law".to_string(),
+        };
+        assert_eq!(system.action_cost(&character, &learn).spirit_stones, -90);
+        assert!(system.action_cost(&character, &Action::Cultivate).is_empty());
+
+        let combat = Action::Combat {
+            target_id: "wolf".to_string(),
+        };
+        assert_eq!(system.action_earnings(&character, &combat).spirit_stones, 15);
+        let hard = NumericalSystem::new().with_difficulty(&DifficultySettings::hard());
+        assert_eq!(hard.action_earnings(&character, &combat).spirit_stones, 11);
+
+        let buy = system.trade_delta(ResourceKind::Herbs, 4, true).unwrap();
+        assert_eq!((buy.herbs, buy.spirit_stones), (4, -20));
+        let sell = hard.trade_delta(ResourceKind::Ores, 2, false).unwrap();
+        assert_eq!((sell.ores, sell.spirit_stones), (-2, 12));
+        assert!(system.trade_delta(ResourceKind::SpiritStones, 1, true).is_none());
+    }
+
+    #[test]
+    fn test_difficulty_shifts_breakthrough_and_cultivation_yield() {
+        let mut character = create_test_character();
+        character.spiritual_root.affinity = 0.7;
+        let context = Context {
+            location: "Cave".to_string(),
+            time_of_day: "Night".to_string(),
+            weather: None,
+            season: None,
+            spiritual_energy: 1.0,
+        };
+        let normal = NumericalSystem::new();
+        let easy = NumericalSystem::new().with_difficulty(&DifficultySettings::easy());
+        let hard = NumericalSystem::new().with_difficulty(&DifficultySettings::hard());
+
+        // 亲和度 0.7 的默认成功率为 0.35，困难难度下跌破 0.3 的门槛
+        assert!(normal.calculate_action_result(&character, &Action::Breakthrough, &context).success);
+        assert!(easy.calculate_action_result(&character, &Action::Breakthrough, &context).success);
+        assert!(!hard.calculate_action_result(&character, &Action::Breakthrough, &context).success);
+
+        assert!(
+            hard.cultivation_power_gain(&character, &context)
+                < normal.cultivation_power_gain(&character, &context)
+        );
+        let progress = |system: &NumericalSystem| {
+            system
+                .calculate_action_result(&character, &Action::Cultivate, &context)
+                .description
+        };
+        assert_ne!(progress(&normal), progress(&hard));
     }
 
     #[test]
@@ -373,23 +1524,27 @@ mod tests {
 
         let heavenly = SpiritualRoot {
             element: Element::Fire,
-            grade: Grade::Heavenly,
+            grade: Grade::heavenly(),
             affinity: 0.8,
+            tier_multiplier: None,
         };
         let double = SpiritualRoot {
             element: Element::Fire,
-            grade: Grade::Double,
+            grade: Grade::double(),
             affinity: 0.8,
+            tier_multiplier: None,
         };
         let triple = SpiritualRoot {
             element: Element::Fire,
-            grade: Grade::Triple,
+            grade: Grade::triple(),
             affinity: 0.8,
+            tier_multiplier: None,
         };
         let pseudo = SpiritualRoot {
             element: Element::Fire,
-            grade: Grade::Pseudo,
+            grade: Grade::pseudo(),
             affinity: 0.8,
+            tier_multiplier: None,
         };
 
         let p_heavenly = system.calculate_initial_combat_power(&heavenly, &realm);
@@ -434,6 +1589,8 @@ mod tests {
                 location: "Cave".to_string(),
                 time_of_day: "Night".to_string(),
                 weather: None,
+                season: None,
+                spiritual_energy: 1.0,
             },
         );
 
@@ -454,6 +1611,8 @@ mod tests {
                 location: "Peak".to_string(),
                 time_of_day: "Dawn".to_string(),
                 weather: None,
+                season: None,
+                spiritual_energy: 1.0,
             },
         );
 
@@ -461,13 +1620,86 @@ mod tests {
         assert!(result.description.contains("突破成功"));
     }
 
+    #[test]
+    fn test_breakthrough_uses_script_formula() {
+        let config = NumericalConfig {
+            breakthrough_chance: Some("if(sub_level < 3, affinity, 0)".to_string()),
+            cultivation_progress: Some("affinity * 20 + technique_count".to_string()),
+        };
+        let system = NumericalSystem::with_config(&config).unwrap();
+        let mut character = create_test_character();
+        character.spiritual_root.affinity = 0.5;
+        let context = ordinary_context();
+
+        // 默认规则下 0.5 * 0.5 = 0.25 会失败，公式给出 0.5 则成功。
+        let result = system.calculate_action_result(&character, &Action::Breakthrough, &context);
+        assert!(result.success);
+
+        let result = system.calculate_action_result(&character, &Action::Cultivate, &context);
+        assert!(result.description.contains("10.0%"));
+    }
+
+    #[test]
+    fn test_formula_reads_items_and_reports_failures() {
+        let config = NumericalConfig {
+            breakthrough_chance: Some("affinity / items".to_string()),
+            cultivation_progress: None,
+        };
+        let system = NumericalSystem::with_config(&config).unwrap();
+        let mut character = create_test_character();
+        character.spiritual_root.affinity = 0.8;
+
+        // 背包为空时除以零，回退到内置规则并留下记录
+        assert_eq!(
+            system.breakthrough_chance(&character),
+            NumericalSystem::new().breakthrough_chance(&character)
+        );
+        let failures = system.take_formula_failures();
+        assert_eq!(failures.len(), 1);
+        assert!(failures[0].contains("breakthrough_chance"));
+        assert!(system.take_formula_failures().is_empty());
+
+        let pill = Item {
+            id: "pill".to_string(),
+            name: "筑基丹".to_string(),
+            description: String::new(),
+            item_type: ItemType::Medicine,
+            lifespan_bonus: 0,
+        };
+        let system = system.with_inventory(&[pill.clone(), pill]);
+        let halved = NumericalSystem::with_config(&NumericalConfig {
+            breakthrough_chance: Some("affinity / 2".to_string()),
+            cultivation_progress: None,
+        })
+        .unwrap();
+        assert_eq!(
+            system.breakthrough_chance(&character),
+            halved.breakthrough_chance(&character)
+        );
+        assert!(system.take_formula_failures().is_empty());
+    }
+
+    #[test]
+    fn test_with_config_rejects_non_whitelisted_variable() {
+        let config = NumericalConfig {
+            breakthrough_chance: None,
+            cultivation_progress: Some("difficulty * 2".to_string()),
+        };
+        assert!(matches!(
+            NumericalSystem::with_config(&config),
+            Err(FormulaError::UnknownVariable(_))
+        ));
+        assert!(NumericalSystem::validate_config(&NumericalConfig::default()).is_ok());
+    }
+
     #[test]
     fn test_calculate_initial_combat_power_applies_minimum_clamps() {
         let system = NumericalSystem::new();
         let spiritual_root = SpiritualRoot {
             element: Element::Earth,
-            grade: Grade::Pseudo,
+            grade: Grade::pseudo(),
             affinity: 0.0,
+            tier_multiplier: None,
         };
         let realm = CultivationRealm::new("Weak Realm".to_string(), 1, 0, 0.0);
 
@@ -476,6 +1708,388 @@ mod tests {
         // base(100) * affinity_min(0.1) * pseudo(1.0) * realm_min(0.1) = 1
         assert_eq!(power, 1);
     }
+
+    #[test]
+    fn test_learn_and_practice_technique_respect_realm_and_synergy() {
+        let technique = |id: &str, level: u32, element: Element| Technique {
+            id: id.to_string(),
+            name: id.to_string(),
+            description: String::new(),
+            required_realm_level: level,
+            element: Some(element),
+            demonic: false,
+        };
+        let system = NumericalSystem::new().with_techniques(&[
+            technique("fire_palm", 1, Element::Fire),
+            technique("water_shield", 1, Element::Water),
+            technique("thunder_law", 3, Element::Thunder),
+        ]);
+        let context = Context {
+            location: "sect".to_string(),
+            time_of_day: "day".to_string(),
+            weather: None,
+            season: None,
+            spiritual_energy: 1.0,
+        };
+        let mut character = create_test_character();
+        let learn = |id: &str| Action::LearnTechnique {
+            technique_id: id.to_string(),
+        };
+
+        assert_eq!(
+            system
+                .learnable_techniques(&character)
+                .map(|t| t.id.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["fire_palm", "water_shield"]
+        );
+        assert!(!system.calculate_action_result(&character, &learn("thunder_law"), &context).success);
+        assert!(!system.calculate_action_result(&character, &learn("missing"), &context).success);
+        let result = system.calculate_action_result(&character, &learn("fire_palm"), &context);
+        assert!(result.success);
+        assert!(result.description.contains("相合"));
+
+        for id in ["fire_palm", "water_shield"] {
+            character.learn_technique(system.technique(id).unwrap().learned());
+        }
+        assert!(!system.calculate_action_result(&character, &learn("fire_palm"), &context).success);
+        // 火灵根修习火系功法快于被克制的水系功法
+        assert!(
+            system.proficiency_gain(&character, &character.techniques[0])
+                > system.proficiency_gain(&character, &character.techniques[1])
+        );
+        let practice = Action::PracticeTechnique {
+            technique_id: "fire_palm".to_string(),
+        };
+        assert!(system.calculate_action_result(&character, &practice, &context).success);
+        character.practice_technique("fire_palm", MAX_TECHNIQUE_PROFICIENCY);
+        assert!(!system.calculate_action_result(&character, &practice, &context).success);
+        assert!(system.cultivation_power_gain(&character, &ordinary_context()) > 0);
+    }
+
+    #[test]
+    fn test_demonic_technique_unlocks_only_for_demonic_alignment() {
+        let blood_art = Technique {
+            id: "blood_art".to_string(),
+            name: "血河大法".to_string(),
+            description: String::new(),
+            required_realm_level: 1,
+            element: None,
+            demonic: true,
+        };
+        let context = Context {
+            location: "sect".to_string(),
+            time_of_day: "day".to_string(),
+            weather: None,
+            season: None,
+            spiritual_energy: 1.0,
+        };
+        let character = create_test_character();
+        let learn = Action::LearnTechnique {
+            technique_id: "blood_art".to_string(),
+        };
+
+        let system = NumericalSystem::new().with_techniques(std::slice::from_ref(&blood_art));
+        assert_eq!(system.learnable_techniques(&character).count(), 0);
+        let refused = system.calculate_action_result(&character, &learn, &context);
+        assert!(!refused.success);
+        assert!(refused.description.contains("魔道功法"));
+
+        let system = system.with_alignment(Alignment::Demonic);
+        assert_eq!(system.learnable_techniques(&character).count(), 1);
+        assert!(system.calculate_action_result(&character, &learn, &context).success);
+    }
+
+    #[test]
+    fn test_tribulation_stops_at_first_failure_and_applies_consequences() {
+        let system = NumericalSystem::new();
+        let mut character = create_test_character();
+        character.cultivation_realm.sub_level = PEAK_SUB_LEVEL;
+        character.cultivation_realm.power_multiplier = 1.2f32.powi(3);
+        character.update_combat_power();
+        let realms = vec![
+            CultivationRealm::new("Qi Condensation".to_string(), 1, 0, 1.0),
+            CultivationRealm::new("Core Formation".to_string(), 3, 0, 4.0),
+            CultivationRealm::new("Foundation".to_string(), 2, 0, 2.0),
+        ];
+        let target = next_major_realm(&realms, &character.cultivation_realm).unwrap();
+        assert_eq!(target.name, "Foundation");
+        assert!(next_major_realm(&realms[1..2], &realms[1]).is_none());
+
+        let hard = system.with_difficulty(&DifficultySettings::hard());
+        for kind in TribulationStageKind::ALL {
+            assert!(
+                hard.tribulation_chance(&character, target, kind)
+                    < NumericalSystem::new().tribulation_chance(&character, target, kind)
+            );
+        }
+
+        let mut rng = GameRng::new(7);
+        for _ in 0..20 {
+            let outcome = NumericalSystem::new().run_tribulation(&character, target, &mut rng);
+            assert_eq!(outcome.target_realm.sub_level, 0);
+            assert_eq!(outcome.lifespan_bonus, 2 * TRIBULATION_LIFESPAN_PER_LEVEL);
+            assert_eq!(outcome.passed, outcome.failed_stage().is_none());
+            assert!(outcome.stages.iter().rev().skip(1).all(|stage| stage.passed));
+            if outcome.passed {
+                assert_eq!(outcome.stages.len(), TribulationStageKind::ALL.len());
+            }
+        }
+
+        let stage = |kind, passed| TribulationStage {
+            kind,
+            chance: 0.5,
+            passed,
+        };
+        let passed = TribulationOutcome {
+            target_realm: target.clone(),
+            stages: vec![
+                stage(TribulationStageKind::Lightning, true),
+                stage(TribulationStageKind::InnerDemon, true),
+            ],
+            passed: true,
+            lifespan_bonus: 2 * TRIBULATION_LIFESPAN_PER_LEVEL,
+        };
+        let mut ascended = character.clone();
+        passed.apply(&mut ascended);
+        assert_eq!(ascended.cultivation_realm.level, 2);
+        assert_eq!(ascended.lifespan.realm_bonus, 50 + 2 * TRIBULATION_LIFESPAN_PER_LEVEL);
+        assert!(ascended.combat_power > character.combat_power);
+
+        let struck = TribulationOutcome {
+            stages: vec![stage(TribulationStageKind::Lightning, false)],
+            passed: false,
+            ..passed.clone()
+        };
+        let mut injured = character.clone();
+        injured.combat_power *= 2;
+        struck.apply(&mut injured);
+        assert!(injured.combat_power < character.combat_power * 2);
+        assert_eq!(injured.lifespan.current_age, 20 + LIGHTNING_INJURY_YEARS);
+        assert!(struck.summary().contains("雷劫"));
+
+        let tempted = TribulationOutcome {
+            stages: vec![
+                stage(TribulationStageKind::Lightning, true),
+                stage(TribulationStageKind::InnerDemon, false),
+            ],
+            passed: false,
+            ..passed
+        };
+        let mut regressed = character.clone();
+        tempted.apply(&mut regressed);
+        assert_eq!(regressed.cultivation_realm.sub_level, PEAK_SUB_LEVEL - 1);
+        assert!(regressed.combat_power < character.combat_power);
+    }
+
+    #[test]
+    fn test_insight_raises_breakthrough_chance_and_comes_from_notable_events() {
+        let system = NumericalSystem::new();
+        let mut character = create_test_character();
+        character.spiritual_root.affinity = 0.4;
+        let base = system.breakthrough_chance(&character);
+        assert!(!system.calculate_breakthrough_result(&character).success);
+
+        character.gain_insight(MAX_INSIGHT + 10);
+        assert_eq!(character.insight, MAX_INSIGHT);
+        assert!((system.breakthrough_chance(&character) - base - MAX_INSIGHT_BONUS).abs() < 1e-6);
+        assert!(system.calculate_breakthrough_result(&character).success);
+        let mut rng = GameRng::new(11);
+        let successes = (0..200)
+            .filter(|_| system.attempt_breakthrough(&character, &mut rng).success)
+            .count();
+        assert!((100..200).contains(&successes));
+
+        assert_eq!(system.insight_gain(&Action::Cultivate), INSIGHT_PER_CULTIVATION);
+        assert_eq!(system.insight_gain(&Action::Rest), 0);
+        let events = vec![
+            "观瀑布而顿悟剑意".to_string(),
+            "在 山谷 发生了一场战斗。".to_string(),
+            "得了一桩机缘".to_string(),
+        ];
+        assert_eq!(insight_from_events(&events), 2 * INSIGHT_PER_NOTABLE_EVENT);
+    }
+
+    #[test]
+    fn test_location_time_and_weather_shape_cultivation_and_rest() {
+        let system = NumericalSystem::new();
+        let mut character = create_test_character();
+        character.combat_power = 1000;
+        let ordinary = ordinary_context();
+        let rich = Context {
+            spiritual_energy: 2.0,
+            ..ordinary_context()
+        };
+        let barren = Context {
+            spiritual_energy: 0.0,
+            ..ordinary_context()
+        };
+        assert_eq!(system.location_efficiency(&ordinary), 1.0);
+        assert_eq!(system.location_efficiency(&barren), MIN_SPIRITUAL_ENERGY_EFFICIENCY);
+        assert!(
+            system.cultivation_power_gain(&character, &rich)
+                > system.cultivation_power_gain(&character, &ordinary)
+        );
+        assert!(system.rest_recovery_days(&rich) > REST_RECOVERY_DAYS);
+        assert_eq!(system.rest_recovery_days(&barren), 1);
+
+        let night = Context {
+            time_of_day: "Night".to_string(),
+            ..ordinary_context()
+        };
+        let storm = Context {
+            weather: Some("Thunderstorm".to_string()),
+            season: None,
+            ..ordinary_context()
+        };
+        let winter = Context {
+            season: Some("冬".to_string()),
+            ..ordinary_context()
+        };
+        assert!(system.location_efficiency(&night) > 1.0);
+        assert!(system.location_efficiency(&storm) < 1.0);
+        assert!(system.location_efficiency(&winter) < 1.0);
+        let cultivation_risk = |context: &Context| {
+            system
+                .action_risk(&character, &Action::Cultivate, context)
+                .unwrap()
+                .chance
+        };
+        assert!(cultivation_risk(&storm) > cultivation_risk(&ordinary));
+        let progress = |context: &Context| {
+            system
+                .calculate_action_result(&character, &Action::Cultivate, context)
+                .description
+        };
+        assert_ne!(progress(&rich), progress(&ordinary));
+
+        let survey = system.survey_location(
+            &character,
+            &Location {
+                id: "peak".to_string(),
+                name: "灵峰".to_string(),
+                description: String::new(),
+                spiritual_energy: 2.0,
+                kind: Default::default(),
+                climate: Default::default(),
+            },
+            &rich,
+            false,
+        );
+        assert_eq!(survey.efficiency, 2.0);
+        assert_eq!(
+            survey.cultivation_gain,
+            system.cultivation_power_gain(&character, &rich)
+        );
+        assert!(!survey.current);
+    }
+
+    #[test]
+    fn test_action_risks_follow_stats_location_and_difficulty() {
+        let system = NumericalSystem::new();
+        let mut character = create_test_character();
+        let rich = Context {
+            location: "Cave".to_string(),
+            time_of_day: "Night".to_string(),
+            weather: None,
+            season: None,
+            spiritual_energy: 1.5,
+        };
+        let barren = Context {
+            spiritual_energy: 0.2,
+            ..rich.clone()
+        };
+        let cultivate = |system: &NumericalSystem, actor: &CharacterStats, context: &Context| {
+            system
+                .action_risk(actor, &Action::Cultivate, context)
+                .unwrap()
+                .chance
+        };
+        assert!(cultivate(&system, &character, &barren) > cultivate(&system, &character, &rich));
+        let hard = NumericalSystem::new().with_difficulty(&DifficultySettings::hard());
+        assert!(cultivate(&hard, &character, &rich) > cultivate(&system, &character, &rich));
+        assert!(system.action_risk(&character, &Action::Rest, &rich).is_none());
+
+        let succeeded = system.calculate_action_result(&character, &Action::Breakthrough, &rich);
+        assert!(succeeded.success);
+        let mut rng = GameRng::new(5);
+        assert!((0..50).all(|_| system
+            .roll_mishap(&character, &Action::Breakthrough, &rich, &succeeded, &mut rng)
+            .is_none()));
+
+        let combat = Action::Combat {
+            target_id: "wolf".to_string(),
+        };
+        let fresh = system.action_risk(&character, &combat, &rich).unwrap();
+        assert_eq!(fresh.kind, MishapKind::CombatInjury);
+        character
+            .status_effects
+            .apply(StatusEffectKind::Injured, 3, "旧伤");
+        assert!(system.action_risk(&character, &combat, &rich).unwrap().chance > fresh.chance);
+
+        let mut result = system.calculate_action_result(&character, &combat, &rich);
+        character.combat_power *= 2;
+        let before = character.combat_power;
+        system.suffer_mishap(&mut character, MishapKind::CombatInjury, &mut result);
+        assert!(!result.success);
+        assert!(character.combat_power < before);
+        assert!(result.stat_changes.iter().any(|c| c.stat_name == "combat_power"));
+        assert_eq!(result.events.last().unwrap(), "激战中身负重伤，只得败退");
+    }
+
+    #[test]
+    fn test_realm_ladder_defines_stages_and_gates_major_breakthroughs() {
+        let mut world = WorldSetting::new();
+        world.cultivation_realms = vec![
+            CultivationRealm::new("练气".to_string(), 1, 0, 1.0),
+            CultivationRealm::new("筑基".to_string(), 2, 0, 2.0),
+        ];
+        world.realm_ladder = vec![
+            RealmStages {
+                level: 1,
+                stages: 9,
+                stage_names: Vec::new(),
+                min_combat_power: 0,
+                lifespan_bonus: None,
+            },
+            RealmStages {
+                level: 2,
+                stages: 3,
+                stage_names: vec!["初期".to_string(), "中期".to_string(), "后期".to_string()],
+                min_combat_power: 1_000_000,
+                lifespan_bonus: Some(100),
+            },
+        ];
+        let system = NumericalSystem::new().with_realm_ladder(&world);
+        let ladder = system.realm_ladder();
+        let mut character = create_test_character();
+        character.spiritual_root.affinity = 1.0;
+        character.cultivation_realm = world.cultivation_realms[0].clone();
+
+        character.cultivation_realm.sub_level = PEAK_SUB_LEVEL;
+        assert!(!ladder.is_peak(&character.cultivation_realm));
+        assert_eq!(ladder.display_name(&character.cultivation_realm), "练气 第4层");
+        assert_eq!(ladder.progress_percent(&character.cultivation_realm), 37);
+        let next = ladder.next_stage(&character.cultivation_realm).unwrap();
+        assert!(system.validate_realm_breakthrough(&character, &next));
+        assert!(system.breakthrough_blocker(&character).is_none());
+
+        character.cultivation_realm.sub_level = 8;
+        assert!(ladder.is_peak(&character.cultivation_realm));
+        assert!(ladder.next_stage(&character.cultivation_realm).is_none());
+        let blocked = system.calculate_breakthrough_result(&character);
+        assert!(!blocked.success);
+        assert!(blocked.description.contains("筑基"));
+
+        character.combat_power = 1_000_000;
+        assert!(system.breakthrough_blocker(&character).is_none());
+        let target = ladder.next_major(&character.cultivation_realm).unwrap();
+        let outcome = system.run_tribulation(&character, target, &mut GameRng::new(3));
+        assert_eq!(outcome.lifespan_bonus, 100);
+        assert_eq!(ladder.peak_sub_level(2), 2);
+        assert_eq!(ladder.stage_name(&outcome.target_realm), "初期");
+        assert_eq!(ladder.lifespan_bonus(1), TRIBULATION_LIFESPAN_PER_LEVEL);
+    }
 }
 
 #[cfg(test)]
@@ -499,10 +2113,10 @@ mod property_tests {
 
     fn arb_grade() -> impl Strategy<Value = Grade> {
         prop_oneof![
-            Just(Grade::Heavenly),
-            Just(Grade::Pseudo),
-            Just(Grade::Triple),
-            Just(Grade::Double),
+            Just(Grade::heavenly()),
+            Just(Grade::pseudo()),
+            Just(Grade::triple()),
+            Just(Grade::double()),
         ]
     }
 
@@ -512,6 +2126,7 @@ mod property_tests {
                 element,
                 grade,
                 affinity,
+                tier_multiplier: None,
             }
         })
     }
@@ -556,6 +2171,8 @@ mod property_tests {
             location,
             time_of_day,
             weather: None,
+            season: None,
+            spiritual_energy: 1.0,
         })
     }
 
@@ -633,8 +2250,9 @@ mod property_tests {
         let mut character = CharacterStats::new(
             SpiritualRoot {
                 element: Element::Fire,
-                grade: Grade::Heavenly,
+                grade: Grade::heavenly(),
                 affinity: 0.8,
+                tier_multiplier: None,
             },
             CultivationRealm::new("Qi Condensation".to_string(), 1, 0, 1.0),
             Lifespan::new(20, 100, 50),
@@ -644,7 +2262,7 @@ mod property_tests {
 
         character.cultivation_realm.sub_level += 1;
         character.cultivation_realm.power_multiplier *= 1.2;
-        character.techniques.push("Fire Palm".to_string());
+        character.techniques.push(LearnedTechnique::named("Fire Palm"));
         
         character.update_combat_power();
         
@@ -660,8 +2278,9 @@ mod property_tests {
         let mut character = CharacterStats::new(
             SpiritualRoot {
                 element: Element::Water,
-                grade: Grade::Double,
+                grade: Grade::double(),
                 affinity: 0.6,
+                tier_multiplier: None,
             },
             CultivationRealm::new("Foundation".to_string(), 2, 0, 2.0),
             Lifespan::new(30, 120, 80),
@@ -675,10 +2294,13 @@ mod property_tests {
         
         assert!(power_after_realm > power_before);
         
-        character.techniques.push("Water Shield".to_string());
+        character.techniques.push(LearnedTechnique::named("Water Shield"));
         character.update_combat_power();
         
-        assert_eq!(character.combat_power, power_after_realm);
+        // 功法加成叠加在境界倍数之上
+        assert!(character.combat_power > power_after_realm);
+        character.update_combat_power();
+        assert!(character.combat_power < power_after_realm * 2);
     }
 }
 
@@ -0,0 +1,227 @@
+use crate::numerical_system::Action;
+use crate::plot_engine::{ActionType, PlayerAction, PlayerOption};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const MAX_NOTABLE_PHRASES: usize = 3;
+const MIN_PHRASE_CHARS: usize = 4;
+const MAX_PHRASE_CHARS: usize = 60;
+const MORAL_STEP: i32 = 5;
+const MAX_SUMMARY_TENDENCIES: usize = 3;
+
+const RIGHTEOUS_KEYWORDS: &[&str] = &[
+    "救", "帮", "保护", "饶", "守护", "行善", "help", "save", "protect", "spare",
+];
+const RUTHLESS_KEYWORDS: &[&str] = &[
+    "杀", "抢", "夺", "骗", "偷", "灭口", "背叛", "kill", "steal", "rob", "betray",
+];
+
+const TENDENCY_KEYWORDS: &[(&str, &[&str])] = &[
+    ("战斗", &["战", "斗", "打", "攻", "fight", "attack", "battle"]),
+    ("修炼", &["修炼", "打坐", "闭关", "吐纳", "cultivate", "meditate"]),
+    ("交际", &["交谈", "询问", "结交", "拜访", "talk", "ask", "befriend"]),
+    ("谨慎", &["观察", "躲", "隐藏", "退避", "observe", "hide", "retreat"]),
+    ("探索", &["探索", "寻找", "前往", "调查", "explore", "search", "investigate"]),
+];
+
+/// 玩家行事风格画像，每回合轻量更新并注入剧情提示词
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PlayerPersona {
+    pub tendencies: HashMap<String, u32>,
+    pub moral_leaning: i32,
+    pub notable_phrases: Vec<String>,
+    pub turns_observed: u32,
+}
+
+impl PlayerPersona {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 根据本回合的行动更新画像
+    pub fn observe(&mut self, action: &PlayerAction, selected_option: Option<&PlayerOption>) {
+        let text = match selected_option {
+            Some(option) => {
+                if let Some(tendency) = option_tendency(&option.action) {
+                    self.bump(tendency);
+                }
+                option.description.clone()
+            }
+            None => action.content.clone(),
+        };
+
+        let lower = text.to_lowercase();
+        for (tendency, keywords) in TENDENCY_KEYWORDS {
+            if selected_option.is_none() && keywords.iter().any(|k| lower.contains(k)) {
+                self.bump(tendency);
+            }
+        }
+
+        if RIGHTEOUS_KEYWORDS.iter().any(|k| lower.contains(k)) {
+            self.moral_leaning += MORAL_STEP;
+        }
+        if RUTHLESS_KEYWORDS.iter().any(|k| lower.contains(k)) {
+            self.moral_leaning -= MORAL_STEP;
+        }
+        self.moral_leaning = self.moral_leaning.clamp(-100, 100);
+
+        if selected_option.is_none() && matches!(action.action_type, ActionType::FreeText) {
+            self.remember_phrase(action.content.trim());
+        }
+
+        self.turns_observed = self.turns_observed.saturating_add(1);
+    }
+
+    /// 生成供提示词使用的画像摘要，尚无观察时返回 None
+    pub fn summary(&self) -> Option<String> {
+        if self.turns_observed == 0 {
+            return None;
+        }
+
+        let mut tendencies = self.tendencies.iter().collect::<Vec<(&String, &u32)>>();
+        tendencies.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        let mut parts = Vec::new();
+        if !tendencies.is_empty() {
+            parts.push(format!(
+                "行事倾向：{}",
+                tendencies
+                    .iter()
+                    .take(MAX_SUMMARY_TENDENCIES)
+                    .map(|(name, count)| format!("{}×{}", name, count))
+                    .collect::<Vec<String>>()
+                    .join("、")
+            ));
+        }
+
+        parts.push(format!(
+            "道德倾向：{}({})",
+            moral_label(self.moral_leaning),
+            self.moral_leaning
+        ));
+
+        if !self.notable_phrases.is_empty() {
+            parts.push(format!(
+                "玩家曾说：{}",
+                self.notable_phrases
+                    .iter()
+                    .map(|p| format!("「{}」", p))
+                    .collect::<Vec<String>>()
+                    .join(" / ")
+            ));
+        }
+
+        Some(parts.join("；"))
+    }
+
+    fn bump(&mut self, tendency: &str) {
+        *self.tendencies.entry(tendency.to_string()).or_insert(0) += 1;
+    }
+
+    fn remember_phrase(&mut self, phrase: &str) {
+        let length = phrase.chars().count();
+        if !(MIN_PHRASE_CHARS..=MAX_PHRASE_CHARS).contains(&length) {
+            return;
+        }
+        self.notable_phrases.retain(|p| p != phrase);
+        self.notable_phrases.push(phrase.to_string());
+        if self.notable_phrases.len() > MAX_NOTABLE_PHRASES {
+            self.notable_phrases.remove(0);
+        }
+    }
+}
+
+fn option_tendency(action: &Action) -> Option<&'static str> {
+    match action {
+        Action::Combat { .. } => Some("战斗"),
+        Action::Cultivate
+        | Action::Breakthrough
+        | Action::LearnTechnique { .. }
+        | Action::PracticeTechnique { .. } => Some("修炼"),
+        Action::Rest => Some("谨慎"),
+        Action::Custom { .. } | Action::UseItem { .. } => None,
+    }
+}
+
+fn moral_label(leaning: i32) -> &'static str {
+    match leaning {
+        30.. => "正直侠义",
+        10..=29 => "偏向善行",
+        -9..=9 => "亦正亦邪",
+        -29..=-10 => "偏向狠辣",
+        _ => "冷酷无情",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plot_engine::new_option_uid;
+
+    fn free_text(content: &str) -> PlayerAction {
+        PlayerAction {
+            action_type: ActionType::FreeText,
+            content: content.to_string(),
+            selected_option_id: None,
+            selected_option_uuid: None,
+            meta: None,
+        }
+    }
+
+    #[test]
+    fn test_empty_persona_has_no_summary() {
+        assert!(PlayerPersona::new().summary().is_none());
+    }
+
+    #[test]
+    fn test_observe_free_text_tracks_tendency_morality_and_phrases() {
+        let mut persona = PlayerPersona::new();
+        persona.observe(&free_text("出手救下被围攻的散修"), None);
+        persona.observe(&free_text("我要保护师妹"), None);
+
+        assert_eq!(persona.turns_observed, 2);
+        assert_eq!(persona.moral_leaning, 2 * MORAL_STEP);
+        assert_eq!(persona.notable_phrases.len(), 2);
+
+        let summary = persona.summary().unwrap();
+        assert!(summary.contains("偏向善行"));
+        assert!(summary.contains("我要保护师妹"));
+    }
+
+    #[test]
+    fn test_observe_selected_option_uses_action_kind() {
+        let mut persona = PlayerPersona::new();
+        let option = PlayerOption {
+            id: 0,
+            uid: new_option_uid(),
+            description: "与妖兽搏杀".to_string(),
+            requirements: Vec::new(),
+            action: Action::Combat {
+                target_id: "beast".to_string(),
+            },
+        };
+        let action = PlayerAction {
+            action_type: ActionType::SelectedOption,
+            content: String::new(),
+            selected_option_id: Some(0),
+            selected_option_uuid: None,
+            meta: None,
+        };
+
+        persona.observe(&action, Some(&option));
+
+        assert_eq!(persona.tendencies.get("战斗"), Some(&1));
+        assert!(persona.moral_leaning < 0);
+        assert!(persona.notable_phrases.is_empty());
+    }
+
+    #[test]
+    fn test_notable_phrases_are_bounded() {
+        let mut persona = PlayerPersona::new();
+        for idx in 0..5 {
+            persona.observe(&free_text(&format!("第{}次闭关修炼", idx)), None);
+        }
+        assert_eq!(persona.notable_phrases.len(), MAX_NOTABLE_PHRASES);
+        assert_eq!(persona.notable_phrases.last().unwrap(), "第4次闭关修炼");
+    }
+}
@@ -1,14 +1,48 @@
-﻿use crate::models::CharacterStats;
-use crate::llm_runtime_config::resolve_llm_config;
-use crate::llm_service::{LLMRequest, LLMService};
-use crate::numerical_system::{Action, ActionResult, Context, NumericalSystem};
+use crate::models::CharacterStats;
+use crate::llm_runtime_config::shared_llm_service;
+use crate::llm_service::{
+    parse_structured, ChatMessage, LLMChatRequest, LLMRequest, LLMResponse, LLMService,
+    LLMServiceError, LLMSubsystem, StructuredResponse,
+};
+use crate::numerical_system::{
+    Action, ActionResult, Context, NumericalSystem, TribulationOutcome, TribulationStage,
+    TribulationStageKind,
+};
+use crate::action_filters::ActionFilters;
+use crate::cancellation;
+use crate::arc_planner::StoryArc;
+use crate::chapter_beats::{beats_satisfied, ChapterBeat};
+use crate::chapter_outline::ChapterOutline;
+use crate::context_assembler::DEFAULT_CONTEXT_TOKENS;
+use crate::facts::{FactStore, MAX_PROMPT_FACTS};
+use crate::generation_diagnostics::{GenerationDiagnostics, ParsePath};
+use crate::generation_failure::{FailureCategory, GenerationFailure};
+use crate::house_rules::HouseRules;
+use crate::karma::{classify_deed, Alignment};
+use crate::npc_alerts::NpcEventNotice;
+use crate::quest_system::QuestUpdates;
+use crate::player_persona::PlayerPersona;
+use crate::provenance::{
+    FallbackKind, SegmentProvenance, ValidatorVerdict, CONSISTENCY_VALIDATOR, REPETITION_VALIDATOR,
+    RESPONSE_VALIDATOR,
+};
 use crate::prompt_builder::{PromptBuilder, PromptConstraints, PromptContext, PromptTemplate};
-use crate::response_validator::{ResponseValidator, ValidationConstraints};
+use crate::response_validator::{NarrativeContext, ResponseValidator, ValidationConstraints};
+use crate::script::ScriptLanguage;
+use crate::side_story::SideStory;
+use crate::token_budget::{TokenBudget, TurnCall};
+use crate::temperature_tuner::{
+    repetition_score, TemperatureBounds, TemperatureTuner, TuningSignal, REPETITION_THRESHOLD,
+};
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
-use std::time::Duration;
-use tokio::runtime::Handle;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::runtime::{Handle, RuntimeFlavor};
 use tokio::task;
+use uuid::Uuid;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ActionType {
@@ -20,7 +54,11 @@ pub enum ActionType {
 pub struct PlayerAction {
     pub action_type: ActionType,
     pub content: String,
+    /// 选项在当前列表中的序号，选项轮转后可能指向别的选项
     pub selected_option_id: Option<usize>,
+    /// 选项的稳定 UID；选择选项时必须提供，同时提供序号时以 UID 为准
+    #[serde(default, alias = "selected_option_uid")]
+    pub selected_option_uuid: Option<String>,
     pub meta: Option<ActionMeta>,
 }
 
@@ -29,15 +67,92 @@ pub struct ActionMeta {
     pub action_kind: Option<String>,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct PlayerOption {
     pub id: usize,
+    /// 选项的稳定标识，重新生成或轮转后不变
+    #[serde(default = "new_option_uid")]
+    pub uid: String,
     pub description: String,
     pub requirements: Vec<String>,
     pub action: Action,
 }
 
+/// 为新选项生成 UID
+pub fn new_option_uid() -> String {
+    Uuid::new_v4().to_string()
+}
+
+/// 新选项沿用旧列表中同描述选项的 UID，使重新生成后的同一选项保持身份不变
+pub fn inherit_option_uids(previous: &[PlayerOption], options: &mut [PlayerOption]) {
+    for option in options.iter_mut() {
+        if let Some(old) = previous.iter().find(|old| old.description == option.description) {
+            option.uid = old.uid.clone();
+        }
+    }
+}
+
+pub const STALE_OPTION_CODE: &str = "stale_option";
+
+/// 提交的选项 UID 已不在当前选项中（前端点击滞后于选项刷新），以 JSON 返回供前端刷新后重选
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StaleOptionError {
+    pub code: String,
+    pub message: String,
+    pub submitted_uid: String,
+    pub current_options: Vec<PlayerOption>,
+}
+
+impl StaleOptionError {
+    fn new(submitted_uid: &str, current_options: &[PlayerOption]) -> Self {
+        Self {
+            code: STALE_OPTION_CODE.to_string(),
+            message: "所选选项已过期，请从当前选项中重新选择".to_string(),
+            submitted_uid: submitted_uid.to_string(),
+            current_options: current_options.to_vec(),
+        }
+    }
+
+    /// 只提交了序号的旧请求：序号可能已指向重新生成后的另一条选项，一律要求刷新
+    fn missing_uid(current_options: &[PlayerOption]) -> Self {
+        Self {
+            message: "选项已更新，请刷新后重新选择".to_string(),
+            ..Self::new("", current_options)
+        }
+    }
+}
+
+impl std::fmt::Display for StaleOptionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match serde_json::to_string(self) {
+            Ok(json) => write!(f, "{}", json),
+            Err(_) => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// 按 UID 解析玩家所选选项在当前列表中的位置，UID 不在当前选项中时报告过期；
+/// 选择选项却只给出序号时同样按过期拒绝，自由输入不对应任何选项
+pub fn selected_option_index(
+    action: &PlayerAction,
+    available_options: &[PlayerOption],
+) -> Result<Option<usize>, String> {
+    if let Some(uid) = action.selected_option_uuid.as_deref() {
+        return available_options
+            .iter()
+            .position(|option| option.uid == uid)
+            .map(Some)
+            .ok_or_else(|| StaleOptionError::new(uid, available_options).to_string());
+    }
+    match action.action_type {
+        ActionType::SelectedOption => {
+            Err(StaleOptionError::missing_uid(available_options).to_string())
+        }
+        ActionType::FreeText => Ok(None),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct Scene {
     pub id: String,
     pub name: String,
@@ -46,7 +161,7 @@ pub struct Scene {
     pub available_options: Vec<PlayerOption>,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct PlotSettings {
     pub recap_enabled: bool,
     pub novel_style: String,
@@ -54,6 +169,31 @@ pub struct PlotSettings {
     pub max_interactions_per_chapter: u8,
     pub target_chapter_words_min: u32,
     pub target_chapter_words_max: u32,
+    /// 新一期世界快报发行时作为插叙段落写入章节
+    #[serde(default)]
+    pub bulletin_segments_enabled: bool,
+    /// 自适应温度调整允许的范围
+    #[serde(default)]
+    pub temperature_bounds: TemperatureBounds,
+    /// 三幕式结构：每章须依次写完 引入→冲突→转折→收束 才能结束，代替字数判定
+    #[serde(default)]
+    pub three_act_structure: bool,
+    /// 两段式续写：每章先规划大纲（节拍、互动点、预定结局），再按大纲逐段续写，写完大纲即结束本章
+    #[serde(default)]
+    pub outline_planning: bool,
+    /// NPC 插叙：冲着玩家来的 NPC 反应改写当前选项，下一回合须先回应找上门的 NPC
+    #[serde(default)]
+    pub npc_interruptions: bool,
+    /// 自由输入的本地歧义度达到该值才请求 LLM 合理性判定，0 表示总是判定，大于 1 表示从不判定
+    #[serde(default = "default_llm_judge_threshold")]
+    pub llm_judge_threshold: f32,
+    /// 叙事、选项与 NPC 对白使用的语言，预设文本同样按此语言输出
+    #[serde(default)]
+    pub language: ScriptLanguage,
+}
+
+fn default_llm_judge_threshold() -> f32 {
+    DEFAULT_LLM_JUDGE_THRESHOLD
 }
 
 impl Default for PlotSettings {
@@ -65,17 +205,36 @@ impl Default for PlotSettings {
             max_interactions_per_chapter: 3,
             target_chapter_words_min: 5000,
             target_chapter_words_max: 7000,
+            bulletin_segments_enabled: false,
+            temperature_bounds: TemperatureBounds::default(),
+            three_act_structure: false,
+            outline_planning: false,
+            npc_interruptions: false,
+            llm_judge_threshold: DEFAULT_LLM_JUDGE_THRESHOLD,
+            language: ScriptLanguage::default(),
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct ChapterState {
     pub index: u32,
     pub title: String,
     pub content: Vec<String>,
     pub summary: String,
     pub interaction_count: u8,
+    /// 低内存模式下正文已写入冷存储，`content` 为空
+    #[serde(default)]
+    pub offloaded: bool,
+    /// 三幕式结构下已完成的节拍数
+    #[serde(default)]
+    pub beats_completed: u8,
+    /// 两段式续写下本章的大纲，新章节的第一段之前规划
+    #[serde(default)]
+    pub outline: Option<ChapterOutline>,
+    /// 各段落的生成溯源，插叙等非生成段落没有记录
+    #[serde(default)]
+    pub provenance: Vec<SegmentProvenance>,
 }
 
 impl ChapterState {
@@ -86,6 +245,10 @@ impl ChapterState {
             content: Vec::new(),
             summary: String::new(),
             interaction_count: 0,
+            offloaded: false,
+            beats_completed: 0,
+            outline: None,
+            provenance: Vec::new(),
         }
     }
 
@@ -97,7 +260,7 @@ impl ChapterState {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct PlotState {
     pub current_scene: Scene,
     pub plot_history: Vec<String>,
@@ -108,12 +271,30 @@ pub struct PlotState {
     pub chapters: Vec<ChapterState>,
     pub segment_count: u32,
     #[serde(default)]
-    pub last_generation_diagnostics: Option<String>,
+    pub last_generation_diagnostics: Option<GenerationDiagnostics>,
     #[serde(default)]
     pub last_option_generation_source: Option<String>,
+    #[serde(default)]
+    pub last_generation_failure: Option<GenerationFailure>,
+    #[serde(default)]
+    pub player_persona: PlayerPersona,
+    #[serde(default)]
+    pub canon_facts: FactStore,
+    #[serde(default)]
+    pub temperature_tuner: TemperatureTuner,
+    #[serde(default)]
+    pub story_arc: Option<StoryArc>,
+    /// 等待玩家回应的 NPC 插叙，下一段续写从这里写起
+    #[serde(default)]
+    pub npc_interruption: Option<NpcEventNotice>,
+    /// 身在秘境时的支线状态，主线剧情在引擎中压栈
+    #[serde(default)]
+    pub side_story: Option<SideStory>,
+    #[serde(default)]
+    pub version: u64,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct PlotUpdate {
     pub new_scene: Option<Scene>,
     pub plot_text: String,
@@ -124,21 +305,98 @@ pub struct PlotUpdate {
     pub chapter_title: Option<String>,
     pub chapter_summary: Option<String>,
     pub chapter_end: bool,
-    pub generation_diagnostics: Option<String>,
+    #[serde(default)]
+    pub generation_diagnostics: GenerationDiagnostics,
+    #[serde(default)]
+    pub generation_failure: Option<GenerationFailure>,
+    /// 本次 LLM 续写对提示模板的反馈，用于自适应温度
+    #[serde(default)]
+    pub tuning_signal: Option<TuningSignal>,
+    /// 本段正文的生成溯源
+    #[serde(default)]
+    pub provenance: Option<SegmentProvenance>,
+    /// 本段剧情带来的任务变化
+    #[serde(default)]
+    pub quest_updates: QuestUpdates,
 }
 
+const SEGMENT_STAGE: &str = "剧情续写";
+pub const SEGMENT_BASE_TEMPERATURE: f32 = 0.7;
+pub const DEFAULT_LLM_JUDGE_THRESHOLD: f32 = 0.5;
+/// 玩家自拟选项的要求标记
+pub const CUSTOM_OPTION_REQUIREMENT: &str = "玩家自拟";
+/// 同一决策点最多保留的自拟选项数
+const MAX_CUSTOM_OPTIONS: usize = 3;
+const CONSISTENCY_REGENERATION_TOKENS: u32 = 700;
+/// 夸大或越界的措辞，出现时本地分类无法确定行动是否合理
+const AMBIGUOUS_FREE_TEXT_MARKERS: &[&str] = &[
+    "无敌", "秒杀", "飞升", "成仙", "所有人", "全部", "瞬间", "立刻", "天下第一", "一步登天",
+    "invincible", "instantly", "everyone", "immortal",
+];
+const TIGHTENED_JSON_RULES: &[&str] = &[
+    "只输出一个 JSON 对象，不要 Markdown 代码块或任何解释",
+    "字符串中的引号必须转义",
+];
+
+#[derive(Clone)]
 pub struct PlotEngine {
     numerical_system: NumericalSystem,
+    action_filters: ActionFilters,
+    house_rules: HouseRules,
+    llm_judge_threshold: f32,
+    /// 由 `ContextAssembler` 按完整游戏状态拼装的上下文，续写与选项生成在此基础上补充本次所需
+    game_context: PromptContext,
+    /// 从小说中途接续时的前情提要，开篇据此接着写
+    story_recap: Option<String>,
+    /// 内容过滤对续写提出的约束
+    content_rules: Vec<String>,
+    /// 续写须保持一致的游戏状态，未设置时不做一致性校验
+    narrative_context: Option<NarrativeContext>,
     prompt_builder: PromptBuilder,
     response_validator: ResponseValidator,
+    /// 注入的共享 LLMService，未注入时按当前配置取共享实例
+    llm_service: Option<Arc<LLMService>>,
+    /// 本回合各次 LLM 调用共用的 token 预算，未设置时不限
+    token_budget: Option<TokenBudget>,
 }
 
-#[derive(Debug, Clone)]
+/// 自由输入的本地分类结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct FreeTextClassification {
+    pub action: Action,
+    pub ambiguity: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct OpeningPlot {
     pub text: String,
     pub options: Vec<String>,
 }
 
+impl OpeningPlot {
+    /// 开篇自带的选项，模型没有给出时为空，由规则生成
+    pub fn player_options(&self) -> Option<Vec<PlayerOption>> {
+        if self.options.is_empty() {
+            return None;
+        }
+        Some(
+            self.options
+                .iter()
+                .enumerate()
+                .map(|(idx, text)| PlayerOption {
+                    id: idx,
+                    uid: new_option_uid(),
+                    description: text.clone(),
+                    requirements: vec![],
+                    action: Action::Custom {
+                        description: text.clone(),
+                    },
+                })
+                .collect(),
+        )
+    }
+}
+
 #[derive(Debug, Clone)]
 struct ChapterSegment {
     text: String,
@@ -147,73 +405,262 @@ struct ChapterSegment {
     chapter_title: Option<String>,
     chapter_summary: Option<String>,
     options: Vec<String>,
-    generation_diagnostics: Option<String>,
+    generation_diagnostics: GenerationDiagnostics,
+    generation_failure: Option<GenerationFailure>,
+    tuning_signal: Option<TuningSignal>,
+    provenance: SegmentProvenance,
+    quest_updates: QuestUpdates,
+}
+
+/// 剧情续写的结构化输出
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(default)]
+struct SegmentPayload {
+    segment_text: String,
+    needs_player_input: bool,
+    chapter_end: bool,
+    chapter_title: Option<String>,
+    chapter_summary: Option<String>,
+    options: Vec<String>,
+    #[serde(flatten)]
+    quests: QuestUpdates,
+    /// 旧提示格式的分项描写，`segment_text` 为空时依次拼接
+    #[serde(flatten)]
+    #[schemars(skip)]
+    legacy: LegacySegmentParts,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct LegacySegmentParts {
+    scene_description: Option<String>,
+    environment_detail: Option<String>,
+    npc_reaction: Option<String>,
+    event_development: Option<String>,
+    player_action_consequence: Option<String>,
+}
+
+impl SegmentPayload {
+    fn text(&self) -> Option<String> {
+        let direct = self.segment_text.trim();
+        if !direct.is_empty() {
+            return Some(direct.to_string());
+        }
+
+        let legacy = &self.legacy;
+        let parts = [
+            &legacy.scene_description,
+            &legacy.environment_detail,
+            &legacy.npc_reaction,
+            &legacy.event_development,
+            &legacy.player_action_consequence,
+        ]
+        .into_iter()
+        .filter_map(|part| part.as_deref().map(str::trim))
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<&str>>();
+        (!parts.is_empty()).then(|| parts.join("\n\n"))
+    }
+}
+
+/// 开篇剧情的结构化输出
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(default)]
+struct OpeningPayload {
+    segment_text: String,
+    #[serde(alias = "action_choices")]
+    options: Vec<String>,
+    #[schemars(skip)]
+    scene: String,
+    #[schemars(skip)]
+    current_status: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(default)]
+struct OptionsPayload {
+    #[serde(alias = "action_choices")]
+    options: Vec<String>,
+}
+
+/// 自由输入解析为游戏行动的结构化输出
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct ActionPayload {
+    action: String,
+    #[serde(default)]
+    target: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+}
+
+/// LLM 对自由输入合理性的判定
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct ReasonablenessVerdict {
+    reasonable: bool,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+/// LLM 对自由输入善恶的判定
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+struct AlignmentVerdict {
+    alignment: String,
 }
 
 impl PlotEngine {
     pub fn new() -> Self {
         Self {
             numerical_system: NumericalSystem::new(),
+            action_filters: ActionFilters::builtin(),
+            house_rules: HouseRules::default(),
+            llm_judge_threshold: DEFAULT_LLM_JUDGE_THRESHOLD,
+            game_context: PromptContext::default(),
+            story_recap: None,
+            content_rules: Vec::new(),
+            narrative_context: None,
             prompt_builder: PromptBuilder::default(),
             response_validator: ResponseValidator::default(),
+            llm_service: None,
+            token_budget: None,
         }
     }
 
-    fn resolve_llm_service(&self) -> Option<LLMService> {
-        let cfg = resolve_llm_config()?;
-        LLMService::new(cfg).ok()
+    pub fn with_llm_service(mut self, llm_service: Arc<LLMService>) -> Self {
+        self.llm_service = Some(llm_service);
+        self
     }
 
-    fn run_llm_request(&self, llm_service: &LLMService, request: LLMRequest) -> Option<crate::llm_service::LLMResponse> {
-        if let Ok(handle) = Handle::try_current() {
-            return task::block_in_place(|| {
-                handle
-                    .block_on(tokio::time::timeout(
-                        Duration::from_secs(45),
-                        llm_service.generate(request),
-                    ))
-                    .ok()
-                    .and_then(Result::ok)
-            });
-        }
+    pub fn set_llm_service(&mut self, llm_service: Option<Arc<LLMService>>) {
+        self.llm_service = llm_service;
+    }
 
-        let runtime = tokio::runtime::Runtime::new().ok()?;
-        runtime
-            .block_on(tokio::time::timeout(
-                Duration::from_secs(45),
-                llm_service.generate(request),
-            ))
-            .ok()
-            .and_then(Result::ok)
+    pub fn set_token_budget(&mut self, token_budget: Option<TokenBudget>) {
+        self.token_budget = token_budget;
     }
 
-    fn extract_json_value(&self, raw: &str) -> Option<Value> {
-        let trimmed = raw.trim();
-        if trimmed.is_empty() {
-            return None;
-        }
+    pub fn with_numerical_system(mut self, numerical_system: NumericalSystem) -> Self {
+        self.numerical_system = numerical_system;
+        self
+    }
 
-        let mut candidate = trimmed.to_string();
-        if trimmed.starts_with("```") {
-            let mut lines = trimmed.lines();
-            let _ = lines.next();
-            candidate = lines.collect::<Vec<&str>>().join("\n");
-            if let Some(stripped) = candidate.strip_suffix("```") {
-                candidate = stripped.trim().to_string();
-            }
-        }
-        let candidate = candidate.trim();
+    pub fn with_action_filters(mut self, action_filters: ActionFilters) -> Self {
+        self.action_filters = action_filters;
+        self
+    }
 
-        if let Ok(value) = serde_json::from_str::<Value>(candidate) {
-            return Some(value);
+    pub fn with_house_rules(mut self, house_rules: HouseRules) -> Self {
+        self.house_rules = house_rules;
+        self
+    }
+
+    /// 自由输入请求 LLM 合理性判定的歧义度门槛
+    pub fn with_llm_judge_threshold(mut self, threshold: f32) -> Self {
+        self.llm_judge_threshold = threshold;
+        self
+    }
+
+    /// 提示要求的输出语言，同时决定预设文本的语言
+    pub fn with_language(mut self, language: ScriptLanguage) -> Self {
+        self.prompt_builder = self.prompt_builder.with_language(language);
+        self
+    }
+
+    pub fn set_language(&mut self, language: ScriptLanguage) {
+        self.prompt_builder = self.prompt_builder.clone().with_language(language);
+    }
+
+    pub fn language(&self) -> ScriptLanguage {
+        self.prompt_builder.language()
+    }
+
+    /// 续写与选项生成共用的游戏状态上下文：角色卡、任务、关系、状态与近期事件
+    pub fn with_game_context(mut self, game_context: PromptContext) -> Self {
+        self.game_context = game_context;
+        self
+    }
+
+    /// 开篇接续的前情提要
+    pub fn with_story_recap(mut self, story_recap: Option<String>) -> Self {
+        self.story_recap = story_recap;
+        self
+    }
+
+    /// 续写提示中追加的内容约束
+    pub fn with_content_rules(mut self, content_rules: Vec<String>) -> Self {
+        self.content_rules = content_rules;
+        self
+    }
+
+    /// 续写段落须与之一致的地点、境界与已身故 NPC
+    pub fn with_narrative_context(mut self, narrative_context: NarrativeContext) -> Self {
+        self.narrative_context = Some(narrative_context);
+        self
+    }
+
+    pub fn numerical_system(&self) -> &NumericalSystem {
+        &self.numerical_system
+    }
+
+    fn resolve_llm_service(&self) -> Option<Arc<LLMService>> {
+        self.llm_service.clone().or_else(shared_llm_service)
+    }
+
+    /// 按回合预算确定本次调用的输出上限；预算不足以发出可选调用时返回 `None`
+    fn budget_tokens(&self, call: TurnCall, prompt: &str, max_tokens: u32) -> Option<u32> {
+        match &self.token_budget {
+            Some(budget) => budget.allocate(call, prompt, max_tokens),
+            None => Some(max_tokens),
         }
+    }
 
-        let start = candidate.find('{')?;
-        let end = candidate.rfind('}')?;
-        if start >= end {
-            return None;
+    fn run_llm_request(&self, llm_service: &LLMService, request: LLMRequest) -> Option<LLMResponse> {
+        self.block_on_llm(llm_service.generate(request))
+    }
+
+    fn run_structured_request<T: DeserializeOwned + JsonSchema + Send>(
+        &self,
+        llm_service: &LLMService,
+        request: LLMRequest,
+    ) -> Option<StructuredResponse<T>> {
+        self.block_on_llm(llm_service.generate_structured(request))
+    }
+
+    fn block_on_llm<T: Send>(
+        &self,
+        call: impl Future<Output = Result<T, LLMServiceError>> + Send,
+    ) -> Option<T> {
+        self.block_on(tokio::time::timeout(Duration::from_secs(45), call))
+            .and_then(Result::ok)
+            .and_then(Result::ok)
+    }
+
+    /// 同步接口借此复用异步实现。一律交给应用运行时驱动，LLM 客户端的连接池只绑定这一个
+    /// 运行时；多线程运行时内让出工作线程原地等待，单线程运行时（只在测试中出现）无法原地
+    /// 阻塞，改由临时线程借应用运行时等待
+    fn block_on<F>(&self, future: F) -> Option<F::Output>
+    where
+        F: Future + Send,
+        F::Output: Send,
+    {
+        // 令牌是任务局部的，换到别的线程或运行时就看不到了，显式带进去才能照常取消
+        let token = cancellation::current_token();
+        let future = async move {
+            match token {
+                Some(token) => token.scope(future).await,
+                None => future.await,
+            }
+        };
+        let app_runtime = tauri::async_runtime::handle();
+        let app_runtime = app_runtime.inner();
+        match Handle::try_current() {
+            Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+                Some(task::block_in_place(|| app_runtime.block_on(future)))
+            }
+            Ok(_) => {
+                std::thread::scope(|scope| scope.spawn(|| app_runtime.block_on(future)).join().ok())
+            }
+            Err(_) => Some(app_runtime.block_on(future)),
         }
-        serde_json::from_str::<Value>(&candidate[start..=end]).ok()
     }
 
     fn extract_string_field_raw(&self, raw: &str, field: &str) -> Option<String> {
@@ -336,40 +783,73 @@ impl PlotEngine {
         Vec::new()
     }
 
-    fn compose_segment_text_from_json(&self, value: &Value) -> Option<String> {
-        let direct = value
-            .get("segment_text")
-            .and_then(Value::as_str)
-            .map(str::trim)
-            .filter(|s| !s.is_empty())
-            .map(ToString::to_string);
-        if direct.is_some() {
-            return direct;
-        }
-
-        let mut parts = Vec::new();
-        for key in [
-            "scene_description",
-            "environment_detail",
-            "npc_reaction",
-            "event_development",
-            "player_action_consequence",
-        ] {
-            if let Some(text) = value
-                .get(key)
-                .and_then(Value::as_str)
-                .map(str::trim)
-                .filter(|s| !s.is_empty())
-            {
-                parts.push(text.to_string());
+    /// 优先使用结构化输出；解析失败时从原文中抢救字段，最后退回纯文本
+    fn chapter_segment_from_response(
+        &self,
+        structured: StructuredResponse<SegmentPayload>,
+        tuning_signal: Option<TuningSignal>,
+        provenance: SegmentProvenance,
+    ) -> Option<ChapterSegment> {
+        let raw = structured.response.text.as_str();
+        let diagnostics = |parse_path| GenerationDiagnostics {
+            retry_count: provenance.retry_count,
+            parse_path: Some(parse_path),
+            ..GenerationDiagnostics::from_response(&structured.response, provenance.model.as_deref())
+        };
+        if let Some(payload) = structured.data {
+            let text = self.normalize_story_text(&payload.text().unwrap_or_default());
+            if !text.is_empty() {
+                return Some(ChapterSegment {
+                    text,
+                    needs_player_input: payload.needs_player_input,
+                    chapter_end: payload.chapter_end,
+                    chapter_title: payload.chapter_title.map(|s| s.trim().to_string()),
+                    chapter_summary: payload.chapter_summary.map(|s| s.trim().to_string()),
+                    options: trimmed_items(payload.options),
+                    generation_diagnostics: diagnostics(ParsePath::StrictJson),
+                    generation_failure: None,
+                    tuning_signal,
+                    provenance,
+                    quest_updates: QuestUpdates {
+                        new_quests: trimmed_items(payload.quests.new_quests),
+                        completed_quests: trimmed_items(payload.quests.completed_quests),
+                    },
+                });
             }
         }
 
-        if parts.is_empty() {
-            None
-        } else {
-            Some(parts.join("\n\n"))
+        if let Some(text) = self.extract_string_field_raw(raw, "segment_text") {
+            return Some(ChapterSegment {
+                text: self.normalize_story_text(&text),
+                needs_player_input: self
+                    .extract_bool_field_raw(raw, "needs_player_input")
+                    .unwrap_or(true),
+                chapter_end: self.extract_bool_field_raw(raw, "chapter_end").unwrap_or(false),
+                chapter_title: self.extract_string_field_raw(raw, "chapter_title"),
+                chapter_summary: self.extract_string_field_raw(raw, "chapter_summary"),
+                options: self.extract_options_field_raw(raw),
+                generation_diagnostics: diagnostics(ParsePath::Salvage),
+                generation_failure: None,
+                tuning_signal,
+                provenance,
+                quest_updates: QuestUpdates::default(),
+            });
         }
+
+        let generation_diagnostics = diagnostics(ParsePath::PlainText);
+        self.sanitize_llm_plain_text(raw).map(|text| ChapterSegment {
+            text: self.normalize_story_text(&text),
+            needs_player_input: true,
+            chapter_end: false,
+            chapter_title: None,
+            chapter_summary: None,
+            options: vec![],
+            generation_diagnostics,
+            generation_failure: None,
+            tuning_signal,
+            provenance,
+            quest_updates: QuestUpdates::default(),
+        })
     }
 
     fn sanitize_llm_plain_text(&self, raw: &str) -> Option<String> {
@@ -438,66 +918,16 @@ impl PlotEngine {
         })
     }
 
+    /// 同步入口：阻塞等待与 `advance_plot_async` 相同的续写流程
     pub fn advance_plot(
         &self,
         current_state: &PlotState,
         action_result: &ActionResult,
     ) -> PlotUpdate {
-        let segment = self.generate_chapter_segment(current_state, action_result);
-        let plot_text = segment.text.clone();
-        let triggered_events = action_result.events.clone();
-
-        let state_changes: Vec<String> = action_result
-            .stat_changes
-            .iter()
-            .map(|change| {
-                format!(
-                    "{}: {} -> {}",
-                    change.stat_name, change.old_value, change.new_value
-                )
-            })
-            .collect();
-
-        let mut available_options = Vec::new();
-        if segment.needs_player_input || segment.chapter_end {
-            if segment.chapter_end {
-                available_options.push(PlayerOption {
-                    id: 0,
-                    description: "翻到下一章".to_string(),
-                    requirements: vec![],
-                    action: Action::Custom {
-                        description: "你翻动书页，进入新的篇章。".to_string(),
-                    },
-                });
-            } else if !segment.options.is_empty() {
-                available_options = segment
-                    .options
-                    .iter()
-                    .enumerate()
-                    .map(|(idx, text)| PlayerOption {
-                        id: idx,
-                        description: text.clone(),
-                        requirements: vec![],
-                        action: Action::Custom {
-                            description: text.clone(),
-                        },
-                    })
-                    .collect();
-            }
-        }
-
-        PlotUpdate {
-            new_scene: None,
-            plot_text,
-            triggered_events,
-            state_changes,
-            is_waiting_for_input: segment.needs_player_input || segment.chapter_end,
-            available_options,
-            chapter_title: segment.chapter_title,
-            chapter_summary: segment.chapter_summary,
-            chapter_end: segment.chapter_end,
-            generation_diagnostics: segment.generation_diagnostics,
-        }
+        let segment = self
+            .block_on(self.generate_chapter_segment_async(current_state, action_result))
+            .unwrap_or_else(|| self.preset_segment(current_state, action_result));
+        self.plot_update_from_segment(segment, action_result)
     }
 
     pub async fn advance_plot_async(
@@ -508,6 +938,14 @@ impl PlotEngine {
         let segment = self
             .generate_chapter_segment_async(current_state, action_result)
             .await;
+        self.plot_update_from_segment(segment, action_result)
+    }
+
+    fn plot_update_from_segment(
+        &self,
+        segment: ChapterSegment,
+        action_result: &ActionResult,
+    ) -> PlotUpdate {
         let plot_text = segment.text.clone();
         let triggered_events = action_result.events.clone();
 
@@ -525,14 +963,7 @@ impl PlotEngine {
         let mut available_options = Vec::new();
         if segment.needs_player_input || segment.chapter_end {
             if segment.chapter_end {
-                available_options.push(PlayerOption {
-                    id: 0,
-                    description: "翻到下一章".to_string(),
-                    requirements: vec![],
-                    action: Action::Custom {
-                        description: "你翻动书页，进入新的篇章。".to_string(),
-                    },
-                });
+                available_options.push(self.next_chapter_option());
             } else if !segment.options.is_empty() {
                 available_options = segment
                     .options
@@ -540,6 +971,7 @@ impl PlotEngine {
                     .enumerate()
                     .map(|(idx, text)| PlayerOption {
                         id: idx,
+                        uid: new_option_uid(),
                         description: text.clone(),
                         requirements: vec![],
                         action: Action::Custom {
@@ -561,31 +993,52 @@ impl PlotEngine {
             chapter_summary: segment.chapter_summary,
             chapter_end: segment.chapter_end,
             generation_diagnostics: segment.generation_diagnostics,
+            generation_failure: segment.generation_failure,
+            tuning_signal: segment.tuning_signal,
+            provenance: Some(segment.provenance),
+            quest_updates: segment.quest_updates,
         }
     }
 
-    pub fn generate_plot_text(&self, current_state: &PlotState, action_result: &ActionResult) -> String {
-        self.generate_chapter_segment(current_state, action_result).text
+    fn next_chapter_option(&self) -> PlayerOption {
+        let (description, action) = match self.language() {
+            ScriptLanguage::Zh => ("翻到下一章", "你翻动书页，进入新的篇章。"),
+            ScriptLanguage::En => ("Turn to the next chapter", "You turn the page into a new chapter."),
+        };
+        PlayerOption {
+            id: 0,
+            uid: new_option_uid(),
+            description: description.to_string(),
+            requirements: vec![],
+            action: Action::Custom {
+                description: action.to_string(),
+            },
+        }
     }
 
-    fn generate_chapter_segment(
-        &self,
-        current_state: &PlotState,
-        action_result: &ActionResult,
-    ) -> ChapterSegment {
-        if let Some(segment) = self.generate_chapter_segment_with_llm(current_state, action_result) {
-            return self.apply_chapter_segment_rules(current_state, segment);
-        }
+    pub fn generate_plot_text(&self, current_state: &PlotState, action_result: &ActionResult) -> String {
+        self.block_on(self.generate_chapter_segment_async(current_state, action_result))
+            .map(|segment| segment.text)
+            .unwrap_or_else(|| self.generate_plot_text_fallback(current_state, action_result))
+    }
 
+    /// 无法启动运行时等续写流程根本跑不起来时的预设段落
+    fn preset_segment(&self, current_state: &PlotState, action_result: &ActionResult) -> ChapterSegment {
         let text = self.generate_plot_text_fallback(current_state, action_result);
         ChapterSegment {
+            chapter_end: fallback_chapter_end(current_state, &text),
             text,
             needs_player_input: true,
-            chapter_end: false,
             chapter_title: None,
             chapter_summary: None,
             options: vec![],
-            generation_diagnostics: Some("回退：同步剧情生成未命中 LLM，已使用预设文本".to_string()),
+            generation_diagnostics: GenerationDiagnostics::preset(
+                "回退：无法启动剧情生成运行时，已使用预设文本",
+            ),
+            generation_failure: None,
+            tuning_signal: None,
+            provenance: SegmentProvenance::fallback(FallbackKind::Preset),
+            quest_updates: QuestUpdates::default(),
         }
     }
 
@@ -594,14 +1047,60 @@ impl PlotEngine {
         current_state: &PlotState,
         action_result: &ActionResult,
     ) -> ChapterSegment {
-        let (segment_from_llm, llm_reason) = self
+        let started = Instant::now();
+        let (segment_from_llm, llm_failure) = self
             .generate_chapter_segment_with_llm_async(current_state, action_result)
             .await;
         if let Some(segment) = segment_from_llm {
+            let mut segment = self
+                .enforce_consistency(current_state, action_result, segment)
+                .await;
+            let repetition = repetition_score(&segment.text, &current_state.current_chapter.content);
+            let verdict = if repetition >= REPETITION_THRESHOLD {
+                segment.tuning_signal = Some(TuningSignal::Repetitive);
+                ValidatorVerdict::failed(REPETITION_VALIDATOR, format!("重复度 {:.2}", repetition))
+            } else {
+                ValidatorVerdict::passed(REPETITION_VALIDATOR)
+            };
+            if let Some(detail) = verdict.detail.as_ref().filter(|_| !verdict.passed) {
+                segment.generation_diagnostics.warn(format!("重复校验未通过：{}", detail));
+            }
+            segment.provenance.validator_verdicts.push(verdict);
+            segment.generation_diagnostics.latency_ms = Some(elapsed_ms(started));
             return self.apply_chapter_segment_rules(current_state, segment);
         }
-
-        if let Some(text) = self.generate_plot_text_with_llm(current_state, action_result) {
+        // 只有模型确实返回了无法使用的内容才计入模板的校验失败率。
+        let failure_signal = llm_failure
+            .as_ref()
+            .filter(|failure| {
+                matches!(
+                    failure.category,
+                    FailureCategory::InvalidResponse | FailureCategory::BudgetExceeded
+                )
+            })
+            .map(|_| TuningSignal::ValidationFailed);
+        let failed_verdicts = llm_failure
+            .as_ref()
+            .filter(|_| failure_signal.is_some())
+            .map(|failure| vec![ValidatorVerdict::failed(RESPONSE_VALIDATOR, failure.summary())])
+            .unwrap_or_default();
+
+        if let Some(text) = self
+            .generate_plot_text_with_llm(current_state, action_result)
+            .await
+        {
+            let mut generation_diagnostics = GenerationDiagnostics {
+                model: self
+                    .resolve_llm_service()
+                    .map(|service| service.model_for(LLMSubsystem::Plot).to_string()),
+                latency_ms: Some(elapsed_ms(started)),
+                parse_path: Some(ParsePath::PlainText),
+                ..GenerationDiagnostics::default()
+            };
+            if let Some(failure) = &llm_failure {
+                generation_diagnostics
+                    .warn(format!("回退：{}；已降级为纯文本续写", failure.summary()));
+            }
             return self.apply_chapter_segment_rules(
                 current_state,
                 ChapterSegment {
@@ -611,28 +1110,47 @@ impl PlotEngine {
                     chapter_title: None,
                     chapter_summary: None,
                     options: vec![],
-                    generation_diagnostics: llm_reason.map(|reason| {
-                        format!("回退：{}；已降级为纯文本续写", reason)
-                    }),
+                    generation_diagnostics,
+                    generation_failure: llm_failure,
+                    tuning_signal: failure_signal,
+                    provenance: SegmentProvenance {
+                        validator_verdicts: failed_verdicts,
+                        ..SegmentProvenance::fallback(FallbackKind::PlainText)
+                    },
+                    quest_updates: QuestUpdates::default(),
                 },
             );
         }
 
         let text = self.generate_plot_text_fallback(current_state, action_result);
-        let fallback_reason = llm_reason.unwrap_or_else(|| {
-            "LLM 续写不可用（可能无配置或返回不可解析）".to_string()
+        let failure = llm_failure.unwrap_or_else(|| {
+            GenerationFailure::new(
+                FailureCategory::InvalidResponse,
+                SEGMENT_STAGE,
+                "LLM 续写不可用（可能无配置或返回不可解析）",
+            )
         });
         ChapterSegment {
+            chapter_end: fallback_chapter_end(current_state, &text),
             text,
             needs_player_input: true,
-            chapter_end: false,
             chapter_title: None,
             chapter_summary: None,
             options: vec![],
-            generation_diagnostics: Some(format!(
-                "回退：{}；纯文本续写也失败，已使用预设文本",
-                fallback_reason
-            )),
+            generation_diagnostics: GenerationDiagnostics {
+                latency_ms: Some(elapsed_ms(started)),
+                ..GenerationDiagnostics::preset(format!(
+                    "回退：{}；纯文本续写也失败，已使用预设文本",
+                    failure.summary()
+                ))
+            },
+            generation_failure: Some(failure),
+            tuning_signal: failure_signal,
+            provenance: SegmentProvenance {
+                validator_verdicts: failed_verdicts,
+                ..SegmentProvenance::fallback(FallbackKind::Preset)
+            },
+            quest_updates: QuestUpdates::default(),
         }
     }
 
@@ -656,7 +1174,17 @@ impl PlotEngine {
             segment.needs_player_input = true;
         }
 
-        if word_count >= settings.target_chapter_words_max as usize
+        if let Some(outline) = current_state.active_outline() {
+            // 本段写完大纲最后一个节拍后才结束章节，互动点按大纲停下，模型自报的 chapter_end 与字数均不作数。
+            segment.chapter_end = outline.ends_with_next_segment();
+            if outline.current_beat().is_some_and(|beat| beat.interaction) {
+                segment.needs_player_input = true;
+            }
+        } else if settings.three_act_structure {
+            // 本段写完收束节拍后才允许结束章节，模型自报的 chapter_end 与字数均不作数。
+            segment.chapter_end =
+                beats_satisfied(current_state.current_chapter.beats_completed.saturating_add(1));
+        } else if word_count >= settings.target_chapter_words_max as usize
             && current_state.current_chapter.interaction_count >= settings.min_interactions_per_chapter
         {
             segment.chapter_end = true;
@@ -684,392 +1212,342 @@ impl PlotEngine {
         segment
     }
 
-    fn generate_chapter_segment_with_llm(
+    /// 段落与游戏状态矛盾时带上更严格的约束重写一次；重写仍矛盾或预算不足时保留原段落并记下未通过
+    async fn enforce_consistency(
         &self,
         current_state: &PlotState,
         action_result: &ActionResult,
-    ) -> Option<ChapterSegment> {
-        if cfg!(test) {
-            return None;
-        }
-        let llm_service = self.resolve_llm_service()?;
-        let settings = &current_state.settings;
-        let recent_segments = current_state
-            .current_chapter
-            .content
-            .iter()
-            .rev()
-            .take(3)
-            .cloned()
-            .collect::<Vec<String>>();
-
-        let context = PromptContext {
-            scene: Some(format!(
-                "章节 {}，玩家行动结果：{}。当前剧情片段：{}",
-                current_state.current_chapter.index,
-                action_result.description,
-                recent_segments.join(" / ")
-            )),
-            location: Some(current_state.current_scene.location.clone()),
-            actor_name: Some("player".to_string()),
-            actor_realm: None,
-            actor_combat_power: None,
-            history_events: action_result.events.clone(),
-            world_setting_summary: Some(format!(
-                "小说风格：{}；请生成一段承接剧情的小说文本。玩家每章需要 2-3 次互动。",
-                settings.novel_style
-            )),
+        mut segment: ChapterSegment,
+    ) -> ChapterSegment {
+        let Some(context) = &self.narrative_context else {
+            return segment;
         };
-
-        let constraints = PromptConstraints {
-            numerical_rules: vec![
-                "必须与行动结果保持一致".to_string(),
-                "每章需要 2-3 次玩家介入点".to_string(),
-                "章节总字数目标 5000-7000 字".to_string(),
-            ],
-            world_rules: vec![
-                "输出严格 JSON".to_string(),
-                "segment_text 必须为中文小说叙事".to_string(),
-                "segment_text 不要包含选项列表".to_string(),
-                "needs_player_input 为 true 时，必须给出 2-4 个 options".to_string(),
-                "chapter_end 仅在章节接近尾声时为 true".to_string(),
-            ],
-            output_schema_hint: Some(
-                "{\"segment_text\":\"string\",\"needs_player_input\":true|false,\"chapter_end\":true|false,\"chapter_title\":\"string\",\"chapter_summary\":\"string\",\"options\":[\"string\"]}".to_string(),
-            ),
+        let error = match self
+            .response_validator
+            .validate_consistency(&segment.text, context)
+        {
+            Ok(()) => {
+                segment
+                    .provenance
+                    .validator_verdicts
+                    .push(ValidatorVerdict::passed(CONSISTENCY_VALIDATOR));
+                return segment;
+            }
+            Err(error) => error,
         };
 
-        let prompt = self.prompt_builder.build_prompt_with_token_limit(
-            PromptTemplate::PlotGeneration,
-            &context,
-            &constraints,
-            1200,
-        );
-
-        let response = self.run_llm_request(
-            &llm_service,
-            LLMRequest {
-                prompt,
-                max_tokens: Some(900),
-                temperature: Some(0.7),
-            },
-        )?;
-
-        self.response_validator
-            .validate_response(
-                &response,
-                &ValidationConstraints {
-                    require_json: false,
-                    max_realm_level: None,
-                    min_combat_power: None,
-                    max_combat_power: None,
-                    max_current_age: None,
-                },
-            )
-            .ok()?;
-
-        if let Some(value) = self.extract_json_value(&response.text) {
-            let text = self
-                .compose_segment_text_from_json(&value)
-                .unwrap_or_default();
-            let text = self.normalize_story_text(&text);
-            let needs_player_input = value
-                .get("needs_player_input")
-                .and_then(Value::as_bool)
-                .unwrap_or(false);
-            let chapter_end = value
-                .get("chapter_end")
-                .and_then(Value::as_bool)
-                .unwrap_or(false);
-            let chapter_title = value
-                .get("chapter_title")
-                .and_then(Value::as_str)
-                .map(|s| s.trim().to_string());
-            let chapter_summary = value
-                .get("chapter_summary")
-                .and_then(Value::as_str)
-                .map(|s| s.trim().to_string());
-            let options = value
-                .get("options")
-                .and_then(Value::as_array)
-                .map(|arr| {
-                    arr.iter()
-                        .filter_map(|v| v.as_str().map(|s| s.trim().to_string()))
-                        .filter(|s| !s.is_empty())
-                        .collect::<Vec<String>>()
-                })
-                .unwrap_or_default();
-
-            if !text.is_empty() {
-                return Some(ChapterSegment {
-                    text,
-                    needs_player_input,
-                    chapter_end,
-                    chapter_title,
-                    chapter_summary,
-                    options,
-                    generation_diagnostics: None,
-                });
+        let retried = match self.budget_tokens(
+            TurnCall::ConsistencyRegeneration,
+            &segment.text,
+            CONSISTENCY_REGENERATION_TOKENS,
+        ) {
+            Some(_) => {
+                let mut rules = self.content_rules.clone();
+                rules.extend(context.prompt_rules());
+                let mut plot_engine = self.clone().with_content_rules(rules);
+                // 重写已按一致性重写计入预算，不再按续写重复扣除
+                plot_engine.set_token_budget(None);
+                plot_engine
+                    .generate_chapter_segment_with_llm_async(current_state, action_result)
+                    .await
+                    .0
+                    .filter(|retried| {
+                        self.response_validator
+                            .validate_consistency(&retried.text, context)
+                            .is_ok()
+                    })
             }
-        }
-
-        if let Some(text) = self.extract_string_field_raw(&response.text, "segment_text") {
-            let text = self.normalize_story_text(&text);
-            let needs_player_input = self
-                .extract_bool_field_raw(&response.text, "needs_player_input")
-                .unwrap_or(true);
-            let chapter_end = self
-                .extract_bool_field_raw(&response.text, "chapter_end")
-                .unwrap_or(false);
-            let chapter_title = self.extract_string_field_raw(&response.text, "chapter_title");
-            let chapter_summary = self.extract_string_field_raw(&response.text, "chapter_summary");
-            let options = self.extract_options_field_raw(&response.text);
+            None => None,
+        };
 
-            return Some(ChapterSegment {
-                text,
-                needs_player_input,
-                chapter_end,
-                chapter_title,
-                chapter_summary,
-                options,
-                generation_diagnostics: None,
-            });
+        match retried {
+            Some(mut retried) => {
+                retried.provenance.retry_count = segment.provenance.retry_count + 1;
+                retried.generation_diagnostics.retry_count =
+                    segment.generation_diagnostics.retry_count + 1;
+                retried
+                    .generation_diagnostics
+                    .warn(format!("一致性校验未通过，已约束重写：{}", error));
+                retried
+                    .provenance
+                    .validator_verdicts
+                    .push(ValidatorVerdict::passed(CONSISTENCY_VALIDATOR));
+                retried
+            }
+            None => {
+                segment
+                    .generation_diagnostics
+                    .warn(format!("一致性校验未通过，重写未能修正：{}", error));
+                segment
+                    .provenance
+                    .validator_verdicts
+                    .push(ValidatorVerdict::failed(CONSISTENCY_VALIDATOR, error.to_string()));
+                segment
+            }
         }
-
-        self.sanitize_llm_plain_text(&response.text).map(|text| ChapterSegment {
-            text: self.normalize_story_text(&text),
-            needs_player_input: true,
-            chapter_end: false,
-            chapter_title: None,
-            chapter_summary: None,
-            options: vec![],
-            generation_diagnostics: None,
-        })
     }
 
     async fn generate_chapter_segment_with_llm_async(
         &self,
         current_state: &PlotState,
         action_result: &ActionResult,
-    ) -> (Option<ChapterSegment>, Option<String>) {
+    ) -> (Option<ChapterSegment>, Option<GenerationFailure>) {
         if cfg!(test) {
             return (None, None);
         }
         let llm_service = match self.resolve_llm_service() {
             Some(service) => service,
-            None => return (None, Some("未检测到可用 LLM 配置".to_string())),
+            None => {
+                return (
+                    None,
+                    Some(GenerationFailure::new(
+                        FailureCategory::MissingConfig,
+                        SEGMENT_STAGE,
+                        "未检测到可用 LLM 配置",
+                    )),
+                )
+            }
         };
         let settings = &current_state.settings;
-        let recent_segments = current_state
+        let template = PromptTemplate::PlotGeneration;
+        let temperature = current_state.temperature_tuner.temperature(
+            &template,
+            SEGMENT_BASE_TEMPERATURE,
+            &settings.temperature_bounds,
+        );
+        let tighten = current_state.temperature_tuner.should_tighten(&template);
+        // 已写出的段落作为 assistant 历史发送，让模型把续写当作同一段对话的延续。
+        let mut history = current_state
             .current_chapter
             .content
             .iter()
             .rev()
             .take(2)
-            .cloned()
-            .collect::<Vec<String>>();
+            .map(|segment| ChatMessage::assistant(segment.clone()))
+            .collect::<Vec<ChatMessage>>();
+        history.reverse();
+        let system_prompt = format!(
+            "你是一位修仙小说作者，文风：{}。对话中的 assistant 消息是你已写出的正文，请紧接其后续写，不要重复。",
+            settings.novel_style
+        );
 
-        let context = PromptContext {
-            scene: Some(format!(
-                "章节 {}，玩家刚刚的选择是：{}。请在正文中自然写入该行动，而不是复述为“玩家行动”。当前剧情片段：{}",
-                current_state.current_chapter.index,
-                action_result.description,
-                recent_segments.join(" / ")
-            )),
-            location: Some(current_state.current_scene.location.clone()),
-            actor_name: Some("player".to_string()),
-            actor_realm: None,
-            actor_combat_power: None,
-            history_events: action_result.events.clone(),
-            world_setting_summary: Some(format!(
+        let context = self.narration_context(
+            current_state,
+            action_result,
+            format!(
+                "章节 {}，玩家刚刚的选择是：{}。请在正文中自然写入该行动，而不是复述为“玩家行动”。",
+                current_state.current_chapter.index, action_result.description,
+            ),
+            format!(
                 "小说风格：{}；请生成一段承接剧情的小说文本。玩家每章需要 2-3 次互动。",
                 settings.novel_style
-            )),
-        };
-
-        let constraints = PromptConstraints {
-            numerical_rules: vec![
-                "必须与行动结果保持一致".to_string(),
-                "每章需要 2-3 次玩家介入点".to_string(),
-                "章节总字数目标 5000-7000 字".to_string(),
-            ],
-            world_rules: vec![
-                "输出严格 JSON".to_string(),
-                "segment_text 必须为中文小说叙事".to_string(),
-                "segment_text 不要包含选项列表".to_string(),
-                "不要复述或改写已出现的段落".to_string(),
-                "每次输出 500-900 字".to_string(),
-                "needs_player_input 为 true 时，必须给出 2-4 个 options".to_string(),
-                "chapter_end 仅在章节接近尾声时为 true".to_string(),
-            ],
-            output_schema_hint: Some(
-                "{\"segment_text\":\"string\",\"needs_player_input\":true|false,\"chapter_end\":true|false,\"chapter_title\":\"string\",\"chapter_summary\":\"string\",\"options\":[\"string\"]}".to_string(),
             ),
-        };
+        );
+
+        let constraints = self.segment_constraints(false, tighten);
 
         // Keep token budget moderate while allowing complete narrative + options payload.
         let output_max = llm_service.api_config.max_tokens.clamp(320, 700);
         let prompt_limit = output_max.saturating_mul(6);
 
         let prompt = self.prompt_builder.build_prompt_with_token_limit(
-            PromptTemplate::PlotGeneration,
+            template.clone(),
             &context,
             &constraints,
             prompt_limit,
         );
-
-        let response = match tokio::time::timeout(
+        let sent_text = history
+            .iter()
+            .map(|message| message.content.as_str())
+            .chain([system_prompt.as_str(), prompt.as_str()])
+            .collect::<Vec<&str>>()
+            .join("\n");
+        let output_max = self
+            .budget_tokens(TurnCall::Narration, &sent_text, output_max)
+            .unwrap_or(output_max);
+
+        let mut regenerated = false;
+        let request = LLMChatRequest {
+            system_prompt: Some(system_prompt.clone()),
+            history: history.clone(),
+            prompt: prompt.clone(),
+            max_tokens: Some(output_max),
+            temperature: Some(temperature),
+            subsystem: LLMSubsystem::Plot,
+            retry_policy: None,
+        };
+        let mut prompt_hash = request.prompt_hash();
+        let structured = match tokio::time::timeout(
             Duration::from_secs(45),
-            llm_service.generate(LLMRequest {
-                prompt: prompt.clone(),
-                max_tokens: Some(output_max),
-                temperature: Some(0.7),
-            }),
+            llm_service.generate_structured::<SegmentPayload>(request),
         )
         .await
         {
             Ok(Ok(resp)) => resp,
             _ => {
+                regenerated = true;
                 let retry_prompt = self.prompt_builder.build_prompt_with_token_limit(
                     PromptTemplate::PlotGeneration,
                     &context,
-                    &PromptConstraints {
-                        numerical_rules: vec![
-                            "必须与行动结果保持一致".to_string(),
-                        ],
-                        world_rules: vec![
-                            "输出严格 JSON".to_string(),
-                            "segment_text 必须为中文小说叙事".to_string(),
-                            "segment_text 不要包含选项列表".to_string(),
-                            "不要复述或改写已出现的段落".to_string(),
-                            "每次输出 300-600 字".to_string(),
-                            "needs_player_input 为 true 时，必须给出 2-4 个 options".to_string(),
-                        ],
-                        output_schema_hint: Some(
-                            "{\"segment_text\":\"string\",\"needs_player_input\":true|false,\"chapter_end\":true|false,\"chapter_title\":\"string\",\"chapter_summary\":\"string\",\"options\":[\"string\"]}".to_string(),
-                        ),
-                    },
+                    &self.segment_constraints(true, tighten),
                     output_max.saturating_mul(3),
                 );
+                let retry_max = output_max.saturating_div(2).max(240);
+                let retry_max = self
+                    .budget_tokens(TurnCall::Narration, &retry_prompt, retry_max)
+                    .unwrap_or(retry_max);
+                let retry_request = LLMChatRequest {
+                    system_prompt: Some(system_prompt),
+                    history,
+                    prompt: retry_prompt,
+                    max_tokens: Some(retry_max),
+                    temperature: Some(temperature),
+                    subsystem: LLMSubsystem::Plot,
+                    retry_policy: None,
+                };
+                prompt_hash = retry_request.prompt_hash();
                 match tokio::time::timeout(
                     Duration::from_secs(30),
-                    llm_service.generate(LLMRequest {
-                        prompt: retry_prompt,
-                        max_tokens: Some(output_max.saturating_div(2).max(240)),
-                        temperature: Some(0.7),
-                    }),
+                    llm_service.generate_structured::<SegmentPayload>(retry_request),
                 )
                 .await
                 {
                     Ok(Ok(resp)) => resp,
-                    _ => return (None, Some("LLM 结构化剧情生成超时或请求失败".to_string())),
+                    Ok(Err(err)) => {
+                        return (None, Some(GenerationFailure::from_llm_error(SEGMENT_STAGE, &err)))
+                    }
+                    Err(_) => {
+                        return (
+                            None,
+                            Some(GenerationFailure::new(
+                                FailureCategory::Timeout,
+                                SEGMENT_STAGE,
+                                "LLM 结构化剧情生成超时",
+                            )),
+                        )
+                    }
                 }
             }
         };
 
-        if self
-            .response_validator
-            .validate_response(
-                &response,
-                &ValidationConstraints {
-                    require_json: false,
-                    max_realm_level: None,
-                    min_combat_power: None,
-                    max_combat_power: None,
-                    max_current_age: None,
-                },
-            )
-            .is_err()
-        {
-            return (None, Some("LLM 返回内容校验失败".to_string()));
-        }
-
-        if let Some(value) = self.extract_json_value(&response.text) {
-            let text = self
-                .compose_segment_text_from_json(&value)
-                .unwrap_or_default();
-            let text = self.normalize_story_text(&text);
-            let needs_player_input = value
-                .get("needs_player_input")
-                .and_then(Value::as_bool)
-                .unwrap_or(false);
-            let chapter_end = value
-                .get("chapter_end")
-                .and_then(Value::as_bool)
-                .unwrap_or(false);
-            let chapter_title = value
-                .get("chapter_title")
-                .and_then(Value::as_str)
-                .map(|s| s.trim().to_string());
-            let chapter_summary = value
-                .get("chapter_summary")
-                .and_then(Value::as_str)
-                .map(|s| s.trim().to_string());
-            let options = value
-                .get("options")
-                .and_then(Value::as_array)
-                .map(|arr| {
-                    arr.iter()
-                        .filter_map(|v| v.as_str().map(|s| s.trim().to_string()))
-                        .filter(|s| !s.is_empty())
-                        .collect::<Vec<String>>()
-                })
-                .unwrap_or_default();
-
-            if !text.is_empty() {
-                return (Some(ChapterSegment {
-                    text,
-                    needs_player_input,
-                    chapter_end,
-                    chapter_title,
-                    chapter_summary,
-                    options,
-                    generation_diagnostics: None,
-                }), None);
-            }
+        if let Err(err) = self.response_validator.validate_response(
+            &structured.response,
+            &ValidationConstraints {
+                require_json: false,
+                max_realm_level: None,
+                min_combat_power: None,
+                max_combat_power: None,
+                max_current_age: None,
+            },
+        ) {
+            return (None, Some(GenerationFailure::from_validation_error(SEGMENT_STAGE, &err)));
         }
 
-        if let Some(text) = self.extract_string_field_raw(&response.text, "segment_text") {
-            let text = self.normalize_story_text(&text);
-            let needs_player_input = self
-                .extract_bool_field_raw(&response.text, "needs_player_input")
-                .unwrap_or(true);
-            let chapter_end = self
-                .extract_bool_field_raw(&response.text, "chapter_end")
-                .unwrap_or(false);
-            let chapter_title = self.extract_string_field_raw(&response.text, "chapter_title");
-            let chapter_summary = self.extract_string_field_raw(&response.text, "chapter_summary");
-            let options = self.extract_options_field_raw(&response.text);
-
-            return (Some(ChapterSegment {
-                text,
-                needs_player_input,
-                chapter_end,
-                chapter_title,
-                chapter_summary,
-                options,
-                generation_diagnostics: None,
-            }), None);
-        }
-
-        match self.sanitize_llm_plain_text(&response.text) {
-            Some(text) => (
-                Some(ChapterSegment {
-                    text: self.normalize_story_text(&text),
-                    needs_player_input: true,
-                    chapter_end: false,
-                    chapter_title: None,
-                    chapter_summary: None,
-                    options: vec![],
-                    generation_diagnostics: None,
-                }),
+        let signal = if regenerated {
+            TuningSignal::Regenerated
+        } else {
+            TuningSignal::Accepted
+        };
+        let provenance = SegmentProvenance {
+            model: Some(llm_service.model_for(LLMSubsystem::Plot).to_string()),
+            temperature: Some(temperature),
+            prompt_hash: Some(prompt_hash),
+            retry_count: u32::from(regenerated),
+            validator_verdicts: vec![ValidatorVerdict::passed(RESPONSE_VALIDATOR)],
+            ..SegmentProvenance::default()
+        };
+        // 输出被 max_tokens 截断时通常无法解析，提示用户调整预算而不是换模型。
+        let category = if structured.response.finish_reason.as_deref() == Some("length") {
+            FailureCategory::BudgetExceeded
+        } else {
+            FailureCategory::InvalidResponse
+        };
+        match self.chapter_segment_from_response(structured, Some(signal), provenance) {
+            Some(segment) => (Some(segment), None),
+            None => (
                 None,
+                Some(GenerationFailure::new(
+                    category,
+                    SEGMENT_STAGE,
+                    "LLM 返回内容无法解析为剧情文本",
+                )),
+            ),
+        }
+    }
+
+    /// 续写的上下文：在游戏状态上下文之上补充本段的场景、按行动检索的设定与本回合事件
+    fn narration_context(
+        &self,
+        current_state: &PlotState,
+        action_result: &ActionResult,
+        scene: String,
+        guidance: String,
+    ) -> PromptContext {
+        let game_context = &self.game_context;
+        PromptContext {
+            scene: Some(scene),
+            location: Some(current_state.current_scene.location.clone()),
+            environment: game_context.environment.clone(),
+            actor_name: game_context
+                .actor_name
+                .clone()
+                .or_else(|| Some("player".to_string())),
+            player_persona: current_state.player_persona.summary(),
+            canon_facts: current_state.canon_facts.prompt_lines(
+                &format!("{} {}", action_result.description, current_state.current_scene.description),
+                MAX_PROMPT_FACTS,
+            ),
+            story_beat: current_state.story_arc.as_ref().and_then(StoryArc::prompt_line),
+            chapter_beat: current_state.chapter_beat_line(),
+            history_events: game_context
+                .history_events
+                .iter()
+                .chain(&action_result.events)
+                .cloned()
+                .collect(),
+            world_setting_summary: Some(match &game_context.world_setting_summary {
+                Some(world) => format!("{}；{}", world, guidance),
+                None => guidance,
+            }),
+            ..game_context.clone()
+        }
+    }
+
+    /// 续写的输出约束；超时重试时缩短篇幅并省去章节与任务要求，内容规则始终保留
+    fn segment_constraints(&self, retry: bool, tighten: bool) -> PromptConstraints {
+        let mut numerical_rules = vec!["必须与行动结果保持一致".to_string()];
+        let mut world_rules = vec![
+            "输出严格 JSON".to_string(),
+            format!("segment_text 必须为{}小说叙事", self.prompt_builder.language().prompt_name()),
+            "segment_text 不要包含选项列表".to_string(),
+            "不要复述或改写已出现的段落".to_string(),
+            if retry { "每次输出 300-600 字" } else { "每次输出 500-900 字" }.to_string(),
+            "needs_player_input 为 true 时，必须给出 2-4 个 options".to_string(),
+        ];
+        if !retry {
+            numerical_rules.extend([
+                "每章需要 2-3 次玩家介入点".to_string(),
+                "章节总字数目标 5000-7000 字".to_string(),
+            ]);
+            world_rules.extend([
+                "chapter_end 仅在章节接近尾声时为 true".to_string(),
+                "剧情出现新的明确目标时写入 new_quests，达成 ActiveQuests 中的目标时将原名写入 completed_quests".to_string(),
+            ]);
+        }
+        world_rules.extend(self.content_rules.iter().cloned());
+        if tighten {
+            world_rules.extend(TIGHTENED_JSON_RULES.iter().map(|rule| rule.to_string()));
+        }
+        PromptConstraints {
+            numerical_rules,
+            world_rules,
+            output_schema_hint: Some(
+                "{\"segment_text\":\"string\",\"needs_player_input\":true|false,\"chapter_end\":true|false,\"chapter_title\":\"string\",\"chapter_summary\":\"string\",\"options\":[\"string\"],\"new_quests\":[\"string\"],\"completed_quests\":[\"string\"]}".to_string(),
             ),
-            None => (None, Some("LLM 返回内容无法解析为剧情文本".to_string())),
         }
     }
 
-    fn generate_plot_text_with_llm(
+    async fn generate_plot_text_with_llm(
         &self,
         current_state: &PlotState,
         action_result: &ActionResult,
@@ -1080,40 +1558,41 @@ impl PlotEngine {
         let llm_service = self.resolve_llm_service()?;
         let prompt = self.prompt_builder.build_prompt_with_token_limit(
             PromptTemplate::PlotGeneration,
-            &PromptContext {
-                scene: Some(format!(
+            &self.narration_context(
+                current_state,
+                action_result,
+                format!(
                     "承接上一段剧情，并自然写入玩家刚刚选择：{}。上一段内容：{}",
-                    action_result.description,
-                    current_state.current_scene.description
-                )),
-                location: Some(current_state.current_scene.location.clone()),
-                actor_name: Some("player".to_string()),
-                actor_realm: None,
-                actor_combat_power: None,
-                history_events: action_result.events.clone(),
-                world_setting_summary: Some("修仙小说风格，强调场景、事件与 NPC 反应".to_string()),
-            },
+                    action_result.description, current_state.current_scene.description
+                ),
+                "修仙小说风格，强调场景、事件与 NPC 反应".to_string(),
+            ),
             &PromptConstraints {
                 numerical_rules: vec!["必须与行动结果保持一致".to_string()],
                 world_rules: vec![
                     "仅输出纯文本".to_string(),
                     "使用简洁的小说叙事".to_string(),
-                    "必须使用中文".to_string(),
+                    format!("必须使用{}", self.prompt_builder.language().prompt_name()),
                     "控制在 220-420 字".to_string(),
                 ],
                 output_schema_hint: None,
             },
-            360,
+            360 + DEFAULT_CONTEXT_TOKENS,
         );
 
-        let response = self.run_llm_request(
-            &llm_service,
-            LLMRequest {
+        let response = tokio::time::timeout(
+            Duration::from_secs(45),
+            llm_service.generate(LLMRequest {
                 prompt,
                 max_tokens: Some(280),
                 temperature: Some(0.7),
-            },
-        )?;
+                subsystem: LLMSubsystem::Plot,
+                retry_policy: None,
+            }),
+        )
+        .await
+        .ok()?
+        .ok()?;
 
         self.response_validator
             .validate_response(
@@ -1127,12 +1606,12 @@ impl PlotEngine {
                 },
             )
             .ok()?;
-        if let Some(value) = self.extract_json_value(&response.text) {
-            if let Some(text) = self.compose_segment_text_from_json(&value) {
-                let normalized = self.normalize_story_text(&text);
-                if !normalized.is_empty() {
-                    return Some(normalized);
-                }
+        if let Some(text) = parse_structured::<SegmentPayload>(&response.text)
+            .and_then(|payload| payload.text())
+        {
+            let normalized = self.normalize_story_text(&text);
+            if !normalized.is_empty() {
+                return Some(normalized);
             }
         }
         self.sanitize_llm_plain_text(&response.text)
@@ -1141,30 +1620,57 @@ impl PlotEngine {
     }
 
     fn generate_plot_text_fallback(&self, current_state: &PlotState, action_result: &ActionResult) -> String {
-        let event_line = if action_result.events.is_empty() {
-            String::new()
-        } else {
-            format!("随后传来的动静与风声里，{}。", action_result.events.join("；"))
+        let location = &current_state.current_scene.location;
+        let text = match self.language() {
+            ScriptLanguage::Zh => {
+                let event_line = if action_result.events.is_empty() {
+                    String::new()
+                } else {
+                    format!("随后传来的动静与风声里，{}。", action_result.events.join("；"))
+                };
+                format!("在{}，你{}。{}", location, action_result.description, event_line)
+            }
+            ScriptLanguage::En => {
+                let event_line = if action_result.events.is_empty() {
+                    String::new()
+                } else {
+                    format!(
+                        "Amid the stirring wind that follows, {}.",
+                        action_result.events.join("; ")
+                    )
+                };
+                format!(
+                    "At {}, you act: {}. {}",
+                    location, action_result.description, event_line
+                )
+            }
         };
-
-        format!(
-            "在{}，你{}。{}",
-            current_state.current_scene.location,
-            action_result.description,
-            event_line
-        )
-        .trim()
-        .to_string()
+        text.trim().to_string()
     }
 
+    /// 同步入口：阻塞等待与 `generate_opening_plot_async` 相同的开篇流程
     pub fn generate_opening_plot(
         &self,
         player_name: &str,
         realm_name: &str,
         spiritual_root: &str,
         location: &str,
-    ) -> String {
-        self.generate_opening_plot_fallback(player_name, realm_name, spiritual_root, location)
+    ) -> OpeningPlot {
+        self.block_on(self.generate_opening_plot_async(
+            player_name,
+            realm_name,
+            spiritual_root,
+            location,
+        ))
+        .unwrap_or_else(|| OpeningPlot {
+            text: self.generate_opening_plot_fallback(
+                player_name,
+                realm_name,
+                spiritual_root,
+                location,
+            ),
+            options: vec![],
+        })
     }
 
     pub async fn generate_opening_plot_async(
@@ -1216,91 +1722,128 @@ impl PlotEngine {
         spiritual_root: &str,
         location: &str,
     ) -> String {
-        format!(
-            "【开篇】{}初入修行之路，身负{}，当前境界为{}。你站在{}，四周灵气浮动，机缘与风险并存。你决定先从何处入手？",
-            player_name, spiritual_root, realm_name, location
-        )
+        if self.story_recap.is_some() {
+            return match self.language() {
+                ScriptLanguage::Zh => format!(
+                    "【接续】前尘往事历历在目。{}身负{}，当前境界为{}，此刻正站在{}，接下来的路由你来走。你决定先从何处入手？",
+                    player_name, spiritual_root, realm_name, location
+                ),
+                ScriptLanguage::En => format!(
+                    "[Continuation] The story so far weighs on {}, who bears {} and stands at the {} realm. You are in {}, and from here on the path is yours. Where will you begin?",
+                    player_name, spiritual_root, realm_name, location
+                ),
+            };
+        }
+        match self.language() {
+            ScriptLanguage::Zh => format!(
+                "【开篇】{}初入修行之路，身负{}，当前境界为{}。你站在{}，四周灵气浮动，机缘与风险并存。你决定先从何处入手？",
+                player_name, spiritual_root, realm_name, location
+            ),
+            ScriptLanguage::En => format!(
+                "[Prologue] {} sets foot on the path of cultivation, bearing {} and currently at the {} realm. You stand in {}, where spiritual energy drifts all around and fortune walks hand in hand with danger. Where will you begin?",
+                player_name, spiritual_root, realm_name, location
+            ),
+        }
     }
 
-    async fn generate_opening_plot_with_llm_async(
+    /// 开篇的提示词；超时重试时缩短篇幅，接续前情时仍要求接着写
+    fn opening_prompt(
         &self,
         player_name: &str,
         realm_name: &str,
         spiritual_root: &str,
         location: &str,
-    ) -> Option<OpeningPlot> {
-        let llm_service = self.resolve_llm_service()?;
-        let output_max = llm_service.api_config.max_tokens.clamp(120, 420);
-        let prompt_limit = output_max.saturating_mul(6);
-
-        let prompt = self.prompt_builder.build_prompt_with_token_limit(
+        retry: bool,
+        prompt_limit: u32,
+    ) -> String {
+        let scene = match (self.story_recap.is_some(), retry) {
+            (true, _) => "请接续前情提要，写主角接手故事后的第一段剧情，不要重复前情，并在结尾抛出行动选择点",
+            (false, false) => "请生成修仙小说的第一段开篇剧情，并在结尾抛出行动选择点",
+            (false, true) => "生成修仙小说开篇，保持简洁但有画面感",
+        };
+        let language = self.prompt_builder.language().prompt_name();
+        self.prompt_builder.build_prompt_with_token_limit(
             PromptTemplate::PlotGeneration,
             &PromptContext {
-                scene: Some("请生成修仙小说的第一段开篇剧情，并在结尾抛出行动选择点".to_string()),
+                scene: Some(scene.to_string()),
                 location: Some(location.to_string()),
                 actor_name: Some(player_name.to_string()),
                 actor_realm: Some(realm_name.to_string()),
                 actor_combat_power: None,
-                history_events: vec![],
+                player_persona: None,
+                canon_facts: Vec::new(),
+                story_beat: None,
+                chapter_beat: None,
+                environment: None,
+                active_quests: Vec::new(),
+                relationships: Vec::new(),
+                status_effects: Vec::new(),
+                history_events: self.story_recap.iter().cloned().collect(),
                 world_setting_summary: Some(format!("主角灵根：{}", spiritual_root)),
             },
             &PromptConstraints {
                 numerical_rules: vec!["不得出现跨境界夸张成长".to_string()],
                 world_rules: vec![
                     "输出严格 JSON".to_string(),
-                    "必须是中文".to_string(),
-                    "segment_text 为中文小说叙事，不能包含选项列表".to_string(),
+                    format!("必须是{}", language),
+                    format!("segment_text 为{}小说叙事，不能包含选项列表", language),
                     "options 必须为 2-4 条简洁选项".to_string(),
-                    "长度控制在 200 到 380 字".to_string(),
+                    if retry { "长度控制在 160 到 260 字" } else { "长度控制在 200 到 380 字" }
+                        .to_string(),
                 ],
                 output_schema_hint: Some(
                     "{\"segment_text\":\"string\",\"options\":[\"string\"]}".to_string(),
                 ),
             },
             prompt_limit,
+        )
+    }
+
+    async fn generate_opening_plot_with_llm_async(
+        &self,
+        player_name: &str,
+        realm_name: &str,
+        spiritual_root: &str,
+        location: &str,
+    ) -> Option<OpeningPlot> {
+        let llm_service = self.resolve_llm_service()?;
+        let output_max = llm_service.api_config.max_tokens.clamp(120, 420);
+        let prompt = self.opening_prompt(
+            player_name,
+            realm_name,
+            spiritual_root,
+            location,
+            false,
+            output_max.saturating_mul(6),
         );
 
-        let response = match llm_service
-            .generate(LLMRequest {
-                prompt: prompt.clone(),
+        let structured = match llm_service
+            .generate_structured::<OpeningPayload>(LLMRequest {
+                prompt,
                 max_tokens: Some(output_max),
                 temperature: Some(0.7),
+                subsystem: LLMSubsystem::Plot,
+                retry_policy: None,
             })
             .await
         {
             Ok(resp) => resp,
             Err(_) => {
-                let retry_prompt = self.prompt_builder.build_prompt_with_token_limit(
-                    PromptTemplate::PlotGeneration,
-                    &PromptContext {
-                        scene: Some("生成修仙小说开篇，保持简洁但有画面感".to_string()),
-                        location: Some(location.to_string()),
-                        actor_name: Some(player_name.to_string()),
-                        actor_realm: Some(realm_name.to_string()),
-                        actor_combat_power: None,
-                        history_events: vec![],
-                        world_setting_summary: Some(format!("主角灵根：{}", spiritual_root)),
-                    },
-                    &PromptConstraints {
-                        numerical_rules: vec!["不得出现跨境界夸张成长".to_string()],
-                        world_rules: vec![
-                            "输出严格 JSON".to_string(),
-                            "必须是中文".to_string(),
-                            "segment_text 为中文小说叙事，不能包含选项列表".to_string(),
-                            "options 必须为 2-4 条简洁选项".to_string(),
-                            "长度控制在 160 到 260 字".to_string(),
-                        ],
-                        output_schema_hint: Some(
-                            "{\"segment_text\":\"string\",\"options\":[\"string\"]}".to_string(),
-                        ),
-                    },
+                let retry_prompt = self.opening_prompt(
+                    player_name,
+                    realm_name,
+                    spiritual_root,
+                    location,
+                    true,
                     output_max.saturating_mul(3),
                 );
                 llm_service
-                    .generate(LLMRequest {
+                    .generate_structured::<OpeningPayload>(LLMRequest {
                         prompt: retry_prompt,
                         max_tokens: Some(output_max.saturating_div(2).max(120)),
                         temperature: Some(0.7),
+                        subsystem: LLMSubsystem::Plot,
+                        retry_policy: None,
                     })
                     .await
                     .ok()?
@@ -1309,7 +1852,7 @@ impl PlotEngine {
 
         self.response_validator
             .validate_response(
-                &response,
+                &structured.response,
                 &ValidationConstraints {
                     require_json: false,
                     max_realm_level: None,
@@ -1320,53 +1863,30 @@ impl PlotEngine {
             )
             .ok()?;
 
-        if let Some(value) = self.extract_json_value(&response.text) {
-            let mut text = value
-                .get("segment_text")
-                .and_then(Value::as_str)
-                .unwrap_or_default()
-                .trim()
-                .to_string();
-
+        let raw = structured.response.text.as_str();
+        if let Some(payload) = structured.data {
+            let mut text = payload.segment_text.trim().to_string();
             if text.is_empty() {
-                let scene = value
-                    .get("scene")
-                    .and_then(Value::as_str)
-                    .unwrap_or_default()
-                    .trim();
-                let status = value
-                    .get("current_status")
-                    .and_then(Value::as_str)
-                    .unwrap_or_default()
-                    .trim();
+                let scene = payload.scene.trim();
+                let status = payload.current_status.trim();
                 if !scene.is_empty() || !status.is_empty() {
                     text = format!("{}{}", scene, if status.is_empty() { "".to_string() } else { format!("\n\n{}", status) });
                 }
             }
-
-            let options = value
-                .get("options")
-                .or_else(|| value.get("action_choices"))
-                .and_then(Value::as_array)
-                .map(|arr| {
-                    arr.iter()
-                        .filter_map(|v| v.as_str().map(|s| s.trim().to_string()))
-                        .filter(|s| !s.is_empty())
-                        .collect::<Vec<String>>()
-                })
-                .unwrap_or_default();
-
             if !text.is_empty() {
-                return Some(OpeningPlot { text, options });
+                return Some(OpeningPlot {
+                    text,
+                    options: trimmed_items(payload.options),
+                });
             }
         }
 
-        if let Some(text) = self.extract_string_field_raw(&response.text, "segment_text") {
-            let options = self.extract_options_field_raw(&response.text);
+        if let Some(text) = self.extract_string_field_raw(raw, "segment_text") {
+            let options = self.extract_options_field_raw(raw);
             return Some(OpeningPlot { text, options });
         }
 
-        let text = response.text.trim().to_string();
+        let text = raw.trim().to_string();
         if text.is_empty() {
             return None;
         }
@@ -1387,23 +1907,26 @@ impl PlotEngine {
         // Cultivate option
         options.push(PlayerOption {
             id: option_id,
+            uid: new_option_uid(),
             description: "静心修炼，稳固境界".to_string(),
             requirements: vec![],
             action: Action::Cultivate,
         });
         option_id += 1;
 
-        // Breakthrough option if sub-level is less than 3
-        if character.cultivation_realm.sub_level < 3 {
+        // Breakthrough option while the realm ladder has a stage left
+        let ladder = self.numerical_system.realm_ladder();
+        if !ladder.is_peak(&character.cultivation_realm) {
             options.push(PlayerOption {
                 id: option_id,
+                uid: new_option_uid(),
                 description: format!(
                     "尝试突破 {}",
                     character.cultivation_realm.name
                 ),
                 requirements: vec![format!(
-                    "当前境界：{}（小层级 {}）",
-                    character.cultivation_realm.name, character.cultivation_realm.sub_level
+                    "当前境界：{}",
+                    ladder.display_name(&character.cultivation_realm)
                 )],
                 action: Action::Breakthrough,
             });
@@ -1413,16 +1936,48 @@ impl PlotEngine {
         // Rest option
         options.push(PlayerOption {
             id: option_id,
+            uid: new_option_uid(),
             description: "调息休整，恢复状态".to_string(),
             requirements: vec![],
             action: Action::Rest,
         });
         option_id += 1;
 
+        // Technique option: learn a new technique, or keep practicing a learned one
+        if let Some(technique) = self.numerical_system.learnable_techniques(character).next() {
+            options.push(PlayerOption {
+                id: option_id,
+                uid: new_option_uid(),
+                description: format!("修习《{}》", technique.name),
+                requirements: vec![format!("境界要求：第 {} 境界", technique.required_realm_level)],
+                action: Action::LearnTechnique {
+                    technique_id: technique.id.clone(),
+                },
+            });
+            option_id += 1;
+        } else if let Some(technique) = character
+            .techniques
+            .iter()
+            .filter(|t| !t.is_mastered())
+            .min_by_key(|t| t.proficiency)
+        {
+            options.push(PlayerOption {
+                id: option_id,
+                uid: new_option_uid(),
+                description: format!("勤练《{}》", technique.name),
+                requirements: vec![format!("当前熟练度：{}", technique.proficiency_name())],
+                action: Action::PracticeTechnique {
+                    technique_id: technique.id.clone(),
+                },
+            });
+            option_id += 1;
+        }
+
         // Location-specific options
         if scene.location == "azure_cloud_sect" || scene.location == "sect" {
             options.push(PlayerOption {
                 id: option_id,
+                uid: new_option_uid(),
                 description: "前往宗门藏经阁".to_string(),
                 requirements: vec![],
                 action: Action::Custom {
@@ -1433,6 +1988,7 @@ impl PlotEngine {
         } else if scene.location == "city" {
             options.push(PlayerOption {
                 id: option_id,
+                uid: new_option_uid(),
                 description: "前往坊市探查消息".to_string(),
                 requirements: vec![],
                 action: Action::Custom {
@@ -1446,6 +2002,7 @@ impl PlotEngine {
         if options.len() < 2 {
             options.push(PlayerOption {
                 id: option_id,
+                uid: new_option_uid(),
                 description: "盘坐冥想，梳理思绪".to_string(),
                 requirements: vec![],
                 action: Action::Custom {
@@ -1468,16 +2025,54 @@ impl PlotEngine {
             return None;
         }
         let llm_service = self.resolve_llm_service()?;
+        let request = self.option_generation_request(scene, character)?;
+        let structured = self.run_structured_request::<OptionsPayload>(&llm_service, request)?;
+        self.options_from_payload(structured)
+    }
+
+    /// 与剧情续写并行生成选项时使用的异步版本
+    pub async fn generate_player_options_with_llm_async(
+        &self,
+        scene: &Scene,
+        character: &CharacterStats,
+    ) -> Option<Vec<PlayerOption>> {
+        if cfg!(test) {
+            return None;
+        }
+        let llm_service = self.resolve_llm_service()?;
+        let request = self.option_generation_request(scene, character)?;
+        let structured = tokio::time::timeout(
+            Duration::from_secs(45),
+            llm_service.generate_structured::<OptionsPayload>(request),
+        )
+        .await
+        .ok()?
+        .ok()?;
+        self.options_from_payload(structured)
+    }
+
+    fn option_generation_request(
+        &self,
+        scene: &Scene,
+        character: &CharacterStats,
+    ) -> Option<LLMRequest> {
         let prompt = self.prompt_builder.build_prompt_with_token_limit(
             PromptTemplate::OptionGeneration,
             &PromptContext {
                 scene: Some(scene.description.clone()),
                 location: Some(scene.location.clone()),
-                actor_name: Some("player".to_string()),
+                actor_name: self
+                    .game_context
+                    .actor_name
+                    .clone()
+                    .or_else(|| Some("player".to_string())),
                 actor_realm: Some(character.cultivation_realm.name.clone()),
                 actor_combat_power: Some(character.combat_power),
-                history_events: Vec::new(),
-                world_setting_summary: Some("基于当前剧情生成玩家可执行选项".to_string()),
+                world_setting_summary: Some(match &self.game_context.world_setting_summary {
+                    Some(world) => format!("{}；基于当前剧情生成玩家可执行选项", world),
+                    None => "基于当前剧情生成玩家可执行选项".to_string(),
+                }),
+                ..self.game_context.clone()
             },
             &PromptConstraints {
                 numerical_rules: vec![
@@ -1488,39 +2083,32 @@ impl PlotEngine {
                     "优先输出严格 JSON".to_string(),
                     "字段为 options 或 action_choices".to_string(),
                     "每条选项不超过 24 字".to_string(),
-                    "只输出中文".to_string(),
+                    format!("只输出{}", self.prompt_builder.language().prompt_name()),
                 ],
                 output_schema_hint: Some(
                     "{\"options\":[\"string\",\"string\"]}".to_string(),
                 ),
             },
-            280,
+            280 + DEFAULT_CONTEXT_TOKENS,
         );
+        let max_tokens = self.budget_tokens(TurnCall::OptionGeneration, &prompt, 220)?;
+
+        Some(LLMRequest {
+            prompt,
+            max_tokens: Some(max_tokens),
+            temperature: Some(0.6),
+            subsystem: LLMSubsystem::Plot,
+            retry_policy: None,
+        })
+    }
 
-        let response = self.run_llm_request(
-            &llm_service,
-            LLMRequest {
-                prompt,
-                max_tokens: Some(220),
-                temperature: Some(0.6),
-            },
-        )?;
-
-        let mut texts = if let Some(value) = self.extract_json_value(&response.text) {
-            value
-                .get("options")
-                .or_else(|| value.get("action_choices"))
-                .and_then(Value::as_array)
-                .map(|arr| {
-                    arr.iter()
-                        .filter_map(|v| v.as_str().map(|s| s.trim().to_string()))
-                        .filter(|s| !s.is_empty())
-                        .take(4)
-                        .collect::<Vec<String>>()
-                })
-                .unwrap_or_default()
-        } else {
-            self.extract_options_field_raw(&response.text)
+    fn options_from_payload(
+        &self,
+        structured: StructuredResponse<OptionsPayload>,
+    ) -> Option<Vec<PlayerOption>> {
+        let mut texts = match structured.data {
+            Some(payload) => trimmed_items(payload.options),
+            None => self.extract_options_field_raw(&structured.response.text),
         };
 
         if texts.len() < 2 {
@@ -1533,6 +2121,7 @@ impl PlotEngine {
             .enumerate()
             .map(|(idx, text)| PlayerOption {
                 id: idx,
+                uid: new_option_uid(),
                 description: text.clone(),
                 requirements: vec![],
                 action: Action::Custom { description: text },
@@ -1547,16 +2136,7 @@ impl PlotEngine {
         available_options: &[PlayerOption],
     ) -> Result<(), String> {
         match action.action_type {
-            ActionType::SelectedOption => {
-                if let Some(option_id) = action.selected_option_id {
-                    if option_id >= available_options.len() {
-                        return Err(format!("无效的选项 ID：{}", option_id));
-                    }
-                    Ok(())
-                } else {
-                    Err("选择选项时必须提供选项 ID".to_string())
-                }
-            }
+            ActionType::SelectedOption => selected_option_index(action, available_options).map(|_| ()),
             ActionType::FreeText => {
                 if let Some(meta) = &action.meta {
                     if meta.action_kind.as_deref() == Some("continue") {
@@ -1584,26 +2164,32 @@ impl PlotEngine {
     ) -> Result<ActionResult, String> {
         self.validate_player_action(action, available_options)?;
 
+        match self.interpret_player_action(action, character, available_options, context)? {
+            Some(interpreted_action) => Ok(self.numerical_system.calculate_action_result(
+                character,
+                &interpreted_action,
+                context,
+            )),
+            None => Ok(ActionResult {
+                success: true,
+                description: action.content.clone(),
+                stat_changes: vec![],
+                events: vec![],
+            }),
+        }
+    }
+
+    /// 把玩家行动解析为数值系统可判定的行动，不做合理性校验
+    pub fn interpret_player_action(
+        &self,
+        action: &PlayerAction,
+        character: &CharacterStats,
+        available_options: &[PlayerOption],
+        context: &Context,
+    ) -> Result<Option<Action>, String> {
         match action.action_type {
-            ActionType::SelectedOption => {
-                let option_id = action.selected_option_id.unwrap();
-                if option_id < available_options.len() {
-                    let selected_option = &available_options[option_id];
-                    let result = self.numerical_system.calculate_action_result(
-                        character,
-                        &selected_option.action,
-                        context,
-                    );
-                    Ok(result)
-                } else {
-                    Ok(ActionResult {
-                        success: true,
-                        description: action.content.clone(),
-                        stat_changes: vec![],
-                        events: vec![],
-                    })
-                }
-            }
+            ActionType::SelectedOption => Ok(selected_option_index(action, available_options)?
+                .map(|option_id| available_options[option_id].action.clone())),
             ActionType::FreeText => {
                 let interpreted_action = if action.meta.as_ref().and_then(|m| m.action_kind.as_deref()) == Some("continue") {
                     Action::Custom {
@@ -1616,15 +2202,45 @@ impl PlotEngine {
                 } else {
                     self.interpret_free_text_action(&action.content, character, context)
                 };
-                Ok(self.numerical_system.calculate_action_result(
-                    character,
-                    &interpreted_action,
-                    context,
-                ))
+                Ok(Some(interpreted_action))
             }
         }
     }
 
+    /// 玩家预先写下的行动：按自由输入的规则校验合理性，解析出行动后作为新选项，编号接在当前选项之后
+    pub fn custom_option(
+        &self,
+        description: &str,
+        character: &CharacterStats,
+        available_options: &[PlayerOption],
+        context: &Context,
+    ) -> Result<PlayerOption, String> {
+        self.validate_free_text_input(description)?;
+        let description = description.trim();
+        if self
+            .find_matching_option_by_text(description, available_options)
+            .is_some()
+        {
+            return Err("当前选项中已有相同的行动".to_string());
+        }
+        let custom_count = available_options
+            .iter()
+            .filter(|option| option.requirements.iter().any(|r| r == CUSTOM_OPTION_REQUIREMENT))
+            .count();
+        if custom_count >= MAX_CUSTOM_OPTIONS {
+            return Err(format!("每个决策点最多添加 {} 个自拟选项", MAX_CUSTOM_OPTIONS));
+        }
+        self.validate_free_text_reasonableness(description, available_options)?;
+
+        Ok(PlayerOption {
+            id: available_options.len(),
+            uid: new_option_uid(),
+            description: description.to_string(),
+            requirements: vec![CUSTOM_OPTION_REQUIREMENT.to_string()],
+            action: self.interpret_free_text_action(description, character, context),
+        })
+    }
+
     fn interpret_free_text_action(
         &self,
         free_text: &str,
@@ -1654,6 +2270,14 @@ impl PlotEngine {
                 actor_name: Some("player".to_string()),
                 actor_realm: Some(character.cultivation_realm.name.clone()),
                 actor_combat_power: Some(character.combat_power),
+                player_persona: None,
+                canon_facts: Vec::new(),
+                story_beat: None,
+                chapter_beat: None,
+                environment: None,
+                active_quests: Vec::new(),
+                relationships: Vec::new(),
+                status_effects: Vec::new(),
                 history_events: Vec::new(),
                 world_setting_summary: Some(
                     "请把玩家自由输入解析为一个游戏内可执行行动".to_string(),
@@ -1666,30 +2290,37 @@ impl PlotEngine {
                 world_rules: vec![
                     "只输出严格 JSON".to_string(),
                     "JSON 字段: action,target,description".to_string(),
-                    "action 仅允许 cultivate|rest|breakthrough|combat|custom".to_string(),
-                    "description 必须为中文".to_string(),
+                    "action 仅允许 cultivate|rest|breakthrough|combat|learn_technique|practice_technique|custom".to_string(),
+                    format!(
+                        "learn_technique 与 practice_technique 的 target 为功法 id，可选：{}",
+                        self.technique_catalog_line(character)
+                    ),
+                    format!("description 必须为{}", self.prompt_builder.language().prompt_name()),
                 ],
                 output_schema_hint: Some(
-                    "{\"action\":\"cultivate|rest|breakthrough|combat|custom\",\"target\":\"optional string\",\"description\":\"optional string\"}".to_string(),
+                    "{\"action\":\"cultivate|rest|breakthrough|combat|learn_technique|practice_technique|custom\",\"target\":\"optional string\",\"description\":\"optional string\"}".to_string(),
                 ),
             },
             300,
         );
+        let max_tokens = self.budget_tokens(TurnCall::IntentParsing, &prompt, 128)?;
 
-        let response = self.run_llm_request(
+        let structured = self.run_structured_request::<ActionPayload>(
             &llm_service,
             LLMRequest {
                 prompt,
-                max_tokens: Some(128),
+                max_tokens: Some(max_tokens),
                 temperature: Some(0.1),
+                subsystem: LLMSubsystem::Plot,
+                retry_policy: None,
             },
         )?;
 
         self.response_validator
             .validate_response(
-                &response,
+                &structured.response,
                 &ValidationConstraints {
-                    require_json: true,
+                    require_json: false,
                     max_realm_level: None,
                     min_combat_power: None,
                     max_combat_power: None,
@@ -1698,29 +2329,117 @@ impl PlotEngine {
             )
             .ok()?;
 
-        let value: Value = serde_json::from_str(&response.text).ok()?;
-        let action_name = value.get("action").and_then(Value::as_str)?;
-        let description = value
-            .get("description")
-            .and_then(Value::as_str)
-            .unwrap_or(free_text)
-            .to_string();
-        let target = value
-            .get("target")
-            .and_then(Value::as_str)
-            .unwrap_or("unknown")
-            .to_string();
-
-        match action_name.to_ascii_lowercase().as_str() {
+        let payload = structured.data?;
+        let description = payload.description.unwrap_or_else(|| free_text.to_string());
+        let target = payload.target.unwrap_or_else(|| "unknown".to_string());
+
+        match payload.action.to_ascii_lowercase().as_str() {
             "cultivate" => Some(Action::Cultivate),
             "rest" => Some(Action::Rest),
             "breakthrough" => Some(Action::Breakthrough),
             "combat" => Some(Action::Combat { target_id: target }),
+            "learn_technique" => Some(Action::LearnTechnique {
+                technique_id: target,
+            }),
+            "practice_technique" => Some(Action::PracticeTechnique {
+                technique_id: target,
+            }),
             "custom" => Some(Action::Custom { description }),
             _ => None,
         }
     }
 
+    /// 为天劫的每一关写一段叙述，LLM 不可用或输出为空时使用模板叙述
+    pub fn narrate_tribulation(&self, outcome: &TribulationOutcome, player_name: &str) -> Vec<String> {
+        outcome
+            .stages
+            .iter()
+            .map(|stage| {
+                self.narrate_tribulation_stage_with_llm(outcome, stage, player_name)
+                    .unwrap_or_else(|| fallback_tribulation_narration(stage, player_name))
+            })
+            .collect()
+    }
+
+    fn narrate_tribulation_stage_with_llm(
+        &self,
+        outcome: &TribulationOutcome,
+        stage: &TribulationStage,
+        player_name: &str,
+    ) -> Option<String> {
+        if cfg!(test) {
+            return None;
+        }
+        let llm_service = self.resolve_llm_service()?;
+        let prompt = self.prompt_builder.build_prompt_with_token_limit(
+            PromptTemplate::TribulationNarration,
+            &PromptContext {
+                scene: Some(format!(
+                    "{}冲击{}，正在渡{}，结果：{}",
+                    player_name,
+                    outcome.target_realm.name,
+                    stage.kind.label(),
+                    if stage.passed { "渡过" } else { "失败" }
+                )),
+                location: None,
+                actor_name: Some(player_name.to_string()),
+                actor_realm: None,
+                actor_combat_power: None,
+                player_persona: None,
+                canon_facts: Vec::new(),
+                story_beat: None,
+                chapter_beat: None,
+                environment: None,
+                active_quests: Vec::new(),
+                relationships: Vec::new(),
+                status_effects: Vec::new(),
+                history_events: Vec::new(),
+                world_setting_summary: None,
+            },
+            &PromptConstraints {
+                numerical_rules: vec!["不得改变这一关的成败".to_string()],
+                world_rules: vec![
+                    format!("用 2 到 3 句{}描写这一关", self.prompt_builder.language().prompt_name()),
+                    "只写这一关，不要提及后续关卡".to_string(),
+                ],
+                output_schema_hint: None,
+            },
+            500,
+        );
+        let response = self.run_llm_request(
+            &llm_service,
+            LLMRequest {
+                prompt,
+                max_tokens: Some(200),
+                temperature: Some(0.8),
+                subsystem: LLMSubsystem::Plot,
+                retry_policy: None,
+            },
+        )?;
+        let text = response.text.trim();
+        (!text.is_empty()).then(|| text.to_string())
+    }
+
+    /// 可修习与已学功法的 id 列表，供 LLM 解析功法类行动
+    fn technique_catalog_line(&self, character: &CharacterStats) -> String {
+        let entries = self
+            .numerical_system
+            .learnable_techniques(character)
+            .map(|t| format!("{}（{}，未学）", t.id, t.name))
+            .chain(
+                character
+                    .techniques
+                    .iter()
+                    .map(|t| format!("{}（{}，{}）", t.id, t.name, t.proficiency_name())),
+            )
+            .collect::<Vec<String>>();
+        if entries.is_empty() {
+            "无".to_string()
+        } else {
+            entries.join("、")
+        }
+    }
+
     fn parse_action_with_rules(&self, free_text: &str) -> Action {
         let lower = free_text.to_ascii_lowercase();
         if contains_any(&lower, &["修炼", "打坐", "cultivate", "meditate", "training"]) {
@@ -1743,49 +2462,152 @@ impl PlotEngine {
         }
     }
 
+    /// 本地规则分类自由输入，返回推断的行动与歧义度（0 为确定，1 为完全无法判断）
+    pub fn classify_free_text(
+        &self,
+        free_text: &str,
+        available_options: &[PlayerOption],
+    ) -> FreeTextClassification {
+        let action = self.parse_action_with_rules(free_text);
+        let lower = free_text.to_ascii_lowercase();
+        let ambiguity = if contains_any(&lower, AMBIGUOUS_FREE_TEXT_MARKERS) {
+            1.0
+        } else if matches!(action, Action::Custom { .. }) {
+            if free_text.trim().chars().count() > 120 {
+                0.6
+            } else {
+                0.3
+            }
+        } else if available_options
+            .iter()
+            .any(|o| action_label(&o.action) == action_label(&action))
+        {
+            0.0
+        } else {
+            0.5
+        };
+        FreeTextClassification { action, ambiguity }
+    }
+
+    /// 依次执行本地分类、关键词规则，只有歧义度达到门槛时才请求 LLM 判定
     fn validate_free_text_reasonableness(
         &self,
         free_text: &str,
         available_options: &[PlayerOption],
     ) -> Result<(), String> {
-        if let Some((reasonable, reason)) =
-            self.validate_behavior_with_llm(free_text, available_options)
-        {
-            if !reasonable {
-                return Err(format!("该行动被判定为不合理：{}", reason));
-            }
-        }
+        let classification = self.classify_free_text(free_text, available_options);
 
-        let lower = free_text.to_ascii_lowercase();
-        if contains_any(
-            &lower,
-            &[
-                "instant immortal",
-                "instantly become immortal",
-                "destroy the world",
-                "god mode",
-                "one punch kill everyone",
-                "一拳秒杀所有人",
-                "瞬间飞升",
-                "毁灭世界",
-                "无敌模式",
-            ],
-        ) {
+        if self.action_filters.blocked_keyword(free_text).is_some() {
             return Err("该行动超出当前世界规则或角色能力范围".to_string());
         }
+        if self.house_rules.skip_reasonableness_check {
+            return Ok(());
+        }
 
+        let lower = free_text.to_ascii_lowercase();
         let can_breakthrough = available_options
             .iter()
             .any(|o| matches!(o.action, Action::Breakthrough));
         if !can_breakthrough
+            && !self.house_rules.allow_cross_realm_feats
             && contains_any(&lower, &["breakthrough", "突破", "advance realm", "渡劫"])
         {
             return Err("当前场景或境界条件不满足突破要求".to_string());
         }
 
+        if classification.ambiguity < self.llm_judge_threshold {
+            return Ok(());
+        }
+        if let Some((reasonable, reason)) =
+            self.validate_behavior_with_llm(free_text, available_options)
+        {
+            if !reasonable {
+                return Err(format!("该行动被判定为不合理：{}", reason));
+            }
+        }
+
         Ok(())
     }
 
+    /// 本回合行动的善恶：修习魔道功法与关键词规则先行，自由输入规则判断不出时请求 LLM，仍无结论视为中立
+    pub fn classify_alignment(
+        &self,
+        action: &PlayerAction,
+        selected_option: Option<&PlayerOption>,
+    ) -> Alignment {
+        if let Some(option) = selected_option {
+            if let Action::LearnTechnique { technique_id } = &option.action {
+                if self
+                    .numerical_system
+                    .technique(technique_id)
+                    .is_some_and(|technique| technique.demonic)
+                {
+                    return Alignment::Demonic;
+                }
+            }
+            return classify_deed(&option.description).unwrap_or_default();
+        }
+        if action.meta.as_ref().and_then(|m| m.action_kind.as_deref()) == Some("continue") {
+            return Alignment::Neutral;
+        }
+        classify_deed(&action.content)
+            .or_else(|| self.judge_alignment_with_llm(&action.content))
+            .unwrap_or_default()
+    }
+
+    fn judge_alignment_with_llm(&self, free_text: &str) -> Option<Alignment> {
+        if cfg!(test) {
+            return None;
+        }
+        let llm_service = self.resolve_llm_service()?;
+        let prompt = self.prompt_builder.build_prompt_with_token_limit(
+            PromptTemplate::OptionGeneration,
+            &PromptContext {
+                scene: Some(format!("玩家行动: {}", free_text)),
+                location: None,
+                actor_name: Some("player".to_string()),
+                actor_realm: None,
+                actor_combat_power: None,
+                player_persona: None,
+                canon_facts: Vec::new(),
+                story_beat: None,
+                chapter_beat: None,
+                environment: None,
+                active_quests: Vec::new(),
+                relationships: Vec::new(),
+                status_effects: Vec::new(),
+                history_events: Vec::new(),
+                world_setting_summary: Some(
+                    "请按修仙世界的正邪观判断玩家行动的善恶取向".to_string(),
+                ),
+            },
+            &PromptConstraints {
+                numerical_rules: Vec::new(),
+                world_rules: vec![
+                    "只输出严格 JSON".to_string(),
+                    "alignment 仅允许 righteous|neutral|demonic，日常修炼、赶路、交谈为 neutral".to_string(),
+                ],
+                output_schema_hint: Some(
+                    "{\"alignment\":\"righteous|neutral|demonic\"}".to_string(),
+                ),
+            },
+            160,
+        );
+        let max_tokens = self.budget_tokens(TurnCall::AlignmentJudgement, &prompt, 48)?;
+
+        let structured = self.run_structured_request::<AlignmentVerdict>(
+            &llm_service,
+            LLMRequest {
+                prompt,
+                max_tokens: Some(max_tokens),
+                temperature: Some(0.1),
+                subsystem: LLMSubsystem::Validation,
+                retry_policy: None,
+            },
+        )?;
+        Alignment::parse(&structured.data?.alignment)
+    }
+
     fn validate_free_text_input(&self, free_text: &str) -> Result<(), String> {
         let trimmed = free_text.trim();
         if trimmed.is_empty() {
@@ -1829,6 +2651,14 @@ impl PlotEngine {
                 actor_name: Some("player".to_string()),
                 actor_realm: None,
                 actor_combat_power: None,
+                player_persona: None,
+                canon_facts: Vec::new(),
+                story_beat: None,
+                chapter_beat: None,
+                environment: None,
+                active_quests: Vec::new(),
+                relationships: Vec::new(),
+                status_effects: Vec::new(),
                 history_events: Vec::new(),
                 world_setting_summary: Some(
                     "请判断玩家行动在当前修仙场景下是否合理".to_string(),
@@ -1839,7 +2669,7 @@ impl PlotEngine {
                 world_rules: vec![
                     "只输出严格 JSON".to_string(),
                     "JSON 字段: reasonable,reason".to_string(),
-                    "reason 必须为中文".to_string(),
+                    format!("reason 必须为{}", self.prompt_builder.language().prompt_name()),
                 ],
                 output_schema_hint: Some(
                     "{\"reasonable\":true|false,\"reason\":\"string\"}".to_string(),
@@ -1847,21 +2677,24 @@ impl PlotEngine {
             },
             220,
         );
+        let max_tokens = self.budget_tokens(TurnCall::BehaviorValidation, &prompt, 96)?;
 
-        let response = self.run_llm_request(
+        let structured = self.run_structured_request::<ReasonablenessVerdict>(
             &llm_service,
             LLMRequest {
                 prompt,
-                max_tokens: Some(96),
+                max_tokens: Some(max_tokens),
                 temperature: Some(0.1),
+                subsystem: LLMSubsystem::Validation,
+                retry_policy: None,
             },
         )?;
 
         self.response_validator
             .validate_response(
-                &response,
+                &structured.response,
                 &ValidationConstraints {
-                    require_json: true,
+                    require_json: false,
                     max_realm_level: None,
                     min_combat_power: None,
                     max_combat_power: None,
@@ -1870,14 +2703,9 @@ impl PlotEngine {
             )
             .ok()?;
 
-        let value: Value = serde_json::from_str(&response.text).ok()?;
-        let reasonable = value.get("reasonable").and_then(Value::as_bool)?;
-        let reason = value
-            .get("reason")
-            .and_then(Value::as_str)
-            .unwrap_or("未提供原因")
-            .to_string();
-        Some((reasonable, reason))
+        let verdict = structured.data?;
+        let reason = verdict.reason.unwrap_or_else(|| "未提供原因".to_string());
+        Some((verdict.reasonable, reason))
     }
 }
 
@@ -1887,6 +2715,39 @@ impl Default for PlotEngine {
     }
 }
 
+fn elapsed_ms(started: Instant) -> u64 {
+    u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX)
+}
+
+fn trimmed_items(items: Vec<String>) -> Vec<String> {
+    items
+        .into_iter()
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+fn fallback_tribulation_narration(stage: &TribulationStage, player_name: &str) -> String {
+    match (stage.kind, stage.passed) {
+        (TribulationStageKind::Lightning, true) => format!(
+            "劫云压顶，天雷接连劈落，{}以肉身硬抗，雷光散尽时仍屹立不倒。",
+            player_name
+        ),
+        (TribulationStageKind::Lightning, false) => format!(
+            "劫云压顶，天雷一道重过一道，{}终究力竭，被雷光轰得经脉受损。",
+            player_name
+        ),
+        (TribulationStageKind::InnerDemon, true) => format!(
+            "雷劫方过，心魔又起，{}守住本心勘破幻境，道心愈发澄明。",
+            player_name
+        ),
+        (TribulationStageKind::InnerDemon, false) => format!(
+            "雷劫方过，心魔又起，{}沉溺幻境难以自拔，一身修为随之跌落。",
+            player_name
+        ),
+    }
+}
+
 fn contains_any(text: &str, keywords: &[&str]) -> bool {
     keywords.iter().any(|k| text.contains(k))
 }
@@ -1898,6 +2759,9 @@ fn action_label(action: &Action) -> &'static str {
         Action::Breakthrough => "breakthrough",
         Action::Rest => "rest",
         Action::Custom { .. } => "custom",
+        Action::LearnTechnique { .. } => "learn_technique",
+        Action::PracticeTechnique { .. } => "practice_technique",
+        Action::UseItem { .. } => "use_item",
     }
 }
 
@@ -1910,6 +2774,25 @@ fn fallback_chapter_title(index: u32, summary: &str) -> String {
     }
 }
 
+/// 预设文本始终等待玩家输入，但与 LLM 续写一样在交互次数用尽或字数达标时结束本章
+fn fallback_chapter_end(current_state: &PlotState, text: &str) -> bool {
+    let settings = &current_state.settings;
+    let chapter = &current_state.current_chapter;
+    if chapter.interaction_count < settings.min_interactions_per_chapter {
+        return false;
+    }
+    if let Some(outline) = current_state.active_outline() {
+        return outline.ends_with_next_segment();
+    }
+    if settings.three_act_structure {
+        return false;
+    }
+    let word_count =
+        chapter.word_count() + text.split_whitespace().count().max(text.chars().count() / 2);
+    chapter.interaction_count >= settings.max_interactions_per_chapter
+        || word_count >= settings.target_chapter_words_max as usize
+}
+
 impl Scene {
     pub fn new(id: String, name: String, description: String, location: String) -> Self {
         Self {
@@ -1941,6 +2824,14 @@ impl PlotState {
             segment_count: 0,
             last_generation_diagnostics: None,
             last_option_generation_source: None,
+            last_generation_failure: None,
+            player_persona: PlayerPersona::new(),
+            canon_facts: FactStore::new(),
+            temperature_tuner: TemperatureTuner::default(),
+            story_arc: None,
+            npc_interruption: None,
+            side_story: None,
+            version: 0,
         }
     }
 
@@ -1948,13 +2839,61 @@ impl PlotState {
         self.plot_history.push(text);
     }
 
+    /// 追加生成的段落并记录其溯源
+    pub fn append_segment_with_provenance(
+        &mut self,
+        text: String,
+        provenance: Option<SegmentProvenance>,
+    ) {
+        if let Some(mut provenance) = provenance {
+            provenance.segment_index = self.current_chapter.content.len();
+            self.current_chapter.provenance.push(provenance);
+        }
+        self.append_segment(text);
+    }
+
+    /// 两段式续写开启且本章已规划大纲时返回大纲
+    pub fn active_outline(&self) -> Option<&ChapterOutline> {
+        self.current_chapter
+            .outline
+            .as_ref()
+            .filter(|_| self.settings.outline_planning)
+    }
+
+    /// 下一段应写的章节节拍说明：本章大纲优先，其次为三幕式结构
+    pub fn chapter_beat_line(&self) -> Option<String> {
+        if let Some(outline) = self.active_outline() {
+            return outline.prompt_line();
+        }
+        if !self.settings.three_act_structure {
+            return None;
+        }
+        ChapterBeat::at(self.current_chapter.beats_completed).map(ChapterBeat::prompt_line)
+    }
+
     pub fn append_segment(&mut self, text: String) {
+        if self.settings.outline_planning {
+            if let Some(outline) = self.current_chapter.outline.as_mut() {
+                outline.advance();
+            }
+        }
+        if self.settings.three_act_structure
+            && !beats_satisfied(self.current_chapter.beats_completed)
+        {
+            self.current_chapter.beats_completed += 1;
+        }
         self.plot_history.push(text.clone());
         self.current_chapter.content.push(text);
         self.segment_count = self.segment_count.saturating_add(1);
         self.current_scene.description = self.current_chapter.content.join("\n\n");
     }
 
+    /// 追加不计入互动、不改变当前场景的插叙段落
+    pub fn append_interlude(&mut self, text: String) {
+        self.plot_history.push(text.clone());
+        self.current_chapter.content.push(text);
+    }
+
     pub fn finalize_chapter(&mut self, title: Option<String>, summary: Option<String>) {
         let mut resolved_summary = self.current_chapter.summary.clone();
         if let Some(summary) = summary {
@@ -1982,6 +2921,21 @@ impl PlotState {
         self.current_scene.description = "新篇章即将展开。".to_string();
         self.segment_count = 0;
     }
+
+    /// 收束进行中的章节，把结局终章写为最后一章；此后本局不再开启新章
+    pub fn write_epilogue(&mut self, ending_title: &str, summary: &str, finale: String) {
+        if !self.current_chapter.content.is_empty() {
+            self.finalize_chapter(None, None);
+        }
+        let title = format!("终章·{}", ending_title);
+        self.current_chapter.title = title.clone();
+        self.current_chapter.summary = summary.to_string();
+        self.current_scene.name = title;
+        self.current_scene.available_options.clear();
+        self.is_waiting_for_input = false;
+        self.append_interlude(finale.clone());
+        self.current_scene.description = finale;
+    }
 }
 
 #[cfg(test)]
@@ -1993,11 +2947,14 @@ mod tests {
         CharacterStats {
             spiritual_root: SpiritualRoot {
                 element: Element::Fire,
-                grade: Grade::Heavenly,
+                grade: Grade::heavenly(),
                 affinity: 0.8,
+                tier_multiplier: None,
             },
             cultivation_realm: CultivationRealm::new("Qi Condensation".to_string(), 1, 0, 1.0),
             techniques: Vec::new(),
+            status_effects: Default::default(),
+            insight: 0,
             lifespan: Lifespan {
                 current_age: 16,
                 max_age: 100,
@@ -2017,6 +2974,7 @@ mod tests {
 
         scene.add_option(PlayerOption {
             id: 0,
+            uid: new_option_uid(),
             description: "Cultivate".to_string(),
             requirements: vec![],
             action: Action::Cultivate,
@@ -2024,6 +2982,7 @@ mod tests {
 
         scene.add_option(PlayerOption {
             id: 1,
+            uid: new_option_uid(),
             description: "Rest".to_string(),
             requirements: vec![],
             action: Action::Rest,
@@ -2060,6 +3019,7 @@ mod tests {
             action_type: ActionType::SelectedOption,
             content: "0".to_string(),
             selected_option_id: Some(0),
+            selected_option_uuid: Some(scene.available_options[0].uid.clone()),
             meta: None,
         };
 
@@ -2075,6 +3035,7 @@ mod tests {
             action_type: ActionType::SelectedOption,
             content: "999".to_string(),
             selected_option_id: Some(999),
+            selected_option_uuid: None,
             meta: None,
         };
 
@@ -2082,6 +3043,79 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_selected_option_uid_survives_rotation() {
+        let scene = create_test_scene();
+        let rest_uid = scene.available_options[1].uid.clone();
+        let mut rotated = scene.available_options.clone();
+        rotated.rotate_left(1);
+        for (idx, option) in rotated.iter_mut().enumerate() {
+            option.id = idx;
+            option.uid = new_option_uid();
+        }
+        inherit_option_uids(&scene.available_options, &mut rotated);
+
+        // 序号仍指向旧位置，UID 应优先并定位到轮转后的同一选项
+        let action = PlayerAction {
+            action_type: ActionType::SelectedOption,
+            content: "Rest".to_string(),
+            selected_option_id: Some(1),
+            selected_option_uuid: Some(rest_uid),
+            meta: None,
+        };
+        assert_eq!(selected_option_index(&action, &rotated), Ok(Some(0)));
+        assert_eq!(rotated[0].description, "Rest");
+    }
+
+    #[test]
+    fn test_validate_rejects_stale_option_uid() {
+        let engine = PlotEngine::new();
+        let scene = create_test_scene();
+        let action = PlayerAction {
+            action_type: ActionType::SelectedOption,
+            content: "0".to_string(),
+            selected_option_id: Some(0),
+            selected_option_uuid: Some("retired-option".to_string()),
+            meta: None,
+        };
+
+        let err = engine
+            .validate_player_action(&action, &scene.available_options)
+            .unwrap_err();
+        let stale: StaleOptionError = serde_json::from_str(&err).unwrap();
+        assert_eq!(stale.code, STALE_OPTION_CODE);
+        assert_eq!(stale.submitted_uid, "retired-option");
+        assert_eq!(stale.current_options, scene.available_options);
+
+        // 只带序号的旧请求可能指向重新生成后的另一条选项，同样要求刷新
+        let positional: PlayerAction = serde_json::from_str(
+            r#"{"action_type":"SelectedOption","content":"0","selected_option_id":0,"meta":null}"#,
+        )
+        .unwrap();
+        let err = engine
+            .validate_player_action(&positional, &scene.available_options)
+            .unwrap_err();
+        let stale: StaleOptionError = serde_json::from_str(&err).unwrap();
+        assert_eq!(stale.code, STALE_OPTION_CODE);
+        assert!(stale.submitted_uid.is_empty());
+
+        let legacy: PlayerAction = serde_json::from_str(&format!(
+            r#"{{"action_type":"SelectedOption","content":"1","selected_option_id":0,"selected_option_uid":"{}","meta":null}}"#,
+            scene.available_options[1].uid
+        ))
+        .unwrap();
+        assert_eq!(
+            selected_option_index(&legacy, &scene.available_options),
+            Ok(Some(1))
+        );
+        let serialized = serde_json::to_value(&legacy).unwrap();
+        assert_eq!(
+            serialized["selected_option_uuid"],
+            scene.available_options[1].uid.as_str()
+        );
+        assert!(serialized.get("selected_option_uid").is_none());
+    }
+
     #[test]
     fn test_validate_free_text_empty() {
         let engine = PlotEngine::new();
@@ -2090,6 +3124,7 @@ mod tests {
             action_type: ActionType::FreeText,
             content: "   ".to_string(),
             selected_option_id: None,
+            selected_option_uuid: None,
             meta: None,
         };
 
@@ -2105,6 +3140,7 @@ mod tests {
             action_type: ActionType::FreeText,
             content: "a".repeat(600),
             selected_option_id: None,
+            selected_option_uuid: None,
             meta: None,
         };
 
@@ -2121,12 +3157,15 @@ mod tests {
             location: "sect".to_string(),
             time_of_day: "morning".to_string(),
             weather: None,
+            season: None,
+            spiritual_energy: 1.0,
         };
 
         let action = PlayerAction {
             action_type: ActionType::SelectedOption,
             content: "0".to_string(),
             selected_option_id: Some(0),
+            selected_option_uuid: Some(scene.available_options[0].uid.clone()),
             meta: None,
         };
 
@@ -2142,6 +3181,43 @@ mod tests {
         assert!(action_result.success);
     }
 
+    #[tokio::test]
+    async fn test_blocking_bridge_keeps_the_cancellation_token() {
+        let engine = PlotEngine::new();
+        let token = cancellation::CancellationToken::new();
+        token.cancel();
+
+        let seen = token
+            .scope(async { engine.block_on(async { cancellation::current_token() }) })
+            .await
+            .flatten();
+        assert!(seen.is_some_and(|seen| seen.is_cancelled()));
+        assert!(engine
+            .block_on(async { cancellation::current_token() })
+            .flatten()
+            .is_none());
+    }
+
+    #[test]
+    fn test_injected_llm_service_is_shared_across_clones() {
+        let service = Arc::new(
+            LLMService::new(crate::llm_service::LLMConfig {
+                endpoint: "https://example.com/v1/chat/completions".to_string(),
+                api_key: "test-key".to_string(),
+                model: "gpt-test".to_string(),
+                max_tokens: 256,
+                temperature: 0.6,
+                provider_kind: crate::llm_provider::ProviderKind::OpenAI,
+                retry_policy: Default::default(),
+            })
+            .unwrap(),
+        );
+        let engine = PlotEngine::new().with_llm_service(service.clone());
+        let cloned = engine.clone();
+        assert!(Arc::ptr_eq(&engine.resolve_llm_service().unwrap(), &service));
+        assert!(Arc::ptr_eq(&cloned.resolve_llm_service().unwrap(), &service));
+    }
+
     #[test]
     fn test_generate_player_options() {
         let engine = PlotEngine::new();
@@ -2238,6 +3314,175 @@ mod tests {
         assert_eq!(update.triggered_events.len(), 1);
     }
 
+    #[tokio::test]
+    async fn test_sync_advance_plot_matches_async_core() {
+        let engine = PlotEngine::new();
+        let state = PlotState::new(create_test_scene());
+        let action_result = ActionResult {
+            success: true,
+            description: "修炼成功".to_string(),
+            stat_changes: vec![],
+            events: vec!["完成一次修炼".to_string()],
+        };
+
+        // 单线程运行时内调用同步接口也不能死锁或崩溃
+        let sync_update = engine.advance_plot(&state, &action_result);
+        let async_update = engine.advance_plot_async(&state, &action_result).await;
+        assert_eq!(sync_update.plot_text, async_update.plot_text);
+        assert_eq!(sync_update.chapter_end, async_update.chapter_end);
+        assert_eq!(sync_update.is_waiting_for_input, async_update.is_waiting_for_input);
+        assert_eq!(sync_update.generation_failure, async_update.generation_failure);
+        assert_eq!(
+            sync_update.generation_diagnostics.warnings,
+            async_update.generation_diagnostics.warnings
+        );
+        assert_eq!(sync_update.provenance, async_update.provenance);
+        assert_eq!(
+            engine.generate_plot_text(&state, &action_result),
+            async_update.plot_text
+        );
+    }
+
+    #[test]
+    fn test_sync_opening_matches_async_on_every_caller_thread() {
+        let engine = PlotEngine::new().with_story_recap(Some("师门覆灭".to_string()));
+        let opening = tauri::async_runtime::block_on(
+            engine.generate_opening_plot_async("林", "练气", "火灵根", "青云峰"),
+        );
+        assert!(opening.text.starts_with("【接续】"));
+
+        // 不在运行时内、单线程运行时内、多线程运行时内调用同步接口，结果都与异步接口一致
+        assert_eq!(engine.generate_opening_plot("林", "练气", "火灵根", "青云峰"), opening);
+        for runtime in [
+            tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap(),
+            tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap(),
+        ] {
+            let from_runtime = runtime.block_on(async {
+                engine.generate_opening_plot("林", "练气", "火灵根", "青云峰")
+            });
+            assert_eq!(from_runtime, opening);
+        }
+    }
+
+    #[test]
+    fn test_segment_constraints_keep_shared_rules_on_retry() {
+        let engine = PlotEngine::new().with_content_rules(vec!["不得出现血腥描写".to_string()]);
+
+        let full = engine.segment_constraints(false, true);
+        let retry = engine.segment_constraints(true, true);
+        for constraints in [&full, &retry] {
+            assert!(constraints
+                .world_rules
+                .contains(&"不要复述或改写已出现的段落".to_string()));
+            assert!(constraints.world_rules.contains(&"不得出现血腥描写".to_string()));
+            assert!(constraints
+                .world_rules
+                .contains(&TIGHTENED_JSON_RULES[0].to_string()));
+        }
+        assert_eq!(full.output_schema_hint, retry.output_schema_hint);
+        assert!(full.world_rules.contains(&"每次输出 500-900 字".to_string()));
+        assert!(retry.world_rules.contains(&"每次输出 300-600 字".to_string()));
+        assert!(retry.numerical_rules.len() < full.numerical_rules.len());
+
+        let recap = engine
+            .clone()
+            .with_story_recap(Some("师门覆灭".to_string()))
+            .opening_prompt("林", "练气", "火灵根", "青云峰", true, 1200);
+        assert!(recap.contains("接续前情提要"));
+        assert!(recap.contains("160 到 260 字"));
+    }
+
+    #[test]
+    fn test_three_act_structure_gates_chapter_end_on_beats() {
+        let engine = PlotEngine::new();
+        let mut state = PlotState::new(create_test_scene());
+        state.settings.three_act_structure = true;
+        state.current_chapter.interaction_count = state.settings.min_interactions_per_chapter;
+        let segment = || ChapterSegment {
+            text: "剑光一闪。".to_string(),
+            needs_player_input: false,
+            chapter_end: true,
+            chapter_title: None,
+            chapter_summary: None,
+            options: vec![],
+            generation_diagnostics: GenerationDiagnostics::default(),
+            generation_failure: None,
+            tuning_signal: None,
+            provenance: SegmentProvenance::default(),
+            quest_updates: QuestUpdates::default(),
+        };
+
+        assert!(state.chapter_beat_line().unwrap().contains("引入"));
+        state.append_segment("山门初开。".to_string());
+        state.append_segment("强敌压境。".to_string());
+        assert_eq!(state.current_chapter.beats_completed, 2);
+        assert!(state.chapter_beat_line().unwrap().contains("转折"));
+        assert!(!engine.apply_chapter_segment_rules(&state, segment()).chapter_end);
+
+        state.append_segment("局势逆转。".to_string());
+        assert!(state.chapter_beat_line().unwrap().contains("收束"));
+        assert!(engine.apply_chapter_segment_rules(&state, segment()).chapter_end);
+
+        state.append_segment("尘埃落定。".to_string());
+        assert_eq!(state.current_chapter.beats_completed, 4);
+        assert!(state.chapter_beat_line().is_none());
+
+        state.settings.three_act_structure = false;
+        assert!(state.chapter_beat_line().is_none());
+    }
+
+    #[test]
+    fn test_chapter_outline_drives_interaction_and_chapter_end() {
+        use crate::arc_planner::ArcSource;
+        use crate::chapter_outline::{ChapterOutline, OutlineBeat};
+
+        let engine = PlotEngine::new();
+        let mut state = PlotState::new(create_test_scene());
+        state.settings.outline_planning = true;
+        state.settings.target_chapter_words_max = 1;
+        state.current_chapter.interaction_count = state.settings.min_interactions_per_chapter;
+        let beat = |summary: &str, interaction: bool| OutlineBeat {
+            summary: summary.to_string(),
+            interaction,
+        };
+        state.current_chapter.outline = Some(ChapterOutline {
+            beats: vec![beat("山门初开", false), beat("强敌压境", true), beat("尘埃落定", false)],
+            planned_ending: "林逸拜入内门".to_string(),
+            beats_completed: 0,
+            source: ArcSource::Template,
+        });
+        let segment = || ChapterSegment {
+            text: "剑光一闪。".to_string(),
+            needs_player_input: false,
+            chapter_end: true,
+            chapter_title: None,
+            chapter_summary: None,
+            options: vec!["迎战".to_string()],
+            generation_diagnostics: GenerationDiagnostics::default(),
+            generation_failure: None,
+            tuning_signal: None,
+            provenance: SegmentProvenance::default(),
+            quest_updates: QuestUpdates::default(),
+        };
+
+        let first = engine.apply_chapter_segment_rules(&state, segment());
+        assert!(!first.chapter_end);
+        assert!(!first.needs_player_input);
+        assert!(!fallback_chapter_end(&state, "很长的预设文本"));
+
+        state.append_segment("山门初开。".to_string());
+        assert!(state.chapter_beat_line().unwrap().contains("强敌压境（写到此处停下"));
+        assert!(engine.apply_chapter_segment_rules(&state, segment()).needs_player_input);
+
+        state.append_segment("强敌压境。".to_string());
+        let last = engine.apply_chapter_segment_rules(&state, segment());
+        assert!(last.chapter_end);
+        assert!(fallback_chapter_end(&state, "尘埃落定。"));
+
+        state.settings.outline_planning = false;
+        assert!(state.chapter_beat_line().is_none());
+    }
+
     #[test]
     fn test_generate_plot_text_contains_required_information() {
         let engine = PlotEngine::new();
@@ -2274,6 +3519,42 @@ mod tests {
         assert!(text.contains("【") || text.contains("。"));
     }
 
+    #[test]
+    fn test_english_language_localizes_fallbacks_and_prompt_rules() {
+        let engine = PlotEngine::new().with_language(ScriptLanguage::En);
+        let state = PlotState::new(create_test_scene());
+        let action_result = ActionResult {
+            success: true,
+            description: "meditate beneath the pines".to_string(),
+            stat_changes: vec![],
+            events: vec!["a crane cries overhead".to_string()],
+        };
+
+        let text = engine.generate_plot_text(&state, &action_result);
+        assert!(text.starts_with("At "));
+        assert!(text.contains("meditate beneath the pines"));
+        assert!(text.contains("a crane cries overhead"));
+        let opening = engine
+            .generate_opening_plot("Lin", "Qi Refining", "a fire root", "Azure Peak")
+            .text;
+        assert!(opening.starts_with("[Prologue] Lin"));
+        assert!(!opening.contains('。'));
+        assert_eq!(engine.next_chapter_option().description, "Turn to the next chapter");
+
+        let prompt = engine.prompt_builder.build_prompt(
+            PromptTemplate::PlotGeneration,
+            &PromptContext::default(),
+            &PromptConstraints {
+                numerical_rules: vec![],
+                world_rules: vec![],
+                output_schema_hint: None,
+            },
+        );
+        assert!(prompt.contains("输出语言必须为英文"));
+        assert!(!prompt.contains("输出语言必须为中文"));
+        assert_eq!(PlotSettings::default().language, ScriptLanguage::Zh);
+    }
+
     #[test]
     fn test_validate_action_with_no_option_id() {
         let engine = PlotEngine::new();
@@ -2283,12 +3564,13 @@ mod tests {
             action_type: ActionType::SelectedOption,
             content: "test".to_string(),
             selected_option_id: None,
+            selected_option_uuid: None,
             meta: None,
         };
 
         let result = engine.validate_player_action(&action, &scene.available_options);
         assert!(result.is_err());
-        assert!(result.unwrap_err().contains("必须提供选项 ID"));
+        assert!(result.unwrap_err().contains(STALE_OPTION_CODE));
     }
 
     #[test]
@@ -2300,6 +3582,7 @@ mod tests {
             action_type: ActionType::FreeText,
             content: "I want to explore the forest".to_string(),
             selected_option_id: None,
+            selected_option_uuid: None,
             meta: None,
         };
 
@@ -2316,6 +3599,7 @@ mod tests {
             action_type: ActionType::FreeText,
             content: "I will instantly become immortal and destroy the world".to_string(),
             selected_option_id: None,
+            selected_option_uuid: None,
             meta: None,
         };
 
@@ -2324,6 +3608,102 @@ mod tests {
         assert!(result.unwrap_err().contains("超出当前世界规则"));
     }
 
+    #[test]
+    fn test_chapter_segment_prefers_structured_output_then_salvages() {
+        let engine = PlotEngine::new();
+        let structured = |text: &str| StructuredResponse::<SegmentPayload> {
+            data: parse_structured(text),
+            response: LLMResponse {
+                text: text.to_string(),
+                model: None,
+                finish_reason: None,
+                prompt_tokens: None,
+                completion_tokens: None,
+                total_tokens: None,
+            },
+        };
+
+        let segment = engine
+            .chapter_segment_from_response(
+                structured(
+                    r#"{"segment_text":"山门前风雪渐紧。","needs_player_input":true,"options":[" 入山 ",""],"new_quests":["寻找师兄"]}"#,
+                ),
+                None,
+                SegmentProvenance::default(),
+            )
+            .unwrap();
+        assert!(segment.text.contains("山门前风雪渐紧"));
+        assert!(segment.needs_player_input);
+        assert_eq!(segment.options, vec!["入山".to_string()]);
+        assert_eq!(segment.quest_updates.new_quests, vec!["寻找师兄".to_string()]);
+        assert_eq!(segment.generation_diagnostics.parse_path, Some(ParsePath::StrictJson));
+
+        let legacy = engine
+            .chapter_segment_from_response(
+                structured(r#"{"scene_description":"古松","npc_reaction":"长老颔首"}"#),
+                None,
+                SegmentProvenance::default(),
+            )
+            .unwrap();
+        assert!(legacy.text.contains("古松") && legacy.text.contains("长老颔首"));
+
+        let truncated = engine
+            .chapter_segment_from_response(
+                structured(r#"{"segment_text":"剑气纵横三万里。","chapter_end":false,"options":["拔"#),
+                None,
+                SegmentProvenance::default(),
+            )
+            .unwrap();
+        assert!(truncated.text.contains("剑气纵横三万里"));
+        assert_eq!(truncated.generation_diagnostics.parse_path, Some(ParsePath::Salvage));
+        assert!(truncated.quest_updates.is_empty());
+    }
+
+    #[test]
+    fn test_classify_free_text_scores_ambiguity() {
+        let engine = PlotEngine::new();
+        let options = create_test_scene().available_options;
+
+        let available = engine.classify_free_text("盘膝打坐，运转周天", &options);
+        assert_eq!(available.action, Action::Cultivate);
+        assert_eq!(available.ambiguity, 0.0);
+
+        let unavailable = engine.classify_free_text("拔剑攻击山贼", &options);
+        assert!(matches!(unavailable.action, Action::Combat { .. }));
+        assert_eq!(unavailable.ambiguity, 0.5);
+
+        let custom = engine.classify_free_text("去后山采药", &options);
+        assert!(custom.ambiguity < DEFAULT_LLM_JUDGE_THRESHOLD);
+
+        let exaggerated = engine.classify_free_text("一掌秒杀所有人", &options);
+        assert!(exaggerated.ambiguity >= DEFAULT_LLM_JUDGE_THRESHOLD);
+    }
+
+    #[test]
+    fn test_validate_action_respects_script_allowlist() {
+        let script_filters = ActionFilters {
+            blocked: vec!["夺舍".to_string()],
+            allowed: vec!["瞬间飞升".to_string()],
+        };
+        let engine = PlotEngine::new()
+            .with_action_filters(ActionFilters::merged(&ActionFilters::builtin(), &script_filters));
+        let scene = create_test_scene();
+        let free_text = |content: &str| PlayerAction {
+            action_type: ActionType::FreeText,
+            content: content.to_string(),
+            selected_option_id: None,
+            selected_option_uuid: None,
+            meta: None,
+        };
+
+        assert!(engine
+            .validate_player_action(&free_text("我要瞬间飞升"), &scene.available_options)
+            .is_ok());
+        assert!(engine
+            .validate_player_action(&free_text("夺舍一名外门弟子"), &scene.available_options)
+            .is_err());
+    }
+
     #[test]
     fn test_process_action_calculates_result_correctly() {
         let engine = PlotEngine::new();
@@ -2333,12 +3713,15 @@ mod tests {
             location: "sect".to_string(),
             time_of_day: "morning".to_string(),
             weather: None,
+            season: None,
+            spiritual_energy: 1.0,
         };
 
         let action = PlayerAction {
             action_type: ActionType::SelectedOption,
             content: "0".to_string(),
             selected_option_id: Some(0),
+            selected_option_uuid: Some(scene.available_options[0].uid.clone()),
             meta: None,
         };
 
@@ -2364,12 +3747,15 @@ mod tests {
             location: "sect".to_string(),
             time_of_day: "morning".to_string(),
             weather: None,
+            season: None,
+            spiritual_energy: 1.0,
         };
 
         let action = PlayerAction {
             action_type: ActionType::SelectedOption,
             content: "999".to_string(),
             selected_option_id: Some(999),
+            selected_option_uuid: None,
             meta: None,
         };
 
@@ -2381,7 +3767,46 @@ mod tests {
         );
 
         assert!(result.is_err());
-        assert!(result.unwrap_err().contains("无效的选项 ID"));
+        assert!(result.unwrap_err().contains(STALE_OPTION_CODE));
+    }
+
+    #[test]
+    fn test_custom_option_is_validated_and_interpreted() {
+        let engine = PlotEngine::new();
+        let character = create_test_character();
+        let mut options = create_test_scene().available_options;
+        let context = Context {
+            location: "sect".to_string(),
+            time_of_day: "morning".to_string(),
+            weather: None,
+            season: None,
+            spiritual_energy: 1.0,
+        };
+
+        let option = engine
+            .custom_option("  I want to explore the forest ", &character, &options, &context)
+            .unwrap();
+        assert_eq!(option.id, options.len());
+        assert_eq!(option.description, "I want to explore the forest");
+        assert_eq!(option.requirements, vec![CUSTOM_OPTION_REQUIREMENT.to_string()]);
+        options.push(option);
+
+        let duplicate = engine.custom_option("i want to explore the forest", &character, &options, &context);
+        assert!(duplicate.unwrap_err().contains("已有相同"));
+        let unreasonable = engine.custom_option(
+            "I will instantly become immortal and destroy the world",
+            &character,
+            &options,
+            &context,
+        );
+        assert!(unreasonable.unwrap_err().contains("超出当前世界规则"));
+
+        for text in ["I want to rest", "I want to meditate"] {
+            let option = engine.custom_option(text, &character, &options, &context).unwrap();
+            options.push(option);
+        }
+        let over_limit = engine.custom_option("I want to wander", &character, &options, &context);
+        assert!(over_limit.unwrap_err().contains("最多添加"));
     }
 
     #[test]
@@ -2393,12 +3818,15 @@ mod tests {
             location: "sect".to_string(),
             time_of_day: "morning".to_string(),
             weather: None,
+            season: None,
+            spiritual_energy: 1.0,
         };
 
         let action = PlayerAction {
             action_type: ActionType::FreeText,
             content: "I want to explore".to_string(),
             selected_option_id: None,
+            selected_option_uuid: None,
             meta: None,
         };
 
@@ -2421,6 +3849,8 @@ mod tests {
             location: "sect".to_string(),
             time_of_day: "morning".to_string(),
             weather: None,
+            season: None,
+            spiritual_energy: 1.0,
         };
 
         let mut scene = Scene::new(
@@ -2432,6 +3862,7 @@ mod tests {
 
         scene.add_option(PlayerOption {
             id: 0,
+            uid: new_option_uid(),
             description: "Cultivate".to_string(),
             requirements: vec![],
             action: Action::Cultivate,
@@ -2439,6 +3870,7 @@ mod tests {
 
         scene.add_option(PlayerOption {
             id: 1,
+            uid: new_option_uid(),
             description: "Rest".to_string(),
             requirements: vec![],
             action: Action::Rest,
@@ -2446,6 +3878,7 @@ mod tests {
 
         scene.add_option(PlayerOption {
             id: 2,
+            uid: new_option_uid(),
             description: "Breakthrough".to_string(),
             requirements: vec![],
             action: Action::Breakthrough,
@@ -2455,6 +3888,7 @@ mod tests {
             action_type: ActionType::SelectedOption,
             content: "0".to_string(),
             selected_option_id: Some(0),
+            selected_option_uuid: Some(scene.available_options[0].uid.clone()),
             meta: None,
         };
 
@@ -2471,6 +3905,7 @@ mod tests {
             action_type: ActionType::SelectedOption,
             content: "1".to_string(),
             selected_option_id: Some(1),
+            selected_option_uuid: Some(scene.available_options[1].uid.clone()),
             meta: None,
         };
 
@@ -2487,6 +3922,7 @@ mod tests {
             action_type: ActionType::SelectedOption,
             content: "2".to_string(),
             selected_option_id: Some(2),
+            selected_option_uuid: Some(scene.available_options[2].uid.clone()),
             meta: None,
         };
 
@@ -2508,12 +3944,15 @@ mod tests {
             location: "sect".to_string(),
             time_of_day: "morning".to_string(),
             weather: None,
+            season: None,
+            spiritual_energy: 1.0,
         };
 
         let action = PlayerAction {
             action_type: ActionType::SelectedOption,
             content: "0".to_string(),
             selected_option_id: Some(0),
+            selected_option_uuid: Some(scene.available_options[0].uid.clone()),
             meta: None,
         };
 
@@ -2543,6 +3982,7 @@ mod property_tests {
                 
                 scene.add_option(PlayerOption {
                     id: 0,
+                    uid: new_option_uid(),
                     description: "Cultivate".to_string(),
                     requirements: vec![],
                     action: Action::Cultivate,
@@ -2550,6 +3990,7 @@ mod property_tests {
                 
                 scene.add_option(PlayerOption {
                     id: 1,
+                    uid: new_option_uid(),
                     description: "Rest".to_string(),
                     requirements: vec![],
                     action: Action::Rest,
@@ -2565,8 +4006,9 @@ mod property_tests {
             CharacterStats {
                 spiritual_root: SpiritualRoot {
                     element: Element::Fire,
-                    grade: Grade::Heavenly,
+                    grade: Grade::heavenly(),
                     affinity: 0.8,
+                    tier_multiplier: None,
                 },
                 cultivation_realm: CultivationRealm::new(
                     "Test Realm".to_string(),
@@ -2575,6 +4017,8 @@ mod property_tests {
                     1.0,
                 ),
                 techniques: Vec::new(),
+                status_effects: Default::default(),
+                insight: 0,
                 lifespan: Lifespan {
                     current_age: 16,
                     max_age: 100,
@@ -2666,8 +4110,9 @@ mod property_tests {
             let character = CharacterStats {
                 spiritual_root: SpiritualRoot {
                     element: Element::Fire,
-                    grade: Grade::Heavenly,
+                    grade: Grade::heavenly(),
                     affinity: 0.8,
+                    tier_multiplier: None,
                 },
                 cultivation_realm: CultivationRealm::new(
                     "Test Realm".to_string(),
@@ -2676,6 +4121,8 @@ mod property_tests {
                     1.0,
                 ),
                 techniques: Vec::new(),
+                status_effects: Default::default(),
+                insight: 0,
                 lifespan: Lifespan {
                     current_age: 16,
                     max_age: 100,
@@ -2687,6 +4134,8 @@ mod property_tests {
                 location: "sect".to_string(),
                 time_of_day: "day".to_string(),
                 weather: None,
+                season: None,
+                spiritual_energy: 1.0,
             };
 
             let action = PlayerAction {
@@ -2697,6 +4146,7 @@ mod property_tests {
                     input
                 },
                 selected_option_id: None,
+                selected_option_uuid: None,
                 meta: None,
             };
 
@@ -2721,6 +4171,7 @@ mod property_tests {
             );
             scene.add_option(PlayerOption {
                 id: 0,
+                uid: new_option_uid(),
                 description: "Cultivate".to_string(),
                 requirements: vec![],
                 action: Action::Cultivate,
@@ -2730,6 +4181,7 @@ mod property_tests {
                 action_type: ActionType::FreeText,
                 content: format!("instantly become immortal and destroy the world {}", suffix),
                 selected_option_id: None,
+                selected_option_uuid: None,
                 meta: None,
             };
 
@@ -2749,6 +4201,7 @@ mod property_tests {
         
         scene.add_option(PlayerOption {
             id: 0,
+            uid: new_option_uid(),
             description: "Option 1".to_string(),
             requirements: vec![],
             action: Action::Cultivate,
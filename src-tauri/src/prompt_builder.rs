@@ -1,4 +1,5 @@
-﻿use serde::{Deserialize, Serialize};
+﻿use crate::script::ScriptLanguage;
+use serde::{Deserialize, Serialize};
 
 pub const DEFAULT_MAX_HISTORY_ITEMS: usize = 12;
 
@@ -7,10 +8,40 @@ pub enum PromptTemplate {
     ScriptGeneration,
     OptionGeneration,
     NpcDecision,
+    NpcDialogue,
     PlotGeneration,
+    MemoryConsolidation,
+    CombatNarration,
+    EndingFinale,
+    NarratorQuery,
+    CompanionAdvice,
+    TribulationNarration,
+    MarketFlavor,
+    EventTriage,
+    NovelPolish,
 }
 
 impl PromptTemplate {
+    /// 模板的稳定名称，用于按模板统计生成情况
+    pub fn name(&self) -> &'static str {
+        match self {
+            PromptTemplate::ScriptGeneration => "ScriptGeneration",
+            PromptTemplate::OptionGeneration => "OptionGeneration",
+            PromptTemplate::NpcDecision => "NpcDecision",
+            PromptTemplate::NpcDialogue => "NpcDialogue",
+            PromptTemplate::PlotGeneration => "PlotGeneration",
+            PromptTemplate::MemoryConsolidation => "MemoryConsolidation",
+            PromptTemplate::CombatNarration => "CombatNarration",
+            PromptTemplate::EndingFinale => "EndingFinale",
+            PromptTemplate::NarratorQuery => "NarratorQuery",
+            PromptTemplate::CompanionAdvice => "CompanionAdvice",
+            PromptTemplate::TribulationNarration => "TribulationNarration",
+            PromptTemplate::MarketFlavor => "MarketFlavor",
+            PromptTemplate::EventTriage => "EventTriage",
+            PromptTemplate::NovelPolish => "NovelPolish",
+        }
+    }
+
     fn instruction(&self) -> &'static str {
         match self {
             PromptTemplate::ScriptGeneration => {
@@ -22,9 +53,39 @@ impl PromptTemplate {
             PromptTemplate::NpcDecision => {
                 "生成符合 NPC 性格与记忆的决策。"
             }
+            PromptTemplate::NpcDialogue => {
+                "以 NPC 的身份回应玩家的话，语气符合其性格、记忆与对玩家的态度。"
+            }
             PromptTemplate::PlotGeneration => {
                 "生成承接最新事件的小说化剧情文本。"
             }
+            PromptTemplate::MemoryConsolidation => {
+                "将 NPC 零散的长期记忆整合为简短的第三人称摘要。"
+            }
+            PromptTemplate::CombatNarration => {
+                "依据回合战报写出简洁的战斗描写，不得改动伤害与胜负。"
+            }
+            PromptTemplate::EndingFinale => {
+                "为角色的这一局写下与所达成结局相称的终章。"
+            }
+            PromptTemplate::NarratorQuery => {
+                "以旁白身份依据已确立的设定回答玩家关于世界的提问，不推进剧情。"
+            }
+            PromptTemplate::CompanionAdvice => {
+                "以同伴的口吻向犹豫不决的玩家推荐下一步行动，只给建议，不替玩家做决定。"
+            }
+            PromptTemplate::TribulationNarration => {
+                "描写角色渡劫时的一关，不得改动这一关的成败。"
+            }
+            PromptTemplate::MarketFlavor => {
+                "为城中市集的每件货品写一句摊主的吆喝，不得改动货品与价格。"
+            }
+            PromptTemplate::EventTriage => {
+                "从近期的普通事件中挑出真正影响主线剧情的几条。"
+            }
+            PromptTemplate::NovelPolish => {
+                "把一章的事件与剧情片段改写成连贯的小说正文，按指定文风行文。"
+            }
         }
     }
 }
@@ -33,9 +94,31 @@ impl PromptTemplate {
 pub struct PromptContext {
     pub scene: Option<String>,
     pub location: Option<String>,
+    /// 时辰、季节与天气
+    #[serde(default)]
+    pub environment: Option<String>,
     pub actor_name: Option<String>,
     pub actor_realm: Option<String>,
     pub actor_combat_power: Option<u64>,
+    #[serde(default)]
+    pub player_persona: Option<String>,
+    #[serde(default)]
+    pub canon_facts: Vec<String>,
+    /// 当前故事弧节拍
+    #[serde(default)]
+    pub story_beat: Option<String>,
+    /// 三幕式结构下本章当前节拍
+    #[serde(default)]
+    pub chapter_beat: Option<String>,
+    /// 玩家进行中的任务
+    #[serde(default)]
+    pub active_quests: Vec<String>,
+    /// NPC 之间及与玩家的关系概况
+    #[serde(default)]
+    pub relationships: Vec<String>,
+    /// 玩家身上的伤势、中毒等状态
+    #[serde(default)]
+    pub status_effects: Vec<String>,
     pub history_events: Vec<String>,
     pub world_setting_summary: Option<String>,
 }
@@ -50,15 +133,27 @@ pub struct PromptConstraints {
 #[derive(Debug, Clone)]
 pub struct PromptBuilder {
     max_history_items: usize,
+    /// 要求模型使用的输出语言
+    language: ScriptLanguage,
 }
 
 impl PromptBuilder {
     pub fn new(max_history_items: usize) -> Self {
         Self {
             max_history_items: max_history_items.max(1),
+            language: ScriptLanguage::default(),
         }
     }
 
+    pub fn with_language(mut self, language: ScriptLanguage) -> Self {
+        self.language = language;
+        self
+    }
+
+    pub fn language(&self) -> ScriptLanguage {
+        self.language
+    }
+
     pub fn build_prompt(
         &self,
         template: PromptTemplate,
@@ -133,6 +228,12 @@ impl PromptBuilder {
         if let Some(location) = &context.location {
             prompt.push_str(&format!("Location: {}\n", truncate_text(location, text_limit)));
         }
+        if let Some(environment) = &context.environment {
+            prompt.push_str(&format!(
+                "Environment: {}\n",
+                truncate_text(environment, text_limit)
+            ));
+        }
         if let Some(actor_name) = &context.actor_name {
             prompt.push_str(&format!("Actor: {}\n", truncate_text(actor_name, text_limit)));
         }
@@ -142,6 +243,42 @@ impl PromptBuilder {
         if let Some(power) = context.actor_combat_power {
             prompt.push_str(&format!("CombatPower: {power}\n"));
         }
+        if let Some(persona) = &context.player_persona {
+            prompt.push_str(&format!(
+                "PlayerPersona: {}\n",
+                truncate_text(persona, text_limit)
+            ));
+        }
+        if !context.canon_facts.is_empty() {
+            prompt.push_str("CanonFacts:\n");
+            for fact in &context.canon_facts {
+                prompt.push_str(&format!("- {}\n", truncate_text(fact, text_limit)));
+            }
+        }
+        if let Some(beat) = &context.story_beat {
+            prompt.push_str(&format!("StoryBeat: {}\n", truncate_text(beat, text_limit)));
+        }
+        if let Some(beat) = &context.chapter_beat {
+            prompt.push_str(&format!("ChapterBeat: {}\n", truncate_text(beat, text_limit)));
+        }
+        if !context.active_quests.is_empty() {
+            prompt.push_str("ActiveQuests:\n");
+            for quest in &context.active_quests {
+                prompt.push_str(&format!("- {}\n", truncate_text(quest, text_limit)));
+            }
+        }
+        if !context.relationships.is_empty() {
+            prompt.push_str("Relationships:\n");
+            for line in &context.relationships {
+                prompt.push_str(&format!("- {}\n", truncate_text(line, text_limit)));
+            }
+        }
+        if !context.status_effects.is_empty() {
+            prompt.push_str("PlayerStatus:\n");
+            for line in &context.status_effects {
+                prompt.push_str(&format!("- {}\n", truncate_text(line, text_limit)));
+            }
+        }
         if let Some(summary) = &context.world_setting_summary {
             prompt.push_str(&format!(
                 "WorldSetting: {}\n",
@@ -191,7 +328,12 @@ impl PromptBuilder {
         } else {
             prompt.push_str("在可能的情况下返回结构稳定的有效 JSON。\n");
         }
-        prompt.push_str("输出语言必须为中文。\n");
+        match self.language {
+            ScriptLanguage::Zh => prompt.push_str("输出语言必须为中文。\n"),
+            ScriptLanguage::En => {
+                prompt.push_str("输出语言必须为英文：所有叙事、对白与选项均使用英文，JSON 字段名保持不变。\n")
+            }
+        }
         prompt.push_str("不得违反任何数值约束与世界规则。\n");
 
         prompt
@@ -237,6 +379,14 @@ mod tests {
             actor_name: Some("Lin Mo".to_string()),
             actor_realm: Some("Qi Condensation - Late".to_string()),
             actor_combat_power: Some(356),
+            player_persona: Some("行事倾向：修炼×3".to_string()),
+            canon_facts: vec!["师尊已陨落".to_string()],
+            story_beat: None,
+            chapter_beat: None,
+            environment: None,
+            active_quests: vec!["寻找失踪的师兄".to_string()],
+            relationships: vec!["敌视玩家：韩立".to_string()],
+            status_effects: vec!["重伤未愈（还剩 3 天）".to_string()],
             history_events: vec![
                 "Defeated a rogue cultivator".to_string(),
                 "Consumed a spirit pill".to_string(),
@@ -285,6 +435,11 @@ mod tests {
         assert!(prompt.contains("Actor: Lin Mo"));
         assert!(prompt.contains("Realm: Qi Condensation - Late"));
         assert!(prompt.contains("CombatPower: 356"));
+        assert!(prompt.contains("PlayerPersona: 行事倾向：修炼×3"));
+        assert!(prompt.contains("CanonFacts:\n- 师尊已陨落"));
+        assert!(prompt.contains("ActiveQuests:\n- 寻找失踪的师兄"));
+        assert!(prompt.contains("Relationships:\n- 敌视玩家：韩立"));
+        assert!(prompt.contains("PlayerStatus:\n- 重伤未愈（还剩 3 天）"));
         assert!(prompt.contains("WorldSetting: Five-element cultivation world"));
         assert!(prompt.contains("No realm jump larger than one major realm per event"));
         assert!(prompt.contains("The sect forbids lethal combat inside the mountain gate"));
@@ -295,6 +450,14 @@ mod tests {
     fn test_build_prompt_limits_history_size() {
         let builder = PromptBuilder::new(2);
         let context = PromptContext {
+            player_persona: None,
+            canon_facts: Vec::new(),
+            story_beat: None,
+            chapter_beat: None,
+            environment: None,
+            active_quests: Vec::new(),
+            relationships: Vec::new(),
+            status_effects: Vec::new(),
             history_events: vec![
                 "event-1".to_string(),
                 "event-2".to_string(),
@@ -318,6 +481,14 @@ mod tests {
     fn test_build_prompt_with_token_limit_truncates_history() {
         let builder = PromptBuilder::new(10);
         let context = PromptContext {
+            player_persona: None,
+            canon_facts: Vec::new(),
+            story_beat: None,
+            chapter_beat: None,
+            environment: None,
+            active_quests: Vec::new(),
+            relationships: Vec::new(),
+            status_effects: Vec::new(),
             history_events: vec![
                 "long history event one".to_string(),
                 "long history event two".to_string(),
@@ -363,6 +534,14 @@ mod tests {
                 actor_name: Some(actor.clone()),
                 actor_realm: Some(realm.clone()),
                 actor_combat_power: Some(123),
+                player_persona: None,
+                canon_facts: Vec::new(),
+                story_beat: None,
+                chapter_beat: None,
+                environment: None,
+                active_quests: Vec::new(),
+                relationships: Vec::new(),
+                status_effects: Vec::new(),
                 history_events: history.clone(),
                 world_setting_summary: Some("world-summary".to_string()),
             };
@@ -424,6 +603,14 @@ mod tests {
                 actor_name: Some("Player".to_string()),
                 actor_realm: Some("Qi Condensation".to_string()),
                 actor_combat_power: Some(100),
+                player_persona: None,
+                canon_facts: Vec::new(),
+                story_beat: None,
+                chapter_beat: None,
+                environment: None,
+                active_quests: Vec::new(),
+                relationships: Vec::new(),
+                status_effects: Vec::new(),
                 history_events: history,
                 world_setting_summary: Some("Cultivation world".to_string()),
             };
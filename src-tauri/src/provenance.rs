@@ -0,0 +1,185 @@
+use crate::plot_engine::ChapterState;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+pub const RESPONSE_VALIDATOR: &str = "response";
+pub const REPETITION_VALIDATOR: &str = "repetition";
+pub const FACTS_VALIDATOR: &str = "facts";
+pub const CONTENT_VALIDATOR: &str = "content";
+pub const CONSISTENCY_VALIDATOR: &str = "consistency";
+
+/// 段落生成时采用的回退方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FallbackKind {
+    /// 结构化续写失败，降级为纯文本续写
+    PlainText,
+    /// LLM 不可用或输出无法使用，改用预设文本
+    Preset,
+}
+
+impl FallbackKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            FallbackKind::PlainText => "纯文本续写",
+            FallbackKind::Preset => "预设文本",
+        }
+    }
+}
+
+/// 单个校验器对段落的判定
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ValidatorVerdict {
+    pub validator: String,
+    pub passed: bool,
+    #[serde(default)]
+    pub detail: Option<String>,
+}
+
+impl ValidatorVerdict {
+    pub fn passed(validator: &str) -> Self {
+        Self {
+            validator: validator.to_string(),
+            passed: true,
+            detail: None,
+        }
+    }
+
+    pub fn failed(validator: &str, detail: impl Into<String>) -> Self {
+        Self {
+            validator: validator.to_string(),
+            passed: false,
+            detail: Some(detail.into()),
+        }
+    }
+}
+
+/// 段落的生成溯源，用于审计某段正文究竟是如何产生的
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct SegmentProvenance {
+    /// 段落在所属章节正文中的位置
+    #[serde(default)]
+    pub segment_index: usize,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    #[serde(default)]
+    pub prompt_hash: Option<String>,
+    #[serde(default)]
+    pub retry_count: u32,
+    #[serde(default)]
+    pub validator_verdicts: Vec<ValidatorVerdict>,
+    #[serde(default)]
+    pub fallback: Option<FallbackKind>,
+}
+
+impl SegmentProvenance {
+    pub fn fallback(kind: FallbackKind) -> Self {
+        Self {
+            fallback: Some(kind),
+            ..Self::default()
+        }
+    }
+
+    /// 单行摘要，附在对局记录的段落之后
+    pub fn summary(&self) -> String {
+        let mut parts = vec![format!(
+            "模型 {}",
+            self.model.as_deref().unwrap_or("无")
+        )];
+        if let Some(temperature) = self.temperature {
+            parts.push(format!("温度 {:.2}", temperature));
+        }
+        if let Some(hash) = &self.prompt_hash {
+            parts.push(format!("提示哈希 {}", hash));
+        }
+        parts.push(format!("重试 {} 次", self.retry_count));
+        if !self.validator_verdicts.is_empty() {
+            let verdicts = self
+                .validator_verdicts
+                .iter()
+                .map(|verdict| match (&verdict.detail, verdict.passed) {
+                    (_, true) => format!("{}:通过", verdict.validator),
+                    (Some(detail), false) => format!("{}:未通过（{}）", verdict.validator, detail),
+                    (None, false) => format!("{}:未通过", verdict.validator),
+                })
+                .collect::<Vec<String>>();
+            parts.push(format!("校验 {}", verdicts.join("，")));
+        }
+        parts.push(format!(
+            "回退 {}",
+            self.fallback.map(|kind| kind.label()).unwrap_or("无")
+        ));
+        parts.join("；")
+    }
+}
+
+/// 按章节输出逐段对局记录，`include_provenance` 为真时在每段后附上生成溯源
+pub fn render_transcript(chapters: &[ChapterState], include_provenance: bool) -> String {
+    let mut output = String::new();
+    for chapter in chapters {
+        output.push_str(&format!("第{}章 {}\n\n", chapter.index, chapter.title));
+        for (idx, segment) in chapter.content.iter().enumerate() {
+            output.push_str(segment.trim());
+            output.push('\n');
+            if include_provenance {
+                if let Some(provenance) = chapter
+                    .provenance
+                    .iter()
+                    .find(|provenance| provenance.segment_index == idx)
+                {
+                    output.push_str(&format!("〔溯源：{}〕\n", provenance.summary()));
+                }
+            }
+            output.push('\n');
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chapter_with_provenance() -> ChapterState {
+        let mut chapter = ChapterState::new(1, "初入山门".to_string());
+        chapter.content = vec!["山门初开。".to_string(), "快报插叙。".to_string()];
+        chapter.provenance = vec![SegmentProvenance {
+            segment_index: 0,
+            model: Some("gpt-test".to_string()),
+            temperature: Some(0.7),
+            prompt_hash: Some("00000000deadbeef".to_string()),
+            retry_count: 1,
+            validator_verdicts: vec![
+                ValidatorVerdict::passed(RESPONSE_VALIDATOR),
+                ValidatorVerdict::failed(FACTS_VALIDATOR, "与既定事实矛盾"),
+            ],
+            fallback: None,
+        }];
+        chapter
+    }
+
+    #[test]
+    fn test_transcript_includes_provenance_only_when_requested() {
+        let chapters = vec![chapter_with_provenance()];
+
+        let plain = render_transcript(&chapters, false);
+        assert!(plain.contains("第1章 初入山门"));
+        assert!(plain.contains("山门初开。"));
+        assert!(!plain.contains("溯源"));
+
+        let audited = render_transcript(&chapters, true);
+        assert_eq!(audited.matches("〔溯源").count(), 1);
+        assert!(audited.contains("模型 gpt-test"));
+        assert!(audited.contains("提示哈希 00000000deadbeef"));
+        assert!(audited.contains("重试 1 次"));
+        assert!(audited.contains("facts:未通过（与既定事实矛盾）"));
+    }
+
+    #[test]
+    fn test_fallback_provenance_summary() {
+        let provenance = SegmentProvenance::fallback(FallbackKind::Preset);
+        assert_eq!(provenance.summary(), "模型 无；重试 0 次；回退 预设文本");
+    }
+}
@@ -0,0 +1,194 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// 同时进行中的任务上限，超出时忽略新任务
+const MAX_ACTIVE_QUESTS: usize = 8;
+/// 写入提示词的进行中任务数
+const MAX_PROMPT_QUESTS: usize = 5;
+const MAX_QUEST_TITLE_CHARS: usize = 40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QuestStatus {
+    Active,
+    Completed,
+    Abandoned,
+}
+
+/// 从剧情中提炼出的目标
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Quest {
+    pub id: String,
+    pub title: String,
+    pub status: QuestStatus,
+    /// 接取任务的游戏日
+    pub started_day: u32,
+    /// 完成或放弃的游戏日
+    pub resolved_day: Option<u32>,
+}
+
+/// LLM 在剧情段落 JSON 中给出的任务变化
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct QuestUpdates {
+    pub new_quests: Vec<String>,
+    pub completed_quests: Vec<String>,
+}
+
+impl QuestUpdates {
+    /// 读取段落 JSON 中的 `new_quests` 与 `completed_quests`，缺失时为空
+    pub fn from_json(value: &Value) -> Self {
+        Self {
+            new_quests: string_list(value.get("new_quests")),
+            completed_quests: string_list(value.get("completed_quests")),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.new_quests.is_empty() && self.completed_quests.is_empty()
+    }
+}
+
+/// 本局的任务记录，随游戏状态存档
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct QuestLog {
+    pub quests: Vec<Quest>,
+    pub next_id: u32,
+}
+
+impl QuestLog {
+    pub fn active(&self) -> impl Iterator<Item = &Quest> {
+        self.quests
+            .iter()
+            .filter(|quest| quest.status == QuestStatus::Active)
+    }
+
+    /// 先结算完成的任务再登记新任务；完成项按标题匹配，与进行中任务重名的新任务会被忽略
+    pub fn apply(&mut self, updates: &QuestUpdates, day: u32) {
+        for title in &updates.completed_quests {
+            let title = normalize_title(title);
+            if let Some(quest) = self
+                .quests
+                .iter_mut()
+                .filter(|quest| quest.status == QuestStatus::Active)
+                .find(|quest| titles_match(&quest.title, &title))
+            {
+                quest.status = QuestStatus::Completed;
+                quest.resolved_day = Some(day);
+            }
+        }
+
+        for title in &updates.new_quests {
+            let title = normalize_title(title);
+            if title.is_empty()
+                || self.active().count() >= MAX_ACTIVE_QUESTS
+                || self.active().any(|quest| quest.title == title)
+            {
+                continue;
+            }
+            self.next_id += 1;
+            self.quests.push(Quest {
+                id: format!("quest_{}", self.next_id),
+                title,
+                status: QuestStatus::Active,
+                started_day: day,
+                resolved_day: None,
+            });
+        }
+    }
+
+    /// 放弃进行中的任务，返回被放弃的任务；任务不存在或已结束时为 `None`
+    pub fn abandon(&mut self, quest_id: &str, day: u32) -> Option<Quest> {
+        let quest = self
+            .quests
+            .iter_mut()
+            .find(|quest| quest.id == quest_id && quest.status == QuestStatus::Active)?;
+        quest.status = QuestStatus::Abandoned;
+        quest.resolved_day = Some(day);
+        Some(quest.clone())
+    }
+
+    /// 最近接取的进行中任务，供剧情续写保持目标导向
+    pub fn prompt_lines(&self) -> Vec<String> {
+        let active = self.active().collect::<Vec<&Quest>>();
+        let start = active.len().saturating_sub(MAX_PROMPT_QUESTS);
+        active[start..]
+            .iter()
+            .map(|quest| quest.title.clone())
+            .collect()
+    }
+}
+
+fn string_list(value: Option<&Value>) -> Vec<String> {
+    value
+        .and_then(Value::as_array)
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(Value::as_str)
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn normalize_title(title: &str) -> String {
+    title.trim().chars().take(MAX_QUEST_TITLE_CHARS).collect()
+}
+
+/// 模型复述任务名时常有增删字，互相包含即视为同一任务
+fn titles_match(existing: &str, reported: &str) -> bool {
+    !reported.is_empty() && (existing.contains(reported) || reported.contains(existing))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_apply_updates_tracks_quest_lifecycle() {
+        let mut log = QuestLog::default();
+        let updates = QuestUpdates::from_json(&json!({
+            "segment_text": "……",
+            "new_quests": ["寻找失踪的师兄", " ", "采集百年灵芝"],
+        }));
+        log.apply(&updates, 3);
+        log.apply(&updates, 4);
+
+        assert_eq!(log.quests.len(), 2);
+        assert_eq!(log.prompt_lines(), vec!["寻找失踪的师兄", "采集百年灵芝"]);
+
+        log.apply(
+            &QuestUpdates {
+                completed_quests: vec!["找到失踪的师兄".to_string(), "失踪的师兄".to_string()],
+                ..QuestUpdates::default()
+            },
+            9,
+        );
+        assert_eq!(log.quests[0].status, QuestStatus::Completed);
+        assert_eq!(log.quests[0].resolved_day, Some(9));
+
+        let abandoned = log.abandon("quest_2", 10).unwrap();
+        assert_eq!(abandoned.status, QuestStatus::Abandoned);
+        assert!(log.abandon("quest_2", 11).is_none());
+        assert!(log.prompt_lines().is_empty());
+    }
+
+    #[test]
+    fn test_active_quests_are_capped() {
+        let mut log = QuestLog::default();
+        let updates = QuestUpdates {
+            new_quests: (0..12).map(|i| format!("目标{}", i)).collect(),
+            ..QuestUpdates::default()
+        };
+        log.apply(&updates, 1);
+
+        assert_eq!(log.active().count(), MAX_ACTIVE_QUESTS);
+        assert_eq!(log.prompt_lines().len(), MAX_PROMPT_QUESTS);
+        assert!(QuestUpdates::from_json(&json!({ "new_quests": "不是数组" })).is_empty());
+    }
+}
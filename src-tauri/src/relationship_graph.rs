@@ -0,0 +1,178 @@
+use crate::npc::{InteractionRecord, NPC};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// 每条关系附带的最近互动条数
+const RECENT_INTERACTIONS: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RelationshipNodeKind {
+    Player,
+    Npc,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RelationshipNode {
+    pub id: String,
+    pub name: String,
+    pub kind: RelationshipNodeKind,
+    pub location: Option<String>,
+}
+
+/// 一条有向关系：`source` 对 `target` 的好感与信任
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RelationshipEdge {
+    pub source: String,
+    pub target: String,
+    pub affinity: i32,
+    pub trust: i32,
+    /// 最近的互动，新的在前
+    pub recent_interactions: Vec<InteractionRecord>,
+}
+
+/// 玩家与全部 NPC 之间的关系网
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RelationshipGraph {
+    pub nodes: Vec<RelationshipNode>,
+    pub edges: Vec<RelationshipEdge>,
+}
+
+impl RelationshipGraph {
+    /// 按 NPC 的关系表构建关系网，指向未知对象的关系不会列出；节点与关系均按 id 排序
+    pub fn build<'a>(
+        npcs: impl Iterator<Item = &'a NPC>,
+        player_id: &str,
+        player_name: &str,
+    ) -> Self {
+        let mut npcs = npcs.collect::<Vec<&NPC>>();
+        npcs.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let mut nodes = vec![RelationshipNode {
+            id: player_id.to_string(),
+            name: player_name.to_string(),
+            kind: RelationshipNodeKind::Player,
+            location: None,
+        }];
+        nodes.extend(npcs.iter().map(|npc| RelationshipNode {
+            id: npc.id.clone(),
+            name: npc.name.clone(),
+            kind: RelationshipNodeKind::Npc,
+            location: npc.location.clone(),
+        }));
+        let known = nodes
+            .iter()
+            .map(|node| node.id.clone())
+            .collect::<HashSet<String>>();
+
+        let mut edges = npcs
+            .iter()
+            .flat_map(|npc| {
+                npc.relationships
+                    .values()
+                    .filter(|relationship| {
+                        relationship.target_id != npc.id && known.contains(&relationship.target_id)
+                    })
+                    .map(|relationship| RelationshipEdge {
+                        source: npc.id.clone(),
+                        target: relationship.target_id.clone(),
+                        affinity: relationship.affinity,
+                        trust: relationship.trust,
+                        recent_interactions: relationship
+                            .history
+                            .iter()
+                            .rev()
+                            .take(RECENT_INTERACTIONS)
+                            .cloned()
+                            .collect(),
+                    })
+            })
+            .collect::<Vec<RelationshipEdge>>();
+        edges.sort_by(|a, b| (&a.source, &a.target).cmp(&(&b.source, &b.target)));
+
+        Self { nodes, edges }
+    }
+
+    pub fn node(&self, id: &str) -> Option<&RelationshipNode> {
+        self.nodes.iter().find(|node| node.id == id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CharacterStats, CultivationRealm, Element, Grade, Lifespan, SpiritualRoot};
+    use crate::npc::{EmotionalState, NPCMemory, Personality, Relationship};
+
+    fn npc(id: &str, relationships: &[(&str, i32, usize)]) -> NPC {
+        NPC {
+            id: id.to_string(),
+            name: format!("{}名", id),
+            stats: CharacterStats::new(
+                SpiritualRoot {
+                    element: Element::Fire,
+                    grade: Grade::double(),
+                    affinity: 0.7,
+                    tier_multiplier: None,
+                },
+                CultivationRealm::new("练气".to_string(), 1, 0, 1.0),
+                Lifespan::new(20, 120, 0),
+            ),
+            personality: Personality {
+                traits: Vec::new(),
+                goals: Vec::new(),
+                values: Vec::new(),
+            },
+            memory: NPCMemory::default(),
+            relationships: relationships
+                .iter()
+                .map(|(target, affinity, interactions)| {
+                    (
+                        target.to_string(),
+                        Relationship {
+                            target_id: target.to_string(),
+                            affinity: *affinity,
+                            trust: 0,
+                            history: (0..*interactions as u64)
+                                .map(|day| InteractionRecord {
+                                    timestamp: day,
+                                    event: format!("第{}天", day),
+                                    affinity_change: 1,
+                                    trust_change: 0,
+                                })
+                                .collect(),
+                        },
+                    )
+                })
+                .collect(),
+            secrets: Vec::new(),
+            location: Some("sect".to_string()),
+            bio: String::new(),
+            emotions: EmotionalState::default(),
+        }
+    }
+
+    #[test]
+    fn test_build_lists_known_edges_with_recent_interactions() {
+        let lin = npc("lin", &[("player", 60, 5), ("han", -70, 0), ("ghost", 10, 0)]);
+        let han = npc("han", &[("lin", -40, 1)]);
+
+        let graph = RelationshipGraph::build([&lin, &han].into_iter(), "player", "少年");
+        assert_eq!(
+            graph.nodes.iter().map(|n| n.id.as_str()).collect::<Vec<&str>>(),
+            vec!["player", "han", "lin"]
+        );
+        assert_eq!(graph.node("player").unwrap().kind, RelationshipNodeKind::Player);
+        assert_eq!(
+            graph
+                .edges
+                .iter()
+                .map(|e| (e.source.as_str(), e.target.as_str()))
+                .collect::<Vec<(&str, &str)>>(),
+            vec![("han", "lin"), ("lin", "han"), ("lin", "player")]
+        );
+        let to_player = &graph.edges[2];
+        assert_eq!(to_player.recent_interactions.len(), RECENT_INTERACTIONS);
+        assert_eq!(to_player.recent_interactions[0].timestamp, 4);
+    }
+}
@@ -1,4 +1,6 @@
-﻿use crate::llm_service::LLMResponse;
+﻿use crate::facts::FactStore;
+use crate::game_state::GameState;
+use crate::llm_service::LLMResponse;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::fmt;
@@ -30,6 +32,8 @@ pub enum ValidationError {
     InvalidJson(String),
     MissingField(String),
     NumericalConstraintViolation(String),
+    FactContradiction(Vec<String>),
+    NarrativeInconsistency(Vec<String>),
     RetryExhausted { attempts: u32, last_error: String },
 }
 
@@ -42,6 +46,12 @@ impl fmt::Display for ValidationError {
             ValidationError::NumericalConstraintViolation(msg) => {
                 write!(f, "numerical constraint violation: {msg}")
             }
+            ValidationError::FactContradiction(conflicts) => {
+                write!(f, "contradicts canon facts: {}", conflicts.join("; "))
+            }
+            ValidationError::NarrativeInconsistency(issues) => {
+                write!(f, "inconsistent with game state: {}", issues.join("; "))
+            }
             ValidationError::RetryExhausted {
                 attempts,
                 last_error,
@@ -57,6 +67,124 @@ impl fmt::Display for ValidationError {
 
 impl std::error::Error for ValidationError {}
 
+/// 断言玩家此刻所在的说法，后接地点名
+const LOCATION_MARKERS: [&str; 6] = ["身处", "置身", "身在", "人在", "站在", "坐在"];
+/// 断言玩家已达某境界的说法，后接境界名
+const REALM_MARKERS: [&str; 8] = ["突破至", "突破到", "晋入", "晋升", "踏入", "迈入", "已是", "达到"];
+/// 紧跟人名时表示其正在场上言行
+const PRESENCE_VERBS: [&str; 10] = [
+    "说", "道", "笑", "点头", "走来", "走进", "出手", "开口", "望向", "看向",
+];
+/// 主语「你」与断言之间允许相隔的字数
+const SUBJECT_WINDOW_CHARS: usize = 12;
+
+/// 续写须与之保持一致的游戏状态
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NarrativeContext {
+    /// 玩家当前所在地点
+    pub location: String,
+    /// 玩家此刻不在的其他地点
+    pub other_locations: Vec<String>,
+    /// 高于玩家当前境界的境界
+    pub higher_realms: Vec<String>,
+    /// 已身故、不应再登场的 NPC
+    pub departed_npcs: Vec<String>,
+}
+
+impl NarrativeContext {
+    pub fn from_game_state(game_state: &GameState, departed_npcs: Vec<String>) -> Self {
+        let locations = &game_state.world_state.locations;
+        let location = locations
+            .get(&game_state.player.location)
+            .map(|location| location.name.clone())
+            .unwrap_or_else(|| game_state.player.location.clone());
+        let mut other_locations = locations
+            .values()
+            .map(|other| other.name.clone())
+            .filter(|name| !name.is_empty() && !location.contains(name.as_str()))
+            .collect::<Vec<String>>();
+        other_locations.sort();
+
+        let player_level = game_state.player.stats.cultivation_realm.level;
+        let current_realm = &game_state.player.stats.cultivation_realm.name;
+        let higher_realms = game_state
+            .script
+            .world_setting
+            .cultivation_realms
+            .iter()
+            .filter(|realm| realm.level > player_level && !current_realm.contains(&realm.name))
+            .map(|realm| realm.name.clone())
+            .collect();
+
+        Self {
+            location,
+            other_locations,
+            higher_realms,
+            departed_npcs,
+        }
+    }
+
+    /// 重写时追加到续写提示的约束
+    pub fn prompt_rules(&self) -> Vec<String> {
+        let mut rules = Vec::new();
+        if !self.location.is_empty() {
+            rules.push(format!("玩家此刻身在{}，不要写成身处别处", self.location));
+        }
+        if let Some(next_realm) = self.higher_realms.first() {
+            rules.push(format!("玩家尚未达到{}，不要写成已突破到更高境界", next_realm));
+        }
+        if !self.departed_npcs.is_empty() {
+            rules.push(format!(
+                "{}已经身故，只能在回忆中提及，不能登场言行",
+                self.departed_npcs.join("、")
+            ));
+        }
+        rules
+    }
+
+    /// 找出段落中与游戏状态矛盾的说法
+    pub fn find_inconsistencies(&self, text: &str) -> Vec<String> {
+        let mut issues = Vec::new();
+        for location in &self.other_locations {
+            if player_asserted(text, &LOCATION_MARKERS, location) {
+                issues.push(format!("玩家身在{}，正文却写在{}", self.location, location));
+            }
+        }
+        for realm in &self.higher_realms {
+            if player_asserted(text, &REALM_MARKERS, realm) {
+                issues.push(format!("正文称玩家已达{}", realm));
+            }
+        }
+        for name in &self.departed_npcs {
+            if PRESENCE_VERBS
+                .iter()
+                .any(|verb| text.contains(&format!("{}{}", name, verb)))
+            {
+                issues.push(format!("已身故的{}在正文中登场", name));
+            }
+        }
+        issues
+    }
+}
+
+/// 正文是否以「你」为主语、用某个说法断言了目标，如「你已身处落霞城」
+fn player_asserted(text: &str, markers: &[&str], target: &str) -> bool {
+    markers.iter().any(|marker| {
+        [format!("{}{}", marker, target), format!("{}了{}", marker, target)]
+            .iter()
+            .any(|phrase| {
+                text.match_indices(phrase.as_str()).any(|(start, _)| {
+                    text[..start]
+                        .chars()
+                        .rev()
+                        .take(SUBJECT_WINDOW_CHARS)
+                        .take_while(|c| !matches!(c, '。' | '！' | '？' | '\n'))
+                        .any(|c| c == '你')
+                })
+            })
+    })
+}
+
 #[derive(Debug, Clone)]
 pub struct ResponseValidator {
     max_retries: u32,
@@ -140,6 +268,34 @@ impl ResponseValidator {
         Ok(())
     }
 
+    /// 检查生成文本是否与已确立的设定事实矛盾
+    pub fn validate_against_facts(
+        &self,
+        text: &str,
+        facts: &FactStore,
+    ) -> Result<(), ValidationError> {
+        let conflicts = facts.find_contradictions(text);
+        if conflicts.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationError::FactContradiction(conflicts))
+        }
+    }
+
+    /// 检查生成文本是否与当前游戏状态（所在地点、境界、已身故的 NPC）矛盾
+    pub fn validate_consistency(
+        &self,
+        text: &str,
+        context: &NarrativeContext,
+    ) -> Result<(), ValidationError> {
+        let issues = context.find_inconsistencies(text);
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationError::NarrativeInconsistency(issues))
+        }
+    }
+
     pub fn validate_with_retry_or_fallback<F>(
         &self,
         initial_response: LLMResponse,
@@ -260,6 +416,47 @@ mod tests {
         assert!(matches!(result, Err(ValidationError::InvalidJson(_))));
     }
 
+    #[test]
+    fn test_validate_against_facts_flags_contradiction() {
+        let validator = ResponseValidator::default();
+        let mut facts = FactStore::new();
+        facts.add_fact("师尊已陨落", 1).unwrap();
+
+        assert!(validator
+            .validate_against_facts("你跪在师尊墓前，久久无言。", &facts)
+            .is_ok());
+        let result = validator.validate_against_facts("师尊推门走来，微笑不语。", &facts);
+        assert!(matches!(result, Err(ValidationError::FactContradiction(c)) if c.len() == 1));
+    }
+
+    #[test]
+    fn test_validate_consistency_flags_location_realm_and_departed_npc() {
+        let validator = ResponseValidator::default();
+        let context = NarrativeContext {
+            location: "青云宗".to_string(),
+            other_locations: vec!["落霞城".to_string()],
+            higher_realms: vec!["金丹期".to_string()],
+            departed_npcs: vec!["林长老".to_string()],
+        };
+
+        assert!(validator
+            .validate_consistency(
+                "你站在青云宗山门前，遥望落霞城的方向，想起林长老生前的教诲。",
+                &context,
+            )
+            .is_ok());
+        let result = validator.validate_consistency(
+            "你此刻身处落霞城，一举突破至金丹期。林长老笑道：好！",
+            &context,
+        );
+        assert!(matches!(result, Err(ValidationError::NarrativeInconsistency(issues)) if issues.len() == 3));
+        // 他人的行踪与境界不算对玩家的断言
+        assert!(validator
+            .validate_consistency("师兄身处落霞城，早已是金丹期修士。", &context)
+            .is_ok());
+        assert!(context.prompt_rules().iter().any(|rule| rule.contains("林长老")));
+    }
+
     #[test]
     fn test_validate_response_rejects_numerical_violation() {
         let validator = ResponseValidator::default();
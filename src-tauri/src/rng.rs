@@ -0,0 +1,136 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+const PCG_MULTIPLIER: u64 = 6_364_136_223_846_793_005;
+const PCG_INCREMENT: u64 = 1_442_695_040_888_963_407;
+
+/// 可设定种子的 PCG32 随机数，随游戏状态保存；同一种子总是得到同样的开局与随机结果，便于重放与复现问题
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct GameRng {
+    /// 本局的初始种子
+    pub seed: u64,
+    pub state: u64,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = Self { seed, state: 0 };
+        rng.step();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.step();
+        rng
+    }
+
+    fn step(&mut self) {
+        self.state = self
+            .state
+            .wrapping_mul(PCG_MULTIPLIER)
+            .wrapping_add(PCG_INCREMENT);
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.step();
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        xorshifted.rotate_right((old >> 59) as u32)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        (u64::from(self.next_u32()) << 32) | u64::from(self.next_u32())
+    }
+
+    /// 闭区间 [min, max] 内的整数
+    pub fn range_u32(&mut self, min: u32, max: u32) -> u32 {
+        if min >= max {
+            return min;
+        }
+        let span = u64::from(max - min) + 1;
+        min + (self.next_u64() % span) as u32
+    }
+
+    pub fn range_f32(&mut self, min: f32, max: f32) -> f32 {
+        if min >= max {
+            return min;
+        }
+        let val = (self.next_u32() as f64 / u32::MAX as f64) as f32;
+        min + (max - min) * val
+    }
+
+    /// 按权重抽取序号，权重全为 0 时返回 0；剧本给出的权重可能很大，总和按 u64 累加
+    pub fn weighted_index(&mut self, weights: &[u32]) -> usize {
+        let total = weights.iter().map(|weight| u64::from(*weight)).sum::<u64>();
+        if total == 0 {
+            return 0;
+        }
+        let mut roll = 1 + self.next_u64() % total;
+        for (idx, weight) in weights.iter().enumerate() {
+            let weight = u64::from(*weight);
+            if weight == 0 {
+                continue;
+            }
+            if roll <= weight {
+                return idx;
+            }
+            roll -= weight;
+        }
+        weights.len().saturating_sub(1)
+    }
+
+    /// 由本局种子与键派生的子种子，不消耗随机数，同一局中同一个键（如地点）总得到同样的结果
+    pub fn derive_seed(&self, key: &str) -> u64 {
+        let hash = key.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+        let mut z = (self.seed ^ hash).wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+impl Default for GameRng {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_seed_replays_same_sequence() {
+        let mut a = GameRng::new(42);
+        let mut b = GameRng::new(42);
+        let first = (0..8).map(|_| a.next_u64()).collect::<Vec<u64>>();
+        assert_eq!(first, (0..8).map(|_| b.next_u64()).collect::<Vec<u64>>());
+        assert_ne!(first, {
+            let mut c = GameRng::new(43);
+            (0..8).map(|_| c.next_u64()).collect::<Vec<u64>>()
+        });
+
+        let restored: GameRng = serde_json::from_str(&serde_json::to_string(&a).unwrap()).unwrap();
+        assert_eq!(restored.clone().next_u64(), a.next_u64());
+        assert_eq!(restored.seed, 42);
+    }
+
+    #[test]
+    fn test_ranges_and_weights_stay_in_bounds() {
+        let mut rng = GameRng::new(7);
+        for _ in 0..200 {
+            assert!((3..=5).contains(&rng.range_u32(3, 5)));
+            assert!((0.2..=0.4).contains(&rng.range_f32(0.2, 0.4)));
+            assert_eq!(rng.weighted_index(&[0, 5, 0]), 1);
+        }
+        assert_eq!(rng.range_u32(9, 9), 9);
+        assert_eq!(rng.weighted_index(&[0, 0]), 0);
+        for _ in 0..50 {
+            assert!(rng.weighted_index(&[u32::MAX, u32::MAX, 0]) < 2);
+        }
+
+        let state = rng.state;
+        assert_eq!(rng.derive_seed("sect"), rng.derive_seed("sect"));
+        assert_ne!(rng.derive_seed("sect"), rng.derive_seed("city"));
+        assert_eq!(rng.state, state);
+    }
+}
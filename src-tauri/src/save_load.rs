@@ -1,11 +1,79 @@
-﻿use crate::game_state::GameState;
+﻿use crate::achievements::AchievementBook;
+use crate::ending::EndingGallery;
+use crate::event_log::EventArchive;
+use crate::game_state::GameState;
+use crate::npc::NPC;
 use crate::plot_engine::PlotState;
 use anyhow::{anyhow, Result};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use schemars::JsonSchema;
+use serde::de::IgnoredAny;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MAX_TRACKED_SAVE_TICKETS: usize = 32;
+const MANIFEST_VERSION: u32 = 1;
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+const AUTOSAVE_SETTINGS_FILE: &str = "autosave_settings.json";
+const ENDING_GALLERY_FILE: &str = "ending_gallery.json";
+const ACHIEVEMENTS_FILE: &str = "achievements.json";
+const COMPRESSED_SAVE_SUFFIX: &str = ".json.gz";
+const MAX_AUTOSAVE_INTERVAL: u32 = 100;
+/// 存档预览中剧情摘录的最大字数
+const SAVE_EXCERPT_MAX_CHARS: usize = 80;
+const SAVE_BUNDLE_FORMAT: &str = "nobody-save-bundle";
+const SAVE_BUNDLE_VERSION: u32 = 1;
+
+/// 自动存档使用的轮换槽位，位于手动存档 1-99 之外
+pub const AUTOSAVE_FIRST_SLOT: u32 = 100;
+pub const AUTOSAVE_SLOT_COUNT: u32 = 5;
+
+/// 各存档槽的写锁，按槽位文件路径区分，不同存档目录互不影响
+static SLOT_LOCKS: OnceLock<Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>> = OnceLock::new();
+
+pub fn is_autosave_slot(slot_id: u32) -> bool {
+    (AUTOSAVE_FIRST_SLOT..AUTOSAVE_FIRST_SLOT + AUTOSAVE_SLOT_COUNT).contains(&slot_id)
+}
+
+/// 自动存档配置：每完成 `interval_actions` 次玩家行动写入一个轮换槽位
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AutosaveSettings {
+    pub enabled: bool,
+    pub interval_actions: u32,
+}
+
+impl Default for AutosaveSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_actions: 10,
+        }
+    }
+}
+
+impl AutosaveSettings {
+    pub fn validate(&self) -> Result<()> {
+        if !(1..=MAX_AUTOSAVE_INTERVAL).contains(&self.interval_actions) {
+            return Err(anyhow!(
+                "自动存档间隔必须在 1-{} 次行动之间，当前为 {}",
+                MAX_AUTOSAVE_INTERVAL,
+                self.interval_actions
+            ));
+        }
+        Ok(())
+    }
+}
 
 /// 游戏持久化的存档/加载系统
+#[derive(Debug, Clone)]
 pub struct SaveLoadSystem {
     save_directory: PathBuf,
 }
@@ -17,10 +85,39 @@ pub struct SaveData {
     pub timestamp: u64,
     pub game_state: GameState,
     pub plot_state: Option<PlotState>,
+    /// NPC 名册（含关系、记忆与情绪），旧存档为空
+    #[serde(default)]
+    pub npcs: Vec<NPC>,
+    /// 存档时所在章节的标题，供读档界面预览
+    #[serde(default)]
+    pub chapter_title: Option<String>,
+    /// 存档时最新一段剧情的摘录
+    #[serde(default)]
+    pub plot_excerpt: Option<String>,
+    /// 事件日志的归档摘要，旧存档为空
+    #[serde(default)]
+    pub event_archives: Vec<EventArchive>,
+    /// 身在秘境时压栈的主线剧情，旧存档为空
+    #[serde(default)]
+    pub plot_stack: Vec<PlotState>,
 }
 
-/// 存档文件元数据
+/// 可在设备间搬运或分享的单文件存档包
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveBundle {
+    pub format: String,
+    pub bundle_version: u32,
+    pub exported_at: u64,
+    /// 导出时所在的存档槽
+    pub source_slot: u32,
+    /// `save` 原文的校验和，导入时据此发现传输中的损坏
+    pub checksum: String,
+    /// 完整存档（包括剧情状态、NPC 名册与事件归档）的 JSON 原文；保留原文以免浮点数重新序列化后校验和不一致
+    pub save: String,
+}
+
+/// 存档文件元数据
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct SaveInfo {
     pub slot_id: u32,
     pub version: String,
@@ -30,6 +127,234 @@ pub struct SaveInfo {
     pub realm: String,
     pub location: String,
     pub game_time: String,
+    /// 存档对局开启的房规，标准规则时为空
+    #[serde(default)]
+    pub house_rules: Vec<String>,
+    /// 累计游玩时长（秒）
+    #[serde(default)]
+    pub play_time_secs: u64,
+    #[serde(default)]
+    pub action_count: u32,
+    #[serde(default)]
+    pub chapter_title: Option<String>,
+    #[serde(default)]
+    pub plot_excerpt: Option<String>,
+    /// 对局已达成结局
+    #[serde(default)]
+    pub completed: bool,
+    #[serde(default)]
+    pub ending_title: Option<String>,
+}
+
+/// 存档清单中单个槽位的记录
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub slot_id: u32,
+    pub file_name: String,
+    pub size_bytes: u64,
+    pub checksum: String,
+    pub modified_at: u64,
+    /// 以下字段来自存档内容，存档无法解析时为空
+    pub save_version: Option<String>,
+    pub saved_at: Option<u64>,
+    pub player_name: Option<String>,
+    /// 对局已达成结局
+    #[serde(default)]
+    pub finished: bool,
+}
+
+/// 存档的头部字段；生成清单时只解析这些，不必反序列化整局状态
+#[derive(Deserialize)]
+struct SaveHeader {
+    version: String,
+    timestamp: u64,
+    game_state: GameStateHeader,
+}
+
+#[derive(Deserialize)]
+struct GameStateHeader {
+    player: PlayerHeader,
+    #[serde(default)]
+    ending: Option<IgnoredAny>,
+}
+
+#[derive(Deserialize)]
+struct PlayerHeader {
+    name: String,
+}
+
+/// 供备份工具使用的全部存档清单
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SaveManifest {
+    pub manifest_version: u32,
+    pub generated_at: u64,
+    pub entries: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ManifestIssueKind {
+    Missing,
+    SizeMismatch,
+    ChecksumMismatch,
+    Unreadable,
+    Untracked,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestIssue {
+    pub slot_id: u32,
+    pub kind: ManifestIssueKind,
+    pub detail: String,
+}
+
+/// 存档与清单的比对结果
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestVerification {
+    pub checked: usize,
+    pub intact: usize,
+    pub issues: Vec<ManifestIssue>,
+}
+
+impl ManifestVerification {
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// 异步存档的阶段
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SaveStage {
+    Queued,
+    Serializing,
+    Writing,
+    Verifying,
+    Completed,
+    Failed,
+}
+
+impl SaveStage {
+    pub fn is_finished(&self) -> bool {
+        matches!(self, SaveStage::Completed | SaveStage::Failed)
+    }
+}
+
+/// 异步存档任务的进度
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SaveProgress {
+    pub ticket: u64,
+    pub slot_id: u32,
+    pub stage: SaveStage,
+    pub error: Option<String>,
+}
+
+/// 记录异步存档任务进度，可跨线程共享
+#[derive(Debug, Clone, Default)]
+pub struct SaveProgressTracker {
+    inner: Arc<Mutex<SaveProgressRegistry>>,
+}
+
+#[derive(Debug, Default)]
+struct SaveProgressRegistry {
+    next_ticket: u64,
+    entries: HashMap<u64, SaveProgress>,
+}
+
+impl SaveProgressTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 登记新的存档任务并返回票据
+    pub fn create(&self, slot_id: u32) -> SaveProgress {
+        let mut registry = self.inner.lock().unwrap();
+        registry.next_ticket = registry.next_ticket.saturating_add(1);
+        let ticket = registry.next_ticket;
+
+        if registry.entries.len() >= MAX_TRACKED_SAVE_TICKETS {
+            // 只淘汰已结束的任务，进行中的任务必须保持可查询
+            let oldest_finished = registry
+                .entries
+                .values()
+                .filter(|progress| progress.stage.is_finished())
+                .map(|progress| progress.ticket)
+                .min();
+            if let Some(oldest) = oldest_finished {
+                registry.entries.remove(&oldest);
+            }
+        }
+
+        let progress = SaveProgress {
+            ticket,
+            slot_id,
+            stage: SaveStage::Queued,
+            error: None,
+        };
+        registry.entries.insert(ticket, progress.clone());
+        progress
+    }
+
+    pub fn update(&self, ticket: u64, stage: SaveStage, error: Option<String>) -> Option<SaveProgress> {
+        let mut registry = self.inner.lock().unwrap();
+        let progress = registry.entries.get_mut(&ticket)?;
+        progress.stage = stage;
+        progress.error = error;
+        Some(progress.clone())
+    }
+
+    pub fn get(&self, ticket: u64) -> Option<SaveProgress> {
+        let registry = self.inner.lock().unwrap();
+        registry.entries.get(&ticket).cloned()
+    }
+}
+
+/// 已准备好数据、等待在后台线程执行的存档任务
+#[derive(Debug)]
+pub struct SaveJob {
+    pub ticket: u64,
+    pub slot_id: u32,
+    save_data: SaveData,
+    system: SaveLoadSystem,
+    tracker: SaveProgressTracker,
+}
+
+impl SaveJob {
+    pub fn new(
+        slot_id: u32,
+        save_data: SaveData,
+        system: SaveLoadSystem,
+        tracker: SaveProgressTracker,
+    ) -> Self {
+        let ticket = tracker.create(slot_id).ticket;
+        Self {
+            ticket,
+            slot_id,
+            save_data,
+            system,
+            tracker,
+        }
+    }
+
+    /// 执行阻塞的存档I/O，每个阶段都会更新进度并回调
+    pub fn run(self, mut on_progress: impl FnMut(&SaveProgress)) -> Result<()> {
+        let ticket = self.ticket;
+        let tracker = self.tracker.clone();
+        let result = self
+            .system
+            .save_game_with_progress(self.slot_id, &self.save_data, |stage| {
+                if let Some(progress) = tracker.update(ticket, stage, None) {
+                    on_progress(&progress);
+                }
+            });
+
+        let final_progress = match &result {
+            Ok(()) => tracker.update(ticket, SaveStage::Completed, None),
+            Err(e) => tracker.update(ticket, SaveStage::Failed, Some(e.to_string())),
+        };
+        if let Some(progress) = final_progress {
+            on_progress(&progress);
+        }
+        result
+    }
 }
 
 impl SaveLoadSystem {
@@ -74,6 +399,10 @@ impl SaveLoadSystem {
         Ok(())
     }
 
+    pub fn save_directory(&self) -> &Path {
+        &self.save_directory
+    }
+
     /// 获取存档槽的存档文件路径
     fn get_save_path(&self, slot_id: u32) -> PathBuf {
         let mut path = self.save_directory.clone();
@@ -81,29 +410,133 @@ impl SaveLoadSystem {
         path
     }
 
+    fn get_compressed_save_path(&self, slot_id: u32) -> PathBuf {
+        self.save_directory
+            .join(format!("save_{}{}", slot_id, COMPRESSED_SAVE_SUFFIX))
+    }
+
+    /// 存档槽当前的存档文件，未压缩的优先
+    fn existing_save_path(&self, slot_id: u32) -> Option<PathBuf> {
+        [self.get_save_path(slot_id), self.get_compressed_save_path(slot_id)]
+            .into_iter()
+            .find(|path| path.exists())
+    }
+
+    /// 读出存档 JSON，压缩存档先解压
+    fn read_save_json(path: &Path) -> Result<String> {
+        if !is_compressed_save(path) {
+            return Ok(fs::read_to_string(path)?);
+        }
+        let mut json = String::new();
+        GzDecoder::new(fs::File::open(path)?).read_to_string(&mut json)?;
+        Ok(json)
+    }
+
+    /// 同一存档槽的存档、压缩与删除依次进行，并发的两次存档不会交错写入
+    fn lock_slot(&self, slot_id: u32) -> Arc<Mutex<()>> {
+        let locks = SLOT_LOCKS.get_or_init(Default::default);
+        let mut locks = locks.lock().unwrap_or_else(PoisonError::into_inner);
+        locks.entry(self.get_save_path(slot_id)).or_default().clone()
+    }
+
+    /// 将存档槽压缩为 gzip 文件并删除原文件，返回节省的字节数；已压缩时返回 0
+    pub fn compress_save(&self, slot_id: u32) -> Result<u64> {
+        let slot_lock = self.lock_slot(slot_id);
+        let _guard = hold(&slot_lock);
+        let save_path = self.get_save_path(slot_id);
+        if !save_path.exists() {
+            return if self.get_compressed_save_path(slot_id).exists() {
+                Ok(0)
+            } else {
+                Err(anyhow!("未找到存档槽 {} 的存档文件", slot_id))
+            };
+        }
+
+        let json = fs::read_to_string(&save_path)?;
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(json.as_bytes())?;
+        let compressed = encoder.finish()?;
+        let compressed_path = self.get_compressed_save_path(slot_id);
+        let temp_path = temp_path_for(&compressed_path);
+        let verified = write_synced(&temp_path, &compressed)
+            .and_then(|()| {
+                let mut written = String::new();
+                GzDecoder::new(fs::File::open(&temp_path)?).read_to_string(&mut written)?;
+                Ok(written)
+            })
+            .and_then(|written| {
+                if written == json {
+                    Ok(())
+                } else {
+                    Err(anyhow!("存档槽 {} 压缩校验失败", slot_id))
+                }
+            });
+        if let Err(error) = verified {
+            let _ = fs::remove_file(&temp_path);
+            return Err(error);
+        }
+        fs::rename(&temp_path, &compressed_path)?;
+
+        fs::remove_file(&save_path)?;
+        Ok((json.len() as u64).saturating_sub(compressed.len() as u64))
+    }
+
     /// 保存游戏到存档槽
     pub fn save_game(&self, slot_id: u32, save_data: &SaveData) -> Result<()> {
+        self.save_game_with_progress(slot_id, save_data, |_| {})
+    }
+
+    /// 保存游戏到存档槽，并在序列化、写入、校验各阶段回调
+    pub fn save_game_with_progress(
+        &self,
+        slot_id: u32,
+        save_data: &SaveData,
+        mut on_stage: impl FnMut(SaveStage),
+    ) -> Result<()> {
         self.ensure_save_directory()?;
 
         // 验证存档数据
         self.validate_save_data(save_data)?;
 
+        on_stage(SaveStage::Serializing);
         let save_path = self.get_save_path(slot_id);
         let json = serde_json::to_string_pretty(save_data)?;
-        fs::write(save_path, json)?;
+
+        // 先写入同目录下的临时文件并校验，再改名覆盖原存档，写到一半崩溃也不会损坏槽位
+        let slot_lock = self.lock_slot(slot_id);
+        let _guard = hold(&slot_lock);
+        let temp_path = temp_path_for(&save_path);
+        on_stage(SaveStage::Writing);
+        let verified = write_synced(&temp_path, json.as_bytes()).and_then(|()| {
+            on_stage(SaveStage::Verifying);
+            if fs::read_to_string(&temp_path)? == json {
+                Ok(())
+            } else {
+                Err(anyhow!("存档槽 {} 写入校验失败", slot_id))
+            }
+        });
+        if let Err(error) = verified {
+            let _ = fs::remove_file(&temp_path);
+            return Err(error);
+        }
+        fs::rename(&temp_path, &save_path)?;
+
+        // 覆盖已压缩的槽位时移除旧的压缩存档
+        let compressed_path = self.get_compressed_save_path(slot_id);
+        if compressed_path.exists() {
+            fs::remove_file(compressed_path)?;
+        }
 
         Ok(())
     }
 
     /// 从存档槽加载游戏
     pub fn load_game(&self, slot_id: u32) -> Result<SaveData> {
-        let save_path = self.get_save_path(slot_id);
-
-        if !save_path.exists() {
+        let Some(save_path) = self.existing_save_path(slot_id) else {
             return Err(anyhow!("未找到存档槽 {} 的存档文件", slot_id));
-        }
+        };
 
-        let json = fs::read_to_string(save_path)?;
+        let json = Self::read_save_json(&save_path)?;
         let save_data: SaveData = serde_json::from_str(&json)?;
 
         // 验证加载的数据
@@ -112,63 +545,340 @@ impl SaveLoadSystem {
         Ok(save_data)
     }
 
-    /// 列出所有可用的存档
+    /// 列出所有可用的手动存档
     pub fn list_saves(&self) -> Result<Vec<SaveInfo>> {
+        self.list_slots(|slot_id| !is_autosave_slot(slot_id))
+    }
+
+    /// 列出自动存档，最新的在前
+    pub fn list_autosaves(&self) -> Result<Vec<SaveInfo>> {
+        let mut saves = self.list_slots(is_autosave_slot)?;
+        saves.sort_by(|a, b| b.timestamp.cmp(&a.timestamp).then(a.slot_id.cmp(&b.slot_id)));
+        Ok(saves)
+    }
+
+    /// 下一次自动存档写入的槽位：优先使用空槽，否则覆盖最早写入的槽位
+    pub fn next_autosave_slot(&self) -> Result<u32> {
+        let mut oldest: Option<(SystemTime, u32)> = None;
+        for slot_id in AUTOSAVE_FIRST_SLOT..AUTOSAVE_FIRST_SLOT + AUTOSAVE_SLOT_COUNT {
+            let Ok(metadata) = fs::metadata(self.get_save_path(slot_id)) else {
+                return Ok(slot_id);
+            };
+            let modified = metadata.modified()?;
+            if oldest.is_none_or(|(time, _)| modified < time) {
+                oldest = Some((modified, slot_id));
+            }
+        }
+        Ok(oldest.map_or(AUTOSAVE_FIRST_SLOT, |(_, slot_id)| slot_id))
+    }
+
+    /// 读取自动存档配置，未配置时使用默认值
+    pub fn autosave_settings(&self) -> AutosaveSettings {
+        fs::read_to_string(self.save_directory.join(AUTOSAVE_SETTINGS_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// 校验并保存自动存档配置
+    pub fn save_autosave_settings(&self, settings: &AutosaveSettings) -> Result<()> {
+        settings.validate()?;
+        self.ensure_save_directory()?;
+        fs::write(
+            self.save_directory.join(AUTOSAVE_SETTINGS_FILE),
+            serde_json::to_string_pretty(settings)?,
+        )?;
+        Ok(())
+    }
+
+    /// 读取跨局的结局图鉴，文件缺失或损坏时为空
+    pub fn ending_gallery(&self) -> EndingGallery {
+        fs::read_to_string(self.save_directory.join(ENDING_GALLERY_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_ending_gallery(&self, gallery: &EndingGallery) -> Result<()> {
+        self.ensure_save_directory()?;
+        fs::write(
+            self.save_directory.join(ENDING_GALLERY_FILE),
+            serde_json::to_string_pretty(gallery)?,
+        )?;
+        Ok(())
+    }
+
+    /// 跨局保存的成就簿，与结局图鉴同放在存档目录
+    pub fn achievement_book(&self) -> AchievementBook {
+        fs::read_to_string(self.save_directory.join(ACHIEVEMENTS_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_achievement_book(&self, book: &AchievementBook) -> Result<()> {
+        self.ensure_save_directory()?;
+        fs::write(
+            self.save_directory.join(ACHIEVEMENTS_FILE),
+            serde_json::to_string_pretty(book)?,
+        )?;
+        Ok(())
+    }
+
+    fn list_slots(&self, include: impl Fn(u32) -> bool) -> Result<Vec<SaveInfo>> {
         if !self.save_directory.exists() {
             return Ok(Vec::new());
         }
 
         let mut saves = Vec::new();
 
+        for (slot_id, _) in self.slot_files()? {
+            if !include(slot_id) {
+                continue;
+            }
+            if let Ok(save_data) = self.load_game(slot_id) {
+                let save_info = SaveInfo {
+                    slot_id,
+                    version: save_data.version,
+                    timestamp: save_data.timestamp,
+                    player_name: save_data.game_state.player.name.clone(),
+                    player_age: save_data.game_state.player.stats.lifespan.current_age,
+                    realm: save_data
+                        .game_state
+                        .player
+                        .stats
+                        .cultivation_realm
+                        .name
+                        .clone(),
+                    location: save_data.game_state.player.location.clone(),
+                    game_time: format!(
+                        "第 {} 年，第 {} 月，第 {} 日",
+                        save_data.game_state.game_time.year,
+                        save_data.game_state.game_time.month,
+                        save_data.game_state.game_time.day
+                    ),
+                    house_rules: save_data
+                        .game_state
+                        .house_rules
+                        .enabled_labels()
+                        .into_iter()
+                        .map(str::to_string)
+                        .collect(),
+                    play_time_secs: save_data.game_state.play_time_secs,
+                    action_count: save_data.game_state.action_count,
+                    chapter_title: save_data.chapter_title,
+                    plot_excerpt: save_data.plot_excerpt,
+                    completed: save_data.game_state.ending.is_some(),
+                    ending_title: save_data.game_state.ending.map(|ending| ending.title),
+                };
+                saves.push(save_info);
+            }
+        }
+
+        Ok(saves)
+    }
+
+    /// 扫描存档目录中的存档文件，按槽位排序
+    fn slot_files(&self) -> Result<Vec<(u32, PathBuf)>> {
+        if !self.save_directory.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut slots = Vec::new();
         for entry in fs::read_dir(&self.save_directory)? {
-            let entry = entry?;
-            let path = entry.path();
-
-            if path.extension().and_then(|s| s.to_str()) == Some("json") {
-                if let Some(file_name) = path.file_stem().and_then(|s| s.to_str()) {
-                    if let Some(rest) = file_name.strip_prefix("save_") {
-                        if let Ok(slot_id) = rest.parse::<u32>() {
-                            if let Ok(save_data) = self.load_game(slot_id) {
-                                let save_info = SaveInfo {
-                                    slot_id,
-                                    version: save_data.version,
-                                    timestamp: save_data.timestamp,
-                                    player_name: save_data.game_state.player.name.clone(),
-                                    player_age: save_data.game_state.player.stats.lifespan.current_age,
-                                    realm: save_data
-                                        .game_state
-                                        .player
-                                        .stats
-                                        .cultivation_realm
-                                        .name
-                                        .clone(),
-                                    location: save_data.game_state.player.location.clone(),
-                                    game_time: format!(
-                                        "第 {} 年，第 {} 月，第 {} 日",
-                                        save_data.game_state.game_time.year,
-                                        save_data.game_state.game_time.month,
-                                        save_data.game_state.game_time.day
-                                    ),
-                                };
-                                saves.push(save_info);
-                            }
-                        }
-                    }
+            let path = entry?.path();
+            let slot_id = path
+                .file_name()
+                .and_then(|s| s.to_str())
+                .and_then(|name| {
+                    name.strip_suffix(COMPRESSED_SAVE_SUFFIX)
+                        .or_else(|| name.strip_suffix(".json"))
+                })
+                .and_then(|name| name.strip_prefix("save_"))
+                .and_then(|rest| rest.parse::<u32>().ok());
+            if let Some(slot_id) = slot_id {
+                slots.push((slot_id, path));
+            }
+        }
+
+        // 同一槽位同时存在两种文件时只保留未压缩的那份
+        slots.sort_by_key(|(slot_id, path)| (*slot_id, is_compressed_save(path)));
+        slots.dedup_by_key(|(slot_id, _)| *slot_id);
+        Ok(slots)
+    }
+
+    /// 生成所有存档槽的清单（校验和、大小、时间戳、版本、玩家名、是否完结）
+    pub fn build_manifest(&self) -> Result<SaveManifest> {
+        let mut entries = Vec::new();
+        for (slot_id, path) in self.slot_files()? {
+            let bytes = fs::read(&path)?;
+            let modified_at = fs::metadata(&path)?
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            let header = Self::read_save_json(&path)
+                .ok()
+                .and_then(|json| serde_json::from_str::<SaveHeader>(&json).ok());
+
+            entries.push(ManifestEntry {
+                slot_id,
+                file_name: path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .unwrap_or_default()
+                    .to_string(),
+                size_bytes: bytes.len() as u64,
+                checksum: checksum(&bytes),
+                modified_at,
+                finished: header
+                    .as_ref()
+                    .is_some_and(|h| h.game_state.ending.is_some()),
+                save_version: header.as_ref().map(|h| h.version.clone()),
+                saved_at: header.as_ref().map(|h| h.timestamp),
+                player_name: header.map(|h| h.game_state.player.name),
+            });
+        }
+
+        Ok(SaveManifest {
+            manifest_version: MANIFEST_VERSION,
+            generated_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            entries,
+        })
+    }
+
+    /// 生成清单并写入指定文件
+    pub fn export_manifest(&self, path: impl AsRef<Path>) -> Result<SaveManifest> {
+        let manifest = self.build_manifest()?;
+        fs::write(path, serde_json::to_string_pretty(&manifest)?)?;
+        Ok(manifest)
+    }
+
+    /// 将当前存档与清单比对，找出缺失、损坏或清单外的存档
+    pub fn verify_against_manifest(&self, path: impl AsRef<Path>) -> Result<ManifestVerification> {
+        let manifest: SaveManifest = serde_json::from_str(&fs::read_to_string(path)?)?;
+        if manifest.manifest_version > MANIFEST_VERSION {
+            return Err(anyhow!(
+                "不支持的清单版本: {}",
+                manifest.manifest_version
+            ));
+        }
+
+        let mut issues = Vec::new();
+        let mut intact = 0;
+        for entry in &manifest.entries {
+            let Some(save_path) = self.existing_save_path(entry.slot_id) else {
+                issues.push(ManifestIssue {
+                    slot_id: entry.slot_id,
+                    kind: ManifestIssueKind::Missing,
+                    detail: format!("缺少存档文件 {}", entry.file_name),
+                });
+                continue;
+            };
+
+            let bytes = match fs::read(&save_path) {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    issues.push(ManifestIssue {
+                        slot_id: entry.slot_id,
+                        kind: ManifestIssueKind::Unreadable,
+                        detail: e.to_string(),
+                    });
+                    continue;
                 }
+            };
+
+            if bytes.len() as u64 != entry.size_bytes {
+                issues.push(ManifestIssue {
+                    slot_id: entry.slot_id,
+                    kind: ManifestIssueKind::SizeMismatch,
+                    detail: format!("期望 {} 字节，实际 {} 字节", entry.size_bytes, bytes.len()),
+                });
+            } else if checksum(&bytes) != entry.checksum {
+                issues.push(ManifestIssue {
+                    slot_id: entry.slot_id,
+                    kind: ManifestIssueKind::ChecksumMismatch,
+                    detail: format!("校验和不匹配，期望 {}", entry.checksum),
+                });
+            } else {
+                intact += 1;
             }
         }
 
-        saves.sort_by_key(|s| s.slot_id);
-        Ok(saves)
+        for (slot_id, _) in self.slot_files()? {
+            if !manifest.entries.iter().any(|e| e.slot_id == slot_id) {
+                issues.push(ManifestIssue {
+                    slot_id,
+                    kind: ManifestIssueKind::Untracked,
+                    detail: "存档不在清单中".to_string(),
+                });
+            }
+        }
+
+        Ok(ManifestVerification {
+            checked: manifest.entries.len(),
+            intact,
+            issues,
+        })
+    }
+
+    /// 将存档槽连同剧情状态与事件归档打包为单个可移植文件
+    pub fn export_save(&self, slot_id: u32, path: impl AsRef<Path>) -> Result<SaveBundle> {
+        let save = serde_json::to_string(&self.load_game(slot_id)?)?;
+        let bundle = SaveBundle {
+            format: SAVE_BUNDLE_FORMAT.to_string(),
+            bundle_version: SAVE_BUNDLE_VERSION,
+            exported_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            source_slot: slot_id,
+            checksum: checksum(save.as_bytes()),
+            save,
+        };
+        fs::write(path, serde_json::to_string_pretty(&bundle)?)?;
+        Ok(bundle)
+    }
+
+    /// 校验存档包后写入第一个空闲的手动存档槽，返回该槽位
+    pub fn import_save(&self, path: impl AsRef<Path>) -> Result<u32> {
+        let json = fs::read_to_string(path)?;
+        let bundle: SaveBundle =
+            serde_json::from_str(&json).map_err(|e| anyhow!("不是有效的存档包: {}", e))?;
+        if bundle.format != SAVE_BUNDLE_FORMAT {
+            return Err(anyhow!("不是有效的存档包: 格式为 {}", bundle.format));
+        }
+        if bundle.bundle_version > SAVE_BUNDLE_VERSION {
+            return Err(anyhow!(
+                "存档包版本 {} 高于当前支持的版本 {}",
+                bundle.bundle_version,
+                SAVE_BUNDLE_VERSION
+            ));
+        }
+        if checksum(bundle.save.as_bytes()) != bundle.checksum {
+            return Err(anyhow!("存档包校验失败，文件可能已损坏"));
+        }
+
+        let save_data: SaveData = serde_json::from_str(&bundle.save)?;
+        self.validate_save_data(&save_data)?;
+        let slot_id = (1..AUTOSAVE_FIRST_SLOT)
+            .find(|slot_id| self.existing_save_path(*slot_id).is_none())
+            .ok_or_else(|| anyhow!("没有空闲的存档槽"))?;
+        self.save_game(slot_id, &save_data)?;
+        Ok(slot_id)
     }
 
     /// 删除存档文件
     pub fn delete_save(&self, slot_id: u32) -> Result<()> {
-        let save_path = self.get_save_path(slot_id);
-
-        if !save_path.exists() {
+        let slot_lock = self.lock_slot(slot_id);
+        let _guard = hold(&slot_lock);
+        let Some(save_path) = self.existing_save_path(slot_id) else {
             return Err(anyhow!("未找到存档槽 {} 的存档文件", slot_id));
-        }
+        };
 
         fs::remove_file(save_path)?;
         Ok(())
@@ -203,6 +913,38 @@ impl SaveLoadSystem {
     }
 }
 
+fn hold(lock: &Mutex<()>) -> MutexGuard<'_, ()> {
+    lock.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// 存档文件旁的临时文件，扩展名不是 .json，扫描存档槽时不会被当成存档
+fn temp_path_for(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+/// 写入并落盘，改名前确保内容已真正写到磁盘上
+fn write_synced(path: &Path, bytes: &[u8]) -> Result<()> {
+    let mut file = fs::File::create(path)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+/// FNV-1a 64 位校验和，用于发现备份恢复后的文件损坏
+fn is_compressed_save(path: &Path) -> bool {
+    path.to_str()
+        .is_some_and(|name| name.ends_with(COMPRESSED_SAVE_SUFFIX))
+}
+
+pub(crate) fn checksum(bytes: &[u8]) -> String {
+    let hash = bytes.iter().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME)
+    });
+    format!("fnv1a64:{:016x}", hash)
+}
+
 impl Default for SaveLoadSystem {
     fn default() -> Self {
         Self::new()
@@ -220,6 +962,11 @@ impl SaveData {
                 .as_secs(),
             game_state,
             plot_state: None,
+            npcs: Vec::new(),
+            chapter_title: None,
+            plot_excerpt: None,
+            event_archives: Vec::new(),
+            plot_stack: Vec::new(),
         }
     }
 
@@ -230,18 +977,68 @@ impl SaveData {
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap()
                 .as_secs(),
+            chapter_title: plot_state
+                .as_ref()
+                .map(|plot_state| plot_state.current_chapter.title.clone())
+                .filter(|title| !title.trim().is_empty()),
+            plot_excerpt: plot_state.as_ref().and_then(plot_excerpt),
             game_state,
             plot_state,
+            npcs: Vec::new(),
+            event_archives: Vec::new(),
+            plot_stack: Vec::new(),
         }
     }
+
+    pub fn with_npcs(mut self, npcs: Vec<NPC>) -> Self {
+        self.npcs = npcs;
+        self
+    }
+
+    pub fn with_event_archives(mut self, archives: Vec<EventArchive>) -> Self {
+        self.event_archives = archives;
+        self
+    }
+
+    pub fn with_plot_stack(mut self, plot_stack: Vec<PlotState>) -> Self {
+        self.plot_stack = plot_stack;
+        self
+    }
+}
+
+/// 本章最新一段正文的开头，本章尚无正文时取剧情历史的最后一段
+fn plot_excerpt(plot_state: &PlotState) -> Option<String> {
+    let latest = plot_state
+        .current_chapter
+        .content
+        .iter()
+        .rev()
+        .chain(plot_state.plot_history.iter().rev())
+        .find(|text| !text.trim().is_empty())?
+        .trim();
+    let mut excerpt = latest
+        .chars()
+        .take(SAVE_EXCERPT_MAX_CHARS)
+        .collect::<String>();
+    if latest.chars().count() > SAVE_EXCERPT_MAX_CHARS {
+        excerpt.push('…');
+    }
+    Some(excerpt)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::game_state::{Character, GameTime, WorldState};
+    use crate::difficulty::DifficultySettings;
+    use crate::house_rules::HouseRules;
+    use crate::karma::Karma;
+    use crate::quest_system::QuestLog;
+    use crate::loot::LootState;
+    use crate::rng::GameRng;
+    use crate::economy::Resources;
     use crate::models::{CharacterStats, CultivationRealm, Element, Grade, Lifespan, SpiritualRoot};
-    use crate::script::{InitialState, Location, Script, ScriptType, WorldSetting};
+    use crate::script::{Climate, InitialState, Location, LocationKind, Script, ScriptType, WorldSetting};
     use tempfile::TempDir;
 
     fn create_test_game_state() -> GameState {
@@ -254,14 +1051,17 @@ mod tests {
             name: "Azure Cloud Sect".to_string(),
             description: "A peaceful cultivation sect".to_string(),
             spiritual_energy: 1.0,
+            kind: LocationKind::Wilderness,
+            climate: Climate::Temperate,
         }];
 
         let initial_state = InitialState {
             player_name: "Test Player".to_string(),
             player_spiritual_root: SpiritualRoot {
                 element: Element::Fire,
-                grade: Grade::Heavenly,
+                grade: Grade::heavenly(),
                 affinity: 0.8,
+                tier_multiplier: None,
             },
             starting_location: "sect".to_string(),
             starting_age: 16,
@@ -278,11 +1078,14 @@ mod tests {
         let stats = CharacterStats {
             spiritual_root: SpiritualRoot {
                 element: Element::Fire,
-                grade: Grade::Heavenly,
+                grade: Grade::heavenly(),
                 affinity: 0.8,
+                tier_multiplier: None,
             },
             cultivation_realm: CultivationRealm::new("Qi Condensation".to_string(), 1, 0, 1.0),
             techniques: Vec::new(),
+            status_effects: Default::default(),
+            insight: 0,
             lifespan: Lifespan {
                 current_age: 16,
                 max_age: 100,
@@ -307,6 +1110,20 @@ mod tests {
             world_state,
             game_time,
             event_history: Vec::new(),
+            version: 0,
+            loot_state: LootState::default(),
+            house_rules: HouseRules::default(),
+            difficulty: DifficultySettings::default(),
+            quests: QuestLog::default(),
+            combat: None,
+            ending: None,
+            play_time_secs: 0,
+            action_count: 0,
+            rng: GameRng::default(),
+            resources: Resources::default(),
+            market: None,
+            stat_history: Vec::new(),
+            karma: Karma::default(),
         }
     }
 
@@ -381,6 +1198,117 @@ mod tests {
         assert_eq!(saves[2].slot_id, 3);
     }
 
+    #[test]
+    fn test_export_and_import_save_bundle() {
+        let source_dir = TempDir::new().unwrap();
+        let source = SaveLoadSystem::with_directory(source_dir.path().join("saves"));
+        let save_data = SaveData::from_game_state(create_test_game_state()).with_event_archives(
+            vec![EventArchive {
+                start_timestamp: 1,
+                end_timestamp: 9,
+                total_events: 20,
+                important_events: 2,
+                summary: "archived 20 events".to_string(),
+                spill_file: None,
+            }],
+        );
+        source.save_game(3, &save_data).unwrap();
+        let bundle_path = source_dir.path().join("bundle.json");
+        let bundle = source.export_save(3, &bundle_path).unwrap();
+        assert_eq!(bundle.source_slot, 3);
+        assert!(bundle.checksum.starts_with("fnv1a64:"));
+
+        let target_dir = TempDir::new().unwrap();
+        let target = SaveLoadSystem::with_directory(target_dir.path().to_path_buf());
+        target.save_game(1, &save_data).unwrap();
+        assert_eq!(target.import_save(&bundle_path).unwrap(), 2);
+        assert_eq!(target.load_game(2).unwrap(), save_data);
+
+        let tampered = fs::read_to_string(&bundle_path)
+            .unwrap()
+            .replace("Test Player", "Other Player");
+        fs::write(&bundle_path, tampered).unwrap();
+        let error = target.import_save(&bundle_path).unwrap_err().to_string();
+        assert!(error.contains("校验失败"));
+        fs::write(&bundle_path, "{}").unwrap();
+        assert!(target.import_save(&bundle_path).is_err());
+    }
+
+    #[test]
+    fn test_list_saves_shows_play_time_and_chapter_preview() {
+        let temp_dir = TempDir::new().unwrap();
+        let system = SaveLoadSystem::with_directory(temp_dir.path().to_path_buf());
+        let mut game_state = create_test_game_state();
+        game_state.play_time_secs = 3_600;
+        game_state.action_count = 42;
+        let mut plot_state = PlotState::new(crate::plot_engine::Scene {
+            id: "opening".to_string(),
+            name: "山门".to_string(),
+            description: String::new(),
+            location: "sect".to_string(),
+            available_options: Vec::new(),
+        });
+        plot_state.append_segment("山".repeat(SAVE_EXCERPT_MAX_CHARS + 10));
+
+        system
+            .save_game(1, &SaveData::from_game_state_with_plot(game_state, Some(plot_state)))
+            .unwrap();
+        let info = &system.list_saves().unwrap()[0];
+        assert_eq!(info.play_time_secs, 3_600);
+        assert_eq!(info.action_count, 42);
+        assert_eq!(info.chapter_title.as_deref(), Some("第一章"));
+        let excerpt = info.plot_excerpt.as_deref().unwrap();
+        assert_eq!(excerpt.chars().count(), SAVE_EXCERPT_MAX_CHARS + 1);
+        assert!(excerpt.ends_with('…'));
+    }
+
+    #[test]
+    fn test_autosave_slots_rotate_and_stay_out_of_manual_list() {
+        let temp_dir = TempDir::new().unwrap();
+        let system = SaveLoadSystem::with_directory(temp_dir.path().to_path_buf());
+        let save_data = SaveData::from_game_state(create_test_game_state());
+        system.save_game(1, &save_data).unwrap();
+
+        for expected in AUTOSAVE_FIRST_SLOT..AUTOSAVE_FIRST_SLOT + AUTOSAVE_SLOT_COUNT {
+            let slot_id = system.next_autosave_slot().unwrap();
+            assert_eq!(slot_id, expected);
+            system.save_game(slot_id, &save_data).unwrap();
+            // 保证各槽位的修改时间可区分
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        assert_eq!(system.next_autosave_slot().unwrap(), AUTOSAVE_FIRST_SLOT);
+        system.save_game(AUTOSAVE_FIRST_SLOT, &save_data).unwrap();
+        assert_eq!(system.next_autosave_slot().unwrap(), AUTOSAVE_FIRST_SLOT + 1);
+
+        let manual = system.list_saves().unwrap();
+        assert_eq!(manual.iter().map(|s| s.slot_id).collect::<Vec<u32>>(), vec![1]);
+        let autosaves = system.list_autosaves().unwrap();
+        assert_eq!(autosaves.len(), AUTOSAVE_SLOT_COUNT as usize);
+        assert!(autosaves.iter().all(|s| is_autosave_slot(s.slot_id)));
+    }
+
+    #[test]
+    fn test_autosave_settings_persist_and_validate() {
+        let temp_dir = TempDir::new().unwrap();
+        let system = SaveLoadSystem::with_directory(temp_dir.path().join("saves"));
+        assert_eq!(system.autosave_settings(), AutosaveSettings::default());
+
+        let settings = AutosaveSettings {
+            enabled: true,
+            interval_actions: 3,
+        };
+        system.save_autosave_settings(&settings).unwrap();
+        assert_eq!(system.autosave_settings(), settings);
+        assert!(system.list_saves().unwrap().is_empty());
+
+        let invalid = AutosaveSettings {
+            enabled: true,
+            interval_actions: 0,
+        };
+        assert!(system.save_autosave_settings(&invalid).is_err());
+        assert_eq!(system.autosave_settings(), settings);
+    }
+
     #[test]
     fn test_delete_save() {
         let temp_dir = TempDir::new().unwrap();
@@ -397,6 +1325,29 @@ mod tests {
         assert!(system.load_game(1).is_err());
     }
 
+    #[test]
+    fn test_compressed_save_stays_loadable() {
+        let temp_dir = TempDir::new().unwrap();
+        let system = SaveLoadSystem::with_directory(temp_dir.path().to_path_buf());
+        let save_data = SaveData::from_game_state(create_test_game_state());
+        system.save_game(1, &save_data).unwrap();
+
+        assert!(system.compress_save(1).unwrap() > 0);
+        assert_eq!(system.compress_save(1).unwrap(), 0);
+        assert!(!system.get_save_path(1).exists());
+        assert_eq!(system.load_game(1).unwrap(), save_data);
+        assert_eq!(system.list_saves().unwrap().len(), 1);
+        assert_eq!(system.build_manifest().unwrap().entries[0].file_name, "save_1.json.gz");
+
+        // 覆盖存档后回到未压缩文件
+        system.save_game(1, &save_data).unwrap();
+        assert!(!system.get_compressed_save_path(1).exists());
+        system.compress_save(1).unwrap();
+        system.delete_save(1).unwrap();
+        assert!(system.load_game(1).is_err());
+        assert!(system.compress_save(1).is_err());
+    }
+
     #[test]
     fn test_delete_nonexistent_save_returns_error() {
         let temp_dir = TempDir::new().unwrap();
@@ -435,6 +1386,74 @@ mod tests {
         assert!(saves.is_empty());
     }
 
+    #[test]
+    fn test_export_manifest_records_every_slot() {
+        let temp_dir = TempDir::new().unwrap();
+        let system = SaveLoadSystem::with_directory(temp_dir.path().join("saves"));
+        let save_data = SaveData::from_game_state(create_test_game_state());
+        system.save_game(1, &save_data).unwrap();
+        system.save_game(3, &save_data).unwrap();
+
+        let manifest_path = temp_dir.path().join("manifest.json");
+        let manifest = system.export_manifest(&manifest_path).unwrap();
+        assert_eq!(manifest.manifest_version, MANIFEST_VERSION);
+        assert_eq!(
+            manifest.entries.iter().map(|e| e.slot_id).collect::<Vec<u32>>(),
+            vec![1, 3]
+        );
+        let entry = &manifest.entries[0];
+        assert_eq!(entry.file_name, "save_1.json");
+        assert_eq!(entry.player_name.as_deref(), Some("Test Player"));
+        assert_eq!(entry.save_version.as_deref(), Some("1.0.0"));
+        assert!(entry.checksum.starts_with("fnv1a64:"));
+        assert!(entry.size_bytes > 0);
+        assert!(!entry.finished);
+
+        let verification = system.verify_against_manifest(&manifest_path).unwrap();
+        assert!(verification.is_clean());
+        assert_eq!(verification.intact, 2);
+    }
+
+    #[test]
+    fn test_verify_against_manifest_reports_damage() {
+        let temp_dir = TempDir::new().unwrap();
+        let system = SaveLoadSystem::with_directory(temp_dir.path().join("saves"));
+        let save_data = SaveData::from_game_state(create_test_game_state());
+        system.save_game(1, &save_data).unwrap();
+        system.save_game(2, &save_data).unwrap();
+        system.save_game(3, &save_data).unwrap();
+
+        let manifest_path = temp_dir.path().join("manifest.json");
+        system.export_manifest(&manifest_path).unwrap();
+
+        // 模拟备份恢复后的各种问题
+        system.delete_save(1).unwrap();
+        let path = system.get_save_path(2);
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[10] ^= 0x01;
+        std::fs::write(&path, bytes).unwrap();
+        std::fs::write(system.get_save_path(3), "{broken").unwrap();
+        system.save_game(4, &save_data).unwrap();
+
+        let verification = system.verify_against_manifest(&manifest_path).unwrap();
+        assert_eq!(verification.checked, 3);
+        assert_eq!(verification.intact, 0);
+        let kinds = verification
+            .issues
+            .iter()
+            .map(|issue| (issue.slot_id, issue.kind.clone()))
+            .collect::<Vec<(u32, ManifestIssueKind)>>();
+        assert_eq!(
+            kinds,
+            vec![
+                (1, ManifestIssueKind::Missing),
+                (2, ManifestIssueKind::ChecksumMismatch),
+                (3, ManifestIssueKind::SizeMismatch),
+                (4, ManifestIssueKind::Untracked),
+            ]
+        );
+    }
+
     #[test]
     fn test_validate_save_data() {
         let system = SaveLoadSystem::new();
@@ -474,6 +1493,92 @@ mod tests {
         assert!(save_data.timestamp > 0);
     }
 
+    #[test]
+    fn test_save_job_reports_stages_in_order() {
+        let temp_dir = TempDir::new().unwrap();
+        let system = SaveLoadSystem::with_directory(temp_dir.path().to_path_buf());
+        let tracker = SaveProgressTracker::new();
+        let save_data = SaveData::from_game_state(create_test_game_state());
+
+        let job = SaveJob::new(3, save_data.clone(), system.clone(), tracker.clone());
+        let ticket = job.ticket;
+        assert_eq!(tracker.get(ticket).unwrap().stage, SaveStage::Queued);
+
+        let mut stages = Vec::new();
+        job.run(|progress| stages.push(progress.stage.clone())).unwrap();
+
+        assert_eq!(
+            stages,
+            vec![
+                SaveStage::Serializing,
+                SaveStage::Writing,
+                SaveStage::Verifying,
+                SaveStage::Completed,
+            ]
+        );
+        assert_eq!(tracker.get(ticket).unwrap().stage, SaveStage::Completed);
+        assert_eq!(system.load_game(3).unwrap(), save_data);
+    }
+
+    #[test]
+    fn test_save_job_failure_is_recorded() {
+        let temp_dir = TempDir::new().unwrap();
+        let system = SaveLoadSystem::with_directory(temp_dir.path().to_path_buf());
+        let tracker = SaveProgressTracker::new();
+        let mut save_data = SaveData::from_game_state(create_test_game_state());
+        save_data.timestamp = 0;
+
+        let job = SaveJob::new(1, save_data, system, tracker.clone());
+        let ticket = job.ticket;
+        assert!(job.run(|_| {}).is_err());
+
+        let progress = tracker.get(ticket).unwrap();
+        assert_eq!(progress.stage, SaveStage::Failed);
+        assert!(progress.error.is_some());
+    }
+
+    #[test]
+    fn test_save_progress_tracker_evicts_only_finished_tickets() {
+        let tracker = SaveProgressTracker::new();
+        let first = tracker.create(1).ticket;
+        for _ in 1..MAX_TRACKED_SAVE_TICKETS {
+            let ticket = tracker.create(1).ticket;
+            tracker.update(ticket, SaveStage::Completed, None);
+        }
+
+        tracker.create(1);
+        assert!(tracker.get(first).is_some());
+        assert!(tracker.get(first + 1).is_none());
+    }
+
+    #[test]
+    fn test_concurrent_saves_to_one_slot_leave_a_whole_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let system = SaveLoadSystem::with_directory(temp_dir.path().to_path_buf());
+        let handles = (0..4)
+            .map(|age| {
+                let system = system.clone();
+                std::thread::spawn(move || {
+                    let mut game_state = create_test_game_state();
+                    game_state.player.stats.lifespan.current_age = 20 + age;
+                    system.save_game(1, &SaveData::from_game_state(game_state))
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.join().unwrap().unwrap();
+        }
+
+        let loaded = system.load_game(1).unwrap();
+        assert!((20..24).contains(&loaded.game_state.player.stats.lifespan.current_age));
+        let leftovers = fs::read_dir(temp_dir.path())
+            .unwrap()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_string_lossy().ends_with(".tmp"))
+            .count();
+        assert_eq!(leftovers, 0);
+    }
+
     #[test]
     fn test_multiple_slots_isolation() {
         let temp_dir = TempDir::new().unwrap();
@@ -505,8 +1610,15 @@ mod tests {
 mod property_tests {
     use super::*;
     use crate::game_state::{Character, GameTime, WorldState};
+    use crate::difficulty::DifficultySettings;
+    use crate::house_rules::HouseRules;
+    use crate::karma::Karma;
+    use crate::quest_system::QuestLog;
+    use crate::loot::LootState;
+    use crate::rng::GameRng;
+    use crate::economy::Resources;
     use crate::models::{CharacterStats, CultivationRealm, Element, Grade, Lifespan, SpiritualRoot};
-    use crate::script::{InitialState, Location, Script, ScriptType, WorldSetting};
+    use crate::script::{Climate, InitialState, Location, LocationKind, Script, ScriptType, WorldSetting};
     use proptest::prelude::*;
     use tempfile::TempDir;
 
@@ -527,14 +1639,17 @@ mod property_tests {
                 name: "宗门".to_string(),
                 description: "修仙宗门".to_string(),
                 spiritual_energy: 1.0,
+                kind: LocationKind::Wilderness,
+                climate: Climate::Temperate,
             }];
 
             let initial_state = InitialState {
                 player_name: player_name.clone(),
                 player_spiritual_root: SpiritualRoot {
                     element: Element::Fire,
-                    grade: Grade::Heavenly,
+                    grade: Grade::heavenly(),
                     affinity: 0.8,
+                    tier_multiplier: None,
                 },
                 starting_location: "sect".to_string(),
                 starting_age: age,
@@ -551,11 +1666,14 @@ mod property_tests {
             let stats = CharacterStats {
                 spiritual_root: SpiritualRoot {
                     element: Element::Fire,
-                    grade: Grade::Heavenly,
+                    grade: Grade::heavenly(),
                     affinity: 0.8,
+                    tier_multiplier: None,
                 },
                 cultivation_realm: CultivationRealm::new("练气".to_string(), 1, 0, 1.0),
                 techniques: Vec::new(),
+                status_effects: Default::default(),
+                insight: 0,
                 lifespan: Lifespan {
                     current_age: age,
                     max_age: 100,
@@ -580,6 +1698,20 @@ mod property_tests {
                 world_state,
                 game_time,
                 event_history: Vec::new(),
+                version: 0,
+                loot_state: LootState::default(),
+                house_rules: HouseRules::default(),
+                difficulty: DifficultySettings::default(),
+                quests: QuestLog::default(),
+                combat: None,
+                ending: None,
+                play_time_secs: 0,
+                action_count: 0,
+                rng: GameRng::default(),
+                resources: Resources::default(),
+                market: None,
+                stat_history: Vec::new(),
+                karma: Karma::default(),
             }
         })
     }
@@ -0,0 +1,136 @@
+use serde::{Deserialize, Serialize};
+
+/// 画面提示中场景描述的最大字数
+const SCENE_SUMMARY_MAX_CHARS: usize = 40;
+/// 插图说明的最大字数
+const CAPTION_MAX_CHARS: usize = 20;
+/// 判定高潮段落的关键词
+const CLIMAX_KEYWORDS: &[&str] = &[
+    "剑", "战", "杀", "斩", "雷", "劫", "突破", "爆发", "血", "怒", "生死", "决斗", "轰",
+];
+const STYLE_SUFFIX: &str = "国风修仙插画，电影感构图，细腻光影";
+
+/// 插图所在位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IllustrationPlacement {
+    ChapterOpening,
+    Climax,
+}
+
+/// 章节内的插图占位标记，供下游工具按段落插入配图
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IllustrationMarker {
+    pub scene_id: String,
+    pub placement: IllustrationPlacement,
+    /// 标记位于该段落之前
+    pub paragraph_index: usize,
+    pub caption: String,
+    pub image_prompt: String,
+}
+
+/// 由段落文本生成文生图提示
+pub fn scene_image_prompt(paragraph: &str, placement: IllustrationPlacement) -> String {
+    let mood = match placement {
+        IllustrationPlacement::ChapterOpening => "远景，氛围铺陈",
+        IllustrationPlacement::Climax => "近景，动势强烈",
+    };
+    format!(
+        "{}，{}，{}",
+        truncate_chars(first_sentence(paragraph), SCENE_SUMMARY_MAX_CHARS),
+        mood,
+        STYLE_SUFFIX
+    )
+}
+
+/// 章节开头与高潮段落的插图标记；高潮取冲突关键词最密集的非首段，没有命中时不标记
+pub fn illustration_markers(chapter_index: u32, content: &str) -> Vec<IllustrationMarker> {
+    let paragraphs = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<&str>>();
+    let Some(opening) = paragraphs.first() else {
+        return Vec::new();
+    };
+
+    let mut markers = vec![marker(
+        chapter_index,
+        0,
+        opening,
+        IllustrationPlacement::ChapterOpening,
+    )];
+    let climax = paragraphs
+        .iter()
+        .enumerate()
+        .skip(1)
+        .map(|(idx, paragraph)| (idx, climax_score(paragraph)))
+        .filter(|(_, score)| *score > 0)
+        .max_by_key(|(idx, score)| (*score, std::cmp::Reverse(*idx)));
+    if let Some((idx, _)) = climax {
+        markers.push(marker(
+            chapter_index,
+            idx,
+            paragraphs[idx],
+            IllustrationPlacement::Climax,
+        ));
+    }
+    markers
+}
+
+fn marker(
+    chapter_index: u32,
+    paragraph_index: usize,
+    paragraph: &str,
+    placement: IllustrationPlacement,
+) -> IllustrationMarker {
+    IllustrationMarker {
+        scene_id: format!("ch{}-p{}", chapter_index, paragraph_index),
+        placement,
+        paragraph_index,
+        caption: truncate_chars(first_sentence(paragraph), CAPTION_MAX_CHARS),
+        image_prompt: scene_image_prompt(paragraph, placement),
+    }
+}
+
+fn climax_score(paragraph: &str) -> usize {
+    CLIMAX_KEYWORDS
+        .iter()
+        .map(|keyword| paragraph.matches(keyword).count())
+        .sum()
+}
+
+fn first_sentence(paragraph: &str) -> &str {
+    paragraph
+        .split(['。', '！', '？', '!', '?'])
+        .map(str::trim)
+        .find(|sentence| !sentence.is_empty())
+        .unwrap_or(paragraph)
+}
+
+fn truncate_chars(text: &str, max_chars: usize) -> String {
+    text.chars().take(max_chars).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_markers_cover_opening_and_climax() {
+        let content = "晨雾笼罩青云山门。弟子们陆续起身。\n午后一切如常。\n雷劫骤降，他拔剑而起，剑光与雷霆相撞！\n夜色渐深。";
+        let markers = illustration_markers(3, content);
+
+        assert_eq!(markers.len(), 2);
+        assert_eq!(markers[0].scene_id, "ch3-p0");
+        assert_eq!(markers[0].placement, IllustrationPlacement::ChapterOpening);
+        assert_eq!(markers[0].caption, "晨雾笼罩青云山门");
+        assert_eq!(markers[1].paragraph_index, 2);
+        assert_eq!(markers[1].placement, IllustrationPlacement::Climax);
+        assert!(markers[1].image_prompt.starts_with("雷劫骤降，他拔剑而起，剑光与雷霆相撞"));
+        assert!(markers[1].image_prompt.ends_with(STYLE_SUFFIX));
+
+        assert_eq!(illustration_markers(1, "风平浪静。\n依旧平静。").len(), 1);
+        assert!(illustration_markers(1, "  \n").is_empty());
+    }
+}
@@ -1,25 +1,84 @@
-use crate::models::{CultivationRealm, Element, Grade, SpiritualRoot};
+use crate::action_filters::ActionFilters;
+use crate::difficulty::DifficultySettings;
+use crate::economy::EconomyConfig;
+use crate::ending::EndingDefinition;
+use crate::game_state::GameTime;
+use crate::loot::DropTable;
+use crate::models::{
+    CultivationRealm, Element, Grade, LearnedTechnique, RealmStages, RootTier, SpiritualRoot,
+};
+use crate::npc::{CoreValue, Goal, NPCSecret, PersonalityTrait};
+use crate::side_story::SecretRealm;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 // Script type enum
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub enum ScriptType {
     ExistingNovel,
     RandomGenerated,
     Custom,
 }
 
+// Kind of location; markets only open in cities
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LocationKind {
+    #[default]
+    Wilderness,
+    Sect,
+    City,
+}
+
+impl LocationKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            LocationKind::Wilderness => "野外",
+            LocationKind::Sect => "宗门",
+            LocationKind::City => "城镇",
+        }
+    }
+
+    // Guess the kind of a location imported from a novel by its name
+    pub fn infer_from_name(name: &str) -> Self {
+        if ["城", "镇", "市", "坊"].iter().any(|marker| name.contains(marker)) {
+            LocationKind::City
+        } else if ["宗", "门", "派", "阁"].iter().any(|marker| name.contains(marker)) {
+            LocationKind::Sect
+        } else {
+            LocationKind::Wilderness
+        }
+    }
+}
+
 // Location in the world
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct Location {
     pub id: String,
     pub name: String,
     pub description: String,
     pub spiritual_energy: f32,
+    #[serde(default)]
+    pub kind: LocationKind,
+    #[serde(default)]
+    pub climate: Climate,
+}
+
+// Climate of a location; decides which weather it tends to get
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Climate {
+    #[default]
+    Temperate,
+    Humid,
+    Arid,
+    Frigid,
+    Tempestuous,
 }
 
 // Faction/Sect
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct Faction {
     pub id: String,
     pub name: String,
@@ -28,33 +87,147 @@ pub struct Faction {
 }
 
 // Technique/Skill
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct Technique {
     pub id: String,
     pub name: String,
     pub description: String,
     pub required_realm_level: u32,
     pub element: Option<Element>,
+    /// 魔道功法，业力落入魔道后才能修习
+    #[serde(default)]
+    pub demonic: bool,
+}
+
+impl Technique {
+    /// 刚习得时的功法条目
+    pub fn learned(&self) -> LearnedTechnique {
+        LearnedTechnique {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            element: self.element.clone(),
+            required_realm_level: self.required_realm_level,
+            proficiency: 0,
+        }
+    }
+}
+
+// Relationship target that stands for the player in NPC definitions
+pub const PLAYER_RELATIONSHIP_TARGET: &str = "player";
+
+// Starting relationship of a scripted NPC; target_id is "player" or another NPC id
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct StartingRelationship {
+    pub target_id: String,
+    #[serde(default)]
+    pub affinity: i32,
+    #[serde(default)]
+    pub trust: i32,
+}
+
+// Named NPC placed in the world when a new game starts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct NpcDefinition {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub bio: String,
+    // Level of one of the cultivation realms; the player's starting realm when omitted
+    #[serde(default)]
+    pub realm_level: Option<u32>,
+    #[serde(default)]
+    pub sub_level: u32,
+    // Rolled from the script's root tiers when omitted
+    #[serde(default)]
+    pub spiritual_root: Option<SpiritualRoot>,
+    #[serde(default = "default_npc_age")]
+    pub age: u32,
+    #[serde(default = "default_npc_max_age")]
+    pub max_age: u32,
+    // Location id; the player's starting location when omitted
+    #[serde(default)]
+    pub location: Option<String>,
+    #[serde(default)]
+    pub traits: Vec<PersonalityTrait>,
+    #[serde(default)]
+    pub goals: Vec<Goal>,
+    #[serde(default)]
+    pub values: Vec<CoreValue>,
+    #[serde(default)]
+    pub relationships: Vec<StartingRelationship>,
+    // Hidden realm or allegiance revealed once a trigger fires; trust triggers may target "player"
+    #[serde(default)]
+    pub secrets: Vec<NPCSecret>,
+}
+
+fn default_npc_age() -> u32 {
+    30
+}
+
+fn default_npc_max_age() -> u32 {
+    150
+}
+
+// Scripted world event that happens once the in-game date reaches it, whatever the player does
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct TimelineEvent {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub year: u32,
+    #[serde(default = "default_timeline_month")]
+    pub month: u32,
+    // Faction id -> power change applied when the event fires
+    #[serde(default)]
+    pub power_changes: HashMap<String, i32>,
+}
+
+fn default_timeline_month() -> u32 {
+    1
+}
+
+impl TimelineEvent {
+    // Day count on which the event fires, comparable with GameTime::total_days
+    pub fn trigger_day(&self) -> u32 {
+        GameTime::new(self.year.max(1), self.month.clamp(1, 12), 1).total_days
+    }
 }
 
 // World setting
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct WorldSetting {
     pub cultivation_realms: Vec<CultivationRealm>,
+    // Minor stages per realm level; levels without an entry keep the early/mid/late/peak four
+    #[serde(default)]
+    pub realm_ladder: Vec<RealmStages>,
     pub spiritual_roots: Vec<SpiritualRoot>,
+    // Talent tiers a spiritual root can be rolled into; scripts without them get the classic four
+    #[serde(default = "RootTier::builtin")]
+    pub root_tiers: Vec<RootTier>,
     pub techniques: Vec<Technique>,
     pub locations: Vec<Location>,
     pub factions: Vec<Faction>,
+    // Named NPCs present at the start; scripts without them get a default sect elder
+    #[serde(default)]
+    pub npcs: Vec<NpcDefinition>,
+    // Scripted world events keyed to the in-game calendar
+    #[serde(default)]
+    pub timeline_events: Vec<TimelineEvent>,
 }
 
 impl WorldSetting {
     pub fn new() -> Self {
         Self {
             cultivation_realms: Vec::new(),
+            realm_ladder: Vec::new(),
             spiritual_roots: Vec::new(),
+            root_tiers: RootTier::builtin(),
             techniques: Vec::new(),
             locations: Vec::new(),
             factions: Vec::new(),
+            npcs: Vec::new(),
+            timeline_events: Vec::new(),
         }
     }
 
@@ -74,22 +247,38 @@ impl WorldSetting {
         setting.spiritual_roots = vec![
             SpiritualRoot {
                 element: Element::Fire,
-                grade: Grade::Heavenly,
+                grade: Grade::heavenly(),
                 affinity: 0.9,
+                tier_multiplier: None,
             },
             SpiritualRoot {
                 element: Element::Water,
-                grade: Grade::Double,
+                grade: Grade::double(),
                 affinity: 0.7,
+                tier_multiplier: None,
             },
             SpiritualRoot {
                 element: Element::Metal,
-                grade: Grade::Triple,
+                grade: Grade::triple(),
                 affinity: 0.5,
+                tier_multiplier: None,
             },
         ];
         setting
     }
+
+    pub fn root_tier(&self, grade: &Grade) -> Option<&RootTier> {
+        RootTier::find(&self.root_tiers, grade)
+    }
+
+    // Human-readable root for prompts, e.g. "Fire·天灵根"; unknown tiers fall back to their id
+    pub fn describe_root(&self, root: &SpiritualRoot) -> String {
+        let tier = self
+            .root_tier(&root.grade)
+            .map(|tier| tier.name.as_str())
+            .unwrap_or(root.grade.id());
+        format!("{:?}·{}", root.element, tier)
+    }
 }
 
 impl Default for WorldSetting {
@@ -98,8 +287,17 @@ impl Default for WorldSetting {
     }
 }
 
+// Script-defined numerical formulas, validated when the script is loaded
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct NumericalConfig {
+    #[serde(default)]
+    pub breakthrough_chance: Option<String>,
+    #[serde(default)]
+    pub cultivation_progress: Option<String>,
+}
+
 // Initial game state
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct InitialState {
     pub player_name: String,
     pub player_spiritual_root: SpiritualRoot,
@@ -107,14 +305,151 @@ pub struct InitialState {
     pub starting_age: u32,
 }
 
+// Language a script's strings are resolved into
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ScriptLanguage {
+    #[default]
+    Zh,
+    En,
+}
+
+impl ScriptLanguage {
+    // Name used inside Chinese prompt rules, e.g. "必须使用中文"
+    pub fn prompt_name(self) -> &'static str {
+        match self {
+            ScriptLanguage::Zh => "中文",
+            ScriptLanguage::En => "英文",
+        }
+    }
+
+    // Name used inside English prompt rules, e.g. "reply in English"
+    pub fn english_name(self) -> &'static str {
+        match self {
+            ScriptLanguage::Zh => "Chinese",
+            ScriptLanguage::En => "English",
+        }
+    }
+}
+
+/// 同一段文本的中英文版本，缺少某种语言时保留剧本原文
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct LocalizedText {
+    #[serde(default)]
+    pub zh: Option<String>,
+    #[serde(default)]
+    pub en: Option<String>,
+}
+
+impl LocalizedText {
+    pub fn get(&self, language: ScriptLanguage) -> Option<&str> {
+        match language {
+            ScriptLanguage::Zh => self.zh.as_deref(),
+            ScriptLanguage::En => self.en.as_deref(),
+        }
+        .filter(|text| !text.trim().is_empty())
+    }
+
+    fn apply(&self, language: ScriptLanguage, target: &mut String) {
+        if let Some(text) = self.get(language) {
+            *target = text.to_string();
+        }
+    }
+}
+
+/// 地点、势力或功法的本地化名称与描述
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct LocalizedEntry {
+    #[serde(default)]
+    pub name: LocalizedText,
+    #[serde(default)]
+    pub description: LocalizedText,
+}
+
+impl LocalizedEntry {
+    fn apply(&self, language: ScriptLanguage, name: &mut String, description: &mut String) {
+        self.name.apply(language, name);
+        self.description.apply(language, description);
+    }
+}
+
+/// 剧本的多语言文本：地点、势力、功法以 id 为键，境界以 level 为键
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ScriptLocalization {
+    #[serde(default)]
+    pub script_name: LocalizedText,
+    #[serde(default)]
+    pub player_name: LocalizedText,
+    #[serde(default)]
+    pub realms: HashMap<u32, LocalizedText>,
+    #[serde(default)]
+    pub locations: HashMap<String, LocalizedEntry>,
+    #[serde(default)]
+    pub factions: HashMap<String, LocalizedEntry>,
+    #[serde(default)]
+    pub techniques: HashMap<String, LocalizedEntry>,
+}
+
+/// 当前的剧本格式版本，格式变化时加一并在 `ScriptManager` 中补上迁移步骤
+pub const SCRIPT_SCHEMA_VERSION: u32 = 2;
+/// 引入版本号之前的剧本视为第 1 版
+pub const LEGACY_SCRIPT_SCHEMA_VERSION: u32 = 1;
+
+fn legacy_script_schema_version() -> u32 {
+    LEGACY_SCRIPT_SCHEMA_VERSION
+}
+
 // Script definition
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct Script {
+    /// 剧本格式版本，旧版剧本加载时逐版迁移到当前版本
+    #[serde(default = "legacy_script_schema_version")]
+    pub schema_version: u32,
     pub id: String,
     pub name: String,
     pub script_type: ScriptType,
     pub world_setting: WorldSetting,
     pub initial_state: InitialState,
+    #[serde(default)]
+    pub numerical_config: NumericalConfig,
+    #[serde(default)]
+    pub drop_tables: Vec<DropTable>,
+    /// 剧本级自由输入过滤，与应用级配置合并生效
+    #[serde(default)]
+    pub action_filters: ActionFilters,
+    /// 可选的中英文文本，加载时按所选语言解析进世界设定
+    #[serde(default)]
+    pub localization: ScriptLocalization,
+    /// 已解析的语言，未选择语言时为空
+    #[serde(default)]
+    pub language: Option<ScriptLanguage>,
+    /// 剧本定义的多个结局，游戏结束时按条件择一
+    #[serde(default)]
+    pub endings: Vec<EndingDefinition>,
+    /// 开局时的默认难度，玩家开局后可再调整
+    #[serde(default)]
+    pub difficulty: DifficultySettings,
+    /// 开局资源、花费与收益的基数
+    #[serde(default)]
+    pub economy: EconomyConfig,
+    /// 从小说中途接续时的前情，开局时写入开篇与剧情历史
+    #[serde(default)]
+    pub story_so_far: Option<StoryRecap>,
+    /// 可从入口地点进入的秘境，进入后剧情暂离主线
+    #[serde(default)]
+    pub secret_realms: Vec<SecretRealm>,
+}
+
+/// 从小说选定章节接手时，此前章节的前情
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct StoryRecap {
+    /// 玩家接手的章节序号，剧情从这一章开始编号
+    pub start_chapter: u32,
+    pub start_chapter_title: String,
+    /// 放在开篇之前的前情提要
+    pub recap: String,
+    /// 此前每章一条的摘要，写入剧情历史
+    pub chapter_summaries: Vec<String>,
 }
 
 impl Script {
@@ -126,13 +461,58 @@ impl Script {
         initial_state: InitialState,
     ) -> Self {
         Self {
+            schema_version: SCRIPT_SCHEMA_VERSION,
             id,
             name,
             script_type,
             world_setting,
             initial_state,
+            numerical_config: NumericalConfig::default(),
+            drop_tables: Vec::new(),
+            action_filters: ActionFilters::default(),
+            localization: ScriptLocalization::default(),
+            language: None,
+            endings: Vec::new(),
+            difficulty: DifficultySettings::default(),
+            economy: EconomyConfig::default(),
+            story_so_far: None,
+            secret_realms: Vec::new(),
         }
     }
+
+    /// 将所选语言的文本解析进剧本名、玩家名与世界设定，提示词随之使用对应语言的名称
+    pub fn localized(mut self, language: ScriptLanguage) -> Self {
+        let localization = &self.localization;
+        localization.script_name.apply(language, &mut self.name);
+        localization
+            .player_name
+            .apply(language, &mut self.initial_state.player_name);
+
+        let world = &mut self.world_setting;
+        for realm in &mut world.cultivation_realms {
+            if let Some(text) = localization.realms.get(&realm.level) {
+                text.apply(language, &mut realm.name);
+            }
+        }
+        for location in &mut world.locations {
+            if let Some(entry) = localization.locations.get(&location.id) {
+                entry.apply(language, &mut location.name, &mut location.description);
+            }
+        }
+        for faction in &mut world.factions {
+            if let Some(entry) = localization.factions.get(&faction.id) {
+                entry.apply(language, &mut faction.name, &mut faction.description);
+            }
+        }
+        for technique in &mut world.techniques {
+            if let Some(entry) = localization.techniques.get(&technique.id) {
+                entry.apply(language, &mut technique.name, &mut technique.description);
+            }
+        }
+
+        self.language = Some(language);
+        self
+    }
 }
 
 #[cfg(test)]
@@ -160,8 +540,9 @@ mod tests {
             player_name: "Test Player".to_string(),
             player_spiritual_root: SpiritualRoot {
                 element: Element::Fire,
-                grade: Grade::Heavenly,
+                grade: Grade::heavenly(),
                 affinity: 0.8,
+                tier_multiplier: None,
             },
             starting_location: "Sect".to_string(),
             starting_age: 16,
@@ -179,4 +560,67 @@ mod tests {
         assert_eq!(script.name, "Test Script");
         assert_eq!(script.script_type, ScriptType::Custom);
     }
+
+    #[test]
+    fn test_localized_script_resolves_requested_language() {
+        let mut world_setting = WorldSetting::with_default_realms();
+        world_setting.locations = vec![Location {
+            id: "sect".to_string(),
+            name: "青云宗".to_string(),
+            description: "云海中的宗门".to_string(),
+            spiritual_energy: 1.0,
+            kind: LocationKind::Wilderness,
+            climate: Climate::Temperate,
+        }];
+        let initial_state = InitialState {
+            player_name: "林远".to_string(),
+            player_spiritual_root: SpiritualRoot {
+                element: Element::Wood,
+                grade: Grade::double(),
+                affinity: 0.6,
+                tier_multiplier: None,
+            },
+            starting_location: "sect".to_string(),
+            starting_age: 16,
+        };
+        let mut script = Script::new(
+            "bilingual".to_string(),
+            "青云志".to_string(),
+            ScriptType::Custom,
+            world_setting,
+            initial_state,
+        );
+        script.localization.script_name.en = Some("Azure Cloud Saga".to_string());
+        script.localization.player_name.en = Some("Lin Yuan".to_string());
+        script.localization.realms.insert(
+            1,
+            LocalizedText {
+                zh: Some("练气".to_string()),
+                en: None,
+            },
+        );
+        script.localization.locations.insert(
+            "sect".to_string(),
+            LocalizedEntry {
+                name: LocalizedText {
+                    zh: None,
+                    en: Some("Azure Cloud Sect".to_string()),
+                },
+                description: LocalizedText::default(),
+            },
+        );
+
+        let english = script.clone().localized(ScriptLanguage::En);
+        assert_eq!(english.name, "Azure Cloud Saga");
+        assert_eq!(english.initial_state.player_name, "Lin Yuan");
+        assert_eq!(english.world_setting.locations[0].name, "Azure Cloud Sect");
+        assert_eq!(english.world_setting.locations[0].description, "云海中的宗门");
+        assert_eq!(english.world_setting.cultivation_realms[0].name, "Qi Condensation");
+        assert_eq!(english.language, Some(ScriptLanguage::En));
+
+        let chinese = script.localized(ScriptLanguage::Zh);
+        assert_eq!(chinese.name, "青云志");
+        assert_eq!(chinese.world_setting.cultivation_realms[0].name, "练气");
+        assert_eq!(chinese.world_setting.locations[0].name, "青云宗");
+    }
 }
@@ -0,0 +1,208 @@
+use crate::save_load::checksum;
+use crate::script::Script;
+use crate::script_manager::ScriptManager;
+use anyhow::{anyhow, Result};
+use reqwest::{Client, Url};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// 下载剧本的大小上限
+pub const MAX_SCRIPT_DOWNLOAD_BYTES: usize = 2 * 1024 * 1024;
+const DOWNLOAD_TIMEOUT_SECS: u64 = 30;
+/// 下载的剧本缓存在存档目录下的子目录
+const SCRIPT_CACHE_DIR: &str = "downloaded_scripts";
+const SCRIPT_SOURCES_FILE: &str = "sources.json";
+
+/// 下载剧本的来源记录
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ScriptProvenance {
+    pub script_id: String,
+    pub script_name: String,
+    pub url: String,
+    /// 下载时的 Unix 时间戳（秒）
+    pub fetched_at: u64,
+    pub checksum: String,
+    /// 本地缓存文件，之后可用 `load_script` 离线加载
+    pub cached_path: String,
+}
+
+/// 从网址导入的剧本及其来源
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ImportedScript {
+    pub script: Script,
+    pub provenance: ScriptProvenance,
+}
+
+/// 从 HTTPS 地址下载社区分享的剧本，校验后缓存到存档目录
+#[derive(Debug, Clone)]
+pub struct ScriptImporter {
+    cache_directory: PathBuf,
+}
+
+impl ScriptImporter {
+    pub fn new(save_directory: &Path) -> Self {
+        Self {
+            cache_directory: save_directory.join(SCRIPT_CACHE_DIR),
+        }
+    }
+
+    /// 只接受带主机名的 https 地址
+    pub fn parse_url(url: &str) -> Result<Url> {
+        let parsed = Url::parse(url.trim()).map_err(|e| anyhow!("剧本地址无效: {}", e))?;
+        if parsed.scheme() != "https" {
+            return Err(anyhow!("剧本地址必须使用 https: {}", url));
+        }
+        if parsed.host_str().is_none_or(str::is_empty) {
+            return Err(anyhow!("剧本地址缺少主机名: {}", url));
+        }
+        Ok(parsed)
+    }
+
+    /// 下载剧本原文，超过大小上限时中止
+    pub async fn download(&self, url: &Url) -> Result<Vec<u8>> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(DOWNLOAD_TIMEOUT_SECS))
+            .build()?;
+        let mut response = client.get(url.clone()).send().await?.error_for_status()?;
+        if let Some(length) = response.content_length() {
+            check_size(length as usize)?;
+        }
+        let mut bytes = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            bytes.extend_from_slice(&chunk);
+            check_size(bytes.len())?;
+        }
+        Ok(bytes)
+    }
+
+    /// 解析并校验下载的剧本，写入缓存并记录来源；同一剧本再次导入时覆盖旧缓存
+    pub fn import(&self, url: &Url, bytes: &[u8], manager: &ScriptManager) -> Result<ImportedScript> {
+        check_size(bytes.len())?;
+        let content = std::str::from_utf8(bytes).map_err(|e| anyhow!("剧本不是 UTF-8 文本: {}", e))?;
+        let script = manager.parse_script_json(content)?;
+        manager.validate_script(&script)?;
+
+        let checksum = checksum(bytes);
+        fs::create_dir_all(&self.cache_directory)?;
+        let cached_path = self.cache_directory.join(cache_file_name(&script.id, &checksum));
+        fs::write(&cached_path, bytes)?;
+
+        let provenance = ScriptProvenance {
+            script_id: script.id.clone(),
+            script_name: script.name.clone(),
+            url: url.to_string(),
+            fetched_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_err(|e| anyhow!("System clock error: {}", e))?
+                .as_secs(),
+            checksum,
+            cached_path: cached_path.to_string_lossy().to_string(),
+        };
+        let mut sources = self.sources();
+        sources.retain(|source| source.script_id != provenance.script_id);
+        sources.push(provenance.clone());
+        fs::write(
+            self.cache_directory.join(SCRIPT_SOURCES_FILE),
+            serde_json::to_string_pretty(&sources)?,
+        )?;
+
+        Ok(ImportedScript { script, provenance })
+    }
+
+    /// 已下载剧本的来源记录，文件缺失或损坏时为空
+    pub fn sources(&self) -> Vec<ScriptProvenance> {
+        fs::read_to_string(self.cache_directory.join(SCRIPT_SOURCES_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+}
+
+fn check_size(len: usize) -> Result<()> {
+    if len > MAX_SCRIPT_DOWNLOAD_BYTES {
+        return Err(anyhow!(
+            "剧本文件过大：超过 {} KB 上限",
+            MAX_SCRIPT_DOWNLOAD_BYTES / 1024
+        ));
+    }
+    Ok(())
+}
+
+/// 缓存文件名取剧本 ID 中的安全字符，ID 不可用时退回校验和
+fn cache_file_name(script_id: &str, checksum: &str) -> String {
+    let safe_id = script_id
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .collect::<String>();
+    if safe_id.is_empty() {
+        format!("{}.json", checksum.replace(':', "_"))
+    } else {
+        format!("{}.json", safe_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::CultivationRealm;
+    use crate::script::Location;
+    use tempfile::TempDir;
+
+    fn shared_script(id: &str) -> Vec<u8> {
+        let manager = ScriptManager::new();
+        let mut script = manager.blank_script();
+        script.id = id.to_string();
+        script.name = "云海问道".to_string();
+        script.world_setting.cultivation_realms =
+            vec![CultivationRealm::new("练气".to_string(), 1, 0, 1.0)];
+        script.world_setting.locations.push(Location {
+            id: "sect".to_string(),
+            name: "青云宗".to_string(),
+            description: String::new(),
+            spiritual_energy: 1.0,
+            kind: Default::default(),
+            climate: Default::default(),
+        });
+        script.initial_state.starting_location = "sect".to_string();
+        serde_json::to_vec(&script).unwrap()
+    }
+
+    #[test]
+    fn test_import_validates_caches_and_records_provenance() {
+        assert!(ScriptImporter::parse_url("http://example.com/a.json").is_err());
+        assert!(ScriptImporter::parse_url("not a url").is_err());
+        let url = ScriptImporter::parse_url(" https://example.com/scripts/cloud.json ").unwrap();
+
+        let dir = TempDir::new().unwrap();
+        let importer = ScriptImporter::new(dir.path());
+        let manager = ScriptManager::new();
+        assert!(importer.import(&url, b"{}", &manager).is_err());
+        let oversized = vec![b' '; MAX_SCRIPT_DOWNLOAD_BYTES + 1];
+        assert!(importer
+            .import(&url, &oversized, &manager)
+            .unwrap_err()
+            .to_string()
+            .contains("过大"));
+
+        let imported = importer
+            .import(&url, &shared_script("../cloud"), &manager)
+            .unwrap();
+        assert_eq!(imported.script.name, "云海问道");
+        let cached = Path::new(&imported.provenance.cached_path);
+        assert_eq!(cached, dir.path().join(SCRIPT_CACHE_DIR).join("cloud.json"));
+        assert!(manager
+            .load_custom_script(&imported.provenance.cached_path)
+            .is_ok());
+
+        importer
+            .import(&url, &shared_script("../cloud"), &manager)
+            .unwrap();
+        let sources = importer.sources();
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].url, "https://example.com/scripts/cloud.json");
+        assert!(sources[0].checksum.starts_with("fnv1a64:"));
+    }
+}
@@ -1,18 +1,478 @@
-use crate::llm_runtime_config::resolve_llm_config;
-use crate::llm_service::{LLMRequest, LLMService};
-use crate::models::{Element, Grade, SpiritualRoot};
+use crate::ending::validate_endings;
+use crate::llm_runtime_config::shared_llm_service;
+use crate::llm_service::{LLMRequest, LLMService, LLMSubsystem};
+use crate::loot::validate_drop_tables;
+use crate::models::{CultivationRealm, Element, Grade, RealmStages, RootTier, SpiritualRoot};
 use crate::novel_parser::{NovelParser, ParsedNovelData};
+use crate::npc::{RevealTrigger, SecretKind};
+use crate::numerical_system::{NumericalSystem, RealmLadder, PEAK_SUB_LEVEL};
 use crate::prompt_builder::{PromptBuilder, PromptConstraints, PromptContext, PromptTemplate};
 use crate::response_validator::{ResponseValidator, ValidationConstraints};
-use crate::script::{InitialState, Location, Script, ScriptType, WorldSetting};
+use crate::script::{
+    Climate, Faction, InitialState, Location, LocationKind, Script, ScriptLanguage, StoryRecap, ScriptLocalization, ScriptType,
+    WorldSetting,
+    LEGACY_SCRIPT_SCHEMA_VERSION, PLAYER_RELATIONSHIP_TARGET, SCRIPT_SCHEMA_VERSION,
+};
+use crate::script_wizard::{apply_answers, generation_brief, WizardAnswers};
+use crate::side_story::validate_secret_realms;
 use anyhow::{anyhow, Result};
+use schemars::schema_for;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::collections::HashSet;
 use std::path::Path;
+use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+/// 本地化表中的键必须指向剧本中已定义的境界、地点、势力或功法
+fn validate_root_tiers(tiers: &[RootTier]) -> Result<()> {
+    if tiers.is_empty() {
+        return Err(anyhow!("no root tiers defined"));
+    }
+    if let Some(id) = first_duplicate(tiers.iter().map(|t| t.id.id().to_string())) {
+        return Err(anyhow!("duplicate tier '{}'", id));
+    }
+    for tier in tiers {
+        if tier.id.id().trim().is_empty() || tier.name.trim().is_empty() {
+            return Err(anyhow!("tier id and name must not be empty"));
+        }
+        if !(0.0..=1.0).contains(&tier.affinity_min)
+            || !(0.0..=1.0).contains(&tier.affinity_max)
+            || tier.affinity_min > tier.affinity_max
+        {
+            return Err(anyhow!(
+                "tier '{}' affinity range must lie within 0-1",
+                tier.id.id()
+            ));
+        }
+        if tier.power_multiplier <= 0.0 {
+            return Err(anyhow!("tier '{}' power multiplier must be positive", tier.id.id()));
+        }
+    }
+    if tiers.iter().all(|tier| tier.rarity_weight == 0) {
+        return Err(anyhow!("at least one tier needs a positive rarity weight"));
+    }
+    Ok(())
+}
+
+fn validate_realm_ladder(ladder: &[RealmStages], realms: &[CultivationRealm]) -> Result<()> {
+    if let Some(level) = first_duplicate(ladder.iter().map(|stages| stages.level)) {
+        return Err(anyhow!("duplicate stages for realm level {}", level));
+    }
+    for stages in ladder {
+        if !realms.iter().any(|realm| realm.level == stages.level) {
+            return Err(anyhow!("unknown realm level {}", stages.level));
+        }
+        if stages.stages == 0 {
+            return Err(anyhow!("realm level {} needs at least one stage", stages.level));
+        }
+        if !stages.stage_names.is_empty() && stages.stage_names.len() != stages.stages as usize {
+            return Err(anyhow!(
+                "realm level {} names {} stages but defines {}",
+                stages.level,
+                stages.stage_names.len(),
+                stages.stages
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn validate_localization(localization: &ScriptLocalization, world: &WorldSetting) -> Result<()> {
+    if let Some(level) = localization
+        .realms
+        .keys()
+        .find(|level| !world.cultivation_realms.iter().any(|realm| realm.level == **level))
+    {
+        return Err(anyhow!("unknown realm level {}", level));
+    }
+    let checks = [
+        (
+            "location",
+            &localization.locations,
+            world.locations.iter().map(|l| l.id.as_str()).collect::<HashSet<&str>>(),
+        ),
+        (
+            "faction",
+            &localization.factions,
+            world.factions.iter().map(|f| f.id.as_str()).collect(),
+        ),
+        (
+            "technique",
+            &localization.techniques,
+            world.techniques.iter().map(|t| t.id.as_str()).collect(),
+        ),
+    ];
+    for (kind, entries, ids) in checks {
+        if let Some(id) = entries.keys().find(|id| !ids.contains(id.as_str())) {
+            return Err(anyhow!("unknown {} id '{}'", kind, id));
+        }
+    }
+    Ok(())
+}
+
+fn validate_npc_definitions(world: &WorldSetting) -> Result<()> {
+    if let Some(id) = first_duplicate(world.npcs.iter().map(|npc| npc.id.clone())) {
+        return Err(anyhow!("duplicate npc '{}'", id));
+    }
+    let ladder = RealmLadder::from_world(world);
+    for npc in &world.npcs {
+        if npc.id.trim().is_empty() || npc.name.trim().is_empty() || npc.id == PLAYER_RELATIONSHIP_TARGET {
+            return Err(anyhow!("npc id and name must not be empty, and the id must not be 'player'"));
+        }
+        if let Some(level) = npc.realm_level {
+            if !world.cultivation_realms.iter().any(|realm| realm.level == level) {
+                return Err(anyhow!("npc '{}' has unknown realm level {}", npc.id, level));
+            }
+        }
+        // Without a realm level the NPC takes the player's realm, so any defined peak is acceptable
+        let peak = match npc.realm_level {
+            Some(level) => ladder.peak_sub_level(level),
+            None => world
+                .cultivation_realms
+                .iter()
+                .map(|realm| ladder.peak_sub_level(realm.level))
+                .max()
+                .unwrap_or(PEAK_SUB_LEVEL),
+        };
+        if npc.sub_level > peak {
+            return Err(anyhow!("npc '{}' sub level must be 0-{}", npc.id, peak));
+        }
+        if npc.age >= npc.max_age {
+            return Err(anyhow!("npc '{}' age must be below max_age", npc.id));
+        }
+        if let Some(location) = &npc.location {
+            if !world.locations.iter().any(|l| &l.id == location) {
+                return Err(anyhow!("npc '{}' has unknown location '{}'", npc.id, location));
+            }
+        }
+        for relationship in &npc.relationships {
+            if relationship.target_id == npc.id
+                || (relationship.target_id != PLAYER_RELATIONSHIP_TARGET
+                    && !world.npcs.iter().any(|other| other.id == relationship.target_id))
+            {
+                return Err(anyhow!(
+                    "npc '{}' has a relationship with unknown target '{}'",
+                    npc.id,
+                    relationship.target_id
+                ));
+            }
+            if !(-100..=100).contains(&relationship.affinity) || !(-100..=100).contains(&relationship.trust) {
+                return Err(anyhow!("npc '{}' relationship values must lie within -100-100", npc.id));
+            }
+        }
+        if let Some(id) = first_duplicate(npc.secrets.iter().map(|secret| secret.id.clone())) {
+            return Err(anyhow!("npc '{}' has duplicate secret '{}'", npc.id, id));
+        }
+        for secret in &npc.secrets {
+            if secret.id.trim().is_empty() || secret.triggers.is_empty() {
+                return Err(anyhow!(
+                    "npc '{}' secrets need an id and at least one trigger",
+                    npc.id
+                ));
+            }
+            if let SecretKind::HiddenRealm(realm) = &secret.kind {
+                if !world
+                    .cultivation_realms
+                    .iter()
+                    .any(|known| known.level == realm.level)
+                {
+                    return Err(anyhow!(
+                        "npc '{}' secret '{}' hides unknown realm level {}",
+                        npc.id,
+                        secret.id,
+                        realm.level
+                    ));
+                }
+            }
+            for trigger in &secret.triggers {
+                match trigger {
+                    RevealTrigger::TrustAtLeast {
+                        target_id,
+                        threshold,
+                    } => {
+                        if target_id != PLAYER_RELATIONSHIP_TARGET
+                            && !world.npcs.iter().any(|other| &other.id == target_id)
+                        {
+                            return Err(anyhow!(
+                                "npc '{}' secret '{}' watches unknown target '{}'",
+                                npc.id,
+                                secret.id,
+                                target_id
+                            ));
+                        }
+                        if !(-100..=100).contains(threshold) {
+                            return Err(anyhow!(
+                                "npc '{}' secret '{}' trust threshold must lie within -100-100",
+                                npc.id,
+                                secret.id
+                            ));
+                        }
+                    }
+                    RevealTrigger::EventKeyword(keyword) => {
+                        if keyword.trim().is_empty() {
+                            return Err(anyhow!(
+                                "npc '{}' secret '{}' has an empty keyword trigger",
+                                npc.id,
+                                secret.id
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn validate_timeline_events(world: &WorldSetting) -> Result<()> {
+    if let Some(id) = first_duplicate(world.timeline_events.iter().map(|event| event.id.clone())) {
+        return Err(anyhow!("duplicate timeline event '{}'", id));
+    }
+    for event in &world.timeline_events {
+        if event.id.trim().is_empty() || event.name.trim().is_empty() {
+            return Err(anyhow!("timeline event id and name must not be empty"));
+        }
+        if event.year == 0 || !(1..=12).contains(&event.month) {
+            return Err(anyhow!(
+                "timeline event '{}' must have a year of at least 1 and a month of 1-12",
+                event.id
+            ));
+        }
+        if let Some(faction_id) = event
+            .power_changes
+            .keys()
+            .find(|id| !world.factions.iter().any(|faction| &faction.id == *id))
+        {
+            return Err(anyhow!(
+                "timeline event '{}' changes the power of unknown faction '{}'",
+                event.id,
+                faction_id
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// 前情提要中逐章列出的最近章节数，更早的章节只写入剧情历史
+const RECAP_CHAPTER_LIMIT: usize = 5;
+
+/// 第 n 步把剧本 JSON 从第 n + 1 版升到第 n + 2 版，步数与当前版本号对应
+const SCRIPT_MIGRATIONS: [fn(&mut Value); (SCRIPT_SCHEMA_VERSION - LEGACY_SCRIPT_SCHEMA_VERSION) as usize] =
+    [infer_location_kinds];
+
+/// 第 1 版的地点没有类型，按名称推断，避免旧剧本中的城镇一律成了野外
+fn infer_location_kinds(script: &mut Value) {
+    let Some(locations) = script
+        .pointer_mut("/world_setting/locations")
+        .and_then(Value::as_array_mut)
+    else {
+        return;
+    };
+    for location in locations.iter_mut().filter_map(Value::as_object_mut) {
+        if location.contains_key("kind") {
+            continue;
+        }
+        let kind = LocationKind::infer_from_name(
+            location.get("name").and_then(Value::as_str).unwrap_or_default(),
+        );
+        location.insert("kind".to_string(), serde_json::to_value(kind).unwrap_or_default());
+    }
+}
+
+fn script_schema_version(script: &Value) -> Result<u32> {
+    let Some(version) = script.get("schema_version") else {
+        return Ok(LEGACY_SCRIPT_SCHEMA_VERSION);
+    };
+    let version = version
+        .as_u64()
+        .and_then(|version| u32::try_from(version).ok())
+        .filter(|version| *version >= LEGACY_SCRIPT_SCHEMA_VERSION)
+        .ok_or_else(|| anyhow!("schema_version must be a positive integer, got {}", version))?;
+    if version > SCRIPT_SCHEMA_VERSION {
+        return Err(anyhow!(
+            "Script schema version {} is newer than the supported version {}; please update the game",
+            version,
+            SCRIPT_SCHEMA_VERSION
+        ));
+    }
+    Ok(version)
+}
+
+/// 对照当前剧本格式的 JSON Schema，收集缺少的必填字段与无法识别（已废弃或拼错）的字段
+#[derive(Default)]
+struct FieldCheck {
+    missing: Vec<String>,
+    unknown: Vec<String>,
+}
+
+impl FieldCheck {
+    fn run(script: &Value) -> Self {
+        let schema = serde_json::to_value(schema_for!(Script)).unwrap_or_default();
+        let mut check = Self::default();
+        check.walk(&schema["definitions"], &schema, script, "");
+        check
+    }
+
+    fn walk(&mut self, definitions: &Value, schema: &Value, value: &Value, path: &str) {
+        let schema = resolve_schema(definitions, schema);
+        match value {
+            Value::Object(fields) => {
+                if let Some(properties) = schema.get("properties").and_then(Value::as_object) {
+                    let required = schema.get("required").and_then(Value::as_array);
+                    for name in required.into_iter().flatten().filter_map(Value::as_str) {
+                        if !fields.contains_key(name) {
+                            self.missing.push(field_path(path, name));
+                        }
+                    }
+                    for (name, field) in fields {
+                        match properties.get(name) {
+                            Some(field_schema) => {
+                                self.walk(definitions, field_schema, field, &field_path(path, name))
+                            }
+                            None => self.unknown.push(field_path(path, name)),
+                        }
+                    }
+                } else if let Some(entry_schema) =
+                    schema.get("additionalProperties").filter(|s| s.is_object())
+                {
+                    for (key, entry) in fields {
+                        self.walk(definitions, entry_schema, entry, &field_path(path, key));
+                    }
+                }
+            }
+            Value::Array(items) => {
+                if let Some(item_schema) = schema.get("items") {
+                    for (index, item) in items.iter().enumerate() {
+                        self.walk(definitions, item_schema, item, &format!("{}[{}]", path, index));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn into_result(self) -> Result<()> {
+        let mut problems = Vec::new();
+        if !self.missing.is_empty() {
+            problems.push(format!("missing fields: {}", self.missing.join(", ")));
+        }
+        if !self.unknown.is_empty() {
+            problems.push(format!(
+                "unknown or deprecated fields: {}",
+                self.unknown.join(", ")
+            ));
+        }
+        if problems.is_empty() {
+            return Ok(());
+        }
+        Err(anyhow!(
+            "Script does not match schema version {}: {}",
+            SCRIPT_SCHEMA_VERSION,
+            problems.join("; ")
+        ))
+    }
+}
+
+/// 展开 `$ref`、单项 `allOf` 与可空类型，其余组合类型原样返回、不再深入
+fn resolve_schema<'a>(definitions: &'a Value, schema: &'a Value) -> &'a Value {
+    if let Some(name) = schema
+        .get("$ref")
+        .and_then(Value::as_str)
+        .and_then(|reference| reference.strip_prefix("#/definitions/"))
+    {
+        return resolve_schema(definitions, &definitions[name]);
+    }
+    if let Some([inner]) = schema.get("allOf").and_then(Value::as_array).map(Vec::as_slice) {
+        return resolve_schema(definitions, inner);
+    }
+    if let Some(variants) = schema.get("anyOf").and_then(Value::as_array) {
+        let mut non_null = variants.iter().filter(|variant| variant["type"] != "null");
+        if let (Some(inner), None) = (non_null.next(), non_null.next()) {
+            return resolve_schema(definitions, inner);
+        }
+    }
+    schema
+}
+
+fn field_path(parent: &str, name: &str) -> String {
+    if parent.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", parent, name)
+    }
+}
+
+// Editable parts of a script, as exposed to the in-app script editor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScriptSection {
+    Name,
+    Realms,
+    RealmLadder,
+    SpiritualRoots,
+    RootTiers,
+    Techniques,
+    Locations,
+    Factions,
+    InitialState,
+    NumericalConfig,
+    DropTables,
+    ActionFilters,
+    Localization,
+    Endings,
+    Npcs,
+    TimelineEvents,
+    SecretRealms,
+}
+
+impl ScriptSection {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScriptSection::Name => "name",
+            ScriptSection::Realms => "realms",
+            ScriptSection::RealmLadder => "realm_ladder",
+            ScriptSection::SpiritualRoots => "spiritual_roots",
+            ScriptSection::RootTiers => "root_tiers",
+            ScriptSection::Techniques => "techniques",
+            ScriptSection::Locations => "locations",
+            ScriptSection::Factions => "factions",
+            ScriptSection::InitialState => "initial_state",
+            ScriptSection::NumericalConfig => "numerical_config",
+            ScriptSection::DropTables => "drop_tables",
+            ScriptSection::ActionFilters => "action_filters",
+            ScriptSection::Localization => "localization",
+            ScriptSection::Endings => "endings",
+            ScriptSection::Npcs => "npcs",
+            ScriptSection::TimelineEvents => "timeline_events",
+            ScriptSection::SecretRealms => "secret_realms",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScriptIssue {
+    pub section: ScriptSection,
+    pub message: String,
+}
+
+// Draft script plus every validation problem found in it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScriptDraftReport {
+    pub script: Script,
+    pub valid: bool,
+    pub issues: Vec<ScriptIssue>,
+}
+
+fn first_duplicate<T: Eq + std::hash::Hash + Clone>(items: impl Iterator<Item = T>) -> Option<T> {
+    let mut seen = HashSet::new();
+    items.into_iter().find(|item| !seen.insert(item.clone()))
+}
+
 // Script manager for loading and validating scripts
 pub struct ScriptManager {
-    llm_service: Option<LLMService>,
+    llm_service: Option<Arc<LLMService>>,
     prompt_builder: PromptBuilder,
     response_validator: ResponseValidator,
 }
@@ -20,18 +480,13 @@ pub struct ScriptManager {
 impl ScriptManager {
     pub fn new() -> Self {
         Self {
-            llm_service: Self::initialize_llm_service_from_env(),
+            llm_service: shared_llm_service(),
             prompt_builder: PromptBuilder::default(),
             response_validator: ResponseValidator::default(),
         }
     }
 
-    fn initialize_llm_service_from_env() -> Option<LLMService> {
-        let cfg = resolve_llm_config()?;
-        LLMService::new(cfg).ok()
-    }
-
-    pub fn with_llm_service(llm_service: LLMService) -> Self {
+    pub fn with_llm_service(llm_service: Arc<LLMService>) -> Self {
         Self {
             llm_service: Some(llm_service),
             prompt_builder: PromptBuilder::default(),
@@ -39,6 +494,10 @@ impl ScriptManager {
         }
     }
 
+    pub fn set_llm_service(&mut self, llm_service: Option<Arc<LLMService>>) {
+        self.llm_service = llm_service;
+    }
+
     // Load custom script from file
     pub fn load_custom_script(&self, file_path: &str) -> Result<Script> {
         let path = Path::new(file_path);
@@ -50,14 +509,35 @@ impl ScriptManager {
         let content = std::fs::read_to_string(path)
             .map_err(|e| anyhow!("Failed to read script file: {}", e))?;
 
-        let script: Script = serde_json::from_str(&content)
-            .map_err(|e| anyhow!("Failed to parse script JSON: {}", e))?;
-
+        let script = self.parse_script_json(&content)?;
         self.validate_script(&script)?;
 
         Ok(script)
     }
 
+    // Load a custom script and resolve its strings into the requested language
+    pub fn load_custom_script_in(&self, file_path: &str, language: ScriptLanguage) -> Result<Script> {
+        Ok(self.load_custom_script(file_path)?.localized(language))
+    }
+
+    // Parse script JSON, upgrading older schema versions step by step and listing every
+    // missing or unrecognised field before deserializing
+    pub fn parse_script_json(&self, content: &str) -> Result<Script> {
+        let mut value: Value = serde_json::from_str(content)
+            .map_err(|e| anyhow!("Failed to parse script JSON: {}", e))?;
+        let version = script_schema_version(&value)?;
+        let pending = (version - LEGACY_SCRIPT_SCHEMA_VERSION) as usize;
+        for migrate in &SCRIPT_MIGRATIONS[pending..] {
+            migrate(&mut value);
+        }
+        if let Some(fields) = value.as_object_mut() {
+            fields.insert("schema_version".to_string(), Value::from(SCRIPT_SCHEMA_VERSION));
+        }
+
+        FieldCheck::run(&value).into_result()?;
+        serde_json::from_value(value).map_err(|e| anyhow!("Failed to parse script JSON: {}", e))
+    }
+
     pub fn extract_novel_characters(&self, file_path: &str) -> Result<Vec<String>> {
         let parser = NovelParser::new();
         let parsed = parser
@@ -71,13 +551,23 @@ impl ScriptManager {
         Ok(parsed.characters)
     }
 
-    pub fn load_existing_novel(&self, file_path: &str, selected_character: &str) -> Result<Script> {
+    // Build a playable script from a novel; with a start chapter the player takes over mid-story
+    pub fn load_existing_novel(
+        &self,
+        file_path: &str,
+        selected_character: &str,
+        start_chapter: Option<u32>,
+    ) -> Result<Script> {
         let parser = NovelParser::new();
         let parsed = parser
             .parse_novel_file(file_path)
             .map_err(|e| anyhow!("Failed to parse novel file: {}", e))?;
 
         let player_name = self.select_character_from_novel(&parsed, selected_character)?;
+        let story_so_far = match start_chapter {
+            Some(number) => self.build_story_recap(&parsed, number)?,
+            None => None,
+        };
         let world_setting = self.build_world_setting_from_novel(&parsed);
 
         let starting_location = world_setting
@@ -92,8 +582,9 @@ impl ScriptManager {
             .cloned()
             .unwrap_or(SpiritualRoot {
                 element: Element::Fire,
-                grade: Grade::Double,
+                grade: Grade::double(),
                 affinity: 0.6,
+                tier_multiplier: None,
             });
 
         let initial_state = InitialState {
@@ -107,13 +598,14 @@ impl ScriptManager {
             .duration_since(UNIX_EPOCH)
             .map_err(|e| anyhow!("System clock error: {}", e))?
             .as_secs();
-        let script = Script::new(
+        let mut script = Script::new(
             format!("novel_{}", seed),
             parsed.title.clone(),
             ScriptType::ExistingNovel,
             world_setting,
             initial_state,
         );
+        script.story_so_far = story_so_far;
 
         self.validate_script(&script)?;
         Ok(script)
@@ -121,45 +613,221 @@ impl ScriptManager {
 
     // Validate script has all required fields
     pub fn validate_script(&self, script: &Script) -> Result<()> {
-        // Check cultivation realms exist
-        if script.world_setting.cultivation_realms.is_empty() {
-            return Err(anyhow!(
-                "Script validation failed: No cultivation realms defined"
-            ));
+        match self.script_issues(script).into_iter().next() {
+            Some(issue) => Err(anyhow!("Script validation failed: {}", issue.message)),
+            None => Ok(()),
         }
+    }
 
-        // Check at least one location exists
-        if script.world_setting.locations.is_empty() {
-            return Err(anyhow!(
-                "Script validation failed: No locations defined"
-            ));
+    // Collect every validation problem, tagged with the editor section it belongs to
+    pub fn script_issues(&self, script: &Script) -> Vec<ScriptIssue> {
+        let world = &script.world_setting;
+        let mut issues = Vec::new();
+        let mut report = |section: ScriptSection, message: String| {
+            issues.push(ScriptIssue { section, message });
+        };
+
+        if world.cultivation_realms.is_empty() {
+            report(ScriptSection::Realms, "No cultivation realms defined".to_string());
+        }
+        if world.locations.is_empty() {
+            report(ScriptSection::Locations, "No locations defined".to_string());
+        }
+        if let Err(e) = validate_realm_ladder(&world.realm_ladder, &world.cultivation_realms) {
+            report(ScriptSection::RealmLadder, format!("Invalid realm ladder: {}", e));
+        }
+        if let Err(e) = validate_root_tiers(&world.root_tiers) {
+            report(ScriptSection::RootTiers, format!("Invalid root tier: {}", e));
+        } else {
+            if let Some(root) = world
+                .spiritual_roots
+                .iter()
+                .find(|root| world.root_tier(&root.grade).is_none())
+            {
+                report(
+                    ScriptSection::SpiritualRoots,
+                    format!("Spiritual root grade '{}' is not a defined tier", root.grade.id()),
+                );
+            }
+            let grade = &script.initial_state.player_spiritual_root.grade;
+            if world.root_tier(grade).is_none() {
+                report(
+                    ScriptSection::InitialState,
+                    format!("Player spiritual root grade '{}' is not a defined tier", grade.id()),
+                );
+            }
         }
 
         // Check starting location is valid
-        let location_exists = script
-            .world_setting
-            .locations
-            .iter()
-            .any(|loc| loc.id == script.initial_state.starting_location);
-
-        if !location_exists {
-            return Err(anyhow!(
-                "Script validation failed: Starting location '{}' not found in world settings",
-                script.initial_state.starting_location
-            ));
+        if !world.locations.is_empty()
+            && !world
+                .locations
+                .iter()
+                .any(|loc| loc.id == script.initial_state.starting_location)
+        {
+            report(
+                ScriptSection::InitialState,
+                format!(
+                    "Starting location '{}' not found in world settings",
+                    script.initial_state.starting_location
+                ),
+            );
         }
 
         // Check starting age is reasonable
         if script.initial_state.starting_age < 10 || script.initial_state.starting_age > 100 {
-            return Err(anyhow!(
-                "Script validation failed: Starting age {} is invalid (should be 10-100)",
-                script.initial_state.starting_age
-            ));
+            report(
+                ScriptSection::InitialState,
+                format!(
+                    "Starting age {} is invalid (should be 10-100)",
+                    script.initial_state.starting_age
+                ),
+            );
+        }
+
+        if let Err(e) = NumericalSystem::validate_config(&script.numerical_config) {
+            report(
+                ScriptSection::NumericalConfig,
+                format!("Invalid numerical formula: {}", e),
+            );
+        }
+
+        let location_ids = world
+            .locations
+            .iter()
+            .map(|loc| loc.id.as_str())
+            .collect::<Vec<&str>>();
+        if let Err(e) = validate_drop_tables(&script.drop_tables, &location_ids) {
+            report(ScriptSection::DropTables, format!("Invalid drop table: {}", e));
+        }
+        if let Err(e) = script.action_filters.normalized() {
+            report(ScriptSection::ActionFilters, format!("Invalid action filters: {}", e));
+        }
+        if let Err(e) = validate_localization(&script.localization, world) {
+            report(ScriptSection::Localization, format!("Invalid localization: {}", e));
+        }
+        if let Err(e) = validate_endings(&script.endings) {
+            report(ScriptSection::Endings, format!("Invalid ending: {}", e));
+        }
+        if let Err(e) = validate_npc_definitions(world) {
+            report(ScriptSection::Npcs, format!("Invalid npc: {}", e));
+        }
+        if let Err(e) = validate_timeline_events(world) {
+            report(ScriptSection::TimelineEvents, format!("Invalid timeline event: {}", e));
+        }
+        if let Err(e) =
+            validate_secret_realms(&script.secret_realms, &location_ids, &script.drop_tables)
+        {
+            report(ScriptSection::SecretRealms, format!("Invalid secret realm: {}", e));
+        }
+
+        issues
+    }
+
+    // Empty draft for the in-app editor; it only becomes valid once realms and locations are filled in
+    pub fn blank_script(&self) -> Script {
+        let id = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| format!("custom_{}", d.as_millis()))
+            .unwrap_or_else(|_| "custom_draft".to_string());
+        Script::new(
+            id,
+            "未命名剧本".to_string(),
+            ScriptType::Custom,
+            WorldSetting::new(),
+            InitialState {
+                player_name: "无名修士".to_string(),
+                player_spiritual_root: SpiritualRoot {
+                    element: Element::Earth,
+                    grade: Grade::pseudo(),
+                    affinity: 0.5,
+                    tier_multiplier: None,
+                },
+                starting_location: String::new(),
+                starting_age: 16,
+            },
+        )
+    }
+
+    // Replace one section of a draft; the JSON must match that section's shape
+    pub fn update_script_section(
+        &self,
+        script: &mut Script,
+        section: ScriptSection,
+        value: Value,
+    ) -> Result<()> {
+        fn parse<T: DeserializeOwned>(section: ScriptSection, value: Value) -> Result<T> {
+            serde_json::from_value(value)
+                .map_err(|e| anyhow!("Invalid {} section: {}", section.name(), e))
         }
 
+        let world = &mut script.world_setting;
+        match section {
+            ScriptSection::Name => script.name = parse(section, value)?,
+            ScriptSection::Realms => world.cultivation_realms = parse(section, value)?,
+            ScriptSection::RealmLadder => world.realm_ladder = parse(section, value)?,
+            ScriptSection::SpiritualRoots => world.spiritual_roots = parse(section, value)?,
+            ScriptSection::RootTiers => world.root_tiers = parse(section, value)?,
+            ScriptSection::Techniques => world.techniques = parse(section, value)?,
+            ScriptSection::Locations => world.locations = parse(section, value)?,
+            ScriptSection::Factions => world.factions = parse(section, value)?,
+            ScriptSection::InitialState => script.initial_state = parse(section, value)?,
+            ScriptSection::NumericalConfig => script.numerical_config = parse(section, value)?,
+            ScriptSection::DropTables => script.drop_tables = parse(section, value)?,
+            ScriptSection::ActionFilters => script.action_filters = parse(section, value)?,
+            ScriptSection::Localization => script.localization = parse(section, value)?,
+            ScriptSection::Endings => script.endings = parse(section, value)?,
+            ScriptSection::Npcs => world.npcs = parse(section, value)?,
+            ScriptSection::TimelineEvents => world.timeline_events = parse(section, value)?,
+            ScriptSection::SecretRealms => script.secret_realms = parse(section, value)?,
+        }
         Ok(())
     }
 
+    // Editor validation is stricter than loading: it also flags blank names and duplicate ids
+    pub fn draft_report(&self, script: &Script) -> ScriptDraftReport {
+        let world = &script.world_setting;
+        let mut issues = Vec::new();
+        if script.name.trim().is_empty() {
+            issues.push(ScriptIssue {
+                section: ScriptSection::Name,
+                message: "Script name is empty".to_string(),
+            });
+        }
+        let duplicates = [
+            (
+                ScriptSection::Realms,
+                first_duplicate(world.cultivation_realms.iter().map(|r| r.level.to_string())),
+            ),
+            (
+                ScriptSection::Locations,
+                first_duplicate(world.locations.iter().map(|l| l.id.clone())),
+            ),
+            (
+                ScriptSection::Factions,
+                first_duplicate(world.factions.iter().map(|f| f.id.clone())),
+            ),
+            (
+                ScriptSection::Techniques,
+                first_duplicate(world.techniques.iter().map(|t| t.id.clone())),
+            ),
+        ];
+        for (section, duplicate) in duplicates {
+            if let Some(id) = duplicate {
+                issues.push(ScriptIssue {
+                    section,
+                    message: format!("Duplicate {} entry '{}'", section.name(), id),
+                });
+            }
+        }
+        issues.extend(self.script_issues(script));
+        ScriptDraftReport {
+            script: script.clone(),
+            valid: issues.is_empty(),
+            issues,
+        }
+    }
+
     pub async fn generate_random_script(&self) -> Result<Script> {
         let generated = if let Some(llm_service) = &self.llm_service {
             self.generate_random_script_with_llm(llm_service).await
@@ -175,7 +843,45 @@ impl ScriptManager {
         }
     }
 
+    /// 按开局向导的问答生成剧本；模型生成失败或落实选择后校验不通过时，改用本地模板
+    pub async fn generate_script_from_answers(&self, answers: &WizardAnswers) -> Result<Script> {
+        let (scene, rules) = generation_brief(answers)?;
+        let generated = if let Some(llm_service) = &self.llm_service {
+            self.generate_script_with_llm(llm_service, &scene, rules)
+                .await
+                .and_then(|script| apply_answers(script, answers))
+                .and_then(|script| self.validate_script(&script).map(|_| script))
+        } else {
+            Err(anyhow!(
+                "未检测到 LLM 配置，使用本地随机剧本模板"
+            ))
+        };
+
+        match generated {
+            Ok(script) => Ok(script),
+            Err(_) => {
+                let script = apply_answers(self.generate_fallback_random_script()?, answers)?;
+                self.validate_script(&script)?;
+                Ok(script)
+            }
+        }
+    }
+
     async fn generate_random_script_with_llm(&self, llm_service: &LLMService) -> Result<Script> {
+        self.generate_script_with_llm(
+            llm_service,
+            "生成一个可游玩的随机修仙世界剧本",
+            Vec::new(),
+        )
+        .await
+    }
+
+    async fn generate_script_with_llm(
+        &self,
+        llm_service: &LLMService,
+        scene: &str,
+        extra_world_rules: Vec<String>,
+    ) -> Result<Script> {
         let mut constraints = PromptConstraints {
             numerical_rules: vec![
                 "境界等级必须按顺序提升".to_string(),
@@ -196,12 +902,25 @@ impl ScriptManager {
         constraints.world_rules.push(
             "灵根数量越少越稀有，修行速度与宗门重视程度应更高".to_string(),
         );
+        constraints.world_rules.push(
+            "world_setting.npcs 给出 3 到 5 名有名有姓的 NPC，包含性格 traits、目标 goals 与对 player 的初始关系 relationships"
+                .to_string(),
+        );
+        constraints.world_rules.extend(extra_world_rules);
         let context = PromptContext {
-            scene: Some("生成一个可游玩的随机修仙世界剧本".to_string()),
+            scene: Some(scene.to_string()),
             location: None,
             actor_name: None,
             actor_realm: None,
             actor_combat_power: None,
+            player_persona: None,
+            canon_facts: Vec::new(),
+            story_beat: None,
+            chapter_beat: None,
+            environment: None,
+            active_quests: Vec::new(),
+            relationships: Vec::new(),
+            status_effects: Vec::new(),
             history_events: Vec::new(),
             world_setting_summary: Some(
                 "需要一个适合新手开局、设定自洽、可直接进入游戏的中文场景".to_string(),
@@ -220,6 +939,8 @@ impl ScriptManager {
                 prompt,
                 max_tokens: Some(700),
                 temperature: Some(0.7),
+                subsystem: LLMSubsystem::Script,
+                retry_policy: None,
             })
             .await
             .map_err(|e| anyhow!("LLM 随机剧本生成失败: {}", e))?;
@@ -294,7 +1015,8 @@ impl ScriptManager {
                         "id": "sect_valley",
                         "name": "宗门外谷",
                         "description": "灵气温和，外门弟子常在此修炼。",
-                        "spiritual_energy": 1.2
+                        "spiritual_energy": 1.2,
+                        "kind": "sect"
                     },
                     {
                         "id": "stone_forest",
@@ -317,7 +1039,31 @@ impl ScriptManager {
                 "player_spiritual_root": { "element": "Fire", "grade": "Double", "affinity": 0.75 },
                 "starting_location": "sect_valley",
                 "starting_age": 16
-            }
+            },
+            "drop_tables": [
+                {
+                    "id": "stone_forest_search",
+                    "source": { "kind": "location", "location_id": "stone_forest" },
+                    "empty_weight": 40,
+                    "pity_threshold": 12,
+                    "entries": [
+                        { "item_id": "low_spirit_stone", "name": "一枚下品灵石", "item_type": "Material", "weight": 50 },
+                        { "item_id": "spirit_grass", "name": "一株凝露草", "item_type": "Material", "weight": 30 },
+                        { "item_id": "beast_core", "name": "一枚妖兽内丹", "description": "蕴含精纯灵力的稀有材料。", "item_type": "Material", "weight": 3, "rarity": "Rare" }
+                    ]
+                },
+                {
+                    "id": "tier1_beasts",
+                    "source": { "kind": "enemy_tier", "tier": 1 },
+                    "empty_weight": 20,
+                    "pity_threshold": 10,
+                    "entries": [
+                        { "item_id": "low_spirit_stone", "name": "一枚下品灵石", "item_type": "Material", "weight": 60 },
+                        { "item_id": "qi_pill", "name": "一颗聚气丹", "item_type": "Medicine", "weight": 20 },
+                        { "item_id": "iron_sword", "name": "一柄玄铁剑", "description": "寒光凛冽的低阶法器。", "item_type": "Artifact", "weight": 2, "rarity": "Rare" }
+                    ]
+                }
+            ]
         }))
         .map_err(|e| anyhow!("Failed to build fallback random script: {}", e))?;
 
@@ -346,15 +1092,75 @@ impl ScriptManager {
         Ok(trimmed.to_string())
     }
 
+    // Chapters before the chosen one become the recap; starting at the first chapter needs none
+    fn build_story_recap(&self, parsed: &ParsedNovelData, start_chapter: u32) -> Result<Option<StoryRecap>> {
+        let index = parsed
+            .chapters
+            .iter()
+            .position(|chapter| chapter.number == start_chapter)
+            .ok_or_else(|| anyhow!("小说中没有第 {} 章", start_chapter))?;
+        if index == 0 {
+            return Ok(None);
+        }
+
+        let chapter_summaries = parsed.chapters[..index]
+            .iter()
+            .map(|chapter| format!("{}：{}", chapter.title, chapter.excerpt))
+            .collect::<Vec<String>>();
+        let omitted = index.saturating_sub(RECAP_CHAPTER_LIMIT);
+        let mut recap = String::from("【前情提要】");
+        if omitted > 0 {
+            recap.push_str(&format!("（前 {} 章从略）", omitted));
+        }
+        recap.push_str(&chapter_summaries[omitted..].join("\n"));
+
+        Ok(Some(StoryRecap {
+            start_chapter,
+            start_chapter_title: parsed.chapters[index].title.clone(),
+            recap,
+            chapter_summaries,
+        }))
+    }
+
     fn build_world_setting_from_novel(&self, parsed: &ParsedNovelData) -> WorldSetting {
         let mut setting = WorldSetting::with_default_realms();
+        if parsed.realms.len() >= 2 {
+            setting.cultivation_realms = parsed
+                .realms
+                .iter()
+                .enumerate()
+                .map(|(idx, name)| {
+                    CultivationRealm::new(name.clone(), idx as u32 + 1, 0, 2f32.powi(idx as i32))
+                })
+                .collect();
+        }
         setting.spiritual_roots = WorldSetting::with_default_spiritual_roots().spiritual_roots;
         setting.locations = self.build_locations_from_novel(&parsed.locations);
         setting.techniques = Vec::new();
-        setting.factions = Vec::new();
+        setting.factions = self.build_factions_from_novel(&parsed.factions);
         setting
     }
 
+    // Factions are listed most-mentioned first, so earlier ones start stronger
+    fn build_factions_from_novel(&self, factions: &[String]) -> Vec<Faction> {
+        let mut seen = HashSet::new();
+        factions
+            .iter()
+            .map(|name| name.trim())
+            .filter(|name| !name.is_empty() && seen.insert(name.to_string()))
+            .enumerate()
+            .map(|(idx, name)| Faction {
+                id: self
+                    .normalize_identifier(name)
+                    .map(|id| format!("{}_{}", id, idx + 1))
+                    .unwrap_or_else(|| format!("faction_{}", idx + 1)),
+                name: name.to_string(),
+                description: format!("从小说导入的势力：{}", name),
+                power_level: 80u32.saturating_sub(idx as u32 * 10).max(30),
+            })
+            .collect()
+    }
+
     fn build_locations_from_novel(&self, locations: &[String]) -> Vec<Location> {
         let mut results = Vec::new();
         let mut seen = HashSet::new();
@@ -376,6 +1182,8 @@ impl ScriptManager {
                 name: name.clone(),
                 description: format!("从小说导入的地点：{}", name),
                 spiritual_energy: 1.0,
+                kind: LocationKind::infer_from_name(name),
+                climate: Climate::Temperate,
             });
         }
 
@@ -385,6 +1193,8 @@ impl ScriptManager {
                 name: "小说起点".to_string(),
                 description: "从小说导入的默认起点".to_string(),
                 spiritual_energy: 1.0,
+                kind: LocationKind::Wilderness,
+                climate: Climate::Temperate,
             });
         }
 
@@ -433,8 +1243,9 @@ mod tests {
         world_setting.spiritual_roots = vec![
             SpiritualRoot {
                 element: Element::Fire,
-                grade: Grade::Heavenly,
+                grade: Grade::heavenly(),
                 affinity: 0.8,
+                tier_multiplier: None,
             },
         ];
         world_setting.locations = vec![Location {
@@ -442,14 +1253,17 @@ mod tests {
             name: "Azure Cloud Sect".to_string(),
             description: "A peaceful cultivation sect".to_string(),
             spiritual_energy: 1.0,
+            kind: LocationKind::Wilderness,
+            climate: Climate::Temperate,
         }];
 
         let initial_state = InitialState {
             player_name: "Test Player".to_string(),
             player_spiritual_root: SpiritualRoot {
                 element: Element::Fire,
-                grade: Grade::Heavenly,
+                grade: Grade::heavenly(),
                 affinity: 0.8,
+                tier_multiplier: None,
             },
             starting_location: "sect".to_string(),
             starting_age: 16,
@@ -515,6 +1329,168 @@ mod tests {
         assert!(result.unwrap_err().to_string().contains("Starting age"));
     }
 
+    #[test]
+    fn test_script_draft_is_built_section_by_section() {
+        let manager = ScriptManager::new();
+        let mut draft = manager.blank_script();
+        let report = manager.draft_report(&draft);
+        assert!(!report.valid);
+        assert!(report.issues.iter().any(|i| i.section == ScriptSection::Realms));
+        assert!(report.issues.iter().any(|i| i.section == ScriptSection::Locations));
+
+        manager
+            .update_script_section(
+                &mut draft,
+                ScriptSection::Realms,
+                serde_json::json!([
+                    { "name": "练气", "level": 1, "sub_level": 0, "power_multiplier": 1.0 }
+                ]),
+            )
+            .unwrap();
+        manager
+            .update_script_section(
+                &mut draft,
+                ScriptSection::Locations,
+                serde_json::json!([
+                    { "id": "sect", "name": "青云宗", "description": "云海之上", "spiritual_energy": 1.0 },
+                    { "id": "sect", "name": "重名", "description": "", "spiritual_energy": 0.5 }
+                ]),
+            )
+            .unwrap();
+        let issues = manager.draft_report(&draft).issues;
+        assert!(issues.iter().any(|i| i.section == ScriptSection::Locations
+            && i.message.contains("Duplicate")));
+        assert!(issues.iter().any(|i| i.section == ScriptSection::InitialState
+            && i.message.contains("Starting location")));
+
+        let err = manager
+            .update_script_section(&mut draft, ScriptSection::Factions, serde_json::json!({}))
+            .unwrap_err();
+        assert!(err.to_string().contains("factions"));
+
+        draft.world_setting.locations.pop();
+        draft.initial_state.starting_location = "sect".to_string();
+        let report = manager.draft_report(&draft);
+        assert!(report.valid, "{:?}", report.issues);
+        assert!(manager.validate_script(&report.script).is_ok());
+    }
+
+    #[test]
+    fn test_legacy_script_json_is_migrated_and_field_problems_are_listed() {
+        let manager = ScriptManager::new();
+        let mut legacy = serde_json::to_value(create_valid_script()).unwrap();
+        legacy.as_object_mut().unwrap().remove("schema_version");
+        let location = &mut legacy["world_setting"]["locations"][0];
+        location["name"] = Value::from("凡人镇");
+        location.as_object_mut().unwrap().remove("kind");
+        let script = manager.parse_script_json(&legacy.to_string()).unwrap();
+        assert_eq!(script.schema_version, SCRIPT_SCHEMA_VERSION);
+        assert_eq!(script.world_setting.locations[0].kind, LocationKind::City);
+
+        let mut broken = legacy.clone();
+        broken["initial_state"].as_object_mut().unwrap().remove("starting_age");
+        broken["world_setting"]["locations"][0]["spirit_energy"] = Value::from(1.0);
+        let err = manager.parse_script_json(&broken.to_string()).unwrap_err().to_string();
+        assert!(err.contains("missing fields: initial_state.starting_age"), "{}", err);
+        assert!(err.contains("unknown or deprecated fields: world_setting.locations[0].spirit_energy"));
+
+        legacy["schema_version"] = Value::from(SCRIPT_SCHEMA_VERSION + 1);
+        let err = manager.parse_script_json(&legacy.to_string()).unwrap_err();
+        assert!(err.to_string().contains("newer than the supported version"));
+
+        let blank = manager.blank_script();
+        let json = serde_json::to_string(&blank).unwrap();
+        assert_eq!(manager.parse_script_json(&json).unwrap(), blank);
+    }
+
+    #[test]
+    fn test_validate_script_invalid_numerical_formula() {
+        let manager = ScriptManager::new();
+        let mut script = create_valid_script();
+        script.numerical_config.breakthrough_chance = Some("affinity * player_gold".to_string());
+
+        let result = manager.validate_script(&script);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("player_gold"));
+    }
+
+    #[test]
+    fn test_validate_script_root_tiers() {
+        let manager = ScriptManager::new();
+        let mut script = create_valid_script();
+        script.initial_state.player_spiritual_root.grade = Grade::new("Chaos");
+        let issues = manager.script_issues(&script);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].section, ScriptSection::InitialState);
+
+        script.world_setting.root_tiers.push(RootTier {
+            id: Grade::new("Chaos"),
+            name: "混沌灵根".to_string(),
+            rarity_weight: 0,
+            affinity_min: 0.9,
+            affinity_max: 0.5,
+            lifespan_bonus: 0,
+            power_multiplier: 4.0,
+        });
+        let issues = manager.script_issues(&script);
+        assert_eq!(issues[0].section, ScriptSection::RootTiers);
+        assert!(issues[0].message.contains("affinity range"));
+
+        script.world_setting.root_tiers.last_mut().unwrap().affinity_min = 0.4;
+        assert!(manager.validate_script(&script).is_ok());
+
+        let legacy = serde_json::json!({
+            "cultivation_realms": [],
+            "spiritual_roots": [{ "element": "Fire", "grade": "Double", "affinity": 0.7 }],
+            "techniques": [],
+            "locations": [],
+            "factions": []
+        });
+        let world: WorldSetting = serde_json::from_value(legacy).unwrap();
+        assert_eq!(world.root_tiers, RootTier::builtin());
+        assert_eq!(world.spiritual_roots[0].power_multiplier(), 2.0);
+        assert!(world.npcs.is_empty());
+        assert!(world.timeline_events.is_empty());
+    }
+
+    #[test]
+    fn test_validate_script_npc_definitions() {
+        let manager = ScriptManager::new();
+        let mut script = create_valid_script();
+        script.world_setting.npcs = serde_json::from_value(serde_json::json!([
+            { "id": "elder", "name": "林长老", "relationships": [{ "target_id": "player", "affinity": 30 }] },
+            { "id": "rival", "name": "韩立", "relationships": [{ "target_id": "elder", "trust": -10 }] }
+        ]))
+        .unwrap();
+        assert!(manager.validate_script(&script).is_ok());
+
+        script.world_setting.npcs[1].relationships[0].target_id = "ghost".to_string();
+        let issues = manager.script_issues(&script);
+        assert_eq!(issues[0].section, ScriptSection::Npcs);
+        assert!(issues[0].message.contains("ghost"));
+
+        script.world_setting.npcs[1].relationships.clear();
+        script.world_setting.npcs[0].secrets = serde_json::from_value(serde_json::json!([{
+            "id": "allegiance",
+            "kind": { "HiddenAllegiance": "Blood Moon Sect" },
+            "triggers": [{ "TrustAtLeast": { "target_id": "player", "threshold": 60 } }]
+        }]))
+        .unwrap();
+        assert!(manager.validate_script(&script).is_ok());
+        script.world_setting.npcs[0].secrets[0].triggers = serde_json::from_value(serde_json::json!([
+            { "TrustAtLeast": { "target_id": "ghost", "threshold": 60 } }
+        ]))
+        .unwrap();
+        assert!(manager.validate_script(&script).unwrap_err().to_string().contains("ghost"));
+        script.world_setting.npcs[0].secrets.clear();
+
+        script.world_setting.npcs[1].realm_level = Some(99);
+        assert!(manager.validate_script(&script).is_err());
+        script.world_setting.npcs[1].realm_level = None;
+        script.world_setting.npcs[1].id = "elder".to_string();
+        assert!(manager.validate_script(&script).unwrap_err().to_string().contains("duplicate npc"));
+    }
+
     #[test]
     fn test_parse_generated_script_from_embedded_json() {
         let manager = ScriptManager::new();
@@ -569,6 +1545,41 @@ mod tests {
         assert!(manager.validate_script(&loaded).is_ok());
     }
 
+    #[test]
+    fn test_load_custom_script_in_language_resolves_localization() {
+        use crate::script::{LocalizedEntry, LocalizedText};
+
+        let manager = ScriptManager::new();
+        let temp = tempfile::tempdir().unwrap();
+        let file_path = temp.path().join("bilingual_script.json");
+        let mut script = create_valid_script();
+        script.localization.locations.insert(
+            "sect".to_string(),
+            LocalizedEntry {
+                name: LocalizedText {
+                    zh: Some("青云宗".to_string()),
+                    en: None,
+                },
+                description: LocalizedText::default(),
+            },
+        );
+        std::fs::write(&file_path, serde_json::to_string(&script).unwrap()).unwrap();
+
+        let loaded = manager
+            .load_custom_script_in(file_path.to_str().unwrap(), ScriptLanguage::Zh)
+            .unwrap();
+        assert_eq!(loaded.world_setting.locations[0].name, "青云宗");
+        assert_eq!(loaded.language, Some(ScriptLanguage::Zh));
+        assert!(manager.validate_script(&loaded).is_ok());
+
+        script
+            .localization
+            .factions
+            .insert("missing".to_string(), LocalizedEntry::default());
+        let result = manager.validate_script(&script);
+        assert!(result.unwrap_err().to_string().contains("unknown faction id 'missing'"));
+    }
+
     #[test]
     fn test_load_custom_script_invalid_json_file() {
         let manager = ScriptManager::new();
@@ -645,13 +1656,64 @@ mod tests {
         .unwrap();
 
         let script = manager
-            .load_existing_novel(file_path.to_str().unwrap(), "Lin Mo")
+            .load_existing_novel(file_path.to_str().unwrap(), "Lin Mo", None)
             .unwrap();
         assert_eq!(script.script_type, ScriptType::ExistingNovel);
         assert_eq!(script.initial_state.player_name, "Lin Mo");
         assert_eq!(script.initial_state.starting_location, "azure_cloud_sect");
         assert!(manager.validate_script(&script).is_ok());
     }
+
+    #[test]
+    fn test_load_existing_novel_reflects_realms_and_factions_of_the_book() {
+        let manager = ScriptManager::new();
+        let temp = tempfile::tempdir().unwrap();
+        let file_path = temp.path().join("novel.txt");
+        std::fs::write(
+            &file_path,
+            "第一章 入门\n林风来到天剑宗，在青牛镇外拜师。林风笑道：“我已是练气弟子。”\n\
+             第二章 筑基\n林风心想：“筑基之后，便可去金丹长老座下。”天剑宗与血煞门素来不和，血煞门弟子常在青牛镇出没。",
+        )
+        .unwrap();
+
+        let script = manager
+            .load_existing_novel(file_path.to_str().unwrap(), "林风", None)
+            .unwrap();
+        let world = &script.world_setting;
+        let realms = world
+            .cultivation_realms
+            .iter()
+            .map(|realm| (realm.name.as_str(), realm.power_multiplier))
+            .collect::<Vec<(&str, f32)>>();
+        assert_eq!(realms, vec![("练气", 1.0), ("筑基", 2.0), ("金丹", 4.0)]);
+        let factions = world.factions.iter().map(|f| f.name.as_str()).collect::<Vec<&str>>();
+        assert_eq!(factions, vec!["天剑宗", "血煞门"]);
+        assert!(world.factions[0].power_level > world.factions[1].power_level);
+        assert!(world
+            .locations
+            .iter()
+            .any(|l| l.name == "天剑宗" && l.kind == LocationKind::Sect));
+        assert!(manager.validate_script(&script).is_ok());
+        assert!(script.story_so_far.is_none());
+
+        let path = file_path.to_str().unwrap();
+        let recap = manager
+            .load_existing_novel(path, "林风", Some(2))
+            .unwrap()
+            .story_so_far
+            .unwrap();
+        assert_eq!(recap.start_chapter_title, "第二章 筑基");
+        assert_eq!(recap.chapter_summaries.len(), 1);
+        assert!(recap.chapter_summaries[0].starts_with("第一章 入门：林风来到天剑宗"));
+        assert!(recap.recap.starts_with("【前情提要】第一章 入门"));
+        assert!(manager
+            .load_existing_novel(path, "林风", Some(1))
+            .unwrap()
+            .story_so_far
+            .is_none());
+        let err = manager.load_existing_novel(path, "林风", Some(9)).unwrap_err();
+        assert!(err.to_string().contains("第 9 章"));
+    }
 }
 
 // Property-based tests
@@ -659,7 +1721,9 @@ mod tests {
 mod proptests {
     use super::*;
     use crate::models::{CultivationRealm, Element, Grade, SpiritualRoot};
-    use crate::script::{Faction, InitialState, Location, ScriptType, Technique, WorldSetting};
+    use crate::script::{
+        Faction, InitialState, Location, LocationKind, ScriptType, Technique, WorldSetting,
+    };
     use proptest::test_runner::TestRunner;
     use proptest::strategy::ValueTree;
     use proptest::prelude::*;
@@ -676,9 +1740,9 @@ mod proptests {
 
     fn arb_grade() -> impl Strategy<Value = Grade> {
         prop_oneof![
-            Just(Grade::Heavenly),
-            Just(Grade::Pseudo),
-            Just(Grade::Double),
+            Just(Grade::heavenly()),
+            Just(Grade::pseudo()),
+            Just(Grade::double()),
         ]
     }
 
@@ -688,6 +1752,7 @@ mod proptests {
                 element,
                 grade,
                 affinity,
+                tier_multiplier: None,
             }
         })
     }
@@ -700,15 +1765,30 @@ mod proptests {
         )
     }
 
+    fn arb_location_kind() -> impl Strategy<Value = LocationKind> {
+        prop_oneof![
+            Just(LocationKind::Wilderness),
+            Just(LocationKind::Sect),
+            Just(LocationKind::City),
+        ]
+    }
+
     fn arb_location() -> impl Strategy<Value = Location> {
-        ("[a-z]{3,10}", "[a-zA-Z ]{5,20}", "[a-zA-Z ]{10,50}", 0.0f32..=10.0f32).prop_map(
-            |(id, name, description, spiritual_energy)| Location {
+        (
+            "[a-z]{3,10}",
+            "[a-zA-Z ]{5,20}",
+            "[a-zA-Z ]{10,50}",
+            0.0f32..=10.0f32,
+            arb_location_kind(),
+        )
+            .prop_map(|(id, name, description, spiritual_energy, kind)| Location {
                 id,
                 name,
                 description,
                 spiritual_energy,
-            },
-        )
+                kind,
+                climate: Climate::Temperate,
+            })
     }
 
     fn arb_faction() -> impl Strategy<Value = Faction> {
@@ -736,6 +1816,7 @@ mod proptests {
                 description,
                 required_realm_level,
                 element,
+                demonic: false,
             })
     }
 
@@ -751,10 +1832,14 @@ mod proptests {
                 |(cultivation_realms, spiritual_roots, techniques, locations, factions)| {
                     WorldSetting {
                         cultivation_realms,
+                        realm_ladder: Vec::new(),
                         spiritual_roots,
+                        root_tiers: RootTier::builtin(),
                         techniques,
                         locations,
                         factions,
+                        npcs: Vec::new(),
+                        timeline_events: Vec::new(),
                     }
                 },
             )
@@ -0,0 +1,305 @@
+use crate::game_state::GameState;
+use crate::script::Script;
+use crate::script_manager::ScriptSection;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// 开发模式下轮询剧本文件的间隔（毫秒）
+pub const SCRIPT_WATCH_INTERVAL_MS: u64 = 1000;
+const RESTART_REQUIRED: &str = "影响已开局的角色与数值，需要重新开局才能生效";
+
+/// 未能热更新的剧本部分
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RejectedSection {
+    pub section: ScriptSection,
+    pub reason: String,
+}
+
+/// 一次热更新的结果
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ScriptReloadReport {
+    pub applied: Vec<ScriptSection>,
+    pub rejected: Vec<RejectedSection>,
+    /// 文件无法读取、解析或未通过校验时整份修改被拒绝
+    pub error: Option<String>,
+}
+
+impl ScriptReloadReport {
+    pub fn failed(error: impl Into<String>) -> Self {
+        Self {
+            error: Some(error.into()),
+            ..Self::default()
+        }
+    }
+
+    fn reject(&mut self, section: ScriptSection, reason: impl Into<String>) {
+        self.rejected.push(RejectedSection {
+            section,
+            reason: reason.into(),
+        });
+    }
+
+    /// 写入事件日志的单行摘要，没有任何变化时为空
+    pub fn summary(&self) -> Option<String> {
+        if let Some(error) = &self.error {
+            return Some(format!("剧本热更新失败：{}", error));
+        }
+        if self.applied.is_empty() && self.rejected.is_empty() {
+            return None;
+        }
+        let names = |sections: &[ScriptSection]| {
+            sections
+                .iter()
+                .map(ScriptSection::name)
+                .collect::<Vec<&str>>()
+                .join("、")
+        };
+        let mut summary = format!("剧本热更新：已应用 {}", names(&self.applied));
+        if !self.rejected.is_empty() {
+            summary.push_str(&format!(
+                "；未应用 {}",
+                names(&self.rejected.iter().map(|r| r.section).collect::<Vec<ScriptSection>>())
+            ));
+        }
+        Some(summary)
+    }
+}
+
+/// 把修改后的剧本中兼容的部分（地点、势力、功法描述、结局与过滤规则等）合并进运行中的对局，
+/// 影响角色与数值的部分保持原样并记入报告
+pub fn hot_reload(state: &mut GameState, updated: &Script) -> ScriptReloadReport {
+    let mut report = ScriptReloadReport::default();
+    if updated.id != state.script.id {
+        return ScriptReloadReport::failed(format!(
+            "剧本 id 由 {} 变为 {}，请重新开局",
+            state.script.id, updated.id
+        ));
+    }
+
+    let current = state.script.clone();
+    let world = &current.world_setting;
+    let new_world = &updated.world_setting;
+    for (section, changed) in [
+        (
+            ScriptSection::Realms,
+            world.cultivation_realms != new_world.cultivation_realms,
+        ),
+        (
+            ScriptSection::SpiritualRoots,
+            world.spiritual_roots != new_world.spiritual_roots,
+        ),
+        (ScriptSection::RootTiers, world.root_tiers != new_world.root_tiers),
+        (
+            ScriptSection::InitialState,
+            current.initial_state != updated.initial_state,
+        ),
+        (
+            ScriptSection::NumericalConfig,
+            current.numerical_config != updated.numerical_config,
+        ),
+        (ScriptSection::DropTables, current.drop_tables != updated.drop_tables),
+        (ScriptSection::Npcs, world.npcs != new_world.npcs),
+        (ScriptSection::RealmLadder, world.realm_ladder != new_world.realm_ladder),
+    ] {
+        if changed {
+            report.reject(section, RESTART_REQUIRED);
+        }
+    }
+
+    if world.techniques != new_world.techniques {
+        let text_only = world.techniques.len() == new_world.techniques.len()
+            && world.techniques.iter().zip(&new_world.techniques).all(|(old, new)| {
+                old.id == new.id
+                    && old.name == new.name
+                    && old.required_realm_level == new.required_realm_level
+                    && old.element == new.element
+            });
+        if text_only {
+            state.script.world_setting.techniques = new_world.techniques.clone();
+            report.applied.push(ScriptSection::Techniques);
+        } else {
+            report.reject(
+                ScriptSection::Techniques,
+                "只能修改已有功法的描述，增删、改名或调整境界与属性需要重新开局",
+            );
+        }
+    }
+
+    if world.locations != new_world.locations {
+        let player_location = state.player.location.clone();
+        if new_world.locations.iter().any(|l| l.id == player_location) {
+            state.script.world_setting.locations = new_world.locations.clone();
+            state.world_state.locations = new_world
+                .locations
+                .iter()
+                .map(|location| (location.id.clone(), location.clone()))
+                .collect();
+            report.applied.push(ScriptSection::Locations);
+        } else {
+            report.reject(
+                ScriptSection::Locations,
+                format!("玩家所在的地点 {} 已被删除", player_location),
+            );
+        }
+    }
+
+    if world.factions != new_world.factions {
+        state.script.world_setting.factions = new_world.factions.clone();
+        report.applied.push(ScriptSection::Factions);
+    }
+    // 已发生的大事记录在世界状态中，修改只影响尚未到来的事件
+    if world.timeline_events != new_world.timeline_events {
+        state.script.world_setting.timeline_events = new_world.timeline_events.clone();
+        report.applied.push(ScriptSection::TimelineEvents);
+    }
+    if current.name != updated.name {
+        state.script.name = updated.name.clone();
+        report.applied.push(ScriptSection::Name);
+    }
+    if current.action_filters != updated.action_filters {
+        state.script.action_filters = updated.action_filters.clone();
+        report.applied.push(ScriptSection::ActionFilters);
+    }
+    if current.endings != updated.endings {
+        state.script.endings = updated.endings.clone();
+        report.applied.push(ScriptSection::Endings);
+    }
+    if current.localization != updated.localization {
+        state.script.localization = updated.localization.clone();
+        report.applied.push(ScriptSection::Localization);
+    }
+    // 已进入的秘境按进入时的设定走完，修改只影响之后的入口
+    if current.secret_realms != updated.secret_realms {
+        state.script.secret_realms = updated.secret_realms.clone();
+        report.applied.push(ScriptSection::SecretRealms);
+    }
+
+    report
+}
+
+/// 按修改时间判断剧本文件是否需要重新加载
+#[derive(Debug, Clone)]
+pub struct ScriptWatcher {
+    path: PathBuf,
+    last_modified: Option<SystemTime>,
+}
+
+impl ScriptWatcher {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let last_modified = modified_time(&path);
+        Self {
+            path,
+            last_modified,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 文件修改时间变化时返回 true 并记下新的时间
+    pub fn poll_changed(&mut self) -> bool {
+        let modified = modified_time(&self.path);
+        if modified.is_none() || modified == self.last_modified {
+            return false;
+        }
+        self.last_modified = modified;
+        true
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_engine::GameEngine;
+    use crate::models::CultivationRealm;
+    use crate::script::{Climate, Faction, Location, LocationKind, Technique};
+    use crate::script_manager::ScriptManager;
+
+    fn location(id: &str, description: &str) -> Location {
+        Location {
+            id: id.to_string(),
+            name: id.to_string(),
+            description: description.to_string(),
+            spiritual_energy: 1.0,
+            kind: LocationKind::Wilderness,
+            climate: Climate::Temperate,
+        }
+    }
+
+    fn running_state() -> GameState {
+        let mut script = ScriptManager::new().blank_script();
+        script
+            .world_setting
+            .cultivation_realms
+            .push(CultivationRealm::new("练气".to_string(), 1, 0, 1.0));
+        script.world_setting.locations.push(location("sect", "山门"));
+        script.world_setting.techniques.push(Technique {
+            id: "sword".to_string(),
+            name: "青云剑诀".to_string(),
+            description: "入门剑法".to_string(),
+            required_realm_level: 1,
+            element: None,
+            demonic: false,
+        });
+        script.initial_state.starting_location = "sect".to_string();
+        let mut engine = GameEngine::new();
+        engine.initialize_game(script).unwrap()
+    }
+
+    #[test]
+    fn test_hot_reload_applies_content_and_rejects_structural_changes() {
+        let mut state = running_state();
+        let mut updated = state.script.clone();
+        updated.world_setting.locations[0].description = "云雾缭绕的山门".to_string();
+        updated.world_setting.locations.push(location("market", "坊市"));
+        updated.world_setting.factions.push(Faction {
+            id: "qingyun".to_string(),
+            name: "青云宗".to_string(),
+            description: "正道大宗".to_string(),
+            power_level: 80,
+        });
+        updated.world_setting.techniques[0].description = "青云宗镇派剑法".to_string();
+        updated.world_setting.cultivation_realms[0].power_multiplier = 2.0;
+
+        let report = hot_reload(&mut state, &updated);
+        assert_eq!(
+            report.applied,
+            vec![
+                ScriptSection::Techniques,
+                ScriptSection::Locations,
+                ScriptSection::Factions
+            ]
+        );
+        assert_eq!(report.rejected.len(), 1);
+        assert_eq!(report.rejected[0].section, ScriptSection::Realms);
+        assert_eq!(state.world_state.locations["sect"].description, "云雾缭绕的山门");
+        assert!(state.world_state.locations.contains_key("market"));
+        assert_eq!(state.script.world_setting.cultivation_realms[0].power_multiplier, 1.0);
+        assert!(report.summary().unwrap().contains("未应用 realms"));
+    }
+
+    #[test]
+    fn test_hot_reload_keeps_player_location_and_technique_names() {
+        let mut state = running_state();
+        let mut updated = state.script.clone();
+        updated.world_setting.locations = vec![location("market", "坊市")];
+        updated.world_setting.techniques[0].name = "改名剑诀".to_string();
+
+        let report = hot_reload(&mut state, &updated);
+        assert!(report.applied.is_empty());
+        assert_eq!(report.rejected.len(), 2);
+        assert!(state.world_state.locations.contains_key("sect"));
+        assert_eq!(state.script.world_setting.techniques[0].name, "青云剑诀");
+
+        updated.id = "another".to_string();
+        assert!(hot_reload(&mut state, &updated).error.is_some());
+    }
+}
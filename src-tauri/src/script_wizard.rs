@@ -0,0 +1,301 @@
+use crate::difficulty::DifficultySettings;
+use crate::rng::GameRng;
+use crate::script::{LocationKind, Script};
+use anyhow::{anyhow, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// 一次建议的名字个数
+pub const SUGGESTED_NAME_COUNT: usize = 6;
+
+struct ThemeEntry {
+    id: &'static str,
+    name: &'static str,
+    description: &'static str,
+    tone: &'static str,
+    surnames: &'static [&'static str],
+    given_names: &'static [&'static str],
+}
+
+const THEMES: &[ThemeEntry] = &[
+    ThemeEntry {
+        id: "orthodox",
+        name: "正道仙途",
+        description: "名门正派林立，宗规森严，循序渐进、步步登仙。",
+        tone: "庄重",
+        surnames: &["李", "林", "沈", "萧", "陆", "苏"],
+        given_names: &["清玄", "云舟", "若尘", "长歌", "明远", "青岚"],
+    },
+    ThemeEntry {
+        id: "demonic",
+        name: "魔道横行",
+        description: "正邪倒悬，弱肉强食，人心比妖兽更险恶。",
+        tone: "阴冷",
+        surnames: &["厉", "冷", "墨", "殷", "夜", "司徒"],
+        given_names: &["无赦", "寒渊", "孤鸿", "断水", "幽冥", "玄煞"],
+    },
+    ThemeEntry {
+        id: "jianghu",
+        name: "江湖游仙",
+        description: "散修游侠行走四方，快意恩仇，机缘多在市井山野。",
+        tone: "轻快",
+        surnames: &["楚", "燕", "秦", "叶", "白", "顾"],
+        given_names: &["小鱼", "不凡", "逍遥", "天明", "三白", "半闲"],
+    },
+    ThemeEntry {
+        id: "primordial",
+        name: "洪荒蛮古",
+        description: "上古遗迹散落大荒，凶兽横行，部族以血脉传承道统。",
+        tone: "苍茫",
+        surnames: &["姬", "姜", "风", "岳", "共工", "轩辕"],
+        given_names: &["烈", "苍", "荒", "炎", "渊", "霆"],
+    },
+    ThemeEntry {
+        id: "decline",
+        name: "末法残灯",
+        description: "天地灵气日渐枯竭，资源紧缺，修士为一线仙缘争得头破血流。",
+        tone: "萧瑟",
+        surnames: &["温", "谢", "韩", "宁", "许", "梅"],
+        given_names: &["残灯", "孤舟", "知秋", "寒衣", "听雪", "照夜"],
+    },
+];
+
+/// 开局向导中可选的世界主题
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct WorldTheme {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    /// 世界基调，写入剧本生成提示
+    pub tone: String,
+}
+
+impl WorldTheme {
+    fn from_entry(entry: &ThemeEntry) -> Self {
+        Self {
+            id: entry.id.to_string(),
+            name: entry.name.to_string(),
+            description: entry.description.to_string(),
+            tone: entry.tone.to_string(),
+        }
+    }
+}
+
+/// 开局身份：拜入宗门或做一名散修
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StartingPath {
+    #[default]
+    Sect,
+    Wanderer,
+}
+
+impl StartingPath {
+    pub fn label(self) -> &'static str {
+        match self {
+            StartingPath::Sect => "宗门弟子",
+            StartingPath::Wanderer => "散修",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WizardDifficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl WizardDifficulty {
+    pub fn settings(self) -> DifficultySettings {
+        match self {
+            WizardDifficulty::Easy => DifficultySettings::easy(),
+            WizardDifficulty::Normal => DifficultySettings::normal(),
+            WizardDifficulty::Hard => DifficultySettings::hard(),
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            WizardDifficulty::Easy => "宽松",
+            WizardDifficulty::Normal => "寻常",
+            WizardDifficulty::Hard => "严苛",
+        }
+    }
+}
+
+/// 开局向导的问答结果
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct WizardAnswers {
+    /// `suggest_world_themes` 给出的主题 id
+    pub theme: String,
+    #[serde(default)]
+    pub starting_path: StartingPath,
+    #[serde(default)]
+    pub difficulty: WizardDifficulty,
+    /// 留空时沿用生成剧本中的名字
+    #[serde(default)]
+    pub player_name: Option<String>,
+}
+
+pub fn suggest_world_themes() -> Vec<WorldTheme> {
+    THEMES.iter().map(WorldTheme::from_entry).collect()
+}
+
+fn theme_entry(theme: &str) -> Result<&'static ThemeEntry> {
+    THEMES
+        .iter()
+        .find(|entry| entry.id == theme)
+        .ok_or_else(|| anyhow!("未知的世界主题: {}", theme))
+}
+
+/// 按主题的姓氏与名字随机组合出不重复的候选名
+pub fn suggest_player_names(theme: &str, rng: &mut GameRng) -> Result<Vec<String>> {
+    let entry = theme_entry(theme)?;
+    let mut names = Vec::new();
+    let combinations = entry.surnames.len() * entry.given_names.len();
+    while names.len() < SUGGESTED_NAME_COUNT.min(combinations) {
+        let surname = entry.surnames[rng.range_u32(0, entry.surnames.len() as u32 - 1) as usize];
+        let given =
+            entry.given_names[rng.range_u32(0, entry.given_names.len() as u32 - 1) as usize];
+        let name = format!("{}{}", surname, given);
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    Ok(names)
+}
+
+/// 交给模型的场景描述与额外约束
+pub fn generation_brief(answers: &WizardAnswers) -> Result<(String, Vec<String>)> {
+    let entry = theme_entry(&answers.theme)?;
+    let scene = format!(
+        "按玩家的选择生成一个修仙世界剧本：主题“{}”，{}",
+        entry.name, entry.description
+    );
+    let mut rules = vec![
+        format!(
+            "整体基调{}，地点、势力与 NPC 的描写都要贴合这一基调",
+            entry.tone
+        ),
+        format!(
+            "玩家以{}身份开局，difficulty 取{}难度",
+            answers.starting_path.label(),
+            answers.difficulty.label()
+        ),
+    ];
+    rules.push(match answers.starting_path {
+        StartingPath::Sect => {
+            "至少一个地点的 kind 为 sect，initial_state.starting_location 指向该宗门".to_string()
+        }
+        StartingPath::Wanderer => {
+            "initial_state.starting_location 指向一处 kind 为 wilderness 或 city 的地点".to_string()
+        }
+    });
+    Ok((scene, rules))
+}
+
+/// 把问答结果落实到剧本上：名字、难度与开局地点；找不到合适的地点时保留剧本原有的起点
+pub fn apply_answers(mut script: Script, answers: &WizardAnswers) -> Result<Script> {
+    let entry = theme_entry(&answers.theme)?;
+    if !script.name.contains(entry.name) {
+        script.name = format!("{}·{}", entry.name, script.name);
+    }
+    if let Some(name) = answers
+        .player_name
+        .as_deref()
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+    {
+        script.initial_state.player_name = name.to_string();
+    }
+    script.difficulty = answers.difficulty.settings();
+    let wants_sect = answers.starting_path == StartingPath::Sect;
+    if let Some(location) = script
+        .world_setting
+        .locations
+        .iter()
+        .find(|location| (location.kind == LocationKind::Sect) == wants_sect)
+    {
+        script.initial_state.starting_location = location.id.clone();
+    }
+    Ok(script)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script_manager::ScriptManager;
+
+    #[test]
+    fn test_names_follow_theme_and_unknown_theme_is_rejected() {
+        let mut rng = GameRng::new(3);
+        let names = suggest_player_names("jianghu", &mut rng).unwrap();
+        assert_eq!(names.len(), SUGGESTED_NAME_COUNT);
+        let entry = theme_entry("jianghu").unwrap();
+        assert!(names
+            .iter()
+            .all(|name| entry.surnames.iter().any(|s| name.starts_with(s))));
+        let mut unique = names.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), names.len());
+
+        assert!(suggest_player_names("unknown", &mut rng).is_err());
+        assert_eq!(suggest_world_themes().len(), THEMES.len());
+    }
+
+    #[tokio::test]
+    async fn test_answers_shape_template_script_without_llm() {
+        let manager = ScriptManager::new();
+        let wanderer = WizardAnswers {
+            theme: "decline".to_string(),
+            starting_path: StartingPath::Wanderer,
+            difficulty: WizardDifficulty::Hard,
+            player_name: Some(" 温照夜 ".to_string()),
+        };
+        let script = manager
+            .generate_script_from_answers(&wanderer)
+            .await
+            .unwrap();
+        assert!(script.name.starts_with("末法残灯"));
+        assert_eq!(script.initial_state.player_name, "温照夜");
+        assert_eq!(script.difficulty, DifficultySettings::hard());
+        let start = script
+            .world_setting
+            .locations
+            .iter()
+            .find(|location| location.id == script.initial_state.starting_location)
+            .unwrap();
+        assert_ne!(start.kind, LocationKind::Sect);
+        assert!(manager.validate_script(&script).is_ok());
+
+        let disciple = WizardAnswers {
+            starting_path: StartingPath::Sect,
+            player_name: None,
+            ..wanderer
+        };
+        let script = manager
+            .generate_script_from_answers(&disciple)
+            .await
+            .unwrap();
+        let start = script
+            .world_setting
+            .locations
+            .iter()
+            .find(|location| location.id == script.initial_state.starting_location)
+            .unwrap();
+        assert_eq!(start.kind, LocationKind::Sect);
+
+        let unknown = WizardAnswers {
+            theme: "unknown".to_string(),
+            ..disciple
+        };
+        assert!(manager
+            .generate_script_from_answers(&unknown)
+            .await
+            .is_err());
+    }
+}
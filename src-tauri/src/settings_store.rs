@@ -0,0 +1,132 @@
+use crate::difficulty::DifficultySettings;
+use crate::llm_service::ModelRoutes;
+use crate::plot_engine::PlotSettings;
+use crate::script::ScriptLanguage;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const APP_SETTINGS_FILE: &str = "app_settings.json";
+
+/// 跨对局保留的应用偏好，新开对局时作为默认值
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppSettings {
+    /// 新对局的剧情设置（文风、章节篇幅、叙事语言等）
+    pub plot_settings: PlotSettings,
+    /// 新对局的难度，未设置时使用剧本给出的默认难度
+    pub difficulty: Option<DifficultySettings>,
+    /// 按子系统改用的模型，未列出的子系统使用 LLM 配置中的模型
+    pub llm_routing: ModelRoutes,
+}
+
+impl AppSettings {
+    pub fn validate(&self) -> Result<()> {
+        let plot = &self.plot_settings;
+        if plot.min_interactions_per_chapter == 0
+            || plot.min_interactions_per_chapter > plot.max_interactions_per_chapter
+        {
+            return Err(anyhow!("每章互动次数范围不合法"));
+        }
+        if plot.target_chapter_words_min == 0
+            || plot.target_chapter_words_min > plot.target_chapter_words_max
+        {
+            return Err(anyhow!("章节字数范围不合法"));
+        }
+        if plot.novel_style.trim().is_empty() {
+            return Err(anyhow!("小说风格不能为空"));
+        }
+        if !plot.llm_judge_threshold.is_finite() || plot.llm_judge_threshold < 0.0 {
+            return Err(anyhow!("LLM 判定阈值必须是非负数"));
+        }
+        if let Some(difficulty) = &self.difficulty {
+            difficulty.validate().map_err(|e| anyhow!(e))?;
+        }
+        if self.llm_routing.values().any(|model| model.trim().is_empty()) {
+            return Err(anyhow!("模型路由中的模型名不能为空"));
+        }
+        Ok(())
+    }
+
+    /// 新对局的剧情设置；剧本指定了本地化语言时叙事语言以剧本为准
+    pub fn plot_settings_for(&self, script_language: Option<ScriptLanguage>) -> PlotSettings {
+        let mut settings = self.plot_settings.clone();
+        if let Some(language) = script_language {
+            settings.language = language;
+        }
+        settings
+    }
+}
+
+/// 应用偏好的读写，文件放在存档目录
+#[derive(Debug, Clone)]
+pub struct SettingsStore {
+    path: PathBuf,
+}
+
+impl SettingsStore {
+    pub fn new(save_directory: &Path) -> Self {
+        Self {
+            path: save_directory.join(APP_SETTINGS_FILE),
+        }
+    }
+
+    /// 读取应用偏好，文件缺失或损坏时使用默认值
+    pub fn load(&self) -> AppSettings {
+        fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// 校验并保存应用偏好
+    pub fn save(&self, settings: &AppSettings) -> Result<()> {
+        settings.validate()?;
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(settings)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm_service::LLMSubsystem;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_settings_round_trip_and_reject_invalid() {
+        let dir = TempDir::new().unwrap();
+        let store = SettingsStore::new(dir.path());
+        assert_eq!(store.load(), AppSettings::default());
+
+        let mut settings = AppSettings::default();
+        settings.plot_settings.novel_style = "古典章回体".to_string();
+        settings.plot_settings.language = ScriptLanguage::En;
+        settings.difficulty = Some(DifficultySettings::hard());
+        settings
+            .llm_routing
+            .insert(LLMSubsystem::Npc, "gpt-4o-mini".to_string());
+        store.save(&settings).unwrap();
+        assert_eq!(store.load(), settings);
+        assert_eq!(settings.plot_settings_for(None).language, ScriptLanguage::En);
+        assert_eq!(
+            settings.plot_settings_for(Some(ScriptLanguage::Zh)).language,
+            ScriptLanguage::Zh
+        );
+
+        let mut invalid = settings.clone();
+        invalid.llm_routing.insert(LLMSubsystem::Plot, " ".to_string());
+        assert!(store.save(&invalid).is_err());
+        invalid = settings.clone();
+        invalid.plot_settings.min_interactions_per_chapter = 9;
+        assert!(store.save(&invalid).is_err());
+        assert_eq!(store.load(), settings);
+
+        fs::write(dir.path().join(APP_SETTINGS_FILE), "{ broken").unwrap();
+        assert_eq!(store.load(), AppSettings::default());
+    }
+}
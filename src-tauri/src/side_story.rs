@@ -0,0 +1,494 @@
+use crate::game_state::{GameState, Item};
+use crate::loot::{DropSource, DropTable};
+use crate::numerical_system::Action;
+use crate::plot_engine::{new_option_uid, ChapterState, PlayerOption, PlotState, Scene};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+
+/// 秘境入口选项的标记，前端据此高亮显示
+pub const SIDE_STORY_ENTRY: &str = "秘境入口";
+/// 秘境中离开选项的标记
+pub const SIDE_STORY_EXIT: &str = "离开秘境";
+/// 进出秘境时写入事件日志的事件类型
+pub const SIDE_STORY_EVENT: &str = "side_story";
+
+/// 剧本定义的秘境：在入口地点可以进入，有自己的场景、受限的选项与掉落表
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct SecretRealm {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// 入口所在地点
+    pub location_id: String,
+    /// 进入所需的最低大境界
+    #[serde(default)]
+    pub min_realm_level: u32,
+    /// 秘境中至多停留的回合数，耗尽后被送出秘境
+    #[serde(default = "default_max_turns")]
+    pub max_turns: u32,
+}
+
+fn default_max_turns() -> u32 {
+    5
+}
+
+impl SecretRealm {
+    fn entry_label(&self) -> String {
+        format!("进入{}", self.name)
+    }
+
+    /// 呈现在入口地点的进入选项
+    pub fn entry_option(&self, id: usize) -> PlayerOption {
+        PlayerOption {
+            id,
+            uid: new_option_uid(),
+            description: self.entry_label(),
+            requirements: vec![SIDE_STORY_ENTRY.to_string()],
+            action: Action::Custom {
+                description: self.entry_label(),
+            },
+        }
+    }
+
+    pub fn is_open_to(&self, game_state: &GameState) -> bool {
+        game_state.player.location == self.location_id
+            && game_state.player.stats.cultivation_realm.level >= self.min_realm_level
+    }
+
+    /// 秘境自己的剧情状态：沿用主线的设置、画像与事实库，另起场景与章节
+    pub fn plot_state(&self, main_plot: &PlotState, game_state: &GameState) -> PlotState {
+        let side_story = SideStory {
+            realm_id: self.id.clone(),
+            realm_name: self.name.clone(),
+            max_turns: self.max_turns,
+            turns_left: self.max_turns,
+            entered_day: game_state.game_time.total_days,
+            entry_combat_power: game_state.player.stats.combat_power,
+            entry_items: game_state
+                .player
+                .inventory
+                .iter()
+                .map(|item| item.id.clone())
+                .collect(),
+        };
+        let mut plot_state = PlotState::new(Scene {
+            id: format!("secret_realm_{}", self.id),
+            name: self.name.clone(),
+            description: self.description.clone(),
+            location: main_plot.current_scene.location.clone(),
+            available_options: side_story.options(),
+        });
+        plot_state.settings = main_plot.settings.clone();
+        // 秘境篇幅短，不单独规划章节大纲
+        plot_state.settings.outline_planning = false;
+        plot_state.current_chapter =
+            ChapterState::new(main_plot.current_chapter.index, self.name.clone());
+        plot_state.player_persona = main_plot.player_persona.clone();
+        plot_state.canon_facts = main_plot.canon_facts.clone();
+        plot_state.temperature_tuner = main_plot.temperature_tuner.clone();
+        plot_state.story_arc = main_plot.story_arc.clone();
+        if !self.description.trim().is_empty() {
+            plot_state.append_interlude(self.description.clone());
+        }
+        plot_state.side_story = Some(side_story);
+        plot_state
+    }
+}
+
+/// 进行中的秘境支线，挂在秘境的剧情状态上
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct SideStory {
+    pub realm_id: String,
+    pub realm_name: String,
+    pub max_turns: u32,
+    /// 还能在秘境中行动的回合数
+    pub turns_left: u32,
+    pub entered_day: u32,
+    /// 进入时的战力与背包物品，离开时据此结算收获
+    pub entry_combat_power: u64,
+    pub entry_items: Vec<String>,
+}
+
+impl SideStory {
+    /// 秘境中只能探寻机缘、打坐修炼或离开
+    pub fn options(&self) -> Vec<PlayerOption> {
+        let explore = format!("在{}中探索机缘", self.realm_name);
+        let leave = format!("离开{}", self.realm_name);
+        vec![
+            PlayerOption {
+                id: 0,
+                uid: new_option_uid(),
+                description: explore.clone(),
+                requirements: Vec::new(),
+                action: Action::Custom {
+                    description: explore,
+                },
+            },
+            PlayerOption {
+                id: 1,
+                uid: new_option_uid(),
+                description: format!("借{}的灵气打坐修炼", self.realm_name),
+                requirements: Vec::new(),
+                action: Action::Cultivate,
+            },
+            PlayerOption {
+                id: 2,
+                uid: new_option_uid(),
+                description: leave.clone(),
+                requirements: vec![SIDE_STORY_EXIT.to_string()],
+                action: Action::Custom { description: leave },
+            },
+        ]
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.turns_left == 0
+    }
+
+    /// 按进入时的快照结算本次秘境之行的收获
+    pub fn outcome(&self, game_state: &GameState) -> SideStoryOutcome {
+        let mut entry_items = self.entry_items.clone();
+        let items = game_state
+            .player
+            .inventory
+            .iter()
+            .filter(
+                |item| match entry_items.iter().position(|id| id == &item.id) {
+                    Some(idx) => {
+                        entry_items.remove(idx);
+                        false
+                    }
+                    None => true,
+                },
+            )
+            .cloned()
+            .collect::<Vec<Item>>();
+        let combat_power_gain = game_state
+            .player
+            .stats
+            .combat_power
+            .saturating_sub(self.entry_combat_power);
+        let forced = self.is_exhausted();
+
+        let mut summary = format!(
+            "{}，你离开{}，历时{}日",
+            if forced {
+                "秘境闭合"
+            } else {
+                "机缘已尽"
+            },
+            self.realm_name,
+            game_state
+                .game_time
+                .total_days
+                .saturating_sub(self.entered_day)
+        );
+        if combat_power_gain > 0 {
+            summary.push_str(&format!("，战力增长{}", combat_power_gain));
+        }
+        if !items.is_empty() {
+            summary.push_str(&format!(
+                "，带出{}",
+                items
+                    .iter()
+                    .map(|item| item.name.as_str())
+                    .collect::<Vec<&str>>()
+                    .join("、")
+            ));
+        }
+        summary.push('。');
+
+        SideStoryOutcome {
+            realm_id: self.realm_id.clone(),
+            realm_name: self.realm_name.clone(),
+            turns_spent: self.max_turns.saturating_sub(self.turns_left),
+            combat_power_gain,
+            items,
+            forced,
+            summary,
+        }
+    }
+}
+
+/// 离开秘境时并回主线的收获
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct SideStoryOutcome {
+    pub realm_id: String,
+    pub realm_name: String,
+    pub turns_spent: u32,
+    pub combat_power_gain: u64,
+    pub items: Vec<Item>,
+    /// 回合耗尽被送出秘境
+    pub forced: bool,
+    pub summary: String,
+}
+
+/// 把秘境中的段落、画像与事实并回压栈的主线剧情，并写下本次收获
+pub fn merge_into(main_plot: &mut PlotState, realm_plot: PlotState, outcome: &SideStoryOutcome) {
+    let PlotState {
+        chapters,
+        current_chapter,
+        player_persona,
+        canon_facts,
+        temperature_tuner,
+        last_action_result,
+        ..
+    } = realm_plot;
+    for text in chapters
+        .into_iter()
+        .chain([current_chapter])
+        .flat_map(|chapter| chapter.content)
+    {
+        main_plot.append_interlude(text);
+    }
+    main_plot.append_interlude(outcome.summary.clone());
+    let options = &mut main_plot.current_scene.available_options;
+    options.retain(|option| !option.requirements.iter().any(|r| r == SIDE_STORY_ENTRY));
+    for (idx, option) in options.iter_mut().enumerate() {
+        option.id = idx;
+    }
+    main_plot.player_persona = player_persona;
+    main_plot.canon_facts = canon_facts;
+    main_plot.temperature_tuner = temperature_tuner;
+    main_plot.last_action_result = last_action_result;
+    main_plot.is_waiting_for_input = true;
+}
+
+/// 选项对应的秘境入口
+pub fn realm_for_option<'a>(
+    realms: &'a [SecretRealm],
+    option: &PlayerOption,
+) -> Option<&'a SecretRealm> {
+    if !option.requirements.iter().any(|r| r == SIDE_STORY_ENTRY) {
+        return None;
+    }
+    realms
+        .iter()
+        .find(|realm| realm.entry_label() == option.description)
+}
+
+pub fn is_exit_option(option: &PlayerOption) -> bool {
+    option.requirements.iter().any(|r| r == SIDE_STORY_EXIT)
+}
+
+/// 用玩家当前能进入的秘境替换选项列表中的入口选项
+pub fn attach_entry_options(
+    options: &mut Vec<PlayerOption>,
+    realms: &[SecretRealm],
+    game_state: &GameState,
+) {
+    options.retain(|option| !option.requirements.iter().any(|r| r == SIDE_STORY_ENTRY));
+    for realm in realms.iter().filter(|realm| realm.is_open_to(game_state)) {
+        options.push(realm.entry_option(options.len()));
+    }
+    for (idx, option) in options.iter_mut().enumerate() {
+        option.id = idx;
+    }
+}
+
+pub fn table_for_secret_realm<'a>(
+    tables: &'a [DropTable],
+    realm_id: &str,
+) -> Option<&'a DropTable> {
+    tables.iter().find(
+        |table| matches!(&table.source, DropSource::SecretRealm { realm_id: id } if id == realm_id),
+    )
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SideStoryError {
+    DuplicateRealm(String),
+    UnknownLocation {
+        realm_id: String,
+        location_id: String,
+    },
+    NoTurns(String),
+    UnknownRealmTable {
+        table_id: String,
+        realm_id: String,
+    },
+}
+
+impl fmt::Display for SideStoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SideStoryError::DuplicateRealm(id) => write!(f, "duplicate secret realm '{id}'"),
+            SideStoryError::UnknownLocation {
+                realm_id,
+                location_id,
+            } => write!(
+                f,
+                "secret realm '{realm_id}' references unknown location '{location_id}'"
+            ),
+            SideStoryError::NoTurns(id) => write!(f, "secret realm '{id}' allows no turns"),
+            SideStoryError::UnknownRealmTable { table_id, realm_id } => write!(
+                f,
+                "drop table '{table_id}' references unknown secret realm '{realm_id}'"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SideStoryError {}
+
+/// 校验剧本中的秘境及其掉落表
+pub fn validate_secret_realms(
+    realms: &[SecretRealm],
+    location_ids: &[&str],
+    tables: &[DropTable],
+) -> Result<(), SideStoryError> {
+    let mut seen = HashSet::new();
+    for realm in realms {
+        if !seen.insert(realm.id.as_str()) {
+            return Err(SideStoryError::DuplicateRealm(realm.id.clone()));
+        }
+        if !location_ids.contains(&realm.location_id.as_str()) {
+            return Err(SideStoryError::UnknownLocation {
+                realm_id: realm.id.clone(),
+                location_id: realm.location_id.clone(),
+            });
+        }
+        if realm.max_turns == 0 {
+            return Err(SideStoryError::NoTurns(realm.id.clone()));
+        }
+    }
+    for table in tables {
+        if let DropSource::SecretRealm { realm_id } = &table.source {
+            if !seen.contains(realm_id.as_str()) {
+                return Err(SideStoryError::UnknownRealmTable {
+                    table_id: table.id.clone(),
+                    realm_id: realm_id.clone(),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_engine::GameEngine;
+    use crate::game_state::ItemType;
+    use crate::loot::DropEntry;
+    use crate::models::CultivationRealm;
+    use crate::script::{Climate, Location, LocationKind};
+    use crate::script_manager::ScriptManager;
+
+    fn realm(id: &str, location_id: &str) -> SecretRealm {
+        SecretRealm {
+            id: id.to_string(),
+            name: "剑冢".to_string(),
+            description: String::new(),
+            location_id: location_id.to_string(),
+            min_realm_level: 0,
+            max_turns: default_max_turns(),
+        }
+    }
+
+    fn item(id: &str, name: &str) -> Item {
+        Item {
+            id: id.to_string(),
+            name: name.to_string(),
+            description: String::new(),
+            item_type: ItemType::Material,
+            lifespan_bonus: 0,
+        }
+    }
+
+    #[test]
+    fn test_outcome_counts_only_items_gained_in_the_realm() {
+        let mut script = ScriptManager::new().blank_script();
+        script
+            .world_setting
+            .cultivation_realms
+            .push(CultivationRealm::new("练气".to_string(), 1, 0, 1.0));
+        script.world_setting.locations.push(Location {
+            id: "sect".to_string(),
+            name: "青云宗".to_string(),
+            description: String::new(),
+            spiritual_energy: 1.0,
+            kind: LocationKind::Sect,
+            climate: Climate::Temperate,
+        });
+        script.initial_state.starting_location = "sect".to_string();
+        let mut engine = GameEngine::new();
+        let mut game_state = engine.initialize_game(script).unwrap();
+        let main_plot = engine.initialize_plot().unwrap();
+        game_state.player.inventory = vec![item("stone", "灵石"), item("herb", "灵草")];
+        let mut side_story = realm("sword_cave", "sect")
+            .plot_state(&main_plot, &game_state)
+            .side_story
+            .unwrap();
+
+        // 用掉一株灵草，又得了一枚灵石与一柄残剑
+        game_state.player.inventory = vec![
+            item("stone", "灵石"),
+            item("stone", "灵石"),
+            item("sword", "残剑"),
+        ];
+        game_state.player.stats.combat_power = side_story.entry_combat_power + 12;
+        game_state.game_time.total_days = side_story.entered_day + 3;
+        side_story.turns_left = 0;
+
+        let outcome = side_story.outcome(&game_state);
+        assert!(outcome.forced);
+        assert_eq!(outcome.turns_spent, default_max_turns());
+        assert_eq!(outcome.combat_power_gain, 12);
+        assert_eq!(
+            outcome
+                .items
+                .iter()
+                .map(|item| item.id.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["stone", "sword"]
+        );
+        assert_eq!(
+            outcome.summary,
+            "秘境闭合，你离开剑冢，历时3日，战力增长12，带出灵石、残剑。"
+        );
+    }
+
+    #[test]
+    fn test_validate_secret_realms() {
+        let table = DropTable {
+            id: "cave_loot".to_string(),
+            source: DropSource::SecretRealm {
+                realm_id: "sword_cave".to_string(),
+            },
+            rolls: 1,
+            empty_weight: 0,
+            entries: vec![DropEntry {
+                item_id: "sword".to_string(),
+                name: "残剑".to_string(),
+                description: String::new(),
+                item_type: ItemType::Artifact,
+                weight: 1,
+                rarity: Default::default(),
+                lifespan_bonus: 0,
+            }],
+            pity_threshold: None,
+        };
+        let realms = vec![realm("sword_cave", "sect")];
+        let tables = vec![table];
+        assert!(validate_secret_realms(&realms, &["sect"], &tables).is_ok());
+        assert_eq!(
+            validate_secret_realms(&[], &["sect"], &tables),
+            Err(SideStoryError::UnknownRealmTable {
+                table_id: "cave_loot".to_string(),
+                realm_id: "sword_cave".to_string(),
+            })
+        );
+        assert!(matches!(
+            validate_secret_realms(&[realm("a", "sect"), realm("a", "sect")], &["sect"], &[]),
+            Err(SideStoryError::DuplicateRealm(_))
+        ));
+        assert!(matches!(
+            validate_secret_realms(&[realm("a", "void")], &["sect"], &[]),
+            Err(SideStoryError::UnknownLocation { .. })
+        ));
+    }
+}
@@ -0,0 +1,56 @@
+use crate::game_state::GameState;
+use crate::plot_engine::{PlayerOption, PlotState, PlotUpdate};
+use crate::save_load::SaveInfo;
+use schemars::schema::RootSchema;
+use schemars::schema_for;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// 前端代码生成所需的模型 JSON Schema，键为 Rust 类型名
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StateSchemas {
+    pub crate_version: String,
+    pub schemas: BTreeMap<String, RootSchema>,
+}
+
+/// 生成前端依赖的核心模型的 JSON Schema，回合结果对应 `PlotUpdate`
+pub fn state_schemas() -> StateSchemas {
+    let schemas = [
+        ("GameState", schema_for!(GameState)),
+        ("PlotState", schema_for!(PlotState)),
+        ("PlayerOption", schema_for!(PlayerOption)),
+        ("PlotUpdate", schema_for!(PlotUpdate)),
+        ("SaveInfo", schema_for!(SaveInfo)),
+    ]
+    .into_iter()
+    .map(|(name, schema)| (name.to_string(), schema))
+    .collect();
+
+    StateSchemas {
+        crate_version: env!("CARGO_PKG_VERSION").to_string(),
+        schemas,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    fn test_state_schemas_describe_core_models() {
+        let schemas = state_schemas();
+        assert_eq!(
+            schemas.schemas.keys().map(String::as_str).collect::<Vec<&str>>(),
+            vec!["GameState", "PlayerOption", "PlotState", "PlotUpdate", "SaveInfo"]
+        );
+
+        let game_state = serde_json::to_value(&schemas.schemas["GameState"]).unwrap();
+        let required = game_state["required"].as_array().unwrap();
+        assert!(required.contains(&Value::from("player")));
+        assert!(game_state["definitions"]["Character"].is_object());
+
+        let save_info = serde_json::to_value(&schemas.schemas["SaveInfo"]).unwrap();
+        assert_eq!(save_info["properties"]["slot_id"]["type"], "integer");
+    }
+}
@@ -0,0 +1,265 @@
+use crate::event_log::GameEvent;
+use crate::game_state::{Character, GameState, GameTime, WorldState};
+use crate::plot_engine::PlotState;
+use crate::script::Script;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, VecDeque};
+
+const MAX_JOURNAL_ENTRIES: usize = 256;
+
+/// 状态中可单独同步的区块
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StateSection {
+    Script,
+    Player,
+    WorldState,
+    GameTime,
+    EventsAppended,
+    EventHistory,
+    Plot,
+}
+
+#[derive(Debug, Clone)]
+struct JournalEntry {
+    version: u64,
+    sections: Vec<StateSection>,
+    prior_last_event_id: u64,
+}
+
+/// 记录每个状态版本改动了哪些区块
+#[derive(Debug, Clone, Default)]
+pub struct StateJournal {
+    version: u64,
+    last_event_id: u64,
+    entries: VecDeque<JournalEntry>,
+}
+
+impl StateJournal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn current_version(&self) -> u64 {
+        self.version
+    }
+
+    /// 比较新旧游戏状态并记录变更，无变化时版本号不变
+    pub fn record_game_change(&mut self, old: Option<&GameState>, new: &GameState) -> u64 {
+        let sections = match old {
+            None => vec![
+                StateSection::Script,
+                StateSection::Player,
+                StateSection::WorldState,
+                StateSection::GameTime,
+                StateSection::EventHistory,
+            ],
+            Some(old) => {
+                let mut sections = Vec::new();
+                if old.script != new.script {
+                    sections.push(StateSection::Script);
+                }
+                if old.player != new.player {
+                    sections.push(StateSection::Player);
+                }
+                if old.world_state != new.world_state {
+                    sections.push(StateSection::WorldState);
+                }
+                if old.game_time != new.game_time {
+                    sections.push(StateSection::GameTime);
+                }
+                if old.event_history != new.event_history {
+                    let appended = new.event_history.len() > old.event_history.len()
+                        && new.event_history.starts_with(&old.event_history);
+                    sections.push(if appended {
+                        StateSection::EventsAppended
+                    } else {
+                        StateSection::EventHistory
+                    });
+                }
+                sections
+            }
+        };
+
+        let last_event_id = new.event_history.last().map(|e| e.id).unwrap_or(0);
+        self.record(sections, last_event_id)
+    }
+
+    /// 比较新旧剧情状态并记录变更，忽略版本号本身
+    pub fn record_plot_change(&mut self, old: Option<&PlotState>, new: &PlotState) -> u64 {
+        let changed = match old {
+            None => true,
+            Some(old) => {
+                let mut normalized = new.clone();
+                normalized.version = old.version;
+                *old != normalized
+            }
+        };
+        let sections = if changed {
+            vec![StateSection::Plot]
+        } else {
+            Vec::new()
+        };
+        self.record(sections, self.last_event_id)
+    }
+
+    /// 返回指定版本之后的变更区块及该版本时的最后事件 ID，记录不足时返回 None
+    pub fn changes_since(&self, version: u64) -> Option<(BTreeSet<StateSection>, u64)> {
+        if version > self.version {
+            return None;
+        }
+        if version == self.version {
+            return Some((BTreeSet::new(), self.last_event_id));
+        }
+
+        let mut newer = self.entries.iter().filter(|e| e.version > version).peekable();
+        let first = newer.peek()?;
+        if first.version != version + 1 {
+            return None;
+        }
+        let watermark = first.prior_last_event_id;
+        let sections = newer.flat_map(|e| e.sections.iter().copied()).collect();
+        Some((sections, watermark))
+    }
+
+    fn record(&mut self, sections: Vec<StateSection>, last_event_id: u64) -> u64 {
+        if sections.is_empty() {
+            return self.version;
+        }
+        self.version += 1;
+        self.entries.push_back(JournalEntry {
+            version: self.version,
+            sections,
+            prior_last_event_id: self.last_event_id,
+        });
+        self.last_event_id = last_event_id;
+        while self.entries.len() > MAX_JOURNAL_ENTRIES {
+            self.entries.pop_front();
+        }
+        self.version
+    }
+}
+
+/// 增量同步结果，只携带客户端版本之后发生变化的区块
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StateDelta {
+    pub version: u64,
+    pub full_resync: bool,
+    pub script: Option<Script>,
+    pub player: Option<Character>,
+    pub world_state: Option<WorldState>,
+    pub game_time: Option<GameTime>,
+    pub event_history: Option<Vec<GameEvent>>,
+    pub new_events: Vec<GameEvent>,
+    pub plot_state: Option<PlotState>,
+}
+
+impl StateDelta {
+    pub fn build(
+        since: u64,
+        journal: &StateJournal,
+        state: &GameState,
+        plot_state: Option<&PlotState>,
+    ) -> Self {
+        let Some((sections, watermark)) = journal.changes_since(since) else {
+            return Self::full(journal.current_version(), state, plot_state);
+        };
+
+        let has = |section: StateSection| sections.contains(&section);
+        let full_history = has(StateSection::EventHistory);
+        Self {
+            version: journal.current_version(),
+            full_resync: false,
+            script: has(StateSection::Script).then(|| state.script.clone()),
+            player: has(StateSection::Player).then(|| state.player.clone()),
+            world_state: has(StateSection::WorldState).then(|| state.world_state.clone()),
+            game_time: has(StateSection::GameTime).then(|| state.game_time.clone()),
+            event_history: full_history.then(|| state.event_history.clone()),
+            new_events: if !full_history && has(StateSection::EventsAppended) {
+                state
+                    .event_history
+                    .iter()
+                    .filter(|e| e.id > watermark)
+                    .cloned()
+                    .collect()
+            } else {
+                Vec::new()
+            },
+            plot_state: if has(StateSection::Plot) {
+                plot_state.cloned()
+            } else {
+                None
+            },
+        }
+    }
+
+    fn full(version: u64, state: &GameState, plot_state: Option<&PlotState>) -> Self {
+        Self {
+            version,
+            full_resync: true,
+            script: Some(state.script.clone()),
+            player: Some(state.player.clone()),
+            world_state: Some(state.world_state.clone()),
+            game_time: Some(state.game_time.clone()),
+            event_history: Some(state.event_history.clone()),
+            new_events: Vec::new(),
+            plot_state: plot_state.cloned(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.full_resync
+            && self.script.is_none()
+            && self.player.is_none()
+            && self.world_state.is_none()
+            && self.game_time.is_none()
+            && self.event_history.is_none()
+            && self.new_events.is_empty()
+            && self.plot_state.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plot_engine::Scene;
+
+    fn plot_state(name: &str) -> PlotState {
+        PlotState::new(Scene::new(
+            "start".to_string(),
+            name.to_string(),
+            "开篇".to_string(),
+            "青云宗".to_string(),
+        ))
+    }
+
+    #[test]
+    fn test_record_plot_change_ignores_version_field() {
+        let mut journal = StateJournal::new();
+        let first = plot_state("第一章");
+        assert_eq!(journal.record_plot_change(None, &first), 1);
+
+        let mut same = first.clone();
+        same.version = 99;
+        assert_eq!(journal.record_plot_change(Some(&first), &same), 1);
+
+        let (sections, _) = journal.changes_since(0).unwrap();
+        assert_eq!(sections.into_iter().collect::<Vec<_>>(), vec![StateSection::Plot]);
+    }
+
+    #[test]
+    fn test_changes_since_evicted_version_requires_full_resync() {
+        let mut journal = StateJournal::new();
+        let mut previous = plot_state("第0章");
+        journal.record_plot_change(None, &previous);
+        for idx in 1..=MAX_JOURNAL_ENTRIES + 1 {
+            let next = plot_state(&format!("第{}章", idx));
+            journal.record_plot_change(Some(&previous), &next);
+            previous = next;
+        }
+
+        assert!(journal.changes_since(0).is_none());
+        assert!(journal.changes_since(journal.current_version() - 1).is_some());
+        assert!(journal.changes_since(journal.current_version() + 1).is_none());
+    }
+}
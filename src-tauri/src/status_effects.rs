@@ -0,0 +1,245 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// 小境界突破成功后顿悟持续的天数
+pub const BREAKTHROUGH_ENLIGHTENMENT_DAYS: u32 = 3;
+/// 突破失败后走火入魔持续的天数
+pub const BREAKTHROUGH_DEVIATION_DAYS: u32 = 5;
+/// 渡劫失败留下的伤势或心魔持续的天数
+pub const TRIBULATION_AFFLICTION_DAYS: u32 = 10;
+/// 战败负伤持续的天数
+pub const DEFEAT_INJURY_DAYS: u32 = 7;
+/// 险胜或脱身时气血低于该比例会负伤
+pub const WOUNDED_HP_RATIO: f32 = 0.3;
+/// 险胜或脱身负伤持续的天数
+pub const WOUNDED_INJURY_DAYS: u32 = 3;
+/// 中了毒功后中毒持续的天数
+pub const POISON_DAYS: u32 = 5;
+/// 行动失手负伤或走火入魔持续的天数
+pub const MISHAP_AFFLICTION_DAYS: u32 = 4;
+/// 休息一次额外缩短伤病的天数
+pub const REST_RECOVERY_DAYS: u32 = 2;
+
+/// 附着在角色身上的临时状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StatusEffectKind {
+    Injured,
+    Enlightened,
+    Poisoned,
+    QiDeviation,
+}
+
+impl StatusEffectKind {
+    pub fn id(&self) -> &'static str {
+        match self {
+            StatusEffectKind::Injured => "injured",
+            StatusEffectKind::Enlightened => "enlightened",
+            StatusEffectKind::Poisoned => "poisoned",
+            StatusEffectKind::QiDeviation => "qi_deviation",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            StatusEffectKind::Injured => "负伤",
+            StatusEffectKind::Enlightened => "顿悟",
+            StatusEffectKind::Poisoned => "中毒",
+            StatusEffectKind::QiDeviation => "走火入魔",
+        }
+    }
+
+    /// 状态对数值的影响，用于提示与属性面板
+    pub fn summary(&self) -> &'static str {
+        match self {
+            StatusEffectKind::Injured => "战力与修炼收益下降，突破更难",
+            StatusEffectKind::Enlightened => "修炼收益大增，突破更易",
+            StatusEffectKind::Poisoned => "余毒未清，战力与修炼收益略降",
+            StatusEffectKind::QiDeviation => "真气紊乱，修炼几无寸进，突破极难",
+        }
+    }
+
+    /// 状态到期时写入行动结果的一句话
+    pub fn recovery_note(&self) -> &'static str {
+        match self {
+            StatusEffectKind::Injured => "伤势已经痊愈",
+            StatusEffectKind::Enlightened => "顿悟的灵光渐渐散去",
+            StatusEffectKind::Poisoned => "体内余毒已清",
+            StatusEffectKind::QiDeviation => "紊乱的真气归于平复",
+        }
+    }
+
+    /// 休息能否加快恢复
+    pub fn is_ailment(&self) -> bool {
+        *self != StatusEffectKind::Enlightened
+    }
+
+    fn cultivation_multiplier(&self) -> f32 {
+        match self {
+            StatusEffectKind::Injured => 0.6,
+            StatusEffectKind::Enlightened => 1.5,
+            StatusEffectKind::Poisoned => 0.7,
+            StatusEffectKind::QiDeviation => 0.3,
+        }
+    }
+
+    fn breakthrough_modifier(&self) -> f32 {
+        match self {
+            StatusEffectKind::Injured => -0.1,
+            StatusEffectKind::Enlightened => 0.15,
+            StatusEffectKind::Poisoned => -0.05,
+            StatusEffectKind::QiDeviation => -0.3,
+        }
+    }
+
+    fn combat_multiplier(&self) -> f32 {
+        match self {
+            StatusEffectKind::Injured => 0.75,
+            StatusEffectKind::Enlightened => 1.0,
+            StatusEffectKind::Poisoned => 0.85,
+            StatusEffectKind::QiDeviation => 0.8,
+        }
+    }
+}
+
+/// 一个生效中的状态
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct StatusEffect {
+    pub kind: StatusEffectKind,
+    /// 剩余的游戏天数
+    pub remaining_days: u32,
+    /// 状态的来由，如「败于韩立之手」
+    pub source: String,
+}
+
+/// 角色身上的全部状态，同种状态至多一条
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(transparent)]
+pub struct StatusEffects(Vec<StatusEffect>);
+
+impl StatusEffects {
+    pub fn iter(&self) -> impl Iterator<Item = &StatusEffect> {
+        self.0.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn has(&self, kind: StatusEffectKind) -> bool {
+        self.0.iter().any(|effect| effect.kind == kind)
+    }
+
+    /// 施加状态；已有同种状态时不叠加，剩余天数取较长者
+    pub fn apply(&mut self, kind: StatusEffectKind, days: u32, source: impl Into<String>) {
+        if days == 0 {
+            return;
+        }
+        match self.0.iter_mut().find(|effect| effect.kind == kind) {
+            Some(effect) => {
+                if days > effect.remaining_days {
+                    effect.remaining_days = days;
+                    effect.source = source.into();
+                }
+            }
+            None => self.0.push(StatusEffect {
+                kind,
+                remaining_days: days,
+                source: source.into(),
+            }),
+        }
+    }
+
+    /// 游戏时间流逝，返回到期解除的状态
+    pub fn tick(&mut self, days: u32) -> Vec<StatusEffectKind> {
+        self.shorten(days, |_| true)
+    }
+
+    /// 休养加快伤病恢复，返回因此解除的状态
+    pub fn recover(&mut self, days: u32) -> Vec<StatusEffectKind> {
+        self.shorten(days, StatusEffectKind::is_ailment)
+    }
+
+    fn shorten(
+        &mut self,
+        days: u32,
+        affects: impl Fn(&StatusEffectKind) -> bool,
+    ) -> Vec<StatusEffectKind> {
+        let mut expired = Vec::new();
+        self.0.retain_mut(|effect| {
+            if !affects(&effect.kind) {
+                return true;
+            }
+            effect.remaining_days = effect.remaining_days.saturating_sub(days);
+            if effect.remaining_days == 0 {
+                expired.push(effect.kind);
+            }
+            effect.remaining_days > 0
+        });
+        expired
+    }
+
+    /// 修炼收益的总倍数
+    pub fn cultivation_multiplier(&self) -> f32 {
+        self.0
+            .iter()
+            .map(|effect| effect.kind.cultivation_multiplier())
+            .product()
+    }
+
+    /// 突破成功率的总加减
+    pub fn breakthrough_modifier(&self) -> f32 {
+        self.0
+            .iter()
+            .map(|effect| effect.kind.breakthrough_modifier())
+            .sum()
+    }
+
+    /// 可发挥战力的总倍数
+    pub fn combat_multiplier(&self) -> f32 {
+        self.0
+            .iter()
+            .map(|effect| effect.kind.combat_multiplier())
+            .product()
+    }
+
+    /// 续写提示中描述玩家状态的行
+    pub fn prompt_lines(&self) -> Vec<String> {
+        self.0
+            .iter()
+            .map(|effect| {
+                format!(
+                    "{}（还剩 {} 天，{}）：{}",
+                    effect.kind.label(),
+                    effect.remaining_days,
+                    effect.source,
+                    effect.kind.summary()
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_effects_refresh_decay_and_recover() {
+        let mut effects = StatusEffects::default();
+        effects.apply(StatusEffectKind::Injured, 3, "险胜");
+        effects.apply(StatusEffectKind::Injured, 7, "战败");
+        effects.apply(StatusEffectKind::Injured, 2, "擦伤");
+        effects.apply(StatusEffectKind::Enlightened, 2, "突破");
+        assert_eq!(effects.iter().count(), 2);
+        assert_eq!(effects.iter().next().unwrap().remaining_days, 7);
+        assert!(effects.cultivation_multiplier() > 0.6);
+        assert!((effects.breakthrough_modifier() - 0.05).abs() < 1e-6);
+
+        assert_eq!(effects.tick(2), vec![StatusEffectKind::Enlightened]);
+        assert_eq!(effects.recover(REST_RECOVERY_DAYS), Vec::new());
+        assert_eq!(effects.tick(3), vec![StatusEffectKind::Injured]);
+        assert!(effects.is_empty());
+        assert_eq!(effects.combat_multiplier(), 1.0);
+    }
+}
@@ -0,0 +1,262 @@
+use crate::cold_storage::ColdStorage;
+use crate::save_load::SaveLoadSystem;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+const SECONDS_PER_DAY: u64 = 86_400;
+pub const DEFAULT_CACHE_MAX_AGE_DAYS: u32 = 7;
+
+/// 磁盘清理策略
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StorageCleanupPolicy {
+    /// 删除超过该天数未改动的冷存储缓存，为空时不清理缓存；本会话的缓存始终保留
+    pub cache_max_age_days: Option<u32>,
+    /// 压缩已达成结局的存档，压缩后仍可读取
+    pub compress_finished_runs: bool,
+}
+
+impl Default for StorageCleanupPolicy {
+    fn default() -> Self {
+        Self {
+            cache_max_age_days: Some(DEFAULT_CACHE_MAX_AGE_DAYS),
+            compress_finished_runs: true,
+        }
+    }
+}
+
+/// 单个存档槽（一局游戏）的磁盘占用
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunStorage {
+    pub slot_id: u32,
+    pub file_name: String,
+    pub bytes: u64,
+    pub compressed: bool,
+    /// 本局是否已达成结局
+    pub finished: bool,
+    pub modified_at: u64,
+}
+
+/// 一个引擎会话的冷存储缓存目录
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CacheStorage {
+    pub session: String,
+    pub bytes: u64,
+    pub modified_at: u64,
+    /// 是否为当前会话正在使用的缓存
+    pub active: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StorageReport {
+    pub total_bytes: u64,
+    pub save_bytes: u64,
+    pub cache_bytes: u64,
+    /// 存档目录中的设置、结局图鉴等其他文件
+    pub other_bytes: u64,
+    pub runs: Vec<RunStorage>,
+    pub caches: Vec<CacheStorage>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StorageCleanupResult {
+    pub removed_caches: Vec<String>,
+    pub compressed_slots: Vec<u32>,
+    pub bytes_freed: u64,
+    /// 清理后的占用情况
+    pub report: StorageReport,
+}
+
+/// 统计存档与冷存储的磁盘占用，并按策略清理
+pub struct StorageManager<'a> {
+    saves: &'a SaveLoadSystem,
+    cold_storage: &'a ColdStorage,
+}
+
+impl<'a> StorageManager<'a> {
+    pub fn new(saves: &'a SaveLoadSystem, cold_storage: &'a ColdStorage) -> Self {
+        Self {
+            saves,
+            cold_storage,
+        }
+    }
+
+    pub fn report(&self) -> Result<StorageReport> {
+        let runs = self
+            .saves
+            .build_manifest()?
+            .entries
+            .into_iter()
+            .map(|entry| RunStorage {
+                slot_id: entry.slot_id,
+                compressed: entry.file_name.ends_with(".gz"),
+                finished: entry.finished,
+                file_name: entry.file_name,
+                bytes: entry.size_bytes,
+                modified_at: entry.modified_at,
+            })
+            .collect::<Vec<RunStorage>>();
+        let caches = self
+            .cold_storage
+            .session_directories()
+            .into_iter()
+            .map(|directory| {
+                let (bytes, modified_at) = directory_usage(&directory);
+                CacheStorage {
+                    session: directory
+                        .file_name()
+                        .and_then(|name| name.to_str())
+                        .unwrap_or_default()
+                        .to_string(),
+                    bytes,
+                    modified_at,
+                    active: directory == self.cold_storage.directory(),
+                }
+            })
+            .collect::<Vec<CacheStorage>>();
+
+        let save_bytes = runs.iter().map(|run| run.bytes).sum::<u64>();
+        let cache_bytes = caches.iter().map(|cache| cache.bytes).sum::<u64>();
+        let other_bytes = directory_usage(self.saves.save_directory())
+            .0
+            .saturating_sub(save_bytes);
+        Ok(StorageReport {
+            total_bytes: save_bytes + cache_bytes + other_bytes,
+            save_bytes,
+            cache_bytes,
+            other_bytes,
+            runs,
+            caches,
+        })
+    }
+
+    /// 按策略删除过期缓存、压缩已完结的存档；`now` 为 Unix 时间戳（秒）
+    pub fn cleanup(
+        &self,
+        policy: &StorageCleanupPolicy,
+        now: u64,
+    ) -> Result<StorageCleanupResult> {
+        let before = self.report()?;
+        let mut result = StorageCleanupResult::default();
+
+        if let Some(days) = policy.cache_max_age_days {
+            let max_age = u64::from(days).saturating_mul(SECONDS_PER_DAY);
+            let root = self.cold_storage.directory().parent();
+            for cache in before.caches.iter().filter(|cache| {
+                !cache.active && now.saturating_sub(cache.modified_at) > max_age
+            }) {
+                if let Some(root) = root {
+                    fs::remove_dir_all(root.join(&cache.session))?;
+                    result.bytes_freed += cache.bytes;
+                    result.removed_caches.push(cache.session.clone());
+                }
+            }
+        }
+
+        if policy.compress_finished_runs {
+            for run in before.runs.iter().filter(|run| run.finished && !run.compressed) {
+                result.bytes_freed += self.saves.compress_save(run.slot_id)?;
+                result.compressed_slots.push(run.slot_id);
+            }
+        }
+
+        result.report = self.report()?;
+        Ok(result)
+    }
+}
+
+/// 目录下文件（不含子目录）的总字节数与最近修改时间
+fn directory_usage(directory: &Path) -> (u64, u64) {
+    let Ok(entries) = fs::read_dir(directory) else {
+        return (0, 0);
+    };
+    entries
+        .filter_map(|entry| entry.ok()?.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .fold((0, 0), |(bytes, newest), metadata| {
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            (bytes + metadata.len(), newest.max(modified))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ending::AchievedEnding;
+    use crate::game_engine::GameEngine;
+    use crate::models::CultivationRealm;
+    use crate::save_load::SaveData;
+    use crate::script::{Climate, Location, LocationKind};
+    use crate::script_manager::ScriptManager;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_cleanup_removes_stale_caches_and_compresses_finished_runs() {
+        let temp_dir = TempDir::new().unwrap();
+        let saves = SaveLoadSystem::with_directory(temp_dir.path().join("saves"));
+        let cold_root = temp_dir.path().join("cold");
+        let cold_storage = ColdStorage::new(cold_root.join("00000000000000aa"));
+        for session in ["00000000000000aa", "00000000000000bb"] {
+            fs::create_dir_all(cold_root.join(session)).unwrap();
+            fs::write(cold_root.join(session).join("chapter_1.json"), "[\"正文\"]").unwrap();
+        }
+        fs::create_dir_all(cold_root.join("not-a-session")).unwrap();
+
+        let mut script = ScriptManager::new().blank_script();
+        script
+            .world_setting
+            .cultivation_realms
+            .push(CultivationRealm::new("练气".to_string(), 1, 0, 1.0));
+        script.world_setting.locations.push(Location {
+            id: "sect".to_string(),
+            name: "青云宗".to_string(),
+            description: String::new(),
+            spiritual_energy: 1.0,
+            kind: LocationKind::Wilderness,
+            climate: Climate::Temperate,
+        });
+        script.initial_state.starting_location = "sect".to_string();
+        let mut engine = GameEngine::new();
+        engine.initialize_game(script).unwrap();
+        let mut state = engine.get_current_state().unwrap();
+        saves.save_game(1, &SaveData::from_game_state(state.clone())).unwrap();
+        state.ending = Some(AchievedEnding {
+            ending_id: "default".to_string(),
+            title: "道途未竟".to_string(),
+            finale: "终章".to_string(),
+            day: 3,
+            cause: None,
+        });
+        saves.save_game(2, &SaveData::from_game_state(state)).unwrap();
+
+        let manager = StorageManager::new(&saves, &cold_storage);
+        let report = manager.report().unwrap();
+        assert_eq!(report.runs.len(), 2);
+        assert!(report.runs[1].finished && !report.runs[0].finished);
+        assert_eq!(report.caches.len(), 2);
+        assert!(report.caches[0].active);
+        assert_eq!(
+            report.total_bytes,
+            report.save_bytes + report.cache_bytes + report.other_bytes
+        );
+
+        let now = report.caches[1].modified_at + 8 * SECONDS_PER_DAY;
+        let result = manager
+            .cleanup(&StorageCleanupPolicy::default(), now)
+            .unwrap();
+        assert_eq!(result.removed_caches, vec!["00000000000000bb".to_string()]);
+        assert_eq!(result.compressed_slots, vec![2]);
+        assert!(result.bytes_freed > 0);
+        assert!(result.report.runs[1].compressed);
+        assert_eq!(result.report.caches.len(), 1);
+        assert!(saves.load_game(2).unwrap().game_state.ending.is_some());
+    }
+}
@@ -1,21 +1,70 @@
-﻿use crate::game_engine::GameEngine;
+﻿use crate::action_filters::{
+    app_action_filters, set_app_action_filters, ActionFilterScope, ActionFilters,
+};
+use crate::achievements::AchievementStatus;
+use crate::cancellation::{self, Generation, GenerationStarted};
+use crate::character_card::CharacterCard;
+use crate::character_sheet::CharacterSheet;
+use crate::cold_storage::MemoryUsageReport;
+use crate::combat_engine::{narrate_round, CombatMove, CombatState};
+use crate::companion::{fallback_advice, phrase_advice, CompanionAdvice};
+use crate::content_filter::{app_content_filter, set_app_content_filter, ContentFilterSettings};
+use crate::context_assembler::ContextAssembler;
+use crate::ending::{narrate_finale, AchievedEnding, EndingGalleryView, EndingSummary};
+use crate::event_log::GameEvent;
+use crate::event_triage::EventTriage;
+use crate::game_engine::GameEngine;
 use crate::game_state::GameState;
-use crate::event_log::EventImportance;
+use crate::generation_diagnostics::GenerationDiagnostics;
+use crate::generation_failure::GenerationFailure;
+use crate::difficulty::DifficultySettings;
+use crate::economy::ResourceKind;
+use crate::house_rules::HouseRules;
+use crate::idle_progression::{IdleProgression, IdleReport};
 use crate::llm_runtime_config::{
     clear_runtime_llm_config, get_llm_config_status as runtime_llm_config_status,
-    resolve_llm_config, set_runtime_llm_config, LLMConfigStatus,
+    resolve_llm_config, set_llm_model_routes, set_runtime_llm_config, shared_llm_service,
+    LLMConfigStatus,
 };
-use crate::llm_service::{LLMConfig, LLMRequest, LLMService};
+use crate::llm_provider::ProviderKind;
+use crate::llm_service::{LLMConfig, LLMRequest, LLMService, LLMSubsystem, RetryPolicy};
+use crate::llm_trace::{clear_traces, recent_traces, LlmTrace, DEFAULT_TRACE_LIMIT};
+use crate::market::{flavor_market, Market, MarketReceipt};
+use crate::memory_consolidation::{MemoryConsolidator, MemoryJob};
+use crate::narrator::{answer_question, NarratorAnswer, MAX_QUESTION_CHARS};
 use crate::novel_generator::{Novel, NovelGenerator};
-use crate::numerical_system::{Action, Context, StatChange};
-use crate::plot_engine::{PlayerAction, PlayerOption, PlotEngine, PlotSettings, PlotState};
-use crate::save_load::SaveInfo;
-use crate::script::Script;
+use crate::npc::{NPCDetail, NPCProfile};
+use crate::npc_alerts::NpcEventNotice;
+use crate::relationship_graph::RelationshipGraph;
+use crate::npc_dialogue::{converse, NPCDialogue, MAX_PLAYER_MESSAGE_CHARS};
+use crate::numerical_system::LocationSurvey;
+use crate::plot_engine::{ChapterState, PlayerAction, PlayerOption, PlotSettings, PlotState};
+use crate::prompt_builder::PromptContext;
+use crate::quest_system::Quest;
+use crate::rng::GameRng;
+use crate::save_load::{
+    is_autosave_slot, AutosaveSettings, ManifestVerification, SaveInfo, SaveJob, SaveManifest,
+    SaveProgress, AUTOSAVE_FIRST_SLOT, AUTOSAVE_SLOT_COUNT,
+};
+use crate::script::{Script, ScriptLanguage};
+use crate::script_import::{ImportedScript, ScriptImporter};
+use crate::script_manager::{ScriptDraftReport, ScriptSection};
+use crate::script_reload::{ScriptReloadReport, SCRIPT_WATCH_INTERVAL_MS};
+use crate::script_wizard::{WizardAnswers, WorldTheme};
+use crate::settings_store::AppSettings;
+use crate::side_story::SideStoryOutcome;
+use crate::state_schema::{state_schemas, StateSchemas};
+use crate::state_sync::StateDelta;
+use crate::storage_manager::{StorageCleanupPolicy, StorageCleanupResult, StorageReport};
+use crate::timeline_branch::{BranchIndex, BranchInfo};
+use crate::turn_gate::TurnStatus;
+use crate::turn_pipeline::{ActionPreview, Turn, TurnPipeline};
+use crate::world_bulletin::{BulletinDesk, BulletinSource, WorldBulletin};
 use crate::app_error::AppError;
 use serde::{Deserialize, Serialize};
 use std::path::Path;
-use std::sync::Mutex;
-use tauri::State;
+use tokio::sync::RwLock;
+use tauri::{AppHandle, Emitter, Manager, State};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ErrorResponse {
@@ -30,10 +79,36 @@ impl From<anyhow::Error> for ErrorResponse {
     }
 }
 
+const SAVE_PROGRESS_EVENT: &str = "save-progress";
+const SCRIPT_RELOAD_EVENT: &str = "script-reloaded";
+const ACHIEVEMENT_UNLOCKED_EVENT: &str = "achievement-unlocked";
+const NPC_EVENT: &str = "npc-event";
+const ENDING_REACHED_EVENT: &str = "ending-reached";
+const GENERATION_STARTED_EVENT: &str = "generation-started";
+
 fn map_error(context: &str, err: impl Into<AppError>) -> String {
     err.into().with_context(context).to_string()
 }
 
+/// 登记一次可取消的生成，并通过 generation-started 事件把 request_id 告知前端
+fn begin_generation(app: &AppHandle, request_id: Option<String>, command: &str) -> Generation {
+    let generation = Generation::begin(request_id);
+    let _ = app.emit(
+        GENERATION_STARTED_EVENT,
+        GenerationStarted {
+            request_id: generation.request_id.clone(),
+            command: command.to_string(),
+        },
+    );
+    generation
+}
+
+/// 取消进行中的生成：丢弃尚未返回的 LLM 请求，本次生成不写入任何状态
+#[tauri::command]
+pub async fn cancel_generation(request_id: String) -> Result<bool, String> {
+    Ok(cancellation::cancel_generation(&request_id))
+}
+
 fn validate_slot_id(slot_id: u32) -> Result<(), AppError> {
     if (1..=99).contains(&slot_id) {
         Ok(())
@@ -45,6 +120,22 @@ fn validate_slot_id(slot_id: u32) -> Result<(), AppError> {
     }
 }
 
+fn validate_autosave_slot_id(slot_id: u32) -> Result<(), AppError> {
+    if is_autosave_slot(slot_id) {
+        Ok(())
+    } else {
+        Err(AppError::new(
+            crate::app_error::AppErrorKind::InvalidInput,
+            format!(
+                "自动存档槽位必须在 {}-{} 之间，当前为 {}",
+                AUTOSAVE_FIRST_SLOT,
+                AUTOSAVE_FIRST_SLOT + AUTOSAVE_SLOT_COUNT - 1,
+                slot_id
+            ),
+        ))
+    }
+}
+
 fn validate_file_path(path: &str, allowed_exts: &[&str]) -> Result<(), AppError> {
     let p = Path::new(path);
     if !p.exists() {
@@ -129,7 +220,11 @@ fn validate_llm_config_input(input: &LLMConfigInput) -> Result<(), AppError> {
             "temperature 必须在 0-2 之间",
         ));
     }
-    if input.api_key.trim().is_empty() {
+    input
+        .retry_policy()
+        .validate()
+        .map_err(|e| AppError::new(crate::app_error::AppErrorKind::InvalidInput, e.to_string()))?;
+    if input.provider_kind.requires_api_key() && input.api_key.trim().is_empty() {
         let endpoint = input.endpoint.to_lowercase();
         let local = endpoint.contains("localhost") || endpoint.contains("127.0.0.1");
         if !local {
@@ -150,34 +245,605 @@ pub struct LLMConfigInput {
     pub model: String,
     pub max_tokens: u32,
     pub temperature: f32,
+    #[serde(default)]
+    pub provider_kind: ProviderKind,
+    /// 请求超时秒数，缺省 30
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    /// 失败后的重试次数，缺省 2
+    #[serde(default)]
+    pub max_retries: Option<u32>,
+    /// 首次重试前的退避毫秒数，之后逐次翻倍，缺省 200
+    #[serde(default)]
+    pub retry_backoff_ms: Option<u64>,
+}
+
+impl LLMConfigInput {
+    fn retry_policy(&self) -> RetryPolicy {
+        let defaults = RetryPolicy::default();
+        RetryPolicy {
+            timeout_secs: self.timeout_secs.unwrap_or(defaults.timeout_secs),
+            max_retries: self.max_retries.unwrap_or(defaults.max_retries),
+            retry_backoff_ms: self.retry_backoff_ms.unwrap_or(defaults.retry_backoff_ms),
+        }
+    }
 }
 
 #[tauri::command]
-pub async fn set_llm_config(input: LLMConfigInput) -> Result<String, String> {
+pub async fn set_llm_config(
+    input: LLMConfigInput,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<String, String> {
     validate_llm_config_input(&input).map_err(|e| map_error("LLM 配置校验失败", e))?;
+    let retry_policy = input.retry_policy();
     let config = LLMConfig {
         endpoint: input.endpoint,
         api_key: input.api_key,
         model: input.model,
         max_tokens: input.max_tokens,
         temperature: input.temperature,
+        provider_kind: input.provider_kind,
+        retry_policy,
     };
     LLMService::new(config.clone()).map_err(|e| map_error("LLM 配置校验失败", e))?;
     set_runtime_llm_config(config);
+    refresh_engine_llm_service(engine.inner()).await;
     Ok("LLM 配置已更新".to_string())
 }
 
+/// 应用级、当前剧本级与合并后生效的自由输入过滤配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActionFilterSettings {
+    pub app: ActionFilters,
+    pub script: Option<ActionFilters>,
+    pub effective: ActionFilters,
+}
+
+#[tauri::command]
+pub async fn get_action_filters(
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<ActionFilterSettings, String> {
+    let engine = engine.read().await;
+    let app = app_action_filters();
+    let script = engine.get_current_state().ok().map(|s| s.script.action_filters);
+    let effective = match &script {
+        Some(script) => ActionFilters::merged(&app, script),
+        None => app.clone(),
+    };
+    Ok(ActionFilterSettings {
+        app,
+        script,
+        effective,
+    })
+}
+
+/// 更新自由输入过滤配置：`App` 写入配置文件，`Script` 写入当前剧本并随存档保存
+#[tauri::command]
+pub async fn update_action_filters(
+    scope: ActionFilterScope,
+    filters: ActionFilters,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<ActionFilters, String> {
+    match scope {
+        ActionFilterScope::App => {
+            set_app_action_filters(&filters).map_err(|e| {
+                map_error(
+                    "更新行动过滤失败",
+                    AppError::new(crate::app_error::AppErrorKind::InvalidInput, e),
+                )
+            })
+        }
+        ActionFilterScope::Script => {
+            let mut engine = engine.write().await;
+            engine
+                .update_script_action_filters(&filters)
+                .map_err(|e| map_error("更新行动过滤失败", e))
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn get_content_filter_settings() -> Result<ContentFilterSettings, String> {
+    Ok(app_content_filter())
+}
+
+/// 更新生成剧情的内容过滤设置，从下一回合起生效
+#[tauri::command]
+pub async fn set_content_filter_settings(
+    settings: ContentFilterSettings,
+) -> Result<ContentFilterSettings, String> {
+    set_app_content_filter(&settings).map_err(|e| {
+        map_error(
+            "更新内容过滤失败",
+            AppError::new(crate::app_error::AppErrorKind::InvalidInput, e),
+        )
+    })
+}
+
+/// 读取本局房规；传入 `rules` 时更新并返回新房规
+#[tauri::command]
+pub async fn house_rules(
+    rules: Option<HouseRules>,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<HouseRules, String> {
+    let mut engine = engine.write().await;
+    match rules {
+        Some(rules) => engine
+            .update_house_rules(rules)
+            .map_err(|e| map_error("更新房规失败", e)),
+        None => engine
+            .get_current_state()
+            .map(|state| state.house_rules)
+            .map_err(|e| map_error("读取房规失败", e)),
+    }
+}
+
+/// 调整本局难度，返回生效后的难度
+#[tauri::command]
+pub async fn set_difficulty(
+    difficulty: DifficultySettings,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<DifficultySettings, String> {
+    let mut engine = engine.write().await;
+    engine
+        .set_difficulty(difficulty)
+        .map_err(|e| map_error("调整难度失败", e))
+}
+
+/// 列出本局全部任务，包括已完成与已放弃的
+#[tauri::command]
+pub async fn get_quests(engine: State<'_, RwLock<GameEngine>>) -> Result<Vec<Quest>, String> {
+    let engine = engine.read().await;
+    engine
+        .get_current_state()
+        .map(|state| state.quests.quests)
+        .map_err(|e| map_error("读取任务失败", e))
+}
+
+#[tauri::command]
+pub async fn abandon_quest(
+    quest_id: String,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<Quest, String> {
+    let mut engine = engine.write().await;
+    engine
+        .abandon_quest(&quest_id)
+        .map_err(|e| map_error("放弃任务失败", e))
+}
+
+/// 进入秘境，返回秘境自己的剧情状态；主线剧情压栈，离开秘境时恢复
+#[tauri::command]
+pub async fn enter_side_story(
+    realm_id: String,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<PlotState, String> {
+    // 与玩家回合共用闸门，回合生成期间不能切换剧情
+    let _turn_ticket = engine.read().await.begin_turn().map_err(|e| e.to_string())?;
+    let mut engine = engine.write().await;
+    engine
+        .enter_side_story(&realm_id)
+        .map_err(|e| map_error("进入秘境失败", e))
+}
+
+/// 离开秘境，秘境中的段落与收获并回主线剧情
+#[tauri::command]
+pub async fn exit_side_story(
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<SideStoryOutcome, String> {
+    let _turn_ticket = engine.read().await.begin_turn().map_err(|e| e.to_string())?;
+    let mut engine = engine.write().await;
+    engine
+        .exit_side_story()
+        .map_err(|e| map_error("离开秘境失败", e))
+}
+
+/// 与 NPC 直接对话，回应会改变其对玩家的好感与信任
+#[tauri::command]
+pub async fn talk_to_npc(
+    npc_id: String,
+    message: String,
+    request_id: Option<String>,
+    app: AppHandle,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<NPCDialogue, String> {
+    let message = message.trim().chars().take(MAX_PLAYER_MESSAGE_CHARS).collect::<String>();
+    if message.is_empty() {
+        return Err(map_error(
+            "对话失败",
+            AppError::new(crate::app_error::AppErrorKind::InvalidInput, "发言不能为空"),
+        ));
+    }
+
+    let (npc, player_id, game_context, language) = {
+        let engine = engine.read().await;
+        let (npc, player_id) = engine
+            .dialogue_partner(&npc_id)
+            .map_err(|e| map_error("对话失败", e))?;
+        let plot_state = engine.get_plot_state().ok();
+        let language = plot_state
+            .as_ref()
+            .map(|plot| plot.settings.language)
+            .unwrap_or_default();
+        let game_context = match (engine.get_current_state(), &plot_state) {
+            (Ok(game_state), Some(plot_state)) => ContextAssembler::new()
+                .with_relationships(engine.relationship_prompt_lines(&player_id))
+                .assemble(&game_state, plot_state),
+            _ => PromptContext::default(),
+        };
+        (npc, player_id, game_context, language)
+    };
+
+    let llm_service = shared_llm_service();
+    let dialogue = begin_generation(&app, request_id, "talk_to_npc")
+        .run(async {
+            Ok(converse(
+                llm_service.as_deref(),
+                &npc,
+                &player_id,
+                &message,
+                &game_context,
+                language,
+            )
+            .await)
+        })
+        .await?;
+
+    let mut engine = engine.write().await;
+    engine
+        .record_dialogue(&message, &dialogue)
+        .map_err(|e| map_error("记录对话失败", e))?;
+    let _ = engine.check_achievements();
+    for achievement in engine.take_unlocked_achievements() {
+        let _ = app.emit(ACHIEVEMENT_UNLOCKED_EVENT, achievement);
+    }
+    Ok(dialogue)
+}
+
+/// 向旁白询问世界设定，回答依据设定事实生成，不推进时间也不改动状态
+#[tauri::command]
+pub async fn ask_narrator(
+    question: String,
+    request_id: Option<String>,
+    app: AppHandle,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<NarratorAnswer, String> {
+    let question = question.trim().chars().take(MAX_QUESTION_CHARS).collect::<String>();
+    if question.is_empty() {
+        return Err(map_error(
+            "询问旁白失败",
+            AppError::new(crate::app_error::AppErrorKind::InvalidInput, "问题不能为空"),
+        ));
+    }
+
+    let (game_state, facts) = {
+        let engine = engine.read().await;
+        engine
+            .narrator_briefing()
+            .map_err(|e| map_error("询问旁白失败", e))?
+    };
+
+    let llm_service = shared_llm_service();
+    begin_generation(&app, request_id, "ask_narrator")
+        .run(async {
+            Ok(answer_question(llm_service.as_deref(), &question, &game_state, &facts).await)
+        })
+        .await
+}
+
+/// 同伴建议：按任务、属性与风险为当前选项排序并说明理由；只给建议，从不自动执行
+#[tauri::command]
+pub async fn suggest_next_action(
+    use_llm: Option<bool>,
+    request_id: Option<String>,
+    app: AppHandle,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<CompanionAdvice, String> {
+    let (game_state, suggestions) = {
+        let engine = engine.read().await;
+        engine
+            .suggest_next_action()
+            .map_err(|e| map_error("生成行动建议失败", e))?
+    };
+
+    if !use_llm.unwrap_or(true) {
+        return Ok(fallback_advice(suggestions));
+    }
+    let llm_service = shared_llm_service();
+    begin_generation(&app, request_id, "suggest_next_action")
+        .run(async { Ok(phrase_advice(llm_service.as_deref(), &game_state, suggestions).await) })
+        .await
+}
+
+/// 查看 NPC 档案，包括好感、信任与当前情绪
+#[tauri::command]
+pub async fn get_npc_profile(
+    npc_id: String,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<NPCProfile, String> {
+    let mut engine = engine.write().await;
+    engine
+        .get_npc_profile(&npc_id)
+        .map_err(|e| map_error("读取NPC档案失败", e))
+}
+
+/// 列出本局全部 NPC 的档案
+#[tauri::command]
+pub async fn get_npcs(engine: State<'_, RwLock<GameEngine>>) -> Result<Vec<NPCProfile>, String> {
+    let mut engine = engine.write().await;
+    engine.list_npcs().map_err(|e| map_error("读取NPC列表失败", e))
+}
+
+/// 查看 NPC 的完整资料，包括目标、人际关系与最近的记忆
+#[tauri::command]
+pub async fn get_npc_detail(
+    npc_id: String,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<NPCDetail, String> {
+    let mut engine = engine.write().await;
+    engine
+        .get_npc_detail(&npc_id)
+        .map_err(|e| map_error("读取NPC资料失败", e))
+}
+
+/// 玩家与全部 NPC 之间的关系网，含好感、信任与最近的互动
+#[tauri::command]
+pub async fn get_relationship_graph(
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<RelationshipGraph, String> {
+    let engine = engine.read().await;
+    engine
+        .relationship_graph()
+        .map_err(|e| map_error("读取关系网失败", e))
+}
+
+/// 向 NPC 发起战斗
+#[tauri::command]
+pub async fn start_combat(
+    target_id: String,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<CombatState, String> {
+    let mut engine = engine.write().await;
+    engine
+        .start_combat(&target_id)
+        .map_err(|e| map_error("开启战斗失败", e))
+}
+
+/// 结算玩家一回合的出手，并为该回合生成战斗描写；永久死亡规则下战败即结束本局
+#[tauri::command]
+pub async fn combat_action(
+    action: CombatMove,
+    request_id: Option<String>,
+    app: AppHandle,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<CombatState, String> {
+    let combat = {
+        let mut engine = engine.write().await;
+        engine
+            .combat_round(action)
+            .map_err(|e| map_error("战斗行动失败", e))?
+    };
+    let Some(round) = combat.rounds.last() else {
+        return Ok(combat);
+    };
+
+    let llm_service = shared_llm_service();
+    // 取消时本回合的出手已结算，只是不写入描写
+    let narration = begin_generation(&app, request_id, "combat_action")
+        .run(async { Ok(narrate_round(llm_service.as_deref(), &combat, round).await) })
+        .await?;
+
+    let combat = {
+        let mut engine = engine.write().await;
+        engine
+            .record_combat_narration(round.round, narration)
+            .map_err(|e| map_error("记录战斗描写失败", e))?
+    };
+    if combat.is_over() {
+        conclude_if_ended(&app, engine.inner()).await;
+    }
+    Ok(combat)
+}
+
+/// 结束本局：按剧本结局条件选出结局，生成终章并计入结局图鉴
+#[tauri::command]
+pub async fn reach_ending(
+    request_id: Option<String>,
+    app: AppHandle,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<AchievedEnding, String> {
+    begin_generation(&app, request_id, "reach_ending")
+        .run(conclude_playthrough(&app, engine.inner()))
+        .await
+}
+
+/// 结局选定后在引擎锁外生成终章，记下结局并立即自动存档
+async fn conclude_playthrough(
+    app: &AppHandle,
+    engine: &RwLock<GameEngine>,
+) -> Result<AchievedEnding, String> {
+    let (ending, cause, game_state) = {
+        let engine = engine.read().await;
+        engine
+            .pending_ending()
+            .map_err(|e| map_error("结算结局失败", e))?
+    };
+
+    let llm_service = shared_llm_service();
+    let finale = narrate_finale(llm_service.as_deref(), &ending, cause, &game_state).await;
+
+    let (achieved, save_job) = {
+        let mut engine = engine.write().await;
+        let achieved = engine
+            .record_ending(&ending, cause, finale)
+            .map_err(|e| map_error("记录结局失败", e))?;
+        (achieved, engine.completion_save_job())
+    };
+    // 存档失败不影响结局，进度同样通过 save-progress 事件推送
+    if let Ok(Some(job)) = save_job {
+        spawn_save_job(job, app.clone());
+    }
+    Ok(achieved)
+}
+
+/// 本局满足自动结束的条件时收场，并把结局推送给前端
+async fn conclude_if_ended(app: &AppHandle, engine: &RwLock<GameEngine>) {
+    let ended = {
+        let engine = engine.read().await;
+        matches!(engine.ending_cause(), Ok(Some(_)))
+    };
+    if !ended {
+        return;
+    }
+    if let Ok(achieved) = conclude_playthrough(app, engine).await {
+        let _ = app.emit(ENDING_REACHED_EVENT, achieved);
+    }
+}
+
+/// 已结束对局的总结：结局、终局属性、战绩与重要事件
+#[tauri::command]
+pub async fn get_ending_summary(
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<EndingSummary, String> {
+    let engine = engine.read().await;
+    engine
+        .ending_summary()
+        .map_err(|e| map_error("读取结局总结失败", e))
+}
+
+#[tauri::command]
+pub async fn get_ending_gallery(
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<EndingGalleryView, String> {
+    let engine = engine.read().await;
+    Ok(engine.ending_gallery())
+}
+
+/// 逛玩家所在城镇的市集；新一批货的吆喝在引擎锁外润色
 #[tauri::command]
-pub async fn clear_llm_config() -> Result<String, String> {
+pub async fn visit_market(
+    request_id: Option<String>,
+    app: AppHandle,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<Market, String> {
+    let market = {
+        let engine = engine.write().await;
+        engine
+            .market_for_visit()
+            .map_err(|e| map_error("打开市集失败", e))?
+    };
+
+    let market = if market.flavored {
+        market
+    } else {
+        let llm_service = shared_llm_service();
+        begin_generation(&app, request_id, "visit_market")
+            .run(async { Ok(flavor_market(llm_service.as_deref(), market).await) })
+            .await?
+    };
+
+    let mut engine = engine.write().await;
+    engine
+        .store_market(market)
+        .map_err(|e| map_error("打开市集失败", e))
+}
+
+#[tauri::command]
+pub async fn buy_market_item(
+    item_id: String,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<MarketReceipt, String> {
+    let mut engine = engine.write().await;
+    engine
+        .buy_market_item(&item_id)
+        .map_err(|e| map_error("购买失败", e))
+}
+
+#[tauri::command]
+pub async fn sell_market_item(
+    item_id: String,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<MarketReceipt, String> {
+    let mut engine = engine.write().await;
+    engine
+        .sell_market_item(&item_id)
+        .map_err(|e| map_error("出售失败", e))
+}
+
+/// 在市集买卖灵草、矿石等资源，`buying` 为 false 时卖出
+#[tauri::command]
+pub async fn trade_resource(
+    kind: ResourceKind,
+    quantity: u64,
+    buying: bool,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<MarketReceipt, String> {
+    let mut engine = engine.write().await;
+    engine
+        .trade_market_resource(kind, quantity, buying)
+        .map_err(|e| map_error("交易资源失败", e))
+}
+
+/// 全部内置成就及跨局的解锁情况
+#[tauri::command]
+pub async fn get_achievements(
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<Vec<AchievementStatus>, String> {
+    let engine = engine.read().await;
+    Ok(engine.achievements())
+}
+
+#[tauri::command]
+pub async fn get_combat_state(
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<Option<CombatState>, String> {
+    let engine = engine.read().await;
+    engine
+        .get_combat_state()
+        .map_err(|e| map_error("读取战斗状态失败", e))
+}
+
+#[tauri::command]
+pub async fn clear_llm_config(engine: State<'_, RwLock<GameEngine>>) -> Result<String, String> {
     clear_runtime_llm_config();
+    refresh_engine_llm_service(engine.inner()).await;
     Ok("已清除运行时 LLM 配置".to_string())
 }
 
+/// LLM 配置变化后重建共享的 LLMService 并重新注入引擎各子系统
+async fn refresh_engine_llm_service(engine: &RwLock<GameEngine>) {
+    let mut engine = engine.write().await;
+    engine.refresh_llm_service();
+}
+
 #[tauri::command]
 pub async fn get_llm_config_status() -> Result<LLMConfigStatus, String> {
     Ok(runtime_llm_config_status())
 }
 
+/// 查看最近的 LLM 调用记录（提示、原始回复、用量与耗时），最新的在前
+#[tauri::command]
+pub async fn get_llm_traces(limit: Option<usize>) -> Result<Vec<LlmTrace>, String> {
+    Ok(recent_traces(limit.unwrap_or(DEFAULT_TRACE_LIMIT)))
+}
+
+#[tauri::command]
+pub async fn clear_llm_traces() -> Result<(), String> {
+    clear_traces();
+    Ok(())
+}
+
+/// 最近一次剧情续写的生成诊断（模型、用量、耗时、重试、解析方式、选项来源与警告），供调试面板使用
+#[tauri::command]
+pub async fn get_generation_diagnostics(
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<Option<GenerationDiagnostics>, String> {
+    let engine = engine.read().await;
+    let plot_state = engine
+        .get_plot_state()
+        .map_err(|e| map_error("获取生成诊断失败", e))?;
+    Ok(plot_state.last_generation_diagnostics)
+}
+
 #[tauri::command]
 pub async fn test_llm_connection() -> Result<String, String> {
     let cfg = resolve_llm_config().ok_or_else(|| "未检测到 LLM 配置".to_string())?;
@@ -187,6 +853,8 @@ pub async fn test_llm_connection() -> Result<String, String> {
             prompt: "请回复：连接成功".to_string(),
             max_tokens: Some(32),
             temperature: Some(0.1),
+            subsystem: LLMSubsystem::Other,
+            retry_policy: None,
         })
         .await
         .map_err(|e| e.to_string())?;
@@ -196,297 +864,711 @@ pub async fn test_llm_connection() -> Result<String, String> {
 #[tauri::command]
 pub async fn initialize_game(
     script: Script,
-    engine: State<'_, Mutex<GameEngine>>,
+    engine: State<'_, RwLock<GameEngine>>,
 ) -> Result<GameState, String> {
-    let mut engine = match engine.lock() {
-        Ok(guard) => guard,
-        Err(poisoned) => poisoned.into_inner(),
-    };
+    let mut engine = engine.write().await;
     engine.initialize_game(script).map_err(|e| e.to_string())
 }
 
+/// 指定随机种子，用于重放对局或复现问题
+#[tauri::command]
+pub async fn set_game_seed(seed: u64, engine: State<'_, RwLock<GameEngine>>) -> Result<(), String> {
+    let mut engine = engine.write().await;
+    engine
+        .set_game_seed(seed)
+        .map_err(|e| map_error("设置随机种子失败", e))
+}
+
 #[tauri::command]
 pub async fn execute_player_action(
     action: PlayerAction,
-    engine: State<'_, Mutex<GameEngine>>,
+    request_id: Option<String>,
+    app: AppHandle,
+    engine: State<'_, RwLock<GameEngine>>,
 ) -> Result<String, String> {
-    let (mut game_state, mut plot_state) = {
-        let engine = match engine.lock() {
-            Ok(guard) => guard,
-            Err(poisoned) => poisoned.into_inner(),
-        };
+    // 连点提交时第二个行动在此被拒绝；凭据持有到本回合的后续处理结束
+    let _turn_ticket = engine.read().await.begin_turn().map_err(|e| e.to_string())?;
+    let (turn, relationship_lines, departed_npcs) = {
+        let mut engine = engine.write().await;
+        // 世界推进：后台尚未处理完的 NPC 事件在本回合开始前补齐
+        engine.drain_npc_inbox().map_err(|e| e.to_string())?;
+        emit_npc_events(&app, engine.take_npc_notices());
+        let npc_digest = engine.take_npc_digest();
         let game_state = engine.get_current_state().map_err(|e| e.to_string())?;
         let plot_state = engine.get_plot_state().map_err(|e| e.to_string())?;
-        (game_state, plot_state)
+        let relationship_lines = engine.relationship_prompt_lines(&game_state.player.id);
+        (
+            Turn::new(action, game_state, plot_state)
+                .with_npc_digest(npc_digest)
+                .with_sequence(engine.turn_sequence()),
+            relationship_lines,
+            engine.departed_npc_names(),
+        )
     };
 
-    let plot_engine = PlotEngine::new();
-    let context = Context {
-        location: game_state.player.location.clone(),
-        time_of_day: "day".to_string(),
-        weather: None,
+    let pipeline = TurnPipeline::for_state(&turn.game_state, &turn.plot_state.settings)
+        .map_err(|e| map_error("剧本数值公式无效", e))?
+        .with_relationship_lines(relationship_lines)
+        .with_departed_npcs(departed_npcs);
+    // 回合只在最后一步提交，取消时引擎状态保持不变
+    let plot_text = begin_generation(&app, request_id, "execute_player_action")
+        .run(pipeline.run(turn, engine.inner()))
+        .await?;
+    conclude_if_ended(&app, engine.inner()).await;
+
+    let (autosave, achievements) = {
+        let mut engine = engine.write().await;
+        // 成就簿与章节检查点写入失败不影响本回合结果
+        let _ = engine.check_achievements();
+        let _ = engine.record_chapter_checkpoint();
+        emit_npc_events(&app, engine.take_npc_notices());
+        (engine.autosave_job_if_due(), engine.take_unlocked_achievements())
     };
+    for achievement in achievements {
+        let _ = app.emit(ACHIEVEMENT_UNLOCKED_EVENT, achievement);
+    }
+    // 自动存档失败不影响本回合结果，进度同样通过 save-progress 事件推送
+    if let Ok(Some(job)) = autosave {
+        spawn_save_job(job, app.clone());
+    }
+    spawn_npc_inbox_drain(app);
+    Ok(plot_text)
+}
 
-    let mut action_result = plot_engine
-        .process_player_action(
-            &action,
-            &game_state.player.stats,
-            &plot_state.current_scene.available_options,
-            &context,
-        )
-        .map_err(|e| e.to_string())?;
+/// 演算行动的判定与属性变化供玩家提交前查看，不改动游戏状态
+#[tauri::command]
+pub async fn preview_player_action(
+    action: PlayerAction,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<ActionPreview, String> {
+    let turn = {
+        let engine = engine.read().await;
+        let game_state = engine.get_current_state().map_err(|e| e.to_string())?;
+        let plot_state = engine.get_plot_state().map_err(|e| e.to_string())?;
+        Turn::new(action, game_state, plot_state)
+    };
 
-    if let Some(selected_option_id) = action.selected_option_id {
-        if let Some(selected_option) = plot_state.current_scene.available_options.get(selected_option_id)
-        {
-            match &selected_option.action {
-                Action::Cultivate => {
-                    let old_power = game_state.player.stats.combat_power;
-                    let gain = ((old_power as f32 * 0.03).round() as u64).max(1);
-                    let new_power = old_power.saturating_add(gain);
-                    game_state.player.stats.combat_power = new_power;
-                    action_result.stat_changes.push(StatChange {
-                        stat_name: "combat_power".to_string(),
-                        old_value: old_power.to_string(),
-                        new_value: new_power.to_string(),
-                    });
-                    action_result.description = format!(
-                        "{} 战力提升了 {}。",
-                        action_result.description, gain
-                    );
-                }
-                Action::Breakthrough => {
-                    if action_result.success
-                        && game_state.player.stats.cultivation_realm.sub_level < 3
-                    {
-                        let old_sub = game_state.player.stats.cultivation_realm.sub_level;
-                        game_state.player.stats.cultivation_realm.sub_level += 1;
-                        game_state.player.stats.cultivation_realm.power_multiplier *= 1.2;
-                        game_state.player.stats.update_combat_power();
-                        action_result.stat_changes.push(StatChange {
-                            stat_name: "realm_sub_level".to_string(),
-                            old_value: old_sub.to_string(),
-                            new_value: game_state
-                                .player
-                                .stats
-                                .cultivation_realm
-                                .sub_level
-                                .to_string(),
-                        });
-                    }
-                }
-                Action::Rest | Action::Custom { .. } | Action::Combat { .. } => {}
-            }
-        }
+    let pipeline = TurnPipeline::for_state(&turn.game_state, &turn.plot_state.settings)
+        .map_err(|e| map_error("剧本数值公式无效", e))?;
+    Ok(pipeline.preview(&turn))
+}
+
+/// 玩家自拟一个行动：按自由输入的规则校验并解析后，追加到当前场景的选项中
+#[tauri::command]
+pub async fn add_custom_option(
+    description: String,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<PlayerOption, String> {
+    let (game_state, plot_state, sequence) = {
+        let engine = engine.read().await;
+        let game_state = engine.get_current_state().map_err(|e| e.to_string())?;
+        let plot_state = engine.get_plot_state().map_err(|e| e.to_string())?;
+        (game_state, plot_state, engine.turn_sequence())
+    };
+
+    let pipeline = TurnPipeline::for_state(&game_state, &plot_state.settings)
+        .map_err(|e| map_error("剧本数值公式无效", e))?;
+    // 合理性判定可能请求 LLM，在锁外进行；期间选项已被替换时按过期处理
+    let option = pipeline.custom_option(&description, &game_state, &plot_state)?;
+
+    let mut engine = engine.write().await;
+    if engine.turn_sequence() != sequence {
+        return Err("校验期间剧情已推进或选项已更新，请重新添加".to_string());
     }
+    engine
+        .add_custom_option(option)
+        .map_err(|e| map_error("添加自拟选项失败", e))
+}
 
-    game_state.game_time.advance_days(1);
-    let timestamp = u64::from(game_state.game_time.total_days);
-
-    let plot_update = plot_engine
-        .advance_plot_async(&plot_state, &action_result)
-        .await;
-
-    let log_entry = if let Some(selected_option_id) = action.selected_option_id {
-        if let Some(selected_option) = plot_state.current_scene.available_options.get(selected_option_id) {
-            match &selected_option.action {
-                Action::Combat { .. } => Some((
-                    "combat",
-                    format!("Player engaged in combat: {}", selected_option.description),
-                    EventImportance::Important,
-                )),
-                Action::Breakthrough => Some((
-                    "breakthrough_attempt",
-                    format!("Player attempted breakthrough: {}", selected_option.description),
-                    EventImportance::Important,
-                )),
-                Action::Custom { .. } | Action::Cultivate | Action::Rest => Some((
-                    "player_action",
-                    selected_option.description.clone(),
-                    EventImportance::Normal,
-                )),
-            }
-        } else {
-            None
-        }
-    } else if matches!(action.action_type, crate::plot_engine::ActionType::FreeText) {
-        Some((
-            "player_free_text",
-            action.content.clone(),
-            EventImportance::Normal,
-        ))
-    } else {
-        None
+/// 闭关快进若干日，不经剧情续写；`narrate` 为 true 且已配置 LLM 时每个游戏月写一段闭关见闻
+#[tauri::command]
+pub async fn advance_time_idle(
+    days: u32,
+    narrate: Option<bool>,
+    request_id: Option<String>,
+    app: AppHandle,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<IdleReport, String> {
+    // 与玩家回合共用闸门，闭关期间不能提交行动
+    let _turn_ticket = engine.read().await.begin_turn().map_err(|e| e.to_string())?;
+    let (report, player_name) = {
+        let mut engine = engine.write().await;
+        let report = engine
+            .advance_time_idle(days)
+            .map_err(|e| map_error("闭关失败", e))?;
+        let game_state = engine.get_current_state().map_err(|e| e.to_string())?;
+        (report, game_state.player.name)
     };
 
-    plot_state.last_action_result = Some(action_result);
-    plot_state.append_segment(plot_update.plot_text.clone());
+    let report = match shared_llm_service().filter(|_| narrate.unwrap_or(false)) {
+        Some(llm_service) => {
+            let progression = IdleProgression::new().with_llm_service(llm_service);
+            // 叙述被取消时结算照旧，剧情中只记一句总结
+            begin_generation(&app, request_id, "advance_time_idle")
+                .run(async { Ok(progression.narrate(report.clone(), &player_name).await) })
+                .await
+                .unwrap_or(report)
+        }
+        None => report,
+    };
+    engine
+        .write()
+        .await
+        .record_idle_narration(&report)
+        .map_err(|e| map_error("写入闭关叙述失败", e))?;
+    conclude_if_ended(&app, engine.inner()).await;
+    spawn_npc_inbox_drain(app);
+    Ok(report)
+}
 
-    if let Some(title) = plot_update.chapter_title.clone() {
-        if !title.trim().is_empty() {
-            plot_state.current_chapter.title = title.trim().to_string();
-            plot_state.current_scene.name = plot_state.current_chapter.title.clone();
+/// 回合结果返回后在后台处理本回合入队的 NPC 事件，不计入玩家等待时间
+fn spawn_npc_inbox_drain(app: AppHandle) {
+    tauri::async_runtime::spawn_blocking(move || {
+        let engine = app.state::<RwLock<GameEngine>>();
+        let mut engine = engine.blocking_write();
+        let _ = engine.drain_npc_inbox();
+        emit_npc_events(&app, engine.take_npc_notices());
+        let jobs = engine.memory_consolidation_jobs();
+        let triage_batch = engine.event_triage_batch();
+        drop(engine);
+        if !jobs.is_empty() {
+            tauri::async_runtime::spawn(consolidate_npc_memories(app.clone(), jobs));
         }
-    }
+        if !triage_batch.is_empty() {
+            tauri::async_runtime::spawn(triage_events(app.clone(), triage_batch));
+        }
+    });
+}
 
-    if plot_update.is_waiting_for_input {
-        plot_state.current_chapter.interaction_count = plot_state
-            .current_chapter
-            .interaction_count
-            .saturating_add(1);
+/// 冲着玩家来的 NPC 主动事件（插手、质问、下战帖）推送给前端
+fn emit_npc_events(app: &AppHandle, notices: Vec<NpcEventNotice>) {
+    for notice in notices {
+        let _ = app.emit(NPC_EVENT, notice);
     }
+}
 
-    if plot_update.chapter_end {
-        plot_state.finalize_chapter(plot_update.chapter_title, plot_update.chapter_summary);
-    }
+/// 大事件后多名 NPC 同时需要整合记忆，分批合并调用 LLM，结果再写回引擎
+async fn consolidate_npc_memories(app: AppHandle, jobs: Vec<MemoryJob>) {
+    let consolidator = match shared_llm_service() {
+        Some(llm_service) => MemoryConsolidator::new().with_llm_service(llm_service),
+        None => MemoryConsolidator::new(),
+    };
+    let report = consolidator.consolidate(&jobs).await;
 
-    plot_state.last_generation_diagnostics = plot_update.generation_diagnostics.clone();
+    let engine = app.state::<RwLock<GameEngine>>();
+    let mut engine = engine.write().await;
+    engine.apply_memory_consolidation(&jobs, &report);
+}
 
-    // 用最新段落更新场景描述，避免选项生成长期绑定旧描述导致“选项不变”。
-    if !plot_update.plot_text.trim().is_empty() {
-        plot_state.current_scene.description = plot_update.plot_text.trim().to_string();
-    }
+/// 近期普通事件攒够一批后交给判定模型复核，关乎主线的提升为重要
+async fn triage_events(app: AppHandle, batch: Vec<GameEvent>) {
+    let triage = match shared_llm_service() {
+        Some(llm_service) => EventTriage::new().with_llm_service(llm_service),
+        None => EventTriage::new(),
+    };
+    let report = triage.review(&batch).await;
 
-    let previous_options = plot_state.current_scene.available_options.clone();
+    let engine = app.state::<RwLock<GameEngine>>();
+    let mut engine = engine.write().await;
+    engine.apply_event_triage(&batch, &report);
+}
 
-    let option_source: String = if plot_update.is_waiting_for_input {
-        if !plot_update.available_options.is_empty() {
-            plot_state.current_scene.available_options = plot_update.available_options;
-            "llm_structured".to_string()
-        } else {
-            let llm_regenerated = plot_engine.generate_player_options_with_llm(
-                &plot_state.current_scene,
-                &game_state.player.stats,
-            );
-            let (mut regenerated_options, mut source) = if let Some(options) = llm_regenerated {
-                (options, "llm_regenerated".to_string())
-            } else {
-                (
-                    plot_engine
-                        .generate_player_options(&plot_state.current_scene, &game_state.player.stats),
-                    "rule_fallback".to_string(),
-                )
-            };
+#[tauri::command]
+pub async fn get_game_state(engine: State<'_, RwLock<GameEngine>>) -> Result<GameState, String> {
+    let engine = engine.read().await;
+    engine.get_current_state().map_err(|e| e.to_string())
+}
 
-            if regenerated_options.is_empty() {
-                regenerated_options = previous_options;
-                source = "previous_reused".to_string();
-            }
+/// 获取最近一次剧情生成失败的原因与建议操作
+#[tauri::command]
+pub async fn get_last_failure(
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<Option<GenerationFailure>, String> {
+    let engine = engine.read().await;
+    engine.get_last_failure().map_err(|e| e.to_string())
+}
 
-            // 通过时间推进对兜底选项做轻量轮转，确保连续交互时选项呈现有变化。
-            if !regenerated_options.is_empty() {
-                let rotation =
-                    (game_state.game_time.total_days as usize) % regenerated_options.len();
-                regenerated_options.rotate_left(rotation);
-                for (idx, option) in regenerated_options.iter_mut().enumerate() {
-                    option.id = idx;
-                }
-            }
-            plot_state.current_scene.available_options = regenerated_options;
-            source
-        }
-    } else {
-        plot_state.current_scene.available_options.clear();
-        "not_waiting_for_input".to_string()
+/// 获取核心模型的 JSON Schema，供前端生成 TypeScript 类型
+#[tauri::command]
+pub async fn get_state_schema() -> Result<StateSchemas, String> {
+    Ok(state_schemas())
+}
+
+/// 获取世界快报，未指定期数时返回最新一期；已配置 LLM 时首次读取会润色正文
+#[tauri::command]
+pub async fn get_world_bulletin(
+    issue: Option<u32>,
+    request_id: Option<String>,
+    app: AppHandle,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<Option<WorldBulletin>, String> {
+    let bulletin = {
+        let engine = engine.write().await;
+        engine.get_world_bulletin(issue).map_err(|e| e.to_string())?
+    };
+    let Some(bulletin) = bulletin else {
+        return Ok(None);
+    };
+    if bulletin.source != BulletinSource::Template {
+        return Ok(Some(bulletin));
+    }
+    let Some(llm_service) = shared_llm_service() else {
+        return Ok(Some(bulletin));
     };
 
-    plot_state.last_option_generation_source = Some(option_source.clone());
-    match &mut plot_state.last_generation_diagnostics {
-        Some(diag) => {
-            diag.push_str(&format!("；选项来源：{}", option_source));
-        }
-        None => {
-            plot_state.last_generation_diagnostics = Some(format!("选项来源：{}", option_source));
-        }
+    let desk = BulletinDesk::new().with_llm_service(llm_service);
+    let polished = begin_generation(&app, request_id, "get_world_bulletin")
+        .run(async { Ok(desk.polish(bulletin).await) })
+        .await?;
+    if polished.source == BulletinSource::Llm {
+        let mut engine = engine.write().await;
+        engine
+            .store_polished_bulletin(polished.clone())
+            .map_err(|e| e.to_string())?;
     }
+    Ok(Some(polished))
+}
+
+/// 获取指定版本之后的状态增量，减少移动端 IPC 负载
+#[tauri::command]
+pub async fn get_state_since(
+    version: u64,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<StateDelta, String> {
+    let engine = engine.read().await;
+    engine.get_state_since(version).map_err(|e| e.to_string())
+}
 
-    let mut engine = match engine.lock() {
-        Ok(guard) => guard,
-        Err(poisoned) => poisoned.into_inner(),
+#[tauri::command]
+pub async fn save_game(
+    slot_id: u32,
+    app: AppHandle,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<u64, String> {
+    validate_slot_id(slot_id).map_err(|e| map_error("保存存档失败", e))?;
+    let job = {
+        let mut engine = engine.write().await;
+        engine.prepare_save_job(slot_id).map_err(|e| e.to_string())?
     };
+    let ticket = job.ticket;
+    spawn_save_job(job, app);
+    Ok(ticket)
+}
 
-    if let Some((event_type, message, importance)) = log_entry {
-        engine.log_event(timestamp, event_type, message, importance);
-    }
+/// 序列化与写盘在阻塞线程中进行，进度通过事件推送，也可用票据查询
+fn spawn_save_job(job: SaveJob, app: AppHandle) {
+    tauri::async_runtime::spawn_blocking(move || {
+        let _ = job.run(|progress| {
+            let _ = app.emit(SAVE_PROGRESS_EVENT, progress.clone());
+        });
+    });
+}
+
+#[tauri::command]
+pub async fn get_save_progress(
+    ticket: u64,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<SaveProgress, String> {
+    let engine = engine.read().await;
+    engine.get_save_progress(ticket).map_err(|e| map_error("查询存档进度失败", e))
+}
+
+#[tauri::command]
+pub async fn load_game(
+    slot_id: u32,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<GameState, String> {
+    validate_slot_id(slot_id).map_err(|e| map_error("加载存档失败", e))?;
+    load_slot(slot_id, engine).await
+}
+
+/// 从自动存档槽位读档
+#[tauri::command]
+pub async fn load_autosave(
+    slot_id: u32,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<GameState, String> {
+    validate_autosave_slot_id(slot_id).map_err(|e| map_error("加载自动存档失败", e))?;
+    load_slot(slot_id, engine).await
+}
 
-    let _npc_reactions = engine
-        .process_npc_reactions_for_events(&plot_update.triggered_events)
+async fn load_slot(slot_id: u32, engine: State<'_, RwLock<GameEngine>>) -> Result<GameState, String> {
+    let save_load_system = {
+        let engine = engine.read().await;
+        engine.save_load_system()
+    };
+
+    let save_data = tauri::async_runtime::spawn_blocking(move || save_load_system.load_game(slot_id))
+        .await
+        .map_err(|e| e.to_string())?
         .map_err(|e| e.to_string())?;
 
+    // 旧存档没有剧情状态时先生成开篇，再拿写锁应用存档
+    let opening = match save_data.plot_state {
+        Some(_) => None,
+        None => {
+            let draft = engine.read().await.opening_draft(&save_data.game_state);
+            Some(draft.generate().await)
+        }
+    };
+
+    let mut engine = engine.write().await;
     engine
-        .update_current_state(game_state)
-        .map_err(|e| e.to_string())?;
+        .apply_loaded_save(slot_id, save_data, opening)
+        .map_err(|e| e.to_string())
+}
+
+/// 从当前时间线的章节边界分叉出新时间线；`chapter` 为分叉时已完成的章数，缺省取最近一章
+#[tauri::command]
+pub async fn create_branch(
+    name: String,
+    chapter: Option<u32>,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<BranchInfo, String> {
+    let mut engine = engine.write().await;
     engine
-        .update_plot_state(plot_state)
-        .map_err(|e| e.to_string())?;
+        .create_branch(&name, chapter)
+        .map_err(|e| map_error("创建时间线失败", e))
+}
+
+#[tauri::command]
+pub async fn list_branches(engine: State<'_, RwLock<GameEngine>>) -> Result<BranchIndex, String> {
+    let engine = engine.read().await;
+    Ok(engine.list_branches())
+}
+
+/// 保存当前时间线的进度并切换到另一条时间线
+#[tauri::command]
+pub async fn switch_branch(
+    branch_id: String,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<GameState, String> {
+    let mut engine = engine.write().await;
+    engine
+        .switch_branch(&branch_id)
+        .map_err(|e| map_error("切换时间线失败", e))
+}
+
+#[tauri::command]
+pub async fn list_save_slots(engine: State<'_, RwLock<GameEngine>>) -> Result<Vec<SaveInfo>, String> {
+    let engine = engine.read().await;
+    engine.list_saves().map_err(|e| e.to_string())
+}
+
+/// 列出自动存档，最新的在前
+#[tauri::command]
+pub async fn list_autosaves(engine: State<'_, RwLock<GameEngine>>) -> Result<Vec<SaveInfo>, String> {
+    let engine = engine.read().await;
+    engine.list_autosaves().map_err(|e| e.to_string())
+}
+
+/// 读取自动存档配置；传入 `settings` 时校验并保存后返回新配置
+#[tauri::command]
+pub async fn autosave_settings(
+    settings: Option<AutosaveSettings>,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<AutosaveSettings, String> {
+    let engine = engine.write().await;
+    match settings {
+        Some(settings) => engine
+            .set_autosave_settings(settings)
+            .map_err(|e| map_error("更新自动存档配置失败", e)),
+        None => Ok(engine.autosave_settings()),
+    }
+}
+
+/// 玩家回合的进行状态：`idle` 或 `generating`
+#[tauri::command]
+pub async fn get_turn_status(engine: State<'_, RwLock<GameEngine>>) -> Result<TurnStatus, String> {
+    let engine = engine.read().await;
+    Ok(engine.turn_status())
+}
+
+/// 跨对局保留的应用偏好（新对局的剧情设置、难度与按子系统的模型路由）
+#[tauri::command]
+pub async fn get_app_settings(engine: State<'_, RwLock<GameEngine>>) -> Result<AppSettings, String> {
+    let engine = engine.read().await;
+    Ok(engine.app_settings())
+}
 
-    Ok(plot_update.plot_text)
+/// 校验并保存应用偏好；模型路由立即生效，剧情设置与难度从下一局开始生效
+#[tauri::command]
+pub async fn update_app_settings(
+    settings: AppSettings,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<AppSettings, String> {
+    let settings = {
+        let engine = engine.write().await;
+        engine
+            .set_app_settings(settings)
+            .map_err(|e| map_error("更新应用设置失败", e))?
+    };
+    set_llm_model_routes(settings.llm_routing.clone());
+    refresh_engine_llm_service(engine.inner()).await;
+    Ok(settings)
 }
 
 #[tauri::command]
-pub async fn get_game_state(engine: State<'_, Mutex<GameEngine>>) -> Result<GameState, String> {
-    let engine = match engine.lock() {
-        Ok(guard) => guard,
-        Err(poisoned) => poisoned.into_inner(),
+pub async fn export_saves_manifest(
+    output_path: String,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<SaveManifest, String> {
+    validate_output_path(&output_path, &["json"]).map_err(|e| map_error("导出存档清单失败", e))?;
+    let save_load_system = {
+        let engine = engine.read().await;
+        engine.save_load_system()
     };
-    engine.get_current_state().map_err(|e| e.to_string())
+
+    tauri::async_runtime::spawn_blocking(move || save_load_system.export_manifest(&output_path))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub async fn save_game(slot_id: u32, engine: State<'_, Mutex<GameEngine>>) -> Result<(), String> {
-    validate_slot_id(slot_id).map_err(|e| map_error("保存存档失败", e))?;
-    let engine = match engine.lock() {
-        Ok(guard) => guard,
-        Err(poisoned) => poisoned.into_inner(),
+pub async fn verify_saves_against_manifest(
+    manifest_path: String,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<ManifestVerification, String> {
+    validate_file_path(&manifest_path, &["json"]).map_err(|e| map_error("校验存档清单失败", e))?;
+    let save_load_system = {
+        let engine = engine.read().await;
+        engine.save_load_system()
     };
-    engine.save_game(slot_id).map_err(|e| e.to_string())
+
+    tauri::async_runtime::spawn_blocking(move || {
+        save_load_system.verify_against_manifest(&manifest_path)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())
 }
 
+/// 将存档槽打包为单个可移植文件，返回存档包的校验和
 #[tauri::command]
-pub async fn load_game(
+pub async fn export_save(
     slot_id: u32,
-    engine: State<'_, Mutex<GameEngine>>,
-) -> Result<GameState, String> {
-    validate_slot_id(slot_id).map_err(|e| map_error("加载存档失败", e))?;
-    let mut engine = match engine.lock() {
-        Ok(guard) => guard,
-        Err(poisoned) => poisoned.into_inner(),
+    output_path: String,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<String, String> {
+    validate_output_path(&output_path, &["json"]).map_err(|e| map_error("导出存档失败", e))?;
+    let save_load_system = {
+        let engine = engine.read().await;
+        engine.save_load_system()
     };
-    engine.load_game(slot_id).map_err(|e| e.to_string())
+
+    tauri::async_runtime::spawn_blocking(move || save_load_system.export_save(slot_id, &output_path))
+        .await
+        .map_err(|e| e.to_string())?
+        .map(|bundle| bundle.checksum)
+        .map_err(|e| map_error("导出存档失败", e))
 }
 
+/// 校验并导入存档包，写入第一个空闲的手动存档槽并返回槽位
 #[tauri::command]
-pub async fn list_save_slots(engine: State<'_, Mutex<GameEngine>>) -> Result<Vec<SaveInfo>, String> {
-    let engine = match engine.lock() {
-        Ok(guard) => guard,
-        Err(poisoned) => poisoned.into_inner(),
+pub async fn import_save(
+    save_path: String,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<u32, String> {
+    validate_file_path(&save_path, &["json"]).map_err(|e| map_error("导入存档失败", e))?;
+    let save_load_system = {
+        let engine = engine.read().await;
+        engine.save_load_system()
     };
-    engine.list_saves().map_err(|e| e.to_string())
+
+    tauri::async_runtime::spawn_blocking(move || save_load_system.import_save(&save_path))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| map_error("导入存档失败", e))
 }
 
 #[tauri::command]
 pub async fn load_script(
     script_path: String,
-    _engine: State<'_, Mutex<GameEngine>>,
+    script_language: Option<ScriptLanguage>,
+    _engine: State<'_, RwLock<GameEngine>>,
 ) -> Result<Script, String> {
     use crate::script_manager::ScriptManager;
 
     validate_file_path(&script_path, &["json"]).map_err(|e| map_error("加载剧本失败", e))?;
     let manager = ScriptManager::new();
-    manager
-        .load_custom_script(&script_path)
-        .map_err(|e| map_error("加载剧本失败", e))
+    let loaded = match script_language {
+        Some(language) => manager.load_custom_script_in(&script_path, language),
+        None => manager.load_custom_script(&script_path),
+    };
+    loaded.map_err(|e| map_error("加载剧本失败", e))
+}
+
+/// 从 HTTPS 地址下载剧本，校验后缓存到存档目录并记录来源
+#[tauri::command]
+pub async fn load_script_from_url(
+    url: String,
+    script_language: Option<ScriptLanguage>,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<ImportedScript, String> {
+    use crate::script_manager::ScriptManager;
+
+    let url = ScriptImporter::parse_url(&url).map_err(|e| map_error("下载剧本失败", e))?;
+    let importer = {
+        let engine = engine.read().await;
+        ScriptImporter::new(engine.save_load_system().save_directory())
+    };
+
+    let bytes = importer
+        .download(&url)
+        .await
+        .map_err(|e| map_error("下载剧本失败", e))?;
+    let mut imported = tauri::async_runtime::spawn_blocking(move || {
+        importer.import(&url, &bytes, &ScriptManager::new())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| map_error("加载剧本失败", e))?;
+    if let Some(language) = script_language {
+        imported.script = imported.script.localized(language);
+    }
+    Ok(imported)
 }
 
+/// 在剧本编辑器中新建空白草稿
 #[tauri::command]
-pub async fn generate_random_script() -> Result<Script, String> {
+pub async fn create_blank_script(
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<ScriptDraftReport, String> {
+    let mut engine = engine.write().await;
+    Ok(engine.create_blank_script())
+}
+
+/// 替换草稿的一个部分，返回更新后的草稿与按部分归类的校验问题
+#[tauri::command]
+pub async fn update_script_section(
+    section: ScriptSection,
+    json: serde_json::Value,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<ScriptDraftReport, String> {
+    let mut engine = engine.write().await;
+    engine
+        .update_script_section(section, json)
+        .map_err(|e| map_error("更新剧本草稿失败", e))
+}
+
+#[tauri::command]
+pub async fn validate_script_draft(
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<ScriptDraftReport, String> {
+    let engine = engine.read().await;
+    engine
+        .validate_script_draft()
+        .map_err(|e| map_error("校验剧本草稿失败", e))
+}
+
+/// 开发模式：监视本局剧本文件，改动后热更新兼容的部分并推送 script-reloaded 事件
+#[tauri::command]
+pub async fn start_script_hot_reload(
+    script_path: String,
+    app: AppHandle,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<(), String> {
+    validate_file_path(&script_path, &["json"]).map_err(|e| map_error("开启剧本热更新失败", e))?;
+    let started = {
+        let mut engine = engine.write().await;
+        engine
+            .watch_script(&script_path)
+            .map_err(|e| map_error("开启剧本热更新失败", e))?
+    };
+    if started {
+        spawn_script_watch(app);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn stop_script_hot_reload(engine: State<'_, RwLock<GameEngine>>) -> Result<(), String> {
+    let mut engine = engine.write().await;
+    engine.unwatch_script();
+    Ok(())
+}
+
+/// 手动触发一次热更新检查，返回本次的应用与拒绝情况；文件未改动时返回 null
+#[tauri::command]
+pub async fn poll_script_reload(
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<Option<ScriptReloadReport>, String> {
+    let mut engine = engine.write().await;
+    engine
+        .poll_script_reload()
+        .map_err(|e| map_error("剧本热更新失败", e))
+}
+
+fn spawn_script_watch(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(std::time::Duration::from_millis(SCRIPT_WATCH_INTERVAL_MS)).await;
+            let report = {
+                let engine = app.state::<RwLock<GameEngine>>();
+                let mut engine = engine.write().await;
+                if !engine.is_watching_script() {
+                    break;
+                }
+                engine.poll_script_reload()
+            };
+            if let Ok(Some(report)) = report {
+                let _ = app.emit(SCRIPT_RELOAD_EVENT, report);
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn generate_random_script(
+    request_id: Option<String>,
+    app: AppHandle,
+) -> Result<Script, String> {
     use crate::script_manager::ScriptManager;
 
     let manager = ScriptManager::new();
-    manager
-        .generate_random_script()
+    begin_generation(&app, request_id, "generate_random_script")
+        .run(async {
+            manager
+                .generate_random_script()
+                .await
+                .map_err(|e| map_error("随机剧本生成失败", e))
+        })
+        .await
+}
+
+/// 开局向导：可选的世界主题
+#[tauri::command]
+pub async fn suggest_world_themes() -> Result<Vec<WorldTheme>, String> {
+    Ok(crate::script_wizard::suggest_world_themes())
+}
+
+/// 开局向导：按主题给出几个候选的角色名
+#[tauri::command]
+pub async fn suggest_player_names(theme: String) -> Result<Vec<String>, String> {
+    let mut rng = GameRng::new(GameEngine::random_seed());
+    crate::script_wizard::suggest_player_names(&theme, &mut rng)
+        .map_err(|e| map_error("生成候选名失败", e))
+}
+
+/// 开局向导：按问答结果生成剧本，模型不可用或结果不合规时改用本地模板
+#[tauri::command]
+pub async fn generate_script_from_answers(
+    answers: WizardAnswers,
+    request_id: Option<String>,
+    app: AppHandle,
+) -> Result<Script, String> {
+    use crate::script_manager::ScriptManager;
+
+    let manager = ScriptManager::new();
+    begin_generation(&app, request_id, "generate_script_from_answers")
+        .run(async {
+            manager
+                .generate_script_from_answers(&answers)
+                .await
+                .map_err(|e| map_error("按向导生成剧本失败", e))
+        })
         .await
-        .map_err(|e| map_error("随机剧本生成失败", e))
 }
 
 #[tauri::command]
@@ -504,6 +1586,7 @@ pub async fn parse_novel_characters(novel_path: String) -> Result<Vec<String>, S
 pub async fn load_existing_novel(
     novel_path: String,
     selected_character: String,
+    start_chapter: Option<u32>,
 ) -> Result<Script, String> {
     use crate::script_manager::ScriptManager;
 
@@ -516,89 +1599,105 @@ pub async fn load_existing_novel(
     }
     let manager = ScriptManager::new();
     manager
-        .load_existing_novel(&novel_path, &selected_character)
+        .load_existing_novel(&novel_path, &selected_character, start_chapter)
         .map_err(|e| map_error("导入现有小说失败", e))
 }
 
 #[tauri::command]
 pub async fn get_player_options(
-    engine: State<'_, Mutex<GameEngine>>,
+    engine: State<'_, RwLock<GameEngine>>,
 ) -> Result<Vec<PlayerOption>, String> {
-    let engine = match engine.lock() {
-        Ok(guard) => guard,
-        Err(poisoned) => poisoned.into_inner(),
-    };
+    let engine = engine.read().await;
     let plot_state = engine.get_plot_state().map_err(|e| e.to_string())?;
     Ok(plot_state.current_scene.available_options)
 }
 
 #[tauri::command]
 pub async fn initialize_plot(
-    engine: State<'_, Mutex<GameEngine>>,
+    engine: State<'_, RwLock<GameEngine>>,
 ) -> Result<PlotState, String> {
-    let (player_name, realm_name, spiritual_root, location) = {
-        let engine = match engine.lock() {
-            Ok(guard) => guard,
-            Err(poisoned) => poisoned.into_inner(),
-        };
+    let draft = {
+        let engine = engine.read().await;
         let state = engine.get_current_state().map_err(|e| e.to_string())?;
-        (
-            state.player.name,
-            state.player.stats.cultivation_realm.name,
-            format!("{:?}", state.player.stats.spiritual_root.element),
-            state.player.location,
-        )
+        engine.opening_draft(&state)
     };
+    let opening = draft.generate().await;
 
-    let plot_engine = PlotEngine::new();
-    let opening = plot_engine
-        .generate_opening_plot_async(&player_name, &realm_name, &spiritual_root, &location)
-        .await;
-
-    let opening_options = if opening.options.is_empty() {
-        None
-    } else {
-        Some(
-            opening
-                .options
-                .iter()
-                .enumerate()
-                .map(|(idx, text)| PlayerOption {
-                    id: idx,
-                    description: text.clone(),
-                    requirements: vec![],
-                    action: Action::Custom {
-                        description: text.clone(),
-                    },
-                })
-                .collect(),
-        )
-    };
-
-    let mut engine = match engine.lock() {
-        Ok(guard) => guard,
-        Err(poisoned) => poisoned.into_inner(),
-    };
+    let mut engine = engine.write().await;
     engine
-        .initialize_plot_with_opening(opening.text, opening_options)
+        .initialize_plot_with_opening(opening.text.clone(), opening.player_options())
         .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 pub async fn get_plot_state(
-    engine: State<'_, Mutex<GameEngine>>,
+    engine: State<'_, RwLock<GameEngine>>,
 ) -> Result<PlotState, String> {
-    let engine = match engine.lock() {
-        Ok(guard) => guard,
-        Err(poisoned) => poisoned.into_inner(),
-    };
+    let engine = engine.read().await;
     engine.get_plot_state().map_err(|e| e.to_string())
 }
 
+/// 获取已完结章节，低内存模式下按需从磁盘读回正文
+#[tauri::command]
+pub async fn get_chapter(
+    index: u32,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<ChapterState, String> {
+    let engine = engine.read().await;
+    engine
+        .get_chapter(index)
+        .map_err(|e| map_error("读取章节失败", e))
+}
+
+/// 开启或关闭低内存模式，返回切换后的内存占用报告
+#[tauri::command]
+pub async fn set_low_memory_mode(
+    enabled: bool,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<MemoryUsageReport, String> {
+    let mut engine = engine.write().await;
+    engine
+        .set_low_memory_mode(enabled)
+        .map_err(|e| map_error("切换低内存模式失败", e))?;
+    Ok(engine.get_memory_usage_report())
+}
+
+/// 获取章节、事件与 NPC 记忆的内存占用估算
+#[tauri::command]
+pub async fn get_memory_usage_report(
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<MemoryUsageReport, String> {
+    let engine = engine.read().await;
+    Ok(engine.get_memory_usage_report())
+}
+
+/// 获取存档与冷存储缓存的磁盘占用
+#[tauri::command]
+pub async fn get_storage_report(
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<StorageReport, String> {
+    let engine = engine.read().await;
+    engine
+        .storage_report()
+        .map_err(|e| map_error("读取磁盘占用失败", e))
+}
+
+/// 按清理策略删除过期缓存、压缩已完结的存档，未传策略时使用默认策略
+#[tauri::command]
+pub async fn run_storage_cleanup(
+    policy: Option<StorageCleanupPolicy>,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<StorageCleanupResult, String> {
+    let engine = engine.write().await;
+    engine
+        .run_storage_cleanup(&policy.unwrap_or_default())
+        .map_err(|e| map_error("清理磁盘失败", e))
+}
+
 #[tauri::command]
 pub async fn update_plot_settings(
     settings: PlotSettings,
-    engine: State<'_, Mutex<GameEngine>>,
+    engine: State<'_, RwLock<GameEngine>>,
 ) -> Result<PlotState, String> {
     if settings.min_interactions_per_chapter == 0
         || settings.max_interactions_per_chapter == 0
@@ -630,10 +1729,7 @@ pub async fn update_plot_settings(
             AppError::new(crate::app_error::AppErrorKind::InvalidInput, "小说风格不能为空"),
         ));
     }
-    let engine = match engine.lock() {
-        Ok(guard) => guard,
-        Err(poisoned) => poisoned.into_inner(),
-    };
+    let mut engine = engine.write().await;
     engine
         .update_plot_settings(settings)
         .map_err(|e| e.to_string())
@@ -642,38 +1738,115 @@ pub async fn update_plot_settings(
 #[tauri::command]
 pub async fn generate_novel(
     title: String,
-    engine: State<'_, Mutex<GameEngine>>,
+    polish: Option<bool>,
+    request_id: Option<String>,
+    app: AppHandle,
+    engine: State<'_, RwLock<GameEngine>>,
 ) -> Result<Novel, String> {
     validate_non_empty(&title, "小说标题").map_err(|e| map_error("生成小说失败", e))?;
-    let events = {
-        let engine = engine.lock().map_err(|e| e.to_string())?;
-        let state = engine.get_current_state().map_err(|e| e.to_string())?;
-        state.event_history
+    let (events, polish_sources) = {
+        let engine = engine.read().await;
+        engine.get_current_state().map_err(|e| e.to_string())?;
+        let events = engine.full_event_history().map_err(|e| e.to_string())?;
+        let polish_sources = if polish.unwrap_or(false) {
+            Some(
+                engine
+                    .novel_polish_sources()
+                    .map_err(|e| map_error("读取剧情片段失败", e))?,
+            )
+        } else {
+            None
+        };
+        (events, polish_sources)
     };
-    generate_novel_from_events(&title, &events).await
+    begin_generation(&app, request_id, "generate_novel")
+        .run(generate_novel_from_events(&title, &events, polish_sources))
+        .await
 }
 
 #[tauri::command]
-pub async fn export_novel(novel: Novel, output_path: String) -> Result<(), String> {
-    validate_output_path(&output_path, &["txt"]).map_err(|e| map_error("导出小说失败", e))?;
-    export_novel_to_path(&novel, &output_path)
+pub async fn export_novel(
+    novel: Novel,
+    output_path: String,
+    include_illustrations: Option<bool>,
+) -> Result<(), String> {
+    validate_output_path(&output_path, &["txt", "md"]).map_err(|e| map_error("导出小说失败", e))?;
+    export_novel_to_path(&novel, &output_path, include_illustrations)
 }
 
-async fn generate_novel_from_events(title: &str, events: &[crate::event_log::GameEvent]) -> Result<Novel, String> {
-    let generator = NovelGenerator::new();
+/// 玩家属性面板：境界进度、战力构成、寿元与生效状态，以及最近的属性变化
+#[tauri::command]
+pub async fn get_character_sheet(
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<CharacterSheet, String> {
+    let engine = engine.read().await;
+    engine
+        .character_sheet()
+        .map_err(|e| map_error("读取属性面板失败", e))
+}
+
+/// 各处灵气与修炼、休息成效的估算，供玩家决定是否动身
+#[tauri::command]
+pub async fn survey_location(
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<Vec<LocationSurvey>, String> {
+    let engine = engine.read().await;
+    engine
+        .survey_locations()
+        .map_err(|e| map_error("勘察地点失败", e))
+}
+
+#[tauri::command]
+pub async fn export_character_card(
+    output_path: String,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<CharacterCard, String> {
+    validate_output_path(&output_path, &["json"]).map_err(|e| map_error("导出角色名片失败", e))?;
+    let engine = engine.read().await;
+    engine
+        .export_character_card(&output_path)
+        .map_err(|e| map_error("导出角色名片失败", e))
+}
+
+#[tauri::command]
+pub async fn export_transcript(
+    output_path: String,
+    include_provenance: Option<bool>,
+    engine: State<'_, RwLock<GameEngine>>,
+) -> Result<(), String> {
+    validate_output_path(&output_path, &["txt"]).map_err(|e| map_error("导出对局记录失败", e))?;
+    let engine = engine.read().await;
+    engine
+        .export_transcript(&output_path, include_provenance.unwrap_or(false))
+        .map_err(|e| map_error("导出对局记录失败", e))
+}
+
+async fn generate_novel_from_events(
+    title: &str,
+    events: &[crate::event_log::GameEvent],
+    polish_sources: Option<(String, Vec<String>)>,
+) -> Result<Novel, String> {
+    let generator = match polish_sources {
+        Some((style, plot_segments)) => NovelGenerator::new().with_polish(style, plot_segments),
+        None => NovelGenerator::new(),
+    };
     generator.generate_novel(title.to_string(), events).await
 }
 
-fn export_novel_to_path(novel: &Novel, output_path: &str) -> Result<(), String> {
+fn export_novel_to_path(
+    novel: &Novel,
+    output_path: &str,
+    include_illustrations: Option<bool>,
+) -> Result<(), String> {
     let generator = NovelGenerator::new();
-    generator.export_to_file(novel, output_path)
+    generator.export_to_file_with(novel, output_path, include_illustrations)
 }
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::event_log::{EventImportance, GameEvent};
     use crate::models::{CultivationRealm, Element, Grade, SpiritualRoot};
-    use crate::script::{InitialState, Location, ScriptType, WorldSetting};
+    use crate::script::{Climate, InitialState, Location, LocationKind, ScriptType, WorldSetting};
     use tempfile::tempdir;
 
     fn create_test_script() -> Script {
@@ -687,14 +1860,17 @@ mod tests {
             name: "Azure Cloud Sect".to_string(),
             description: "A peaceful cultivation sect".to_string(),
             spiritual_energy: 1.0,
+            kind: LocationKind::Wilderness,
+            climate: Climate::Temperate,
         }];
 
         let initial_state = InitialState {
             player_name: "Test Player".to_string(),
             player_spiritual_root: SpiritualRoot {
                 element: Element::Fire,
-                grade: Grade::Heavenly,
+                grade: Grade::heavenly(),
                 affinity: 0.9,
+                tier_multiplier: None,
             },
             starting_location: "sect".to_string(),
             starting_age: 16,
@@ -744,6 +1920,15 @@ mod tests {
         assert!(validate_slot_id(99).is_ok());
         assert!(validate_slot_id(0).is_err());
         assert!(validate_slot_id(120).is_err());
+        assert!(validate_slot_id(AUTOSAVE_FIRST_SLOT).is_err());
+    }
+
+    #[test]
+    fn test_validate_autosave_slot_id_bounds() {
+        assert!(validate_autosave_slot_id(100).is_ok());
+        assert!(validate_autosave_slot_id(104).is_ok());
+        assert!(validate_autosave_slot_id(99).is_err());
+        assert!(validate_autosave_slot_id(105).is_err());
     }
 
     #[test]
@@ -773,6 +1958,10 @@ mod tests {
             model: "test".to_string(),
             max_tokens: 128,
             temperature: 0.7,
+            provider_kind: ProviderKind::OpenAI,
+            timeout_secs: None,
+            max_retries: None,
+            retry_backoff_ms: None,
         };
         assert!(validate_llm_config_input(&input).is_err());
     }
@@ -785,6 +1974,39 @@ mod tests {
             model: "test".to_string(),
             max_tokens: 128,
             temperature: 0.7,
+            provider_kind: ProviderKind::OpenAI,
+            timeout_secs: None,
+            max_retries: None,
+            retry_backoff_ms: None,
+        };
+        assert!(validate_llm_config_input(&input).is_ok());
+
+        let slow = LLMConfigInput {
+            timeout_secs: Some(0),
+            ..input.clone()
+        };
+        assert!(validate_llm_config_input(&slow).is_err());
+        let retries = LLMConfigInput {
+            max_retries: Some(4),
+            retry_backoff_ms: Some(500),
+            ..input
+        };
+        assert!(validate_llm_config_input(&retries).is_ok());
+        assert_eq!(retries.retry_policy().max_retries, 4);
+    }
+
+    #[test]
+    fn test_validate_llm_config_allows_remote_ollama_without_key() {
+        let input = LLMConfigInput {
+            endpoint: "https://ollama.example.com/api/chat".to_string(),
+            api_key: "".to_string(),
+            model: "qwen".to_string(),
+            max_tokens: 128,
+            temperature: 0.7,
+            provider_kind: ProviderKind::Ollama,
+            timeout_secs: None,
+            max_retries: None,
+            retry_backoff_ms: None,
         };
         assert!(validate_llm_config_input(&input).is_ok());
     }
@@ -809,6 +2031,8 @@ mod tests {
                 event_type: std::sync::Arc::from("cultivation"),
                 description: std::sync::Arc::from("Player cultivated"),
                 importance: EventImportance::Normal,
+                repeat_count: 1,
+                last_timestamp: None,
             },
             GameEvent {
                 id: 2,
@@ -816,10 +2040,12 @@ mod tests {
                 event_type: std::sync::Arc::from("combat"),
                 description: std::sync::Arc::from("Player won duel"),
                 importance: EventImportance::Important,
+                repeat_count: 1,
+                last_timestamp: None,
             },
         ];
 
-        let novel = generate_novel_from_events("Test Novel", &events).await.unwrap();
+        let novel = generate_novel_from_events("Test Novel", &events, None).await.unwrap();
         assert_eq!(novel.title, "Test Novel");
         assert_eq!(novel.total_events, 2);
         assert!(!novel.chapters.is_empty());
@@ -834,13 +2060,15 @@ mod tests {
                 title: "Start".to_string(),
                 content: "A new journey starts.".to_string(),
                 source_event_ids: vec![1],
+                illustrations: Vec::new(),
+                polished: false,
             }],
             total_events: 1,
         };
 
         let dir = tempfile::tempdir().unwrap();
         let output = dir.path().join("novel_out.txt");
-        let result = export_novel_to_path(&novel, output.to_str().unwrap());
+        let result = export_novel_to_path(&novel, output.to_str().unwrap(), None);
         assert!(result.is_ok());
         assert!(output.exists());
     }
@@ -0,0 +1,235 @@
+use crate::prompt_builder::PromptTemplate;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet, VecDeque};
+
+const OUTCOME_WINDOW: usize = 10;
+const MIN_OUTCOMES_FOR_COOLING: usize = 3;
+const FAILURE_RATE_THRESHOLD: f32 = 0.3;
+const TEMPERATURE_STEP: f32 = 0.1;
+const RECENT_SEGMENTS_FOR_REPETITION: usize = 3;
+/// 与近期段落的字符二元组重合度超过该值时视为重复
+pub const REPETITION_THRESHOLD: f32 = 0.6;
+
+/// 自适应温度的上下限
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct TemperatureBounds {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl Default for TemperatureBounds {
+    fn default() -> Self {
+        Self { min: 0.3, max: 1.1 }
+    }
+}
+
+impl TemperatureBounds {
+    fn clamp(&self, value: f32) -> f32 {
+        let (low, high) = if self.min <= self.max {
+            (self.min, self.max)
+        } else {
+            (self.max, self.min)
+        };
+        value.clamp(low, high)
+    }
+}
+
+/// 单次生成对模板的反馈
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum TuningSignal {
+    Accepted,
+    Regenerated,
+    ValidationFailed,
+    Repetitive,
+}
+
+/// 单个提示模板的生成统计与温度偏移
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct TemplateTuning {
+    pub adjustment: f32,
+    pub requests: u32,
+    pub regenerations: u32,
+    pub validation_failures: u32,
+    pub repetitions: u32,
+    #[serde(default)]
+    recent: VecDeque<TuningSignal>,
+}
+
+impl TemplateTuning {
+    /// 最近窗口内需要重生成或校验失败的比例
+    pub fn failure_rate(&self) -> f32 {
+        if self.recent.is_empty() {
+            return 0.0;
+        }
+        let failures = self
+            .recent
+            .iter()
+            .filter(|s| matches!(s, TuningSignal::Regenerated | TuningSignal::ValidationFailed))
+            .count();
+        failures as f32 / self.recent.len() as f32
+    }
+
+    fn is_unstable(&self) -> bool {
+        self.recent.len() >= MIN_OUTCOMES_FOR_COOLING && self.failure_rate() >= FAILURE_RATE_THRESHOLD
+    }
+}
+
+/// 按模板跟踪重生成与校验失败率，自动调整采样温度，随剧情状态保存
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct TemperatureTuner {
+    pub templates: BTreeMap<String, TemplateTuning>,
+}
+
+impl TemperatureTuner {
+    /// 模板当前应使用的温度
+    pub fn temperature(&self, template: &PromptTemplate, base: f32, bounds: &TemperatureBounds) -> f32 {
+        let adjustment = self
+            .templates
+            .get(template.name())
+            .map(|t| t.adjustment)
+            .unwrap_or(0.0);
+        bounds.clamp(base + adjustment)
+    }
+
+    /// 模板近期频繁产出无效 JSON 时返回 true，调用方应收紧输出约束
+    pub fn should_tighten(&self, template: &PromptTemplate) -> bool {
+        self.templates
+            .get(template.name())
+            .is_some_and(TemplateTuning::is_unstable)
+    }
+
+    /// 记录一次生成结果，温度发生变化时返回供诊断展示的说明
+    pub fn record(
+        &mut self,
+        template: &PromptTemplate,
+        base: f32,
+        signal: TuningSignal,
+        bounds: &TemperatureBounds,
+    ) -> Option<String> {
+        let before = self.temperature(template, base, bounds);
+        let tuning = self.templates.entry(template.name().to_string()).or_default();
+
+        tuning.requests = tuning.requests.saturating_add(1);
+        match signal {
+            TuningSignal::Accepted => {}
+            TuningSignal::Regenerated => tuning.regenerations = tuning.regenerations.saturating_add(1),
+            TuningSignal::ValidationFailed => {
+                tuning.validation_failures = tuning.validation_failures.saturating_add(1)
+            }
+            TuningSignal::Repetitive => tuning.repetitions = tuning.repetitions.saturating_add(1),
+        }
+        tuning.recent.push_back(signal);
+        while tuning.recent.len() > OUTCOME_WINDOW {
+            tuning.recent.pop_front();
+        }
+
+        let reason = if signal == TuningSignal::Repetitive {
+            tuning.adjustment += TEMPERATURE_STEP;
+            "内容重复".to_string()
+        } else if signal != TuningSignal::Accepted && tuning.is_unstable() {
+            tuning.adjustment -= TEMPERATURE_STEP;
+            format!("近期失败率 {:.0}%", tuning.failure_rate() * 100.0)
+        } else {
+            return None;
+        };
+        // 偏移量不超过上下限允许的范围，避免越界后长时间无法回调。
+        tuning.adjustment = bounds.clamp(base + tuning.adjustment) - base;
+
+        let after = self.temperature(template, base, bounds);
+        if (after - before).abs() < f32::EPSILON {
+            return None;
+        }
+        Some(format!(
+            "温度调整：{} {:.2} → {:.2}（{}）",
+            template.name(),
+            before,
+            after,
+            reason
+        ))
+    }
+}
+
+/// 新段落与近期段落的最大字符二元组重合度（0-1）
+pub fn repetition_score(candidate: &str, recent: &[String]) -> f32 {
+    let candidate = bigrams(candidate);
+    if candidate.is_empty() {
+        return 0.0;
+    }
+    recent
+        .iter()
+        .rev()
+        .take(RECENT_SEGMENTS_FOR_REPETITION)
+        .map(|previous| {
+            let previous = bigrams(previous);
+            let shared = candidate.intersection(&previous).count();
+            shared as f32 / candidate.len() as f32
+        })
+        .fold(0.0, f32::max)
+}
+
+fn bigrams(text: &str) -> HashSet<(char, char)> {
+    let chars = text
+        .chars()
+        .filter(|c| !c.is_whitespace() && !c.is_ascii_punctuation())
+        .collect::<Vec<char>>();
+    chars.windows(2).map(|pair| (pair[0], pair[1])).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: f32 = 0.7;
+
+    #[test]
+    fn test_repeated_validation_failures_cool_template_within_bounds() {
+        let mut tuner = TemperatureTuner::default();
+        let bounds = TemperatureBounds { min: 0.5, max: 1.0 };
+        let template = PromptTemplate::PlotGeneration;
+
+        assert!(tuner
+            .record(&template, BASE, TuningSignal::ValidationFailed, &bounds)
+            .is_none());
+        assert!(!tuner.should_tighten(&template));
+        tuner.record(&template, BASE, TuningSignal::Accepted, &bounds);
+        let note = tuner
+            .record(&template, BASE, TuningSignal::Regenerated, &bounds)
+            .unwrap();
+        assert!(note.contains("PlotGeneration 0.70 → 0.60"));
+        assert!(tuner.should_tighten(&template));
+
+        for _ in 0..5 {
+            tuner.record(&template, BASE, TuningSignal::ValidationFailed, &bounds);
+        }
+        assert!((tuner.temperature(&template, BASE, &bounds) - 0.5).abs() < 1e-6);
+        assert!((tuner.temperature(&PromptTemplate::NpcDecision, BASE, &bounds) - BASE).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_repetitive_content_warms_template() {
+        let mut tuner = TemperatureTuner::default();
+        let bounds = TemperatureBounds::default();
+        let template = PromptTemplate::PlotGeneration;
+
+        let note = tuner
+            .record(&template, BASE, TuningSignal::Repetitive, &bounds)
+            .unwrap();
+        assert!(note.contains("内容重复"));
+        assert!((tuner.temperature(&template, BASE, &bounds) - 0.8).abs() < 1e-6);
+        assert_eq!(tuner.templates["PlotGeneration"].repetitions, 1);
+    }
+
+    #[test]
+    fn test_repetition_score_detects_near_duplicates() {
+        let recent = vec![
+            "山门外云雾缭绕，林逸负手而立，望向远处的青云峰。".to_string(),
+        ];
+        let repeated = "山门外云雾缭绕，林逸负手而立，远望青云峰。";
+        let fresh = "夜半时分，丹房里药香四溢，炉火映红了少年的面庞。";
+
+        assert!(repetition_score(repeated, &recent) >= REPETITION_THRESHOLD);
+        assert!(repetition_score(fresh, &recent) < REPETITION_THRESHOLD);
+        assert_eq!(repetition_score("", &recent), 0.0);
+    }
+}
@@ -0,0 +1,226 @@
+use crate::save_load::SaveData;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 开局时所在、尚未分叉的主线时间线
+pub const MAIN_BRANCH_ID: &str = "main";
+const BRANCH_INDEX_FILE: &str = "index.json";
+const BRANCH_HEAD_FILE: &str = "head.json";
+const MAX_BRANCH_NAME_CHARS: usize = 30;
+
+/// 一条时间线：从父时间线的某个章节边界分叉，此前的检查点与父时间线相同
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BranchInfo {
+    pub id: String,
+    pub name: String,
+    /// 分叉来源的时间线，主线为空
+    pub parent_id: Option<String>,
+    /// 分叉时已完成的章数，主线为 0
+    pub forked_at_chapter: u32,
+    pub created_at: u64,
+    /// 已记录检查点的章节边界（已完成的章数），升序
+    pub checkpoints: Vec<u32>,
+}
+
+/// 本局的时间线列表与当前所在的时间线
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BranchIndex {
+    pub active_id: String,
+    pub branches: Vec<BranchInfo>,
+}
+
+impl Default for BranchIndex {
+    fn default() -> Self {
+        Self {
+            active_id: MAIN_BRANCH_ID.to_string(),
+            branches: vec![BranchInfo {
+                id: MAIN_BRANCH_ID.to_string(),
+                name: "主线".to_string(),
+                parent_id: None,
+                forked_at_chapter: 0,
+                created_at: now_secs(),
+                checkpoints: Vec::new(),
+            }],
+        }
+    }
+}
+
+impl BranchIndex {
+    pub fn get(&self, id: &str) -> Option<&BranchInfo> {
+        self.branches.iter().find(|branch| branch.id == id)
+    }
+
+    fn get_mut(&mut self, id: &str) -> Option<&mut BranchInfo> {
+        self.branches.iter_mut().find(|branch| branch.id == id)
+    }
+
+    pub fn active(&self) -> Option<&BranchInfo> {
+        self.get(&self.active_id)
+    }
+}
+
+/// 时间线存档目录：每条时间线一个子目录，存放章节边界检查点与离开时的进度
+#[derive(Debug, Clone)]
+pub struct BranchStore {
+    directory: PathBuf,
+}
+
+impl BranchStore {
+    pub fn new(directory: PathBuf) -> Self {
+        Self { directory }
+    }
+
+    /// 读取时间线列表，文件缺失或损坏时只有主线
+    pub fn index(&self) -> BranchIndex {
+        fs::read_to_string(self.directory.join(BRANCH_INDEX_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save_index(&self, index: &BranchIndex) -> Result<()> {
+        fs::create_dir_all(&self.directory)?;
+        fs::write(
+            self.directory.join(BRANCH_INDEX_FILE),
+            serde_json::to_string_pretty(index)?,
+        )?;
+        Ok(())
+    }
+
+    /// 开始新的一局时清空上一局的所有时间线
+    pub fn reset(&self) -> Result<()> {
+        if self.directory.exists() {
+            fs::remove_dir_all(&self.directory)?;
+        }
+        Ok(())
+    }
+
+    fn branch_dir(&self, id: &str) -> PathBuf {
+        self.directory.join(id)
+    }
+
+    fn checkpoint_path(&self, id: &str, chapter: u32) -> PathBuf {
+        self.branch_dir(id).join(format!("chapter_{}.json", chapter))
+    }
+
+    pub fn has_checkpoint(&self, chapter: u32) -> bool {
+        self.index()
+            .active()
+            .is_some_and(|branch| branch.checkpoints.contains(&chapter))
+    }
+
+    /// 记录当前时间线在章节边界的检查点；读档回到更早的章节后，之后的旧检查点作废
+    pub fn record_checkpoint(&self, chapter: u32, save: &SaveData) -> Result<()> {
+        let mut index = self.index();
+        let active_id = index.active_id.clone();
+        write_save(&self.checkpoint_path(&active_id, chapter), save)?;
+        let branch = index
+            .get_mut(&active_id)
+            .ok_or_else(|| anyhow!("未找到时间线 {}", active_id))?;
+        branch.checkpoints.retain(|recorded| *recorded < chapter);
+        branch.checkpoints.push(chapter);
+        self.save_index(&index)
+    }
+
+    /// 从当前时间线的章节边界分叉出新时间线，未指定章节时取最近的检查点；不切换当前时间线
+    pub fn create_branch(&self, name: &str, chapter: Option<u32>) -> Result<BranchInfo> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(anyhow!("时间线名称不能为空"));
+        }
+        if name.chars().count() > MAX_BRANCH_NAME_CHARS {
+            return Err(anyhow!("时间线名称不能超过 {} 个字", MAX_BRANCH_NAME_CHARS));
+        }
+
+        let mut index = self.index();
+        if index.branches.iter().any(|branch| branch.name == name) {
+            return Err(anyhow!("已有名为「{}」的时间线", name));
+        }
+        let parent = index
+            .active()
+            .cloned()
+            .ok_or_else(|| anyhow!("未找到时间线 {}", index.active_id))?;
+        let chapter = match chapter {
+            Some(chapter) if parent.checkpoints.contains(&chapter) => chapter,
+            Some(chapter) => return Err(anyhow!("当前时间线没有第 {} 章结束时的检查点", chapter)),
+            None => *parent
+                .checkpoints
+                .last()
+                .ok_or_else(|| anyhow!("当前时间线尚未完成任何章节，无法分叉"))?,
+        };
+
+        let id = (1..)
+            .map(|n| format!("branch_{}", n))
+            .find(|id| index.get(id).is_none())
+            .unwrap_or_default();
+        // 分叉点之前的检查点复制给新时间线，父时间线之后被删改也不影响
+        let checkpoints = parent
+            .checkpoints
+            .iter()
+            .copied()
+            .filter(|recorded| *recorded <= chapter)
+            .collect::<Vec<u32>>();
+        fs::create_dir_all(self.branch_dir(&id))?;
+        for recorded in &checkpoints {
+            fs::copy(
+                self.checkpoint_path(&parent.id, *recorded),
+                self.checkpoint_path(&id, *recorded),
+            )?;
+        }
+        fs::copy(
+            self.checkpoint_path(&parent.id, chapter),
+            self.branch_dir(&id).join(BRANCH_HEAD_FILE),
+        )?;
+
+        let branch = BranchInfo {
+            id,
+            name: name.to_string(),
+            parent_id: Some(parent.id),
+            forked_at_chapter: chapter,
+            created_at: now_secs(),
+            checkpoints,
+        };
+        index.branches.push(branch.clone());
+        self.save_index(&index)?;
+        Ok(branch)
+    }
+
+    /// 离开当前时间线前保存其进度
+    pub fn save_head(&self, save: &SaveData) -> Result<()> {
+        let index = self.index();
+        write_save(&self.branch_dir(&index.active_id).join(BRANCH_HEAD_FILE), save)?;
+        self.save_index(&index)
+    }
+
+    /// 切换当前时间线并返回它上次离开时的进度
+    pub fn switch_to(&self, id: &str) -> Result<SaveData> {
+        let mut index = self.index();
+        if index.get(id).is_none() {
+            return Err(anyhow!("未找到时间线 {}", id));
+        }
+        let json = fs::read_to_string(self.branch_dir(id).join(BRANCH_HEAD_FILE))
+            .map_err(|e| anyhow!("时间线 {} 没有可读取的进度: {}", id, e))?;
+        let save = serde_json::from_str::<SaveData>(&json)?;
+        index.active_id = id.to_string();
+        self.save_index(&index)?;
+        Ok(save)
+    }
+}
+
+fn write_save(path: &Path, save: &SaveData) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, serde_json::to_string(save)?)?;
+    Ok(())
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
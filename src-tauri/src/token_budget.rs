@@ -0,0 +1,240 @@
+use crate::llm_provider::ProviderKind;
+use crate::llm_service::LLMConfig;
+use crate::prompt_builder::estimate_token_count;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// 未知模型按最保守的上下文窗口估计
+const DEFAULT_CONTEXT_WINDOW: u32 = 8192;
+/// Ollama 默认只为模型分配 4K 上下文，与模型本身的上限无关
+const OLLAMA_CONTEXT_WINDOW: u32 = 4096;
+/// 续写段落至少保留的提示与输出 token，可选调用不得占用这部分
+const NARRATION_RESERVE: u32 = 1200;
+
+/// 回合内一次 LLM 调用的用途
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TurnCall {
+    BehaviorValidation,
+    IntentParsing,
+    AlignmentJudgement,
+    ArcPlanning,
+    ChapterOutline,
+    Narration,
+    OptionGeneration,
+    ContentRegeneration,
+    ConsistencyRegeneration,
+}
+
+impl TurnCall {
+    pub fn label(self) -> &'static str {
+        match self {
+            TurnCall::BehaviorValidation => "行为合理性校验",
+            TurnCall::IntentParsing => "意图解析",
+            TurnCall::AlignmentJudgement => "善恶判定",
+            TurnCall::ArcPlanning => "故事弧规划",
+            TurnCall::ChapterOutline => "章节大纲规划",
+            TurnCall::Narration => "剧情续写",
+            TurnCall::OptionGeneration => "选项生成",
+            TurnCall::ContentRegeneration => "内容过滤重写",
+            TurnCall::ConsistencyRegeneration => "一致性重写",
+        }
+    }
+
+    /// 可选调用在预算不足时跳过，由本地规则或模板兜底；续写无论如何都会发出
+    pub fn is_optional(self) -> bool {
+        self != TurnCall::Narration
+    }
+
+    /// 降级后仍可用的最少输出 token
+    fn min_output_tokens(self) -> u32 {
+        match self {
+            TurnCall::BehaviorValidation => 48,
+            TurnCall::IntentParsing => 64,
+            TurnCall::AlignmentJudgement => 32,
+            TurnCall::ArcPlanning => 300,
+            TurnCall::ChapterOutline => 200,
+            TurnCall::Narration => 240,
+            TurnCall::OptionGeneration => 120,
+            TurnCall::ContentRegeneration => 240,
+            TurnCall::ConsistencyRegeneration => 240,
+        }
+    }
+}
+
+/// 按服务商与模型名估计上下文窗口
+pub fn context_window(config: &LLMConfig) -> u32 {
+    if config.provider_kind == ProviderKind::Ollama {
+        return OLLAMA_CONTEXT_WINDOW;
+    }
+    let model = config.model.to_lowercase();
+    if model.contains("gemini") {
+        1_000_000
+    } else if model.contains("claude") {
+        200_000
+    } else if ["gpt-4o", "gpt-4.1", "gpt-4-turbo", "o1", "o3", "o4"]
+        .iter()
+        .any(|family| model.contains(family))
+    {
+        128_000
+    } else if model.contains("deepseek") {
+        64_000
+    } else if model.contains("qwen") || model.contains("glm") {
+        32_768
+    } else if model.contains("gpt-3.5") {
+        16_385
+    } else {
+        DEFAULT_CONTEXT_WINDOW
+    }
+}
+
+#[derive(Debug, Default)]
+struct BudgetLedger {
+    total: u32,
+    spent: u32,
+    narration_allocated: bool,
+    skipped: Vec<TurnCall>,
+    downgraded: Vec<TurnCall>,
+}
+
+/// 一个回合内所有 LLM 调用共用的 token 预算，克隆后共享同一账本
+#[derive(Debug, Clone)]
+pub struct TokenBudget {
+    ledger: Arc<Mutex<BudgetLedger>>,
+}
+
+impl TokenBudget {
+    pub fn new(total: u32) -> Self {
+        Self {
+            ledger: Arc::new(Mutex::new(BudgetLedger {
+                total,
+                ..BudgetLedger::default()
+            })),
+        }
+    }
+
+    /// 以模型的上下文窗口作为一个回合的总预算
+    pub fn for_config(config: &LLMConfig) -> Self {
+        Self::new(context_window(config))
+    }
+
+    fn ledger(&self) -> MutexGuard<'_, BudgetLedger> {
+        match self.ledger.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    pub fn remaining(&self) -> u32 {
+        let ledger = self.ledger();
+        ledger.total.saturating_sub(ledger.spent)
+    }
+
+    /// 为一次调用分配输出 token：预算充足时给足 `max_tokens`，不足时压低到剩余额度；
+    /// 可选调用连最少输出都放不下时返回 `None`，调用方应跳过该调用
+    pub fn allocate(&self, call: TurnCall, prompt: &str, max_tokens: u32) -> Option<u32> {
+        let prompt_tokens = estimate_token_count(prompt);
+        let mut ledger = self.ledger();
+        let reserve = if call.is_optional() && !ledger.narration_allocated {
+            NARRATION_RESERVE
+        } else {
+            0
+        };
+        let available = ledger
+            .total
+            .saturating_sub(ledger.spent)
+            .saturating_sub(reserve)
+            .saturating_sub(prompt_tokens);
+        let floor = call.min_output_tokens().min(max_tokens);
+        let granted = if available >= max_tokens {
+            max_tokens
+        } else if available >= floor {
+            ledger.downgraded.push(call);
+            available
+        } else if call.is_optional() {
+            ledger.skipped.push(call);
+            return None;
+        } else {
+            ledger.downgraded.push(call);
+            floor
+        };
+        if call == TurnCall::Narration {
+            ledger.narration_allocated = true;
+        }
+        ledger.spent = ledger
+            .spent
+            .saturating_add(prompt_tokens)
+            .saturating_add(granted);
+        Some(granted)
+    }
+
+    /// 本回合因预算跳过或压缩的调用说明，写入生成诊断；预算宽裕时为空
+    pub fn summary(&self) -> Option<String> {
+        let ledger = self.ledger();
+        if ledger.skipped.is_empty() && ledger.downgraded.is_empty() {
+            return None;
+        }
+        let labels = |calls: &[TurnCall]| {
+            calls
+                .iter()
+                .map(|call| call.label())
+                .collect::<Vec<&str>>()
+                .join("、")
+        };
+        let mut parts = Vec::new();
+        if !ledger.skipped.is_empty() {
+            parts.push(format!("跳过{}", labels(&ledger.skipped)));
+        }
+        if !ledger.downgraded.is_empty() {
+            parts.push(format!("压缩{}的输出", labels(&ledger.downgraded)));
+        }
+        Some(format!(
+            "token 预算紧张（上下文 {}，已用约 {}）：{}",
+            ledger.total,
+            ledger.spent,
+            parts.join("，")
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tight_budget_skips_optional_calls_but_keeps_narration() {
+        let budget = TokenBudget::new(1500);
+        let prompt = "token ".repeat(300);
+        assert_eq!(budget.allocate(TurnCall::BehaviorValidation, &prompt, 96), None);
+        assert_eq!(budget.remaining(), 1500);
+
+        assert_eq!(budget.allocate(TurnCall::Narration, &prompt, 700), Some(700));
+        assert_eq!(budget.remaining(), 500);
+        assert_eq!(budget.allocate(TurnCall::OptionGeneration, &prompt, 220), Some(200));
+        assert_eq!(budget.allocate(TurnCall::ContentRegeneration, &prompt, 700), None);
+        assert_eq!(budget.allocate(TurnCall::Narration, &prompt, 700), Some(240));
+
+        let summary = budget.summary().unwrap();
+        assert!(summary.contains("跳过行为合理性校验、内容过滤重写"));
+        assert!(summary.contains("压缩选项生成、剧情续写的输出"));
+
+        let roomy = TokenBudget::new(128_000);
+        assert_eq!(roomy.allocate(TurnCall::BehaviorValidation, &prompt, 96), Some(96));
+        assert_eq!(roomy.summary(), None);
+    }
+
+    #[test]
+    fn test_context_window_follows_provider_and_model() {
+        let config = |provider_kind, model: &str| LLMConfig {
+            endpoint: "https://example.com".to_string(),
+            api_key: "key".to_string(),
+            model: model.to_string(),
+            max_tokens: 1024,
+            temperature: 0.7,
+            provider_kind,
+            retry_policy: Default::default(),
+        };
+        assert_eq!(context_window(&config(ProviderKind::OpenAI, "gpt-4o-mini")), 128_000);
+        assert_eq!(context_window(&config(ProviderKind::OpenAI, "deepseek-ai/DeepSeek-V3.2")), 64_000);
+        assert_eq!(context_window(&config(ProviderKind::Ollama, "qwen2.5")), OLLAMA_CONTEXT_WINDOW);
+        assert_eq!(context_window(&config(ProviderKind::OpenAI, "mystery")), DEFAULT_CONTEXT_WINDOW);
+    }
+}
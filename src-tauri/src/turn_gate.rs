@@ -0,0 +1,78 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// 回合状态：空闲，或有一个回合正在生成
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TurnStatus {
+    Idle,
+    Generating,
+}
+
+/// 保证同一时间只有一个玩家回合在生成，连点提交的第二个行动直接拒绝
+#[derive(Debug, Clone, Default)]
+pub struct TurnGate {
+    in_flight: Arc<AtomicBool>,
+}
+
+impl TurnGate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 开始一个回合；已有回合在生成时返回错误
+    pub fn begin(&self) -> Result<TurnTicket> {
+        if self
+            .in_flight
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            return Err(anyhow!("上一回合仍在生成中，请等待其完成后再行动"));
+        }
+        Ok(TurnTicket {
+            in_flight: Arc::clone(&self.in_flight),
+        })
+    }
+
+    pub fn status(&self) -> TurnStatus {
+        if self.in_flight.load(Ordering::SeqCst) {
+            TurnStatus::Generating
+        } else {
+            TurnStatus::Idle
+        }
+    }
+}
+
+/// 进行中的回合，结束（包括出错或被取消）时自动回到空闲
+#[derive(Debug)]
+pub struct TurnTicket {
+    in_flight: Arc<AtomicBool>,
+}
+
+impl Drop for TurnTicket {
+    fn drop(&mut self) {
+        self.in_flight.store(false, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_second_turn_is_rejected_until_first_finishes() {
+        let gate = TurnGate::new();
+        assert_eq!(gate.status(), TurnStatus::Idle);
+
+        let ticket = gate.begin().unwrap();
+        assert_eq!(gate.status(), TurnStatus::Generating);
+        assert!(gate.clone().begin().is_err());
+
+        drop(ticket);
+        assert_eq!(gate.status(), TurnStatus::Idle);
+        assert!(gate.begin().is_ok());
+        assert_eq!(serde_json::to_string(&TurnStatus::Generating).unwrap(), "\"generating\"");
+    }
+}
@@ -0,0 +1,2862 @@
+use crate::achievements::{BREAKTHROUGH_EVENT, CHAPTER_COMPLETE_EVENT};
+use crate::action_filters::{app_action_filters, ActionFilters};
+use crate::arc_planner::{replan_reason, ArcPlanner, ArcSource};
+use crate::chapter_outline::ChapterOutliner;
+use crate::content_filter::{app_content_filter, ContentFilterSettings};
+use crate::context_assembler::ContextAssembler;
+use crate::duel::{attach_duel_options, settle_declined, settle_duel, DuelOutcome, DuelResult};
+use crate::event_log::EventImportance;
+use crate::faction_war::{self, FactionChange};
+use crate::formula::FormulaError;
+use crate::game_engine::GameEngine;
+use crate::game_state::{GameState, GlobalEvent, Item};
+use crate::generation_diagnostics::GenerationDiagnostics;
+use crate::llm_runtime_config::shared_llm_service;
+use crate::loot::{table_for_enemy_tier, table_for_location, DropSource, DropTable};
+use crate::models::{CharacterStats, Lifespan, StatDelta};
+use crate::npc_alerts::NpcEventNotice;
+use crate::numerical_system::{
+    insight_from_events, Action, ActionResult, Context, NumericalSystem, StatChange,
+};
+use crate::plot_engine::{
+    inherit_option_uids, new_option_uid, selected_option_index, ActionType, PlayerAction,
+    PlayerOption, PlotEngine, PlotSettings, PlotState, PlotUpdate, Scene, SEGMENT_BASE_TEMPERATURE,
+};
+use crate::prompt_builder::PromptTemplate;
+use crate::provenance::{ValidatorVerdict, CONTENT_VALIDATOR, FACTS_VALIDATOR};
+use crate::response_validator::{NarrativeContext, ResponseValidator};
+use crate::side_story::{
+    attach_entry_options, is_exit_option, realm_for_option, table_for_secret_realm,
+};
+use crate::status_effects::{
+    StatusEffectKind, BREAKTHROUGH_DEVIATION_DAYS, BREAKTHROUGH_ENLIGHTENMENT_DAYS,
+};
+use crate::token_budget::{TokenBudget, TurnCall};
+use crate::world_clock::context_at;
+use crate::world_timeline::{event_line, fire_due_events, WORLD_EVENT};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+const EXPLORATION_KEYWORDS: &[&str] = &["探索", "搜寻", "寻找", "调查", "explore", "search"];
+/// 关闭永久死亡时，寿元耗尽后续命的年数
+const PERMADEATH_REPRIEVE_YEARS: u32 = 10;
+/// 寿元耗尽、角色坐化时写入事件日志的事件类型
+pub const LIFESPAN_EXHAUSTED_EVENT: &str = "lifespan_exhausted";
+/// 内容过滤重写一段正文预计的输出 token
+const CONTENT_REGENERATION_TOKENS: u32 = 700;
+
+/// 回合结束时写入事件日志的条目
+#[derive(Debug, Clone, PartialEq)]
+pub struct TurnLogEntry {
+    pub event_type: &'static str,
+    pub message: String,
+    pub importance: EventImportance,
+}
+
+/// 行动预览：在不改动任何状态的前提下演算一次行动，供玩家提交前查看后果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActionPreview {
+    /// 行动能否通过校验，未通过时原因列在 `warnings` 中
+    pub valid: bool,
+    /// 解析出的行动，无法解析时为空
+    pub action: Option<Action>,
+    /// 预估的判定结果与属性变化，不含战利品、决斗等随机结算
+    pub estimated_result: Option<ActionResult>,
+    pub warnings: Vec<String>,
+}
+
+/// 一个回合在流水线各阶段之间传递的数据
+#[derive(Debug, Clone)]
+pub struct Turn {
+    pub action: PlayerAction,
+    pub game_state: GameState,
+    pub plot_state: PlotState,
+    pub selected_option: Option<PlayerOption>,
+    pub action_result: Option<ActionResult>,
+    pub plot_update: Option<PlotUpdate>,
+    pub log_entry: Option<TurnLogEntry>,
+    pub option_source: Option<String>,
+    /// 本回合应战或过期的决斗结算
+    pub duel_outcomes: Vec<DuelOutcome>,
+    /// 上一回合剧情事件引发的 NPC 反应摘要，作为本回合续写的背景
+    pub npc_digest: Vec<String>,
+    /// 与剧情续写并行生成的选项，续写未附带选项时使用
+    pub prefetched_options: Option<Vec<PlayerOption>>,
+    /// 本回合到期发生的剧本大事
+    pub world_events: Vec<GlobalEvent>,
+    /// 本回合的势力消长与战事
+    pub faction_changes: Vec<FactionChange>,
+    /// 取快照时引擎的状态写入序号，提交时据此拒绝过期的回写
+    pub sequence: u64,
+    /// 本回合回应的 NPC 插叙，提交时据此分辨生成期间后台新挂上的插叙
+    pub answered_interruption: Option<NpcEventNotice>,
+}
+
+impl Turn {
+    pub fn new(action: PlayerAction, game_state: GameState, plot_state: PlotState) -> Self {
+        Self {
+            action,
+            game_state,
+            plot_state,
+            selected_option: None,
+            action_result: None,
+            plot_update: None,
+            log_entry: None,
+            option_source: None,
+            duel_outcomes: Vec::new(),
+            npc_digest: Vec::new(),
+            prefetched_options: None,
+            world_events: Vec::new(),
+            faction_changes: Vec::new(),
+            sequence: 0,
+            answered_interruption: None,
+        }
+    }
+
+    pub fn with_sequence(mut self, sequence: u64) -> Self {
+        self.sequence = sequence;
+        self
+    }
+
+    pub fn with_npc_digest(mut self, npc_digest: Vec<String>) -> Self {
+        self.npc_digest = npc_digest;
+        self
+    }
+
+    pub fn timestamp(&self) -> u64 {
+        u64::from(self.game_state.game_time.total_days)
+    }
+}
+
+/// 玩家回合处理流水线：validate → resolve → narrate → react → regenerate options → commit
+pub struct TurnPipeline {
+    plot_engine: PlotEngine,
+    arc_planner: ArcPlanner,
+    chapter_outliner: ChapterOutliner,
+    content_filter: ContentFilterSettings,
+    token_budget: Option<TokenBudget>,
+    /// 已身故的 NPC，续写中不应再登场
+    departed_npcs: Vec<String>,
+    /// 人物关系概况，由引擎在回合开始时从 NPC 关系网汇总
+    relationship_lines: Vec<String>,
+}
+
+impl TurnPipeline {
+    pub fn new(plot_engine: PlotEngine) -> Self {
+        Self {
+            plot_engine,
+            arc_planner: ArcPlanner::new(),
+            chapter_outliner: ChapterOutliner::new(),
+            content_filter: ContentFilterSettings::default(),
+            token_budget: None,
+            departed_npcs: Vec::new(),
+            relationship_lines: Vec::new(),
+        }
+    }
+
+    pub fn with_arc_planner(mut self, arc_planner: ArcPlanner) -> Self {
+        self.arc_planner = arc_planner;
+        self
+    }
+
+    pub fn with_chapter_outliner(mut self, chapter_outliner: ChapterOutliner) -> Self {
+        self.chapter_outliner = chapter_outliner;
+        self
+    }
+
+    pub fn with_content_filter(mut self, content_filter: ContentFilterSettings) -> Self {
+        self.content_filter = content_filter;
+        self
+    }
+
+    /// 本回合各次 LLM 调用共用的 token 预算，预算紧张时跳过可选调用
+    pub fn with_token_budget(mut self, token_budget: TokenBudget) -> Self {
+        self.plot_engine.set_token_budget(Some(token_budget.clone()));
+        self.token_budget = Some(token_budget);
+        self
+    }
+
+    /// 续写时参考的人物关系概况，由引擎在回合开始时从 NPC 关系网汇总
+    pub fn with_relationship_lines(mut self, relationship_lines: Vec<String>) -> Self {
+        self.relationship_lines = relationship_lines;
+        self
+    }
+
+    /// 续写一致性校验时视为已身故的 NPC
+    pub fn with_departed_npcs(mut self, departed_npcs: Vec<String>) -> Self {
+        self.departed_npcs = departed_npcs;
+        self
+    }
+
+    /// 带上本回合游戏状态上下文的剧情引擎，续写与选项生成共用
+    fn plot_engine_for(&self, turn: &Turn) -> PlotEngine {
+        let game_context = ContextAssembler::new()
+            .with_relationships(self.relationship_lines.clone())
+            .assemble(&turn.game_state, &turn.plot_state);
+        self.plot_engine.clone().with_game_context(game_context)
+    }
+
+    /// 按剧本数值配置、行动过滤配置、本局房规与剧情设置构建流水线
+    pub fn for_state(
+        game_state: &GameState,
+        plot_settings: &PlotSettings,
+    ) -> Result<Self, FormulaError> {
+        let numerical_system = NumericalSystem::with_config(&game_state.script.numerical_config)?
+            .with_house_rules(&game_state.house_rules)
+            .with_difficulty(&game_state.difficulty)
+            .with_techniques(&game_state.script.world_setting.techniques)
+            .with_alignment(game_state.karma.alignment())
+            .with_economy(&game_state.script.economy)
+            .with_realm_ladder(&game_state.script.world_setting)
+            .with_inventory(&game_state.player.inventory);
+        let action_filters =
+            ActionFilters::merged(&app_action_filters(), &game_state.script.action_filters);
+        let content_filter = app_content_filter();
+        let llm_service = shared_llm_service();
+        let mut plot_engine = PlotEngine::new()
+            .with_numerical_system(numerical_system)
+            .with_action_filters(action_filters)
+            .with_house_rules(game_state.house_rules)
+            .with_llm_judge_threshold(plot_settings.llm_judge_threshold)
+            .with_language(plot_settings.language)
+            .with_content_rules(content_filter.prompt_rules());
+        plot_engine.set_llm_service(llm_service.clone());
+        let pipeline = Self::new(plot_engine).with_content_filter(content_filter);
+        Ok(match llm_service {
+            Some(llm_service) => {
+                let token_budget = TokenBudget::for_config(&llm_service.api_config);
+                pipeline
+                    .with_arc_planner(
+                        ArcPlanner::new()
+                            .with_llm_service(llm_service.clone())
+                            .with_token_budget(token_budget.clone()),
+                    )
+                    .with_chapter_outliner(
+                        ChapterOutliner::new()
+                            .with_llm_service(llm_service)
+                            .with_token_budget(token_budget.clone()),
+                    )
+                    .with_token_budget(token_budget)
+            }
+            None => pipeline,
+        })
+    }
+
+    /// 依次执行所有阶段，只在提交阶段持有引擎写锁
+    pub async fn run(&self, mut turn: Turn, engine: &RwLock<GameEngine>) -> Result<String, String> {
+        self.validate(&mut turn)?;
+        self.resolve(&mut turn);
+        self.narrate(&mut turn).await;
+        self.react(&mut turn);
+        self.regenerate_options(&mut turn);
+
+        let mut engine = engine.write().await;
+        self.commit(turn, &mut engine)
+    }
+
+    /// 校验行动并计算数值判定结果
+    pub fn validate(&self, turn: &mut Turn) -> Result<(), String> {
+        if let Some(reason) = game_over_reason(&turn.game_state) {
+            return Err(reason);
+        }
+        if turn.plot_state.side_story.is_some()
+            && matches!(turn.action.action_type, ActionType::FreeText)
+        {
+            return Err("秘境之中只能从给定的选项中行动".to_string());
+        }
+        let context = action_context(&turn.game_state);
+
+        let action_result = self.plot_engine.process_player_action(
+            &turn.action,
+            &turn.game_state.player.stats,
+            &turn.plot_state.current_scene.available_options,
+            &context,
+        )?;
+
+        let available_options = &turn.plot_state.current_scene.available_options;
+        turn.selected_option = selected_option_index(&turn.action, available_options)?
+            .and_then(|idx| available_options.get(idx).cloned());
+        if let Some(option) = &turn.selected_option {
+            let cost = self
+                .plot_engine
+                .numerical_system()
+                .action_cost(&turn.game_state.player.stats, &option.action);
+            turn.game_state.resources.check_affordable(&cost)?;
+        }
+        turn.action_result = Some(action_result);
+        Ok(())
+    }
+
+    /// 演算行动而不改动回合数据：校验、解析行动并预估属性变化；
+    /// 与正式回合一致，只有选择选项才结算属性变化
+    /// 把玩家自拟的行动校验、解析为可追加到当前场景的选项
+    pub fn custom_option(
+        &self,
+        description: &str,
+        game_state: &GameState,
+        plot_state: &PlotState,
+    ) -> Result<PlayerOption, String> {
+        self.plot_engine.custom_option(
+            description,
+            &game_state.player.stats,
+            &plot_state.current_scene.available_options,
+            &action_context(game_state),
+        )
+    }
+
+    pub fn preview(&self, turn: &Turn) -> ActionPreview {
+        let available_options = &turn.plot_state.current_scene.available_options;
+        let mut warnings = Vec::new();
+        if let Some(reason) = game_over_reason(&turn.game_state) {
+            warnings.push(reason);
+        }
+        if let Err(error) = self
+            .plot_engine
+            .validate_player_action(&turn.action, available_options)
+        {
+            warnings.push(error);
+        }
+
+        let context = action_context(&turn.game_state);
+        let stats = &turn.game_state.player.stats;
+        let numerical_system = self.plot_engine.numerical_system();
+        // 解析失败的原因与校验相同，已记入警告
+        let action = self
+            .plot_engine
+            .interpret_player_action(&turn.action, stats, available_options, &context)
+            .ok()
+            .flatten();
+        let selected = matches!(turn.action.action_type, ActionType::SelectedOption);
+        let mut resources = turn.game_state.resources;
+        let cost_changes = match action.as_ref().filter(|_| selected) {
+            Some(action) => resources
+                .apply(&numerical_system.action_cost(stats, action))
+                .unwrap_or_else(|reason| {
+                    warnings.push(reason);
+                    Vec::new()
+                }),
+            None => Vec::new(),
+        };
+        let valid = warnings.is_empty();
+
+        let estimated_result = action.as_ref().map(|action| {
+            let mut result = numerical_system.calculate_action_result(stats, action, &context);
+            if selected {
+                if result.success {
+                    result.stat_changes.extend(cost_changes);
+                }
+                self.apply_action_effects(action, &mut stats.clone(), &context, &mut result);
+            }
+            result
+        });
+
+        if let Some(result) = estimated_result.as_ref().filter(|result| !result.success) {
+            warnings.push(format!("判定预计失败：{}", result.description));
+        }
+        if let Some(risk) = action
+            .as_ref()
+            .filter(|_| selected)
+            .and_then(|action| numerical_system.action_risk(stats, action, &context))
+            .filter(|risk| risk.chance > 0.0)
+        {
+            warnings.push(format!(
+                "此举约有 {:.0}% 的风险{}",
+                risk.chance * 100.0,
+                risk.kind.label()
+            ));
+        }
+        let ladder = numerical_system.realm_ladder();
+        if selected
+            && matches!(action, Some(Action::Breakthrough))
+            && ladder.is_peak(&stats.cultivation_realm)
+        {
+            if let Some(target_realm) = ladder.next_major(&stats.cultivation_realm) {
+                warnings.push(format!(
+                    "冲击{}将引来雷劫与心魔劫，渡劫失败会受伤折寿或修为跌落",
+                    target_realm.name
+                ));
+            }
+        }
+        if !selected && !matches!(action, None | Some(Action::Custom { .. })) {
+            warnings.push("自由输入只推进剧情，不结算属性变化，选择对应选项方可获得收益".to_string());
+        }
+        warnings.extend(numerical_system.take_formula_failures());
+
+        ActionPreview {
+            valid,
+            action,
+            estimated_result,
+            warnings,
+        }
+    }
+
+    /// 将所选行动的效果应用到角色属性，更新玩家画像与业力并推进游戏时间
+    pub fn resolve(&self, turn: &mut Turn) {
+        turn.plot_state
+            .player_persona
+            .observe(&turn.action, turn.selected_option.as_ref());
+        let alignment = self
+            .plot_engine
+            .classify_alignment(&turn.action, turn.selected_option.as_ref());
+        let deed = turn
+            .selected_option
+            .as_ref()
+            .map_or(turn.action.content.as_str(), |option| option.description.as_str());
+        turn.game_state
+            .karma
+            .record(turn.game_state.game_time.total_days, alignment, deed);
+
+        let peak_breakthrough = turn
+            .selected_option
+            .as_ref()
+            .is_some_and(|option| matches!(option.action, Action::Breakthrough))
+            && self
+                .plot_engine
+                .numerical_system()
+                .realm_ladder()
+                .is_peak(&turn.game_state.player.stats.cultivation_realm);
+        self.attempt_breakthrough(turn);
+        self.roll_mishap(turn);
+        self.pay_action_cost(turn);
+        let context = action_context(&turn.game_state);
+        if let (Some(selected_option), Some(action_result)) =
+            (&turn.selected_option, turn.action_result.as_mut())
+        {
+            let stats = &mut turn.game_state.player.stats;
+            self.apply_action_effects(&selected_option.action, stats, &context, action_result);
+            if action_result.success {
+                stats.gain_insight(
+                    self.plot_engine
+                        .numerical_system()
+                        .insight_gain(&selected_option.action),
+                );
+            }
+        }
+
+        if peak_breakthrough {
+            self.resolve_tribulation(turn);
+        }
+        self.collect_earnings(turn);
+        self.consume_item(turn);
+        self.resolve_duel(turn);
+        self.roll_loot(turn);
+        if let Some(side_story) = turn.plot_state.side_story.as_mut() {
+            side_story.turns_left = side_story.turns_left.saturating_sub(1);
+        }
+        let year = turn.game_state.game_time.year;
+        turn.game_state.game_time.advance_days(1);
+        self.expire_status_effects(turn, 1);
+        self.age_player(turn, year);
+        self.advance_world_timeline(turn);
+        self.expire_duel_challenges(turn);
+        self.settle_exhausted_lifespan(turn);
+    }
+
+    /// 状态随时间流逝，到期解除的写入行动结果
+    fn expire_status_effects(&self, turn: &mut Turn, days: u32) {
+        let expired = turn.game_state.player.stats.status_effects.tick(days);
+        if let Some(action_result) = turn.action_result.as_mut() {
+            action_result
+                .events
+                .extend(expired.iter().map(|kind| kind.recovery_note().to_string()));
+        }
+    }
+
+    /// 剧本时间线上到期的大事无论玩家做什么都会发生，写入续写背景并触发 NPC 反应；
+    /// 随后推演势力消长与战事
+    fn advance_world_timeline(&self, turn: &mut Turn) {
+        let fired = fire_due_events(&mut turn.game_state);
+        let mut faction_changes = Vec::new();
+        for event in &fired {
+            let power_changes = turn
+                .game_state
+                .script
+                .world_setting
+                .timeline_events
+                .iter()
+                .find(|definition| definition.id == event.id)
+                .map(|definition| definition.power_changes.clone())
+                .unwrap_or_default();
+            faction_changes.extend(faction_war::apply_power_changes(
+                &mut turn.game_state,
+                &power_changes,
+                &event.name,
+            ));
+        }
+        faction_changes.extend(faction_war::simulate(&mut turn.game_state));
+        if let Some(action_result) = turn.action_result.as_mut() {
+            action_result.events.extend(fired.iter().map(event_line));
+            action_result
+                .events
+                .extend(faction_changes.iter().map(|change| change.description.clone()));
+        }
+        turn.world_events.extend(fired);
+        turn.faction_changes.extend(faction_changes);
+    }
+
+    /// 服用所选的丹药：从背包取出并结算药效，背包中没有时行动落空
+    fn consume_item(&self, turn: &mut Turn) {
+        let Some(Action::UseItem { item_id }) = turn.selected_option.as_ref().map(|o| &o.action)
+        else {
+            return;
+        };
+        let Some(action_result) = turn.action_result.as_mut() else {
+            return;
+        };
+        let player = &mut turn.game_state.player;
+        let Some(idx) = player.inventory.iter().position(|item| &item.id == item_id) else {
+            action_result.success = false;
+            action_result.description = "背包中并没有这件物品。".to_string();
+            return;
+        };
+        let item = player.inventory.remove(idx);
+        action_result.stat_changes.push(StatChange {
+            stat_name: "inventory".to_string(),
+            old_value: (player.inventory.len() + 1).to_string(),
+            new_value: player.inventory.len().to_string(),
+        });
+        if item.extends_lifespan() {
+            action_result.stat_changes.extend(
+                player
+                    .stats
+                    .apply_stat_change(StatDelta::LifespanBonus(item.lifespan_bonus)),
+            );
+            action_result.description =
+                format!("你服下{}，药力化开，寿元增加 {} 年。", item.name, item.lifespan_bonus);
+        } else {
+            action_result.description = format!("你服下{}，却未觉有何变化。", item.name);
+        }
+        action_result.events.push(format!("服下{}", item.name));
+    }
+
+    /// 行动判定成功时扣除所选行动的花费；付不起时行动落空
+    fn pay_action_cost(&self, turn: &mut Turn) {
+        let (Some(option), Some(action_result)) =
+            (&turn.selected_option, turn.action_result.as_mut())
+        else {
+            return;
+        };
+        if !action_result.success {
+            return;
+        }
+        let cost = self
+            .plot_engine
+            .numerical_system()
+            .action_cost(&turn.game_state.player.stats, &option.action);
+        match turn.game_state.resources.apply(&cost) {
+            Ok(changes) => action_result.stat_changes.extend(changes),
+            Err(reason) => {
+                action_result.success = false;
+                action_result.description = reason;
+            }
+        }
+    }
+
+    /// 冲击瓶颈按含悟性加成的成功率掷定成败，尽数消耗积累的悟性；战力不足无法冲关时不消耗
+    fn attempt_breakthrough(&self, turn: &mut Turn) {
+        let (Some(option), Some(action_result)) =
+            (&turn.selected_option, turn.action_result.as_mut())
+        else {
+            return;
+        };
+        if !matches!(option.action, Action::Breakthrough) {
+            return;
+        }
+        let numerical_system = self.plot_engine.numerical_system();
+        let stats = &mut turn.game_state.player.stats;
+        if numerical_system.breakthrough_blocker(stats).is_none() {
+            *action_result = numerical_system.attempt_breakthrough(stats, &mut turn.game_state.rng);
+            if stats.spend_insight() > 0 {
+                action_result
+                    .events
+                    .push("多日积累的心得尽数化入此番冲关".to_string());
+            }
+        }
+    }
+
+    /// 按风险模型掷定所选行动是否失手，失手时折损战力、留下伤病并判为失败
+    fn roll_mishap(&self, turn: &mut Turn) {
+        let (Some(option), Some(action_result)) =
+            (&turn.selected_option, turn.action_result.as_mut())
+        else {
+            return;
+        };
+        let numerical_system = self.plot_engine.numerical_system();
+        let context = action_context(&turn.game_state);
+        let game_state = &mut turn.game_state;
+        if let Some(kind) = numerical_system.roll_mishap(
+            &game_state.player.stats,
+            &option.action,
+            &context,
+            action_result,
+            &mut game_state.rng,
+        ) {
+            numerical_system.suffer_mishap(&mut game_state.player.stats, kind, action_result);
+        }
+    }
+
+    /// 行动成功后计入所得的灵石与资源
+    fn collect_earnings(&self, turn: &mut Turn) {
+        let (Some(option), Some(action_result)) =
+            (&turn.selected_option, turn.action_result.as_mut())
+        else {
+            return;
+        };
+        if !action_result.success {
+            return;
+        }
+        let earnings = self
+            .plot_engine
+            .numerical_system()
+            .action_earnings(&turn.game_state.player.stats, &option.action);
+        if earnings.is_empty() {
+            return;
+        }
+        if let Ok(changes) = turn.game_state.resources.apply(&earnings) {
+            action_result.stat_changes.extend(changes);
+        }
+    }
+
+    /// 跨入新的一年时角色增长年岁
+    fn age_player(&self, turn: &mut Turn, previous_year: u32) {
+        let years = turn.game_state.game_time.year.saturating_sub(previous_year);
+        if years == 0 {
+            return;
+        }
+        let change = self
+            .plot_engine
+            .numerical_system()
+            .update_lifespan(&mut turn.game_state.player.stats, years);
+        if let (Some(change), Some(action_result)) = (change, turn.action_result.as_mut()) {
+            action_result.stat_changes.push(change);
+        }
+    }
+
+    /// 按行动修改角色属性，并把属性变化记入判定结果
+    fn apply_action_effects(
+        &self,
+        action: &Action,
+        stats: &mut CharacterStats,
+        context: &Context,
+        action_result: &mut ActionResult,
+    ) {
+        let numerical_system = self.plot_engine.numerical_system();
+        match action {
+            Action::Cultivate if action_result.success => {
+                let gain = numerical_system.cultivation_power_gain(stats, context);
+                if let Some(change) = stats.apply_stat_change(StatDelta::CombatPower(gain)) {
+                    action_result.stat_changes.push(change);
+                }
+                action_result.description = format!(
+                    "{} 战力提升了 {}。",
+                    action_result.description, gain
+                );
+            }
+            Action::Breakthrough => {
+                let next_stage = numerical_system
+                    .realm_ladder()
+                    .next_stage(&stats.cultivation_realm);
+                if let Some(next_realm) = next_stage.filter(|_| action_result.success) {
+                    action_result
+                        .stat_changes
+                        .extend(stats.advance_realm(next_realm));
+                    stats.status_effects.apply(
+                        StatusEffectKind::Enlightened,
+                        BREAKTHROUGH_ENLIGHTENMENT_DAYS,
+                        "突破后心境通明",
+                    );
+                    action_result.events.push("突破之后心有所悟".to_string());
+                } else if !action_result.success {
+                    stats.status_effects.apply(
+                        StatusEffectKind::QiDeviation,
+                        BREAKTHROUGH_DEVIATION_DAYS,
+                        "强行突破失败",
+                    );
+                    action_result.events.push("突破受挫，真气逆行，走火入魔".to_string());
+                }
+            }
+            Action::Rest => {
+                let recovered = stats
+                    .status_effects
+                    .recover(numerical_system.rest_recovery_days(context));
+                action_result
+                    .events
+                    .extend(recovered.iter().map(|kind| kind.recovery_note().to_string()));
+            }
+            Action::LearnTechnique { technique_id } if action_result.success => {
+                if let Some(technique) = numerical_system.technique(technique_id) {
+                    action_result
+                        .stat_changes
+                        .extend(stats.learn_technique(technique.learned()));
+                }
+            }
+            Action::PracticeTechnique { technique_id } if action_result.success => {
+                if let Some(gain) = stats
+                    .technique(technique_id)
+                    .map(|technique| numerical_system.proficiency_gain(stats, technique))
+                {
+                    action_result
+                        .stat_changes
+                        .extend(stats.practice_technique(technique_id, gain));
+                }
+            }
+            Action::Custom { .. }
+            | Action::Cultivate
+            | Action::Combat { .. }
+            | Action::LearnTechnique { .. }
+            | Action::PracticeTechnique { .. }
+            | Action::UseItem { .. } => {}
+        }
+    }
+
+    /// 圆满期突破下一大境界时渡劫：逐关判定并叙述，成功晋升增寿，失败则受伤或修为跌落
+    fn resolve_tribulation(&self, turn: &mut Turn) {
+        let Some(action_result) = turn.action_result.as_mut().filter(|result| result.success)
+        else {
+            return;
+        };
+        let game_state = &mut turn.game_state;
+        let numerical_system = self.plot_engine.numerical_system();
+        let Some(target_realm) = numerical_system
+            .realm_ladder()
+            .next_major(&game_state.player.stats.cultivation_realm)
+        else {
+            return;
+        };
+
+        let outcome = numerical_system.run_tribulation(
+            &game_state.player.stats,
+            target_realm,
+            &mut game_state.rng,
+        );
+        action_result.events.extend(
+            self.plot_engine
+                .narrate_tribulation(&outcome, &game_state.player.name),
+        );
+        action_result
+            .stat_changes
+            .extend(outcome.apply(&mut game_state.player.stats));
+        action_result.success = outcome.passed;
+        action_result.description = outcome.summary();
+    }
+
+    /// 寿元耗尽时：房规关闭永久死亡则续命，否则角色坐化，本局不能再行动
+    fn settle_exhausted_lifespan(&self, turn: &mut Turn) {
+        let game_state = &mut turn.game_state;
+        let stats = &mut game_state.player.stats;
+        if stats.lifespan.is_alive() {
+            return;
+        }
+        let Some(action_result) = turn.action_result.as_mut() else {
+            return;
+        };
+        if !game_state.house_rules.disable_permadeath {
+            action_result
+                .events
+                .push(format!("寿元耗尽，{}就此坐化", game_state.player.name));
+            return;
+        }
+        let overdue = stats
+            .lifespan
+            .current_age
+            .saturating_sub(stats.lifespan.total_max_age());
+        if let Some(change) = stats.apply_stat_change(StatDelta::LifespanBonus(
+            overdue.saturating_add(PERMADEATH_REPRIEVE_YEARS),
+        )) {
+            action_result.stat_changes.push(change);
+        }
+        action_result.events.push(format!(
+            "寿元耗尽之际，房规护持，续命 {} 年",
+            PERMADEATH_REPRIEVE_YEARS
+        ));
+    }
+
+    /// 应下战帖时按双方战力结算决斗，胜负决定战利品与声望
+    fn resolve_duel(&self, turn: &mut Turn) {
+        let Some(Action::Combat { target_id }) = turn.selected_option.as_ref().map(|o| &o.action)
+        else {
+            return;
+        };
+        let Some(action_result) = turn.action_result.as_mut() else {
+            return;
+        };
+        let game_state = &mut turn.game_state;
+        let Some(challenge) = game_state.world_state.duel_board.take_pending(target_id) else {
+            return;
+        };
+
+        let combat = self
+            .plot_engine
+            .numerical_system()
+            .calculate_combat_outcome(&game_state.player.stats, &challenge.challenger_stats);
+        let player_won = combat.winner_id == "attacker";
+        let outcome = settle_duel(
+            &challenge,
+            player_won,
+            &mut game_state.player,
+            &mut game_state.world_state,
+            game_state.game_time.total_days,
+        );
+
+        action_result.success = player_won;
+        action_result.description = outcome.description.clone();
+        if let Some(faction_id) = &outcome.faction_id {
+            let new_value = game_state.world_state.faction_reputation[faction_id];
+            action_result.stat_changes.push(StatChange {
+                stat_name: format!("reputation:{}", faction_id),
+                old_value: (new_value - outcome.reputation_change).to_string(),
+                new_value: new_value.to_string(),
+            });
+        }
+        action_result.events.push(outcome.description.clone());
+        turn.duel_outcomes.push(outcome);
+    }
+
+    /// 过期未应的战帖视为怯战，折损声望
+    fn expire_duel_challenges(&self, turn: &mut Turn) {
+        let world_state = &mut turn.game_state.world_state;
+        let current_day = turn.game_state.game_time.total_days;
+        for challenge in world_state.duel_board.take_expired(current_day) {
+            let outcome = settle_declined(&challenge, world_state, current_day);
+            if let Some(action_result) = turn.action_result.as_mut() {
+                action_result.events.push(outcome.description.clone());
+            }
+            turn.duel_outcomes.push(outcome);
+        }
+    }
+
+    /// 战斗胜利或探索时按掉落表结算战利品；秘境中探索改用秘境的掉落表
+    fn roll_loot(&self, turn: &mut Turn) {
+        let Some(action_result) = turn.action_result.as_mut() else {
+            return;
+        };
+        let game_state = &mut turn.game_state;
+        let tables = &game_state.script.drop_tables;
+
+        let table: Option<&DropTable> = match turn.selected_option.as_ref().map(|o| &o.action) {
+            Some(Action::Combat { .. }) if action_result.success => table_for_enemy_tier(
+                tables,
+                game_state.player.stats.cultivation_realm.level,
+            ),
+            Some(Action::Custom { description }) if is_exploration(description) => {
+                match &turn.plot_state.side_story {
+                    Some(side_story) => table_for_secret_realm(tables, &side_story.realm_id),
+                    None => table_for_location(tables, &game_state.player.location),
+                }
+            }
+            None if is_exploration(&turn.action.content) => {
+                table_for_location(tables, &game_state.player.location)
+            }
+            _ => None,
+        };
+        let Some(table) = table else {
+            return;
+        };
+        if matches!(table.source, DropSource::Location { .. })
+            && game_state
+                .world_state
+                .faction_standings
+                .is_route_closed(&game_state.player.location)
+        {
+            action_result.events.push("战火封锁此地，探索无功而返".to_string());
+            return;
+        }
+
+        let roll = game_state
+            .loot_state
+            .roll(&game_state.difficulty.scarce_table(table));
+        let Some(narration) = roll.narration() else {
+            return;
+        };
+        let old_count = game_state.player.inventory.len();
+        game_state.player.inventory.extend(roll.items);
+        action_result.stat_changes.push(StatChange {
+            stat_name: "inventory".to_string(),
+            old_value: old_count.to_string(),
+            new_value: game_state.player.inventory.len().to_string(),
+        });
+        action_result.description = format!("{} {}。", action_result.description, narration);
+        action_result.events.push(narration);
+    }
+
+    /// 生成剧情片段并更新章节状态
+    pub async fn narrate(&self, turn: &mut Turn) {
+        let Some(action_result) = turn.action_result.take() else {
+            return;
+        };
+
+        // 开局、每隔数章或剧情偏离大纲时，先规划故事弧再续写段落。
+        let arc_note = match replan_reason(&turn.plot_state) {
+            Some(reason) => {
+                let arc = self
+                    .arc_planner
+                    .plan(&turn.plot_state, &turn.game_state, reason)
+                    .await;
+                let note = format!("故事弧规划：第{}弧《{}》（{}）", arc.index, arc.title, reason.label());
+                turn.plot_state.story_arc = Some(arc);
+                Some(note)
+            }
+            None => None,
+        };
+
+        // 两段式续写：新章节先规划大纲，段落再按大纲节拍续写，写完大纲即结束本章。
+        let outline_note = if turn.plot_state.settings.outline_planning
+            && turn.plot_state.current_chapter.outline.is_none()
+        {
+            let outline = self
+                .chapter_outliner
+                .plan(&turn.plot_state, &turn.game_state)
+                .await;
+            let note = format!(
+                "章节大纲规划：第{}章 {} 个节拍、{} 个互动点（{}）",
+                turn.plot_state.current_chapter.index,
+                outline.beats.len(),
+                outline.interaction_points(),
+                match outline.source {
+                    ArcSource::Llm => "LLM",
+                    ArcSource::Template => "模板",
+                }
+            );
+            turn.plot_state.current_chapter.outline = Some(outline);
+            Some(note)
+        } else {
+            None
+        };
+
+        // NPC 反应只作为续写背景，不计入本回合触发的事件，避免再次入队引发连锁反应。
+        let mut narrated_result = action_result.clone();
+        narrated_result.events.splice(0..0, turn.npc_digest.iter().cloned());
+        // 本回合即是对插叙的回应，提交后插叙随之结束
+        if let Some(interruption) = turn.plot_state.npc_interruption.take() {
+            narrated_result.events.insert(0, interruption.prompt_line());
+            turn.answered_interruption = Some(interruption);
+        }
+        // 选项只依赖行动结果而不依赖新段落，与续写同时请求；段落自带选项时预取结果弃用。
+        let prefetch_options = wants_option_prefetch(&turn.plot_state);
+        let option_scene = Scene {
+            description: action_result.description.clone(),
+            ..turn.plot_state.current_scene.clone()
+        };
+        // 一致性与状态上下文按结算后的状态，行动可能已改变境界或带来伤病
+        let plot_engine = self
+            .plot_engine_for(turn)
+            .with_narrative_context(NarrativeContext::from_game_state(
+                &turn.game_state,
+                self.departed_npcs.clone(),
+            ));
+        let (mut plot_update, prefetched_options) = segment_with_options(
+            plot_engine.advance_plot_async(&turn.plot_state, &narrated_result),
+            prefetch_options.then(|| {
+                plot_engine.generate_player_options_with_llm_async(
+                    &option_scene,
+                    &turn.game_state.player.stats,
+                )
+            }),
+        )
+        .await;
+        turn.prefetched_options = prefetched_options;
+        plot_update.triggered_events = action_result.events.clone();
+        turn.game_state
+            .player
+            .stats
+            .gain_insight(insight_from_events(&plot_update.triggered_events));
+        plot_update
+            .state_changes
+            .extend(lifespan_warning(&turn.game_state.player.stats.lifespan));
+        let content_verdict = self
+            .filter_content(&plot_engine, &turn.plot_state, &narrated_result, &mut plot_update)
+            .await;
+
+        let today = turn.game_state.game_time.total_days;
+        turn.game_state.quests.apply(&plot_update.quest_updates, today);
+
+        let timestamp = turn.timestamp();
+        let plot_state = &mut turn.plot_state;
+        plot_state.last_action_result = Some(action_result);
+        let facts_check = ResponseValidator::default()
+            .validate_against_facts(&plot_update.plot_text, &plot_state.canon_facts);
+        if let Some(provenance) = plot_update.provenance.as_mut() {
+            provenance.segment_index = plot_state.current_chapter.content.len();
+            provenance.validator_verdicts.push(content_verdict.clone());
+            provenance.validator_verdicts.push(match &facts_check {
+                Ok(()) => ValidatorVerdict::passed(FACTS_VALIDATOR),
+                Err(error) => ValidatorVerdict::failed(FACTS_VALIDATOR, error.to_string()),
+            });
+        }
+        plot_state.append_segment_with_provenance(
+            plot_update.plot_text.clone(),
+            plot_update.provenance.clone(),
+        );
+
+        if let Some(title) = plot_update.chapter_title.clone() {
+            if !title.trim().is_empty() {
+                plot_state.current_chapter.title = title.trim().to_string();
+                plot_state.current_scene.name = plot_state.current_chapter.title.clone();
+            }
+        }
+
+        if plot_update.is_waiting_for_input {
+            plot_state.current_chapter.interaction_count = plot_state
+                .current_chapter
+                .interaction_count
+                .saturating_add(1);
+        }
+
+        if plot_update.chapter_end {
+            plot_state.finalize_chapter(
+                plot_update.chapter_title.clone(),
+                plot_update.chapter_summary.clone(),
+            );
+        }
+
+        let mut diagnostics = plot_update.generation_diagnostics.clone();
+        plot_state.last_generation_failure = plot_update.generation_failure.clone();
+        if let Some(arc) = plot_state.story_arc.as_mut() {
+            arc.observe_segment(&plot_update.plot_text);
+        }
+        if let Some(note) = arc_note {
+            diagnostics.warn(note);
+        }
+        if let Some(note) = outline_note {
+            diagnostics.warn(note);
+        }
+        for note in plot_engine.numerical_system().take_formula_failures() {
+            diagnostics.warn(note);
+        }
+
+        if let Some(signal) = plot_update.tuning_signal {
+            let bounds = plot_state.settings.temperature_bounds;
+            if let Some(note) = plot_state.temperature_tuner.record(
+                &PromptTemplate::PlotGeneration,
+                SEGMENT_BASE_TEMPERATURE,
+                signal,
+                &bounds,
+            ) {
+                diagnostics.warn(note);
+            }
+        }
+
+        if let Some(note) = self.token_budget.as_ref().and_then(TokenBudget::summary) {
+            diagnostics.warn(note);
+        }
+
+        if let Some(note) = content_verdict.detail.filter(|_| !content_verdict.passed) {
+            diagnostics.warn(note);
+        }
+
+        // 与既定事实矛盾的段落只记录诊断，不写入事实库。
+        if let Err(error) = facts_check {
+            diagnostics.warn(error.to_string());
+        }
+        plot_state.last_generation_diagnostics = Some(diagnostics);
+        plot_state
+            .canon_facts
+            .record_from_text(&plot_update.plot_text, timestamp);
+
+        // 用最新段落更新场景描述，避免选项生成长期绑定旧描述导致“选项不变”。
+        if !plot_update.plot_text.trim().is_empty() {
+            plot_state.current_scene.description = plot_update.plot_text.trim().to_string();
+        }
+
+        turn.plot_update = Some(plot_update);
+    }
+
+    /// 按内容过滤设置检查生成的段落：违规时以更严格的约束重新生成一次，仍违规则遮蔽违规词
+    async fn filter_content(
+        &self,
+        plot_engine: &PlotEngine,
+        plot_state: &PlotState,
+        action_result: &ActionResult,
+        plot_update: &mut PlotUpdate,
+    ) -> ValidatorVerdict {
+        let violations = self.content_filter.violations(&plot_update.plot_text);
+        if violations.is_empty() {
+            return ValidatorVerdict::passed(CONTENT_VALIDATOR);
+        }
+
+        let regenerate = match &self.token_budget {
+            Some(budget) => budget
+                .allocate(
+                    TurnCall::ContentRegeneration,
+                    &plot_update.plot_text,
+                    CONTENT_REGENERATION_TOKENS,
+                )
+                .is_some(),
+            None => true,
+        };
+        if regenerate {
+            let mut plot_engine = plot_engine
+                .clone()
+                .with_content_rules(self.content_filter.stricter_rules(&violations));
+            // 重写已按内容过滤计入预算，不再按续写重复扣除
+            plot_engine.set_token_budget(None);
+            let mut retried = plot_engine.advance_plot_async(plot_state, action_result).await;
+            retried.triggered_events = std::mem::take(&mut plot_update.triggered_events);
+            *plot_update = retried;
+        }
+
+        let remaining = self.content_filter.violations(&plot_update.plot_text);
+        if remaining.is_empty() {
+            return ValidatorVerdict::passed(CONTENT_VALIDATOR);
+        }
+        plot_update.plot_text = self.content_filter.redact(&plot_update.plot_text);
+        ValidatorVerdict::failed(
+            CONTENT_VALIDATOR,
+            format!(
+                "内容过滤：重新生成后仍出现{}，已遮蔽",
+                remaining
+                    .iter()
+                    .map(|v| format!("「{}」", v.term))
+                    .collect::<Vec<String>>()
+                    .join("")
+            ),
+        )
+    }
+
+    /// 根据玩家行动生成需要记录的事件
+    pub fn react(&self, turn: &mut Turn) {
+        let fought = turn
+            .duel_outcomes
+            .iter()
+            .find(|outcome| outcome.result != DuelResult::Declined);
+        let player = &turn.game_state.player;
+        turn.log_entry = if game_over_reason(&turn.game_state).is_some()
+            && !player.stats.lifespan.is_alive()
+        {
+            Some(TurnLogEntry {
+                event_type: LIFESPAN_EXHAUSTED_EVENT,
+                message: format!("{} 寿元耗尽而坐化", player.name),
+                importance: EventImportance::Important,
+            })
+        } else if let Some(outcome) = fought {
+            Some(TurnLogEntry {
+                event_type: "duel",
+                message: outcome.description.clone(),
+                importance: EventImportance::Important,
+            })
+        } else if let Some(selected_option) = &turn.selected_option {
+            let succeeded = turn
+                .action_result
+                .as_ref()
+                .or(turn.plot_state.last_action_result.as_ref())
+                .is_some_and(|result| result.success);
+            Some(match &selected_option.action {
+                Action::Combat { .. } => TurnLogEntry {
+                    event_type: "combat",
+                    message: format!("Player engaged in combat: {}", selected_option.description),
+                    importance: EventImportance::Important,
+                },
+                Action::Breakthrough if succeeded => TurnLogEntry {
+                    event_type: BREAKTHROUGH_EVENT,
+                    message: format!("Player broke through: {}", selected_option.description),
+                    importance: EventImportance::Important,
+                },
+                Action::Breakthrough => TurnLogEntry {
+                    event_type: "breakthrough_attempt",
+                    message: format!("Player attempted breakthrough: {}", selected_option.description),
+                    importance: EventImportance::Important,
+                },
+                Action::LearnTechnique { .. } if succeeded => TurnLogEntry {
+                    event_type: "technique_learned",
+                    message: format!("Player learned a technique: {}", selected_option.description),
+                    importance: EventImportance::Important,
+                },
+                Action::Custom { .. }
+                | Action::Cultivate
+                | Action::Rest
+                | Action::LearnTechnique { .. }
+                | Action::PracticeTechnique { .. }
+                | Action::UseItem { .. } => TurnLogEntry {
+                    event_type: "player_action",
+                    message: selected_option.description.clone(),
+                    importance: EventImportance::Normal,
+                },
+            })
+        } else if turn.action.selected_option_id.is_none()
+            && turn.action.selected_option_uuid.is_none()
+            && matches!(turn.action.action_type, ActionType::FreeText)
+        {
+            Some(TurnLogEntry {
+                event_type: "player_free_text",
+                message: turn.action.content.clone(),
+                importance: EventImportance::Normal,
+            })
+        } else {
+            None
+        };
+    }
+
+    /// 为下一回合生成可选行动
+    pub fn regenerate_options(&self, turn: &mut Turn) {
+        let plot_engine = self.plot_engine_for(turn);
+        let Some(plot_update) = turn.plot_update.as_mut() else {
+            return;
+        };
+        let plot_state = &mut turn.plot_state;
+        let previous_options = plot_state.current_scene.available_options.clone();
+
+        let option_source = if plot_update.is_waiting_for_input {
+            if let Some(side_story) = &plot_state.side_story {
+                plot_state.current_scene.available_options = side_story.options();
+                "side_story".to_string()
+            } else if !plot_update.available_options.is_empty() {
+                plot_state.current_scene.available_options =
+                    std::mem::take(&mut plot_update.available_options);
+                "llm_structured".to_string()
+            } else {
+                let (llm_regenerated, llm_source) = match turn.prefetched_options.take() {
+                    Some(options) => (Some(options), "llm_prefetched"),
+                    None => (
+                        plot_engine.generate_player_options_with_llm(
+                            &plot_state.current_scene,
+                            &turn.game_state.player.stats,
+                        ),
+                        "llm_regenerated",
+                    ),
+                };
+                let (mut regenerated_options, mut source) = if let Some(options) = llm_regenerated {
+                    (options, llm_source.to_string())
+                } else {
+                    (
+                        self.plot_engine.generate_player_options(
+                            &plot_state.current_scene,
+                            &turn.game_state.player.stats,
+                        ),
+                        "rule_fallback".to_string(),
+                    )
+                };
+
+                if regenerated_options.is_empty() {
+                    regenerated_options = previous_options.clone();
+                    source = "previous_reused".to_string();
+                }
+
+                // 通过时间推进对兜底选项做轻量轮转，确保连续交互时选项呈现有变化。
+                if !regenerated_options.is_empty() {
+                    let rotation =
+                        (turn.game_state.game_time.total_days as usize) % regenerated_options.len();
+                    regenerated_options.rotate_left(rotation);
+                    for (idx, option) in regenerated_options.iter_mut().enumerate() {
+                        option.id = idx;
+                    }
+                }
+                plot_state.current_scene.available_options = regenerated_options;
+                source
+            }
+        } else {
+            plot_state.current_scene.available_options.clear();
+            "not_waiting_for_input".to_string()
+        };
+
+        if plot_update.is_waiting_for_input && plot_state.side_story.is_none() {
+            attach_entry_options(
+                &mut plot_state.current_scene.available_options,
+                &turn.game_state.script.secret_realms,
+                &turn.game_state,
+            );
+            attach_duel_options(
+                &mut plot_state.current_scene.available_options,
+                &turn.game_state.world_state.duel_board.pending,
+            );
+            attach_longevity_option(
+                &mut plot_state.current_scene.available_options,
+                &turn.game_state.player.inventory,
+            );
+        }
+        inherit_option_uids(
+            &previous_options,
+            &mut plot_state.current_scene.available_options,
+        );
+
+        plot_state.last_option_generation_source = Some(option_source.clone());
+        plot_state
+            .last_generation_diagnostics
+            .get_or_insert_with(GenerationDiagnostics::default)
+            .option_source = Some(option_source.clone());
+        turn.option_source = Some(option_source);
+    }
+
+    /// 记录事件、触发NPC反应并写回引擎状态，返回本回合的剧情文本
+    pub fn commit(&self, turn: Turn, engine: &mut GameEngine) -> Result<String, String> {
+        // 生成期间其他命令改动过状态时整回合作废，日志与 NPC 事件也不写入
+        engine
+            .check_turn_sequence(turn.sequence)
+            .map_err(|e| e.to_string())?;
+        let timestamp = turn.timestamp();
+        let Turn {
+            action,
+            mut game_state,
+            plot_state,
+            selected_option,
+            plot_update,
+            log_entry,
+            duel_outcomes,
+            world_events,
+            faction_changes,
+            answered_interruption,
+            ..
+        } = turn;
+        let plot_update = plot_update.ok_or_else(|| "回合尚未生成剧情".to_string())?;
+
+        if let Some(entry) = log_entry {
+            engine.log_event(timestamp, entry.event_type, entry.message, entry.importance);
+        }
+        for event in &world_events {
+            engine.log_event(timestamp, WORLD_EVENT, event_line(event), EventImportance::Important);
+        }
+        for change in faction_changes {
+            engine.log_event(timestamp, change.event_type, change.description, change.importance);
+        }
+        for outcome in &duel_outcomes {
+            engine.apply_duel_outcome(outcome, timestamp);
+        }
+
+        engine.enqueue_npc_events(timestamp, &plot_update.triggered_events);
+
+        let previous_location = engine
+            .get_current_state()
+            .ok()
+            .map(|state| state.player.location);
+        let current_location = game_state.player.location.clone();
+        let previous_plot = engine.get_plot_state().ok();
+        let finished_chapters = previous_plot
+            .as_ref()
+            .map_or(0, |previous| previous.chapters.len());
+        // 生成期间后台 NPC 挂上的插叙不在快照里，也不推进写入序号，写回后重新挂到新场景上
+        let background_interruption = previous_plot
+            .and_then(|previous| previous.npc_interruption)
+            .filter(|notice| answered_interruption.as_ref() != Some(notice));
+        for chapter in plot_state.chapters.iter().skip(finished_chapters) {
+            engine.log_event(
+                timestamp,
+                CHAPTER_COMPLETE_EVENT,
+                format!("「{}」完结", chapter.title),
+                EventImportance::Important,
+            );
+        }
+
+        let realm_entry = selected_option
+            .as_ref()
+            .and_then(|option| realm_for_option(&game_state.script.secret_realms, option))
+            .map(|realm| realm.id.clone());
+        let leaves_side_story = plot_state.side_story.as_ref().is_some_and(|side_story| {
+            side_story.is_exhausted() || selected_option.as_ref().is_some_and(is_exit_option)
+        });
+        if let Some(result) = &plot_state.last_action_result {
+            let source = selected_option.map_or(action.content, |option| option.description);
+            game_state.record_stat_changes(source.trim(), &result.stat_changes);
+        }
+        engine.record_player_action(&mut game_state);
+        engine
+            .update_current_state(game_state)
+            .map_err(|e| e.to_string())?;
+        engine
+            .update_plot_state(plot_state)
+            .map_err(|e| e.to_string())?;
+        if let Some(notice) = background_interruption {
+            engine.interrupt_with(&notice);
+        }
+        // 回合快照里的事件记录可能落后于后台处理的 NPC 事件，以事件日志为准
+        engine.sync_event_history_to_state();
+
+        // 本回合的段落写入后再进出秘境：进入前的一段留在主线，秘境的最后一段随秘境并回主线
+        if let Some(realm_id) = realm_entry {
+            engine
+                .enter_side_story(&realm_id)
+                .map_err(|e| e.to_string())?;
+        } else if leaves_side_story {
+            engine.exit_side_story().map_err(|e| e.to_string())?;
+        }
+
+        if previous_location.as_deref() != Some(current_location.as_str()) {
+            // 新地点的NPC生成失败不影响本回合结果
+            let _ = engine.populate_location_on_discovery(&current_location);
+        }
+
+        // 秘境中的选项受限，天下事与战帖留到回到主线后再处理
+        if !engine.in_side_story() {
+            engine
+                .publish_bulletin_if_due()
+                .map_err(|e| e.to_string())?;
+            engine
+                .issue_duel_challenge()
+                .map_err(|e| e.to_string())?;
+        }
+
+        Ok(plot_update.plot_text)
+    }
+}
+
+/// 本局已无法继续行动的原因：已达成结局，或寿元耗尽且未开启续命房规
+pub fn game_over_reason(game_state: &GameState) -> Option<String> {
+    if let Some(ending) = &game_state.ending {
+        return Some(format!("本局已以「{}」结局收场", ending.title));
+    }
+    (!game_state.player.stats.lifespan.is_alive() && !game_state.house_rules.disable_permadeath)
+        .then(|| format!("{}寿元已尽，无法再行动", game_state.player.name))
+}
+
+/// 步入暮年或寿元耗尽时写入剧情更新的提醒
+fn lifespan_warning(lifespan: &Lifespan) -> Option<String> {
+    if !lifespan.is_alive() {
+        Some("寿元已尽".to_string())
+    } else if lifespan.in_twilight() {
+        Some(format!(
+            "寿元将尽：{} 岁，仅余 {} 年",
+            lifespan.current_age,
+            lifespan.remaining_years()
+        ))
+    } else {
+        None
+    }
+}
+
+/// 背包中有延寿丹药时追加服用药效最强者的选项，先移除沿用下来的旧服药选项
+fn attach_longevity_option(options: &mut Vec<PlayerOption>, inventory: &[Item]) {
+    options.retain(|option| !matches!(option.action, Action::UseItem { .. }));
+    if let Some(item) = inventory
+        .iter()
+        .filter(|item| item.extends_lifespan())
+        .max_by_key(|item| item.lifespan_bonus)
+    {
+        options.push(PlayerOption {
+            id: options.len(),
+            uid: new_option_uid(),
+            description: format!("服用{}", item.name),
+            requirements: vec![format!("寿元 +{} 年", item.lifespan_bonus)],
+            action: Action::UseItem {
+                item_id: item.id.clone(),
+            },
+        });
+    }
+    for (idx, option) in options.iter_mut().enumerate() {
+        option.id = idx;
+    }
+}
+
+/// 角色当前所在之处的判定情境
+pub(crate) fn action_context(game_state: &GameState) -> Context {
+    context_at(game_state, &game_state.player.location)
+}
+
+/// 秘境中的选项是固定的，不必预取；其余情况选项都不依赖新段落，可与续写同时请求
+fn wants_option_prefetch(plot_state: &PlotState) -> bool {
+    plot_state.side_story.is_none()
+}
+
+/// 同时等待续写与选项请求，两者互不依赖，回合耗时取较慢的一方
+async fn segment_with_options<T, S, O>(
+    segment: S,
+    options: Option<O>,
+) -> (T, Option<Vec<PlayerOption>>)
+where
+    S: std::future::Future<Output = T>,
+    O: std::future::Future<Output = Option<Vec<PlayerOption>>>,
+{
+    tokio::join!(segment, async {
+        match options {
+            Some(options) => options.await,
+            None => None,
+        }
+    })
+}
+
+fn is_exploration(text: &str) -> bool {
+    let lower = text.to_lowercase();
+    EXPLORATION_KEYWORDS.iter().any(|k| lower.contains(k))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::duel::{DuelChallenge, DuelStake};
+    use crate::game_state::{GameTime, ItemType};
+    use crate::generation_diagnostics::ParsePath;
+    use crate::house_rules::HouseRules;
+    use crate::karma::{Alignment, Karma};
+    use crate::loot::{DropEntry, DropRarity, DropSource};
+    use crate::models::{CultivationRealm, Element, Grade, SpiritualRoot, MAX_INSIGHT};
+    use crate::numerical_system::PEAK_SUB_LEVEL;
+    use crate::rng::GameRng;
+    use crate::script::{Climate, InitialState, Location, LocationKind, Script, ScriptType, WorldSetting};
+    use crate::side_story::{SecretRealm, SideStory};
+
+    fn create_test_engine() -> GameEngine {
+        engine_for(create_test_script())
+    }
+
+    fn create_test_script() -> Script {
+        let mut world_setting = WorldSetting::new();
+        world_setting.cultivation_realms = vec![
+            CultivationRealm::new("Qi Condensation".to_string(), 1, 0, 1.0),
+            CultivationRealm::new("Foundation Establishment".to_string(), 2, 0, 2.0),
+        ];
+        world_setting.locations = vec![Location {
+            id: "sect".to_string(),
+            name: "Azure Cloud Sect".to_string(),
+            description: "A peaceful cultivation sect".to_string(),
+            spiritual_energy: 1.0,
+            kind: LocationKind::Wilderness,
+            climate: Climate::Temperate,
+        }];
+
+        let initial_state = InitialState {
+            player_name: "Test Player".to_string(),
+            player_spiritual_root: SpiritualRoot {
+                element: Element::Fire,
+                grade: Grade::heavenly(),
+                affinity: 0.9,
+                tier_multiplier: None,
+            },
+            starting_location: "sect".to_string(),
+            starting_age: 16,
+        };
+        Script::new(
+            "test_script".to_string(),
+            "Test Script".to_string(),
+            ScriptType::Custom,
+            world_setting,
+            initial_state,
+        )
+    }
+
+    fn engine_for(script: Script) -> GameEngine {
+        let mut engine = GameEngine::new();
+        engine.initialize_game(script).unwrap();
+        engine.initialize_plot().unwrap();
+        engine
+    }
+
+    fn option_turn(engine: &GameEngine, action: Action) -> Turn {
+        let mut plot_state = engine.get_plot_state().unwrap();
+        plot_state.current_scene.available_options = vec![PlayerOption {
+            id: 0,
+            uid: new_option_uid(),
+            description: "test option".to_string(),
+            requirements: Vec::new(),
+            action,
+        }];
+        let uid = plot_state.current_scene.available_options[0].uid.clone();
+        let mut turn = Turn::new(
+            PlayerAction {
+                action_type: ActionType::SelectedOption,
+                content: String::new(),
+                selected_option_id: Some(0),
+                selected_option_uuid: Some(uid),
+                meta: None,
+            },
+            engine.get_current_state().unwrap(),
+            plot_state,
+        )
+        .with_sequence(engine.turn_sequence());
+        turn.game_state.rng = steady_rng();
+        turn
+    }
+
+    /// 首次掷骰不低于失手概率上限的随机数，测试中所选的行动不会失手
+    fn steady_rng() -> GameRng {
+        (0..)
+            .map(GameRng::new)
+            .find(|rng| rng.clone().range_f32(0.0, 1.0) >= 0.6)
+            .unwrap()
+    }
+
+    fn free_text_turn(engine: &GameEngine, content: &str) -> Turn {
+        Turn::new(
+            PlayerAction {
+                action_type: ActionType::FreeText,
+                content: content.to_string(),
+                selected_option_id: None,
+                selected_option_uuid: None,
+                meta: None,
+            },
+            engine.get_current_state().unwrap(),
+            engine.get_plot_state().unwrap(),
+        )
+        .with_sequence(engine.turn_sequence())
+    }
+
+    fn pipeline(engine: &GameEngine) -> TurnPipeline {
+        TurnPipeline::for_state(
+            &engine.get_current_state().unwrap(),
+            &engine.get_plot_state().unwrap().settings,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_validate_selects_option_and_computes_result() {
+        let engine = create_test_engine();
+        let pipeline = pipeline(&engine);
+        let mut turn = option_turn(&engine, Action::Rest);
+
+        pipeline.validate(&mut turn).unwrap();
+
+        assert_eq!(turn.selected_option.as_ref().unwrap().description, "test option");
+        assert!(turn.action_result.is_some());
+    }
+
+    #[test]
+    fn test_validate_rejects_unknown_option() {
+        let engine = create_test_engine();
+        let pipeline = pipeline(&engine);
+        let mut turn = option_turn(&engine, Action::Rest);
+        turn.action.selected_option_id = Some(5);
+        turn.action.selected_option_uuid = Some("retired-option".to_string());
+
+        assert!(pipeline.validate(&mut turn).is_err());
+    }
+
+    #[test]
+    fn test_preview_estimates_changes_without_mutating_turn() {
+        let engine = create_test_engine();
+        let pipeline = pipeline(&engine);
+        let turn = option_turn(&engine, Action::Cultivate);
+        let old_power = turn.game_state.player.stats.combat_power;
+
+        let preview = pipeline.preview(&turn);
+        assert!(preview.valid);
+        assert_eq!(preview.action, Some(Action::Cultivate));
+        let change = preview
+            .estimated_result
+            .unwrap()
+            .stat_changes
+            .into_iter()
+            .find(|c| c.stat_name == "combat_power")
+            .unwrap();
+        assert_eq!(change.old_value, old_power.to_string());
+        assert_eq!(turn.game_state.player.stats.combat_power, old_power);
+
+        let free_text = pipeline.preview(&free_text_turn(&engine, "闭关修炼一日"));
+        assert_eq!(free_text.action, Some(Action::Cultivate));
+        assert!(free_text.estimated_result.unwrap().stat_changes.is_empty());
+        assert!(free_text.warnings.iter().any(|w| w.contains("不结算属性变化")));
+
+        let rejected = pipeline.preview(&rest_only(free_text_turn(&engine, "我要立刻突破")));
+        assert!(!rejected.valid);
+        assert_eq!(rejected.action, Some(Action::Breakthrough));
+        assert!(rejected.warnings[0].contains("突破"));
+    }
+
+    #[test]
+    fn test_resolve_cultivate_increases_combat_power_and_time() {
+        let engine = create_test_engine();
+        let pipeline = pipeline(&engine);
+        let mut turn = option_turn(&engine, Action::Cultivate);
+        let old_power = turn.game_state.player.stats.combat_power;
+        let old_days = turn.game_state.game_time.total_days;
+
+        pipeline.validate(&mut turn).unwrap();
+        pipeline.resolve(&mut turn);
+
+        assert!(turn.game_state.player.stats.combat_power > old_power);
+        assert!(turn.game_state.player.stats.insight > 0);
+        assert_eq!(turn.game_state.game_time.total_days, old_days + 1);
+        assert_eq!(turn.plot_state.player_persona.turns_observed, 1);
+        assert!(turn
+            .action_result
+            .as_ref()
+            .unwrap()
+            .stat_changes
+            .iter()
+            .any(|c| c.stat_name == "combat_power"));
+    }
+
+    fn rest_only(mut turn: Turn) -> Turn {
+        turn.plot_state.current_scene.available_options = vec![PlayerOption {
+            id: 0,
+            uid: new_option_uid(),
+            description: "原地调息".to_string(),
+            requirements: Vec::new(),
+            action: Action::Rest,
+        }];
+        turn
+    }
+
+    #[test]
+    fn test_house_rules_relax_validation_and_permadeath() {
+        let mut engine = create_test_engine();
+        let standard = pipeline(&engine);
+        let mut turn = rest_only(free_text_turn(&engine, "我要立刻突破"));
+        assert!(standard.validate(&mut turn).is_err());
+
+        let mut state = engine.get_current_state().unwrap();
+        state.house_rules = HouseRules {
+            skip_reasonableness_check: true,
+            disable_permadeath: true,
+            ..HouseRules::default()
+        };
+        state.player.stats.lifespan.current_age = state.player.stats.lifespan.total_max_age();
+        engine.update_current_state(state).unwrap();
+
+        let relaxed = pipeline(&engine);
+        let mut turn = rest_only(free_text_turn(&engine, "我要立刻突破"));
+        relaxed.validate(&mut turn).unwrap();
+        relaxed.resolve(&mut turn);
+
+        assert!(turn.game_state.player.stats.lifespan.is_alive());
+        assert!(turn
+            .action_result
+            .as_ref()
+            .unwrap()
+            .events
+            .iter()
+            .any(|e| e.contains("续命")));
+    }
+
+    #[test]
+    fn test_year_rollover_ages_player_until_death() {
+        let engine = create_test_engine();
+        let pipeline = pipeline(&engine);
+        let mut turn = option_turn(&engine, Action::Rest);
+        turn.game_state.game_time = GameTime::new(1, 12, 30);
+        let lifespan = &mut turn.game_state.player.stats.lifespan;
+        lifespan.current_age = lifespan.total_max_age() - 1;
+
+        pipeline.validate(&mut turn).unwrap();
+        pipeline.resolve(&mut turn);
+        pipeline.react(&mut turn);
+
+        let stats = &turn.game_state.player.stats;
+        assert!(!stats.lifespan.is_alive());
+        let result = turn.action_result.as_ref().unwrap();
+        assert!(result.stat_changes.iter().any(|c| c.stat_name == "age"));
+        assert!(result.events.iter().any(|e| e.contains("坐化")));
+        assert_eq!(turn.log_entry.as_ref().unwrap().event_type, LIFESPAN_EXHAUSTED_EVENT);
+        assert_eq!(lifespan_warning(&stats.lifespan).unwrap(), "寿元已尽");
+
+        let mut next = option_turn(&engine, Action::Rest);
+        next.game_state = turn.game_state.clone();
+        assert!(pipeline.validate(&mut next).unwrap_err().contains("寿元已尽"));
+    }
+
+    #[test]
+    fn test_longevity_pill_is_offered_and_consumed() {
+        let engine = create_test_engine();
+        let pipeline = pipeline(&engine);
+        let pill = Item {
+            id: "longevity_pill".to_string(),
+            name: "延寿丹".to_string(),
+            description: String::new(),
+            item_type: ItemType::Medicine,
+            lifespan_bonus: 20,
+        };
+        let mut options = Vec::new();
+        attach_longevity_option(&mut options, std::slice::from_ref(&pill));
+        assert_eq!(options.len(), 1);
+        let action = options[0].action.clone();
+
+        let mut turn = option_turn(&engine, action);
+        turn.game_state.player.inventory.push(pill);
+        let lifespan = &mut turn.game_state.player.stats.lifespan;
+        lifespan.current_age = lifespan.total_max_age() - 5;
+        assert!(lifespan_warning(lifespan).unwrap().contains("寿元将尽"));
+        let max_age = lifespan.total_max_age();
+
+        pipeline.validate(&mut turn).unwrap();
+        pipeline.resolve(&mut turn);
+
+        assert!(turn.game_state.player.inventory.is_empty());
+        assert_eq!(turn.game_state.player.stats.lifespan.total_max_age(), max_age + 20);
+        assert!(turn.action_result.unwrap().description.contains("寿元增加 20 年"));
+    }
+
+    fn guaranteed_table(source: DropSource) -> DropTable {
+        DropTable {
+            id: "test_table".to_string(),
+            source,
+            rolls: 1,
+            empty_weight: 0,
+            entries: vec![DropEntry {
+                item_id: "low_spirit_stone".to_string(),
+                name: "一枚下品灵石".to_string(),
+                description: String::new(),
+                item_type: ItemType::Material,
+                weight: 1,
+                rarity: DropRarity::Common,
+                lifespan_bonus: 0,
+            }],
+            pity_threshold: None,
+        }
+    }
+
+    #[test]
+    fn test_resolve_combat_victory_rolls_enemy_loot() {
+        let engine = create_test_engine();
+        let pipeline = pipeline(&engine);
+        let mut turn = option_turn(
+            &engine,
+            Action::Combat {
+                target_id: "wolf".to_string(),
+            },
+        );
+        turn.game_state.script.drop_tables = vec![guaranteed_table(DropSource::EnemyTier { tier: 1 })];
+
+        pipeline.validate(&mut turn).unwrap();
+        pipeline.resolve(&mut turn);
+
+        assert_eq!(turn.game_state.player.inventory.len(), 1);
+        let result = turn.action_result.unwrap();
+        assert!(result.description.contains("拾得一枚下品灵石"));
+        assert!(result.events.iter().any(|e| e == "拾得一枚下品灵石"));
+    }
+
+    #[test]
+    fn test_breakthrough_spends_spirit_stones_and_combat_earns_them() {
+        let engine = create_test_engine();
+        let pipeline = pipeline(&engine);
+        let mut turn = option_turn(&engine, Action::Breakthrough);
+        assert_eq!(turn.game_state.resources.spirit_stones, 100);
+        turn.game_state.player.stats.spiritual_root.affinity = 1.0;
+        turn.game_state.player.stats.insight = MAX_INSIGHT;
+
+        pipeline.validate(&mut turn).unwrap();
+        pipeline.resolve(&mut turn);
+        assert_eq!(turn.game_state.resources.spirit_stones, 90);
+        assert_eq!(turn.game_state.player.stats.insight, 0);
+        assert!(turn
+            .action_result
+            .as_ref()
+            .unwrap()
+            .events
+            .iter()
+            .any(|e| e.contains("心得")));
+        assert!(turn
+            .action_result
+            .as_ref()
+            .unwrap()
+            .stat_changes
+            .iter()
+            .any(|c| c.stat_name == "spirit_stones" && c.new_value == "90"));
+
+        let mut broke = option_turn(&engine, Action::Breakthrough);
+        broke.game_state.resources.spirit_stones = 5;
+        assert!(pipeline.preview(&broke).warnings[0].contains("灵石需要 10，现有 5"));
+        assert!(pipeline.validate(&mut broke).unwrap_err().contains("资源不足"));
+
+        let mut combat = option_turn(
+            &engine,
+            Action::Combat {
+                target_id: "wolf".to_string(),
+            },
+        );
+        pipeline.validate(&mut combat).unwrap();
+        pipeline.resolve(&mut combat);
+        assert_eq!(combat.game_state.resources.spirit_stones, 115);
+    }
+
+    #[test]
+    fn test_resolve_free_text_exploration_rolls_location_loot() {
+        let engine = create_test_engine();
+        let pipeline = pipeline(&engine);
+        let source = DropSource::Location {
+            location_id: "sect".to_string(),
+        };
+
+        let mut turn = free_text_turn(&engine, "在后山探索一番");
+        turn.game_state.script.drop_tables = vec![guaranteed_table(source.clone())];
+        pipeline.validate(&mut turn).unwrap();
+        pipeline.resolve(&mut turn);
+        assert_eq!(turn.game_state.player.inventory.len(), 1);
+
+        let mut idle = free_text_turn(&engine, "静静打坐");
+        idle.game_state.script.drop_tables = vec![guaranteed_table(source)];
+        pipeline.validate(&mut idle).unwrap();
+        pipeline.resolve(&mut idle);
+        assert!(idle.game_state.player.inventory.is_empty());
+    }
+
+    fn select_option(engine: &GameEngine, idx: usize) -> Turn {
+        let plot_state = engine.get_plot_state().unwrap();
+        let uid = plot_state.current_scene.available_options[idx].uid.clone();
+        Turn::new(
+            PlayerAction {
+                action_type: ActionType::SelectedOption,
+                content: String::new(),
+                selected_option_id: Some(idx),
+                selected_option_uuid: Some(uid),
+                meta: None,
+            },
+            engine.get_current_state().unwrap(),
+            plot_state,
+        )
+        .with_sequence(engine.turn_sequence())
+    }
+
+    async fn play(pipeline: &TurnPipeline, mut turn: Turn, engine: &mut GameEngine) {
+        pipeline.validate(&mut turn).unwrap();
+        pipeline.resolve(&mut turn);
+        pipeline.narrate(&mut turn).await;
+        pipeline.react(&mut turn);
+        pipeline.regenerate_options(&mut turn);
+        pipeline.commit(turn, engine).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_secret_realm_side_story_uses_realm_loot_and_returns_to_main_plot() {
+        let mut engine = create_test_engine();
+        let mut state = engine.get_current_state().unwrap();
+        state.script.secret_realms = vec![SecretRealm {
+            id: "sword_cave".to_string(),
+            name: "剑冢".to_string(),
+            description: "石壁上刻满残缺的剑痕。".to_string(),
+            location_id: "sect".to_string(),
+            min_realm_level: 0,
+            max_turns: 2,
+        }];
+        state.script.drop_tables = vec![guaranteed_table(DropSource::SecretRealm {
+            realm_id: "sword_cave".to_string(),
+        })];
+        engine.update_current_state(state).unwrap();
+        let mut main_plot = engine.get_plot_state().unwrap();
+        attach_entry_options(
+            &mut main_plot.current_scene.available_options,
+            &engine.get_current_state().unwrap().script.secret_realms,
+            &engine.get_current_state().unwrap(),
+        );
+        engine.update_plot_state(main_plot.clone()).unwrap();
+        let pipeline = pipeline(&engine);
+
+        let entry = main_plot.current_scene.available_options.len() - 1;
+        play(&pipeline, select_option(&engine, entry), &mut engine).await;
+        assert!(engine.in_side_story());
+        let realm_plot = engine.get_plot_state().unwrap();
+        assert_eq!(realm_plot.side_story.as_ref().unwrap().turns_left, 2);
+        assert_eq!(realm_plot.current_scene.available_options.len(), 3);
+        assert!(pipeline
+            .validate(&mut free_text_turn(&engine, "四处走走"))
+            .unwrap_err()
+            .contains("秘境"));
+
+        play(&pipeline, select_option(&engine, 0), &mut engine).await;
+        let realm_plot = engine.get_plot_state().unwrap();
+        assert_eq!(realm_plot.side_story.as_ref().unwrap().turns_left, 1);
+        assert_eq!(realm_plot.last_option_generation_source.as_deref(), Some("side_story"));
+        assert_eq!(engine.get_current_state().unwrap().player.inventory.len(), 1);
+
+        // 回合耗尽，被送出秘境
+        play(&pipeline, select_option(&engine, 1), &mut engine).await;
+        assert!(!engine.in_side_story());
+        let main_plot = engine.get_plot_state().unwrap();
+        assert!(main_plot.side_story.is_none());
+        assert!(main_plot
+            .current_chapter
+            .content
+            .iter()
+            .any(|text| text.starts_with("秘境闭合") && text.contains("一枚下品灵石")));
+        assert!(main_plot
+            .current_scene
+            .available_options
+            .iter()
+            .all(|option| !option.description.contains("剑冢")));
+        assert_eq!(engine.get_current_state().unwrap().player.inventory.len(), 1);
+    }
+
+    #[test]
+    fn test_resolve_records_karma_from_deeds() {
+        let engine = create_test_engine();
+        let pipeline = pipeline(&engine);
+
+        let mut turn = free_text_turn(&engine, "出手救下被山匪围攻的村民");
+        pipeline.validate(&mut turn).unwrap();
+        pipeline.resolve(&mut turn);
+        let karma = &turn.game_state.karma;
+        assert_eq!(karma.score, 6);
+        assert_eq!(karma.recent[0].alignment, Alignment::Righteous);
+        assert_eq!(karma.recent[0].source, "出手救下被山匪围攻的村民");
+
+        let mut idle = free_text_turn(&engine, "静静打坐");
+        pipeline.validate(&mut idle).unwrap();
+        pipeline.resolve(&mut idle);
+        assert_eq!(idle.game_state.karma, Karma::default());
+    }
+
+    #[tokio::test]
+    async fn test_narrate_appends_segment() {
+        let engine = create_test_engine();
+        let pipeline = pipeline(&engine);
+        let mut turn = free_text_turn(&engine, "I meditate under the waterfall");
+        let old_segments = turn.plot_state.plot_history.len();
+
+        pipeline.validate(&mut turn).unwrap();
+        pipeline.resolve(&mut turn);
+        pipeline.narrate(&mut turn).await;
+
+        assert!(turn.plot_update.is_some());
+        assert!(turn.plot_state.last_action_result.is_some());
+        assert!(turn.plot_state.plot_history.len() > old_segments);
+        assert_eq!(
+            turn.plot_state.last_generation_failure,
+            turn.plot_update.as_ref().unwrap().generation_failure
+        );
+    }
+
+    #[tokio::test]
+    async fn test_narrate_records_segment_provenance() {
+        let engine = create_test_engine();
+        let pipeline = pipeline(&engine);
+        let mut turn = free_text_turn(&engine, "I meditate under the waterfall");
+
+        pipeline.validate(&mut turn).unwrap();
+        pipeline.resolve(&mut turn);
+        pipeline.narrate(&mut turn).await;
+
+        let chapter = &turn.plot_state.current_chapter;
+        let provenance = chapter.provenance.last().unwrap();
+        assert_eq!(provenance.segment_index, chapter.content.len() - 1);
+        assert!(provenance.fallback.is_some());
+        assert!(provenance
+            .validator_verdicts
+            .iter()
+            .any(|verdict| verdict.validator == FACTS_VALIDATOR));
+        assert_eq!(
+            turn.plot_update.as_ref().unwrap().provenance.as_ref(),
+            Some(provenance)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_narrate_redacts_content_that_survives_regeneration() {
+        let engine = create_test_engine();
+        let mut first = free_text_turn(&engine, "I meditate under the waterfall");
+        let pipeline = pipeline(&engine);
+        pipeline.validate(&mut first).unwrap();
+        pipeline.resolve(&mut first);
+        pipeline.narrate(&mut first).await;
+        let blocked = first
+            .plot_update
+            .unwrap()
+            .plot_text
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .take(2)
+            .collect::<String>();
+
+        let pipeline = TurnPipeline::new(PlotEngine::new()).with_content_filter(
+            ContentFilterSettings {
+                blocked_words: vec![blocked.clone()],
+                ..ContentFilterSettings::default()
+            },
+        );
+        let mut turn = free_text_turn(&engine, "I meditate under the waterfall");
+        pipeline.validate(&mut turn).unwrap();
+        pipeline.resolve(&mut turn);
+        pipeline.narrate(&mut turn).await;
+
+        let plot_update = turn.plot_update.as_ref().unwrap();
+        assert!(!plot_update.plot_text.contains(&blocked));
+        assert!(turn.plot_state.current_chapter.content.last().unwrap().contains("□□"));
+        let verdict = plot_update
+            .provenance
+            .as_ref()
+            .unwrap()
+            .validator_verdicts
+            .iter()
+            .find(|verdict| verdict.validator == CONTENT_VALIDATOR)
+            .unwrap();
+        assert!(!verdict.passed);
+        assert!(turn
+            .plot_state
+            .last_generation_diagnostics
+            .as_ref()
+            .is_some_and(|diagnostics| diagnostics.summary().contains("内容过滤")));
+    }
+
+    #[tokio::test]
+    async fn test_tight_token_budget_skips_content_regeneration() {
+        let engine = create_test_engine();
+        let mut first = free_text_turn(&engine, "I meditate under the waterfall");
+        pipeline(&engine).validate(&mut first).unwrap();
+        pipeline(&engine).resolve(&mut first);
+        pipeline(&engine).narrate(&mut first).await;
+        let blocked = first
+            .plot_update
+            .unwrap()
+            .plot_text
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .take(2)
+            .collect::<String>();
+
+        let pipeline = TurnPipeline::new(PlotEngine::new())
+            .with_content_filter(ContentFilterSettings {
+                blocked_words: vec![blocked.clone()],
+                ..ContentFilterSettings::default()
+            })
+            .with_token_budget(TokenBudget::new(0));
+        let mut turn = free_text_turn(&engine, "I meditate under the waterfall");
+        pipeline.validate(&mut turn).unwrap();
+        pipeline.resolve(&mut turn);
+        pipeline.narrate(&mut turn).await;
+
+        assert!(!turn.plot_update.as_ref().unwrap().plot_text.contains(&blocked));
+        assert!(turn
+            .plot_state
+            .last_generation_diagnostics
+            .as_ref()
+            .is_some_and(|diagnostics| diagnostics.summary().contains("跳过内容过滤重写")));
+    }
+
+    #[tokio::test]
+    async fn test_narrate_plans_story_arc_before_first_segment() {
+        let engine = create_test_engine();
+        let pipeline = pipeline(&engine);
+        let mut turn = free_text_turn(&engine, "I meditate under the waterfall");
+        assert!(turn.plot_state.story_arc.is_none());
+
+        pipeline.validate(&mut turn).unwrap();
+        pipeline.resolve(&mut turn);
+        pipeline.narrate(&mut turn).await;
+
+        let arc = turn.plot_state.story_arc.as_ref().unwrap();
+        assert_eq!(arc.index, 1);
+        assert_eq!(arc.start_chapter, turn.plot_state.current_chapter.index);
+        assert!(turn
+            .plot_state
+            .last_generation_diagnostics
+            .as_ref()
+            .unwrap()
+            .warnings
+            .iter()
+            .any(|warning| warning.contains("故事弧规划：第1弧")));
+    }
+
+    #[tokio::test]
+    async fn test_narrate_plans_chapter_outline_when_enabled() {
+        let engine = create_test_engine();
+        let pipeline = pipeline(&engine);
+        let mut turn = free_text_turn(&engine, "I meditate under the waterfall");
+        turn.plot_state.settings.outline_planning = true;
+
+        pipeline.validate(&mut turn).unwrap();
+        pipeline.resolve(&mut turn);
+        pipeline.narrate(&mut turn).await;
+
+        let outline = turn.plot_state.current_chapter.outline.as_ref().unwrap();
+        assert_eq!(outline.beats_completed, 1);
+        assert!(turn
+            .plot_state
+            .chapter_beat_line()
+            .unwrap()
+            .starts_with("本章大纲节拍 2/"));
+        assert!(turn
+            .plot_state
+            .last_generation_diagnostics
+            .as_ref()
+            .unwrap()
+            .warnings
+            .iter()
+            .any(|warning| warning.starts_with("章节大纲规划：第")));
+    }
+
+    #[test]
+    fn test_react_logs_breakthrough_as_important() {
+        let engine = create_test_engine();
+        let pipeline = pipeline(&engine);
+        let mut turn = option_turn(&engine, Action::Breakthrough);
+        turn.game_state.player.stats.spiritual_root.affinity = 0.1;
+
+        pipeline.validate(&mut turn).unwrap();
+        pipeline.react(&mut turn);
+
+        let entry = turn.log_entry.unwrap();
+        assert_eq!(entry.event_type, "breakthrough_attempt");
+        assert_eq!(entry.importance, EventImportance::Important);
+
+        let mut turn = option_turn(&engine, Action::Breakthrough);
+        pipeline.validate(&mut turn).unwrap();
+        assert!(turn.action_result.as_ref().unwrap().success);
+        pipeline.react(&mut turn);
+        assert_eq!(turn.log_entry.unwrap().event_type, BREAKTHROUGH_EVENT);
+    }
+
+    #[test]
+    fn test_peak_breakthrough_runs_tribulation() {
+        let engine = create_test_engine();
+        let pipeline = pipeline(&engine);
+        let mut turn = option_turn(&engine, Action::Breakthrough);
+        turn.game_state.player.stats.cultivation_realm.sub_level = PEAK_SUB_LEVEL;
+        // 灵根亲和度与悟性俱满时冲关必定成功，只看天劫
+        turn.game_state.player.stats.spiritual_root.affinity = 1.0;
+        turn.game_state.player.stats.insight = MAX_INSIGHT;
+        let before = turn.game_state.player.stats.clone();
+
+        let preview = pipeline.preview(&turn);
+        assert!(preview.warnings.iter().any(|w| w.contains("Foundation Establishment")));
+
+        pipeline.validate(&mut turn).unwrap();
+        pipeline.resolve(&mut turn);
+
+        let result = turn.action_result.as_ref().unwrap();
+        let stats = &turn.game_state.player.stats;
+        assert!(result.events.iter().any(|e| e.contains("劫云压顶")));
+        if result.success {
+            assert_eq!(stats.cultivation_realm.name, "Foundation Establishment");
+            assert_eq!(stats.cultivation_realm.sub_level, 0);
+            assert!(stats.lifespan.total_max_age() > before.lifespan.total_max_age());
+        } else {
+            assert_eq!(stats.cultivation_realm.level, before.cultivation_realm.level);
+            assert!(
+                stats.cultivation_realm.sub_level < PEAK_SUB_LEVEL
+                    || stats.lifespan.current_age > before.lifespan.current_age
+            );
+            assert!(!stats.status_effects.is_empty());
+        }
+        assert!(result.description.contains("Foundation Establishment"));
+    }
+
+    #[test]
+    fn test_react_logs_free_text() {
+        let engine = create_test_engine();
+        let pipeline = pipeline(&engine);
+        let mut turn = free_text_turn(&engine, "I look around");
+
+        pipeline.react(&mut turn);
+
+        let entry = turn.log_entry.unwrap();
+        assert_eq!(entry.event_type, "player_free_text");
+        assert_eq!(entry.message, "I look around");
+    }
+
+    fn pending_duel(turn: &mut Turn, combat_power: u64, issued_day: u32) {
+        let mut challenger_stats = turn.game_state.player.stats.clone();
+        challenger_stats.combat_power = combat_power;
+        turn.game_state.world_state.duel_board.issue(DuelChallenge {
+            id: 0,
+            challenger_id: "rival".to_string(),
+            challenger_name: "赵烈".to_string(),
+            challenger_stats,
+            stake: DuelStake::Face,
+            rivalry: 0.8,
+            issued_day,
+            faction_id: Some("sect".to_string()),
+        });
+    }
+
+    #[test]
+    fn test_accepted_duel_is_settled_by_combat_power() {
+        let engine = create_test_engine();
+        let pipeline = pipeline(&engine);
+        let mut turn = option_turn(
+            &engine,
+            Action::Combat {
+                target_id: "rival".to_string(),
+            },
+        );
+        let today = turn.game_state.game_time.total_days;
+        pending_duel(&mut turn, 0, today);
+
+        pipeline.validate(&mut turn).unwrap();
+        pipeline.resolve(&mut turn);
+        pipeline.react(&mut turn);
+
+        assert_eq!(turn.duel_outcomes.len(), 1);
+        assert_eq!(turn.duel_outcomes[0].result, DuelResult::Won);
+        assert!(turn.action_result.as_ref().unwrap().success);
+        assert!(turn.game_state.world_state.duel_board.pending.is_empty());
+        assert_eq!(turn.game_state.world_state.faction_reputation["sect"], 3);
+        assert_eq!(turn.log_entry.unwrap().event_type, "duel");
+    }
+
+    #[test]
+    fn test_ignored_duel_expires_as_declined() {
+        let engine = create_test_engine();
+        let pipeline = pipeline(&engine);
+        let mut turn = option_turn(&engine, Action::Rest);
+        let today = turn.game_state.game_time.total_days;
+        pending_duel(&mut turn, 100, today);
+        turn.game_state.game_time.advance_days(3);
+
+        pipeline.validate(&mut turn).unwrap();
+        pipeline.resolve(&mut turn);
+
+        assert_eq!(turn.duel_outcomes[0].result, DuelResult::Declined);
+        assert_eq!(turn.game_state.world_state.faction_reputation["sect"], -2);
+        assert!(turn
+            .action_result
+            .as_ref()
+            .unwrap()
+            .events
+            .iter()
+            .any(|e| e.contains("怯战")));
+    }
+
+    #[tokio::test]
+    async fn test_regenerate_options_records_source() {
+        let engine = create_test_engine();
+        let pipeline = pipeline(&engine);
+        let mut turn = free_text_turn(&engine, "I look around");
+
+        pipeline.validate(&mut turn).unwrap();
+        pipeline.resolve(&mut turn);
+        pipeline.narrate(&mut turn).await;
+        pipeline.regenerate_options(&mut turn);
+
+        let source = turn.option_source.clone().unwrap();
+        assert_eq!(
+            turn.plot_state.last_option_generation_source.as_deref(),
+            Some(source.as_str())
+        );
+        let diagnostics = turn.plot_state.last_generation_diagnostics.as_ref().unwrap();
+        assert_eq!(diagnostics.option_source.as_deref(), Some(source.as_str()));
+        assert_eq!(diagnostics.parse_path, Some(ParsePath::Preset));
+    }
+
+    #[tokio::test]
+    async fn test_prefetched_options_are_used_when_narration_brings_none() {
+        let engine = create_test_engine();
+        let pipeline = pipeline(&engine);
+        let mut turn = free_text_turn(&engine, "I look around");
+
+        pipeline.validate(&mut turn).unwrap();
+        pipeline.resolve(&mut turn);
+        pipeline.narrate(&mut turn).await;
+        let prefetched = ["拜访坊市", "闭关修炼"]
+            .iter()
+            .enumerate()
+            .map(|(idx, text)| PlayerOption {
+                id: idx,
+                uid: new_option_uid(),
+                description: text.to_string(),
+                requirements: vec![],
+                action: Action::Custom {
+                    description: text.to_string(),
+                },
+            })
+            .collect::<Vec<PlayerOption>>();
+        turn.prefetched_options = Some(prefetched.clone());
+        pipeline.regenerate_options(&mut turn);
+
+        assert_eq!(turn.option_source.as_deref(), Some("llm_prefetched"));
+        assert!(turn.prefetched_options.is_none());
+        let descriptions = turn
+            .plot_state
+            .current_scene
+            .available_options
+            .iter()
+            .map(|option| option.description.as_str())
+            .collect::<Vec<&str>>();
+        assert!(descriptions.contains(&"拜访坊市"));
+        assert!(descriptions.contains(&"闭关修炼"));
+    }
+
+    #[tokio::test]
+    async fn test_options_are_requested_alongside_the_segment_by_default() {
+        let engine = create_test_engine();
+        let mut turn = free_text_turn(&engine, "I look around");
+        assert!(turn.plot_state.active_outline().is_none());
+        assert!(wants_option_prefetch(&turn.plot_state));
+
+        // 两个请求各自等到对方开始后才返回，串行执行会卡死在第一个请求上
+        let barrier = std::sync::Arc::new(tokio::sync::Barrier::new(2));
+        let segment = {
+            let barrier = barrier.clone();
+            async move {
+                barrier.wait().await;
+                "segment"
+            }
+        };
+        let options = async move {
+            barrier.wait().await;
+            Some(Vec::new())
+        };
+        let (segment, prefetched) = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            segment_with_options(segment, Some(options)),
+        )
+        .await
+        .expect("segment and option requests must overlap");
+        assert_eq!(segment, "segment");
+        assert_eq!(prefetched, Some(Vec::new()));
+
+        turn.plot_state.side_story = Some(SideStory {
+            realm_id: "sword_cave".to_string(),
+            realm_name: "剑冢".to_string(),
+            max_turns: 2,
+            turns_left: 2,
+            entered_day: 0,
+            entry_combat_power: 0,
+            entry_items: Vec::new(),
+        });
+        assert!(!wants_option_prefetch(&turn.plot_state));
+    }
+
+    #[tokio::test]
+    async fn test_segment_options_take_precedence_over_prefetched_ones() {
+        let engine = create_test_engine();
+        let pipeline = pipeline(&engine);
+        let mut turn = free_text_turn(&engine, "I look around");
+
+        pipeline.validate(&mut turn).unwrap();
+        pipeline.resolve(&mut turn);
+        pipeline.narrate(&mut turn).await;
+        let option = |text: &str| PlayerOption {
+            id: 0,
+            uid: new_option_uid(),
+            description: text.to_string(),
+            requirements: vec![],
+            action: Action::Custom {
+                description: text.to_string(),
+            },
+        };
+        turn.prefetched_options = Some(vec![option("闭关修炼")]);
+        let plot_update = turn.plot_update.as_mut().unwrap();
+        plot_update.is_waiting_for_input = true;
+        plot_update.available_options = vec![option("side with the elders")];
+        pipeline.regenerate_options(&mut turn);
+
+        assert_eq!(turn.option_source.as_deref(), Some("llm_structured"));
+        assert_eq!(
+            turn.plot_state.current_scene.available_options[0].description,
+            "side with the elders"
+        );
+    }
+
+    #[test]
+    fn test_commit_requires_narration() {
+        let mut engine = create_test_engine();
+        let pipeline = pipeline(&engine);
+        let turn = free_text_turn(&engine, "I look around");
+
+        assert!(pipeline.commit(turn, &mut engine).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_run_commits_state_to_engine() {
+        let engine = create_test_engine();
+        let pipeline = pipeline(&engine);
+        let turn = option_turn(&engine, Action::Cultivate);
+        let old_days = turn.game_state.game_time.total_days;
+        let engine = RwLock::new(engine);
+
+        let text = pipeline.run(turn, &engine).await.unwrap();
+
+        let engine = engine.read().await;
+        let state = engine.get_current_state().unwrap();
+        assert!(!text.is_empty());
+        assert_eq!(state.game_time.total_days, old_days + 1);
+        assert_eq!(state.action_count, 1);
+        assert!(engine.get_plot_state().unwrap().last_action_result.is_some());
+        assert!(state
+            .stat_history
+            .iter()
+            .any(|entry| entry.source == "test option" && entry.change.stat_name == "combat_power"));
+    }
+
+    #[tokio::test]
+    async fn test_background_npc_drain_does_not_void_the_turn_in_flight() {
+        let mut script = create_test_script();
+        script.world_setting.npcs = serde_json::from_value(serde_json::json!([{
+            "id": "elder_yan",
+            "name": "Elder Yan",
+            "traits": ["Aggressive"]
+        }]))
+        .unwrap();
+        let mut engine = engine_for(script);
+        let mut plot_state = engine.get_plot_state().unwrap();
+        plot_state.settings.npc_interruptions = true;
+        engine.update_plot_state(plot_state).unwrap();
+        let pipeline = pipeline(&engine);
+        let turn = option_turn(&engine, Action::Cultivate);
+        let old_days = turn.game_state.game_time.total_days;
+        let engine = RwLock::new(engine);
+
+        {
+            let mut engine = engine.write().await;
+            let events = vec!["主角在宗门大比上当众羞辱执法弟子".to_string()];
+            engine.process_npc_reactions_for_events(&events).unwrap();
+            assert!(!engine.take_npc_notices().is_empty());
+        }
+        pipeline.run(turn, &engine).await.unwrap();
+
+        let engine = engine.read().await;
+        assert_eq!(
+            engine.get_current_state().unwrap().game_time.total_days,
+            old_days + 1
+        );
+        let plot = engine.get_plot_state().unwrap();
+        assert!(plot.npc_interruption.is_some());
+        assert!(plot.is_waiting_for_input);
+        assert!(plot
+            .current_scene
+            .available_options
+            .iter()
+            .any(|option| option
+                .requirements
+                .iter()
+                .any(|r| r == crate::npc_alerts::INTERRUPTION_REQUIREMENT)));
+    }
+
+    #[tokio::test]
+    async fn test_scripted_npc_secret_is_revealed_after_a_turn() {
+        let mut script = create_test_script();
+        script.world_setting.npcs = serde_json::from_value(serde_json::json!([{
+            "id": "elder_mo",
+            "name": "Elder Mo",
+            "relationships": [{ "target_id": "player", "trust": 40 }],
+            "secrets": [{
+                "id": "true_realm",
+                "kind": { "HiddenRealm": { "name": "Foundation Establishment", "level": 2, "sub_level": 0, "power_multiplier": 2.0 } },
+                "triggers": [{ "TrustAtLeast": { "target_id": "player", "threshold": 40 } }]
+            }]
+        }]))
+        .unwrap();
+        let engine = engine_for(script);
+        let pipeline = pipeline(&engine);
+        let turn = free_text_turn(&engine, "I meditate and look around");
+        let engine = RwLock::new(engine);
+
+        pipeline.run(turn, &engine).await.unwrap();
+
+        let mut engine = engine.write().await;
+        assert!(engine.pending_npc_events() > 0);
+        engine.drain_npc_inbox().unwrap();
+        let elder = engine.dialogue_partner("elder_mo").unwrap().0;
+        assert_eq!(elder.revealed_secrets().len(), 1);
+        assert_eq!(elder.stats.cultivation_realm.level, 2);
+        assert!(engine
+            .get_current_state()
+            .unwrap()
+            .event_history
+            .iter()
+            .any(|e| e.event_type.as_ref() == "secret_revealed"));
+    }
+
+    #[tokio::test]
+    async fn test_run_rejects_turn_when_state_changed_during_generation() {
+        let engine = create_test_engine();
+        let pipeline = pipeline(&engine);
+        let turn = option_turn(&engine, Action::Cultivate);
+        let old_days = turn.game_state.game_time.total_days;
+        let engine = RwLock::new(engine);
+
+        {
+            let mut engine = engine.write().await;
+            let mut plot_state = engine.get_plot_state().unwrap();
+            plot_state.settings.novel_style = "古典章回体".to_string();
+            engine.update_plot_state(plot_state).unwrap();
+        }
+        let error = pipeline.run(turn, &engine).await.unwrap_err();
+
+        let engine = engine.read().await;
+        assert!(error.contains("本回合未提交"));
+        assert_eq!(engine.get_current_state().unwrap().game_time.total_days, old_days);
+        assert_eq!(engine.get_plot_state().unwrap().settings.novel_style, "古典章回体");
+
+        let retry = option_turn(&engine, Action::Cultivate);
+        assert_eq!(retry.sequence, engine.turn_sequence());
+    }
+
+    #[tokio::test]
+    async fn test_due_timeline_event_reaches_narration_and_event_log() {
+        let mut engine = create_test_engine();
+        let pipeline = pipeline(&engine);
+        let mut turn = option_turn(&engine, Action::Rest);
+        turn.game_state.script.world_setting.timeline_events = vec![crate::script::TimelineEvent {
+            id: "sect_war".to_string(),
+            name: "宗门大战".to_string(),
+            description: "正魔两道在青云山下开战".to_string(),
+            year: 1,
+            month: 1,
+            power_changes: std::collections::HashMap::from([("sect".to_string(), -20)]),
+        }];
+        turn.game_state.script.world_setting.factions.push(crate::script::Faction {
+            id: "sect".to_string(),
+            name: "青云宗".to_string(),
+            description: String::new(),
+            power_level: 50,
+        });
+
+        pipeline.validate(&mut turn).unwrap();
+        pipeline.resolve(&mut turn);
+        assert_eq!(turn.world_events.len(), 1);
+        assert_eq!(
+            turn.faction_changes[0].description,
+            "势力消长（宗门大战）：青云宗 -20"
+        );
+        assert!(turn
+            .action_result
+            .as_ref()
+            .unwrap()
+            .events
+            .contains(&"世界大事：宗门大战——正魔两道在青云山下开战".to_string()));
+        pipeline.narrate(&mut turn).await;
+        pipeline.react(&mut turn);
+        pipeline.regenerate_options(&mut turn);
+        pipeline.commit(turn, &mut engine).unwrap();
+
+        let state = engine.get_current_state().unwrap();
+        assert_eq!(state.world_state.global_events[0].id, "sect_war");
+        assert_eq!(state.world_state.faction_standings.power["sect"], 30);
+        assert!(engine
+            .full_event_history()
+            .unwrap()
+            .iter()
+            .any(|e| e.event_type.as_ref() == WORLD_EVENT && e.description.contains("宗门大战")));
+    }
+
+    #[tokio::test]
+    async fn test_npc_events_are_queued_and_digest_stays_out_of_events() {
+        let mut engine = create_test_engine();
+        let pipeline = pipeline(&engine);
+        let mut turn = free_text_turn(&engine, "I meditate under the waterfall")
+            .with_npc_digest(vec!["林长老：暗中观察".to_string()]);
+
+        pipeline.validate(&mut turn).unwrap();
+        pipeline.resolve(&mut turn);
+        pipeline.narrate(&mut turn).await;
+        let update = turn.plot_update.as_ref().unwrap();
+        assert!(!update.triggered_events.iter().any(|e| e.contains("暗中观察")));
+        let triggered = update.triggered_events.len();
+
+        pipeline.commit(turn, &mut engine).unwrap();
+
+        assert_eq!(engine.pending_npc_events(), triggered);
+        assert!(!engine
+            .get_current_state()
+            .unwrap()
+            .event_history
+            .iter()
+            .any(|e| e.event_type.as_ref() == "npc_reaction"));
+    }
+}
+
+#[cfg(test)]
+mod property_tests {
+    use super::*;
+    use crate::models::CultivationRealm;
+    use crate::script::{Climate, Location, LocationKind};
+    use crate::script_manager::ScriptManager;
+    use proptest::prelude::*;
+
+    const FREE_TEXTS: &[&str] = &["四处走走", "静静打坐", "在后山探索一番", "与同门师兄闲聊"];
+
+    /// 玩家的一步操作
+    #[derive(Debug, Clone)]
+    enum Step {
+        /// 按序号选择当前选项，没有选项时改为自由输入
+        Choose(usize),
+        FreeText(&'static str),
+    }
+
+    fn arb_step() -> impl Strategy<Value = Step> {
+        prop_oneof![
+            (0usize..5).prop_map(Step::Choose),
+            proptest::sample::select(FREE_TEXTS).prop_map(Step::FreeText),
+        ]
+    }
+
+    /// 回合流水线的简化参照模型，只跟踪时间、行动数、小层级与已完结章节数
+    #[derive(Debug, Clone, PartialEq)]
+    struct TurnModel {
+        total_days: u32,
+        action_count: u32,
+        sub_level: u32,
+        finished_chapters: usize,
+    }
+
+    impl TurnModel {
+        fn observe(engine: &GameEngine) -> Self {
+            let state = engine.get_current_state().unwrap();
+            Self {
+                total_days: state.game_time.total_days,
+                action_count: state.action_count,
+                sub_level: state.player.stats.cultivation_realm.sub_level,
+                finished_chapters: engine.get_plot_state().unwrap().chapters.len(),
+            }
+        }
+
+        /// 按所选行动推进一回合；突破的成败由判定决定，模型直接采用
+        fn step(&mut self, action: Option<&Action>, succeeded: bool, chapter_end: bool) {
+            self.total_days += 1;
+            self.action_count += 1;
+            if matches!(action, Some(Action::Breakthrough)) && succeeded && self.sub_level < 3 {
+                self.sub_level += 1;
+            }
+            if chapter_end {
+                self.finished_chapters += 1;
+            }
+        }
+    }
+
+    fn create_engine() -> GameEngine {
+        let mut script = ScriptManager::new().blank_script();
+        script
+            .world_setting
+            .cultivation_realms
+            .push(CultivationRealm::new("练气".to_string(), 1, 0, 1.0));
+        script.world_setting.locations.push(Location {
+            id: "sect".to_string(),
+            name: "青云宗".to_string(),
+            description: String::new(),
+            spiritual_energy: 1.0,
+            kind: LocationKind::Wilderness,
+            climate: Climate::Temperate,
+        });
+        script.initial_state.starting_location = "sect".to_string();
+        let mut engine = GameEngine::new();
+        engine.initialize_game(script).unwrap();
+        engine.initialize_plot().unwrap();
+
+        // 调低章节字数与交互次数，让短序列也能跨过章节边界
+        let mut plot_state = engine.get_plot_state().unwrap();
+        plot_state.settings.min_interactions_per_chapter = 1;
+        plot_state.settings.max_interactions_per_chapter = 2;
+        plot_state.settings.target_chapter_words_min = 60;
+        plot_state.settings.target_chapter_words_max = 120;
+        engine.update_plot_state(plot_state).unwrap();
+        engine
+    }
+
+    fn turn_for(engine: &GameEngine, step: &Step) -> Turn {
+        let plot_state = engine.get_plot_state().unwrap();
+        let option_count = plot_state.current_scene.available_options.len();
+        let action = match step {
+            Step::Choose(index) if option_count > 0 => PlayerAction {
+                action_type: ActionType::SelectedOption,
+                content: String::new(),
+                selected_option_id: Some(index % option_count),
+                selected_option_uuid: Some(
+                    plot_state.current_scene.available_options[index % option_count]
+                        .uid
+                        .clone(),
+                ),
+                meta: None,
+            },
+            Step::Choose(_) => PlayerAction {
+                action_type: ActionType::FreeText,
+                content: FREE_TEXTS[0].to_string(),
+                selected_option_id: None,
+                selected_option_uuid: None,
+                meta: None,
+            },
+            Step::FreeText(text) => PlayerAction {
+                action_type: ActionType::FreeText,
+                content: text.to_string(),
+                selected_option_id: None,
+                selected_option_uuid: None,
+                meta: None,
+            },
+        };
+        Turn::new(action, engine.get_current_state().unwrap(), plot_state)
+            .with_sequence(engine.turn_sequence())
+    }
+
+    /// 逐阶段执行一回合并与模型对照；被拒绝的行动不得改动引擎状态
+    fn run_step(
+        runtime: &tokio::runtime::Runtime,
+        pipeline: &TurnPipeline,
+        engine: &mut GameEngine,
+        model: &mut TurnModel,
+        step: &Step,
+    ) -> Result<(), TestCaseError> {
+        let mut turn = turn_for(engine, step);
+        if pipeline.validate(&mut turn).is_err() {
+            prop_assert_eq!(&TurnModel::observe(engine), model);
+            return Ok(());
+        }
+        let action = turn.selected_option.as_ref().map(|option| option.action.clone());
+        let old_power = turn.game_state.player.stats.combat_power;
+        let status_multiplier = turn
+            .game_state
+            .player
+            .stats
+            .status_effects
+            .cultivation_multiplier();
+        let efficiency = pipeline
+            .plot_engine
+            .numerical_system()
+            .location_efficiency(&action_context(&turn.game_state));
+
+        pipeline.resolve(&mut turn);
+        // 突破在结算时才掷定成败
+        let succeeded = turn.action_result.as_ref().is_some_and(|result| result.success);
+        runtime.block_on(pipeline.narrate(&mut turn));
+        pipeline.react(&mut turn);
+        pipeline.regenerate_options(&mut turn);
+        let update = turn.plot_update.clone().unwrap();
+        let text = pipeline.commit(turn, engine).unwrap();
+        prop_assert!(!text.trim().is_empty());
+
+        model.step(action.as_ref(), succeeded, update.chapter_end);
+        prop_assert_eq!(&TurnModel::observe(engine), model);
+
+        let state = engine.get_current_state().unwrap();
+        let stats = &state.player.stats;
+        // 修炼至少带来 3% 的增长并按走火入魔等状态与时令天气折算（低于境界底线时会被抬到底线），突破会重算战力，
+        // 修炼岔气或战斗负伤会折损战力，其余行动不改变战力
+        match action {
+            Some(Action::Cultivate | Action::Combat { .. }) if !succeeded => {
+                prop_assert!(
+                    stats.combat_power <= old_power.max(stats.calculate_base_combat_power())
+                );
+            }
+            Some(Action::Cultivate) => {
+                let gain = ((old_power as f32 * 0.03 * status_multiplier * efficiency).round() as u64)
+                    .max(1);
+                prop_assert!(stats.combat_power >= old_power + gain);
+            }
+            Some(Action::Breakthrough) => {}
+            _ => prop_assert_eq!(stats.combat_power, old_power),
+        }
+        prop_assert!(stats.combat_power > 0);
+        prop_assert!(stats.cultivation_realm.sub_level <= 3);
+        prop_assert!(stats.cultivation_realm.power_multiplier > 0.0);
+        prop_assert!((0.0..=1.0).contains(&stats.spiritual_root.affinity));
+        prop_assert!(stats.lifespan.is_alive());
+
+        let plot_state = engine.get_plot_state().unwrap();
+        let options = &plot_state.current_scene.available_options;
+        if update.chapter_end {
+            prop_assert_eq!(options.len(), 1);
+        } else if update.is_waiting_for_input {
+            prop_assert!((2..=5).contains(&options.len()), "{} options", options.len());
+            prop_assert!(options.iter().enumerate().all(|(idx, option)| option.id == idx));
+        } else {
+            prop_assert!(options.is_empty());
+        }
+
+        // 结束章节的那一段也计一次交互，已完结章节因此最多比上限多一次
+        let settings = &plot_state.settings;
+        let chapter = &plot_state.current_chapter;
+        prop_assert_eq!(chapter.index as usize, plot_state.chapters.len() + 1);
+        prop_assert!(chapter.interaction_count <= settings.max_interactions_per_chapter);
+        if chapter.word_count() >= settings.target_chapter_words_max as usize {
+            prop_assert!(chapter.interaction_count <= settings.min_interactions_per_chapter);
+        }
+        for finished in &plot_state.chapters {
+            prop_assert!(!finished.content.is_empty());
+            prop_assert!(finished.interaction_count > settings.min_interactions_per_chapter);
+            prop_assert!(finished.interaction_count <= settings.max_interactions_per_chapter + 1);
+        }
+        Ok(())
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(100))]
+
+        // Feature: Nobody, Property 34: turn pipeline matches the reference model
+        #[test]
+        fn test_property_34_turns_follow_reference_model(
+            steps in proptest::collection::vec(arb_step(), 1..12)
+        ) {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            let mut engine = create_engine();
+            let pipeline = TurnPipeline::for_state(
+                &engine.get_current_state().unwrap(),
+                &engine.get_plot_state().unwrap().settings,
+            )
+            .unwrap();
+            let mut model = TurnModel::observe(&engine);
+
+            for step in &steps {
+                let previous_days = model.total_days;
+                run_step(&runtime, &pipeline, &mut engine, &mut model, step)?;
+                prop_assert!(model.total_days >= previous_days);
+            }
+        }
+    }
+}
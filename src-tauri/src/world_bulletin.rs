@@ -0,0 +1,296 @@
+use crate::game_state::GameState;
+use crate::llm_service::{LLMRequest, LLMService, LLMSubsystem};
+use crate::npc::NPC;
+use crate::prompt_builder::{PromptBuilder, PromptConstraints, PromptContext, PromptTemplate};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+const MAX_ISSUES: usize = 30;
+const MAX_HEADLINES: usize = 6;
+const MIN_NPC_MEMORY_IMPORTANCE: f32 = 0.5;
+const MAX_BULLETIN_CHARS: usize = 600;
+const MASTHEAD_SUFFIXES: &[&str] = &["宗", "门", "派", "阁", "谷", "宫"];
+
+/// 快报的刊行周期
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum BulletinPeriod {
+    Daily,
+    #[default]
+    Weekly,
+}
+
+impl BulletinPeriod {
+    pub fn days(self) -> u32 {
+        match self {
+            BulletinPeriod::Daily => 1,
+            BulletinPeriod::Weekly => 7,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum BulletinSource {
+    Template,
+    Llm,
+}
+
+/// 一期世界快报
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct WorldBulletin {
+    pub issue: u32,
+    pub title: String,
+    pub from_day: u32,
+    pub to_day: u32,
+    pub headlines: Vec<String>,
+    pub body: String,
+    pub source: BulletinSource,
+}
+
+/// 已发行的快报，随世界状态保存
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct BulletinBoard {
+    pub period: BulletinPeriod,
+    pub last_issued_day: u32,
+    pub issues: Vec<WorldBulletin>,
+}
+
+impl BulletinBoard {
+    /// 距上次发行已满一个周期时返回 true
+    pub fn is_due(&self, current_day: u32) -> bool {
+        current_day >= self.last_issued_day.saturating_add(self.period.days())
+    }
+
+    pub fn next_issue(&self) -> u32 {
+        self.issues.last().map(|b| b.issue + 1).unwrap_or(1)
+    }
+
+    pub fn latest(&self) -> Option<&WorldBulletin> {
+        self.issues.last()
+    }
+
+    pub fn get(&self, issue: u32) -> Option<&WorldBulletin> {
+        self.issues.iter().find(|b| b.issue == issue)
+    }
+
+    pub fn publish(&mut self, bulletin: WorldBulletin) {
+        self.last_issued_day = bulletin.to_day;
+        self.issues.push(bulletin);
+        if self.issues.len() > MAX_ISSUES {
+            let overflow = self.issues.len() - MAX_ISSUES;
+            self.issues.drain(0..overflow);
+        }
+    }
+
+    /// 用润色后的版本替换同期快报
+    pub fn replace(&mut self, bulletin: WorldBulletin) -> bool {
+        match self.issues.iter_mut().find(|b| b.issue == bulletin.issue) {
+            Some(existing) => {
+                *existing = bulletin;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// 汇编世界大事与 NPC 动向的快报编辑部
+pub struct BulletinDesk {
+    prompt_builder: PromptBuilder,
+    llm_service: Option<Arc<LLMService>>,
+}
+
+impl BulletinDesk {
+    pub fn new() -> Self {
+        Self {
+            prompt_builder: PromptBuilder::default(),
+            llm_service: None,
+        }
+    }
+
+    pub fn with_llm_service(mut self, llm_service: Arc<LLMService>) -> Self {
+        self.llm_service = Some(llm_service);
+        self
+    }
+
+    /// 按模板汇编自上期以来的世界事件与 NPC 动向
+    pub fn compile(&self, state: &GameState, npcs: &[&NPC], issue: u32) -> WorldBulletin {
+        let board = &state.world_state.bulletin_board;
+        let from_day = board.last_issued_day.saturating_add(1);
+        let to_day = state.game_time.total_days;
+        let in_window = |timestamp: u64| {
+            timestamp >= u64::from(from_day) && timestamp <= u64::from(to_day)
+        };
+
+        let mut headlines = state
+            .world_state
+            .global_events
+            .iter()
+            .filter(|event| in_window(event.timestamp))
+            .map(|event| format!("{}：{}", event.name, event.description))
+            .collect::<Vec<String>>();
+
+        let mut doings = npcs
+            .iter()
+            .flat_map(|npc| {
+                npc.memory
+                    .short_term
+                    .iter()
+                    .chain(npc.memory.long_term.iter())
+                    .filter(|entry| {
+                        entry.importance >= MIN_NPC_MEMORY_IMPORTANCE && in_window(entry.timestamp)
+                    })
+                    .map(move |entry| (entry.importance, format!("{}：{}", npc.name, entry.event)))
+            })
+            .collect::<Vec<(f32, String)>>();
+        doings.sort_by(|a, b| b.0.total_cmp(&a.0));
+        headlines.extend(doings.into_iter().map(|(_, text)| text));
+        headlines.dedup();
+        headlines.truncate(MAX_HEADLINES);
+
+        let title = format!("{} 第{}期", masthead(state), issue);
+        let body = if headlines.is_empty() {
+            format!("{}：近日四方平静，各宗门弟子潜心修炼，并无大事发生。", title)
+        } else {
+            let items = headlines
+                .iter()
+                .enumerate()
+                .map(|(idx, line)| format!("{}、{}", idx + 1, line))
+                .collect::<Vec<String>>()
+                .join("\n");
+            format!("{}（第{}日至第{}日）\n{}", title, from_day, to_day, items)
+        };
+
+        WorldBulletin {
+            issue,
+            title,
+            from_day,
+            to_day,
+            headlines,
+            body,
+            source: BulletinSource::Template,
+        }
+    }
+
+    /// 使用 LLM 将模板快报改写为说书人口吻，失败时原样返回
+    pub async fn polish(&self, bulletin: WorldBulletin) -> WorldBulletin {
+        if cfg!(test) || bulletin.headlines.is_empty() {
+            return bulletin;
+        }
+        let Some(llm_service) = &self.llm_service else {
+            return bulletin;
+        };
+
+        let prompt = self.prompt_builder.build_prompt_with_token_limit(
+            PromptTemplate::PlotGeneration,
+            &PromptContext {
+                scene: Some(format!("撰写一期修仙界小报《{}》", bulletin.title)),
+                location: None,
+                actor_name: None,
+                actor_realm: None,
+                actor_combat_power: None,
+                player_persona: None,
+                canon_facts: Vec::new(),
+                story_beat: None,
+                chapter_beat: None,
+                environment: None,
+                active_quests: Vec::new(),
+                relationships: Vec::new(),
+                status_effects: Vec::new(),
+                history_events: bulletin.headlines.clone(),
+                world_setting_summary: None,
+            },
+            &PromptConstraints {
+                numerical_rules: Vec::new(),
+                world_rules: vec![
+                    "以小报口吻逐条报道，不得编造列表之外的大事".to_string(),
+                    "不超过 400 字".to_string(),
+                ],
+                output_schema_hint: Some("纯文本，不要 JSON".to_string()),
+            },
+            800,
+        );
+
+        match llm_service
+            .generate(LLMRequest {
+                prompt,
+                max_tokens: Some(500),
+                temperature: Some(0.8),
+                subsystem: LLMSubsystem::Plot,
+                retry_policy: None,
+            })
+            .await
+        {
+            Ok(response) if !response.text.trim().is_empty() => WorldBulletin {
+                body: response.text.trim().chars().take(MAX_BULLETIN_CHARS).collect(),
+                source: BulletinSource::Llm,
+                ..bulletin
+            },
+            _ => bulletin,
+        }
+    }
+}
+
+impl Default for BulletinDesk {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 以首个势力命名快报，如“青云宗”对应“青云快报”
+fn masthead(state: &GameState) -> String {
+    let Some(faction) = state.script.world_setting.factions.first() else {
+        return "修仙界快报".to_string();
+    };
+    let name = MASTHEAD_SUFFIXES
+        .iter()
+        .find_map(|suffix| faction.name.strip_suffix(suffix))
+        .filter(|name| !name.is_empty())
+        .unwrap_or(&faction.name);
+    format!("{}快报", name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bulletin(issue: u32, to_day: u32) -> WorldBulletin {
+        WorldBulletin {
+            issue,
+            title: format!("快报 第{}期", issue),
+            from_day: 1,
+            to_day,
+            headlines: Vec::new(),
+            body: String::new(),
+            source: BulletinSource::Template,
+        }
+    }
+
+    #[test]
+    fn test_board_is_due_after_one_period() {
+        let mut board = BulletinBoard::default();
+        assert!(board.is_due(7));
+        board.publish(bulletin(1, 7));
+        assert!(!board.is_due(13));
+        assert!(board.is_due(14));
+        assert_eq!(board.next_issue(), 2);
+    }
+
+    #[test]
+    fn test_board_keeps_bounded_history_and_replaces_issue() {
+        let mut board = BulletinBoard {
+            period: BulletinPeriod::Daily,
+            ..BulletinBoard::default()
+        };
+        for issue in 1..=(MAX_ISSUES as u32 + 5) {
+            board.publish(bulletin(issue, issue));
+        }
+        assert_eq!(board.issues.len(), MAX_ISSUES);
+        assert!(board.get(1).is_none());
+
+        let mut polished = bulletin(board.next_issue() - 1, 0);
+        polished.source = BulletinSource::Llm;
+        assert!(board.replace(polished));
+        assert_eq!(board.latest().unwrap().source, BulletinSource::Llm);
+    }
+}
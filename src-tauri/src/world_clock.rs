@@ -0,0 +1,238 @@
+use crate::game_state::{GameState, GameTime};
+use crate::numerical_system::Context;
+use crate::rng::GameRng;
+use crate::script::{Climate, Location};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// 一天中的时辰。回合以日计，时辰随天数轮转
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeOfDay {
+    Dawn,
+    Day,
+    Dusk,
+    Night,
+}
+
+impl TimeOfDay {
+    pub fn from_time(time: &GameTime) -> Self {
+        match time.total_days % 4 {
+            0 => TimeOfDay::Night,
+            1 => TimeOfDay::Dawn,
+            2 => TimeOfDay::Day,
+            _ => TimeOfDay::Dusk,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TimeOfDay::Dawn => "清晨",
+            TimeOfDay::Day => "白昼",
+            TimeOfDay::Dusk => "黄昏",
+            TimeOfDay::Night => "夜晚",
+        }
+    }
+}
+
+/// 四季，每季三个月
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Season {
+    Spring,
+    Summer,
+    Autumn,
+    Winter,
+}
+
+impl Season {
+    pub fn from_time(time: &GameTime) -> Self {
+        match time.month {
+            1..=3 => Season::Spring,
+            4..=6 => Season::Summer,
+            7..=9 => Season::Autumn,
+            _ => Season::Winter,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Season::Spring => "春",
+            Season::Summer => "夏",
+            Season::Autumn => "秋",
+            Season::Winter => "冬",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Weather {
+    Clear,
+    Cloudy,
+    Fog,
+    Rain,
+    Snow,
+    Storm,
+}
+
+impl Weather {
+    const ALL: [Weather; 6] = [
+        Weather::Clear,
+        Weather::Cloudy,
+        Weather::Fog,
+        Weather::Rain,
+        Weather::Snow,
+        Weather::Storm,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Weather::Clear => "晴",
+            Weather::Cloudy => "阴",
+            Weather::Fog => "雾",
+            Weather::Rain => "雨",
+            Weather::Snow => "雪",
+            Weather::Storm => "雷暴",
+        }
+    }
+}
+
+/// 按气候与季节给出各种天气的权重，顺序同 `Weather::ALL`；
+/// 入冬后雨化为雪，盛夏多雷暴
+fn weather_weights(climate: Climate, season: Season) -> [u32; 6] {
+    let mut weights = match climate {
+        Climate::Temperate => [5, 3, 1, 2, 0, 1],
+        Climate::Humid => [3, 3, 2, 4, 0, 2],
+        Climate::Arid => [8, 2, 0, 1, 0, 1],
+        Climate::Frigid => [4, 3, 1, 0, 4, 0],
+        Climate::Tempestuous => [2, 2, 1, 3, 0, 4],
+    };
+    match season {
+        Season::Winter => {
+            weights[4] += weights[3];
+            weights[3] = 0;
+        }
+        Season::Summer => weights[5] += 1,
+        Season::Spring | Season::Autumn => {}
+    }
+    weights
+}
+
+/// 某地某日的时辰、季节与天气。天气由本局种子、地点与日期派生，
+/// 同一天在同一处总是相同，读档重放也不会变
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct WorldClock {
+    pub time_of_day: TimeOfDay,
+    pub season: Season,
+    pub weather: Weather,
+}
+
+impl WorldClock {
+    pub fn at(time: &GameTime, location: Option<&Location>, rng: &GameRng) -> Self {
+        let season = Season::from_time(time);
+        let (id, climate) = location.map_or(("", Climate::default()), |location| {
+            (location.id.as_str(), location.climate)
+        });
+        let mut weather_rng =
+            GameRng::new(rng.derive_seed(&format!("weather:{}:{}", id, time.total_days)));
+        let weather = Weather::ALL[weather_rng.weighted_index(&weather_weights(climate, season))];
+        Self {
+            time_of_day: TimeOfDay::from_time(time),
+            season,
+            weather,
+        }
+    }
+
+    /// 角色当前所在之处
+    pub fn for_player(game_state: &GameState) -> Self {
+        Self::at(
+            &game_state.game_time,
+            game_state
+                .world_state
+                .locations
+                .get(&game_state.player.location),
+            &game_state.rng,
+        )
+    }
+
+    /// 写入提示词的一行，如“春季，黄昏，雨”
+    pub fn prompt_line(&self) -> String {
+        format!(
+            "{}季，{}，{}",
+            self.season.label(),
+            self.time_of_day.label(),
+            self.weather.label()
+        )
+    }
+}
+
+/// 在某处行动的判定情境：时辰、季节、天气与灵气浓度
+pub fn context_at(game_state: &GameState, location_id: &str) -> Context {
+    let location = game_state.world_state.locations.get(location_id);
+    let clock = WorldClock::at(&game_state.game_time, location, &game_state.rng);
+    Context {
+        location: location_id.to_string(),
+        time_of_day: clock.time_of_day.label().to_string(),
+        weather: Some(clock.weather.label().to_string()),
+        season: Some(clock.season.label().to_string()),
+        spiritual_energy: location.map_or(1.0, |location| location.spiritual_energy),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script::LocationKind;
+
+    fn location(id: &str, climate: Climate) -> Location {
+        Location {
+            id: id.to_string(),
+            name: id.to_string(),
+            description: String::new(),
+            spiritual_energy: 1.0,
+            kind: LocationKind::Wilderness,
+            climate,
+        }
+    }
+
+    #[test]
+    fn test_clock_follows_game_time_and_climate() {
+        let rng = GameRng::new(7);
+        let peak = location("peak", Climate::Frigid);
+        let spring = GameTime::new(1, 2, 10);
+        let clock = WorldClock::at(&spring, Some(&peak), &rng);
+        assert_eq!(clock.season, Season::Spring);
+        assert_eq!(clock, WorldClock::at(&spring, Some(&peak), &rng));
+        assert_eq!(Season::from_time(&GameTime::new(3, 11, 1)), Season::Winter);
+
+        let times_of_day = (0..4)
+            .map(|day| {
+                let mut time = spring.clone();
+                time.advance_days(day);
+                TimeOfDay::from_time(&time)
+            })
+            .collect::<Vec<TimeOfDay>>();
+        assert!(times_of_day.contains(&TimeOfDay::Night));
+        assert!(times_of_day.contains(&TimeOfDay::Day));
+
+        let desert = location("desert", Climate::Arid);
+        let summer_days = (0..60).map(|day| {
+            let mut time = GameTime::new(1, 5, 1);
+            time.advance_days(day);
+            time
+        });
+        for time in summer_days {
+            assert_ne!(
+                WorldClock::at(&time, Some(&desert), &rng).weather,
+                Weather::Snow
+            );
+            let winter = GameTime::new(1, 12, time.day);
+            assert_ne!(
+                WorldClock::at(&winter, Some(&peak), &rng).weather,
+                Weather::Rain
+            );
+        }
+        assert!(clock.prompt_line().starts_with("春季"));
+    }
+}
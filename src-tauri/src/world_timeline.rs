@@ -0,0 +1,103 @@
+use crate::game_state::{GameState, GlobalEvent};
+
+/// 剧本时间线上的大事发生时写入事件日志的事件类型
+pub const WORLD_EVENT: &str = "world_event";
+
+/// 把已到日期、尚未发生的剧本大事记入世界状态，按发生日期先后返回本次新发生的事件
+pub fn fire_due_events(state: &mut GameState) -> Vec<GlobalEvent> {
+    let today = state.game_time.total_days;
+    let mut due = state
+        .script
+        .world_setting
+        .timeline_events
+        .iter()
+        .filter(|event| event.trigger_day() <= today)
+        .filter(|event| {
+            !state
+                .world_state
+                .global_events
+                .iter()
+                .any(|fired| fired.id == event.id)
+        })
+        .collect::<Vec<_>>();
+    due.sort_by_key(|event| event.trigger_day());
+
+    let fired = due
+        .into_iter()
+        .map(|event| GlobalEvent {
+            id: event.id.clone(),
+            name: event.name.clone(),
+            description: event.description.clone(),
+            timestamp: u64::from(today),
+        })
+        .collect::<Vec<GlobalEvent>>();
+    state.world_state.global_events.extend(fired.iter().cloned());
+    fired
+}
+
+/// 写入续写背景与事件日志的一行描述
+pub fn event_line(event: &GlobalEvent) -> String {
+    if event.description.trim().is_empty() {
+        format!("世界大事：{}", event.name)
+    } else {
+        format!("世界大事：{}——{}", event.name, event.description)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_engine::GameEngine;
+    use crate::models::CultivationRealm;
+    use crate::script::{Climate, Location, LocationKind, TimelineEvent};
+    use crate::script_manager::ScriptManager;
+
+    fn timeline_event(id: &str, name: &str, year: u32, month: u32) -> TimelineEvent {
+        TimelineEvent {
+            id: id.to_string(),
+            name: name.to_string(),
+            description: String::new(),
+            year,
+            month,
+            power_changes: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_events_fire_once_when_their_date_arrives() {
+        let mut script = ScriptManager::new().blank_script();
+        script
+            .world_setting
+            .cultivation_realms
+            .push(CultivationRealm::new("练气".to_string(), 1, 0, 1.0));
+        script.world_setting.locations.push(Location {
+            id: "sect".to_string(),
+            name: "青云宗".to_string(),
+            description: String::new(),
+            spiritual_energy: 1.0,
+            kind: LocationKind::Sect,
+            climate: Climate::Temperate,
+        });
+        script.initial_state.starting_location = "sect".to_string();
+        script.world_setting.timeline_events = vec![
+            timeline_event("secret_realm", "秘境开启", 5, 1),
+            timeline_event("sect_war", "宗门大战", 3, 6),
+        ];
+        let mut state = GameEngine::new().initialize_game(script).unwrap();
+
+        assert!(fire_due_events(&mut state).is_empty());
+
+        state.game_time.advance_days(360 * 5);
+        let fired = fire_due_events(&mut state);
+        assert_eq!(
+            fired.iter().map(|event| event.id.as_str()).collect::<Vec<&str>>(),
+            vec!["sect_war", "secret_realm"]
+        );
+        assert_eq!(fired[0].timestamp, u64::from(state.game_time.total_days));
+        assert_eq!(event_line(&fired[0]), "世界大事：宗门大战");
+        assert_eq!(state.world_state.global_events.len(), 2);
+
+        state.game_time.advance_days(30);
+        assert!(fire_due_events(&mut state).is_empty());
+    }
+}
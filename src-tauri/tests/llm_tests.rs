@@ -1,3 +1,4 @@
+use nobody_lib::llm_provider::ProviderKind;
 use nobody_lib::llm_service::LLMConfig;
 
 #[test]
@@ -9,6 +10,8 @@ fn test_llm_config_validation() {
         model: "gpt-4".to_string(),
         max_tokens: 2000,
         temperature: 0.7,
+        provider_kind: ProviderKind::OpenAI,
+        retry_policy: Default::default(),
     };
     assert!(config.validate().is_ok());
 
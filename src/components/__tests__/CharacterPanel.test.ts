@@ -18,7 +18,15 @@ const buildCharacter = (): Character => ({
       sub_level: 2,
       power_multiplier: 1,
     },
-    techniques: ['Fire Palm'],
+    techniques: [
+      {
+        id: 'fire_palm',
+        name: 'Fire Palm',
+        element: Element.Fire,
+        required_realm_level: 1,
+        proficiency: 0,
+      },
+    ],
     lifespan: {
       current_age: 80,
       max_age: 100,
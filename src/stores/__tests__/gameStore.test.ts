@@ -164,7 +164,7 @@ describe('gameStore', () => {
 
     expect(invokeWithTimeoutMock).toHaveBeenCalledWith(
       'execute_player_action',
-      { action: expect.any(Object) },
+      { action: expect.any(Object), requestId: expect.any(String) },
       140000,
       expect.any(String),
     );
@@ -1,6 +1,7 @@
 import { defineStore } from 'pinia';
 import { invoke } from '@tauri-apps/api/core';
 import { invokeWithTimeout } from '../utils/tauriInvoke';
+import { parseStaleOptionError } from '../utils/playerInput';
 import type {
   Script,
   GameState,
@@ -8,8 +9,11 @@ import type {
   PlayerAction,
   PlayerOption,
   SaveInfo,
+  SaveProgress,
 } from '../types/game';
 
+const SAVE_PROGRESS_POLL_MS = 100;
+
 interface GameStoreState {
   currentScript: Script | null;
   gameState: GameState | null;
@@ -65,7 +69,7 @@ export const useGameStore = defineStore('game', {
       try {
         await invokeWithTimeout<string>(
           'execute_player_action',
-          { action },
+          { action, requestId: crypto.randomUUID() },
           140000,
           '剧情推进超时，请稍后重试',
         );
@@ -86,15 +90,26 @@ export const useGameStore = defineStore('game', {
         );
         this.plotState = plotState;
 
-        // 显示 LLM 诊断信息（如果有）
-        if (plotState.last_generation_diagnostics) {
+        // 显示 LLM 诊断信息（如果有），优先展示可操作的失败原因与建议
+        if (plotState.last_generation_failure) {
+          const failure = plotState.last_generation_failure;
+          console.warn('LLM 生成失败:', failure);
+          this.error = `${failure.likely_cause}。建议：${failure.suggested_action}`;
+        } else if (plotState.last_generation_diagnostics?.warnings.length) {
           console.warn('LLM 诊断信息:', plotState.last_generation_diagnostics);
-          this.error = plotState.last_generation_diagnostics;
+          this.error = plotState.last_generation_diagnostics.warnings.join('；');
         }
       } catch (error) {
         const message = error instanceof Error ? error.message : String(error);
+        const stale = parseStaleOptionError(message);
 
-        if (message.includes('剧情推进超时')) {
+        if (stale) {
+          // 选项已在别处刷新，换上最新选项让玩家重选
+          if (this.plotState) {
+            this.plotState.current_scene.available_options = stale.current_options;
+          }
+          this.error = stale.message;
+        } else if (message.includes('剧情推进超时')) {
           try {
             const latestPlotState = await invokeWithTimeout<PlotState>(
               'get_plot_state',
@@ -103,7 +118,8 @@ export const useGameStore = defineStore('game', {
               '获取剧情状态超时，请重试',
             );
             this.plotState = latestPlotState;
-            this.error = latestPlotState.last_generation_diagnostics ?? '剧情推进超时，请稍后重试';
+            this.error =
+              latestPlotState.last_generation_diagnostics?.warnings.join('；') || '剧情推进超时，请稍后重试';
           } catch {
             this.error = '剧情推进超时，请稍后重试。您可以尝试重新连接或调整 LLM 设置。';
           }
@@ -120,7 +136,17 @@ export const useGameStore = defineStore('game', {
       this.error = null;
 
       try {
-        await invoke('save_game', { slotId });
+        const ticket = await invoke<number>('save_game', { slotId });
+        for (;;) {
+          const progress = await invoke<SaveProgress>('get_save_progress', { ticket });
+          if (progress.stage === 'Completed') {
+            break;
+          }
+          if (progress.stage === 'Failed') {
+            throw new Error(progress.error ?? '保存存档失败');
+          }
+          await new Promise((resolve) => setTimeout(resolve, SAVE_PROGRESS_POLL_MS));
+        }
       } catch (error) {
         this.error = error instanceof Error ? error.message : String(error);
         throw error;
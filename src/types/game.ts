@@ -1,11 +1,107 @@
 // Game types matching Rust backend structures
 
 export interface Script {
+  schema_version?: number;
   id: string;
   name: string;
   script_type: ScriptType;
   world_setting: WorldSetting;
   initial_state: InitialState;
+  drop_tables?: DropTable[];
+  action_filters?: ActionFilters;
+  localization?: ScriptLocalization;
+  language?: ScriptLanguage | null;
+  endings?: EndingDefinition[];
+  difficulty?: DifficultySettings;
+  economy?: EconomyConfig;
+  story_so_far?: StoryRecap | null;
+  secret_realms?: SecretRealm[];
+}
+
+export interface StoryRecap {
+  start_chapter: number;
+  start_chapter_title: string;
+  recap: string;
+  chapter_summaries: string[];
+}
+
+export type ScriptLanguage = 'zh' | 'en';
+
+export interface ScriptProvenance {
+  script_id: string;
+  script_name: string;
+  url: string;
+  fetched_at: number;
+  checksum: string;
+  cached_path: string;
+}
+
+export interface ImportedScript {
+  script: Script;
+  provenance: ScriptProvenance;
+}
+
+export interface LocalizedText {
+  zh?: string | null;
+  en?: string | null;
+}
+
+export interface LocalizedEntry {
+  name?: LocalizedText;
+  description?: LocalizedText;
+}
+
+export interface ScriptLocalization {
+  script_name?: LocalizedText;
+  player_name?: LocalizedText;
+  realms?: Record<string, LocalizedText>;
+  locations?: Record<string, LocalizedEntry>;
+  factions?: Record<string, LocalizedEntry>;
+  techniques?: Record<string, LocalizedEntry>;
+}
+
+export interface ActionFilters {
+  blocked: string[];
+  allowed: string[];
+}
+
+export interface ActionFilterSettings {
+  app: ActionFilters;
+  script: ActionFilters | null;
+  effective: ActionFilters;
+}
+
+export type ContentIntensity = 'mild' | 'moderate' | 'unrestricted';
+
+export interface ContentFilterSettings {
+  enabled: boolean;
+  blocked_words: string[];
+  violence: ContentIntensity;
+  romance: ContentIntensity;
+}
+
+export type DropSource =
+  | { kind: "location"; location_id: string }
+  | { kind: "enemy_tier"; tier: number }
+  | { kind: "secret_realm"; realm_id: string };
+
+export interface DropEntry {
+  item_id: string;
+  name: string;
+  description?: string;
+  item_type: "Technique" | "Artifact" | "Medicine" | "Material";
+  weight: number;
+  rarity?: "Common" | "Rare";
+  lifespan_bonus?: number;
+}
+
+export interface DropTable {
+  id: string;
+  source: DropSource;
+  rolls?: number;
+  empty_weight?: number;
+  entries: DropEntry[];
+  pity_threshold?: number | null;
 }
 
 export enum ScriptType {
@@ -16,10 +112,58 @@ export enum ScriptType {
 
 export interface WorldSetting {
   cultivation_realms: CultivationRealm[];
+  realm_ladder?: RealmStages[];
   spiritual_roots: SpiritualRoot[];
+  root_tiers?: RootTier[];
   techniques: Technique[];
   locations: Location[];
   factions: Faction[];
+  npcs?: NpcDefinition[];
+  timeline_events?: TimelineEvent[];
+}
+
+export interface TimelineEvent {
+  id: string;
+  name: string;
+  description?: string;
+  year: number;
+  month?: number;
+  power_changes?: Record<string, number>;
+}
+
+export interface StartingRelationship {
+  target_id: string;
+  affinity?: number;
+  trust?: number;
+}
+
+export interface NpcDefinition {
+  id: string;
+  name: string;
+  bio?: string;
+  realm_level?: number | null;
+  sub_level?: number;
+  spiritual_root?: SpiritualRoot | null;
+  age?: number;
+  max_age?: number;
+  location?: string | null;
+  traits?: string[];
+  goals?: { description: string; priority: number }[];
+  values?: { name: string; weight: number }[];
+  relationships?: StartingRelationship[];
+  secrets?: NpcSecret[];
+}
+
+// target_id "player" stands for the player
+export type RevealTrigger =
+  | { TrustAtLeast: { target_id: string; threshold: number } }
+  | { EventKeyword: string };
+
+export interface NpcSecret {
+  id: string;
+  kind: { HiddenRealm: CultivationRealm } | { HiddenAllegiance: string };
+  triggers: RevealTrigger[];
+  reveal_text?: string | null;
 }
 
 export interface Technique {
@@ -28,6 +172,7 @@ export interface Technique {
   description: string;
   required_realm_level: number;
   element: Element | null;
+  demonic?: boolean;
 }
 
 export interface CultivationRealm {
@@ -37,10 +182,21 @@ export interface CultivationRealm {
   power_multiplier: number;
 }
 
+/** 某一大境界的小境界划分，未定义的境界为初期至圆满期四层 */
+export interface RealmStages {
+  level: number;
+  stages: number;
+  stage_names?: string[];
+  min_combat_power?: number;
+  lifespan_bonus?: number | null;
+}
+
 export interface SpiritualRoot {
   element: Element;
-  grade: Grade;
+  /** 内置品阶为 `Grade` 中的值，剧本可在 `root_tiers` 中定义其他品阶 */
+  grade: Grade | string;
   affinity: number;
+  tier_multiplier?: number;
 }
 
 export enum Element {
@@ -58,11 +214,27 @@ export enum Grade {
   Double = "Double"
 }
 
+export interface RootTier {
+  id: string;
+  name: string;
+  rarity_weight: number;
+  affinity_min: number;
+  affinity_max: number;
+  lifespan_bonus: number;
+  power_multiplier: number;
+}
+
+export type LocationKind = 'wilderness' | 'sect' | 'city';
+
+export type Climate = 'temperate' | 'humid' | 'arid' | 'frigid' | 'tempestuous';
+
 export interface Location {
   id: string;
   name: string;
   description: string;
   spiritual_energy: number;
+  kind?: LocationKind;
+  climate?: Climate;
 }
 
 export interface Faction {
@@ -85,6 +257,49 @@ export interface GameState {
   world_state: WorldState;
   game_time: GameTime;
   event_history: GameEvent[];
+  version?: number;
+  house_rules?: HouseRules;
+  difficulty?: DifficultySettings;
+  quests?: QuestLog;
+  combat?: CombatState | null;
+  ending?: AchievedEnding | null;
+  play_time_secs?: number;
+  action_count?: number;
+  rng?: GameRng;
+  resources?: Resources;
+  market?: Market | null;
+  stat_history?: StatHistoryEntry[];
+  karma?: Karma;
+}
+
+export type Alignment = 'righteous' | 'neutral' | 'demonic';
+
+export interface KarmaShift {
+  day: number;
+  alignment: Alignment;
+  source: string;
+}
+
+export interface Karma {
+  score: number;
+  recent: KarmaShift[];
+}
+
+export interface GameRng {
+  seed: number;
+  state: number;
+}
+
+export interface StateDelta {
+  version: number;
+  full_resync: boolean;
+  script: Script | null;
+  player: Character | null;
+  world_state: WorldState | null;
+  game_time: GameTime | null;
+  event_history: GameEvent[] | null;
+  new_events: GameEvent[];
+  plot_state: PlotState | null;
 }
 
 export interface GameEvent {
@@ -93,6 +308,8 @@ export interface GameEvent {
   event_type: string;
   description: string;
   importance: EventImportance;
+  repeat_count?: number;
+  last_timestamp?: number | null;
 }
 
 export enum EventImportance {
@@ -108,12 +325,32 @@ export interface Character {
   location: string;
 }
 
+export interface LearnedTechnique {
+  id: string;
+  name: string;
+  element: Element | null;
+  required_realm_level: number;
+  /** 熟练度 0-100 */
+  proficiency: number;
+}
+
 export interface CharacterStats {
   spiritual_root: SpiritualRoot;
   cultivation_realm: CultivationRealm;
-  techniques: string[];
+  techniques: LearnedTechnique[];
   lifespan: Lifespan;
   combat_power: number;
+  status_effects?: StatusEffect[];
+  /** 隐藏的悟性积累，冲击瓶颈时消耗 */
+  insight?: number;
+}
+
+export type StatusEffectKind = 'injured' | 'enlightened' | 'poisoned' | 'qi_deviation';
+
+export interface StatusEffect {
+  kind: StatusEffectKind;
+  remaining_days: number;
+  source: string;
 }
 
 export interface Lifespan {
@@ -126,6 +363,74 @@ export interface WorldState {
   locations: Record<string, Location>;
   factions: Record<string, Faction>;
   global_events: string[];
+  bulletin_board?: BulletinBoard;
+  duel_board?: DuelBoard;
+  faction_reputation?: Record<string, number>;
+  faction_standings?: FactionStandings;
+}
+
+export interface FactionWar {
+  attacker_id: string;
+  defender_id: string;
+  location_id: string;
+  started_day: number;
+  ends_day: number;
+  original_energy: number;
+}
+
+export interface FactionStandings {
+  power: Record<string, number>;
+  wars: FactionWar[];
+  last_tick_day: number;
+}
+
+export interface WorldBulletin {
+  issue: number;
+  title: string;
+  from_day: number;
+  to_day: number;
+  headlines: string[];
+  body: string;
+  source: 'Template' | 'Llm';
+}
+
+export interface BulletinBoard {
+  period: 'Daily' | 'Weekly';
+  last_issued_day: number;
+  issues: WorldBulletin[];
+}
+
+export type DuelStake = 'Face' | 'Resources' | 'SectStanding';
+
+export interface DuelChallenge {
+  id: number;
+  challenger_id: string;
+  challenger_name: string;
+  challenger_stats: CharacterStats;
+  stake: DuelStake;
+  rivalry: number;
+  issued_day: number;
+  faction_id: string | null;
+}
+
+export interface DuelOutcome {
+  challenge_id: number;
+  challenger_id: string;
+  challenger_name: string;
+  stake: DuelStake;
+  result: 'Won' | 'Lost' | 'Declined';
+  day: number;
+  faction_id: string | null;
+  reputation_change: number;
+  affinity_change: number;
+  description: string;
+}
+
+export interface DuelBoard {
+  pending: DuelChallenge[];
+  last_challenged_day: Record<string, number>;
+  records: DuelOutcome[];
+  next_id: number;
 }
 
 export interface GameTime {
@@ -135,17 +440,100 @@ export interface GameTime {
   total_days: number;
 }
 
+export interface PlayerPersona {
+  tendencies: Record<string, number>;
+  moral_leaning: number;
+  notable_phrases: string[];
+  turns_observed: number;
+}
+
+export interface CanonFact {
+  id: number;
+  statement: string;
+  established_at: number;
+}
+
+export interface FactStore {
+  facts: CanonFact[];
+  next_id: number;
+}
+
+export type FailureCategory =
+  | 'MissingConfig'
+  | 'Authentication'
+  | 'Timeout'
+  | 'BudgetExceeded'
+  | 'InvalidResponse'
+  | 'Network'
+  | 'ApiError'
+  | 'Cancelled';
+
+export interface GenerationFailure {
+  category: FailureCategory;
+  stage: string;
+  detail: string;
+  likely_cause: string;
+  suggested_action: string;
+}
+
+export type ParsePath = 'strict_json' | 'salvage' | 'plain_text' | 'preset';
+
+export interface GenerationDiagnostics {
+  model: string | null;
+  prompt_tokens: number | null;
+  completion_tokens: number | null;
+  total_tokens: number | null;
+  latency_ms: number | null;
+  retry_count: number;
+  parse_path: ParsePath | null;
+  option_source: string | null;
+  warnings: string[];
+}
+
+export type TurnStatus = 'idle' | 'generating';
+
+export interface GenerationStarted {
+  request_id: string;
+  command: string;
+}
+
 export interface PlotState {
   current_scene: Scene;
   plot_history: string[];
   is_waiting_for_input: boolean;
   last_action_result: ActionResult | null;
-  last_generation_diagnostics?: string | null;
+  last_generation_diagnostics?: GenerationDiagnostics | null;
+  last_generation_failure?: GenerationFailure | null;
   last_option_generation_source?: string | null;
+  player_persona?: PlayerPersona;
+  canon_facts?: FactStore;
+  temperature_tuner?: TemperatureTuner;
+  story_arc?: StoryArc | null;
+  npc_interruption?: NpcEventNotice | null;
+  side_story?: SideStory | null;
   settings: PlotSettings;
   current_chapter: ChapterState;
   chapters: ChapterState[];
   segment_count: number;
+  version?: number;
+}
+
+export interface ArcBeat {
+  title: string;
+  summary: string;
+  reached?: boolean;
+}
+
+export interface StoryArc {
+  index: number;
+  title: string;
+  beats: ArcBeat[];
+  antagonists: string[];
+  climax: string;
+  start_chapter: number;
+  current_beat: number;
+  off_beat_segments?: number;
+  source: 'Template' | 'Llm';
 }
 
 export interface PlotSettings {
@@ -155,6 +543,33 @@ export interface PlotSettings {
   max_interactions_per_chapter: number;
   target_chapter_words_min: number;
   target_chapter_words_max: number;
+  bulletin_segments_enabled?: boolean;
+  temperature_bounds?: TemperatureBounds;
+  three_act_structure?: boolean;
+  outline_planning?: boolean;
+  npc_interruptions?: boolean;
+  llm_judge_threshold?: number;
+  language?: ScriptLanguage;
+}
+
+export interface TemperatureBounds {
+  min: number;
+  max: number;
+}
+
+export type TuningSignal = 'Accepted' | 'Regenerated' | 'ValidationFailed' | 'Repetitive';
+
+export interface TemplateTuning {
+  adjustment: number;
+  requests: number;
+  regenerations: number;
+  validation_failures: number;
+  repetitions: number;
+  recent?: TuningSignal[];
+}
+
+export interface TemperatureTuner {
+  templates: Record<string, TemplateTuning>;
 }
 
 export interface ChapterState {
@@ -163,6 +578,40 @@ export interface ChapterState {
   content: string[];
   summary: string;
   interaction_count: number;
+  offloaded?: boolean;
+  beats_completed?: number;
+  outline?: ChapterOutline | null;
+  provenance?: SegmentProvenance[];
+}
+
+export interface OutlineBeat {
+  summary: string;
+  interaction?: boolean;
+}
+
+export interface ChapterOutline {
+  beats: OutlineBeat[];
+  planned_ending: string;
+  beats_completed?: number;
+  source: 'Template' | 'Llm';
+}
+
+export type FallbackKind = 'plain_text' | 'preset';
+
+export interface ValidatorVerdict {
+  validator: string;
+  passed: boolean;
+  detail?: string | null;
+}
+
+export interface SegmentProvenance {
+  segment_index: number;
+  model?: string | null;
+  temperature?: number | null;
+  prompt_hash?: string | null;
+  retry_count: number;
+  validator_verdicts: ValidatorVerdict[];
+  fallback?: FallbackKind | null;
 }
 
 export interface Scene {
@@ -175,6 +624,7 @@ export interface Scene {
 
 export interface PlayerOption {
   id: number;
+  uid: string;
   description: string;
   requirements: string[];
   action: Action;
@@ -185,6 +635,9 @@ export interface Action {
   Breakthrough?: null;
   Rest?: null;
   Custom?: { description: string };
+  LearnTechnique?: { technique_id: string };
+  PracticeTechnique?: { technique_id: string };
+  UseItem?: { item_id: string };
 }
 
 export interface ActionResult {
@@ -194,19 +647,123 @@ export interface ActionResult {
   events: string[];
 }
 
+export interface ActionPreview {
+  valid: boolean;
+  action: Action | null;
+  estimated_result: ActionResult | null;
+  warnings: string[];
+}
+
 export interface StatChange {
   stat_name: string;
   old_value: number;
   new_value: number;
 }
 
+export interface IdleMonth {
+  year: number;
+  month: number;
+  days: number;
+  combat_power_gain: number;
+  events: string[];
+  summary: string;
+  narrated: boolean;
+}
+
+export interface IdleReport {
+  requested_days: number;
+  days: number;
+  combat_power_gain: number;
+  stat_changes: StatChange[];
+  months: IdleMonth[];
+  interrupted: string | null;
+  summary: string;
+}
+
+export interface StatHistoryEntry {
+  timestamp: number;
+  source: string;
+  change: StatChange;
+}
+
+export interface TechniqueBonus {
+  id: string;
+  name: string;
+  proficiency: number;
+  synergy: number;
+  bonus: number;
+}
+
+export interface CombatPowerBreakdown {
+  base: number;
+  root_multiplier: number;
+  affinity_multiplier: number;
+  realm_multiplier: number;
+  technique_multiplier: number;
+  techniques: TechniqueBonus[];
+  base_combat_power: number;
+  accumulated: number;
+  total: number;
+}
+
+export interface LifespanView {
+  current_age: number;
+  total_max_age: number;
+  remaining_years: number;
+  remaining_percent: number;
+  vitality: number;
+  in_twilight: boolean;
+}
+
+export interface ActiveEffect {
+  id: string;
+  name: string;
+  description: string;
+}
+
+export interface CharacterSheet {
+  name: string;
+  realm: string;
+  realm_progress_percent: number;
+  next_realm: string | null;
+  breakthrough_chance: number;
+  /** 悟性积累进度（0-100），冲关后清零 */
+  insight_percent: number;
+  cultivation_gain: number;
+  combat_power: CombatPowerBreakdown;
+  lifespan: LifespanView;
+  active_effects: ActiveEffect[];
+  recent_stat_changes: StatHistoryEntry[];
+}
+
+export interface LocationSurvey {
+  location_id: string;
+  name: string;
+  spiritual_energy: number;
+  weather: string | null;
+  /** 相对寻常之地的修炼效率倍率 */
+  efficiency: number;
+  cultivation_gain: number;
+  rest_recovery_days: number;
+  current: boolean;
+}
+
 export interface PlayerAction {
   action_type: ActionType;
   content: string;
   selected_option_id: number | null;
+  selected_option_uuid?: string | null;
   meta?: ActionMeta | null;
 }
 
+/** 所选选项已不在当前列表中（或只提交了序号）时后端返回的 JSON 错误 */
+export interface StaleOptionError {
+  code: 'stale_option';
+  message: string;
+  submitted_uid: string;
+  current_options: PlayerOption[];
+}
+
 export interface ActionMeta {
   action_kind?: string | null;
 }
@@ -220,6 +777,571 @@ export interface SaveInfo {
   realm: string;
   location: string;
   game_time: string;
+  house_rules?: string[];
+  play_time_secs?: number;
+  action_count?: number;
+  chapter_title?: string | null;
+  plot_excerpt?: string | null;
+  completed?: boolean;
+  ending_title?: string | null;
+}
+
+export interface BranchInfo {
+  id: string;
+  name: string;
+  parent_id: string | null;
+  forked_at_chapter: number;
+  created_at: number;
+  checkpoints: number[];
+}
+
+export interface BranchIndex {
+  active_id: string;
+  branches: BranchInfo[];
+}
+
+export interface HouseRules {
+  skip_reasonableness_check: boolean;
+  allow_cross_realm_feats: boolean;
+  disable_permadeath: boolean;
+}
+
+export interface DifficultySettings {
+  breakthrough_modifier: number;
+  resource_scarcity: number;
+  lifespan_pressure: number;
+  npc_aggression: number;
+}
+
+export type ResourceKind = 'spirit_stones' | 'herbs' | 'ores';
+
+export interface Resources {
+  spirit_stones: number;
+  herbs: number;
+  ores: number;
+}
+
+export interface EconomyConfig {
+  starting_resources?: Resources;
+  breakthrough_stones?: number;
+  tribulation_herbs?: number;
+  technique_price_per_level?: number;
+  combat_reward_stones?: number;
+  herb_price?: number;
+  ore_price?: number;
+}
+
+export interface MarketItem {
+  id: string;
+  name: string;
+  description: string;
+  item_type: "Technique" | "Artifact" | "Medicine" | "Material";
+  lifespan_bonus: number;
+}
+
+export interface MarketListing {
+  item: MarketItem;
+  rarity: "Common" | "Rare";
+  price: number;
+  stock: number;
+  pitch: string;
+}
+
+export interface ResourceQuote {
+  kind: ResourceKind;
+  buy_price: number;
+  sell_price: number;
+}
+
+export interface Market {
+  location_id: string;
+  location_name: string;
+  rotation: number;
+  listings: MarketListing[];
+  resource_quotes: ResourceQuote[];
+  flavored: boolean;
+}
+
+export interface MarketReceipt {
+  description: string;
+  stat_changes: StatChange[];
+  market: Market;
+}
+
+export interface EmotionalState {
+  anger: number;
+  fear: number;
+  joy: number;
+  grief: number;
+  last_updated: number;
+}
+
+export interface NPCProfile {
+  id: string;
+  name: string;
+  realm: string;
+  location: string | null;
+  bio: string;
+  traits: string[];
+  affinity: number;
+  trust: number;
+  emotions: EmotionalState;
+  dominant_emotion: string | null;
+}
+
+export interface RelationshipSummary {
+  target_id: string;
+  target_name: string;
+  affinity: number;
+  trust: number;
+}
+
+export type RelationshipNodeKind = 'player' | 'npc';
+
+export interface RelationshipNode {
+  id: string;
+  name: string;
+  kind: RelationshipNodeKind;
+  location: string | null;
+}
+
+export interface InteractionRecord {
+  timestamp: number;
+  event: string;
+  affinity_change: number;
+  trust_change: number;
+}
+
+export interface RelationshipEdge {
+  source: string;
+  target: string;
+  affinity: number;
+  trust: number;
+  recent_interactions: InteractionRecord[];
+}
+
+export interface RelationshipGraph {
+  nodes: RelationshipNode[];
+  edges: RelationshipEdge[];
+}
+
+export interface NPCDetail {
+  profile: NPCProfile;
+  age: number;
+  max_age: number;
+  combat_power: number;
+  techniques: string[];
+  goals: { description: string; priority: number }[];
+  values: { name: string; weight: number }[];
+  relationships: RelationshipSummary[];
+  recent_memories: string[];
+  revealed_secrets: RevealedSecret[];
+}
+
+export interface RevealedSecret {
+  id: string;
+  kind: NpcSecret['kind'];
+  reveal_text: string | null;
+  revealed_at: number;
+}
+
+export type QuestStatus = 'active' | 'completed' | 'abandoned';
+
+export interface Quest {
+  id: string;
+  title: string;
+  status: QuestStatus;
+  started_day: number;
+  resolved_day: number | null;
+}
+
+export interface QuestLog {
+  quests: Quest[];
+  next_id: number;
+}
+
+export interface NPCDialogue {
+  npc_id: string;
+  npc_name: string;
+  text: string;
+  affinity_delta: number;
+  trust_delta: number;
+}
+
+export interface NarratorAnswer {
+  question: string;
+  answer: string;
+  sources: string[];
+}
+
+export type SuggestionRisk = 'low' | 'medium' | 'high';
+
+export interface ActionSuggestion {
+  option_id: number;
+  option_uid: string;
+  description: string;
+  score: number;
+  risk: SuggestionRisk;
+  reasons: string[];
+}
+
+export interface CompanionAdvice {
+  suggestions: ActionSuggestion[];
+  advice: string;
+  phrased_by_llm: boolean;
+}
+
+export type CombatMove = 'Attack' | 'Defend' | 'Flee' | { Technique: { name: string } };
+
+export type CombatStatus = 'ongoing' | 'victory' | 'defeat' | 'fled' | 'stalemate';
+
+export interface Combatant {
+  id: string;
+  name: string;
+  realm: string;
+  hp: number;
+  max_hp: number;
+  qi: number;
+  max_qi: number;
+  attack: number;
+  defense: number;
+  initiative: number;
+  techniques: string[];
+}
+
+export interface CombatLogEntry {
+  actor_id: string;
+  action: CombatMove;
+  damage: number;
+  description: string;
+}
+
+export interface CombatRound {
+  round: number;
+  entries: CombatLogEntry[];
+  narration: string | null;
+}
+
+export interface CombatState {
+  started_day: number;
+  player: Combatant;
+  enemy: Combatant;
+  status: CombatStatus;
+  rounds: CombatRound[];
+}
+
+export interface EndingDefinition {
+  id: string;
+  title: string;
+  description: string;
+  condition?: string;
+  priority?: number;
+  ends_playthrough?: boolean;
+}
+
+export type EndingCause = 'death' | 'lifespan_exhausted' | 'ascension' | 'goals_completed';
+
+export interface AchievedEnding {
+  ending_id: string;
+  title: string;
+  finale: string;
+  day: number;
+  cause?: EndingCause | null;
+}
+
+export interface EndingSummary {
+  ending: AchievedEnding;
+  script_name: string;
+  player_name: string;
+  realm: string;
+  age: number;
+  total_days: number;
+  play_time_secs: number;
+  action_count: number;
+  techniques: number;
+  quests_completed: number;
+  duels_won: number;
+  duels_lost: number;
+  highlights: string[];
+}
+
+export interface GalleryEntry {
+  script_id: string;
+  script_name: string;
+  ending_id: string;
+  title: string;
+  times_achieved: number;
+  first_achieved_at: number;
+  last_achieved_at: number;
+}
+
+export interface GallerySlot {
+  ending_id: string;
+  title: string | null;
+  achieved: boolean;
+  times_achieved: number;
+}
+
+export interface EndingGalleryView {
+  entries: GalleryEntry[];
+  current_script: GallerySlot[];
+}
+
+export interface AchievementStatus {
+  id: string;
+  title: string;
+  description: string;
+  unlocked: boolean;
+  unlocked_at?: number | null;
+}
+
+export type NpcEventKind = 'reaction' | 'duel_challenge';
+
+export interface NpcEventNotice {
+  kind: NpcEventKind;
+  npc_id: string;
+  npc_name: string;
+  action: string;
+  summary: string;
+  reason: string;
+  timestamp: number;
+}
+
+export interface SecretRealm {
+  id: string;
+  name: string;
+  description?: string;
+  location_id: string;
+  min_realm_level?: number;
+  max_turns?: number;
+}
+
+export interface SideStory {
+  realm_id: string;
+  realm_name: string;
+  max_turns: number;
+  turns_left: number;
+  entered_day: number;
+  entry_combat_power: number;
+  entry_items: string[];
+}
+
+export interface Item {
+  id: string;
+  name: string;
+  description: string;
+  item_type: "Technique" | "Artifact" | "Medicine" | "Material";
+  lifespan_bonus?: number;
+}
+
+export interface SideStoryOutcome {
+  realm_id: string;
+  realm_name: string;
+  turns_spent: number;
+  combat_power_gain: number;
+  items: Item[];
+  forced: boolean;
+  summary: string;
+}
+
+export interface UnlockedAchievement {
+  id: string;
+  title: string;
+  description: string;
+  script_id: string;
+  script_name: string;
+  unlocked_at: number;
+}
+
+export type ScriptSection =
+  | 'name'
+  | 'realms'
+  | 'realm_ladder'
+  | 'spiritual_roots'
+  | 'root_tiers'
+  | 'techniques'
+  | 'locations'
+  | 'factions'
+  | 'initial_state'
+  | 'numerical_config'
+  | 'drop_tables'
+  | 'action_filters'
+  | 'localization'
+  | 'endings'
+  | 'npcs'
+  | 'timeline_events'
+  | 'secret_realms';
+
+export interface ScriptIssue {
+  section: ScriptSection;
+  message: string;
+}
+
+export interface ScriptDraftReport {
+  script: Script;
+  valid: boolean;
+  issues: ScriptIssue[];
+}
+
+export interface WorldTheme {
+  id: string;
+  name: string;
+  description: string;
+  tone: string;
+}
+
+export type StartingPath = 'sect' | 'wanderer';
+
+export type WizardDifficulty = 'easy' | 'normal' | 'hard';
+
+export interface WizardAnswers {
+  /** WorldTheme.id */
+  theme: string;
+  starting_path?: StartingPath;
+  difficulty?: WizardDifficulty;
+  player_name?: string | null;
+}
+
+export interface RejectedSection {
+  section: ScriptSection;
+  reason: string;
+}
+
+export interface ScriptReloadReport {
+  applied: ScriptSection[];
+  rejected: RejectedSection[];
+  error?: string | null;
+}
+
+export type LLMSubsystem = 'plot' | 'npc' | 'script' | 'validation' | 'novel' | 'other';
+
+export interface LlmTrace {
+  id: number;
+  recorded_at: number;
+  subsystem: LLMSubsystem;
+  model: string;
+  prompt: string;
+  response: string | null;
+  error: string | null;
+  cached: boolean;
+  latency_ms: number;
+  prompt_tokens: number | null;
+  completion_tokens: number | null;
+  total_tokens: number | null;
+}
+
+export interface AppSettings {
+  plot_settings: PlotSettings;
+  difficulty?: DifficultySettings | null;
+  llm_routing?: Partial<Record<LLMSubsystem, string>>;
+}
+
+export interface AutosaveSettings {
+  enabled: boolean;
+  interval_actions: number;
+}
+
+export interface ManifestEntry {
+  slot_id: number;
+  file_name: string;
+  size_bytes: number;
+  checksum: string;
+  modified_at: number;
+  save_version: string | null;
+  saved_at: number | null;
+  player_name: string | null;
+}
+
+export interface SaveManifest {
+  manifest_version: number;
+  generated_at: number;
+  entries: ManifestEntry[];
+}
+
+export type ManifestIssueKind =
+  | 'Missing'
+  | 'SizeMismatch'
+  | 'ChecksumMismatch'
+  | 'Unreadable'
+  | 'Untracked';
+
+export interface ManifestIssue {
+  slot_id: number;
+  kind: ManifestIssueKind;
+  detail: string;
+}
+
+export interface ManifestVerification {
+  checked: number;
+  intact: number;
+  issues: ManifestIssue[];
+}
+
+export interface StorageCleanupPolicy {
+  cache_max_age_days?: number | null;
+  compress_finished_runs?: boolean;
+}
+
+export interface RunStorage {
+  slot_id: number;
+  file_name: string;
+  bytes: number;
+  compressed: boolean;
+  finished: boolean;
+  modified_at: number;
+}
+
+export interface CacheStorage {
+  session: string;
+  bytes: number;
+  modified_at: number;
+  active: boolean;
+}
+
+export interface StorageReport {
+  total_bytes: number;
+  save_bytes: number;
+  cache_bytes: number;
+  other_bytes: number;
+  runs: RunStorage[];
+  caches: CacheStorage[];
+}
+
+export interface StorageCleanupResult {
+  removed_caches: string[];
+  compressed_slots: number[];
+  bytes_freed: number;
+  report: StorageReport;
+}
+
+export interface MemoryUsageReport {
+  low_memory_mode: boolean;
+  chapters_in_memory: number;
+  chapters_offloaded: number;
+  chapter_bytes: number;
+  events_in_memory: number;
+  event_bytes: number;
+  event_archives: number;
+  archived_event_files: number;
+  npc_memory_entries: number;
+  npc_memory_bytes: number;
+  estimated_total_bytes: number;
+  offloaded_bytes_on_disk: number;
+}
+
+export type SaveStage =
+  | 'Queued'
+  | 'Serializing'
+  | 'Writing'
+  | 'Verifying'
+  | 'Completed'
+  | 'Failed';
+
+export interface SaveProgress {
+  ticket: number;
+  slot_id: number;
+  stage: SaveStage;
+  error: string | null;
 }
 
 export enum ActionType {
@@ -5,6 +5,7 @@ import {
   createFreeTextAction,
   createOptionAction,
   createContinueAction,
+  parseStaleOptionError,
   toggleInputMode,
   validateFreeTextInput,
 } from './playerInput';
@@ -28,6 +29,7 @@ describe('playerInput utils', () => {
   it('builds option payload', () => {
     const option: PlayerOption = {
       id: 2,
+      uid: 'opt-meditate',
       description: 'Cultivate in meditation room',
       requirements: [],
       action: { Cultivate: null },
@@ -36,9 +38,19 @@ describe('playerInput utils', () => {
     const action = createOptionAction(option);
     expect(action.action_type).toBe(ActionType.SelectedOption);
     expect(action.selected_option_id).toBe(2);
+    expect(action.selected_option_uuid).toBe('opt-meditate');
     expect(action.content).toBe(option.description);
   });
 
+  it('recognizes stale option errors', () => {
+    const stale = parseStaleOptionError(
+      JSON.stringify({ code: 'stale_option', message: '选项已更新', submitted_uid: '', current_options: [] }),
+    );
+    expect(stale?.message).toBe('选项已更新');
+    expect(parseStaleOptionError('操作失败')).toBeNull();
+    expect(parseStaleOptionError('{"code":"other"}')).toBeNull();
+  });
+
   it('builds continue payload', () => {
     const action = createContinueAction();
     expect(action.action_type).toBe(ActionType.FreeText);
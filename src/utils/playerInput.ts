@@ -1,4 +1,4 @@
-﻿import type { PlayerAction, PlayerOption } from '../types/game';
+﻿import type { PlayerAction, PlayerOption, StaleOptionError } from '../types/game';
 import { ActionType } from '../types/game';
 
 export interface InputValidationResult {
@@ -30,10 +30,21 @@ export function createOptionAction(option: PlayerOption): PlayerAction {
     action_type: ActionType.SelectedOption,
     content: option.description,
     selected_option_id: option.id,
+    selected_option_uuid: option.uid,
     meta: null,
   };
 }
 
+/** 识别选项过期错误，前端据此换上最新选项让玩家重选 */
+export function parseStaleOptionError(message: string): StaleOptionError | null {
+  try {
+    const parsed = JSON.parse(message);
+    return parsed?.code === 'stale_option' && Array.isArray(parsed.current_options) ? parsed : null;
+  } catch {
+    return null;
+  }
+}
+
 export function createFreeTextAction(text: string): PlayerAction {
   return {
     action_type: ActionType.FreeText,
@@ -1,3 +1,5 @@
+import type { ScriptLanguage } from '../types/game';
+
 export interface StorySettings {
   recap_enabled: boolean;
   novel_style: string;
@@ -5,6 +7,8 @@ export interface StorySettings {
   max_interactions_per_chapter: number;
   target_chapter_words_min: number;
   target_chapter_words_max: number;
+  bulletin_segments_enabled: boolean;
+  language: ScriptLanguage;
 }
 
 const STORAGE_KEY = 'nobody_story_settings';
@@ -16,6 +20,8 @@ const defaultSettings: StorySettings = {
   max_interactions_per_chapter: 3,
   target_chapter_words_min: 5000,
   target_chapter_words_max: 7000,
+  bulletin_segments_enabled: false,
+  language: 'zh',
 };
 
 export const getStorySettings = (): StorySettings => {
@@ -51,6 +57,14 @@ export const getStorySettings = (): StorySettings => {
         typeof parsed.target_chapter_words_max === 'number'
           ? parsed.target_chapter_words_max
           : defaultSettings.target_chapter_words_max,
+      bulletin_segments_enabled:
+        typeof parsed.bulletin_segments_enabled === 'boolean'
+          ? parsed.bulletin_segments_enabled
+          : defaultSettings.bulletin_segments_enabled,
+      language:
+        parsed.language === 'zh' || parsed.language === 'en'
+          ? parsed.language
+          : defaultSettings.language,
     };
   } catch {
     return { ...defaultSettings };
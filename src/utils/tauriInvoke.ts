@@ -9,7 +9,13 @@ export async function invokeWithTimeout<T>(
   let timer: ReturnType<typeof setTimeout> | undefined;
   try {
     const timeout = new Promise<never>((_, reject) => {
-      timer = setTimeout(() => reject(new Error(timeoutMessage)), timeoutMs);
+      timer = setTimeout(() => {
+        // 可取消的生成超时后通知后端放弃请求，避免结果在之后悄悄写入状态
+        if (typeof args?.requestId === 'string') {
+          invoke('cancel_generation', { requestId: args.requestId }).catch(() => undefined);
+        }
+        reject(new Error(timeoutMessage));
+      }, timeoutMs);
     });
     return await Promise.race([invoke<T>(command, args ?? {}), timeout]);
   } finally {